@@ -0,0 +1,152 @@
+//! How sshs talks to hosts. The default backend runs the system `ssh` client; the experimental
+//! in-process one (`in-process` cargo feature) connects with libssh2, for the sessions in tabs
+//! and the scripts. It is chosen with `[connection] backend` in the configuration file, or
+//! `--backend`.
+
+use anyhow::anyhow;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::process::Stdio;
+
+use crate::exec;
+use crate::ssh::{self, ConnectOptions, Host};
+use crate::ui::session::PtySession;
+
+#[cfg(feature = "in-process")]
+mod in_process;
+#[cfg(feature = "in-process")]
+pub use in_process::InProcessBackend;
+
+/// The backends sshs can be built with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackendKind {
+    /// The `ssh` client of the system
+    #[default]
+    Exec,
+    /// libssh2, within sshs (experimental)
+    InProcess,
+}
+
+impl BackendKind {
+    /// # Errors
+    ///
+    /// Will return `Err` if sshs was built without the backend.
+    pub fn create(self) -> anyhow::Result<Box<dyn ConnectionBackend>> {
+        match self {
+            BackendKind::Exec => Ok(Box::new(ExecBackend)),
+            #[cfg(feature = "in-process")]
+            BackendKind::InProcess => Ok(Box::new(InProcessBackend)),
+            #[cfg(not(feature = "in-process"))]
+            BackendKind::InProcess => Err(anyhow!(
+                "sshs was built without the in-process backend (cargo install sshs --features in-process)"
+            )),
+        }
+    }
+}
+
+pub trait ConnectionBackend {
+    /// Short name shown to the user.
+    fn name(&self) -> &'static str;
+
+    /// Runs an interactive session on the current terminal until it ends. Returns the exit
+    /// code of the session, `None` when it was killed by a signal.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the session cannot be started.
+    fn interactive(&self, host: &Host, options: &ConnectOptions) -> anyhow::Result<Option<i32>>;
+
+    /// Starts an interactive session in a pseudo-terminal of `rows` x `cols`, which sshs draws
+    /// in a tab, so that several sessions run at once.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the session cannot be started, or the backend has no sessions in
+    /// tabs.
+    fn spawn_tab(
+        &self,
+        _host: &Host,
+        _options: &ConnectOptions,
+        _rows: u16,
+        _cols: u16,
+    ) -> anyhow::Result<PtySession> {
+        Err(anyhow!(
+            "The {} backend cannot run sessions in tabs",
+            self.name()
+        ))
+    }
+
+    /// Runs `script` on `host` without prompting for anything, returning its output.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the host cannot be reached without a prompt or the script fails.
+    fn batch(&self, host: &Host, script: &str) -> anyhow::Result<String>;
+}
+
+/// Runs the `ssh` client of the system, which reads the SSH configuration itself.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExecBackend;
+
+impl ConnectionBackend for ExecBackend {
+    fn name(&self) -> &'static str {
+        "exec"
+    }
+
+    fn interactive(&self, host: &Host, options: &ConnectOptions) -> anyhow::Result<Option<i32>> {
+        let mut args = host.build_ssh_command(options);
+        if let Some(log) = &options.session_log {
+            args = ssh::recorded_command(&args, log);
+        }
+        let status = exec::status(&mut ssh::command(&args))?;
+
+        Ok(status.code())
+    }
+
+    fn spawn_tab(
+        &self,
+        host: &Host,
+        options: &ConnectOptions,
+        rows: u16,
+        cols: u16,
+    ) -> anyhow::Result<PtySession> {
+        let mut args = host.build_ssh_command(options);
+        if let Some(log) = &options.session_log {
+            args = ssh::recorded_command(&args, log);
+        }
+
+        PtySession::spawn(&args, rows, cols)
+    }
+
+    fn batch(&self, host: &Host, script: &str) -> anyhow::Result<String> {
+        let output =
+            exec::output(ssh::command(&host.build_batch_command(script)).stdin(Stdio::null()))?;
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "ssh to {} failed: {}",
+                host.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create() {
+        assert_eq!(BackendKind::Exec.create().unwrap().name(), "exec");
+        let in_process = BackendKind::InProcess.create();
+        if cfg!(feature = "in-process") {
+            assert_eq!(in_process.unwrap().name(), "in-process");
+        } else {
+            assert!(in_process.is_err());
+        }
+    }
+}
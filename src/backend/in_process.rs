@@ -0,0 +1,332 @@
+//! Experimental backend connecting from sshs itself, with libssh2. It only knows what sshs
+//! parsed of the SSH configuration: the destination, port, user and identity file of a host. It
+//! authenticates with the agent, then the identity files without a passphrase, and accepts the
+//! host keys already in `~/.ssh/known_hosts` only. Hosts behind a proxy are refused. The
+//! sessions taking over the terminal still run `ssh`, which is the one asking for passwords.
+
+use anyhow::{anyhow, bail, Context, Result};
+use ssh2::{Channel, CheckResult, KnownHostFileKind, Session};
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use super::{ConnectionBackend, ExecBackend};
+use crate::ssh::{self, ConnectOptions, Host};
+use crate::ui::session::{PtySession, SessionProcess};
+
+const KNOWN_HOSTS: &str = "~/.ssh/known_hosts";
+
+/// Private keys tried, in order, for hosts without an `IdentityFile`, as `ssh` does
+const DEFAULT_IDENTITIES: [&str; 3] = ["~/.ssh/id_ed25519", "~/.ssh/id_ecdsa", "~/.ssh/id_rsa"];
+
+/// Time the channel thread waits when there was nothing to read or write
+const IDLE_DELAY: Duration = Duration::from_millis(10);
+
+/// Connects with libssh2 for the sessions in tabs and the scripts.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InProcessBackend;
+
+impl ConnectionBackend for InProcessBackend {
+    fn name(&self) -> &'static str {
+        "in-process"
+    }
+
+    /// The terminal is handed over to `ssh`, as with the exec backend.
+    fn interactive(&self, host: &Host, options: &ConnectOptions) -> Result<Option<i32>> {
+        ExecBackend.interactive(host, options)
+    }
+
+    fn spawn_tab(
+        &self,
+        host: &Host,
+        options: &ConnectOptions,
+        rows: u16,
+        cols: u16,
+    ) -> Result<PtySession> {
+        if options.dynamic_forward.is_some() || options.jump_host.is_some() {
+            bail!("The in-process backend has no forwards nor jump hosts");
+        }
+        if options.forward_agent || host.forward_agent {
+            bail!("The in-process backend cannot forward the agent");
+        }
+
+        let session = connect(host)?;
+        let mut channel = session.channel_session()?;
+        channel.request_pty(
+            "xterm-256color",
+            None,
+            Some((u32::from(cols), u32::from(rows), 0, 0)),
+        )?;
+        match &options.remote_command {
+            Some(command) => channel.exec(command)?,
+            None => channel.shell()?,
+        }
+        let log = options
+            .session_log
+            .as_ref()
+            .map(|path| File::options().create(true).append(true).open(path))
+            .transpose()
+            .context("Failed to open the session log")?;
+
+        let (output, chunks) = mpsc::channel();
+        let (requests, received) = mpsc::channel();
+        let exit = Arc::new(Mutex::new(None));
+        let status = Arc::clone(&exit);
+        thread::spawn(move || {
+            let code = run_channel(&session, &mut channel, log, &output, &received);
+            *status.lock().unwrap_or_else(PoisonError::into_inner) = Some(code);
+        });
+
+        Ok(PtySession::new(
+            Box::new(ChannelReader {
+                chunks,
+                chunk: Vec::new(),
+                read: 0,
+            }),
+            Box::new(ChannelWriter(requests.clone())),
+            Box::new(ChannelProcess { requests, exit }),
+            rows,
+            cols,
+        ))
+    }
+
+    fn batch(&self, host: &Host, script: &str) -> Result<String> {
+        let session = connect(host)?;
+        let mut channel = session.channel_session()?;
+        channel.exec(script)?;
+
+        let mut output = String::new();
+        channel.read_to_string(&mut output)?;
+        let mut errors = String::new();
+        channel.stderr().read_to_string(&mut errors)?;
+        channel.wait_close()?;
+
+        if channel.exit_status()? != 0 {
+            return Err(anyhow!("ssh to {} failed: {}", host.name, errors.trim()));
+        }
+
+        Ok(output)
+    }
+}
+
+/// A session on `host`, authenticated.
+fn connect(host: &Host) -> Result<Session> {
+    if host.proxy_command.is_some() || host.proxy_jump.is_some() {
+        bail!(
+            "The in-process backend cannot reach {} through a proxy",
+            host.name
+        );
+    }
+    let destination = ssh::unbracket(&host.destination);
+    let port = host
+        .port
+        .as_deref()
+        .map_or(Ok(22), str::parse::<u16>)
+        .with_context(|| format!("Invalid port of {}", host.name))?;
+    let user = host
+        .user
+        .clone()
+        .or_else(|| std::env::var("USER").ok())
+        .ok_or_else(|| anyhow!("No user to connect to {} as", host.name))?;
+
+    let stream = TcpStream::connect((destination, port))
+        .with_context(|| format!("Failed to connect to {}", host.name))?;
+    let mut session = Session::new()?;
+    session.set_tcp_stream(stream);
+    session.handshake()?;
+    check_host_key(&session, destination, port)?;
+    authenticate(&session, host, &user)?;
+
+    Ok(session)
+}
+
+/// Accepts the host keys of `~/.ssh/known_hosts` only, there is no prompt to accept a new one.
+fn check_host_key(session: &Session, destination: &str, port: u16) -> Result<()> {
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| anyhow!("{destination} sent no host key"))?;
+    let mut known_hosts = session.known_hosts()?;
+    let path = shellexpand::tilde(KNOWN_HOSTS).to_string();
+    // A missing file knows no host
+    let _ = known_hosts.read_file(Path::new(&path), KnownHostFileKind::OpenSSH);
+
+    match known_hosts.check_port(destination, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => bail!(
+            "The host key of {destination} does not match the one in {KNOWN_HOSTS}, someone may \
+             be impersonating it"
+        ),
+        CheckResult::NotFound => bail!(
+            "The host key of {destination} is not in {KNOWN_HOSTS}, connect once with ssh to \
+             check and accept it"
+        ),
+        CheckResult::Failure => bail!("Failed to check the host key of {destination}"),
+    }
+}
+
+/// The agent first, then the identity files without a passphrase.
+fn authenticate(session: &Session, host: &Host, user: &str) -> Result<()> {
+    let _ = session.userauth_agent(user);
+
+    let identities = match &host.identity_file {
+        Some(identity) => vec![identity.as_str()],
+        None => DEFAULT_IDENTITIES.to_vec(),
+    };
+    for identity in identities {
+        if session.authenticated() {
+            break;
+        }
+        let path = shellexpand::tilde(identity).to_string();
+        if Path::new(&path).exists() {
+            let _ = session.userauth_pubkey_file(user, None, Path::new(&path), None);
+        }
+    }
+
+    if !session.authenticated() {
+        bail!(
+            "{} accepted neither the keys of the agent nor the identity files of {user}",
+            host.name
+        );
+    }
+
+    Ok(())
+}
+
+/// What the session asks of the channel thread
+enum Request {
+    Input(Vec<u8>),
+    Resize(u16, u16),
+    Close,
+}
+
+/// Copies the output of `channel` to `output` (and `log`), and carries out the requests, until
+/// the channel ends or is closed. Returns its exit code.
+fn run_channel(
+    session: &Session,
+    channel: &mut Channel,
+    mut log: Option<File>,
+    output: &Sender<Vec<u8>>,
+    requests: &Receiver<Request>,
+) -> Option<i32> {
+    let mut buffer = [0; 8192];
+    // Reads do not wait, so that the requests are not held up by a quiet session
+    session.set_blocking(false);
+    loop {
+        let mut idle = true;
+        match channel.read(&mut buffer) {
+            Ok(0) if channel.eof() => break,
+            Ok(0) => {}
+            Ok(read) => {
+                idle = false;
+                if let Some(log) = &mut log {
+                    let _ = log.write_all(&buffer[..read]);
+                }
+                if output.send(buffer[..read].to_vec()).is_err() {
+                    break;
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(_) => break,
+        }
+
+        let request = match requests.try_recv() {
+            Ok(request) => request,
+            Err(TryRecvError::Empty) => {
+                if idle {
+                    thread::sleep(IDLE_DELAY);
+                }
+                continue;
+            }
+            Err(TryRecvError::Disconnected) => Request::Close,
+        };
+        session.set_blocking(true);
+        let done = match request {
+            Request::Input(bytes) => channel.write_all(&bytes).is_err(),
+            Request::Resize(rows, cols) => {
+                let _ = channel.request_pty_size(u32::from(cols), u32::from(rows), None, None);
+                false
+            }
+            Request::Close => {
+                let _ = channel.close();
+                true
+            }
+        };
+        session.set_blocking(false);
+        if done {
+            break;
+        }
+    }
+
+    session.set_blocking(true);
+    let _ = channel.wait_close();
+    channel.exit_status().ok()
+}
+
+/// The output of the channel, as the channel thread sends it
+struct ChannelReader {
+    chunks: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    /// Bytes of `chunk` already read
+    read: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.read == self.chunk.len() {
+            // The channel thread is gone once the channel has ended
+            let Ok(chunk) = self.chunks.recv() else {
+                return Ok(0);
+            };
+            self.chunk = chunk;
+            self.read = 0;
+        }
+
+        let read = buffer.len().min(self.chunk.len() - self.read);
+        buffer[..read].copy_from_slice(&self.chunk[self.read..self.read + read]);
+        self.read += read;
+        Ok(read)
+    }
+}
+
+struct ChannelWriter(Sender<Request>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0
+            .send(Request::Input(bytes.to_vec()))
+            .map_err(|_| io::Error::from(ErrorKind::BrokenPipe))?;
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct ChannelProcess {
+    requests: Sender<Request>,
+    /// Set by the channel thread once the channel has ended
+    exit: Arc<Mutex<Option<Option<i32>>>>,
+}
+
+impl SessionProcess for ChannelProcess {
+    fn resize(&mut self, rows: u16, cols: u16) -> Result<()> {
+        self.requests
+            .send(Request::Resize(rows, cols))
+            .map_err(|_| anyhow!("The session has ended"))
+    }
+
+    fn try_wait(&mut self) -> Option<Option<i32>> {
+        *self.exit.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn kill(&mut self) {
+        let _ = self.requests.send(Request::Close);
+    }
+}
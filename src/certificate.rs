@@ -0,0 +1,182 @@
+//! OpenSSH certificates (`CertificateFile`), inspected with `ssh-keygen -L`.
+
+use anyhow::anyhow;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::exec;
+use crate::ssh::parse_date;
+
+/// Certificates expiring within this many seconds are flagged as expiring soon (7 days)
+pub const EXPIRING_SOON_SECONDS: i64 = 7 * 86_400;
+
+/// The fields of a certificate shown to the user.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Certificate {
+    pub key_id: String,
+    pub principals: Vec<String>,
+    /// Validity as printed by `ssh-keygen`, e.g. `from 2024-01-01T00:00:00 to 2024-02-01T00:00:00`
+    pub validity: String,
+    /// End of validity in seconds since the epoch, `None` when valid forever
+    pub valid_until: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateStatus {
+    Valid,
+    /// Expires in the given number of seconds
+    ExpiringSoon(i64),
+    Expired,
+}
+
+impl Certificate {
+    /// Inspects the certificate at `path` with `ssh-keygen -L`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `ssh-keygen` cannot be run or cannot read the certificate.
+    pub fn inspect(path: &str) -> anyhow::Result<Certificate> {
+        let path = shellexpand::tilde(path).to_string();
+        let output = exec::output(Command::new("ssh-keygen").args(["-L", "-f", &path]))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "ssh-keygen cannot read '{path}': {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        parse_keygen_output(&String::from_utf8_lossy(&output.stdout))
+            .ok_or_else(|| anyhow!("'{path}' is not an SSH certificate"))
+    }
+
+    /// Status of the certificate at `now` (seconds since the epoch).
+    #[must_use]
+    pub fn status(&self, now: i64) -> CertificateStatus {
+        match self.valid_until {
+            Some(until) if until <= now => CertificateStatus::Expired,
+            Some(until) if until - now <= EXPIRING_SOON_SECONDS => {
+                CertificateStatus::ExpiringSoon(until - now)
+            }
+            _ => CertificateStatus::Valid,
+        }
+    }
+}
+
+/// Current time, in seconds since the epoch.
+#[must_use]
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| {
+            i64::try_from(elapsed.as_secs()).unwrap_or(i64::MAX)
+        })
+}
+
+/// Parses the output of `ssh-keygen -L`.
+fn parse_keygen_output(output: &str) -> Option<Certificate> {
+    let mut certificate = Certificate::default();
+    let mut is_certificate = false;
+    let mut in_principals = false;
+
+    for line in output.lines() {
+        let indent = line.len() - line.trim_start().len();
+        let line = line.trim();
+
+        // Principals are listed on their own, more indented, lines
+        if in_principals && indent > 8 {
+            if line != "(none)" {
+                certificate.principals.push(line.to_string());
+            }
+            continue;
+        }
+        in_principals = false;
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key {
+            "Type" => is_certificate = value.contains("certificate"),
+            "Key ID" => certificate.key_id = value.trim_matches('"').to_string(),
+            "Valid" => {
+                certificate.validity = value.to_string();
+                certificate.valid_until = match value.split_once(" to ") {
+                    Some((_, until)) => parse_timestamp(until),
+                    None => value.strip_prefix("before ").and_then(parse_timestamp),
+                };
+            }
+            "Principals" => in_principals = true,
+            _ => {}
+        }
+    }
+
+    is_certificate.then_some(certificate)
+}
+
+/// Parses a `YYYY-MM-DDTHH:MM:SS` timestamp into seconds since the epoch.
+///
+/// `ssh-keygen` prints local times; they are read as UTC, which is close enough to warn about
+/// expiring certificates.
+fn parse_timestamp(timestamp: &str) -> Option<i64> {
+    let (date, time) = timestamp.trim().split_once('T')?;
+    let mut seconds = parse_date(date)? * 86_400;
+
+    for (part, unit) in time.split(':').zip([3600, 60, 1]) {
+        seconds += part.parse::<i64>().ok()? * unit;
+    }
+
+    Some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYGEN_OUTPUT: &str = "/home/me/.ssh/id_ed25519-cert.pub:
+        Type: ssh-ed25519-cert-v01@openssh.com user certificate
+        Public key: ED25519-CERT SHA256:2mCfQdMBXEnLDUSw4UdI6yHzjbKRmRgz0wB+cJDZIvQ
+        Signing CA: ED25519 SHA256:Q1sPH+ht7DWTp3P0pHDAcgYK8HPN0HHVs4VyAdbDl4c (using ssh-ed25519)
+        Key ID: \"me@example.com\"
+        Serial: 42
+        Valid: from 2025-01-01T08:00:00 to 2025-01-31T08:00:00
+        Principals:
+                me
+                deploy
+        Critical Options: (none)
+        Extensions:
+                permit-pty
+";
+
+    #[test]
+    fn test_parse_keygen_output() {
+        let certificate = parse_keygen_output(KEYGEN_OUTPUT).unwrap();
+
+        assert_eq!(certificate.key_id, "me@example.com");
+        assert_eq!(certificate.principals, vec!["me", "deploy"]);
+        assert_eq!(
+            certificate.validity,
+            "from 2025-01-01T08:00:00 to 2025-01-31T08:00:00"
+        );
+
+        let until = parse_date("2025-01-31").unwrap() * 86_400 + 8 * 3600;
+        assert_eq!(certificate.valid_until, Some(until));
+        assert_eq!(
+            certificate.status(until - 30 * 86_400),
+            CertificateStatus::Valid
+        );
+        assert_eq!(
+            certificate.status(until - 3600),
+            CertificateStatus::ExpiringSoon(3600)
+        );
+        assert_eq!(certificate.status(until), CertificateStatus::Expired);
+
+        let forever =
+            KEYGEN_OUTPUT.replace("from 2025-01-01T08:00:00 to 2025-01-31T08:00:00", "forever");
+        let certificate = parse_keygen_output(&forever).unwrap();
+        assert_eq!(certificate.valid_until, None);
+        assert_eq!(certificate.status(now()), CertificateStatus::Valid);
+
+        // Plain public keys are not certificates
+        assert_eq!(parse_keygen_output("Type: ssh-ed25519\n"), None);
+    }
+}
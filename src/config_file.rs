@@ -0,0 +1,292 @@
+//! Defaults of the command line flags and display settings, read from
+//! `~/.config/sshs/config.toml`. The display settings of the file win over the ones saved from
+//! the settings panel, and the flags given on the command line over both.
+//!
+//! The leader key and the keys of the host list actions are configurable (`[keys]`), see
+//! [`crate::ui::keymap`]. A commented file listing every setting is written on first run.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::backend::BackendKind;
+use crate::settings::{Matcher, Settings, SortMode, Theme};
+
+pub const DEFAULT_CONFIG_FILE: &str = "~/.config/sshs/config.toml";
+
+/// Written on first run, every setting commented out with its default value
+pub const DEFAULT_CONFIG_FILE_CONTENT: &str = r#"# sshs configuration. The command line flags win over this file. A display setting set here
+# is applied on every start, over the one saved from the settings panel (,).
+
+# SSH configuration files, read in order (--config)
+# config = ["/etc/ssh/ssh_config", "~/.ssh/config"]
+
+[display]
+# Color theme: blue, emerald, violet, amber or rose
+# theme = "blue"
+# Order of the hosts: config (as declared), name or destination (--sort)
+# sort = "name"
+# Search: fuzzy, substring or nucleo
+# matcher = "fuzzy"
+
+[templates]
+# Command run to connect, with the host fields (--template)
+# command = 'ssh "{{{name}}}"'
+# Commands run when a session starts and ends, when the connection fails, and when a SOCKS
+# proxy opens and closes (--on-session-start-template...)
+# session_start = 'notify-send "Connected to {{name}}"'
+# session_end = 'notify-send "{{name}}: {{exit_code}} after {{duration}}"'
+# connection_failure = 'notify-send "{{name}} unreachable: {{error}}"'
+# socks = 'echo "SOCKS proxy $SSHS_SOCKS_STATE on $SSHS_SOCKS_PORT"'
+
+[connection]
+# How sshs connects: exec runs ssh, in-process connects with libssh2 from sshs itself for the
+# sessions in tabs and the scripts (experimental, built with the in-process feature) (--backend)
+# backend = "exec"
+
+[keys]
+# Key starting the leader sequences (--leader)
+# leader = "\\"
+# Keys of the host list actions, by action name. Navigation (j, k, g, G, h, l), undo (u), marks
+# (space, v) and the saved searches (1-9) keep their keys
+# [keys.actions]
+# connect-socks = "D"
+# connect-agent = "A"
+# add-host = "n"
+# edit-host = "e"
+# delete-host = "d"
+# host-details = "i"
+# search = "/"
+# quit = "q"
+
+# Triggers on what the sessions print, one [[triggers]] table each. A line matching the pattern
+# (a regular expression) is highlighted, rings the bell, marks its tab with ! until shown, or
+# runs a hook with the line in {{trigger_line}} and $SSHS_TRIGGER_LINE. Hosts add their own with
+# `# sshs:trigger-highlight=<pattern>`, `trigger-beep` and `trigger-mark`.
+# [[triggers]]
+# pattern = "ERROR|FATAL"
+# highlight = true
+# beep = false
+# mark = true
+# hook = 'notify-send "{{name}}: $SSHS_TRIGGER_LINE"'
+"#;
+
+/// Contents of the configuration file, every setting optional.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    /// SSH configuration files
+    pub config: Option<Vec<String>>,
+    pub display: Display,
+    pub templates: Templates,
+    pub connection: Connection,
+    pub keys: Keys,
+    pub triggers: Vec<Trigger>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Display {
+    pub theme: Option<Theme>,
+    pub sort: Option<SortMode>,
+    pub matcher: Option<Matcher>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Templates {
+    pub command: Option<String>,
+    pub session_start: Option<String>,
+    pub session_end: Option<String>,
+    pub connection_failure: Option<String>,
+    pub socks: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Connection {
+    pub backend: Option<BackendKind>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Keys {
+    pub leader: Option<char>,
+    /// Keys of the host list actions, by action name
+    pub actions: BTreeMap<String, char>,
+}
+
+/// A `[[triggers]]` table: what a line of session output matching `pattern` does
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Trigger {
+    pub pattern: String,
+    pub highlight: bool,
+    pub beep: bool,
+    pub mark: bool,
+    pub hook: Option<String>,
+}
+
+impl ConfigFile {
+    /// Reads the file at `path`. A missing file is written with [`DEFAULT_CONFIG_FILE_CONTENT`]
+    /// when `create` is set (the default path), and an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file cannot be read or is invalid.
+    pub fn load(path: &str, create: bool) -> Result<ConfigFile> {
+        let path = shellexpand::tilde(path).to_string();
+        let path = Path::new(&path);
+        if create && !path.exists() {
+            // Only a convenience, sshs runs the same without it
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            let _ = std::fs::write(path, DEFAULT_CONFIG_FILE_CONTENT);
+            return Ok(ConfigFile::default());
+        }
+
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        ConfigFile::parse(&text).with_context(|| format!("Invalid {}", path.display()))
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if `text` is not valid TOML, or has an unknown setting.
+    pub fn parse(text: &str) -> Result<ConfigFile> {
+        Ok(toml::from_str(text)?)
+    }
+}
+
+impl Display {
+    /// `settings` with the display settings of the file. The flags given on the command line
+    /// are applied over them later, by [`Settings::apply_flags`].
+    #[must_use]
+    pub fn apply(&self, mut settings: Settings) -> Settings {
+        if let Some(theme) = self.theme {
+            settings.theme = theme;
+        }
+        if let Some(matcher) = self.matcher {
+            settings.matcher = matcher;
+        }
+        if let Some(sort) = self.sort {
+            settings.sort = sort;
+        }
+
+        settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::keymap::Keymap;
+
+    #[test]
+    fn test_parse() {
+        let file = ConfigFile::parse(
+            r#"
+config = [
+    "~/.ssh/config",  # the personal one
+    '~/work/ssh_config',
+]
+
+[display]
+theme = "rose"
+sort = "destination"
+
+[templates]
+command = 'ssh -t "{{{name}}}" tmux new -A'
+session_end = "notify-send \"{{name}} ✓\""
+
+[connection]
+backend = "in-process"
+
+[keys]
+leader = " "
+
+[keys.actions]
+connect-socks = "s"
+
+[[triggers]]
+pattern = "kernel panic"
+beep = true
+mark = true
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            file.config.as_deref(),
+            Some(&["~/.ssh/config".to_string(), "~/work/ssh_config".to_string()][..])
+        );
+        assert_eq!(file.display.theme, Some(Theme::Rose));
+        assert_eq!(
+            file.templates.command.as_deref(),
+            Some(r#"ssh -t "{{{name}}}" tmux new -A"#)
+        );
+        assert_eq!(
+            file.templates.session_end.as_deref(),
+            Some("notify-send \"{{name}} ✓\"")
+        );
+        assert_eq!(file.connection.backend, Some(BackendKind::InProcess));
+        assert_eq!(file.keys.leader, Some(' '));
+        assert_eq!(file.keys.actions["connect-socks"], 's');
+        assert_eq!(
+            file.triggers,
+            [Trigger {
+                pattern: "kernel panic".to_string(),
+                beep: true,
+                mark: true,
+                ..Default::default()
+            }]
+        );
+
+        let settings = file.display.apply(Settings::default());
+        assert_eq!(settings.sort, SortMode::Destination);
+        assert_eq!(settings.matcher, Matcher::Fuzzy);
+
+        for (text, error) in [
+            ("theme = \"rose\"", "unknown field"),
+            ("[display]\nsort = 1.5", "line 2"),
+            ("[keys]\nleader = \"ab\"", "line 2"),
+            ("[keys.actions]\nquit = \"Q!\"", "line 2"),
+            ("[keys]\nleader = \"\\\\\"\nleader = \"x\"", "duplicate key"),
+            ("config = [\"a\" \"b\"]", "line 1"),
+            ("[templates]\ncommand = \"ssh", "line 2"),
+        ] {
+            let message = format!("{:#}", ConfigFile::parse(text).unwrap_err());
+            assert!(message.contains(error), "{text}: {message}");
+        }
+    }
+
+    #[test]
+    fn test_default_file_lists_valid_settings() {
+        assert_eq!(
+            ConfigFile::parse(DEFAULT_CONFIG_FILE_CONTENT).unwrap(),
+            ConfigFile::default()
+        );
+
+        // Every commented setting, uncommented
+        let uncommented = DEFAULT_CONFIG_FILE_CONTENT
+            .lines()
+            .map(|line| match line.strip_prefix("# ") {
+                Some(setting) if setting.contains(" = ") || setting.starts_with('[') => setting,
+                _ => line,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let file = ConfigFile::parse(&uncommented).unwrap();
+        assert_eq!(file.display.sort, Some(SortMode::Name));
+        assert_eq!(
+            file.templates.command.as_deref(),
+            Some("ssh \"{{{name}}}\"")
+        );
+        assert_eq!(file.keys.leader, Some('\\'));
+        assert_eq!(Keymap::new(&file.keys.actions).unwrap(), Keymap::default());
+        assert!(file.templates.socks.is_some());
+        assert!(file.triggers[0].hook.is_some());
+    }
+}
@@ -0,0 +1,181 @@
+//! Dotfiles pushed to opted-in hosts before the shell opens, through an inline shell script
+//! decoding them from base64 (no `scp`, a single round trip).
+
+use anyhow::{anyhow, Context};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// The bootstrap script goes on the `ssh` command line, keep it well below `ARG_MAX`
+pub const MAX_BUNDLE_SIZE: usize = 64 * 1024;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A dotfile and its path on the remote host, relative to the home directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dotfile {
+    pub remote_path: String,
+    pub content: Vec<u8>,
+}
+
+/// Reads the dotfiles to push. Files inside the local home directory keep their relative path
+/// (`~/.config/git/config` goes to `~/.config/git/config`), other files go to the remote home
+/// directory.
+///
+/// # Errors
+///
+/// Will return `Err` if a file cannot be read, has an unsupported name, or if the bundle is
+/// larger than [`MAX_BUNDLE_SIZE`].
+pub fn load(paths: &[String]) -> anyhow::Result<Vec<Dotfile>> {
+    let home = PathBuf::from(shellexpand::tilde("~").to_string());
+
+    let mut dotfiles = Vec::new();
+    let mut size = 0;
+    for raw_path in paths {
+        let path = PathBuf::from(shellexpand::tilde(raw_path).to_string());
+        let content = std::fs::read(&path)
+            .with_context(|| format!("Failed to read dotfile '{}'", path.display()))?;
+
+        size += content.len();
+        if size > MAX_BUNDLE_SIZE {
+            return Err(anyhow!(
+                "Dotfiles are larger than {} KiB",
+                MAX_BUNDLE_SIZE / 1024
+            ));
+        }
+
+        dotfiles.push(Dotfile {
+            remote_path: remote_path(&path, &home)?,
+            content,
+        });
+    }
+
+    Ok(dotfiles)
+}
+
+fn remote_path(path: &Path, home: &Path) -> anyhow::Result<String> {
+    let relative = match path.strip_prefix(home) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => PathBuf::from(path.file_name().unwrap_or_default()),
+    };
+    let relative = relative.to_string_lossy().to_string();
+
+    // The path is written in the script unquoted by the shell, only allow plain names
+    let is_safe = !relative.is_empty()
+        && relative
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '/'))
+        && relative
+            .split('/')
+            .all(|part| part != ".." && !part.is_empty());
+    if !is_safe {
+        return Err(anyhow!("Unsupported dotfile path '{}'", path.display()));
+    }
+
+    Ok(relative)
+}
+
+/// Builds the remote shell script writing the dotfiles into the home directory.
+#[must_use]
+pub fn bootstrap_script(dotfiles: &[Dotfile]) -> String {
+    let mut script = String::from("set -e; cd \"$HOME\"");
+
+    for dotfile in dotfiles {
+        if let Some((directory, _)) = dotfile.remote_path.rsplit_once('/') {
+            write!(script, "; mkdir -p {directory}").unwrap();
+        }
+        write!(
+            script,
+            "; printf %s {} | base64 -d > {}",
+            base64_encode(&dotfile.content),
+            dotfile.remote_path
+        )
+        .unwrap();
+    }
+
+    script
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let indexes = [
+            b[0] >> 2,
+            ((b[0] & 0x03) << 4) | (b[1] >> 4),
+            ((b[1] & 0x0f) << 2) | (b[2] >> 6),
+            b[2] & 0x3f,
+        ];
+
+        for (i, index) in indexes.iter().enumerate() {
+            if i <= chunk.len() {
+                encoded.push(char::from(BASE64_ALPHABET[usize::from(*index)]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(
+            base64_encode(b"alias ll='ls -l'\n"),
+            "YWxpYXMgbGw9J2xzIC1sJwo="
+        );
+    }
+
+    #[test]
+    fn test_remote_path() {
+        let home = Path::new("/home/me");
+
+        assert_eq!(
+            remote_path(Path::new("/home/me/.config/git/config"), home).unwrap(),
+            ".config/git/config"
+        );
+        assert_eq!(
+            remote_path(Path::new("/opt/dotfiles/.bashrc"), home).unwrap(),
+            ".bashrc"
+        );
+        assert!(remote_path(Path::new("/home/me/my file"), home).is_err());
+        assert!(remote_path(Path::new("/home/me/$(reboot)"), home).is_err());
+    }
+
+    #[test]
+    fn test_load_and_bootstrap_script() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(".inputrc");
+        std::fs::write(&path, "set bell-style none\n").unwrap();
+
+        let dotfiles = load(&[path.display().to_string()]).unwrap();
+        assert_eq!(dotfiles[0].remote_path, ".inputrc");
+
+        let nested = Dotfile {
+            remote_path: ".config/git/config".to_string(),
+            content: b"foo".to_vec(),
+        };
+        assert_eq!(
+            bootstrap_script(&[nested]),
+            "set -e; cd \"$HOME\"; mkdir -p .config/git; printf %s Zm9v | base64 -d > .config/git/config"
+        );
+
+        std::fs::write(&path, vec![b'x'; MAX_BUNDLE_SIZE + 1]).unwrap();
+        assert!(load(&[path.display().to_string()]).is_err());
+        assert!(load(&["/nonexistent/.bashrc".to_string()]).is_err());
+    }
+}
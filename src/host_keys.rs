@@ -0,0 +1,354 @@
+//! Host key inventory (`--host-key-report`): the key every host offers, keys shared by several
+//! machines (cloned VMs) and keys changed since the previous scan, written as CSV.
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::process::Stdio;
+
+use crate::{exec, ssh};
+
+/// Hosts scanned at the same time
+const SCAN_PARALLELISM: usize = 16;
+
+const CSV_HEADER: &str =
+    "host,destination,status,key_type,fingerprint,shared_with,previous_fingerprint,error";
+
+/// Key offered by a host, as logged by `ssh`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct HostKey {
+    /// e.g. `ssh-ed25519`
+    pub key_type: String,
+    /// e.g. `SHA256:...`
+    pub fingerprint: String,
+}
+
+impl HostKey {
+    /// Reads the key from the output of `ssh -v`. Jump hosts log their key first, the last one
+    /// is the key of the host itself.
+    fn from_ssh_log(log: &str) -> Option<HostKey> {
+        let (_, key) = log
+            .lines()
+            .rev()
+            .find_map(|line| line.split_once("Server host key: "))?;
+        let (key_type, fingerprint) = key.trim().split_once(' ')?;
+
+        Some(HostKey {
+            key_type: key_type.to_string(),
+            fingerprint: fingerprint.to_string(),
+        })
+    }
+}
+
+/// Scans the key `host` offers, without authenticating.
+///
+/// # Errors
+///
+/// Will return `Err` if `ssh` cannot be started or the host is not reached.
+pub fn scan(host: &ssh::Host) -> anyhow::Result<HostKey> {
+    let output = exec::output(ssh::command(&host.build_host_key_command()).stdin(Stdio::null()))
+        .context("Failed to run ssh")?;
+    let log = String::from_utf8_lossy(&output.stderr);
+
+    HostKey::from_ssh_log(&log).ok_or_else(|| {
+        let reason = log
+            .lines()
+            .rev()
+            .map(str::trim)
+            .find(|line| {
+                !line.is_empty() && !line.starts_with("debug") && !line.starts_with("OpenSSH_")
+            })
+            .unwrap_or("No host key received");
+        anyhow!("{reason}")
+    })
+}
+
+/// Scans the keys of `hosts`, a few at a time. The results are in the order of `hosts`.
+#[must_use]
+pub fn scan_all(hosts: &[ssh::Host]) -> Vec<anyhow::Result<HostKey>> {
+    hosts
+        .chunks(SCAN_PARALLELISM)
+        .flat_map(|chunk| {
+            std::thread::scope(|scope| {
+                let scans = chunk
+                    .iter()
+                    .map(|host| scope.spawn(|| scan(host)))
+                    .collect::<Vec<_>>();
+
+                scans
+                    .into_iter()
+                    .map(|scan| {
+                        scan.join()
+                            .unwrap_or_else(|_| Err(anyhow!("The scan panicked")))
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect()
+}
+
+/// Scan of one host, compared with the other hosts and with the previous scan.
+#[derive(Debug)]
+pub struct Entry {
+    pub host: String,
+    pub destination: String,
+    /// Key found, or why there is none
+    pub key: Result<HostKey, String>,
+    /// Hosts with another destination offering the same key
+    pub shared_with: Vec<String>,
+    /// Key found by the previous scan, when it was another one
+    pub changed_from: Option<HostKey>,
+}
+
+impl Entry {
+    #[must_use]
+    pub fn status(&self) -> &'static str {
+        if self.key.is_err() {
+            "unreachable"
+        } else if self.changed_from.is_some() {
+            "changed"
+        } else if !self.shared_with.is_empty() {
+            "shared"
+        } else {
+            "ok"
+        }
+    }
+}
+
+/// Host keys of a whole scan.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub entries: Vec<Entry>,
+}
+
+impl Report {
+    /// Compares the `scans` of `hosts` with each other, and with the keys of the `previous`
+    /// scan. Aliases of the same destination are not reported as sharing their key.
+    #[must_use]
+    pub fn new(
+        hosts: &[ssh::Host],
+        scans: Vec<anyhow::Result<HostKey>>,
+        previous: &BTreeMap<String, HostKey>,
+    ) -> Report {
+        let mut entries = hosts
+            .iter()
+            .zip(scans)
+            .map(|(host, key)| Entry {
+                host: host.name.clone(),
+                destination: host.destination.clone(),
+                changed_from: key.as_ref().ok().and_then(|key| {
+                    previous
+                        .get(&host.name)
+                        .filter(|previous| *previous != key)
+                        .cloned()
+                }),
+                key: key.map_err(|e| format!("{e:#}")),
+                shared_with: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut by_key: BTreeMap<&HostKey, Vec<(&str, &str)>> = BTreeMap::new();
+        for entry in &entries {
+            if let Ok(key) = &entry.key {
+                by_key
+                    .entry(key)
+                    .or_default()
+                    .push((&entry.host, &entry.destination));
+            }
+        }
+        let shared_with = entries
+            .iter()
+            .map(|entry| match &entry.key {
+                Ok(key) => by_key[key]
+                    .iter()
+                    .filter(|(_, destination)| *destination != entry.destination)
+                    .map(|(host, _)| (*host).to_string())
+                    .collect(),
+                Err(_) => Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        for (entry, shared_with) in entries.iter_mut().zip(shared_with) {
+            entry.shared_with = shared_with;
+        }
+
+        Report { entries }
+    }
+
+    /// Keys found, by host name, for the next scan to compare with.
+    pub fn found_keys(&self) -> impl Iterator<Item = (String, HostKey)> + '_ {
+        self.entries.iter().filter_map(|entry| {
+            entry
+                .key
+                .as_ref()
+                .ok()
+                .map(|key| (entry.host.clone(), key.clone()))
+        })
+    }
+
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("{CSV_HEADER}\n");
+
+        for entry in &self.entries {
+            let (key_type, fingerprint, error) = match &entry.key {
+                Ok(key) => (key.key_type.as_str(), key.fingerprint.as_str(), ""),
+                Err(e) => ("", "", e.as_str()),
+            };
+            let fields = [
+                entry.host.as_str(),
+                entry.destination.as_str(),
+                entry.status(),
+                key_type,
+                fingerprint,
+                &entry.shared_with.join(" "),
+                entry
+                    .changed_from
+                    .as_ref()
+                    .map_or("", |key| key.fingerprint.as_str()),
+                error,
+            ];
+
+            csv.push_str(&fields.map(csv_field).join(","));
+            csv.push('\n');
+        }
+
+        csv
+    }
+
+    /// Writes the report to `path` as CSV.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file cannot be written.
+    pub fn write_csv(&self, path: &str) -> anyhow::Result<()> {
+        fs::write(shellexpand::tilde(path).as_ref(), self.to_csv())
+            .with_context(|| format!("Failed to write the host key report '{path}'"))
+    }
+
+    /// One line summary, e.g. `Scanned 12 hosts: 1 unreachable, 2 sharing a key, 0 changed`.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let count = |status: &str| {
+            self.entries
+                .iter()
+                .filter(|entry| entry.status() == status)
+                .count()
+        };
+
+        format!(
+            "Scanned {} hosts: {} unreachable, {} sharing a key, {} changed since the previous scan",
+            self.entries.len(),
+            count("unreachable"),
+            self.entries
+                .iter()
+                .filter(|entry| !entry.shared_with.is_empty())
+                .count(),
+            count("changed"),
+        )
+    }
+}
+
+/// Quotes `value` when it has a comma, a quote or a line break.
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(fingerprint: &str) -> HostKey {
+        HostKey {
+            key_type: "ssh-ed25519".to_string(),
+            fingerprint: fingerprint.to_string(),
+        }
+    }
+
+    fn host(name: &str, destination: &str) -> ssh::Host {
+        ssh::Host {
+            name: name.to_string(),
+            destination: destination.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_host_key_from_ssh_log() {
+        let log = "OpenSSH_9.6p1, OpenSSL 3.0.13\n\
+                   debug1: Server host key: ssh-ed25519 SHA256:jump\n\
+                   debug1: Authenticating to web:22 as 'deploy'\n\
+                   debug1: Server host key: ecdsa-sha2-nistp256 SHA256:web\r\n\
+                   deploy@web: Permission denied (publickey).\n";
+        assert_eq!(
+            HostKey::from_ssh_log(log),
+            Some(HostKey {
+                key_type: "ecdsa-sha2-nistp256".to_string(),
+                fingerprint: "SHA256:web".to_string(),
+            })
+        );
+
+        let log = "ssh: connect to host web port 22: Connection refused\n";
+        assert_eq!(HostKey::from_ssh_log(log), None);
+    }
+
+    #[test]
+    fn test_report_flags_shared_and_changed_keys() {
+        let hosts = [
+            host("web-1", "10.0.0.1"),
+            host("web-2", "10.0.0.2"),
+            host("web-1-admin", "10.0.0.1"),
+            host("db", "10.0.0.3"),
+            host("backup", "10.0.0.4"),
+        ];
+        let scans = vec![
+            Ok(key("SHA256:clone")),
+            Ok(key("SHA256:clone")),
+            Ok(key("SHA256:clone")),
+            Ok(key("SHA256:db-new")),
+            Err(anyhow!(
+                "ssh: connect to host 10.0.0.4 port 22: Connection refused"
+            )),
+        ];
+        let previous = [
+            ("db".to_string(), key("SHA256:db-old")),
+            ("web-1".to_string(), key("SHA256:clone")),
+        ]
+        .into();
+
+        let report = Report::new(&hosts, scans, &previous);
+        let statuses = report.entries.iter().map(Entry::status).collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            ["shared", "shared", "shared", "changed", "unreachable"]
+        );
+        assert_eq!(report.entries[0].shared_with, ["web-2"]);
+        assert_eq!(report.entries[1].shared_with, ["web-1", "web-1-admin"]);
+        assert_eq!(report.found_keys().count(), 4);
+        assert_eq!(
+            report.summary(),
+            "Scanned 5 hosts: 1 unreachable, 3 sharing a key, 1 changed since the previous scan"
+        );
+
+        let csv = report.to_csv();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(
+            lines[2],
+            "web-2,10.0.0.2,shared,ssh-ed25519,SHA256:clone,web-1 web-1-admin,,"
+        );
+        assert_eq!(
+            lines[4],
+            "db,10.0.0.3,changed,ssh-ed25519,SHA256:db-new,,SHA256:db-old,"
+        );
+        assert_eq!(
+            lines[5],
+            "backup,10.0.0.4,unreachable,,,,,ssh: connect to host 10.0.0.4 port 22: Connection refused"
+        );
+        assert_eq!(csv_field("no \"key\", sorry"), "\"no \"\"key\"\", sorry\"");
+    }
+}
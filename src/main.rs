@@ -1,20 +1,46 @@
-pub mod searchable;
-pub mod ssh;
-pub mod ssh_config;
+pub mod backend;
+pub mod certificate;
+pub mod config_file;
+pub mod dotfiles;
+pub mod host_keys;
+pub mod matcher;
+pub mod settings;
+pub mod state;
+pub mod stats;
 pub mod ui;
+pub mod vault;
 
-use anyhow::Result;
-use clap::Parser;
+pub use sshs_core::{exec, log, searchable, shared_metadata, ssh, ssh_config};
+
+use anyhow::{Context, Result};
+use backend::BackendKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use config_file::{ConfigFile, DEFAULT_CONFIG_FILE};
+use std::path::PathBuf;
+use std::time::Duration;
+use ui::actions::DEFAULT_LEADER;
 use ui::app::{App, AppConfig};
+use ui::capabilities::{Capabilities, ColorMode, Override};
+use ui::driver::{self, UiDriver};
+use ui::keymap::Keymap;
+use ui::session_log::DEFAULT_SESSION_LOG_DIR;
+use ui::tabs::{TabNaming, Truncation, DEFAULT_TAB_NAME_TEMPLATE};
+use ui::triggers::Trigger;
+use ui::unused_hosts::DEFAULT_UNUSED_AFTER_MONTHS;
 
 // Constants for default configuration
 const DEFAULT_SYSTEM_SSH_CONFIG: &str = "/etc/ssh/ssh_config";
 const DEFAULT_USER_SSH_CONFIG: &str = "~/.ssh/config";
+const DEFAULT_STATE_FILE: &str = "~/.local/share/sshs/state.json";
+const DEFAULT_MANAGED_CONFIG: &str = "~/.ssh/config.d/sshs.conf";
 const DEFAULT_SSH_TEMPLATE: &str = "ssh \"{{{name}}}\"";
 
 // Default values for CLI flags
 const DEFAULT_SORT_BY_NAME: bool = true;
 const DEFAULT_EXIT_AFTER_SESSION: bool = false;
+const DEFAULT_SOCKS_PORT: u16 = 1080;
+const DEFAULT_CONNECT_DELAY_MS: u64 = 800;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -31,19 +57,54 @@ struct Args {
     )]
     config: Vec<String>,
 
-    /// Shows `ProxyCommand`
+    /// Defaults of the flags and display settings, the flags given win over the file
+    /// [default: ~/.config/sshs/config.toml, written with every setting commented out on first
+    /// run]
+    #[arg(long, value_name = "PATH")]
+    config_file: Option<String>,
+
+    /// Write hosts created by sshs to a dedicated file, included from the SSH configuration
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_MANAGED_CONFIG,
+    )]
+    managed_config: Option<String>,
+
+    /// Path to the sshs state file (trusted hosts, settings, ...)
+    #[arg(long, default_value = DEFAULT_STATE_FILE)]
+    state_file: String,
+
+    /// Keep the state encrypted in `<state file>.vault`, unlocked with a passphrase asked before
+    /// the interface starts (or `SSHS_VAULT_PASSPHRASE`). A plain state file is moved into it
+    #[arg(long)]
+    vault: bool,
+
+    /// Save the passphrase of the vault in the OS keychain, to be asked only once
+    #[arg(long, requires = "vault")]
+    vault_keychain: bool,
+
+    /// Shows `ProxyCommand`, even when hidden from the settings panel
     #[arg(long)]
     show_proxy_command: bool,
 
-    /// Host search filter
+    /// Host search filter, or the name of a saved search
     #[arg(short, long)]
     search: Option<String>,
 
-    /// Sort hosts by hostname
+    /// Connect right away when --search leaves a single host, instead of showing the list
+    #[arg(long, requires = "search")]
+    connect_if_unique: bool,
+
+    /// Sort hosts by hostname, even when another order is saved from the settings panel
     #[arg(long, default_value_t = DEFAULT_SORT_BY_NAME)]
     sort: bool,
 
-    /// Handlebars template of the command to execute
+    /// Handlebars template of the command to execute, with the host fields (`{{destination}}`,
+    /// `{{port}}`, `{{metadata.<key>}}`...). `{{port_flag}}` (`-p<port>`) and `{{user_at}}`
+    /// (`<user>@`) are empty for hosts without them, `{{default user "root"}}` falls back on a
+    /// value, and arguments rendering to nothing are dropped
     #[arg(short, long, default_value = DEFAULT_SSH_TEMPLATE)]
     template: String,
 
@@ -51,28 +112,373 @@ struct Args {
     #[arg(long, value_name = "TEMPLATE")]
     on_session_start_template: Option<String>,
 
-    /// Handlebars template of the command to execute when an SSH session ends
+    /// Handlebars template of the command to execute when an SSH session ends, also given
+    /// `{{exit_code}}`, `{{duration}}`, `{{duration_seconds}}`, `{{error}}` and `{{tab_name}}`,
+    /// set in its environment as well (`SSHS_EXIT_CODE`...)
     #[arg(long, value_name = "TEMPLATE")]
     on_session_end_template: Option<String>,
 
+    /// Handlebars template of the command to execute when the connection fails, before the
+    /// session end one, with the same values
+    #[arg(long, value_name = "TEMPLATE")]
+    on_connection_failure_template: Option<String>,
+
+    /// Handlebars template of the command to execute when a SOCKS proxy connection opens and
+    /// closes (`SSHS_SOCKS_STATE` and `SSHS_SOCKS_PORT` are set in its environment)
+    #[arg(long, value_name = "TEMPLATE")]
+    on_socks_template: Option<String>,
+
+    /// Local port of the SOCKS proxy opened with the `D` key
+    #[arg(long, default_value_t = DEFAULT_SOCKS_PORT)]
+    socks_port: u16,
+
+    /// User to connect as when a host has no `User` [default: the local account]
+    #[arg(long, value_name = "USER")]
+    default_user: Option<String>,
+
+    /// Dotfiles pushed to the hosts opted in with `# sshs:dotfiles=yes` before opening the shell
+    #[arg(long, value_name = "FILE", num_args = 1..)]
+    dotfiles: Vec<String>,
+
+    /// JSON file of host tags and metadata shared by a team, merged under the metadata of the
+    /// SSH configuration
+    #[arg(long, value_name = "FILE")]
+    shared_metadata: Option<String>,
+
     /// Exit after ending the SSH session
     #[arg(short, long, default_value_t = DEFAULT_EXIT_AFTER_SESSION)]
     exit: bool,
+
+    /// Milliseconds the connection screen stays up, 0 to skip it (banners still show)
+    #[arg(long, value_name = "MS", default_value_t = DEFAULT_CONNECT_DELAY_MS)]
+    connect_delay: u64,
+
+    /// Milliseconds the session summary stays up, 0 to skip it [default: until a key is
+    /// pressed, skipped with --exit]
+    #[arg(long, value_name = "MS")]
+    end_screen_delay: Option<u64>,
+
+    /// Handlebars template of the session tab names, with `index`, `name`, `user`,
+    /// `destination` and `port`
+    #[arg(long, value_name = "TEMPLATE", default_value = DEFAULT_TAB_NAME_TEMPLATE)]
+    tab_name_template: String,
+
+    /// Maximum width of the session tab names, longer names are cut
+    #[arg(long, value_name = "COLUMNS")]
+    tab_name_width: Option<usize>,
+
+    /// Which part of a tab name too long for --tab-name-width is cut
+    #[arg(long, value_enum, default_value_t = Truncation::Middle)]
+    tab_name_truncation: Truncation,
+
+    /// Keep usage statistics (connections, failures, time in sessions) and write them to FILE
+    /// after every session: a Prometheus textfile when it ends with `.prom`, JSON otherwise
+    #[arg(long, value_name = "FILE")]
+    stats_file: Option<String>,
+
+    /// Append every change sshs makes to the SSH configuration (who, when, lines added and
+    /// removed) to FILE, viewable from the File menu
+    #[arg(long, value_name = "FILE")]
+    audit_log: Option<String>,
+
+    /// Safe mode, for reviewing untrusted configurations: no process is ever started,
+    /// connecting copies the `ssh` command instead, and sessions are disabled
+    #[arg(long, conflicts_with = "workspace")]
+    no_exec: bool,
+
+    /// For scripts and benchmarks: no connection screen or session summary, and no
+    /// informational messages, only errors on stderr
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Resolve the destinations of the listed hosts in the background and show their addresses
+    /// in the host details
+    #[arg(long)]
+    dns_prefetch: bool,
+
+    /// Check every SECONDS that the SSH port of each host accepts connections, shown as a dot
+    /// in the host list (green, yellow when slow, red when unreachable). C checks right away
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    health_check_interval: Option<u64>,
+
+    /// Months without a connection after which the unused hosts report lists a host
+    #[arg(long, value_name = "MONTHS", default_value_t = DEFAULT_UNUSED_AFTER_MONTHS)]
+    unused_after: u32,
+
+    /// Key starting the multi-key sequences (`<leader>tn` opens a session), with a popup
+    /// listing what can follow. It takes over the key's own binding
+    #[arg(long, value_name = "KEY", default_value_t = DEFAULT_LEADER)]
+    leader: char,
+
+    /// Record every session to a log file of its host, under --session-log-dir. Hosts opt out
+    /// with `# sshs:log=no`, or in without the flag with `# sshs:log=yes`. O opens the last log
+    #[arg(long)]
+    log_sessions: bool,
+
+    /// Directory of the session logs, one subdirectory per host
+    #[arg(long, value_name = "DIR", default_value = DEFAULT_SESSION_LOG_DIR)]
+    session_log_dir: String,
+
+    /// Colors, left to the terminal when never [default: auto, off when `NO_COLOR` is set or TERM
+    /// is dumb, unless `CLICOLOR_FORCE` is set]
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto, hide_default_value = true)]
+    color: ColorMode,
+
+    /// 24-bit colors, mapped to the 16 ANSI colors when off [default: on when COLORTERM is
+    /// truecolor or 24bit]
+    #[arg(long, value_enum, default_value_t = Override::Auto, hide_default_value = true)]
+    truecolor: Override,
+
+    /// Box drawing characters and symbols, replaced with ASCII when off [default: on with a
+    /// UTF-8 locale, outside of the Linux console]
+    #[arg(long, value_enum, default_value_t = Override::Auto, hide_default_value = true)]
+    unicode: Override,
+
+    /// Mouse capture [default: on, except for terminals printing mouse events as text]
+    #[arg(long, value_enum, default_value_t = Override::Auto, hide_default_value = true)]
+    mouse: Override,
+
+    /// Scan the host key of every host (matching --search), flag keys shared by several
+    /// machines and keys changed since the previous scan, write them to FILE as CSV and exit
+    #[arg(long, value_name = "FILE", conflicts_with = "no_exec")]
+    host_key_report: Option<String>,
+
+    /// Same as `sshs diff OLD NEW`, kept for the scripts using it
+    #[arg(long, num_args = 2, value_names = ["OLD", "NEW"], hide = true)]
+    diff: Option<Vec<String>>,
+
+    /// How to connect: run ssh, or connect from sshs itself with libssh2 for the sessions in tabs
+    /// and the scripts (experimental, needs the in-process feature)
+    #[arg(long, value_enum, default_value_t = BackendKind::Exec)]
+    backend: BackendKind,
+
+    /// Open the session tabs of a workspace saved from the Sessions menu
+    #[arg(long, value_name = "NAME")]
+    workspace: Option<String>,
+
+    /// Run a script of UI actions without a terminal and print the final screen
+    #[arg(long, value_name = "FILE")]
+    headless_script: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Args {
+    /// Takes the settings of the configuration file not given on the command line.
+    fn merge(&mut self, file: &ConfigFile, matches: &ArgMatches) {
+        let from_flags = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        if let (Some(config), false) = (&file.config, from_flags("config")) {
+            self.config.clone_from(config);
+        }
+        if let (Some(template), false) = (&file.templates.command, from_flags("template")) {
+            self.template.clone_from(template);
+        }
+        for (template, id, value) in [
+            (
+                &mut self.on_session_start_template,
+                "on_session_start_template",
+                &file.templates.session_start,
+            ),
+            (
+                &mut self.on_session_end_template,
+                "on_session_end_template",
+                &file.templates.session_end,
+            ),
+            (
+                &mut self.on_connection_failure_template,
+                "on_connection_failure_template",
+                &file.templates.connection_failure,
+            ),
+            (
+                &mut self.on_socks_template,
+                "on_socks_template",
+                &file.templates.socks,
+            ),
+        ] {
+            if value.is_some() && !from_flags(id) {
+                template.clone_from(value);
+            }
+        }
+        if let (Some(leader), false) = (file.keys.leader, from_flags("leader")) {
+            self.leader = leader;
+        }
+        if let (Some(backend), false) = (file.connection.backend, from_flags("backend")) {
+            self.backend = backend;
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Learn the basics (search, connect, edit, tabs and tunnels) step by step, on sample hosts
+    Tutorial,
+    /// Compare two SSH configuration files host by host (hosts added and removed, options
+    /// changed once wildcard blocks are applied), print the differences and exit
+    Diff {
+        /// SSH configuration file before the change
+        old: String,
+        /// SSH configuration file after the change
+        new: String,
+    },
+}
+
+/// Prints the hosts changed between the SSH configuration files `old` and `new`.
+fn print_diff(old: &str, new: &str) -> Result<()> {
+    let [old, new] = [old, new].map(|path| PathBuf::from(shellexpand::tilde(path).as_ref()));
+    let hosts = ssh_config::diff::diff_files(&old, &new)?;
+    if hosts.is_empty() {
+        println!("No host changed");
+    }
+    for line in ui::backups::host_diff_lines(&hosts) {
+        println!("{line}");
+    }
+
+    Ok(())
 }
 
 fn main() -> Result<()> {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if args.no_exec {
+        exec::disable();
+    }
+    if args.quiet {
+        log::set_quiet();
+    }
+
+    if let Some(Command::Diff { old, new }) = &args.command {
+        return print_diff(old, new);
+    }
+    if let Some(paths) = &args.diff {
+        return print_diff(&paths[0], &paths[1]);
+    }
+
+    let file = ConfigFile::load(
+        args.config_file.as_deref().unwrap_or(DEFAULT_CONFIG_FILE),
+        args.config_file.is_none(),
+    )?;
+    args.merge(&file, &matches);
+
+    // Catch template mistakes now rather than when connecting
+    for (flag, template, values) in [
+        ("--template", Some(&args.template), &[][..]),
+        (
+            "--on-session-start-template",
+            args.on_session_start_template.as_ref(),
+            &[],
+        ),
+        (
+            "--on-session-end-template",
+            args.on_session_end_template.as_ref(),
+            &ui::session_summary::TEMPLATE_VALUES,
+        ),
+        (
+            "--on-connection-failure-template",
+            args.on_connection_failure_template.as_ref(),
+            &ui::session_summary::TEMPLATE_VALUES,
+        ),
+        ("--on-socks-template", args.on_socks_template.as_ref(), &[]),
+    ] {
+        if let Some(template) = template {
+            ssh::validate_command_template_with(template, values)
+                .with_context(|| format!("Invalid {flag}"))?;
+        }
+    }
+
+    let triggers = file
+        .triggers
+        .iter()
+        .map(Trigger::from_config)
+        .collect::<Result<Vec<_>>>()
+        .context("Invalid [[triggers]] in the configuration file")?;
 
-    let mut app = App::new(&AppConfig {
+    let keymap = Keymap::new(&file.keys.actions)
+        .context("Invalid [keys.actions] in the configuration file")?;
+
+    let config = AppConfig {
         config_paths: args.config,
+        vault: if args.vault {
+            Some(vault::open(&args.state_file, args.vault_keychain)?)
+        } else {
+            None
+        },
+        state_path: args.state_file,
+        managed_config_path: args.managed_config,
         search_filter: args.search,
+        connect_if_unique: args.connect_if_unique,
         sort_by_name: args.sort,
         show_proxy_command: args.show_proxy_command,
+        sort_given: matches.value_source("sort") == Some(ValueSource::CommandLine),
+        show_proxy_command_given: matches.value_source("show_proxy_command")
+            == Some(ValueSource::CommandLine),
         command_template: args.template,
         command_template_on_session_start: args.on_session_start_template,
         command_template_on_session_end: args.on_session_end_template,
+        command_template_on_connection_failure: args.on_connection_failure_template,
+        command_template_on_socks: args.on_socks_template,
         exit_after_ssh_session_ends: args.exit,
-    })?;
+        socks_port: args.socks_port,
+        default_user: args.default_user,
+        dotfiles: args.dotfiles,
+        shared_metadata_path: args.shared_metadata,
+        connect_delay: Duration::from_millis(args.connect_delay),
+        end_screen_delay: args.end_screen_delay.map(Duration::from_millis),
+        tab_naming: TabNaming {
+            template: args.tab_name_template,
+            max_width: args.tab_name_width,
+            truncation: args.tab_name_truncation,
+        },
+        workspace: args.workspace,
+        stats_file: args.stats_file,
+        audit_log: args.audit_log,
+        no_exec: args.no_exec,
+        dns_prefetch: args.dns_prefetch,
+        health_check_interval: args.health_check_interval.map(Duration::from_secs),
+        unused_after_months: args.unused_after,
+        leader: args.leader,
+        display: file.display.clone(),
+        keymap,
+        log_sessions: args.log_sessions,
+        session_log_dir: args.session_log_dir,
+        capabilities: Capabilities::detect(args.color, args.truecolor, args.unicode, args.mouse),
+        triggers,
+        backend: args.backend,
+    };
+
+    if let Some(Command::Tutorial) = args.command {
+        return ui::tutorial::run(&config);
+    }
+
+    let mut app = App::new(&config)?;
+
+    if let Some(report_path) = args.host_key_report {
+        let hosts = app
+            .hosts
+            .iter()
+            .filter(|host| !host.is_template())
+            .cloned()
+            .collect::<Vec<_>>();
+        let report =
+            host_keys::Report::new(&hosts, host_keys::scan_all(&hosts), &app.state.host_keys);
+        report.write_csv(&report_path)?;
+        app.state.record_host_keys(report.found_keys());
+        app.state.save()?;
+        println!("{}", report.summary());
+
+        return Ok(());
+    }
+
+    if let Some(script_path) = args.headless_script {
+        let script = std::fs::read_to_string(&script_path)?;
+        let mut driver = UiDriver::new(app, driver::DEFAULT_WIDTH, driver::DEFAULT_HEIGHT)?;
+        driver.run_script(&script)?;
+        println!("{}", driver.screen());
+
+        return Ok(());
+    }
+
     app.start()?;
 
     Ok(())
@@ -0,0 +1,369 @@
+//! Backends matching the search input against the host fields, picked with
+//! [`Settings::matcher`](crate::settings::Settings::matcher).
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use nucleo_matcher::pattern::{Atom, AtomKind, CaseMatching, Normalization};
+use nucleo_matcher::Utf32Str;
+
+use crate::settings::{Column, Matcher};
+use crate::ssh::Host;
+
+pub trait SearchMatcher {
+    /// Whether `value` matches the search input `pattern`, never empty.
+    fn is_match(&mut self, value: &str, pattern: &str) -> bool;
+
+    /// Indices of the characters of `value` matching `pattern`, in order, `None` when it does
+    /// not match.
+    fn match_indices(&mut self, value: &str, pattern: &str) -> Option<Vec<usize>>;
+}
+
+/// Fuzzy matching of the `skim` finder.
+#[derive(Default)]
+pub struct SkimMatcher(SkimMatcherV2);
+
+impl SearchMatcher for SkimMatcher {
+    fn is_match(&mut self, value: &str, pattern: &str) -> bool {
+        self.0.fuzzy_match(value, pattern).is_some()
+    }
+
+    fn match_indices(&mut self, value: &str, pattern: &str) -> Option<Vec<usize>> {
+        self.0
+            .fuzzy_indices(value, pattern)
+            .map(|(_, indices)| indices)
+    }
+}
+
+/// Fuzzy matching of the `nucleo` finder (helix), much faster on large configurations. The
+/// pattern is compiled once for every host instead of once per field.
+pub struct NucleoMatcher {
+    matcher: nucleo_matcher::Matcher,
+    /// Last pattern and its compiled form
+    atom: Option<(String, Atom)>,
+    buffer: Vec<char>,
+}
+
+impl Default for NucleoMatcher {
+    fn default() -> Self {
+        Self {
+            matcher: nucleo_matcher::Matcher::new(nucleo_matcher::Config::DEFAULT),
+            atom: None,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl NucleoMatcher {
+    /// Compiles `pattern`, unless it is the last one.
+    fn compile(&mut self, pattern: &str) {
+        if self.atom.as_ref().is_none_or(|(last, _)| last != pattern) {
+            let atom = Atom::new(
+                pattern,
+                CaseMatching::Smart,
+                Normalization::Smart,
+                AtomKind::Fuzzy,
+                false,
+            );
+            self.atom = Some((pattern.to_string(), atom));
+        }
+    }
+}
+
+impl SearchMatcher for NucleoMatcher {
+    fn is_match(&mut self, value: &str, pattern: &str) -> bool {
+        self.compile(pattern);
+        let Some((_, atom)) = &self.atom else {
+            return false;
+        };
+
+        atom.score(Utf32Str::new(value, &mut self.buffer), &mut self.matcher)
+            .is_some()
+    }
+
+    fn match_indices(&mut self, value: &str, pattern: &str) -> Option<Vec<usize>> {
+        self.compile(pattern);
+        let (_, atom) = self.atom.as_ref()?;
+
+        let mut indices = Vec::new();
+        atom.indices(
+            Utf32Str::new(value, &mut self.buffer),
+            &mut self.matcher,
+            &mut indices,
+        )?;
+        indices.sort_unstable();
+        indices.dedup();
+
+        Some(
+            indices
+                .into_iter()
+                .filter_map(|index| usize::try_from(index).ok())
+                .collect(),
+        )
+    }
+}
+
+/// Case-insensitive substring.
+#[derive(Default)]
+pub struct SubstringMatcher;
+
+impl SearchMatcher for SubstringMatcher {
+    fn is_match(&mut self, value: &str, pattern: &str) -> bool {
+        value.to_lowercase().contains(&pattern.to_lowercase())
+    }
+
+    fn match_indices(&mut self, value: &str, pattern: &str) -> Option<Vec<usize>> {
+        let value = value.to_lowercase();
+        let pattern = pattern.to_lowercase();
+        let start = value[..value.find(&pattern)?].chars().count();
+
+        Some((start..start + pattern.chars().count()).collect())
+    }
+}
+
+/// Fields a search term can be scoped to, as `field:value` (`tag:prod user:root`)
+pub const SEARCH_FIELDS: [&str; 5] = ["name", "host", "user", "tag", "port"];
+
+/// A value of a host matched by a field-scoped term, for the host list to highlight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMatch {
+    /// Column showing the value
+    pub column: Column,
+    /// The value matched: the name, the aliases, one of the tags...
+    pub value: String,
+    /// Indices of the characters of `value` matching the term
+    pub indices: Vec<usize>,
+}
+
+/// A search input split into its field-scoped terms and the rest of the text, which is
+/// matched against every field.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SearchQuery<'a> {
+    /// `(field, value)`, with a field of [`SEARCH_FIELDS`]
+    pub scoped: Vec<(&'a str, &'a str)>,
+    pub text: String,
+}
+
+impl<'a> SearchQuery<'a> {
+    /// Splits `search`. A term is only scoped when its prefix is a known field, so `fe80::1`
+    /// stays plain text.
+    #[must_use]
+    pub fn parse(search: &'a str) -> Self {
+        let mut scoped = Vec::new();
+        let mut text = Vec::new();
+
+        for term in search.split_whitespace() {
+            match term.split_once(':') {
+                Some((field, value)) if !value.is_empty() && SEARCH_FIELDS.contains(&field) => {
+                    scoped.push((field, value));
+                }
+                _ => text.push(term),
+            }
+        }
+
+        Self {
+            scoped,
+            text: text.join(" "),
+        }
+    }
+
+    /// Whether `host` has every scoped term in its field and the text in any field.
+    pub fn is_match(&self, matcher: &mut dyn SearchMatcher, host: &Host) -> bool {
+        let mut is_match = |value: &str, pattern: &str| matcher.is_match(value, pattern);
+
+        let scoped = self.scoped.iter().all(|(field, pattern)| match *field {
+            "name" => is_match(&host.name, pattern) || is_match(&host.aliases, pattern),
+            "host" => is_match(&host.destination, pattern),
+            "user" => host
+                .user
+                .as_deref()
+                .is_some_and(|user| is_match(user, pattern)),
+            "tag" => host.tags.iter().any(|tag| is_match(tag, pattern)),
+            "port" => host.port.as_deref().is_some_and(|port| port == *pattern),
+            _ => false,
+        });
+
+        scoped
+            && (self.text.is_empty()
+                || is_match(&host.name, &self.text)
+                || is_match(&host.destination, &self.text)
+                || is_match(&host.aliases, &self.text)
+                || host.tags.iter().any(|tag| is_match(tag, &self.text)))
+    }
+
+    /// The values of `host` matched by the scoped terms, and where. Empty without scoped terms.
+    pub fn field_matches(&self, matcher: &mut dyn SearchMatcher, host: &Host) -> Vec<FieldMatch> {
+        let mut found = Vec::new();
+        for (field, pattern) in &self.scoped {
+            let values: Vec<(Column, &str)> = match *field {
+                "name" => vec![(Column::Name, &host.name), (Column::Aliases, &host.aliases)],
+                "host" => vec![(Column::Destination, &host.destination)],
+                "user" => host
+                    .user
+                    .iter()
+                    .map(|user| (Column::User, user.as_str()))
+                    .collect(),
+                "tag" => host
+                    .tags
+                    .iter()
+                    .map(|tag| (Column::Tags, tag.as_str()))
+                    .collect(),
+                "port" => host
+                    .port
+                    .iter()
+                    .map(|port| (Column::Port, port.as_str()))
+                    .collect(),
+                _ => Vec::new(),
+            };
+
+            for (column, value) in values {
+                // Ports are compared whole
+                let indices = if column == Column::Port {
+                    (value == *pattern).then(|| (0..value.chars().count()).collect())
+                } else {
+                    matcher.match_indices(value, pattern)
+                };
+                if let Some(indices) = indices {
+                    found.push(FieldMatch {
+                        column,
+                        value: value.to_string(),
+                        indices,
+                    });
+                }
+            }
+        }
+
+        found
+    }
+}
+
+impl Matcher {
+    #[must_use]
+    pub fn backend(self) -> Box<dyn SearchMatcher> {
+        match self {
+            Matcher::Fuzzy => Box::<SkimMatcher>::default(),
+            Matcher::Nucleo => Box::<NucleoMatcher>::default(),
+            Matcher::Substring => Box::new(SubstringMatcher),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_backends() {
+        for matcher in Matcher::ALL {
+            let mut backend = matcher.backend();
+            assert!(backend.is_match("web-01.example.com", "web"), "{matcher:?}");
+            assert_eq!(
+                backend.match_indices("Web-01.example.com", "web"),
+                Some(vec![0, 1, 2]),
+                "{matcher:?}"
+            );
+            assert_eq!(backend.match_indices("db-01", "web"), None, "{matcher:?}");
+            assert!(backend.is_match("Web-01.example.com", "web"), "{matcher:?}");
+            assert!(!backend.is_match("db-01.example.com", "web"), "{matcher:?}");
+        }
+
+        for matcher in [Matcher::Fuzzy, Matcher::Nucleo] {
+            let mut backend = matcher.backend();
+            assert!(
+                backend.is_match("web-01.example.com", "w01ex"),
+                "{matcher:?}"
+            );
+            // The compiled pattern follows the input
+            assert!(
+                !backend.is_match("web-01.example.com", "w02"),
+                "{matcher:?}"
+            );
+        }
+        assert!(!SubstringMatcher.is_match("web-01.example.com", "w01ex"));
+    }
+
+    #[test]
+    fn test_field_scoped_search() {
+        let query = SearchQuery::parse("tag:prod  eu user:root fe80::1");
+        assert_eq!(query.scoped, [("tag", "prod"), ("user", "root")]);
+        assert_eq!(query.text, "eu fe80::1");
+
+        let host = Host {
+            name: "web-eu".to_string(),
+            destination: "web.example.com".to_string(),
+            user: Some("root".to_string()),
+            port: Some("2222".to_string()),
+            tags: vec!["prod".to_string()],
+            ..Default::default()
+        };
+        let mut backend = Matcher::Substring.backend();
+        for (search, expected) in [
+            ("tag:prod eu", true),
+            ("tag:prod us", false),
+            ("user:root port:2222", true),
+            ("port:22", false),
+            ("host:example name:web", true),
+            ("tag:dev", false),
+        ] {
+            assert_eq!(
+                SearchQuery::parse(search).is_match(backend.as_mut(), &host),
+                expected,
+                "{search}"
+            );
+        }
+
+        let matches =
+            SearchQuery::parse("user:oo port:2222 eu").field_matches(backend.as_mut(), &host);
+        assert_eq!(
+            matches,
+            [
+                FieldMatch {
+                    column: Column::User,
+                    value: "root".to_string(),
+                    indices: vec![1, 2],
+                },
+                FieldMatch {
+                    column: Column::Port,
+                    value: "2222".to_string(),
+                    indices: vec![0, 1, 2, 3],
+                },
+            ]
+        );
+        assert!(SearchQuery::parse("web")
+            .field_matches(backend.as_mut(), &host)
+            .is_empty());
+    }
+
+    /// Compares the backends on 10 000 hosts, as typing a few characters would:
+    /// `cargo test --release matcher -- --ignored --nocapture`
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_backends_on_10k_hosts() {
+        let values = (0..10_000)
+            .map(|i| {
+                format!(
+                    "app-{i}.{}.example.com",
+                    ["eu-west", "us-east", "ap-south"][i % 3]
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for matcher in Matcher::ALL {
+            let mut backend = matcher.backend();
+            let start = Instant::now();
+            let mut matches = 0;
+            for pattern in ["a", "ap", "app", "app-9", "app-99", "app-999.eu"] {
+                matches += values
+                    .iter()
+                    .filter(|value| backend.is_match(value, pattern))
+                    .count();
+            }
+
+            println!(
+                "{:<10} {:>6} matches in {:?}",
+                matcher.label(),
+                matches,
+                start.elapsed()
+            );
+        }
+    }
+}
@@ -0,0 +1,444 @@
+//! Display preferences, adjustable at runtime from the settings panel and saved in the state
+//! file.
+
+use ratatui::style::palette::tailwind;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::ssh::Host;
+
+/// Order of the hosts in the table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortMode {
+    /// Order of declaration in the SSH configuration
+    #[default]
+    Config,
+    Name,
+    Destination,
+}
+
+/// Hosts sharing the same group are listed together, under a Group column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GroupMode {
+    #[default]
+    None,
+    /// First tag of the host
+    Tag,
+    /// Configuration file the host is declared in
+    File,
+}
+
+/// Color theme of the interface.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    #[default]
+    Blue,
+    Emerald,
+    Violet,
+    Amber,
+    Rose,
+}
+
+/// How the search input is matched against the hosts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Matcher {
+    /// Fuzzy, with the matcher of `skim`. `nucleo` finds the same hosts about 3 times faster
+    /// (`bench_backends_on_10k_hosts`), but both filter 10 000 hosts within a frame.
+    #[default]
+    Fuzzy,
+    /// Case-insensitive substring
+    Substring,
+    /// Fuzzy, with the faster matcher of `nucleo`
+    Nucleo,
+}
+
+/// A column of the hosts table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Column {
+    Name,
+    Aliases,
+    User,
+    Destination,
+    Port,
+    Tags,
+    Proxy,
+    Identity,
+    Jump,
+}
+
+/// Runtime display preferences.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub sort: SortMode,
+    pub group: GroupMode,
+    pub theme: Theme,
+    pub matcher: Matcher,
+    /// Columns the user chose to hide
+    pub hidden_columns: BTreeSet<Column>,
+    /// `dd` comments the host out at once, with an undo toast, instead of asking first
+    pub quick_delete: bool,
+    /// The system configuration (`/etc/ssh`) is not loaded until this is switched back off
+    pub skip_system_config: bool,
+    /// Red border around the whole interface while the current tab is a production session
+    pub prod_border: bool,
+    /// GitHub is asked once a day whether a new release is out
+    pub check_updates: bool,
+    /// Each alias of a host also gets its own row, to connect by that name
+    pub alias_rows: bool,
+    /// The facts gathered from the hosts (distribution, cloud, virtualization) are added to
+    /// their tags
+    pub fact_tags: bool,
+}
+
+impl SortMode {
+    pub const ALL: &'static [SortMode] = &[SortMode::Config, SortMode::Name, SortMode::Destination];
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Config => "config order",
+            SortMode::Name => "name",
+            SortMode::Destination => "destination",
+        }
+    }
+}
+
+impl GroupMode {
+    pub const ALL: &'static [GroupMode] = &[GroupMode::None, GroupMode::Tag, GroupMode::File];
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            GroupMode::None => "none",
+            GroupMode::Tag => "tag",
+            GroupMode::File => "file",
+        }
+    }
+
+    /// Group of `host`, empty for hosts without one (and when grouping is disabled).
+    #[must_use]
+    pub fn group_of(self, host: &Host) -> String {
+        match self {
+            GroupMode::None => String::new(),
+            GroupMode::Tag => host.tags.first().cloned().unwrap_or_default(),
+            GroupMode::File => host
+                .source_file
+                .as_ref()
+                .and_then(|path| path.file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl Theme {
+    pub const ALL: &'static [Theme] = &[
+        Theme::Blue,
+        Theme::Emerald,
+        Theme::Violet,
+        Theme::Amber,
+        Theme::Rose,
+    ];
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::Blue => "blue",
+            Theme::Emerald => "emerald",
+            Theme::Violet => "violet",
+            Theme::Amber => "amber",
+            Theme::Rose => "rose",
+        }
+    }
+
+    #[must_use]
+    pub fn palette(self) -> tailwind::Palette {
+        match self {
+            Theme::Blue => tailwind::BLUE,
+            Theme::Emerald => tailwind::EMERALD,
+            Theme::Violet => tailwind::VIOLET,
+            Theme::Amber => tailwind::AMBER,
+            Theme::Rose => tailwind::ROSE,
+        }
+    }
+}
+
+impl Matcher {
+    pub const ALL: &'static [Matcher] = &[Matcher::Fuzzy, Matcher::Substring, Matcher::Nucleo];
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Matcher::Fuzzy => "fuzzy",
+            Matcher::Substring => "substring",
+            Matcher::Nucleo => "nucleo",
+        }
+    }
+}
+
+impl Column {
+    /// Columns that can be hidden (the name is always shown).
+    pub const OPTIONAL: &'static [Column] = &[
+        Column::Aliases,
+        Column::User,
+        Column::Destination,
+        Column::Port,
+        Column::Tags,
+        Column::Proxy,
+        Column::Identity,
+        Column::Jump,
+    ];
+
+    #[must_use]
+    pub fn title(self) -> &'static str {
+        match self {
+            Column::Name => "Name",
+            Column::Aliases => "Aliases",
+            Column::User => "User",
+            Column::Destination => "Destination",
+            Column::Port => "Port",
+            Column::Tags => "Tags",
+            Column::Proxy => "Proxy",
+            Column::Identity => "Identity",
+            Column::Jump => "Jump",
+        }
+    }
+
+    /// Text shown in this column for `host`. Hosts without a `User` show `inferred_user`.
+    #[must_use]
+    pub fn value(self, host: &Host, inferred_user: &str) -> String {
+        match self {
+            Column::Name => host.connect_as.clone().unwrap_or_else(|| host.name.clone()),
+            Column::Aliases if host.connect_as.is_some() => format!("alias of {}", host.name),
+            Column::Aliases => host.aliases.clone(),
+            Column::User => host
+                .user
+                .clone()
+                .unwrap_or_else(|| inferred_user.to_string()),
+            Column::Destination => host.destination.clone(),
+            Column::Port => host.port.clone().unwrap_or_default(),
+            Column::Tags => host.tags.join(", "),
+            Column::Proxy => host.expanded_proxy_command().unwrap_or_default(),
+            Column::Identity => host.identity_file.clone().unwrap_or_default(),
+            Column::Jump => host.proxy_jump.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Settings matching the `--sort` and `--show-proxy-command` flags, used until the user
+    /// saves their own from the settings panel. The identity and jump columns start hidden.
+    #[must_use]
+    pub fn from_flags(sort_by_name: bool, show_proxy_command: bool) -> Self {
+        let mut settings = Settings {
+            sort: if sort_by_name {
+                SortMode::Name
+            } else {
+                SortMode::Config
+            },
+            ..Default::default()
+        };
+        if !show_proxy_command {
+            settings.hidden_columns.insert(Column::Proxy);
+        }
+        settings
+            .hidden_columns
+            .extend([Column::Identity, Column::Jump]);
+
+        settings
+    }
+
+    /// Applies the `--sort` and `--show-proxy-command` flags given on the command line (`None`
+    /// for the others), which win over the saved settings for the run.
+    pub fn apply_flags(&mut self, sort_by_name: Option<bool>, show_proxy_command: Option<bool>) {
+        if let Some(sort_by_name) = sort_by_name {
+            self.sort = if sort_by_name {
+                SortMode::Name
+            } else {
+                SortMode::Config
+            };
+        }
+        match show_proxy_command {
+            Some(true) => {
+                self.hidden_columns.remove(&Column::Proxy);
+            }
+            Some(false) => {
+                self.hidden_columns.insert(Column::Proxy);
+            }
+            None => {}
+        }
+    }
+
+    #[must_use]
+    pub fn is_column_shown(&self, column: Column) -> bool {
+        !self.hidden_columns.contains(&column)
+    }
+
+    pub fn toggle_column(&mut self, column: Column) {
+        if !self.hidden_columns.remove(&column) {
+            self.hidden_columns.insert(column);
+        }
+    }
+
+    /// `hosts`, each followed by the rows of its aliases when they are shown.
+    #[must_use]
+    pub fn with_alias_rows(&self, hosts: Vec<Host>) -> Vec<Host> {
+        if !self.alias_rows {
+            return hosts;
+        }
+
+        hosts
+            .into_iter()
+            .flat_map(|host| {
+                let rows = host.alias_rows();
+                std::iter::once(host).chain(rows)
+            })
+            .collect()
+    }
+
+    /// Sorts `hosts` by group, then by the sort mode. Hosts without a group come last.
+    pub fn sort_hosts(&self, hosts: &mut [Host]) {
+        let sort_key = |host: &Host| match self.sort {
+            SortMode::Config => String::new(),
+            SortMode::Name => host.name.to_lowercase(),
+            SortMode::Destination => host.destination.to_lowercase(),
+        };
+
+        // Stable sort, so the configuration order is kept within equal keys
+        hosts.sort_by_cached_key(|host| {
+            let group = self.group.group_of(host);
+            (group.is_empty(), group, sort_key(host))
+        });
+    }
+}
+
+/// Returns the value following (or preceding) `current` in `all`, wrapping around.
+#[must_use]
+pub fn cycle<T: Copy + PartialEq>(all: &[T], current: T, forward: bool) -> T {
+    let index = all.iter().position(|value| *value == current).unwrap_or(0);
+    let next = if forward {
+        (index + 1) % all.len()
+    } else {
+        (index + all.len() - 1) % all.len()
+    };
+
+    all[next]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn host(name: &str, destination: &str, tag: Option<&str>) -> Host {
+        Host {
+            name: name.to_string(),
+            destination: destination.to_string(),
+            tags: tag.map(str::to_string).into_iter().collect(),
+            source_file: Some(PathBuf::from("/home/me/.ssh/config")),
+            ..Default::default()
+        }
+    }
+
+    fn names(hosts: &[Host]) -> Vec<&str> {
+        hosts.iter().map(|host| host.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_and_group_hosts() {
+        let mut hosts = vec![
+            host("web", "b.example.com", Some("prod")),
+            host("db", "c.example.com", None),
+            host("api", "a.example.com", Some("prod")),
+            host("ci", "d.example.com", Some("build")),
+        ];
+
+        let mut settings = Settings::default();
+        settings.sort_hosts(&mut hosts);
+        assert_eq!(names(&hosts), vec!["web", "db", "api", "ci"]);
+
+        settings.sort = SortMode::Name;
+        settings.sort_hosts(&mut hosts);
+        assert_eq!(names(&hosts), vec!["api", "ci", "db", "web"]);
+
+        // Ungrouped hosts come last
+        settings.group = GroupMode::Tag;
+        settings.sort_hosts(&mut hosts);
+        assert_eq!(names(&hosts), vec!["ci", "api", "web", "db"]);
+
+        settings.group = GroupMode::File;
+        settings.sort = SortMode::Destination;
+        settings.sort_hosts(&mut hosts);
+        assert_eq!(names(&hosts), vec!["api", "web", "db", "ci"]);
+        assert_eq!(settings.group.group_of(&hosts[0]), "config");
+    }
+
+    #[test]
+    fn test_alias_rows_stay_with_their_host() {
+        let hosts = vec![
+            Host {
+                aliases: "www, web-eu".to_string(),
+                ..host("web", "b.example.com", None)
+            },
+            host("api", "a.example.com", None),
+        ];
+
+        let mut settings = Settings::default();
+        assert_eq!(settings.with_alias_rows(hosts.clone()).len(), 2);
+
+        settings.alias_rows = true;
+        settings.sort = SortMode::Name;
+        let mut rows = settings.with_alias_rows(hosts);
+        settings.sort_hosts(&mut rows);
+        let value = |column: Column| {
+            rows.iter()
+                .map(|host| column.value(host, ""))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(value(Column::Name), ["api", "web", "www", "web-eu"]);
+        assert_eq!(
+            value(Column::Aliases),
+            ["", "www, web-eu", "alias of web", "alias of web"]
+        );
+    }
+
+    #[test]
+    fn test_settings_from_flags_and_cycle() {
+        let settings = Settings::from_flags(true, false);
+        assert_eq!(settings.sort, SortMode::Name);
+        assert!(!settings.is_column_shown(Column::Proxy));
+        assert!(!settings.is_column_shown(Column::Jump));
+        assert!(settings.is_column_shown(Column::User));
+
+        let mut settings = Settings::from_flags(false, true);
+        assert_eq!(settings.sort, SortMode::Config);
+        settings.toggle_column(Column::Port);
+        assert!(!settings.is_column_shown(Column::Port));
+        settings.toggle_column(Column::Port);
+        assert!(settings.is_column_shown(Column::Port));
+
+        // Flags given on the command line win over the saved settings
+        let mut saved = Settings {
+            sort: SortMode::Destination,
+            ..Settings::from_flags(true, false)
+        };
+        saved.apply_flags(None, None);
+        assert_eq!(saved.sort, SortMode::Destination);
+        saved.apply_flags(Some(true), Some(true));
+        assert_eq!(saved.sort, SortMode::Name);
+        assert!(saved.is_column_shown(Column::Proxy));
+
+        assert_eq!(cycle(Theme::ALL, Theme::Rose, true), Theme::Blue);
+        assert_eq!(cycle(Theme::ALL, Theme::Blue, false), Theme::Rose);
+        assert_eq!(
+            cycle(Matcher::ALL, Matcher::Fuzzy, true),
+            Matcher::Substring
+        );
+    }
+}
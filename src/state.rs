@@ -0,0 +1,701 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::host_keys::HostKey;
+use crate::settings::Settings;
+use crate::stats::UsageStats;
+use crate::ui::facts::HostFacts;
+use crate::ui::updates::UpdateCheck;
+use crate::vault::{self, Vault};
+
+/// Number of remote commands (or template values) remembered per host
+const REMOTE_COMMAND_HISTORY_SIZE: usize = 20;
+
+/// Number of events (connections, failures, health changes) kept per host for its timeline
+const HOST_EVENT_HISTORY_SIZE: usize = 200;
+
+/// Health check answers are kept this long (a week), see [`State::record_health_sample`]
+pub const HEALTH_HISTORY_SECONDS: u64 = 7 * 24 * 3600;
+
+/// A health check answer the same as the previous one is only kept when that one is older
+/// than this, so that a short `--health-check-interval` does not grow the file
+const HEALTH_SAMPLE_SPACING: u64 = 10 * 60;
+
+/// Format of the state file, bumped when a change needs a migration in [`State::migrate`]
+const STATE_VERSION: u32 = 1;
+
+/// Changes are written in batches, at most once per delay, and when sshs exits
+pub const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// What happened to a host, in its timeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HostEventKind {
+    Connection,
+    Failure,
+    ConfigChange,
+    Health,
+}
+
+impl HostEventKind {
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            HostEventKind::Connection => "connection",
+            HostEventKind::Failure => "failure",
+            HostEventKind::ConfigChange => "config change",
+            HostEventKind::Health => "health",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostEvent {
+    /// Unix time of the event
+    pub time: u64,
+    pub kind: HostEventKind,
+    /// e.g. `session of 5m 02s, exit code 0`
+    pub detail: String,
+}
+
+/// Persistent sshs state, stored as JSON next to (but separate from) the SSH configuration.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    /// Format of the file, 0 for the files written before it was versioned
+    #[serde(default)]
+    pub version: u32,
+
+    /// Hosts for which the agent forwarding safety prompt is skipped
+    #[serde(default)]
+    pub trusted_agent_hosts: BTreeSet<String>,
+
+    /// Remote commands run on connect, per host, most recent first
+    #[serde(default)]
+    pub remote_command_history: BTreeMap<String, Vec<String>>,
+
+    /// Values used to connect to template hosts (`Host gw-%d`), per host, most recent first
+    #[serde(default)]
+    pub template_value_history: BTreeMap<String, Vec<String>>,
+
+    /// Targets of the port checks, per host, most recent first
+    #[serde(default)]
+    pub port_check_history: BTreeMap<String, Vec<String>>,
+
+    /// Forwards started in the background with `t`, per host, most recent first
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub forward_history: BTreeMap<String, Vec<String>>,
+
+    /// Commands run on the marked hosts with `E`, most recent first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub batch_commands: Vec<String>,
+
+    /// Names given to the session tabs, per host
+    #[serde(default)]
+    pub tab_names: BTreeMap<String, String>,
+
+    /// Named sets of session tabs, as the host names of the tabs in order
+    #[serde(default)]
+    pub workspaces: BTreeMap<String, Vec<String>>,
+
+    /// Named searches, recalled with `F` or the number keys (in name order)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub saved_searches: BTreeMap<String, String>,
+
+    /// Usage statistics, only kept when exported with `--stats-file`
+    #[serde(default, skip_serializing_if = "UsageStats::is_empty")]
+    pub usage_stats: UsageStats,
+
+    /// Day of the last connection (days since 1970-01-01), per host
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub last_connections: BTreeMap<String, i64>,
+
+    /// Day the connections started being recorded, hosts never connected to since are unused
+    /// from then on
+    #[serde(default)]
+    pub connections_recorded_since: Option<i64>,
+
+    /// Connections, failures and health changes, per host, oldest first
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub host_events: BTreeMap<String, Vec<HostEvent>>,
+
+    /// Answers of the health checks, per host, oldest first: unix time and reachability
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub health_samples: BTreeMap<String, Vec<(u64, bool)>>,
+
+    /// Host keys found by the last `--host-key-report`, per host
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub host_keys: BTreeMap<String, HostKey>,
+
+    /// Facts gathered from the hosts with `I`, per host
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub host_facts: BTreeMap<String, HostFacts>,
+
+    /// Display preferences saved from the settings panel
+    #[serde(default)]
+    pub settings: Option<Settings>,
+
+    /// Last check for a new release, with the Update check setting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_check: Option<UpdateCheck>,
+
+    #[serde(skip)]
+    path: Option<PathBuf>,
+
+    /// Key the file is encrypted with, `None` for a plain JSON file
+    #[serde(skip)]
+    vault: Option<Vault>,
+
+    /// First change not written yet
+    #[serde(skip)]
+    changed_at: Option<Instant>,
+}
+
+impl State {
+    /// Loads the state file, falling back to an empty state when it does not exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the state file exists but cannot be read or parsed.
+    pub fn load(raw_path: &str) -> Result<State> {
+        let path = PathBuf::from(shellexpand::tilde(raw_path).to_string());
+
+        let mut state = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str::<State>(&content)
+                .with_context(|| format!("Invalid state file '{}'", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to read state file '{}'", path.display()))
+            }
+        };
+        state
+            .migrate()
+            .with_context(|| format!("Invalid state file '{}'", path.display()))?;
+        state.purge_health_samples(unix_now());
+        state.path = Some(path);
+
+        Ok(state)
+    }
+
+    /// Loads the state from the vault of the state file (see [`crate::vault`]). A plain state
+    /// file left from before is moved into the new vault, and removed once the vault is written.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the vault cannot be read, decrypted or parsed, or the plain file
+    /// cannot be moved into it.
+    pub fn load_vault(raw_path: &str, vault: Vault) -> Result<State> {
+        let plain_path = PathBuf::from(shellexpand::tilde(raw_path).to_string());
+        let path = vault::vault_path(&plain_path);
+
+        let (mut state, is_new) = match fs::read(&path) {
+            Ok(data) => {
+                let mut state = serde_json::from_slice::<State>(&vault.decrypt(&data)?)
+                    .with_context(|| format!("Invalid vault '{}'", path.display()))?;
+                state
+                    .migrate()
+                    .with_context(|| format!("Invalid vault '{}'", path.display()))?;
+                state.purge_health_samples(unix_now());
+                (state, false)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                (State::load(raw_path)?, true)
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to read vault '{}'", path.display()))
+            }
+        };
+        state.path = Some(path);
+        state.vault = Some(vault);
+
+        if is_new {
+            state.save()?;
+            if plain_path.exists() {
+                fs::remove_file(&plain_path).with_context(|| {
+                    format!("Failed to remove state file '{}'", plain_path.display())
+                })?;
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// Brings a state read from an older file up to the current format.
+    fn migrate(&mut self) -> Result<()> {
+        if self.version > STATE_VERSION {
+            bail!(
+                "Written by a newer sshs (format {}, this version reads up to {STATE_VERSION})",
+                self.version
+            );
+        }
+
+        // Format 0 only lacked the version
+        self.version = STATE_VERSION;
+
+        Ok(())
+    }
+
+    /// Writes the state back to the file it was loaded from, replacing it atomically so that
+    /// an interrupted write cannot corrupt it. The state of a vault is encrypted before it is
+    /// written, even to the temporary file. In-memory states are not saved.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the state file cannot be written.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let content = serde_json::to_string_pretty(self)?.into_bytes();
+        let content = match &self.vault {
+            Some(vault) => vault.encrypt(&content)?,
+            None => content,
+        };
+        fs::write(&temporary, content)
+            .and_then(|()| fs::rename(&temporary, path))
+            .with_context(|| format!("Failed to write state file '{}'", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Records that the state changed, to be written by the next [`State::flush`].
+    pub fn mark_changed(&mut self) {
+        self.changed_at.get_or_insert_with(Instant::now);
+    }
+
+    /// Time left before the pending changes are due, `None` when there are none.
+    #[must_use]
+    pub fn save_due_in(&self) -> Option<Duration> {
+        self.changed_at
+            .map(|changed_at| SAVE_DELAY.saturating_sub(changed_at.elapsed()))
+    }
+
+    /// Writes the pending changes, if any. They stay pending when the write fails.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the state file cannot be written.
+    pub fn flush(&mut self) -> Result<()> {
+        let Some(changed_at) = self.changed_at.take() else {
+            return Ok(());
+        };
+
+        self.save()
+            .inspect_err(|_| self.changed_at = Some(changed_at))
+    }
+
+    /// Writes the pending changes once [`SAVE_DELAY`] has passed since the first of them.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the state file cannot be written.
+    pub fn flush_if_due(&mut self) -> Result<()> {
+        if self.save_due_in() == Some(Duration::ZERO) {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn is_agent_trusted(&self, host_name: &str) -> bool {
+        self.trusted_agent_hosts.contains(host_name)
+    }
+
+    pub fn trust_agent_host(&mut self, host_name: &str) {
+        self.trusted_agent_hosts.insert(host_name.to_string());
+    }
+
+    #[must_use]
+    pub fn remote_commands(&self, host_name: &str) -> Vec<String> {
+        self.remote_command_history
+            .get(host_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Moves `command` to the front of the host's history.
+    pub fn record_remote_command(&mut self, host_name: &str, command: &str) {
+        record_recent(&mut self.remote_command_history, host_name, command);
+    }
+
+    #[must_use]
+    pub fn template_values(&self, host_name: &str) -> Vec<String> {
+        self.template_value_history
+            .get(host_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Moves `value` to the front of the template host's history.
+    pub fn record_template_value(&mut self, host_name: &str, value: &str) {
+        record_recent(&mut self.template_value_history, host_name, value);
+    }
+
+    #[must_use]
+    pub fn port_check_targets(&self, host_name: &str) -> Vec<String> {
+        self.port_check_history
+            .get(host_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Moves `target` to the front of the host's port check history.
+    pub fn record_port_check_target(&mut self, host_name: &str, target: &str) {
+        record_recent(&mut self.port_check_history, host_name, target);
+    }
+
+    #[must_use]
+    pub fn forwards(&self, host_name: &str) -> Vec<String> {
+        self.forward_history
+            .get(host_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Moves `forward` to the front of the host's saved forwards.
+    pub fn record_forward(&mut self, host_name: &str, forward: &str) {
+        record_recent(&mut self.forward_history, host_name, forward);
+    }
+
+    /// Moves `command` to the front of the commands run on the marked hosts.
+    pub fn record_batch_command(&mut self, command: &str) {
+        self.batch_commands.retain(|previous| previous != command);
+        self.batch_commands.insert(0, command.to_string());
+        self.batch_commands.truncate(REMOTE_COMMAND_HISTORY_SIZE);
+    }
+
+    /// Keeps the keys found by a host key scan for the next one to compare with. Hosts that
+    /// were not reached keep their previous key.
+    pub fn record_host_keys(&mut self, keys: impl IntoIterator<Item = (String, HostKey)>) {
+        self.host_keys.extend(keys);
+    }
+
+    /// Starts recording the connections on `today`, unless they already are. Returns whether
+    /// the state changed.
+    pub fn start_recording_connections(&mut self, today: i64) -> bool {
+        let started = self.connections_recorded_since.is_none();
+        self.connections_recorded_since.get_or_insert(today);
+        started
+    }
+
+    pub fn record_connection(&mut self, host_name: &str, today: i64) {
+        self.start_recording_connections(today);
+        self.last_connections.insert(host_name.to_string(), today);
+    }
+
+    /// Appends `event` to the host's timeline, forgetting the oldest events past the limit.
+    pub fn record_host_event(&mut self, host_name: &str, event: HostEvent) {
+        let events = self.host_events.entry(host_name.to_string()).or_default();
+
+        events.push(event);
+        let excess = events.len().saturating_sub(HOST_EVENT_HISTORY_SIZE);
+        events.drain(..excess);
+    }
+
+    /// Records the result of the health `check` (e.g. a port check) of the host when it differs
+    /// from the previous one. Returns whether it was recorded.
+    pub fn record_health(&mut self, host_name: &str, check: &str, status: &str, time: u64) -> bool {
+        let prefix = format!("{check}: ");
+        let previous = self.host_events.get(host_name).and_then(|events| {
+            events
+                .iter()
+                .rev()
+                .filter(|event| event.kind == HostEventKind::Health)
+                .find_map(|event| event.detail.strip_prefix(&prefix))
+        });
+        if previous == Some(status) {
+            return false;
+        }
+
+        self.record_host_event(
+            host_name,
+            HostEvent {
+                time,
+                kind: HostEventKind::Health,
+                detail: format!("{prefix}{status}"),
+            },
+        );
+        true
+    }
+
+    /// Records the answer of the health check of the host at `time`, then forgets its answers
+    /// older than [`HEALTH_HISTORY_SECONDS`]. An answer the same as the previous one, less than
+    /// [`HEALTH_SAMPLE_SPACING`] after it, is not recorded. Returns whether it was recorded.
+    pub fn record_health_sample(&mut self, host_name: &str, reachable: bool, time: u64) -> bool {
+        let samples = self
+            .health_samples
+            .entry(host_name.to_string())
+            .or_default();
+        let recorded = match samples.last() {
+            Some(&(last_time, last_reachable)) => {
+                last_reachable != reachable || time >= last_time + HEALTH_SAMPLE_SPACING
+            }
+            None => true,
+        };
+        if recorded {
+            samples.push((time, reachable));
+        }
+
+        let oldest = time.saturating_sub(HEALTH_HISTORY_SECONDS);
+        samples.retain(|&(time, _)| time >= oldest);
+        recorded
+    }
+
+    /// Forgets the health check answers older than [`HEALTH_HISTORY_SECONDS`] at `now`, and the
+    /// hosts left without any.
+    pub fn purge_health_samples(&mut self, now: u64) {
+        let oldest = now.saturating_sub(HEALTH_HISTORY_SECONDS);
+        for samples in self.health_samples.values_mut() {
+            samples.retain(|&(time, _)| time >= oldest);
+        }
+        self.health_samples.retain(|_, samples| !samples.is_empty());
+    }
+
+    /// Host names of the tabs of the workspace, in order
+    #[must_use]
+    pub fn workspace(&self, name: &str) -> Option<&[String]> {
+        self.workspaces.get(name).map(Vec::as_slice)
+    }
+
+    /// Saves the tabs as workspace `name`, replacing a previous one with the same name.
+    pub fn save_workspace(&mut self, name: &str, host_names: Vec<String>) {
+        self.workspaces.insert(name.to_string(), host_names);
+    }
+
+    /// Query of the saved search `name`
+    #[must_use]
+    pub fn saved_search(&self, name: &str) -> Option<&str> {
+        self.saved_searches.get(name).map(String::as_str)
+    }
+}
+
+fn record_recent(histories: &mut BTreeMap<String, Vec<String>>, host_name: &str, value: &str) {
+    let history = histories.entry(host_name.to_string()).or_default();
+
+    history.retain(|previous| previous != value);
+    history.insert(0, value.to_string());
+    history.truncate(REMOTE_COMMAND_HISTORY_SIZE);
+}
+
+/// Current unix time, in seconds
+fn unix_now() -> u64 {
+    u64::try_from(crate::certificate::now()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_missing_state_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+
+        let state = State::load(path.to_str().unwrap()).unwrap();
+        assert!(state.trusted_agent_hosts.is_empty());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_trusted_agent_hosts_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("nested").join("state.json");
+        let raw_path = path.to_str().unwrap();
+
+        let mut state = State::load(raw_path).unwrap();
+        state.trust_agent_host("bastion");
+        state.save().unwrap();
+
+        let state = State::load(raw_path).unwrap();
+        assert!(state.is_agent_trusted("bastion"));
+        assert!(!state.is_agent_trusted("web"));
+    }
+
+    #[test]
+    fn test_plain_state_moves_into_vault() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+        let raw_path = path.to_str().unwrap();
+
+        let mut state = State::load(raw_path).unwrap();
+        state.trust_agent_host("bastion");
+        state.save().unwrap();
+
+        let vault = Vault::create("correct horse").unwrap();
+        let mut state = State::load_vault(raw_path, vault).unwrap();
+        assert!(state.is_agent_trusted("bastion"));
+        assert!(!path.exists());
+        state.trust_agent_host("web");
+        state.save().unwrap();
+
+        let data = fs::read(vault::vault_path(&path)).unwrap();
+        assert!(!data.windows(7).any(|window| window == b"bastion"));
+
+        let vault = Vault::unlock("correct horse", &data).unwrap();
+        let state = State::load_vault(raw_path, vault).unwrap();
+        assert!(state.is_agent_trusted("bastion"));
+        assert!(state.is_agent_trusted("web"));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_vault_migration_failures() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+        let raw_path = path.to_str().unwrap();
+        let vault_path = vault::vault_path(&path);
+
+        // A plain file that cannot be read is left as it is, and no vault is written
+        fs::write(&path, "{\"version\":").unwrap();
+        assert!(State::load_vault(raw_path, Vault::create("correct horse").unwrap()).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"version\":");
+        assert!(!vault_path.exists());
+
+        // Without a plain file, the vault starts empty
+        fs::remove_file(&path).unwrap();
+        let state = State::load_vault(raw_path, Vault::create("correct horse").unwrap()).unwrap();
+        assert!(!state.is_agent_trusted("bastion"));
+        assert!(vault_path.exists());
+
+        // A vault of another key is neither read nor replaced, even with a plain file next to it
+        let data = fs::read(&vault_path).unwrap();
+        fs::write(&path, "{}").unwrap();
+        let message = State::load_vault(raw_path, Vault::create("wrong horse").unwrap())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(message, "Wrong passphrase, or the vault was modified");
+        assert_eq!(fs::read(&vault_path).unwrap(), data);
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_host_events() {
+        let mut state = State::default();
+        assert!(state.record_health("web", "db:5432", "reachable", 10));
+        assert!(!state.record_health("web", "db:5432", "reachable", 20));
+        assert!(state.record_health("web", "cache:6379", "reachable", 30));
+        assert!(state.record_health("web", "db:5432", "unreachable", 40));
+
+        let times = |state: &State| {
+            state.host_events["web"]
+                .iter()
+                .map(|e| e.time)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(times(&state), [10, 30, 40]);
+
+        for time in 0..HOST_EVENT_HISTORY_SIZE as u64 {
+            state.record_host_event(
+                "web",
+                HostEvent {
+                    time: 100 + time,
+                    kind: HostEventKind::Connection,
+                    detail: String::new(),
+                },
+            );
+        }
+        assert_eq!(state.host_events["web"].len(), HOST_EVENT_HISTORY_SIZE);
+        assert_eq!(times(&state)[0], 100);
+    }
+
+    #[test]
+    fn test_health_samples() {
+        let mut state = State::default();
+        let start = 1_000_000;
+        assert!(state.record_health_sample("web", true, start));
+        // The same answer is only kept once in a while, a change right away
+        assert!(!state.record_health_sample("web", true, start + 60));
+        assert!(state.record_health_sample("web", false, start + 120));
+        assert!(state.record_health_sample("web", false, start + 120 + HEALTH_SAMPLE_SPACING));
+        assert_eq!(state.health_samples["web"].len(), 3);
+
+        // Answers older than a week are forgotten
+        let later = start + 121 + HEALTH_HISTORY_SECONDS;
+        assert!(state.record_health_sample("web", true, later));
+        assert_eq!(
+            state.health_samples["web"],
+            [(start + 120 + HEALTH_SAMPLE_SPACING, false), (later, true)]
+        );
+
+        state.record_health_sample("db", true, start);
+        state.purge_health_samples(later);
+        assert!(!state.health_samples.contains_key("db"));
+        assert!(state.health_samples.contains_key("web"));
+    }
+
+    #[test]
+    fn test_remote_command_history() {
+        let mut state = State::default();
+        state.record_remote_command("web", "htop");
+        state.record_remote_command("web", "df -h");
+        state.record_remote_command("web", "htop");
+
+        assert_eq!(state.remote_commands("web"), vec!["htop", "df -h"]);
+        assert!(state.remote_commands("db").is_empty());
+
+        for i in 0..30 {
+            state.record_remote_command("db", &format!("echo {i}"));
+        }
+        assert_eq!(
+            state.remote_commands("db").len(),
+            REMOTE_COMMAND_HISTORY_SIZE
+        );
+        assert_eq!(state.remote_commands("db")[0], "echo 29");
+    }
+
+    #[test]
+    fn test_template_value_history() {
+        let mut state = State::default();
+        state.record_template_value("gw-%d", "12");
+        state.record_template_value("gw-%d", "7");
+
+        assert_eq!(state.template_values("gw-%d"), vec!["7", "12"]);
+        assert!(state.remote_commands("gw-%d").is_empty());
+    }
+
+    #[test]
+    fn test_changes_are_batched_and_versioned() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+        let raw_path = path.to_str().unwrap();
+        fs::write(&path, r#"{"trusted_agent_hosts":["bastion"]}"#).unwrap();
+
+        let mut state = State::load(raw_path).unwrap();
+        assert_eq!(state.version, STATE_VERSION);
+        assert!(state.is_agent_trusted("bastion"));
+
+        state.trust_agent_host("web");
+        state.mark_changed();
+        state.flush_if_due().unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("web"));
+
+        state.flush().unwrap();
+        assert_eq!(state.save_due_in(), None);
+        let state = State::load(raw_path).unwrap();
+        assert!(state.is_agent_trusted("web"));
+
+        fs::write(&path, r#"{"version":99}"#).unwrap();
+        let err = State::load(raw_path).unwrap_err();
+        assert!(format!("{err:#}").contains("newer sshs"));
+    }
+
+    #[test]
+    fn test_in_memory_state_is_not_saved() {
+        let mut state = State::default();
+        state.trust_agent_host("bastion");
+        assert!(state.save().is_ok());
+    }
+}
@@ -0,0 +1,194 @@
+//! Opt-in usage statistics (`--stats-file`): connections, failures and time spent in sessions,
+//! per host. They are kept in the state file and exported after every session, as JSON or as
+//! a Prometheus textfile (`.prom`) for the node exporter's textfile collector.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+/// Counters of a host, or of all of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostStats {
+    pub connections: u64,
+    /// Connections that failed before a session started (`ssh` exit code 255)
+    pub failures: u64,
+    /// Time spent in the sessions that did start
+    pub session_seconds: u64,
+}
+
+impl HostStats {
+    fn add(&mut self, other: &HostStats) {
+        self.connections += other.connections;
+        self.failures += other.failures;
+        self.session_seconds += other.session_seconds;
+    }
+}
+
+/// Usage statistics, by host name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UsageStats {
+    pub hosts: BTreeMap<String, HostStats>,
+}
+
+/// Reads one counter of a host
+type Counter = fn(&HostStats) -> u64;
+
+/// Metrics of the Prometheus export: name, help and the counter they are read from
+const METRICS: &[(&str, &str, Counter)] = &[
+    (
+        "sshs_connections_total",
+        "SSH connections started from sshs.",
+        |stats| stats.connections,
+    ),
+    (
+        "sshs_connection_failures_total",
+        "SSH connections that failed before a session started.",
+        |stats| stats.failures,
+    ),
+    (
+        "sshs_session_seconds_total",
+        "Time spent in SSH sessions.",
+        |stats| stats.session_seconds,
+    ),
+];
+
+impl UsageStats {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+
+    /// Counts a connection to `host` that lasted `duration`.
+    pub fn record(&mut self, host: &str, duration: Duration, failed: bool) {
+        let stats = self.hosts.entry(host.to_string()).or_default();
+
+        stats.connections += 1;
+        if failed {
+            stats.failures += 1;
+        } else {
+            stats.session_seconds += duration.as_secs();
+        }
+    }
+
+    #[must_use]
+    pub fn totals(&self) -> HostStats {
+        let mut totals = HostStats::default();
+        for stats in self.hosts.values() {
+            totals.add(stats);
+        }
+
+        totals
+    }
+
+    /// JSON export, with the totals next to the per host counters.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the statistics cannot be serialized.
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(&serde_json::json!({
+            "totals": self.totals(),
+            "hosts": self.hosts,
+        }))?)
+    }
+
+    /// Prometheus text format export, one counter per host.
+    #[must_use]
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+
+        for (name, help, value) in METRICS {
+            let _ = writeln!(text, "# HELP {name} {help}");
+            let _ = writeln!(text, "# TYPE {name} counter");
+            for (host, stats) in &self.hosts {
+                let _ = writeln!(
+                    text,
+                    "{name}{{host=\"{}\"}} {}",
+                    escape_label(host),
+                    value(stats)
+                );
+            }
+        }
+
+        text
+    }
+
+    /// Writes the statistics to `raw_path`, as a Prometheus textfile when it ends with `.prom`
+    /// and as JSON otherwise. The file is replaced atomically, so a collector never reads half
+    /// of it.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file cannot be written.
+    pub fn export(&self, raw_path: &str) -> anyhow::Result<()> {
+        let path = shellexpand::tilde(raw_path).to_string();
+        let path = Path::new(&path);
+
+        let content = if path
+            .extension()
+            .is_some_and(|extension| extension == "prom")
+        {
+            self.to_prometheus()
+        } else {
+            self.to_json()?
+        };
+
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        std::fs::write(&temporary, content)
+            .and_then(|()| std::fs::rename(&temporary, path))
+            .with_context(|| format!("Failed to write stats file '{}'", path.display()))
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_stats_exports() {
+        let mut stats = UsageStats::default();
+        stats.record("web", Duration::from_secs(90), false);
+        stats.record("web", Duration::from_secs(2), true);
+        stats.record("db \"primary\"", Duration::from_secs(30), false);
+
+        assert_eq!(
+            stats.totals(),
+            HostStats {
+                connections: 3,
+                failures: 1,
+                session_seconds: 120,
+            }
+        );
+
+        let prometheus = stats.to_prometheus();
+        assert!(prometheus.contains("# TYPE sshs_connections_total counter\n"));
+        assert!(prometheus.contains("sshs_connections_total{host=\"web\"} 2\n"));
+        assert!(prometheus.contains("sshs_connection_failures_total{host=\"web\"} 1\n"));
+        assert!(prometheus.contains("sshs_session_seconds_total{host=\"db \\\"primary\\\"\"} 30\n"));
+
+        let dir = TempDir::new().unwrap();
+        let json_path = dir.path().join("stats.json");
+        stats.export(json_path.to_str().unwrap()).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&json_path).unwrap()).unwrap();
+        assert_eq!(json["totals"]["session_seconds"], 120);
+        assert_eq!(json["hosts"]["web"]["connections"], 2);
+
+        let prom_path = dir.path().join("sshs.prom");
+        stats.export(prom_path.to_str().unwrap()).unwrap();
+        assert_eq!(std::fs::read_to_string(&prom_path).unwrap(), prometheus);
+    }
+}
@@ -0,0 +1,570 @@
+//! Central registry of user actions, shared by the keybindings and the menu bar.
+
+use ratatui::layout::Rect;
+
+/// An action the user can trigger from a keybinding or a menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Connect,
+    ConnectSocks,
+    ConnectAgent,
+    ConnectWithCommand,
+    ConnectJump,
+    BackgroundSocks,
+    Forward,
+    SavedForwards,
+    AddHost,
+    EditHost,
+    DeleteHost,
+    ExpiredHosts,
+    UnusedHosts,
+    EditTags,
+    HostDetails,
+    Timeline,
+    AuthorizedKeys,
+    CopyPublicKey,
+    CopyConfigBlock,
+    BatchRun,
+    PortCheck,
+    HealthCheck,
+    StateFilter,
+    IncidentMode,
+    GatherFacts,
+    WebConsole,
+    OpenLinks,
+    ReloadHosts,
+    Backups,
+    AuditLog,
+    Settings,
+    Search,
+    SaveSearch,
+    SavedSearches,
+    ToggleGroup,
+    RecentHosts,
+    Presentation,
+    NewSession,
+    RenameTab,
+    LockTab,
+    SaveWorkspace,
+    OpenWorkspace,
+    BackgroundTunnels,
+    BrowseFiles,
+    SessionLog,
+    ExportScrollback,
+    About,
+    WhatsNew,
+    Quit,
+}
+
+impl Action {
+    /// Human readable label, used in menus.
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Connect => "Connect",
+            Action::ConnectSocks => "Connect with SOCKS proxy",
+            Action::ConnectAgent => "Connect with agent forwarding",
+            Action::ConnectWithCommand => "Connect and run...",
+            Action::ConnectJump => "Connect through jump host...",
+            Action::BackgroundSocks => "SOCKS proxy in the background",
+            Action::Forward => "Forward ports in the background...",
+            Action::SavedForwards => "Start the saved forwards",
+            Action::AddHost => "New host",
+            Action::EditHost => "Edit host",
+            Action::DeleteHost => "Delete host",
+            Action::ExpiredHosts => "Clean up expired hosts",
+            Action::UnusedHosts => "Unused hosts",
+            Action::EditTags => "Edit tags of listed hosts...",
+            Action::HostDetails => "Host details",
+            Action::Timeline => "Host timeline",
+            Action::AuthorizedKeys => "Remote authorized keys",
+            Action::CopyPublicKey => "Copy public key",
+            Action::CopyConfigBlock => "Copy config block",
+            Action::BatchRun => "Run on the marked hosts...",
+            Action::PortCheck => "Check port from host...",
+            Action::HealthCheck => "Check reachability",
+            Action::StateFilter => "Filter by state",
+            Action::IncidentMode => "Incident mode",
+            Action::GatherFacts => "Gather facts",
+            Action::WebConsole => "Open web console",
+            Action::OpenLinks => "Open host links...",
+            Action::ReloadHosts => "Reload SSH config",
+            Action::Backups => "Config backups",
+            Action::AuditLog => "Audit log",
+            Action::Settings => "Settings",
+            Action::Search => "Search",
+            Action::SaveSearch => "Save search...",
+            Action::SavedSearches => "Saved searches...",
+            Action::ToggleGroup => "Collapse/expand group",
+            Action::RecentHosts => "Recent hosts",
+            Action::Presentation => "Presentation mode",
+            Action::NewSession => "New session",
+            Action::RenameTab => "Rename tab",
+            Action::LockTab => "Lock/unlock tab",
+            Action::SaveWorkspace => "Save workspace...",
+            Action::OpenWorkspace => "Open workspace...",
+            Action::BackgroundTunnels => "Background tunnels",
+            Action::BrowseFiles => "Browse files (SFTP)",
+            Action::SessionLog => "Last session log",
+            Action::ExportScrollback => "Export session output",
+            Action::About => "About sshs",
+            Action::WhatsNew => "What's new",
+            Action::Quit => "Quit",
+        }
+    }
+
+    /// Keybinding triggering the same action, shown next to the label.
+    #[must_use]
+    pub fn key_hint(self) -> &'static str {
+        match self {
+            Action::Connect => "Enter",
+            Action::ConnectSocks => "D",
+            Action::ConnectAgent => "A",
+            Action::ConnectWithCommand => "!",
+            Action::ConnectJump => "J",
+            Action::BackgroundSocks => "S",
+            Action::BackgroundTunnels => "B",
+            Action::BrowseFiles => "f",
+            Action::SessionLog => "O",
+            Action::ExportScrollback => "Ctrl+S",
+            Action::Forward => "t",
+            Action::SavedForwards => "L",
+            Action::AddHost => "n",
+            Action::EditHost => "e",
+            Action::DeleteHost => "d",
+            Action::ExpiredHosts => "X",
+            Action::UnusedHosts => "U",
+            Action::EditTags => "T",
+            Action::HostDetails => "i",
+            Action::Timeline => "H",
+            Action::AuthorizedKeys => "K",
+            Action::CopyPublicKey => "y",
+            Action::CopyConfigBlock => "Y",
+            Action::BatchRun => "E",
+            Action::PortCheck => "P",
+            Action::HealthCheck => "C",
+            Action::StateFilter => "c",
+            Action::IncidentMode => "x",
+            Action::GatherFacts => "I",
+            Action::WebConsole => "w",
+            Action::OpenLinks => "o",
+            Action::Backups => "b",
+            Action::Settings => ",",
+            Action::ReloadHosts
+            | Action::AuditLog
+            | Action::SaveWorkspace
+            | Action::OpenWorkspace
+            | Action::About => "",
+            Action::Search => "/",
+            Action::SaveSearch => "M",
+            Action::SavedSearches => "F",
+            Action::ToggleGroup => "z",
+            Action::RecentHosts => "R",
+            Action::Presentation => "p",
+            Action::WhatsNew => "V",
+            Action::NewSession => "Ctrl+N",
+            Action::RenameTab => "Ctrl+T",
+            Action::LockTab => "Ctrl+L",
+            Action::Quit => "q",
+        }
+    }
+}
+
+/// A top-level menu of the menu bar.
+pub struct Menu {
+    pub title: &'static str,
+    pub actions: &'static [Action],
+}
+
+pub const MENUS: &[Menu] = &[
+    Menu {
+        title: "File",
+        actions: &[
+            Action::ReloadHosts,
+            Action::Backups,
+            Action::AuditLog,
+            Action::Quit,
+        ],
+    },
+    Menu {
+        title: "Hosts",
+        actions: &[
+            Action::Connect,
+            Action::ConnectSocks,
+            Action::ConnectAgent,
+            Action::ConnectWithCommand,
+            Action::ConnectJump,
+            Action::AddHost,
+            Action::EditHost,
+            Action::DeleteHost,
+            Action::ExpiredHosts,
+            Action::UnusedHosts,
+            Action::EditTags,
+            Action::HostDetails,
+            Action::Timeline,
+            Action::AuthorizedKeys,
+            Action::CopyConfigBlock,
+            Action::CopyPublicKey,
+        ],
+    },
+    Menu {
+        title: "Sessions",
+        actions: &[
+            Action::NewSession,
+            Action::RenameTab,
+            Action::SaveWorkspace,
+            Action::OpenWorkspace,
+            Action::LockTab,
+            Action::BackgroundSocks,
+            Action::Forward,
+            Action::SavedForwards,
+            Action::BatchRun,
+            Action::BackgroundTunnels,
+            Action::BrowseFiles,
+            Action::SessionLog,
+            Action::ExportScrollback,
+        ],
+    },
+    Menu {
+        title: "Tools",
+        actions: &[
+            Action::Search,
+            Action::SaveSearch,
+            Action::SavedSearches,
+            Action::ToggleGroup,
+            Action::RecentHosts,
+            Action::Presentation,
+            Action::Settings,
+            Action::PortCheck,
+            Action::HealthCheck,
+            Action::StateFilter,
+            Action::IncidentMode,
+            Action::GatherFacts,
+            Action::WebConsole,
+            Action::OpenLinks,
+        ],
+    },
+    Menu {
+        title: "Help",
+        actions: &[Action::About, Action::WhatsNew],
+    },
+];
+
+/// Key starting the leader sequences, as in Vim
+pub const DEFAULT_LEADER: char = '\\';
+
+/// Actions reached by typing the leader key, the key of the group, then the key of the action:
+/// `<leader>tn` opens a new session.
+pub struct LeaderGroup {
+    pub key: char,
+    pub title: &'static str,
+    pub bindings: &'static [(char, Action)],
+}
+
+impl LeaderGroup {
+    #[must_use]
+    pub fn find(key: char) -> Option<&'static LeaderGroup> {
+        LEADER_GROUPS.iter().find(|group| group.key == key)
+    }
+
+    #[must_use]
+    pub fn action(&self, key: char) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(binding, _)| *binding == key)
+            .map(|(_, action)| *action)
+    }
+}
+
+/// Leader sequences, reaching the actions without a key of their own and leaving the Ctrl
+/// combinations terminals keep for themselves alone.
+pub const LEADER_GROUPS: &[LeaderGroup] = &[
+    LeaderGroup {
+        key: 'c',
+        title: "connect",
+        bindings: &[
+            ('c', Action::Connect),
+            ('s', Action::ConnectSocks),
+            ('a', Action::ConnectAgent),
+            ('r', Action::ConnectWithCommand),
+            ('j', Action::ConnectJump),
+            ('f', Action::BrowseFiles),
+            ('e', Action::BatchRun),
+            ('l', Action::SessionLog),
+        ],
+    },
+    LeaderGroup {
+        key: 'h',
+        title: "hosts",
+        bindings: &[
+            ('n', Action::AddHost),
+            ('e', Action::EditHost),
+            ('d', Action::DeleteHost),
+            ('t', Action::EditTags),
+            ('i', Action::HostDetails),
+            ('l', Action::Timeline),
+            ('k', Action::AuthorizedKeys),
+            ('y', Action::CopyPublicKey),
+            ('c', Action::CopyConfigBlock),
+            ('x', Action::ExpiredHosts),
+            ('u', Action::UnusedHosts),
+        ],
+    },
+    LeaderGroup {
+        key: 't',
+        title: "tabs",
+        bindings: &[
+            ('n', Action::NewSession),
+            ('r', Action::RenameTab),
+            ('l', Action::LockTab),
+            ('s', Action::SaveWorkspace),
+            ('o', Action::OpenWorkspace),
+            ('e', Action::ExportScrollback),
+        ],
+    },
+    LeaderGroup {
+        key: 'f',
+        title: "forwards",
+        bindings: &[
+            ('f', Action::Forward),
+            ('l', Action::SavedForwards),
+            ('s', Action::BackgroundSocks),
+            ('t', Action::BackgroundTunnels),
+        ],
+    },
+    LeaderGroup {
+        key: 's',
+        title: "search",
+        bindings: &[
+            ('s', Action::Search),
+            ('m', Action::SaveSearch),
+            ('l', Action::SavedSearches),
+            ('r', Action::RecentHosts),
+            ('g', Action::ToggleGroup),
+            ('p', Action::Presentation),
+        ],
+    },
+    LeaderGroup {
+        key: 'x',
+        title: "tools",
+        bindings: &[
+            ('p', Action::PortCheck),
+            ('h', Action::HealthCheck),
+            ('s', Action::StateFilter),
+            ('i', Action::IncidentMode),
+            ('f', Action::GatherFacts),
+            ('w', Action::WebConsole),
+            ('o', Action::OpenLinks),
+        ],
+    },
+    LeaderGroup {
+        key: 'r',
+        title: "config",
+        bindings: &[
+            ('r', Action::ReloadHosts),
+            ('b', Action::Backups),
+            ('a', Action::AuditLog),
+            ('s', Action::Settings),
+        ],
+    },
+    LeaderGroup {
+        key: '?',
+        title: "help",
+        bindings: &[('a', Action::About), ('n', Action::WhatsNew)],
+    },
+];
+
+/// Selection inside the open menu bar.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MenuState {
+    pub menu: usize,
+    pub item: usize,
+}
+
+impl MenuState {
+    #[must_use]
+    pub fn current_menu(&self) -> &'static Menu {
+        &MENUS[self.menu]
+    }
+
+    #[must_use]
+    pub fn selected_action(&self) -> Action {
+        self.current_menu().actions[self.item]
+    }
+
+    pub fn next_menu(&mut self) {
+        self.menu = (self.menu + 1) % MENUS.len();
+        self.item = 0;
+    }
+
+    pub fn previous_menu(&mut self) {
+        self.menu = (self.menu + MENUS.len() - 1) % MENUS.len();
+        self.item = 0;
+    }
+
+    pub fn next_item(&mut self) {
+        self.item = (self.item + 1) % self.current_menu().actions.len();
+    }
+
+    pub fn previous_item(&mut self) {
+        let len = self.current_menu().actions.len();
+        self.item = (self.item + len - 1) % len;
+    }
+
+    /// Start and width of each menu title on the bar, from its left edge.
+    pub fn title_columns() -> impl Iterator<Item = (u16, u16)> {
+        MENUS.iter().scan(0, |start, menu| {
+            let width = u16::try_from(menu.title.len() + 2).unwrap_or_default();
+            let column = *start;
+            *start += width;
+            Some((column, width))
+        })
+    }
+
+    /// Area of the dropdown of the open menu in `area`, under its title.
+    #[must_use]
+    pub fn dropdown_area(&self, area: Rect) -> Rect {
+        let current = self.current_menu();
+        let label_width = current
+            .actions
+            .iter()
+            .map(|action| action.label().len() + action.key_hint().len() + 3)
+            .max()
+            .unwrap_or_default();
+        let width = u16::try_from(label_width).unwrap_or(40) + 2;
+        let height = u16::try_from(current.actions.len()).unwrap_or_default() + 2;
+        let x = Self::title_columns()
+            .nth(self.menu)
+            .map_or(0, |(start, _)| start);
+
+        Rect::new(
+            area.x + x.min(area.width.saturating_sub(width)),
+            area.y + 1,
+            width.min(area.width),
+            height.min(area.height.saturating_sub(1)),
+        )
+    }
+
+    /// What a click at `column`, `row` of `area` lands on.
+    #[must_use]
+    pub fn click(&self, area: Rect, column: u16, row: u16) -> MenuClick {
+        if row == area.y {
+            return Self::title_columns()
+                .position(|(start, width)| {
+                    (start..start + width).contains(&column.saturating_sub(area.x))
+                })
+                .map_or(MenuClick::Outside, MenuClick::Menu);
+        }
+
+        // Inside the borders of the dropdown
+        let dropdown = self.dropdown_area(area);
+        if column > dropdown.x
+            && column < dropdown.right().saturating_sub(1)
+            && row > dropdown.y
+            && row < dropdown.bottom().saturating_sub(1)
+        {
+            return MenuClick::Item(usize::from(row - dropdown.y - 1));
+        }
+
+        MenuClick::Outside
+    }
+}
+
+/// Where a mouse click lands while the menu bar is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuClick {
+    /// The title of the menu at this index
+    Menu(usize),
+    /// The action at this index in the open dropdown
+    Item(usize),
+    Outside,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_menu_navigation_wraps() {
+        let mut state = MenuState::default();
+        assert_eq!(state.current_menu().title, "File");
+
+        state.previous_menu();
+        assert_eq!(state.current_menu().title, "Help");
+
+        state.next_menu();
+        state.next_menu();
+        assert_eq!(state.current_menu().title, "Hosts");
+        assert_eq!(state.selected_action(), Action::Connect);
+
+        state.previous_item();
+        assert_eq!(state.selected_action(), Action::CopyPublicKey);
+
+        state.next_item();
+        state.next_item();
+        assert_eq!(state.selected_action(), Action::ConnectSocks);
+
+        // Switching menus resets the selected item
+        state.next_menu();
+        assert_eq!(state.item, 0);
+    }
+
+    #[test]
+    fn test_menu_clicks() {
+        let area = Rect::new(0, 0, 120, 30);
+        let state = MenuState::default();
+
+        // " File " then " Hosts "
+        assert_eq!(state.click(area, 0, 0), MenuClick::Menu(0));
+        assert_eq!(state.click(area, 6, 0), MenuClick::Menu(1));
+        assert_eq!(state.click(area, 119, 0), MenuClick::Outside);
+
+        // Below the border of the dropdown
+        assert_eq!(state.click(area, 2, 2), MenuClick::Item(0));
+        assert_eq!(state.click(area, 2, 3), MenuClick::Item(1));
+        assert_eq!(state.click(area, 0, 2), MenuClick::Outside);
+        assert_eq!(state.click(area, 2, 1), MenuClick::Outside);
+        assert_eq!(state.click(area, 100, 20), MenuClick::Outside);
+    }
+
+    #[test]
+    fn test_leader_sequences_reach_every_action() {
+        assert_eq!(
+            LeaderGroup::find('t').and_then(|group| group.action('n')),
+            Some(Action::NewSession)
+        );
+        assert!(LeaderGroup::find('z').is_none());
+
+        for (index, group) in LEADER_GROUPS.iter().enumerate() {
+            assert_eq!(LeaderGroup::find(group.key).unwrap().title, group.title);
+            for (position, (key, _)) in group.bindings.iter().enumerate() {
+                assert!(
+                    !group.bindings[..position]
+                        .iter()
+                        .any(|(other, _)| other == key),
+                    "{key} bound twice in the {} group",
+                    group.title
+                );
+            }
+            assert!(!LEADER_GROUPS[..index]
+                .iter()
+                .any(|other| other.key == group.key));
+        }
+
+        // Quitting stays on `q`
+        for action in MENUS.iter().flat_map(|menu| menu.actions) {
+            let reachable = LEADER_GROUPS
+                .iter()
+                .flat_map(|group| group.bindings)
+                .any(|(_, bound)| bound == action);
+            assert!(reachable || *action == Action::Quit, "{action:?}");
+        }
+    }
+
+    #[test]
+    fn test_every_menu_has_actions() {
+        for menu in MENUS {
+            assert!(!menu.actions.is_empty(), "{} is empty", menu.title);
+        }
+    }
+}
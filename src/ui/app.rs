@@ -1,20 +1,23 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use crossterm::{
     cursor::{Hide, Show},
     event::{
-        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
-        KeyModifiers,
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
     },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 #[allow(clippy::wildcard_imports)]
 use ratatui::{prelude::*, widgets::*};
 use std::{
     cell::RefCell,
     cmp::{max, min},
+    collections::{btree_map, BTreeMap, BTreeSet},
+    fmt::Write as _,
     io,
+    path::{Path, PathBuf},
     process::Command,
     rc::Rc,
     thread,
@@ -25,9 +28,54 @@ use tui_input::backend::crossterm::EventHandler;
 use tui_input::Input;
 use unicode_width::UnicodeWidthStr;
 
-use super::form::{AddHostForm, FormState};
-use super::tabs::TabManager;
-use crate::{searchable::Searchable, ssh};
+use super::actions::{Action, LeaderGroup, MenuClick, MenuState};
+use super::audit_log::{self, AuditLogView};
+use super::authorized_keys::{self, AuthorizedKeysView};
+use super::background_tunnels::{self, Forward, TunnelsView};
+use super::backups::{self, BackupsView, Expected};
+use super::batch_run::{self, BatchRun};
+use super::bulk_tags::{self, TagEdit};
+use super::capabilities::Capabilities;
+use super::clipboard;
+use super::config_errors::{self, ConfigErrorsView, ConfigProblem};
+use super::dns::DnsCache;
+use super::facts;
+use super::form::{
+    self, known_hosts_destinations, AddHostForm, FormState, IDENTITY_FILE_FIELD, KNOWN_HOSTS_PATH,
+    OPTIONS_FIELD,
+};
+use super::health::{self, HealthChecker, StateCounts, StateFilter};
+use super::incident::{self, Incident};
+use super::keymap::Keymap;
+use super::managed_config;
+use super::network::{Network, NetworkWatch};
+use super::port_check::{self, PortStatus};
+use super::prompt::{Prompt, PromptKind};
+use super::recent;
+use super::session;
+use super::session_log;
+use super::session_summary::{SessionSummary, SummaryAction};
+use super::settings_panel::SettingsPanel;
+use super::sftp::{self, Direction, SftpView, Side, Transfer};
+use super::tabs::{Session, TabManager, TabNaming};
+use super::timeline::{self, TimelineView};
+use super::triggers::{self, Trigger};
+use super::tutorial::Tutorial;
+use super::unused_hosts::{self, UnusedHost, DEFAULT_UNUSED_HOSTS_EXPORT, STALE_TAG};
+use super::updates::{self, Release, UpdateCheck, UpdateChecker};
+use super::web_console;
+use crate::backend::{BackendKind, ConnectionBackend};
+use crate::certificate::{self, Certificate, CertificateStatus};
+use crate::config_file;
+use crate::dotfiles;
+use crate::exec;
+use crate::log;
+use crate::matcher::SearchQuery;
+use crate::settings::{Column, GroupMode, Matcher, Settings};
+use crate::shared_metadata::SharedMetadata;
+use crate::state::{HostEvent, HostEventKind, State};
+use crate::vault::Vault;
+use crate::{searchable::Searchable, ssh, ssh_config};
 
 // UI Constants
 pub const INFO_TEXT: &str = "(Esc) quit | (↑) move up | (↓) move down | (enter) select | (Ctrl+N) new host | (Ctrl+E) edit host";
@@ -40,6 +88,60 @@ pub const CURSOR_VERTICAL_OFFSET: u16 = 1;
 pub const COLUMN_PADDING: u16 = 1;
 pub const SEARCHBAR_HORIZONTAL_PADDING: u16 = 3;
 pub const TABLE_HEADER_HEIGHT: u16 = 1;
+/// Shown instead of the destinations in the presentation mode
+pub const PRESENTATION_MASK: &str = "••••••";
+
+/// Confirmation action shown before forwarding the SSH agent to an untrusted host
+pub const AGENT_FORWARDING_ACTION: &str = "Forward agent";
+/// Confirmation action shown before adding the managed file `Include` to the main configuration
+pub const MANAGED_INCLUDE_ACTION: &str = "Add Include";
+/// Confirmation action shown with the important banner of a host, before connecting
+pub const BANNER_ACTION: &str = "Connect";
+/// Confirmation action shown when editing a host declared in a system configuration file
+pub const SYSTEM_EDIT_ACTION: &str = "Override";
+/// Confirmation action of the expired hosts report, removing them all
+pub const EXPIRED_CLEANUP_ACTION: &str = "Remove all";
+pub const BULK_TAGS_ACTION: &str = "Apply tags";
+pub const UNUSED_HOSTS_ACTION: &str = "Tag stale";
+
+/// Maximum number of expired hosts listed in the cleanup report
+const REPORT_MAX_HOSTS: usize = 8;
+
+/// Minimum time the connection screen stays up when it shows a banner
+const BANNER_DELAY: Duration = Duration::from_millis(2500);
+
+/// How long a host deleted with `dd` can be brought back with `u`
+pub const UNDO_DELETE_DELAY: Duration = Duration::from_secs(10);
+
+/// Prefix of the lines of a host deleted with `dd`, which stays in the file, commented out
+const DELETED_HOST_PREFIX: &str = "# [deleted] ";
+
+/// Prefix of the lines of the hosts commented out from the unused hosts report
+const UNUSED_HOST_PREFIX: &str = "# [unused] ";
+
+/// Hosts from the top of the list whose destinations are resolved in advance, a screen or two
+const DNS_PREFETCH_HOSTS: usize = 50;
+
+/// How often the list is redrawn while destinations are being resolved
+const DNS_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the answer of the update check is looked for while it runs
+const UPDATE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often the footer shows the progress of a file transfer
+const TRANSFER_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How often the results of a batch run are updated
+const BATCH_RUN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How often the screen of the shown tab is redrawn
+const SESSION_POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// Time without a resize of the terminal after which the sessions are resized, so that a
+/// window being dragged resizes them once rather than on every step
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(50);
+/// How often the sessions of the other tabs are checked for their end
+const SESSION_EXIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Why sessions cannot be opened in safe mode
+const NO_EXEC_SESSIONS: &str = "Sessions are disabled with --no-exec";
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FocusState {
@@ -52,15 +154,79 @@ pub enum FocusState {
 #[derive(Clone, Debug)]
 pub struct AppConfig {
     pub config_paths: Vec<String>,
+    pub state_path: String,
+    /// Key of the encrypted state, unlocked before the interface starts (`--vault`)
+    pub vault: Option<Vault>,
+    /// Dedicated file for hosts created by sshs, included from the main configuration
+    pub managed_config_path: Option<String>,
 
     pub search_filter: Option<String>,
+    /// Connect right away, without the host list, when the search filter leaves a single host
+    pub connect_if_unique: bool,
     pub sort_by_name: bool,
     pub show_proxy_command: bool,
+    /// Whether `--sort` and `--show-proxy-command` were given on the command line, winning over
+    /// the saved settings for the run
+    pub sort_given: bool,
+    pub show_proxy_command_given: bool,
 
     pub command_template: String,
     pub command_template_on_session_start: Option<String>,
     pub command_template_on_session_end: Option<String>,
+    /// Run when the connection fails, before the session end template
+    pub command_template_on_connection_failure: Option<String>,
+    pub command_template_on_socks: Option<String>,
     pub exit_after_ssh_session_ends: bool,
+
+    pub socks_port: u16,
+
+    /// User for hosts without a `User` entry, the local account when `None`
+    pub default_user: Option<String>,
+
+    /// Dotfiles pushed to opted-in hosts before opening the shell
+    pub dotfiles: Vec<String>,
+
+    /// Team-shared host metadata, merged under the metadata of the SSH configuration
+    pub shared_metadata_path: Option<String>,
+
+    /// How long the connection screen stays up, zero to skip it
+    pub connect_delay: Duration,
+    /// How long the session summary stays up, until a key is pressed when `None`
+    pub end_screen_delay: Option<Duration>,
+    /// How the session tabs are named
+    pub tab_naming: TabNaming,
+    /// Workspace whose tabs are opened at startup
+    pub workspace: Option<String>,
+    /// File the usage statistics are exported to, none are kept without it
+    pub stats_file: Option<String>,
+    /// Append-only log of the configuration changes, none is kept without it
+    pub audit_log: Option<String>,
+    /// Safe mode: connecting copies the `ssh` command instead of running it, and sessions are
+    /// disabled. The processes themselves are refused by [`crate::exec`].
+    pub no_exec: bool,
+    /// Resolve the destinations of the listed hosts in the background
+    pub dns_prefetch: bool,
+    /// Time between two checks of the reachability of the hosts, only checked on request
+    /// when `None`
+    pub health_check_interval: Option<Duration>,
+    /// Months without a connection after which a host is reported as unused
+    pub unused_after_months: u32,
+    /// Key starting the leader sequences
+    pub leader: char,
+    /// Display settings of the configuration file, over the ones saved from the settings panel
+    pub display: config_file::Display,
+    /// Keys of the host list actions
+    pub keymap: Keymap,
+    /// Record the sessions of the hosts without `# sshs:log=no`
+    pub log_sessions: bool,
+    /// Where the session logs go, one directory per host
+    pub session_log_dir: String,
+    /// What the terminal can display, with fallbacks for the rest
+    pub capabilities: Capabilities,
+    /// Triggers on what every session prints, from the configuration file
+    pub triggers: Vec<Trigger>,
+    /// How sshs connects to the hosts (`--backend`)
+    pub backend: BackendKind,
 }
 
 pub struct App {
@@ -86,14 +252,136 @@ pub struct App {
     // Confirmation dialog
     pub confirm_message: Option<String>,
     pub confirm_action: Option<String>,
+    /// Action waiting for a confirmation, run by the main loop once confirmed
+    pub pending_action: Option<PendingAction>,
+
+    // Persistent state
+    pub state: State,
 
     // Vim-like navigation
     pub focus_state: FocusState,
     pub last_key_time: Option<Instant>,
     pub pending_g: bool, // For detecting "gg" sequence
+    pub pending_d: bool, // For detecting "dd" sequence, when quick delete is on
+    /// Keys typed after the leader, `None` outside of a leader sequence
+    pub leader_keys: Option<String>,
 
     // Tab management
     pub tab_manager: TabManager,
+    /// The screen of the current tab is shown instead of the hosts, and gets the keys
+    pub session_shown: bool,
+    /// Size (rows, columns) the sessions of the tabs were last given
+    pub session_size: Option<(u16, u16)>,
+    /// When the terminal was last resized, the sessions follow once it stops
+    pub resized_at: Option<Instant>,
+    /// Text with newlines pasted into the current tab, sent once confirmed
+    pub pending_paste: Option<String>,
+
+    /// Runs the SSH sessions and remote commands
+    pub backend: Box<dyn ConnectionBackend>,
+
+    // Menu bar (F10), `None` when closed
+    pub menu: Option<MenuState>,
+
+    // Single-line text prompt, `None` when closed
+    pub prompt: Option<Prompt>,
+
+    // Host to connect to once its jump host is picked in the list, `None` when not picking
+    pub jump_target: Option<String>,
+
+    // Configuration backups view, `None` when closed
+    pub backups: Option<BackupsView>,
+    // Audit log view, `None` when closed
+    pub audit_log: Option<AuditLogView>,
+    // Timeline of a host, `None` when closed
+    pub timeline: Option<TimelineView>,
+    // Tunnels running as systemd user units, `None` when closed
+    pub background_tunnels: Option<TunnelsView>,
+    // SOCKS proxies running as systemd user units, as last seen, shown in the status bar
+    pub socks_units: BTreeSet<String>,
+    // SFTP browser of a host, `None` when closed
+    pub sftp: Option<SftpView>,
+    // File copied by `sftp` in the background, shown in the footer
+    pub transfer: Option<Transfer>,
+    // Network followed to connect to the fallback of the hosts it cannot reach, `None` when no
+    // host has a fallback
+    pub network: Option<NetworkWatch>,
+    // Hosts marked with Space or a visual range (v), by name, the targets of a batch run
+    pub marked_hosts: BTreeSet<String>,
+    // Row where the visual range started, `None` outside of the visual mode
+    pub visual_anchor: Option<usize>,
+    // Command run on the marked hosts, and its results pane (`None` when closed)
+    pub batch_run: Option<BatchRun>,
+    // Problems found while loading the SSH configuration, `None` when closed
+    pub config_errors: Option<ConfigErrorsView>,
+
+    // Display preferences, and their panel (`None` when closed)
+    pub settings: Settings,
+    pub settings_panel: Option<SettingsPanel>,
+    // Groups only showing their first host while nothing is searched
+    pub collapsed_groups: BTreeSet<String>,
+    // Hosts sorted by their last connection, with a Last used column, instead of grouped
+    pub recent_view: bool,
+    // Only the hosts in this state are listed, among those matching the search
+    pub state_filter: StateFilter,
+    // Hosts matching the search in each state, shown in the search bar
+    pub state_counts: StateCounts,
+    // Incident mode, with what it changed
+    pub incident: Option<Incident>,
+    // Only the names and tags of the hosts are shown, for screen sharing
+    pub presentation: bool,
+
+    // Remote authorized_keys view, `None` when closed
+    pub authorized_keys: Option<AuthorizedKeysView>,
+
+    // Host deleted with `dd`, until its undo toast expires
+    pub deleted_host: Option<DeletedHost>,
+
+    // Details of the selected host, and the option whose origin can be edited
+    pub show_details: bool,
+    pub details_option: usize,
+    /// Whether the description of the selected option is shown (`?`)
+    pub details_help: bool,
+    /// Certificates of the hosts, by path (or why they cannot be read)
+    pub certificates: BTreeMap<String, Result<Certificate, String>>,
+    /// Addresses of the host destinations, `None` without `--dns-prefetch`
+    pub dns: Option<DnsCache>,
+    /// Reachability of the hosts, `None` (and no column) until checked once
+    pub health: Option<HealthChecker>,
+    /// Tags edit previewed, applied to the listed hosts once confirmed
+    pub tag_edit: Option<TagEdit>,
+    /// Steps of the walkthrough, with `sshs tutorial`
+    pub tutorial: Option<Tutorial>,
+    /// Check for a new release running in the background
+    pub update_checker: Option<UpdateChecker>,
+    /// Newer release found by the update check, shown as a badge in the footer
+    pub available_update: Option<Release>,
+    /// Whether the changelog of the available update is shown
+    pub show_changelog: bool,
+}
+
+/// Work deferred from a dialog that needs the terminal, run by the event loop once the current
+/// key has been handled.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PendingAction {
+    Connect(ssh::ConnectOptions),
+    /// Edit a root-owned configuration file with `sudoedit`
+    SudoEdit(PathBuf),
+    /// Open a configuration problem in `$EDITOR`
+    EditConfig(ConfigProblem),
+    /// Open the line declaring an option of the host details in `$EDITOR`
+    EditOption(PathBuf, usize),
+}
+
+/// A host commented out with `dd`, restored by `u` while its toast is shown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeletedHost {
+    pub name: String,
+    pub config_path: String,
+    /// Content of the configuration file before and after the deletion
+    pub previous_content: String,
+    pub content: String,
+    pub deleted_at: Instant,
 }
 
 #[derive(PartialEq, Debug)]
@@ -107,36 +395,53 @@ pub enum AppKeyAction {
 impl App {
     /// # Errors
     ///
-    /// Will return `Err` if the SSH configuration file cannot be parsed.
+    /// Will return `Err` if no host at all could be loaded from the SSH configuration files
+    /// because of parse errors.
     pub fn new(config: &AppConfig) -> Result<App> {
-        let mut hosts = Vec::new();
+        let state = match &config.vault {
+            Some(vault) => State::load_vault(&config.state_path, vault.clone())?,
+            None => State::load(&config.state_path)?,
+        };
+        // `--search` names a saved search, or is the search itself
+        let search_input = config
+            .search_filter
+            .as_deref()
+            .map(|search| state.saved_search(search).unwrap_or(search).to_string())
+            .unwrap_or_default();
+        if let Some(path) = &config.audit_log {
+            audit_log::check(path)?;
+        }
 
-        for path in &config.config_paths {
-            let parsed_hosts = match ssh::parse_config(path) {
-                Ok(hosts) => hosts,
-                Err(err) => {
-                    if path == "/etc/ssh/ssh_config" {
-                        if let ssh::ParseConfigError::Io(io_err) = &err {
-                            // Ignore missing system-wide SSH configuration file
-                            if io_err.kind() == std::io::ErrorKind::NotFound {
-                                continue;
-                            }
-                        }
-                    }
+        // Settings saved from the settings panel take precedence over the configuration file,
+        // then the default flags. The flags given on the command line win over all of them
+        let mut settings = config
+            .display
+            .apply(state.settings.clone().unwrap_or_else(|| {
+                Settings::from_flags(config.sort_by_name, config.show_proxy_command)
+            }));
+        settings.apply_flags(
+            config.sort_given.then_some(config.sort_by_name),
+            config
+                .show_proxy_command_given
+                .then_some(config.show_proxy_command),
+        );
 
-                    anyhow::bail!("Failed to parse SSH configuration file '{}': {}", path, err);
-                }
-            };
+        let (mut hosts, config_problems) =
+            load_config_hosts(&config.config_paths, settings.skip_system_config)?;
 
-            hosts.extend(parsed_hosts);
+        if let Some(path) = &config.shared_metadata_path {
+            SharedMetadata::load(path)?.apply(&mut hosts);
         }
-
-        if config.sort_by_name {
-            hosts.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        if settings.fact_tags {
+            facts::apply(&mut hosts, &state.host_facts);
         }
+        let mut hosts = settings.with_alias_rows(hosts);
+        settings.sort_hosts(&mut hosts);
 
-        let search_input = config.search_filter.clone().unwrap_or_default();
-        let matcher = SkimMatcherV2::default();
+        let mut tab_manager =
+            TabManager::with_naming(config.tab_naming.clone(), state.tab_names.clone());
+        tab_manager.set_log_sessions(config.log_sessions);
+        let network = NetworkWatch::start(&hosts);
 
         let mut app = App {
             config: config.clone(),
@@ -145,19 +450,12 @@ impl App {
 
             table_state: TableState::default().with_selected(0),
             table_columns_constraints: Vec::new(),
-            palette: tailwind::BLUE,
+            palette: settings.theme.palette(),
 
             hosts: Searchable::new(
                 hosts,
                 &search_input,
-                move |host: &&ssh::Host, search_value: &str| -> bool {
-                    search_value.is_empty()
-                        || matcher.fuzzy_match(&host.name, search_value).is_some()
-                        || matcher
-                            .fuzzy_match(&host.destination, search_value)
-                            .is_some()
-                        || matcher.fuzzy_match(&host.aliases, search_value).is_some()
-                },
+                host_search_predicate(settings.matcher),
             ),
 
             add_host_form: None,
@@ -170,14 +468,76 @@ impl App {
 
             confirm_message: None,
             confirm_action: None,
+            pending_action: None,
+
+            state,
 
             focus_state: FocusState::Normal,
             last_key_time: None,
             pending_g: false,
-
-            tab_manager: TabManager::new(),
+            pending_d: false,
+            leader_keys: None,
+
+            tab_manager,
+            session_shown: false,
+            session_size: None,
+            resized_at: None,
+            pending_paste: None,
+            backend: config.backend.create()?,
+
+            menu: None,
+
+            prompt: None,
+            jump_target: None,
+            backups: None,
+            audit_log: None,
+            timeline: None,
+            background_tunnels: None,
+            socks_units: BTreeSet::new(),
+            sftp: None,
+            transfer: None,
+            network,
+            marked_hosts: BTreeSet::new(),
+            visual_anchor: None,
+            batch_run: None,
+            config_errors: (!config_problems.is_empty())
+                .then(|| ConfigErrorsView::new(config_problems)),
+
+            settings,
+            settings_panel: None,
+            collapsed_groups: BTreeSet::new(),
+            recent_view: false,
+            state_filter: StateFilter::All,
+            state_counts: StateCounts::default(),
+            incident: None,
+            presentation: false,
+
+            authorized_keys: None,
+
+            deleted_host: None,
+            show_details: false,
+            details_option: 0,
+            details_help: false,
+            certificates: BTreeMap::new(),
+            dns: config.dns_prefetch.then(DnsCache::default),
+            health: config
+                .health_check_interval
+                .map(|interval| HealthChecker::new(Some(interval))),
+            tag_edit: None,
+            tutorial: None,
+            update_checker: None,
+            available_update: None,
+            show_changelog: false,
         };
+        app.inspect_certificates();
         app.calculate_table_columns_constraints();
+        if app.state.start_recording_connections(ssh::today()) {
+            app.state.mark_changed();
+        }
+
+        if let Some(name) = &config.workspace {
+            app.open_workspace(name)?;
+        }
 
         Ok(app)
     }
@@ -191,11 +551,16 @@ impl App {
         let terminal = Rc::new(RefCell::new(Terminal::new(backend)?));
 
         // Set up terminal
-        safe_setup_terminal(&terminal)?;
+        safe_setup_terminal(&terminal, self.config.capabilities.mouse)?;
 
         // Run the application with appropriate error handling
         let res = self.run(&terminal);
 
+        // Write the state changes still waiting for their batch
+        if let Err(e) = self.state.flush() {
+            eprintln!("Warning: Failed to save state: {e}");
+        }
+
         // Ensure we always restore the terminal state
         let restore_result = safe_restore_terminal(&terminal);
 
@@ -220,87 +585,254 @@ impl App {
     where
         B: Backend + std::io::Write,
     {
+        if self.connects_at_startup()
+            && self.request_connection(terminal, ssh::ConnectOptions::default())?
+                == AppKeyAction::Stop
+        {
+            return Ok(());
+        }
+
+        self.start_update_check();
+
         loop {
             // Check if feedback message should be cleared due to timeout
             self.check_feedback_timeout();
+            self.update_tutorial();
+            self.flush_state_if_due();
+            self.prefetch_dns();
+            self.poll_update_check();
+            self.poll_transfer();
+            self.poll_batch_run();
+            self.poll_network();
+            self.poll_health();
+            let size = terminal.borrow().size()?;
+            let area = super::render::session_area(self, Rect::new(0, 0, size.width, size.height));
+            self.resize_sessions(area.height, area.width);
+            if self.poll_sessions(area.height, area.width) {
+                let mut terminal = terminal.borrow_mut();
+                std::io::Write::write_all(terminal.backend_mut(), b"\x07")?;
+                std::io::Write::flush(terminal.backend_mut())?;
+            }
 
             terminal.borrow_mut().draw(|f| super::render::ui(f, self))?;
 
+            // Wake up to write the pending state changes, or to show the resolved addresses,
+            // even when no key is pressed
+            let resolving = self.dns.as_ref().is_some_and(DnsCache::is_busy);
+            let wake_in = [
+                self.state.save_due_in(),
+                resolving.then_some(DNS_POLL_INTERVAL),
+                self.update_checker
+                    .is_some()
+                    .then_some(UPDATE_POLL_INTERVAL),
+                self.transfer.is_some().then_some(TRANSFER_POLL_INTERVAL),
+                self.batch_run
+                    .as_ref()
+                    .is_some_and(BatchRun::is_running)
+                    .then_some(BATCH_RUN_POLL_INTERVAL),
+                self.network
+                    .as_ref()
+                    .map(|network| network.wake_in(Instant::now())),
+                self.health
+                    .as_ref()
+                    .and_then(|health| health.wake_in(Instant::now())),
+                self.resized_at
+                    .map(|resized_at| RESIZE_DEBOUNCE.saturating_sub(resized_at.elapsed())),
+                self.tab_manager
+                    .has_sessions()
+                    .then_some(if self.session_shown {
+                        SESSION_POLL_INTERVAL
+                    } else {
+                        SESSION_EXIT_POLL_INTERVAL
+                    }),
+            ];
+            if let Some(delay) = wake_in.into_iter().flatten().min() {
+                if !event::poll(delay)? {
+                    continue;
+                }
+            }
+
             let ev = event::read()?;
+            if self.handle_event(terminal, &ev)? {
+                break;
+            }
+        }
 
-            if let Event::Key(key) = ev {
-                if key.kind == KeyEventKind::Press {
-                    match self.form_state {
-                        FormState::Hidden => {
-                            let action = self.on_key_press(terminal, key)?;
-                            match action {
-                                AppKeyAction::Stop => break,
-                                AppKeyAction::Ok | AppKeyAction::Confirm => continue, // Should not happen in this state
-                                AppKeyAction::Continue => {}
-                            }
-                        }
-                        FormState::Active | FormState::Confirming => {
-                            let action = self.on_form_key_press(key)?;
-                            match action {
-                                AppKeyAction::Stop => {
-                                    self.form_state = FormState::Hidden;
-                                    self.add_host_form = None;
-                                    self.confirm_message = None;
-                                    self.confirm_action = None;
-                                    self.is_edit_mode = false;
-                                    self.editing_host_index = None;
-                                    continue;
-                                }
-                                AppKeyAction::Ok | AppKeyAction::Confirm => continue,
-                                AppKeyAction::Continue => {}
-                            }
-                        }
+        Ok(())
+    }
+
+    /// Starts the daily check for a new release, when turned on in the settings. The result of
+    /// a check made today is used instead.
+    pub fn start_update_check(&mut self) {
+        if !self.settings.check_updates || self.config.no_exec {
+            self.available_update = None;
+            return;
+        }
+        if self.update_checker.is_some() {
+            return;
+        }
+
+        match &self.state.update_check {
+            Some(check) if check.day == ssh::today() => {
+                self.available_update = check.latest.clone().filter(|release| {
+                    updates::is_newer(&release.version, env!("CARGO_PKG_VERSION"))
+                });
+            }
+            _ => self.update_checker = Some(UpdateChecker::start()),
+        }
+    }
+
+    /// Records the result of the update check once it is done. A failed check is not reported,
+    /// it runs again the next day.
+    fn poll_update_check(&mut self) {
+        let Some(result) = self.update_checker.as_ref().and_then(UpdateChecker::poll) else {
+            return;
+        };
+        self.update_checker = None;
+
+        let latest = match result {
+            Ok(release) => Some(release),
+            Err(_) => self
+                .state
+                .update_check
+                .as_ref()
+                .and_then(|check| check.latest.clone()),
+        };
+        self.state.update_check = Some(UpdateCheck {
+            day: ssh::today(),
+            latest,
+        });
+        self.state.mark_changed();
+        self.start_update_check();
+    }
+
+    /// Looks up the destinations of the hosts on screen, and a few more, in the background.
+    /// Called on every redraw: the lookups of hosts filtered out or scrolled away are dropped.
+    fn prefetch_dns(&mut self) {
+        let Some(dns) = &mut self.dns else {
+            return;
+        };
+
+        // Hosts reached through a proxy are resolved on the other side
+        let destinations = self
+            .hosts
+            .iter()
+            .skip(self.table_state.offset())
+            .take(DNS_PREFETCH_HOSTS)
+            .filter(|host| host.proxy_command.is_none() && !host.is_template())
+            .map(|host| ssh::unbracket(&host.destination))
+            .filter(|destination| destination.parse::<std::net::IpAddr>().is_err());
+        dns.prefetch(destinations, Instant::now());
+        dns.poll();
+    }
+
+    /// Whether `--connect-if-unique` applies: the search filter left exactly one host.
+    fn connects_at_startup(&self) -> bool {
+        self.config.connect_if_unique
+            && self.config.search_filter.is_some()
+            && self.hosts.len() == 1
+    }
+
+    /// Handles a single terminal event. Returns `true` when the application should stop.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the event triggers an action that fails (e.g. a connection).
+    pub fn handle_event<B>(
+        &mut self,
+        terminal: &Rc<RefCell<Terminal<B>>>,
+        ev: &Event,
+    ) -> Result<bool>
+    where
+        B: Backend + std::io::Write,
+    {
+        if let Event::Mouse(mouse) = *ev {
+            return self.handle_mouse(terminal, mouse);
+        }
+        if let Event::Resize(..) = ev {
+            self.resized_at = Some(Instant::now());
+            return Ok(false);
+        }
+        if let Event::Paste(text) = ev {
+            return self.handle_paste(terminal, text);
+        }
+        let Event::Key(key) = *ev else {
+            return Ok(false);
+        };
+
+        if key.kind == KeyEventKind::Press {
+            match self.form_state {
+                FormState::Hidden => {
+                    let action = self.on_key_press(terminal, key)?;
+                    match action {
+                        AppKeyAction::Stop => return Ok(true),
+                        AppKeyAction::Ok | AppKeyAction::Confirm => return Ok(false), // Should not happen in this state
+                        AppKeyAction::Continue => {}
                     }
                 }
+                FormState::Active | FormState::Confirming => {
+                    let action = self.on_form_key_press(key)?;
 
-                match self.form_state {
-                    FormState::Hidden => {
-                        // Handle search input only in Search mode
-                        // But handle mode transitions FIRST before passing events to search input
-                        if self.focus_state == FocusState::Search {
-                            // Check for mode-changing keys first
-                            if let Event::Key(key) = &ev {
-                                match key.code {
-                                    KeyCode::Esc | KeyCode::Enter => {
-                                        // Handle mode transition, don't pass to search input
-                                        // This will be handled in the key press handler below
-                                    }
-                                    _ => {
-                                        // For all other keys, let search input handle them
-                                        self.search.handle_event(&ev);
-                                        self.hosts.search(self.search.value());
-
-                                        let selected = self.table_state.selected().unwrap_or(0);
-                                        if selected >= self.hosts.len() {
-                                            self.table_state.select(Some(match self.hosts.len() {
-                                                0 => 0,
-                                                _ => self.hosts.len() - 1,
-                                            }));
-                                        }
-                                    }
-                                }
-                            }
-                        }
+                    if let Some(pending) = self.pending_action.take() {
+                        return self.run_pending_action(terminal, pending);
                     }
-                    FormState::Active => {
-                        if let Some(form) = &mut self.add_host_form {
-                            form.handle_event(&ev);
+
+                    match action {
+                        AppKeyAction::Stop => {
+                            self.form_state = FormState::Hidden;
+                            self.add_host_form = None;
+                            self.confirm_message = None;
+                            self.confirm_action = None;
+                            self.is_edit_mode = false;
+                            self.editing_host_index = None;
+                            return Ok(false);
                         }
+                        AppKeyAction::Ok | AppKeyAction::Confirm => return Ok(false),
+                        AppKeyAction::Continue => {}
                     }
-                    FormState::Confirming => {
-                        // Don't handle regular events in confirmation mode
-                        // Only key presses are handled
+                }
+            }
+        }
+
+        match self.form_state {
+            FormState::Hidden => {
+                // Handle search input only in Search mode
+                // But handle mode transitions FIRST before passing events to search input
+                if self.focus_state == FocusState::Search {
+                    // Check for mode-changing keys first
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Enter => {
+                            // Handle mode transition, don't pass to search input
+                            // This will be handled in the key press handler below
+                        }
+                        _ => {
+                            // For all other keys, let search input handle them
+                            self.search.handle_event(ev);
+                            self.refresh_search();
+
+                            let selected = self.table_state.selected().unwrap_or(0);
+                            if selected >= self.hosts.len() {
+                                self.table_state.select(Some(match self.hosts.len() {
+                                    0 => 0,
+                                    _ => self.hosts.len() - 1,
+                                }));
+                            }
+                        }
                     }
                 }
             }
+            FormState::Active => {
+                if let Some(form) = &mut self.add_host_form {
+                    form.handle_event(ev);
+                }
+            }
+            FormState::Confirming => {
+                // Don't handle regular events in confirmation mode
+                // Only key presses are handled
+            }
         }
 
-        Ok(())
+        Ok(false)
     }
 
     fn on_key_press<B>(
@@ -311,6 +843,63 @@ impl App {
     where
         B: Backend + std::io::Write,
     {
+        // Prompts and the menu bar grab every key while they are open
+        if self.prompt.is_some() {
+            return self.handle_prompt_keys(terminal, key);
+        }
+        if self.menu.is_some() {
+            return self.handle_menu_keys(terminal, key);
+        }
+        if self.pending_paste.is_some() {
+            return Ok(self.handle_paste_keys(key));
+        }
+        if self.session_shown {
+            return Ok(self.handle_session_keys(key));
+        }
+        if self.config_errors.is_some() {
+            return Ok(self.handle_config_errors_keys(key));
+        }
+        if self.backups.is_some() {
+            return self.handle_backups_keys(key);
+        }
+        if self.audit_log.is_some() {
+            return Ok(self.handle_audit_log_keys(key));
+        }
+        if self.show_changelog {
+            if matches!(
+                key.code,
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q' | 'V')
+            ) {
+                self.show_changelog = false;
+            }
+            return Ok(AppKeyAction::Ok);
+        }
+        if self.timeline.is_some() {
+            return Ok(self.handle_timeline_keys(key));
+        }
+        if self.background_tunnels.is_some() {
+            return Ok(self.handle_background_tunnels_keys(key));
+        }
+        if self.sftp.is_some() {
+            return Ok(self.handle_sftp_keys(key));
+        }
+        if self.batch_run.is_some() {
+            return Ok(self.handle_batch_run_keys(key));
+        }
+        if self.settings_panel.is_some() {
+            return self.handle_settings_keys(key);
+        }
+        if self.authorized_keys.is_some() {
+            return Ok(self.handle_authorized_keys_keys(key));
+        }
+        if self.show_details {
+            return Ok(self.handle_details_keys(key));
+        }
+        if key.code == KeyCode::F(10) {
+            self.menu = Some(MenuState::default());
+            return Ok(AppKeyAction::Ok);
+        }
+
         let is_ctrl_pressed = key.modifiers.contains(KeyModifiers::CONTROL);
 
         // Handle global Ctrl shortcuts first
@@ -339,27 +928,71 @@ impl App {
         #[allow(clippy::enum_glob_use)]
         use KeyCode::*;
 
-        // Check for timeout on pending 'g' key
-        if self.pending_g {
+        // Picking the jump host: the list works as usual, Enter picks and Esc cancels
+        if let Some(target) = self.jump_target.clone() {
+            match key.code {
+                Enter => {
+                    self.jump_target = None;
+                    return self.connect_through_selected_jump_host(terminal, &target);
+                }
+                Esc => {
+                    self.jump_target = None;
+                    return Ok(AppKeyAction::Ok);
+                }
+                _ => {}
+            }
+        }
+
+        if self.leader_keys.is_some() {
+            return self.handle_leader_keys(terminal, key);
+        }
+        if key.code == Char(self.config.leader) {
+            self.leader_keys = Some(String::new());
+            self.pending_g = false;
+            self.pending_d = false;
+            self.last_key_time = None;
+            return Ok(AppKeyAction::Ok);
+        }
+
+        // Check for timeout on pending 'g' or 'd' key
+        if self.pending_g || self.pending_d {
             if let Some(last_time) = self.last_key_time {
                 if last_time.elapsed() > Duration::from_millis(1000) {
                     self.pending_g = false;
+                    self.pending_d = false;
                     self.last_key_time = None;
                 }
             }
         }
 
-        match key.code {
-            // Quit application with 'q' (Vim-like)
-            Char('q') => return Ok(AppKeyAction::Stop),
+        // The actions of the keymap, `[keys.actions]` in the configuration file
+        let action = match key.code {
+            Char(c) => self.config.keymap.action(c),
+            _ => None,
+        };
+        match (action, key.code) {
+            (Some(Action::DeleteHost), _) if self.settings.quick_delete => {
+                if self.pending_d {
+                    // Second press - delete without asking, `u` brings the host back
+                    self.pending_d = false;
+                    self.last_key_time = None;
+                    if let Err(e) = self.soft_delete_selected_host() {
+                        self.set_feedback_message(format!("Error: {e}"), true);
+                    }
+                } else {
+                    self.pending_d = true;
+                    self.last_key_time = Some(Instant::now());
+                }
+            }
+            (Some(action), _) => return self.perform_action(terminal, action),
 
-            Char('h' | 'l') => {} // Reserved for future horizontal navigation
+            (None, Char('h' | 'l')) => {} // Reserved for future horizontal navigation
 
             // Jump to extremes
-            Char('G') | End => self
+            (None, Char('G') | End) => self
                 .table_state
                 .select(Some(self.hosts.len().saturating_sub(1))),
-            Char('g') => {
+            (None, Char('g')) => {
                 if self.pending_g {
                     // Second 'g' - jump to top
                     self.table_state.select(Some(0));
@@ -372,30 +1005,28 @@ impl App {
                 }
             }
 
-            // Search mode transitions
-            Char('/') => {
-                self.focus_state = FocusState::Search;
-                // Clear search to start fresh
-                self.search = Input::default();
-                self.hosts.search("");
-            }
-
-            // Host management (single key - more Vim-like)
-            Char('n') => {
-                self.open_add_host_form();
-            }
-            Char('e') => {
-                self.open_edit_host_form();
+            (None, Char('u')) => self.undo_delete(),
+            (None, Char(' ')) => self.toggle_mark(),
+            (None, Char('v')) => self.toggle_visual_range(),
+            (None, Esc) if self.visual_anchor.is_some() => self.visual_anchor = None,
+            (None, Esc) if !self.marked_hosts.is_empty() => {
+                self.marked_hosts.clear();
+                self.set_feedback_message("Cleared the marks".to_string(), false);
             }
-            Char('d') => {
-                self.open_delete_host_confirmation();
+            (None, Char(digit @ '1'..='9')) => {
+                // Saved searches in name order
+                let index = usize::from(digit as u8 - b'1');
+                match self.state.saved_searches.keys().nth(index).cloned() {
+                    Some(name) => self.apply_saved_search(&name),
+                    None => self.set_feedback_message(format!("No saved search {digit}"), true),
+                }
             }
 
             // Navigation keys - vim and traditional combined
-            Char('j') | Down | Tab => self.next(),
-            Char('k') | Up | BackTab => self.previous(),
-            Home => self.table_state.select(Some(0)),
-            PageDown => {
+            (None, Char('j') | Down | Tab) => self.next(),
+            (None, Char('k') | Up | BackTab) => self.previous(),
+            (None, Home) => self.table_state.select(Some(0)),
+            (None, PageDown) => {
                 let i = self.table_state.selected().unwrap_or(0);
                 let target = min(
                     i.saturating_add(PAGE_SIZE),
@@ -403,480 +1034,3894 @@ impl App {
                 );
                 self.table_state.select(Some(target));
             }
-            PageUp => {
+            (None, PageUp) => {
                 let i = self.table_state.selected().unwrap_or(0);
                 let target = max(i.saturating_sub(PAGE_SIZE), 0);
                 self.table_state.select(Some(target));
             }
 
             // Connect to host
-            Enter => {
-                return self.connect_to_selected_host(terminal);
-            }
+            (None, Enter) => return self.perform_action(terminal, Action::Connect),
 
             _ => return Ok(AppKeyAction::Continue),
         }
 
-        // Clear pending 'g' for any other key
+        // Clear pending 'g' or 'd' for any other key
         if !matches!(key.code, Char('g')) {
             self.pending_g = false;
+        }
+        if action != Some(Action::DeleteHost) {
+            self.pending_d = false;
+        }
+        if !self.pending_g && !self.pending_d {
             self.last_key_time = None;
         }
 
         Ok(AppKeyAction::Ok)
     }
 
-    fn handle_search_mode_keys(&mut self, key: KeyEvent) -> AppKeyAction {
-        #[allow(clippy::enum_glob_use)]
-        use KeyCode::*;
+    /// Keys following the leader: the key of a group, then the key of one of its actions, as
+    /// listed by the popup. Backspace goes back to the groups, any other key cancels.
+    fn handle_leader_keys<B>(
+        &mut self,
+        terminal: &Rc<RefCell<Terminal<B>>>,
+        key: KeyEvent,
+    ) -> Result<AppKeyAction>
+    where
+        B: Backend + std::io::Write,
+    {
+        let mut typed = self.leader_keys.take().unwrap_or_default();
+        let KeyCode::Char(c) = key.code else {
+            if key.code == KeyCode::Backspace && typed.pop().is_some() {
+                self.leader_keys = Some(typed);
+            }
+            return Ok(AppKeyAction::Ok);
+        };
 
-        match key.code {
-            Esc => {
-                // Exit search mode, return to normal mode
-                self.focus_state = FocusState::Normal;
-                // Clear search text and show all hosts
-                self.search = Input::default();
-                self.hosts.search("");
-                // Focus on first host
-                if !self.hosts.is_empty() {
-                    self.table_state.select(Some(0));
+        let sequence = format!("{}{typed}{c}", self.config.leader);
+        match typed.chars().next().and_then(LeaderGroup::find) {
+            None => match LeaderGroup::find(c) {
+                Some(_) => self.leader_keys = Some(c.to_string()),
+                None => self.set_feedback_message(format!("Nothing on {sequence}"), true),
+            },
+            Some(group) => match group.action(c) {
+                Some(action) => return self.perform_action(terminal, action),
+                None => self.set_feedback_message(format!("Nothing on {sequence}"), true),
+            },
+        }
+
+        Ok(AppKeyAction::Ok)
+    }
+
+    /// Runs an action from the central registry, whether it comes from a key or the menu bar.
+    fn perform_action<B>(
+        &mut self,
+        terminal: &Rc<RefCell<Terminal<B>>>,
+        action: Action,
+    ) -> Result<AppKeyAction>
+    where
+        B: Backend + std::io::Write,
+    {
+        // Any action interrupts a pending "gg" or "dd" sequence
+        self.pending_g = false;
+        self.pending_d = false;
+        self.last_key_time = None;
+
+        match action {
+            Action::Connect => {
+                return self.request_connection(terminal, ssh::ConnectOptions::default());
+            }
+            Action::ConnectSocks => {
+                // Connect with an ad-hoc SOCKS proxy (dynamic forward)
+                let options = ssh::ConnectOptions {
+                    dynamic_forward: Some(self.config.socks_port),
+                    ..Default::default()
+                };
+                return self.request_connection(terminal, options);
+            }
+            Action::ConnectAgent => {
+                // Connect with the local SSH agent forwarded
+                let options = ssh::ConnectOptions {
+                    forward_agent: true,
+                    ..Default::default()
+                };
+                return self.request_connection(terminal, options);
+            }
+            Action::ConnectWithCommand => {
+                let selected = self.table_state.selected().unwrap_or(0);
+                if selected < self.hosts.len() {
+                    let history = self.state.remote_commands(&self.hosts[selected].name);
+                    self.prompt = Some(Prompt::new(PromptKind::RemoteCommand, history));
                 }
             }
-            Enter => {
-                // Finish search and switch to normal mode with focus on first result
-                self.focus_state = FocusState::Normal;
-                if !self.hosts.is_empty() {
-                    self.table_state.select(Some(0));
+            Action::ConnectJump => {
+                let selected = self.table_state.selected().unwrap_or(0);
+                if selected < self.hosts.len() {
+                    self.jump_target = Some(self.hosts[selected].name.clone());
                 }
             }
-            _ => {
-                // Let the search field handle the input - this is already done in the main loop
-                return AppKeyAction::Continue;
+            Action::BackgroundSocks => self.start_background_socks(),
+            Action::Forward => {
+                if let Some(host) = self.selected_connection_host() {
+                    let history = self.state.forwards(&host.name);
+                    self.prompt = Some(Prompt::new(PromptKind::Forward, history));
+                }
             }
-        }
-
-        AppKeyAction::Ok
-    }
-
-    fn on_key_press_ctrl(&mut self, key: KeyEvent) -> AppKeyAction {
-        #[allow(clippy::enum_glob_use)]
-        use KeyCode::*;
-
-        match key.code {
-            Char('c') => AppKeyAction::Stop,
-            Char('j') => {
-                self.next();
-                AppKeyAction::Ok
+            Action::SavedForwards => self.start_saved_forwards(),
+            Action::AddHost => self.open_add_host_form(),
+            Action::EditHost => self.open_edit_host_form(),
+            Action::DeleteHost => self.open_delete_host_confirmation(),
+            Action::ExpiredHosts => self.open_expired_hosts_report(),
+            Action::UnusedHosts => self.open_unused_hosts_report(),
+            Action::EditTags => {
+                if self.hosts.is_empty() {
+                    self.set_feedback_message("No host listed".to_string(), false);
+                } else {
+                    self.prompt = Some(Prompt::new(PromptKind::BulkTags, Vec::new()));
+                }
             }
-            Char('f') => {
-                // Ctrl+F to enter search mode (alternative to '/')
+            Action::CopyPublicKey => self.copy_selected_public_key(terminal),
+            Action::CopyConfigBlock => self.copy_selected_config_block(terminal),
+            Action::ReloadHosts => match self.reload_hosts() {
+                Ok(()) => self.set_feedback_message("SSH config reloaded".to_string(), false),
+                Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+            },
+            Action::Backups => self.open_backups_view(),
+            Action::AuditLog => self.open_audit_log_view(),
+            Action::Settings => self.settings_panel = Some(SettingsPanel::default()),
+            Action::HostDetails => {
+                self.show_details = !self.hosts.is_empty();
+                self.details_option = 0;
+                self.details_help = false;
+            }
+            Action::Timeline => self.open_timeline_of_selected_host(),
+            Action::AuthorizedKeys => self.open_authorized_keys_view(),
+            Action::GatherFacts => self.gather_facts(),
+            Action::PortCheck => {
+                let selected = self.table_state.selected().unwrap_or(0);
+                if selected < self.hosts.len() {
+                    let history = self.state.port_check_targets(&self.hosts[selected].name);
+                    self.prompt = Some(Prompt::new(PromptKind::PortCheck, history));
+                }
+            }
+            Action::WebConsole => self.open_web_console_of_selected_host(),
+            Action::OpenLinks => self.open_links_of_selected_host(),
+            Action::HealthCheck => self.check_health(),
+            Action::StateFilter => self.cycle_state_filter(),
+            Action::IncidentMode => self.toggle_incident_mode(),
+            Action::ToggleGroup => self.toggle_selected_group(),
+            Action::RecentHosts => self.toggle_recent_view(),
+            Action::Presentation => self.toggle_presentation(),
+            Action::Search => {
                 self.focus_state = FocusState::Search;
+                // Clear search to start fresh
                 self.search = Input::default();
-                self.hosts.search("");
-                AppKeyAction::Ok
+                self.refresh_search();
             }
-            Char('k' | 'p') => {
-                self.previous();
-                AppKeyAction::Ok
+            Action::SaveSearch => {
+                if self.search.value().trim().is_empty() {
+                    self.set_feedback_message("Type a search to save first (/)".to_string(), true);
+                } else {
+                    let names = self.state.saved_searches.keys().cloned().collect();
+                    self.prompt = Some(Prompt::new(PromptKind::SaveSearch, names));
+                }
             }
-            Char('n') => {
-                // Ctrl+N to open new tab/session
-                self.open_new_session();
-                AppKeyAction::Ok
+            Action::SavedSearches => {
+                if self.state.saved_searches.is_empty() {
+                    self.set_feedback_message("No saved search, save one with M".to_string(), true);
+                } else {
+                    let names = self.state.saved_searches.keys().cloned().collect();
+                    self.prompt = Some(Prompt::new(PromptKind::OpenSearch, names));
+                }
             }
-            Char('1') => {
-                // Ctrl+1 to switch to first tab
-                self.tab_manager.switch_to_session(1);
-                AppKeyAction::Ok
+            Action::NewSession => self.open_new_session(),
+            Action::RenameTab => self.open_rename_tab_prompt(),
+            Action::LockTab => self.toggle_tab_lock(),
+            Action::ExportScrollback => self.open_export_scrollback_prompt(),
+            Action::SaveWorkspace => {
+                if self.tab_manager.has_sessions() {
+                    self.open_workspace_prompt(PromptKind::SaveWorkspace);
+                } else {
+                    self.set_feedback_message("No session to save".to_string(), true);
+                }
             }
-            Char('2') => {
-                // Ctrl+2 to switch to second tab
-                self.tab_manager.switch_to_session(2);
-                AppKeyAction::Ok
+            Action::OpenWorkspace => {
+                if self.tab_manager.has_locked_sessions() {
+                    self.set_feedback_message(
+                        "Unlock the tabs first, opening a workspace closes them".to_string(),
+                        true,
+                    );
+                } else if self.state.workspaces.is_empty() {
+                    self.set_feedback_message("No saved workspace".to_string(), true);
+                } else {
+                    self.open_workspace_prompt(PromptKind::OpenWorkspace);
+                }
+            }
+            Action::BackgroundTunnels => self.open_background_tunnels_view(),
+            Action::BrowseFiles => self.open_sftp_browser(),
+            Action::SessionLog => self.open_session_log(terminal)?,
+            Action::BatchRun => {
+                if self.config.no_exec {
+                    self.set_feedback_message(
+                        "Batch runs are disabled with --no-exec".to_string(),
+                        true,
+                    );
+                } else if !self.hosts.is_empty() {
+                    let kind = PromptKind::BatchCommand(batch_run::Mode::Sequential);
+                    let history = self.state.batch_commands.clone();
+                    self.prompt = Some(Prompt::new(kind, history));
+                }
+            }
+            Action::About => {
+                let version = env!("CARGO_PKG_VERSION");
+                self.set_feedback_message(format!("sshs {version}"), false);
+            }
+            Action::WhatsNew => {
+                if self.available_update.is_some() {
+                    self.show_changelog = true;
+                } else if !self.settings.check_updates {
+                    self.set_feedback_message(
+                        "Turn the update check on in the settings (,)".to_string(),
+                        false,
+                    );
+                } else if self.update_checker.is_some() {
+                    self.set_feedback_message("Checking for updates...".to_string(), false);
+                } else {
+                    let version = env!("CARGO_PKG_VERSION");
+                    self.set_feedback_message(format!("sshs {version} is up to date"), false);
+                }
+            }
+            Action::Quit => return Ok(AppKeyAction::Stop),
+        }
+
+        Ok(AppKeyAction::Ok)
+    }
+
+    /// Gathers the facts of the listed hosts whose cached ones are missing or stale, one host
+    /// after the other, and adds them to the tags when the Fact tags setting is on.
+    fn gather_facts(&mut self) {
+        let now = recent::now();
+        let hosts = self
+            .hosts
+            .iter()
+            .filter(|host| !host.is_template())
+            .filter(|host| {
+                self.state
+                    .host_facts
+                    .get(&host.name)
+                    .is_none_or(|facts| facts.is_stale(now))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if hosts.is_empty() {
+            self.set_feedback_message(
+                format!(
+                    "The facts of the listed hosts are up to date (gathered every {} days)",
+                    facts::FACTS_TTL_SECONDS / 86_400
+                ),
+                false,
+            );
+            return;
+        }
+
+        let mut gathered = 0;
+        let mut errors = Vec::new();
+        for host in &hosts {
+            match facts::gather(self.backend.as_ref(), host, now) {
+                Ok(host_facts) => {
+                    self.state.host_facts.insert(host.name.clone(), host_facts);
+                    gathered += 1;
+                }
+                Err(e) => errors.push(format!("{e:#}")),
+            }
+        }
+        self.state.mark_changed();
+
+        let mut message = format!("Gathered the facts of {gathered} host(s)");
+        if let Some(error) = errors.first() {
+            let _ = write!(message, ", {} failed: {error}", errors.len());
+        }
+        if self.settings.fact_tags {
+            if let Err(e) = self.reload_hosts() {
+                let _ = write!(message, ", reloading the hosts failed: {e}");
+            }
+        } else if gathered > 0 {
+            message.push_str(", turn Fact tags on in the settings (,) to tag the hosts");
+        }
+        self.set_feedback_message(message, !errors.is_empty());
+    }
+
+    /// Checks from the selected host whether `input` (`address:port`) accepts connections, and
+    /// reports it in the feedback message.
+    fn check_port_from_selected_host(&mut self, input: &str) {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            return;
+        }
+        let host = self.hosts[selected].clone();
+
+        let target = match port_check::Target::parse(input) {
+            Ok(target) => target,
+            Err(e) => {
+                self.set_feedback_message(format!("Error: {e}"), true);
+                return;
+            }
+        };
+        self.state
+            .record_port_check_target(&host.name, &target.to_string());
+        self.state.mark_changed();
+
+        let status = port_check::check(self.backend.as_ref(), &host, &target);
+        if let Ok(status @ (PortStatus::Open | PortStatus::Closed)) = status {
+            let reachable = if status == PortStatus::Open {
+                "reachable"
+            } else {
+                "unreachable"
+            };
+            let time = u64::try_from(certificate::now()).unwrap_or_default();
+            if self
+                .state
+                .record_health(&host.name, &target.to_string(), reachable, time)
+            {
+                self.state.mark_changed();
+            }
+        }
+
+        let (message, is_error) = match status {
+            Ok(PortStatus::Open) => (format!("{} can reach {target}", host.name), false),
+            Ok(PortStatus::Closed) => (format!("{} cannot reach {target}", host.name), true),
+            Ok(PortStatus::Unsupported) => (
+                format!("{} has neither nc nor bash to check with", host.name),
+                true,
+            ),
+            Err(e) => (format!("Error: {e}"), true),
+        };
+        self.set_feedback_message(message, is_error);
+    }
+
+    /// Opens the web console of the selected host, asking which one when it has several.
+    fn open_web_console_of_selected_host(&mut self) {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            return;
+        }
+
+        let endpoints: Vec<String> = self.hosts[selected]
+            .web_endpoints()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        match endpoints.as_slice() {
+            [] => self.set_feedback_message(
+                format!(
+                    "{} has no web console (#_Web <url>)",
+                    self.hosts[selected].name
+                ),
+                true,
+            ),
+            [url] => self.open_web_console(url),
+            _ => {
+                let mut prompt = Prompt::new(PromptKind::WebConsole, endpoints);
+                prompt.history_previous();
+                self.prompt = Some(prompt);
+            }
+        }
+    }
+
+    fn open_web_console(&mut self, url: &str) {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            return;
+        }
+        let host = &self.hosts[selected];
+
+        let result = web_console::Endpoint::parse(url)
+            .and_then(|endpoint| web_console::open(host, &endpoint));
+        match result {
+            Ok(endpoint) => self.set_feedback_message(format!("Opened {endpoint}"), false),
+            Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+        }
+    }
+
+    /// Opens the link of the selected host, asking which one when it has several.
+    fn open_links_of_selected_host(&mut self) {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            return;
+        }
+
+        let links: Vec<String> = self.hosts[selected]
+            .links()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        match links.as_slice() {
+            [] => self.set_feedback_message(
+                format!("{} has no links (#_Links <url>)", self.hosts[selected].name),
+                true,
+            ),
+            [link] => self.open_link(link),
+            _ => {
+                let mut prompt = Prompt::new(PromptKind::Link, links);
+                prompt.history_previous();
+                self.prompt = Some(prompt);
+            }
+        }
+    }
+
+    fn open_link(&mut self, link: &str) {
+        match web_console::open_link(link) {
+            Ok(()) => self.set_feedback_message(format!("Opened {link}"), false),
+            Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+        }
+    }
+
+    fn copy_selected_public_key<B>(&mut self, terminal: &Rc<RefCell<Terminal<B>>>)
+    where
+        B: Backend + std::io::Write,
+    {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            self.set_feedback_message("No host selected".to_string(), true);
+            return;
+        }
+
+        let (keys, source) = match self.hosts[selected].public_keys() {
+            Ok(found) => found,
+            Err(e) => {
+                self.set_feedback_message(format!("Error: {e}"), true);
+                return;
+            }
+        };
+        if let [key] = keys.as_slice() {
+            self.copy_public_key(terminal, key, &source);
+            return;
+        }
+
+        // Several keys only come from the agent, the user picks one
+        let mut prompt = Prompt::new(PromptKind::AgentKey, keys);
+        prompt.history_previous();
+        self.prompt = Some(prompt);
+    }
+
+    fn copy_public_key<B>(&mut self, terminal: &Rc<RefCell<Terminal<B>>>, key: &str, source: &str)
+    where
+        B: Backend + std::io::Write,
+    {
+        match clipboard::copy(terminal.borrow_mut().backend_mut(), key) {
+            Ok(()) => self.set_feedback_message(format!("Copied public key ({source})"), false),
+            Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+        }
+    }
+
+    /// Copies the block of the selected host as written in its config file, comments included.
+    fn copy_selected_config_block<B>(&mut self, terminal: &Rc<RefCell<Terminal<B>>>)
+    where
+        B: Backend + std::io::Write,
+    {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            self.set_feedback_message("No host selected".to_string(), true);
+            return;
+        }
+
+        let result = form::host_config_block(&self.hosts[selected]).and_then(|(block, path)| {
+            clipboard::copy(terminal.borrow_mut().backend_mut(), &block)?;
+            Ok((block.lines().count(), path))
+        });
+
+        match result {
+            Ok((lines, path)) => {
+                self.set_feedback_message(
+                    format!("Copied {lines} lines from {}", path.display()),
+                    false,
+                );
+            }
+            Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+        }
+    }
+
+    fn handle_prompt_keys<B>(
+        &mut self,
+        terminal: &Rc<RefCell<Terminal<B>>>,
+        key: KeyEvent,
+    ) -> Result<AppKeyAction>
+    where
+        B: Backend + std::io::Write,
+    {
+        let Some(prompt) = &mut self.prompt else {
+            return Ok(AppKeyAction::Continue);
+        };
+
+        match key.code {
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Up => prompt.history_previous(),
+            KeyCode::Down => prompt.history_next(),
+            KeyCode::Tab => match prompt.kind {
+                PromptKind::BatchCommand(mode) => {
+                    prompt.kind = PromptKind::BatchCommand(mode.toggle());
+                }
+                PromptKind::ExportScrollback(formatted) => {
+                    prompt.kind = PromptKind::ExportScrollback(!formatted);
+                }
+                _ => {}
+            },
+            KeyCode::Enter => {
+                let kind = prompt.kind.clone();
+                let value = prompt.value();
+                self.prompt = None;
+
+                match value {
+                    Some(value) => return self.on_prompt_submit(terminal, kind, value),
+                    // An empty tab name goes back to the automatic one
+                    None if kind == PromptKind::TabName => {
+                        return self.on_prompt_submit(terminal, kind, String::new())
+                    }
+                    // An empty export path copies to the clipboard
+                    None if matches!(kind, PromptKind::ExportScrollback(_)) => {
+                        return self.on_prompt_submit(terminal, kind, String::new())
+                    }
+                    None => {}
+                }
+            }
+            _ => prompt.handle_event(&Event::Key(key)),
+        }
+
+        Ok(AppKeyAction::Ok)
+    }
+
+    fn on_prompt_submit<B>(
+        &mut self,
+        terminal: &Rc<RefCell<Terminal<B>>>,
+        kind: PromptKind,
+        value: String,
+    ) -> Result<AppKeyAction>
+    where
+        B: Backend + std::io::Write,
+    {
+        match kind {
+            PromptKind::RemoteCommand => {
+                let selected = self.table_state.selected().unwrap_or(0);
+                if selected >= self.hosts.len() {
+                    return Ok(AppKeyAction::Ok);
+                }
+
+                self.state
+                    .record_remote_command(&self.hosts[selected].name, &value);
+                self.state.mark_changed();
+
+                let options = ssh::ConnectOptions {
+                    remote_command: Some(value),
+                    ..Default::default()
+                };
+                self.request_connection(terminal, options)
+            }
+            PromptKind::TemplateValue(mut options) => {
+                let selected = self.table_state.selected().unwrap_or(0);
+                if selected >= self.hosts.len() {
+                    return Ok(AppKeyAction::Ok);
+                }
+
+                let host = &self.hosts[selected];
+                if let Err(e) = host.instantiate(&value) {
+                    self.set_feedback_message(format!("Error: {e}"), true);
+                    return Ok(AppKeyAction::Ok);
+                }
+
+                self.state.record_template_value(&host.name, &value);
+                self.state.mark_changed();
+
+                options.template_value = Some(value);
+                self.request_connection(terminal, options)
+            }
+            PromptKind::SaveWorkspace => {
+                let host_names = self.tab_manager.host_names();
+                let count = host_names.len();
+                self.state.save_workspace(&value, host_names);
+                self.state.mark_changed();
+                self.set_feedback_message(
+                    format!("Workspace '{value}' saved ({count} sessions)"),
+                    false,
+                );
+
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::SaveSearch => {
+                let query = self.search.value().trim().to_string();
+                self.state.saved_searches.insert(value.clone(), query);
+                self.state.mark_changed();
+                self.set_feedback_message(format!("Search '{value}' saved"), false);
+
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::OpenSearch => {
+                self.apply_saved_search(&value);
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::PortCheck => {
+                self.check_port_from_selected_host(&value);
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::Forward => {
+                self.start_forward(&value);
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::BatchCommand(mode) => {
+                self.start_batch_run(&value, mode);
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::BulkTags => {
+                match TagEdit::parse(&value) {
+                    Ok(edit) => self.open_tags_preview(edit),
+                    Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+                }
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::WebConsole => {
+                self.open_web_console(&value);
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::Link => {
+                self.open_link(&value);
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::AgentKey => {
+                self.copy_public_key(terminal, &value, "SSH agent");
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::ExportScrollback(formatted) => {
+                self.export_scrollback(terminal, &value, formatted)?;
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::ExportUnusedHosts => {
+                let unused = self.unused_hosts();
+                match unused_hosts::write_csv(&value, &unused) {
+                    Ok(()) => self.set_feedback_message(
+                        format!("Exported {} unused host(s) to {value}", unused.len()),
+                        false,
+                    ),
+                    Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+                }
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::OpenWorkspace => {
+                if let Err(e) = self.open_workspace(&value) {
+                    self.set_feedback_message(format!("Error: {e}"), true);
+                }
+
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::TabName => {
+                if self.tab_manager.rename_current_session(&value).is_some() {
+                    self.state.tab_names = self.tab_manager.custom_names().clone();
+                    self.state.mark_changed();
+                }
+
+                Ok(AppKeyAction::Ok)
+            }
+            PromptKind::UnlockTab => {
+                let Some(session) = self.tab_manager.current_session_mut() else {
+                    return Ok(AppKeyAction::Ok);
+                };
+                if value.trim() == session.host.name {
+                    session.locked = false;
+                    self.set_feedback_message("Tab unlocked".to_string(), false);
+                } else {
+                    let message = format!("Tab still locked, type '{}'", session.host.name);
+                    self.set_feedback_message(message, true);
+                }
+
+                Ok(AppKeyAction::Ok)
+            }
+        }
+    }
+
+    fn handle_config_errors_keys(&mut self, key: KeyEvent) -> AppKeyAction {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        let Some(view) = &mut self.config_errors else {
+            return AppKeyAction::Continue;
+        };
+
+        match key.code {
+            Esc | Char('q') => self.config_errors = None,
+            Up | Char('k') => view.previous(),
+            Down | Char('j') => view.next(),
+            Enter | Char('e') => {
+                if let Some(problem) = view.selected_problem() {
+                    self.pending_action = Some(PendingAction::EditConfig(problem.clone()));
+                }
+            }
+            _ => {}
+        }
+
+        AppKeyAction::Ok
+    }
+
+    fn handle_backups_keys(&mut self, key: KeyEvent) -> Result<AppKeyAction> {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        let Some(view) = &mut self.backups else {
+            return Ok(AppKeyAction::Continue);
+        };
+
+        if view.confirm_delete {
+            view.confirm_delete = false;
+            if matches!(key.code, Char('y' | 'Y')) {
+                let Some(backup) = view.selected_backup() else {
+                    return Ok(AppKeyAction::Ok);
+                };
+                let path = backup.path.clone();
+
+                match backup.delete() {
+                    Ok(()) => {
+                        view.remove_selected();
+                        self.set_feedback_message(format!("Deleted {}", path.display()), false);
+                    }
+                    Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+                }
+            }
+            return Ok(AppKeyAction::Ok);
+        }
+
+        match key.code {
+            Esc | Char('q') => {
+                // Close the diff first, then the view
+                if view.diff.is_some() {
+                    view.diff = None;
+                } else {
+                    self.backups = None;
+                }
+            }
+            Up | Char('k') => {
+                if view.diff.is_some() {
+                    view.scroll = view.scroll.saturating_sub(1);
+                } else {
+                    view.previous();
+                }
+            }
+            Down | Char('j') => {
+                if view.diff.is_some() {
+                    view.scroll = view.scroll.saturating_add(1);
+                } else {
+                    view.next();
+                }
+            }
+            Enter => {
+                if view.diff.is_some() {
+                    view.diff = None;
+                } else if let Err(e) = view.load_diff() {
+                    self.set_feedback_message(format!("Error: {e:#}"), true);
+                }
+            }
+            Char('h') if view.diff.is_some() => {
+                view.by_host = !view.by_host;
+                if let Err(e) = view.load_diff() {
+                    self.set_feedback_message(format!("Error: {e:#}"), true);
+                }
+            }
+            Char('r') => {
+                let Some(backup) = view.selected_backup() else {
+                    return Ok(AppKeyAction::Ok);
+                };
+                let original = backup.original.clone();
+                let file = original.display().to_string();
+                let log = self.config.audit_log.as_deref();
+                let before = audit_log::snapshot(log, &file);
+
+                let result = backup
+                    .restore()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|()| {
+                        audit_log::record_change(log, "restore backup", "", &file, before)
+                    });
+                match result {
+                    Ok(()) => {
+                        view.diff = None;
+                        self.reload_hosts()?;
+                        self.set_feedback_message(
+                            format!("Restored {} (press r again to undo)", original.display()),
+                            false,
+                        );
+                    }
+                    Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+                }
+            }
+            Char('d') | Delete => view.confirm_delete = view.selected_backup().is_some(),
+            _ => {}
+        }
+
+        Ok(AppKeyAction::Ok)
+    }
+
+    fn handle_audit_log_keys(&mut self, key: KeyEvent) -> AppKeyAction {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        let Some(view) = &mut self.audit_log else {
+            return AppKeyAction::Continue;
+        };
+
+        match key.code {
+            Esc | Char('q') => {
+                // Close the diff first, then the view
+                if view.show_diff {
+                    view.show_diff = false;
+                } else {
+                    self.audit_log = None;
+                }
+            }
+            Up | Char('k') => {
+                if view.show_diff {
+                    view.scroll = view.scroll.saturating_sub(1);
+                } else {
+                    view.previous();
+                }
+            }
+            Down | Char('j') => {
+                if view.show_diff {
+                    view.scroll = view.scroll.saturating_add(1);
+                } else {
+                    view.next();
+                }
+            }
+            Enter => {
+                view.show_diff = !view.show_diff && view.selected_entry().is_some();
+                view.scroll = 0;
+            }
+            Char('x') => {
+                let path = self.config.audit_log.clone().unwrap_or_default();
+                match audit_log::export(&path) {
+                    Ok(export) => {
+                        self.set_feedback_message(
+                            format!("Exported to {}", export.display()),
+                            false,
+                        );
+                    }
+                    Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+                }
+            }
+            _ => {}
+        }
+
+        AppKeyAction::Ok
+    }
+
+    /// Fetches the `authorized_keys` of the selected host.
+    fn open_authorized_keys_view(&mut self) {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            self.set_feedback_message("No host selected".to_string(), true);
+            return;
+        }
+
+        let host = self.hosts[selected].clone();
+        if host.is_template() {
+            self.set_feedback_message(format!("'{}' is a template host", host.name), true);
+            return;
+        }
+
+        match authorized_keys::fetch(self.backend.as_ref(), &host) {
+            Ok(content) => {
+                let keys = authorized_keys::parse(&content, &authorized_keys::local_public_keys());
+                self.authorized_keys = Some(AuthorizedKeysView::new(host, keys));
+            }
+            Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+        }
+    }
+
+    fn handle_authorized_keys_keys(&mut self, key: KeyEvent) -> AppKeyAction {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        let Some(view) = &mut self.authorized_keys else {
+            return AppKeyAction::Continue;
+        };
+
+        if view.confirm_remove {
+            view.confirm_remove = false;
+            if matches!(key.code, Char('y' | 'Y')) {
+                let Some(entry) = view.selected_key().cloned() else {
+                    return AppKeyAction::Ok;
+                };
+
+                // Fetch the file again to show what the host actually has
+                let result = authorized_keys::remove(self.backend.as_ref(), &view.host, &entry)
+                    .and_then(|()| authorized_keys::fetch(self.backend.as_ref(), &view.host));
+                match result {
+                    Ok(content) => {
+                        let keys =
+                            authorized_keys::parse(&content, &authorized_keys::local_public_keys());
+                        let is_removed = !keys.iter().any(|key| key.line == entry.line);
+                        view.replace_keys(keys);
+
+                        if is_removed {
+                            self.set_feedback_message(
+                                format!(
+                                    "Removed '{}' (previous file kept in authorized_keys.sshs.bak)",
+                                    entry.comment
+                                ),
+                                false,
+                            );
+                        } else {
+                            self.set_feedback_message(
+                                format!("'{}' could not be removed", entry.comment),
+                                true,
+                            );
+                        }
+                    }
+                    Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+                }
+            }
+            return AppKeyAction::Ok;
+        }
+
+        match key.code {
+            Esc | Char('q') => self.authorized_keys = None,
+            Up | Char('k') => view.previous(),
+            Down | Char('j') => view.next(),
+            Char('d') | Delete => view.confirm_remove = view.selected_key().is_some(),
+            _ => {}
+        }
+
+        AppKeyAction::Ok
+    }
+
+    fn handle_details_keys(&mut self, key: KeyEvent) -> AppKeyAction {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        let origins: Vec<(PathBuf, usize)> = self
+            .table_state
+            .selected()
+            .filter(|selected| *selected < self.hosts.len())
+            .map(|selected| {
+                self.hosts[selected]
+                    .option_origins()
+                    .into_iter()
+                    .filter_map(|(_, origin)| Some((origin.path.clone()?, origin.line)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        match key.code {
+            Esc | Char('q' | 'i') => self.show_details = false,
+            // The details follow the selection
+            Up | Char('k') => {
+                self.previous();
+                self.details_option = 0;
+            }
+            Down | Char('j') => {
+                self.next();
+                self.details_option = 0;
+            }
+            Tab if !origins.is_empty() => {
+                self.details_option = (self.details_option + 1) % origins.len();
+            }
+            BackTab if !origins.is_empty() => {
+                self.details_option = (self.details_option + origins.len() - 1) % origins.len();
+            }
+            Enter | Char('e') => {
+                if let Some((path, line)) = origins.get(self.details_option) {
+                    self.pending_action = Some(PendingAction::EditOption(path.clone(), *line));
+                }
+            }
+            Char('?') => self.details_help = !self.details_help,
+            _ => {}
+        }
+
+        AppKeyAction::Ok
+    }
+
+    fn handle_settings_keys(&mut self, key: KeyEvent) -> Result<AppKeyAction> {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        let Some(panel) = &mut self.settings_panel else {
+            return Ok(AppKeyAction::Continue);
+        };
+
+        let forward = match key.code {
+            Esc | Char('q' | ',') => {
+                self.settings_panel = None;
+                return Ok(AppKeyAction::Ok);
+            }
+            Up | Char('k') => {
+                panel.previous();
+                return Ok(AppKeyAction::Ok);
+            }
+            Down | Char('j') | Tab => {
+                panel.next();
+                return Ok(AppKeyAction::Ok);
+            }
+            Right | Char('l' | ' ') | Enter => true,
+            Left | Char('h') => false,
+            _ => return Ok(AppKeyAction::Ok),
+        };
+
+        panel.selected_field().change(&mut self.settings, forward);
+        self.apply_settings()?;
+
+        Ok(AppKeyAction::Ok)
+    }
+
+    /// Applies the current settings to the host list and saves them in the state file.
+    fn apply_settings(&mut self) -> Result<()> {
+        self.palette = self.settings.theme.palette();
+        self.reload_hosts()?;
+
+        self.state.settings = Some(self.settings.clone());
+        self.state.mark_changed();
+        self.start_update_check();
+
+        Ok(())
+    }
+
+    fn handle_menu_keys<B>(
+        &mut self,
+        terminal: &Rc<RefCell<Terminal<B>>>,
+        key: KeyEvent,
+    ) -> Result<AppKeyAction>
+    where
+        B: Backend + std::io::Write,
+    {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        let Some(menu) = &mut self.menu else {
+            return Ok(AppKeyAction::Continue);
+        };
+
+        match key.code {
+            Esc | F(10) => self.menu = None,
+            Left | Char('h') => menu.previous_menu(),
+            Right | Char('l') => menu.next_menu(),
+            Up | Char('k') => menu.previous_item(),
+            Down | Char('j') => menu.next_item(),
+            Enter => {
+                let action = menu.selected_action();
+                self.menu = None;
+                return self.perform_action(terminal, action);
+            }
+            _ => {}
+        }
+
+        Ok(AppKeyAction::Ok)
+    }
+
+    /// Handles the clicks on the open menu bar: a title opens its menu, an action runs, and a
+    /// click anywhere else closes the menu. Returns `true` when the application should stop.
+    fn handle_mouse<B>(
+        &mut self,
+        terminal: &Rc<RefCell<Terminal<B>>>,
+        mouse: MouseEvent,
+    ) -> Result<bool>
+    where
+        B: Backend + std::io::Write,
+    {
+        let (Some(menu), MouseEventKind::Down(MouseButton::Left)) = (&mut self.menu, mouse.kind)
+        else {
+            return Ok(false);
+        };
+
+        let area = terminal.borrow_mut().get_frame().area();
+        match menu.click(area, mouse.column, mouse.row) {
+            MenuClick::Menu(index) => {
+                *menu = MenuState {
+                    menu: index,
+                    item: 0,
+                }
+            }
+            MenuClick::Item(index) => {
+                menu.item = index;
+                let action = menu.selected_action();
+                self.menu = None;
+                return Ok(self.perform_action(terminal, action)? == AppKeyAction::Stop);
+            }
+            MenuClick::Outside => self.menu = None,
+        }
+
+        Ok(false)
+    }
+
+    fn handle_search_mode_keys(&mut self, key: KeyEvent) -> AppKeyAction {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        match key.code {
+            Esc => {
+                // Exit search mode, return to normal mode
+                self.focus_state = FocusState::Normal;
+                // Clear search text and show all hosts
+                self.search = Input::default();
+                self.refresh_search();
+                // Focus on first host
+                if !self.hosts.is_empty() {
+                    self.table_state.select(Some(0));
+                }
+            }
+            Enter => {
+                // Finish search and switch to normal mode with focus on first result
+                self.focus_state = FocusState::Normal;
+                if !self.hosts.is_empty() {
+                    self.table_state.select(Some(0));
+                }
+            }
+            _ => {
+                // Let the search field handle the input - this is already done in the main loop
+                return AppKeyAction::Continue;
+            }
+        }
+
+        AppKeyAction::Ok
+    }
+
+    fn on_key_press_ctrl(&mut self, key: KeyEvent) -> AppKeyAction {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        match key.code {
+            Char('c') => AppKeyAction::Stop,
+            Char('j') => {
+                self.next();
+                AppKeyAction::Ok
+            }
+            Char('f') => {
+                // Ctrl+F to enter search mode (alternative to '/')
+                self.focus_state = FocusState::Search;
+                self.search = Input::default();
+                self.refresh_search();
+                AppKeyAction::Ok
+            }
+            Char('k' | 'p') => {
+                self.previous();
+                AppKeyAction::Ok
+            }
+            Char('n') => {
+                // Ctrl+N to open new tab/session
+                self.open_new_session();
+                AppKeyAction::Ok
+            }
+            Char('t') => {
+                // Ctrl+T to rename the current tab
+                self.open_rename_tab_prompt();
+                AppKeyAction::Ok
+            }
+            Char('l') => {
+                // Ctrl+L to lock the current tab, or to unlock it with its host name
+                self.toggle_tab_lock();
+                AppKeyAction::Ok
+            }
+            Char('s') => {
+                // Ctrl+S to export the output of the current tab
+                self.open_export_scrollback_prompt();
+                AppKeyAction::Ok
+            }
+            Char('1') => {
+                // Ctrl+1 to switch to first tab
+                self.show_session(1);
+                AppKeyAction::Ok
+            }
+            Char('2') => {
+                // Ctrl+2 to switch to second tab
+                self.show_session(2);
+                AppKeyAction::Ok
+            }
+            Char('3') => {
+                // Ctrl+3 to switch to third tab
+                self.show_session(3);
+                AppKeyAction::Ok
+            }
+            _ => AppKeyAction::Continue,
+        }
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn on_form_key_press(&mut self, key: KeyEvent) -> Result<AppKeyAction> {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        // If we're in confirmation mode, handle that first
+        if self.form_state == FormState::Confirming {
+            if self.confirm_action.as_deref() == Some(AGENT_FORWARDING_ACTION) {
+                return Ok(self.on_agent_forwarding_confirm_key(key));
+            }
+            if self.confirm_action.as_deref() == Some(BANNER_ACTION) {
+                return Ok(self.on_banner_confirm_key(key));
+            }
+            if self.confirm_action.as_deref() == Some(MANAGED_INCLUDE_ACTION) {
+                return self.on_managed_include_confirm_key(key);
+            }
+            if self.confirm_action.as_deref() == Some(SYSTEM_EDIT_ACTION) {
+                return Ok(self.on_system_edit_confirm_key(key));
+            }
+            if self.confirm_action.as_deref() == Some(EXPIRED_CLEANUP_ACTION) {
+                return self.on_expired_cleanup_confirm_key(key);
+            }
+            if self.confirm_action.as_deref() == Some(BULK_TAGS_ACTION) {
+                return self.on_bulk_tags_confirm_key(key);
+            }
+            if self.confirm_action.as_deref() == Some(UNUSED_HOSTS_ACTION) {
+                return self.on_unused_hosts_confirm_key(key);
+            }
+
+            match key.code {
+                Esc | Char('n' | 'N') => {
+                    // Cancel the confirmation
+                    self.form_state = FormState::Active;
+                    self.confirm_message = None;
+                    self.confirm_action = None;
+                    return Ok(AppKeyAction::Ok);
+                }
+                Enter | Char('y' | 'Y') => {
+                    // Check if this is a delete confirmation
+                    if let Some(action) = &self.confirm_action {
+                        if action == "Delete" {
+                            // Handle host deletion
+                            self.form_state = FormState::Hidden;
+                            let result = self.delete_selected_host();
+
+                            match result {
+                                Ok(()) => {
+                                    self.confirm_message = None;
+                                    self.confirm_action = None;
+                                    self.editing_host_index = None;
+                                    return Ok(AppKeyAction::Ok);
+                                }
+                                Err(e) => {
+                                    self.set_feedback_message(
+                                        format!("Error deleting host: {e}"),
+                                        true,
+                                    );
+                                    self.confirm_message = None;
+                                    self.confirm_action = None;
+                                    self.editing_host_index = None;
+                                    return Ok(AppKeyAction::Ok);
+                                }
+                            }
+                        }
+                    }
+
+                    // Proceed with saving (existing functionality)
+                    self.form_state = FormState::Active;
+
+                    // Save the host (we already validated it's valid)
+                    let result = if self.is_edit_mode {
+                        self.update_existing_host()
+                    } else {
+                        self.save_new_host()
+                    };
+
+                    match result {
+                        Ok(()) => {
+                            let message = if self.is_edit_mode {
+                                "Host updated successfully!"
+                            } else {
+                                "Host added successfully!"
+                            };
+                            self.set_feedback_message(message.to_string(), false);
+                            self.form_state = FormState::Hidden;
+                            self.add_host_form = None;
+                            self.confirm_message = None;
+                            self.confirm_action = None;
+                            self.is_edit_mode = false;
+                            self.editing_host_index = None;
+
+                            // Reload the hosts
+                            self.reload_hosts()?;
+
+                            return Ok(AppKeyAction::Ok);
+                        }
+                        Err(e) => {
+                            self.set_feedback_message(format!("Error: {e}"), true);
+                            self.confirm_message = None;
+                            self.confirm_action = None;
+                            return Ok(AppKeyAction::Ok);
+                        }
+                    }
+                }
+                _ => return Ok(AppKeyAction::Continue),
+            }
+        }
+
+        // Normal form handling
+        match key.code {
+            Esc => Ok(AppKeyAction::Stop),
+            // Pasted lines arrive as Enter presses, they stay in the options
+            Enter
+                if self
+                    .add_host_form
+                    .as_ref()
+                    .is_some_and(|form| form.active_field == OPTIONS_FIELD) =>
+            {
+                if let Some(form) = &mut self.add_host_form {
+                    form.insert_option_line();
+                }
+                Ok(AppKeyAction::Ok)
+            }
+            Enter => {
+                let is_new_valid_host = !self.is_edit_mode
+                    && self
+                        .add_host_form
+                        .as_ref()
+                        .is_some_and(AddHostForm::is_valid);
+                if is_new_valid_host {
+                    match self.prepare_managed_config() {
+                        Ok(true) => {
+                            // Ask before touching the main configuration
+                            self.confirm_message = Some(format!(
+                                "Add 'Include {}' to {}?",
+                                self.config
+                                    .managed_config_path
+                                    .as_deref()
+                                    .unwrap_or_default(),
+                                self.config.config_paths[1]
+                            ));
+                            self.confirm_action = Some(MANAGED_INCLUDE_ACTION.to_string());
+                            self.form_state = FormState::Confirming;
+                            return Ok(AppKeyAction::Confirm);
+                        }
+                        Ok(false) => {}
+                        Err(e) => {
+                            self.set_feedback_message(format!("Error: {e}"), true);
+                            return Ok(AppKeyAction::Ok);
+                        }
+                    }
+                }
+
+                if let Some(form) = &self.add_host_form {
+                    if form.is_valid() {
+                        // Check if the host already exists
+                        let config_path = self.form_config_path();
+                        match form.check_duplicate(&config_path) {
+                            Ok(true) => {
+                                // Host exists, show confirmation dialog
+                                self.confirm_message = Some(format!(
+                                    "Host '{}' already exists. Overwrite?",
+                                    form.host_name.value().trim()
+                                ));
+                                self.confirm_action = Some("Overwrite".to_string());
+                                self.form_state = FormState::Confirming;
+                                return Ok(AppKeyAction::Confirm);
+                            }
+                            Ok(false) => {
+                                // No duplicate, proceed with saving
+                                let result = if self.is_edit_mode {
+                                    self.update_existing_host()
+                                } else {
+                                    self.save_new_host()
+                                };
+
+                                match result {
+                                    Ok(()) => {
+                                        let message = if self.is_edit_mode {
+                                            "Host updated successfully!"
+                                        } else {
+                                            "Host added successfully!"
+                                        };
+                                        self.set_feedback_message(message.to_string(), false);
+                                        self.form_state = FormState::Hidden;
+                                        self.add_host_form = None;
+                                        self.is_edit_mode = false;
+                                        self.editing_host_index = None;
+
+                                        // Reload the hosts
+                                        self.reload_hosts()?;
+
+                                        return Ok(AppKeyAction::Ok);
+                                    }
+                                    Err(e) => {
+                                        self.set_feedback_message(format!("Error: {e}"), true);
+                                        return Ok(AppKeyAction::Ok);
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                // Error checking for duplicates
+                                self.set_feedback_message(
+                                    format!("Error checking for duplicates: {e}"),
+                                    true,
+                                );
+                                return Ok(AppKeyAction::Ok);
+                            }
+                        }
+                    }
+
+                    // Show specific validation error message
+                    if let Some(error_message) = form.validation_error() {
+                        self.set_feedback_message(error_message, true);
+                    } else {
+                        self.set_feedback_message("Invalid form data".to_string(), true);
+                    }
+
+                    return Ok(AppKeyAction::Ok);
+                }
+                Ok(AppKeyAction::Continue)
+            }
+            Tab => {
+                if let Some(form) = &mut self.add_host_form {
+                    // Tab completes the identity file, or takes the highlighted hostname
+                    // suggestion, before moving on
+                    let is_completed = form.accept_suggestion()
+                        || (form.active_field == IDENTITY_FILE_FIELD
+                            && form.complete_identity_file());
+                    if !is_completed {
+                        form.next_field();
+                    }
+                    return Ok(AppKeyAction::Ok);
+                }
+                Ok(AppKeyAction::Continue)
+            }
+            BackTab => {
+                if let Some(form) = &mut self.add_host_form {
+                    form.previous_field();
+                    return Ok(AppKeyAction::Ok);
+                }
+                Ok(AppKeyAction::Continue)
+            }
+            Down | Up => {
+                let forward = key.code == Down;
+                if let Some(form) = &mut self.add_host_form {
+                    if form.select_suggestion(forward) {
+                        return Ok(AppKeyAction::Ok);
+                    }
+                }
+                Ok(AppKeyAction::Continue)
+            }
+            Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let Some(form) = &mut self.add_host_form else {
+                    return Ok(AppKeyAction::Continue);
+                };
+                match form.generate_identity_file() {
+                    Ok(path) => self.set_feedback_message(format!("Generated {path}"), false),
+                    Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+                }
+                Ok(AppKeyAction::Ok)
+            }
+            _ => Ok(AppKeyAction::Continue),
+        }
+    }
+
+    fn on_agent_forwarding_confirm_key(&mut self, key: KeyEvent) -> AppKeyAction {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        match key.code {
+            Esc | Char('n' | 'N') => {
+                self.pending_action = None;
+            }
+            Enter | Char('y' | 'Y') => {}
+            Char('t' | 'T') => {
+                let selected = self.table_state.selected().unwrap_or(0);
+                if selected < self.hosts.len() {
+                    let host_name = self.hosts[selected].name.clone();
+                    self.state.trust_agent_host(&host_name);
+                    self.state.mark_changed();
+                }
+            }
+            _ => return AppKeyAction::Continue,
+        }
+
+        self.form_state = FormState::Hidden;
+        self.confirm_message = None;
+        self.confirm_action = None;
+
+        AppKeyAction::Ok
+    }
+
+    fn on_banner_confirm_key(&mut self, key: KeyEvent) -> AppKeyAction {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        let pending = match key.code {
+            Enter | Char('y' | 'Y') => self.pending_action.take(),
+            Esc | Char('n' | 'N') => None,
+            _ => return AppKeyAction::Continue,
+        };
+
+        self.pending_action = None;
+        self.form_state = FormState::Hidden;
+        self.confirm_message = None;
+        self.confirm_action = None;
+
+        if let Some(PendingAction::Connect(mut options)) = pending {
+            // The agent forwarding confirmation may still be needed
+            options.banner_acknowledged = true;
+            if let Some(options) = self.prepare_connection(options) {
+                self.pending_action = Some(PendingAction::Connect(options));
+            }
+        }
+
+        AppKeyAction::Ok
+    }
+
+    fn on_expired_cleanup_confirm_key(&mut self, key: KeyEvent) -> Result<AppKeyAction> {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        let confirmed = match key.code {
+            Enter | Char('y' | 'Y') => true,
+            Esc | Char('n' | 'N') => false,
+            _ => return Ok(AppKeyAction::Continue),
+        };
+
+        self.form_state = FormState::Hidden;
+        self.confirm_message = None;
+        self.confirm_action = None;
+
+        if confirmed {
+            self.remove_expired_hosts()?;
+        }
+
+        Ok(AppKeyAction::Ok)
+    }
+
+    fn on_bulk_tags_confirm_key(&mut self, key: KeyEvent) -> Result<AppKeyAction> {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        let confirmed = match key.code {
+            Enter | Char('y' | 'Y') => true,
+            Esc | Char('n' | 'N') => false,
+            _ => return Ok(AppKeyAction::Continue),
+        };
+
+        self.form_state = FormState::Hidden;
+        self.confirm_message = None;
+        self.confirm_action = None;
+        let edit = self.tag_edit.take();
+
+        if let (true, Some(edit)) = (confirmed, edit) {
+            let names = self
+                .hosts
+                .iter()
+                .map(|host| host.name.clone())
+                .collect::<Vec<_>>();
+            self.apply_tag_edit(&names, &edit)?;
+        }
+
+        Ok(AppKeyAction::Ok)
+    }
+
+    fn on_unused_hosts_confirm_key(&mut self, key: KeyEvent) -> Result<AppKeyAction> {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        if !matches!(
+            key.code,
+            Enter | Char('y' | 'Y' | 'c' | 'C' | 'x' | 'X' | 'n' | 'N') | Esc
+        ) {
+            return Ok(AppKeyAction::Continue);
+        }
+
+        self.form_state = FormState::Hidden;
+        self.confirm_message = None;
+        self.confirm_action = None;
+
+        match key.code {
+            Enter | Char('y' | 'Y') => {
+                let edit = TagEdit {
+                    add: vec![STALE_TAG.to_string()],
+                    remove: Vec::new(),
+                };
+                let names = self.unused_host_names();
+                self.apply_tag_edit(&names, &edit)?;
+            }
+            Char('c' | 'C') => self.comment_out_unused_hosts()?,
+            Char('x' | 'X') => {
+                let mut prompt = Prompt::new(
+                    PromptKind::ExportUnusedHosts,
+                    vec![DEFAULT_UNUSED_HOSTS_EXPORT.to_string()],
+                );
+                prompt.history_previous();
+                self.prompt = Some(prompt);
+            }
+            _ => {}
+        }
+
+        Ok(AppKeyAction::Ok)
+    }
+
+    fn on_system_edit_confirm_key(&mut self, key: KeyEvent) -> AppKeyAction {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        let selected = self.editing_host_index.take();
+        self.form_state = FormState::Hidden;
+        self.confirm_message = None;
+        self.confirm_action = None;
+
+        let Some(host) = selected
+            .filter(|index| *index < self.hosts.len())
+            .map(|index| self.hosts[index].clone())
+        else {
+            return AppKeyAction::Ok;
+        };
+
+        match key.code {
+            Enter | Char('y' | 'Y') => {
+                // ssh uses the first value it finds and reads the user configuration first, so a
+                // new block with the same name there overrides the system one
+                let mut form = AddHostForm::new();
+                form.populate_from_host(&host);
+                form.set_existing_blocks(self.existing_host_blocks(Some(&host.name)));
+                form.set_known_destinations(self.known_destinations());
+                self.add_host_form = Some(form);
+                self.form_state = FormState::Active;
+                self.is_edit_mode = false;
+            }
+            Char('s' | 'S') => {
+                if let Some(path) = host.source_file {
+                    self.pending_action = Some(PendingAction::SudoEdit(path));
+                }
+            }
+            Esc | Char('n' | 'N') => {}
+            _ => {
+                // Keep the dialog open on unrelated keys
+                self.editing_host_index = selected;
+                self.form_state = FormState::Confirming;
+                self.confirm_action = Some(SYSTEM_EDIT_ACTION.to_string());
+                self.confirm_message = Some(system_edit_message(&host));
+                return AppKeyAction::Continue;
+            }
+        }
+
+        AppKeyAction::Ok
+    }
+
+    fn on_managed_include_confirm_key(&mut self, key: KeyEvent) -> Result<AppKeyAction> {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        self.form_state = FormState::Active;
+        self.confirm_message = None;
+        self.confirm_action = None;
+
+        match key.code {
+            Esc | Char('n' | 'N') => {
+                self.set_feedback_message(
+                    "Host not saved: the sshs file must be included from the SSH config"
+                        .to_string(),
+                    true,
+                );
+                Ok(AppKeyAction::Ok)
+            }
+            Enter | Char('y' | 'Y') => {
+                let main_path = shellexpand::tilde(&self.config.config_paths[1]).to_string();
+                let managed_path = self.config.managed_config_path.clone().unwrap_or_default();
+
+                let log = self.config.audit_log.as_deref();
+                let before = audit_log::snapshot(log, &main_path);
+                let result = managed_config::add_include(&main_path, &managed_path)
+                    .and_then(|()| {
+                        audit_log::record_change(
+                            log,
+                            "include managed file",
+                            "",
+                            &main_path,
+                            before,
+                        )
+                    })
+                    .and_then(|()| managed_config::ensure_exists(&managed_path));
+                if let Err(e) = result {
+                    self.set_feedback_message(format!("Error: {e}"), true);
+                    return Ok(AppKeyAction::Ok);
+                }
+
+                // Resume saving the host now that the managed file is included
+                self.on_form_key_press(KeyEvent::new(Enter, KeyModifiers::NONE))
+            }
+            _ => {
+                // Keep the dialog open on unrelated keys
+                self.form_state = FormState::Confirming;
+                self.confirm_action = Some(MANAGED_INCLUDE_ACTION.to_string());
+                Ok(AppKeyAction::Continue)
+            }
+        }
+    }
+
+    fn next(&mut self) {
+        let i = match self.table_state.selected() {
+            Some(i) => {
+                if self.hosts.is_empty() || i >= self.hosts.len() - 1 {
+                    0
+                } else {
+                    i + 1
+                }
+            }
+            None => 0,
+        };
+        self.table_state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        let i = match self.table_state.selected() {
+            Some(i) => {
+                if self.hosts.is_empty() {
+                    0
+                } else if i == 0 {
+                    self.hosts.len() - 1
+                } else {
+                    i - 1
+                }
+            }
+            None => 0,
+        };
+        self.table_state.select(Some(i));
+    }
+
+    /// Whether any host carries tags, in which case the table shows a Tags column.
+    #[must_use]
+    pub fn has_tags(&self) -> bool {
+        self.hosts
+            .non_filtered_iter()
+            .any(|host| !host.tags.is_empty())
+    }
+
+    /// Columns of the hosts table, following the settings. The Tags column is only shown when
+    /// a host carries tags, and the presentation mode only keeps the names and tags.
+    #[must_use]
+    pub fn visible_columns(&self) -> Vec<Column> {
+        let has_tags = self.has_tags();
+
+        std::iter::once(Column::Name)
+            .chain(Column::OPTIONAL.iter().copied().filter(|column| {
+                self.settings.is_column_shown(*column)
+                    && (*column != Column::Tags || has_tags)
+                    && (!self.presentation || matches!(column, Column::Aliases | Column::Tags))
+            }))
+            .collect()
+    }
+
+    pub fn calculate_table_columns_constraints(&mut self) {
+        let inferred_user = self.inferred_user().unwrap_or_default();
+        let mut lengths = Vec::new();
+
+        if self.health.is_some() {
+            lengths.push(health::COLUMN_TITLE.len());
+        }
+
+        if self.group_mode() != GroupMode::None {
+            let group_len = self
+                .group_sizes()
+                .iter()
+                .map(|(group, size)| self.group_label(group, *size).width())
+                .max()
+                .unwrap_or(0);
+            lengths.push(group_len.max("Group".len()));
+        }
+
+        for column in self.visible_columns() {
+            // The name column fits the filtered hosts, the others every host
+            let hosts = if column == Column::Name {
+                self.hosts.iter()
+            } else {
+                self.hosts.non_filtered_iter()
+            };
+
+            let len = hosts
+                .map(|d| {
+                    let width = column.value(d, &inferred_user).width();
+                    // Room for the certificate warning badge
+                    match (column, self.certificate_status(d)) {
+                        (Column::Name, Some(status)) if status != CertificateStatus::Valid => {
+                            width + 2
+                        }
+                        _ => width,
+                    }
+                })
+                .max()
+                .unwrap_or(0);
+            lengths.push(len);
+        }
+        if self.recent_view {
+            let now = recent::now();
+            let len = self
+                .hosts
+                .non_filtered_iter()
+                .map(|host| recent::format_last_used(recent::last_used(&self.state, host), now))
+                .map(|text| text.width())
+                .max()
+                .unwrap_or(0);
+            lengths.push(len.max(recent::COLUMN_TITLE.len()));
+        }
+        if self.incident.is_some() {
+            let now = recent::now();
+            let len = self
+                .hosts
+                .non_filtered_iter()
+                .map(|host| {
+                    incident::format_since(incident::state_changed_at(&self.state, &host.name), now)
+                })
+                .map(|text| text.width())
+                .max()
+                .unwrap_or(0);
+            lengths.push(len.max(incident::COLUMN_TITLE.len()));
+        }
+
+        self.table_columns_constraints = vec![
+            // +COLUMN_PADDING for padding
+            Constraint::Length(u16::try_from(lengths[0]).unwrap_or_default() + COLUMN_PADDING),
+        ];
+        self.table_columns_constraints.extend(
+            lengths.iter().skip(1).map(|len| {
+                Constraint::Min(u16::try_from(*len).unwrap_or_default() + COLUMN_PADDING)
+            }),
+        );
+    }
+
+    /// Inspects the certificates of the hosts that are not known yet.
+    fn inspect_certificates(&mut self) {
+        let paths = self
+            .hosts
+            .non_filtered_iter()
+            .filter_map(ssh::Host::certificate_path)
+            .collect::<Vec<_>>();
+
+        for path in paths {
+            if let btree_map::Entry::Vacant(entry) = self.certificates.entry(path) {
+                let certificate = Certificate::inspect(entry.key()).map_err(|e| e.to_string());
+                entry.insert(certificate);
+            }
+        }
+    }
+
+    /// Certificate presented to `host`, with its path.
+    #[must_use]
+    pub fn certificate_of(
+        &self,
+        host: &ssh::Host,
+    ) -> Option<(String, &Result<Certificate, String>)> {
+        let path = host.certificate_path()?;
+        let certificate = self.certificates.get(&path)?;
+
+        Some((path, certificate))
+    }
+
+    /// Status of the certificate presented to `host`, when it has a readable one.
+    #[must_use]
+    pub fn certificate_status(&self, host: &ssh::Host) -> Option<CertificateStatus> {
+        match self.certificate_of(host)? {
+            (_, Ok(certificate)) => Some(certificate.status(certificate::now())),
+            (_, Err(_)) => None,
+        }
+    }
+
+    /// User connected as for hosts without a `User` entry.
+    #[must_use]
+    pub fn inferred_user(&self) -> Option<String> {
+        self.config
+            .default_user
+            .clone()
+            .or_else(ssh::local_username)
+    }
+
+    fn open_add_host_form(&mut self) {
+        let mut form = AddHostForm::new();
+        form.set_existing_blocks(self.existing_host_blocks(None));
+        form.set_known_destinations(self.known_destinations());
+
+        self.add_host_form = Some(form);
+        self.form_state = FormState::Active;
+        self.feedback_message = None;
+        self.feedback_timeout = None;
+        self.is_edit_mode = false;
+        self.editing_host_index = None;
+    }
+
+    fn open_new_session(&mut self) {
+        if self.config.no_exec {
+            self.set_feedback_message(NO_EXEC_SESSIONS.to_string(), true);
+            return;
+        }
+
+        // For MVP, Ctrl+N creates a new session with the currently selected host
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            self.set_feedback_message("No host selected for new session".to_string(), true);
+            return;
+        }
+
+        // The same prompts as a connection: template value, important banner, agent forwarding
+        let options = ssh::ConnectOptions {
+            in_tab: true,
+            ..Default::default()
+        };
+        let Some(options) = self.prepare_connection(options) else {
+            return;
+        };
+        match self.connection_host(selected, &options) {
+            Ok(host) => self.open_session_tab(host, &options),
+            Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+        }
+    }
+
+    /// Opens a tab for a session on `host`, started by the event loop. The banner of the host
+    /// is shown as the message.
+    fn open_session_tab(&mut self, host: ssh::Host, options: &ssh::ConnectOptions) {
+        let banner = host.banner();
+        let host_name = host.name.clone();
+        let session_id = match self.tab_manager.add_session(host) {
+            Ok(session_id) => session_id,
+            Err(e) => {
+                self.set_feedback_message(format!("Error: {e}"), true);
+                return;
+            }
+        };
+
+        if let Some(session) = self.tab_manager.current_session_mut() {
+            session.options = options.clone();
+        }
+        self.session_shown = true;
+        let message = match banner {
+            Some(banner) => format!("{host_name}: {}", banner.replace('\n', " ")),
+            None => format!("New session {session_id} created (Ctrl+Q: back to the hosts)"),
+        };
+        self.set_feedback_message(message, false);
+    }
+
+    /// Switches to the tab `one_based_index`, showing its session.
+    fn show_session(&mut self, one_based_index: usize) {
+        if self.tab_manager.switch_to_session(one_based_index) {
+            self.session_shown = true;
+        }
+    }
+
+    /// Keys pressed while a tab is shown: sshs keeps the ones switching, renaming, locking and
+    /// exporting tabs, and `Ctrl+Q` going back to the hosts. The others are written to the session,
+    /// unless its tab is locked.
+    fn handle_session_keys(&mut self, key: KeyEvent) -> AppKeyAction {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            match key.code {
+                KeyCode::Char('q') => {
+                    self.session_shown = false;
+                    return AppKeyAction::Ok;
+                }
+                KeyCode::Char('1' | '2' | '3' | 't' | 'l' | 's') => {
+                    return self.on_key_press_ctrl(key)
+                }
+                _ => {}
+            }
+        }
+
+        let Some(session) = self.tab_manager.current_session_mut() else {
+            self.session_shown = false;
+            return AppKeyAction::Ok;
+        };
+        if session.locked {
+            self.set_feedback_message("The tab is locked (Ctrl+L to unlock)".to_string(), true);
+            return AppKeyAction::Ok;
+        }
+        // Still starting
+        let Some(pty) = session.pty.as_mut() else {
+            return AppKeyAction::Ok;
+        };
+
+        let application_cursor = pty.parser().screen().application_cursor();
+        let sent = match session::encode_key(key, application_cursor) {
+            Some(bytes) => pty.send_input(&bytes),
+            None => Ok(()),
+        };
+        if let Err(e) = sent {
+            self.set_feedback_message(format!("The session did not get the key: {e}"), true);
+        }
+
+        AppKeyAction::Ok
+    }
+
+    /// Text pasted at once: into the shown tab, or typed key by key anywhere else, as it was
+    /// before pastes arrived at once.
+    fn handle_paste<B>(&mut self, terminal: &Rc<RefCell<Terminal<B>>>, text: &str) -> Result<bool>
+    where
+        B: Backend + std::io::Write,
+    {
+        // The confirmation of the previous paste is not answered by this one
+        if self.pending_paste.is_some() {
+            return Ok(false);
+        }
+        if self.session_shown && self.prompt.is_none() && self.menu.is_none() {
+            self.paste_into_session(text);
+            return Ok(false);
+        }
+
+        for c in text.replace("\r\n", "\n").chars() {
+            let code = match c {
+                '\n' | '\r' => KeyCode::Enter,
+                '\t' => KeyCode::Tab,
+                c => KeyCode::Char(c),
+            };
+            let key = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+            if self.handle_event(terminal, &key)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Sends `text` to the shown tab. Text with a newline, which would run commands, waits for
+    /// a confirmation instead, unless its host allows it (`# sshs:paste=allow`).
+    fn paste_into_session(&mut self, text: &str) {
+        let Some(session) = self.tab_manager.current_session_mut() else {
+            return;
+        };
+        if session.locked {
+            self.set_feedback_message("The tab is locked (Ctrl+L to unlock)".to_string(), true);
+            return;
+        }
+        let confirm =
+            text.contains(['\n', '\r']) && session.host.metadata_value("paste") != Some("allow");
+        // Still starting
+        let Some(pty) = session.pty.as_mut() else {
+            return;
+        };
+
+        if confirm {
+            self.pending_paste = Some(text.to_string());
+        } else if let Err(e) = pty.paste(text) {
+            self.set_feedback_message(format!("The session did not get the paste: {e}"), true);
+        }
+    }
+
+    /// Keys of the paste confirmation: `Enter` sends the text as is, `t` without its trailing
+    /// newlines, `Esc` drops it.
+    fn handle_paste_keys(&mut self, key: KeyEvent) -> AppKeyAction {
+        let Some(text) = self.pending_paste.take() else {
+            return AppKeyAction::Ok;
+        };
+        let text = match key.code {
+            KeyCode::Enter | KeyCode::Char('y') => text,
+            KeyCode::Char('t') => text.trim_end_matches(['\n', '\r']).to_string(),
+            KeyCode::Esc | KeyCode::Char('n' | 'q') => {
+                self.set_feedback_message("Paste cancelled".to_string(), false);
+                return AppKeyAction::Ok;
+            }
+            _ => {
+                self.pending_paste = Some(text);
+                return AppKeyAction::Ok;
+            }
+        };
+
+        let sent = self
+            .tab_manager
+            .current_session_mut()
+            .and_then(|session| session.pty.as_mut())
+            .map(|pty| pty.paste(&text));
+        if let Some(Err(e)) = sent {
+            self.set_feedback_message(format!("The session did not get the paste: {e}"), true);
+        }
+
+        AppKeyAction::Ok
+    }
+
+    /// Gives the sessions of the tabs the size of the area they are drawn in, `rows` x `cols`,
+    /// once the terminal has not been resized for [`RESIZE_DEBOUNCE`]. The tabs not shown are
+    /// resized too, so that their full screen programs have already redrawn when shown.
+    fn resize_sessions(&mut self, rows: u16, cols: u16) {
+        if self.session_size == Some((rows, cols)) {
+            self.resized_at = None;
+            return;
+        }
+        if self
+            .resized_at
+            .is_some_and(|resized_at| resized_at.elapsed() < RESIZE_DEBOUNCE)
+        {
+            return;
+        }
+
+        for session in self.tab_manager.sessions_mut() {
+            if let Some(pty) = &mut session.pty {
+                pty.resize(rows, cols);
+            }
+        }
+        self.session_size = Some((rows, cols));
+        self.resized_at = None;
+    }
+
+    /// Starts the sessions of the tabs opened since the last call, in a terminal of `rows` x
+    /// `cols`, and closes the tabs whose session has ended. A session that
+    /// cannot start closes its tab too.
+    ///
+    /// The sessions go through what a connection taking over the terminal does: the session
+    /// start hook when they start, then the history, the statistics and the session end (or
+    /// connection failure) hooks when they end. Every message is shown, not only the last one.
+    /// Returns whether a trigger rang the bell.
+    fn poll_sessions(&mut self, rows: u16, cols: u16) -> bool {
+        let mut messages = Vec::new();
+        let mut sessions_changed = self.start_sessions(rows, cols, &mut messages);
+
+        let now = Instant::now();
+        for session in self.tab_manager.sessions_mut() {
+            if let Some(pty) = &session.pty {
+                session.throughput.sample(pty.bytes_received(), now);
+            }
+        }
+        let bell = self.fire_triggers(&mut messages);
+
+        let current = self.tab_manager.current_session().map(|session| session.id);
+        for (session, exit) in self.tab_manager.close_ended_sessions() {
+            sessions_changed = true;
+            if Some(session.id) == current {
+                self.session_shown = false;
+            }
+            let status = match exit {
+                Some(code) => format!("exit {code}"),
+                None => "killed by a signal".to_string(),
+            };
+            messages.push((
+                format!(
+                    "Session {} on {} ended ({status})",
+                    session.id, session.host.name
+                ),
+                exit != Some(0),
+            ));
+            let summary = SessionSummary {
+                host: session.host.name.clone(),
+                destination: session.host.destination.clone(),
+                duration: session
+                    .started_at
+                    .map_or(Duration::ZERO, |started| started.elapsed()),
+                exit_code: exit,
+                error: None,
+            };
+            messages.extend(self.end_tab_session(&session, &summary));
+        }
+        if !self.tab_manager.has_sessions() {
+            self.session_shown = false;
+        }
+        // The hosts with a session changed
+        if sessions_changed {
+            self.refresh_search();
+        }
+
+        if !messages.is_empty() {
+            let is_error = messages.iter().any(|(_, is_error)| *is_error);
+            let message = messages
+                .into_iter()
+                .map(|(message, _)| message)
+                .collect::<Vec<_>>()
+                .join(" | ");
+            self.set_feedback_message(message, is_error);
+        }
+
+        bell
+    }
+
+    /// Starts the sessions of the tabs opened since the last call, with their triggers, and
+    /// closes the tabs whose session cannot start. Returns whether there were any.
+    fn start_sessions(&mut self, rows: u16, cols: u16, messages: &mut Vec<(String, bool)>) -> bool {
+        let mut failed = Vec::new();
+        let waiting: Vec<_> = self
+            .tab_manager
+            .sessions()
+            .iter()
+            .enumerate()
+            .filter(|(_, session)| session.pty.is_none())
+            .map(|(index, session)| (index, session.host.clone(), session.logged))
+            .collect();
+        let started = !waiting.is_empty();
+        for (index, host, logged) in waiting {
+            let mut host = host.for_connection();
+            if host.user.is_none() {
+                host.user.clone_from(&self.config.default_user);
+            }
+            let mut options = self.tab_manager.sessions()[index].options.clone();
+            if logged {
+                match session_log::new_log(
+                    &self.config.session_log_dir,
+                    &host.name,
+                    certificate::now(),
+                ) {
+                    Ok(path) => options.session_log = Some(path),
+                    Err(e) => messages.push((format!("Session not recorded: {e:#}"), true)),
+                }
+            }
+
+            if let Some(template) = &self.config.command_template_on_session_start {
+                if let Err(e) = host.spawn_command_template_with_values(template, &[]) {
+                    messages.push((format!("Session start hook failed: {e:#}"), true));
+                }
+            }
+            match self.backend.spawn_tab(&host, &options, rows, cols) {
+                Ok(mut pty) => {
+                    let session_triggers = triggers::for_host(&self.config.triggers, &host)
+                        .and_then(|session_triggers| {
+                            if !session_triggers.is_empty() {
+                                pty.watch(triggers::pattern_set(&session_triggers)?);
+                            }
+                            Ok(session_triggers)
+                        })
+                        .unwrap_or_else(|e| {
+                            messages.push((format!("Triggers not watched: {e:#}"), true));
+                            Vec::new()
+                        });
+                    let session = &mut self.tab_manager.sessions_mut()[index];
+                    session.pty = Some(pty);
+                    session.triggers = session_triggers;
+                    session.started_at = Some(Instant::now());
+                }
+                Err(e) => failed.push((index, e)),
+            }
+        }
+        for (index, e) in failed.into_iter().rev() {
+            let session = self.tab_manager.close_session(index);
+            messages.push((
+                format!(
+                    "Session {} on {} could not start: {e:#}",
+                    session.id, session.host.name
+                ),
+                true,
+            ));
+            let summary = SessionSummary {
+                host: session.host.name.clone(),
+                destination: session.host.destination.clone(),
+                duration: Duration::ZERO,
+                exit_code: None,
+                error: Some(format!("{e:#}")),
+            };
+            messages.extend(self.end_tab_session(&session, &summary));
+        }
+
+        started
+    }
+
+    /// Runs the triggers on the lines the sessions printed since the last call: marks the tabs
+    /// not shown, and runs the hooks, once in [`triggers::ALERT_INTERVAL`] per session at most.
+    /// Returns whether a trigger rings the bell.
+    fn fire_triggers(&mut self, messages: &mut Vec<(String, bool)>) -> bool {
+        let shown = self
+            .tab_manager
+            .current_session()
+            .filter(|_| self.session_shown)
+            .map(|session| session.id);
+        let now = Instant::now();
+        let mut bell = false;
+
+        for session in self.tab_manager.sessions_mut() {
+            if Some(session.id) == shown {
+                session.marked = false;
+            }
+            let Some(pty) = &session.pty else {
+                continue;
+            };
+
+            for (index, line) in pty.matched_lines() {
+                let Some(trigger) = session.triggers.get(index) else {
+                    continue;
+                };
+                if trigger.mark && Some(session.id) != shown {
+                    session.marked = true;
+                }
+
+                let alerts = trigger.beep || trigger.hook.is_some();
+                let quiet = session.alerted_at.is_some_and(|alerted_at| {
+                    now.duration_since(alerted_at) < triggers::ALERT_INTERVAL
+                });
+                if !alerts || quiet {
+                    continue;
+                }
+                session.alerted_at = Some(now);
+                bell |= trigger.beep;
+                if let Some(hook) = &trigger.hook {
+                    let values = [("trigger_line", line)];
+                    if let Err(e) = session
+                        .host
+                        .spawn_command_template_with_values(hook, &values)
+                    {
+                        messages.push((format!("Trigger hook failed: {e:#}"), true));
+                    }
+                }
+            }
+        }
+
+        bell
+    }
+
+    /// Records the tab session that ended, or could not start, as summed up by `summary`, and
+    /// runs the connection failure and session end hooks in the background. Returns the
+    /// failures of the hooks.
+    fn end_tab_session(
+        &mut self,
+        session: &Session,
+        summary: &SessionSummary,
+    ) -> Vec<(String, bool)> {
+        self.record_usage(summary);
+
+        let host = &session.host;
+        let tab_name = session
+            .custom_name
+            .clone()
+            .unwrap_or_else(|| self.tab_manager.naming().render(session.id, host));
+        let values = summary.template_values(Some(&tab_name));
+        let hooks = [
+            (
+                &self.config.command_template_on_connection_failure,
+                summary.is_connection_error(),
+            ),
+            (&self.config.command_template_on_session_end, true),
+        ];
+
+        hooks
+            .into_iter()
+            .filter_map(|(template, runs)| template.as_ref().filter(|_| runs))
+            .filter_map(|template| {
+                host.spawn_command_template_with_values(template, &values)
+                    .err()
+            })
+            .map(|e| (format!("Session end hook failed: {e:#}"), true))
+            .collect()
+    }
+
+    fn open_rename_tab_prompt(&mut self) {
+        let Some(session) = self.tab_manager.current_session() else {
+            self.set_feedback_message("No session to rename".to_string(), true);
+            return;
+        };
+        if session.locked {
+            self.set_feedback_message("The tab is locked (Ctrl+L to unlock)".to_string(), true);
+            return;
+        }
+
+        let mut prompt = Prompt::new(PromptKind::TabName, Vec::new());
+        if let Some(name) = &session.custom_name {
+            prompt.input = Input::from(name.clone());
+        }
+        self.prompt = Some(prompt);
+    }
+
+    /// Asks where to export the output of the current tab, in the home directory by default.
+    fn open_export_scrollback_prompt(&mut self) {
+        let Some(session) = self.tab_manager.current_session() else {
+            self.set_feedback_message("No session to export".to_string(), true);
+            return;
+        };
+        if session.pty.is_none() {
+            self.set_feedback_message("The session has not started yet".to_string(), true);
+            return;
+        }
+
+        let path = session_log::export_path(&session.host.name, certificate::now());
+        let mut prompt = Prompt::new(PromptKind::ExportScrollback(false), Vec::new());
+        prompt.input = Input::from(path);
+        self.prompt = Some(prompt);
+    }
+
+    /// Writes what the current session still holds to `path`, or copies it to the clipboard
+    /// when `path` is empty. With `formatted`, the colors are kept as escape sequences.
+    fn export_scrollback<B>(
+        &mut self,
+        terminal: &Rc<RefCell<Terminal<B>>>,
+        path: &str,
+        formatted: bool,
+    ) -> Result<()>
+    where
+        B: Backend + std::io::Write,
+    {
+        let Some(session) = self.tab_manager.current_session() else {
+            return Ok(());
+        };
+        let Some(pty) = &session.pty else {
+            return Ok(());
+        };
+        let output = pty.scrollback(formatted);
+        let text = String::from_utf8_lossy(&output);
+        let lines = text.lines().count();
+
+        if path.is_empty() {
+            clipboard::copy(terminal.borrow_mut().backend_mut(), &text)?;
+            self.set_feedback_message(format!("Copied {lines} line(s) of the session"), false);
+            return Ok(());
+        }
+        match session_log::write_export(path, &output) {
+            Ok(path) => self.set_feedback_message(
+                format!(
+                    "Exported {lines} line(s) of the session to {}",
+                    path.display()
+                ),
+                false,
+            ),
+            Err(e) => self.set_feedback_message(format!("{e:#}"), true),
+        }
+
+        Ok(())
+    }
+
+    /// Locks the current tab, so that no keystroke reaches its session, or asks for its host
+    /// name to unlock it: a deliberate sequence a stray key press cannot complete.
+    fn toggle_tab_lock(&mut self) {
+        let Some(session) = self.tab_manager.current_session_mut() else {
+            self.set_feedback_message("No session to lock".to_string(), true);
+            return;
+        };
+
+        if session.locked {
+            self.prompt = Some(Prompt::new(PromptKind::UnlockTab, Vec::new()));
+        } else {
+            session.locked = true;
+            let message = format!(
+                "Tab locked, keystrokes are ignored (Ctrl+L then '{}' to unlock)",
+                session.host.name
+            );
+            self.set_feedback_message(message, false);
+        }
+    }
+
+    /// Filters the hosts with the search. While nothing is searched, collapsed groups only
+    /// show their first host.
+    fn refresh_search(&mut self) {
+        self.hosts.search(self.search.value());
+        self.filter_by_state();
+        if !self.search.value().is_empty() || self.collapsed_groups.is_empty() {
+            return;
+        }
+
+        let group_mode = self.group_mode();
+        let collapsed = &self.collapsed_groups;
+        let mut seen = BTreeSet::new();
+        self.hosts.retain(|host| {
+            let group = group_mode.group_of(host);
+            !collapsed.contains(&group) || seen.insert(group)
+        });
+    }
+
+    /// Counts the hosts matching the search in each state, and keeps those in the state of
+    /// the filter.
+    fn filter_by_state(&mut self) {
+        let sessions: BTreeSet<String> = self.tab_manager.host_names().into_iter().collect();
+        let health = self.health.as_ref();
+        let state = |host: &ssh::Host| {
+            (
+                health.and_then(|checker| checker.get(&host.name)),
+                sessions.contains(&host.name),
+            )
+        };
+
+        let mut counts = StateCounts::default();
+        for host in &self.hosts {
+            let (health, has_session) = state(host);
+            counts.add(health, has_session);
+        }
+        self.state_counts = counts;
+
+        let filter = self.state_filter;
+        if filter != StateFilter::All {
+            self.hosts.retain(|host| {
+                let (health, has_session) = state(host);
+                filter.matches(health, has_session)
+            });
+            if self
+                .table_state
+                .selected()
+                .is_some_and(|selected| selected >= self.hosts.len())
+            {
+                self.table_state
+                    .select(Some(self.hosts.len().saturating_sub(1)));
+            }
+        }
+    }
+
+    /// Lists the hosts in the next state: reachable, unreachable, with a session, then all of
+    /// them again. The reachability filters start the checks when they are not running.
+    fn cycle_state_filter(&mut self) {
+        self.state_filter = self.state_filter.next();
+        if matches!(
+            self.state_filter,
+            StateFilter::Reachable | StateFilter::Unreachable
+        ) && self.health.is_none()
+        {
+            self.check_health();
+        }
+
+        self.refresh_search();
+        self.table_state.select(Some(0));
+        self.set_feedback_message(
+            format!(
+                "Showing {} (c for the next filter)",
+                self.state_filter.label()
+            ),
+            false,
+        );
+    }
+
+    /// Enters incident mode, or leaves it, putting back the filter and the health checks as
+    /// they were.
+    fn toggle_incident_mode(&mut self) {
+        let message = if let Some(incident) = self.incident.take() {
+            self.state_filter = incident.state_filter;
+            match incident.check_interval {
+                Some(interval) => {
+                    if let Some(checker) = &mut self.health {
+                        checker.set_interval(interval);
+                    }
+                }
+                None => self.health = None,
+            }
+            "Incident mode off"
+        } else {
+            self.incident = Some(Incident {
+                state_filter: self.state_filter,
+                check_interval: self.health.as_ref().map(HealthChecker::interval),
+            });
+            self.state_filter = StateFilter::Unreachable;
+
+            let checker = self.health.get_or_insert_with(|| HealthChecker::new(None));
+            let interval = checker
+                .interval()
+                .map_or(incident::CHECK_INTERVAL, |interval| {
+                    interval.min(incident::CHECK_INTERVAL)
+                });
+            checker.set_interval(Some(interval));
+            let hosts: Vec<ssh::Host> = self.hosts.non_filtered_iter().cloned().collect();
+            checker.refresh(&hosts, Instant::now());
+            "Incident mode: unreachable hosts, longest without a connection first (x to leave)"
+        };
+
+        if let Err(e) = self.reload_hosts() {
+            self.set_feedback_message(format!("Failed to reload the hosts: {e}"), true);
+            return;
+        }
+        self.table_state.select(Some(0));
+        self.set_feedback_message(message.to_string(), false);
+    }
+
+    /// How the hosts are grouped: not at all in the Recent view or incident mode.
+    #[must_use]
+    pub fn group_mode(&self) -> GroupMode {
+        if self.recent_view || self.incident.is_some() {
+            GroupMode::None
+        } else {
+            self.settings.group
+        }
+    }
+
+    /// Switches between the Recent view and the hosts sorted and grouped by the settings.
+    fn toggle_recent_view(&mut self) {
+        self.recent_view = !self.recent_view;
+        if let Err(e) = self.reload_hosts() {
+            self.set_feedback_message(format!("Failed to reload the hosts: {e}"), true);
+            return;
+        }
+
+        self.table_state.select(Some(0));
+        let message = if self.recent_view {
+            "Recent hosts, most recently connected first (R to leave)"
+        } else {
+            "Hosts in the order of the settings"
+        };
+        self.set_feedback_message(message.to_string(), false);
+    }
+
+    /// Switches the presentation mode, hiding the destinations, users and ports of the hosts.
+    fn toggle_presentation(&mut self) {
+        self.presentation = !self.presentation;
+        self.calculate_table_columns_constraints();
+
+        let message = if self.presentation {
+            "Presentation mode, destinations are hidden (p to leave)"
+        } else {
+            "Presentation mode off"
+        };
+        self.set_feedback_message(message.to_string(), false);
+    }
+
+    /// Number of hosts of each group.
+    #[must_use]
+    pub fn group_sizes(&self) -> BTreeMap<String, usize> {
+        let mut sizes = BTreeMap::new();
+        for host in self.hosts.non_filtered_iter() {
+            *sizes.entry(self.group_mode().group_of(host)).or_default() += 1;
+        }
+
+        sizes
+    }
+
+    /// Text of the group column on the first host of `group`, e.g. `▸ work (12)` when collapsed.
+    #[must_use]
+    pub fn group_label(&self, group: &str, size: usize) -> String {
+        if group.is_empty() {
+            String::new()
+        } else if self.collapsed_groups.contains(group) {
+            format!("▸ {group} ({size})")
+        } else {
+            format!("▾ {group}")
+        }
+    }
+
+    /// Collapses (or expands) the group of the selected host, keeping it selected.
+    fn toggle_selected_group(&mut self) {
+        if self.group_mode() == GroupMode::None {
+            self.set_feedback_message(
+                "Group the hosts first, in the settings (,)".to_string(),
+                true,
+            );
+            return;
+        }
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            return;
+        }
+
+        let group = self.group_mode().group_of(&self.hosts[selected]);
+        if group.is_empty() {
+            self.set_feedback_message("This host is in no group".to_string(), true);
+            return;
+        }
+        if !self.collapsed_groups.remove(&group) {
+            self.collapsed_groups.insert(group.clone());
+        }
+
+        self.refresh_search();
+        let position = self
+            .hosts
+            .iter()
+            .position(|host| self.group_mode().group_of(host) == group);
+        self.table_state.select(Some(position.unwrap_or(0)));
+        self.calculate_table_columns_constraints();
+    }
+
+    /// Replaces the search with the saved search `name`.
+    fn apply_saved_search(&mut self, name: &str) {
+        let Some(query) = self.state.saved_search(name).map(str::to_string) else {
+            self.set_feedback_message(format!("Unknown saved search '{name}'"), true);
+            return;
+        };
+
+        self.search = Input::from(query);
+        self.refresh_search();
+        self.table_state.select(Some(0));
+        self.set_feedback_message(
+            format!("Search '{name}': {} host(s)", self.hosts.len()),
+            false,
+        );
+    }
+
+    /// Opens the workspace name prompt, ↑ recalls the saved names
+    fn open_workspace_prompt(&mut self, kind: PromptKind) {
+        let names = self.state.workspaces.keys().cloned().collect();
+        self.prompt = Some(Prompt::new(kind, names));
+    }
+
+    /// Replaces the open tabs with the sessions of the saved workspace `name`. Hosts that are
+    /// no longer in the configuration are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if there is no workspace `name`.
+    pub fn open_workspace(&mut self, name: &str) -> Result<()> {
+        if self.config.no_exec {
+            anyhow::bail!(NO_EXEC_SESSIONS);
+        }
+        let Some(host_names) = self.state.workspace(name) else {
+            anyhow::bail!("Unknown workspace '{name}'");
+        };
+
+        let mut hosts = Vec::new();
+        let mut missing = Vec::new();
+        for host_name in host_names {
+            match self
+                .hosts
+                .non_filtered_iter()
+                .find(|host| &host.name == host_name)
+            {
+                Some(host) => hosts.push(host.clone()),
+                None => missing.push(host_name.clone()),
+            }
+        }
+
+        self.tab_manager.close_all();
+        self.session_shown = false;
+        for host in hosts {
+            self.tab_manager.add_session(host)?;
+        }
+
+        if missing.is_empty() {
+            self.set_feedback_message(
+                format!(
+                    "Workspace '{name}' opened ({} sessions)",
+                    self.tab_manager.session_count()
+                ),
+                false,
+            );
+        } else {
+            self.set_feedback_message(
+                format!(
+                    "Workspace '{name}' opened, unknown hosts skipped: {}",
+                    missing.join(", ")
+                ),
+                true,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Connects to `target` through the selected host, overriding the configured jump host.
+    fn connect_through_selected_jump_host<B>(
+        &mut self,
+        terminal: &Rc<RefCell<Terminal<B>>>,
+        target: &str,
+    ) -> Result<AppKeyAction>
+    where
+        B: Backend + std::io::Write,
+    {
+        let selected = self.table_state.selected().unwrap_or(0);
+        let Some(jump_host) = self
+            .hosts
+            .iter()
+            .nth(selected)
+            .map(|host| host.name.clone())
+        else {
+            return Ok(AppKeyAction::Ok);
+        };
+        if jump_host == target {
+            self.set_feedback_message(format!("{target} cannot be its own jump host"), true);
+            return Ok(AppKeyAction::Ok);
+        }
+
+        // The search may have hidden the target while picking the jump host
+        if !self.hosts.iter().any(|host| host.name == target) {
+            self.search = Input::default();
+            self.refresh_search();
+        }
+        let Some(index) = self.hosts.iter().position(|host| host.name == target) else {
+            self.set_feedback_message(format!("{target} is no longer in the configuration"), true);
+            return Ok(AppKeyAction::Ok);
+        };
+        self.table_state.select(Some(index));
+
+        let options = ssh::ConnectOptions {
+            jump_host: Some(jump_host),
+            ..Default::default()
+        };
+        self.request_connection(terminal, options)
+    }
+
+    fn open_edit_host_form(&mut self) {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            self.set_feedback_message("No host selected for editing".to_string(), true);
+            return;
+        }
+
+        let host = &self.hosts[selected];
+
+        // System files are root-owned, offer an override or sudoedit instead
+        if host.source_file.as_deref().is_some_and(is_system_config) {
+            self.confirm_message = Some(system_edit_message(host));
+            self.confirm_action = Some(SYSTEM_EDIT_ACTION.to_string());
+            self.form_state = FormState::Confirming;
+            self.editing_host_index = Some(selected);
+            return;
+        }
+
+        let mut form = AddHostForm::new();
+
+        // Pre-populate the form with existing host data
+        form.populate_from_host(host);
+        form.set_existing_blocks(self.existing_host_blocks(Some(&host.name)));
+        form.set_known_destinations(self.known_destinations());
+
+        self.add_host_form = Some(form);
+        self.form_state = FormState::Active;
+        self.feedback_message = None;
+        self.feedback_timeout = None;
+        self.is_edit_mode = true;
+        self.editing_host_index = Some(selected);
+    }
+
+    /// Raw host blocks of every configuration file, without the blocks declaring `excluded`.
+    /// Unreadable files are skipped, they only weaken the alias collision checks.
+    fn existing_host_blocks(&self, excluded: Option<&str>) -> Vec<ssh_config::Host> {
+        self.config
+            .config_paths
+            .iter()
+            .filter_map(|path| {
+                ssh_config::Parser::new()
+                    .parse_file(shellexpand::tilde(path).to_string())
+                    .ok()
+            })
+            .flatten()
+            .filter(|block| {
+                !excluded.is_some_and(|name| block.get_patterns().iter().any(|p| p == name))
+            })
+            .collect()
+    }
+
+    /// Destinations suggested in the hostname field: those of the hosts, and the hosts of
+    /// `~/.ssh/known_hosts`.
+    fn known_destinations(&self) -> Vec<String> {
+        let known_hosts = PathBuf::from(shellexpand::tilde(KNOWN_HOSTS_PATH).to_string());
+
+        self.hosts
+            .non_filtered_iter()
+            .map(|host| host.destination.clone())
+            .chain(known_hosts_destinations(&known_hosts))
+            .collect()
+    }
+
+    /// Makes sure the managed file can receive new hosts. Returns `true` when the main
+    /// configuration does not include it yet and the user has to consent to adding the directive.
+    fn prepare_managed_config(&self) -> Result<bool> {
+        let Some(managed_path) = &self.config.managed_config_path else {
+            return Ok(false);
+        };
+
+        let main_path = shellexpand::tilde(&self.config.config_paths[1]).to_string();
+        if !managed_config::is_included(&main_path, managed_path)? {
+            return Ok(true);
+        }
+
+        managed_config::ensure_exists(managed_path)?;
+        Ok(false)
+    }
+
+    /// Configuration file new hosts are written to.
+    fn new_host_config_path(&self) -> String {
+        let path = self
+            .config
+            .managed_config_path
+            .as_ref()
+            .unwrap_or(&self.config.config_paths[1]);
+
+        shellexpand::tilde(path).to_string()
+    }
+
+    /// Configuration file declaring `host`, falling back to the user configuration.
+    fn host_config_path(&self, host: &ssh::Host) -> String {
+        match &host.source_file {
+            Some(path) => path.display().to_string(),
+            None => shellexpand::tilde(&self.config.config_paths[1]).to_string(),
+        }
+    }
+
+    /// Configuration file targeted by the host form being submitted.
+    fn form_config_path(&self) -> String {
+        match self.editing_host_index {
+            Some(index) if self.is_edit_mode && index < self.hosts.len() => {
+                self.host_config_path(&self.hosts[index])
+            }
+            _ => self.new_host_config_path(),
+        }
+    }
+
+    fn save_new_host(&self) -> Result<()> {
+        if let Some(form) = &self.add_host_form {
+            let config_path = self.new_host_config_path();
+            let log = self.config.audit_log.as_deref();
+            let before = audit_log::snapshot(log, &config_path);
+            form.save_to_config(&config_path)?;
+
+            audit_log::record_change(
+                log,
+                "add host",
+                form.host_name.value(),
+                &config_path,
+                before,
+            )
+        } else {
+            Err(anyhow::anyhow!("Form is not initialized"))
+        }
+    }
+
+    fn update_existing_host(&self) -> Result<()> {
+        if let Some(form) = &self.add_host_form {
+            if let Some(host_index) = self.editing_host_index {
+                let original_host = &self.hosts[host_index];
+                let config_path = self.host_config_path(original_host);
+                let log = self.config.audit_log.as_deref();
+                let before = audit_log::snapshot(log, &config_path);
+                form.update_host_in_config(&config_path, original_host)?;
+
+                let action = if form.host_name.value() == original_host.name {
+                    "edit host"
+                } else {
+                    "rename host"
+                };
+                audit_log::record_change(log, action, &original_host.name, &config_path, before)
+            } else {
+                Err(anyhow::anyhow!("No host selected for editing"))
+            }
+        } else {
+            Err(anyhow::anyhow!("Form is not initialized"))
+        }
+    }
+
+    fn open_audit_log_view(&mut self) {
+        let Some(path) = &self.config.audit_log else {
+            self.set_feedback_message(
+                "No audit log, start sshs with --audit-log <file>".to_string(),
+                true,
+            );
+            return;
+        };
+
+        match audit_log::read(path) {
+            Ok(entries) if entries.is_empty() => {
+                self.set_feedback_message("No change recorded yet".to_string(), false);
+            }
+            Ok(entries) => self.audit_log = Some(AuditLogView::new(entries)),
+            Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+        }
+    }
+
+    /// Opens the timeline of the selected host, with the config changes of the audit log when
+    /// there is one.
+    fn open_timeline_of_selected_host(&mut self) {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            return;
+        }
+        let host = self.hosts[selected].name.clone();
+
+        let entries = match self.config.audit_log.as_deref().map(audit_log::read) {
+            Some(Ok(entries)) => entries,
+            Some(Err(e)) => {
+                self.set_feedback_message(format!("Error: {e}"), true);
+                return;
+            }
+            None => Vec::new(),
+        };
+
+        let events = timeline::collect(&host, &self.state, &entries);
+        if events.is_empty() {
+            self.set_feedback_message(format!("Nothing recorded for '{host}' yet"), false);
+            return;
+        }
+        self.timeline = Some(TimelineView::new(host, events));
+    }
+
+    fn handle_timeline_keys(&mut self, key: KeyEvent) -> AppKeyAction {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        let Some(view) = &mut self.timeline else {
+            return AppKeyAction::Continue;
+        };
+
+        if view.searching {
+            match key.code {
+                Enter | Esc => view.searching = false,
+                Backspace => {
+                    let mut search = view.search.clone();
+                    search.pop();
+                    view.set_search(search);
+                }
+                Char(c) => view.set_search(format!("{}{c}", view.search)),
+                _ => {}
+            }
+            return AppKeyAction::Ok;
+        }
+
+        match key.code {
+            Esc if !view.search.is_empty() => view.set_search(String::new()),
+            Esc | Char('q') => self.timeline = None,
+            Up | Char('k') => view.previous(),
+            Down | Char('j') => view.next(),
+            Char('/') => view.searching = true,
+            Char('x') => {
+                let path = timeline::export_path(&view.host);
+                match view.export(&path) {
+                    Ok(()) => self.set_feedback_message(format!("Exported to {path}"), false),
+                    Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+                }
+            }
+            _ => {}
+        }
+
+        AppKeyAction::Ok
+    }
+
+    /// Selected host as connected to outside of a session (background tunnels, file
+    /// transfers), `None` with a message for template hosts.
+    fn selected_connection_host(&mut self) -> Option<ssh::Host> {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            return None;
+        }
+        let mut host = self.through_fallback(self.hosts[selected].clone().for_connection());
+        if host.is_template() {
+            self.set_feedback_message(
+                format!("'{}' needs a value, connect to it instead", host.name),
+                true,
+            );
+            return None;
+        }
+        if host.user.is_none() {
+            host.user.clone_from(&self.config.default_user);
+        }
+
+        Some(host)
+    }
+
+    /// Starts a SOCKS proxy through the selected host as a systemd user unit, which keeps
+    /// running once sshs exits.
+    fn start_background_socks(&mut self) {
+        let Some(host) = self.selected_connection_host() else {
+            return;
+        };
+
+        let forward = Forward::Dynamic {
+            port: self.config.socks_port,
+        };
+        match background_tunnels::start(&host, &forward) {
+            Ok(unit) => {
+                self.set_feedback_message(
+                    format!(
+                        "{} running as {unit}, stop it from (B)",
+                        forward.describe(&host.name)
+                    ),
+                    false,
+                );
+                self.socks_units.insert(unit);
+            }
+            Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+        }
+    }
+
+    /// Starts the forward typed in the prompt through the selected host, and saves it for the
+    /// host so that `L` starts it again.
+    fn start_forward(&mut self, value: &str) {
+        let Some(host) = self.selected_connection_host() else {
+            return;
+        };
+
+        let result = Forward::parse(value).and_then(|forward| {
+            let unit = background_tunnels::start(&host, &forward)?;
+            Ok((forward, unit))
+        });
+        match result {
+            Ok((forward, unit)) => {
+                if background_tunnels::socks_port(&unit).is_some() {
+                    self.socks_units.insert(unit.clone());
+                }
+                self.state.record_forward(&host.name, &forward.to_string());
+                self.state.mark_changed();
+                self.set_feedback_message(
+                    format!(
+                        "{} running as {unit}, stop it from (B)",
+                        forward.describe(&host.name)
+                    ),
+                    false,
+                );
+            }
+            Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+        }
+    }
+
+    /// Starts the forwards saved for the selected host which are not running already.
+    fn start_saved_forwards(&mut self) {
+        let Some(host) = self.selected_connection_host() else {
+            return;
+        };
+        let forwards = self.state.forwards(&host.name);
+        if forwards.is_empty() {
+            self.set_feedback_message(
+                format!("No saved forward for '{}', add one with (t)", host.name),
+                true,
+            );
+            return;
+        }
+
+        let running = match background_tunnels::list() {
+            Ok(units) => {
+                self.socks_units = background_tunnels::socks_units(&units);
+                units
+                    .into_iter()
+                    .filter(|unit| unit.state == "active")
+                    .map(|unit| unit.name)
+                    .collect::<Vec<_>>()
+            }
+            Err(e) => {
+                self.set_feedback_message(format!("Error: {e}"), true);
+                return;
+            }
+        };
+
+        let (mut started, mut skipped, mut errors) = (0, 0, Vec::new());
+        for forward in forwards
+            .iter()
+            .filter_map(|value| Forward::parse(value).ok())
+        {
+            let unit = format!(
+                "{}.service",
+                background_tunnels::unit_name(&host.name, &forward)
+            );
+            if running.contains(&unit) {
+                skipped += 1;
+                continue;
+            }
+            match background_tunnels::start(&host, &forward) {
+                Ok(unit) => {
+                    started += 1;
+                    if background_tunnels::socks_port(&unit).is_some() {
+                        self.socks_units.insert(unit);
+                    }
+                }
+                Err(e) => errors.push(format!("{forward}: {e}")),
+            }
+        }
+
+        let skipped = if skipped > 0 {
+            format!(", {skipped} already running")
+        } else {
+            String::new()
+        };
+        let message = if errors.is_empty() {
+            format!(
+                "Started {started} forward(s) of '{}'{skipped}, see them in (B)",
+                host.name
+            )
+        } else {
+            format!(
+                "Started {started} forward(s) of '{}'{skipped}. Error: {}",
+                host.name,
+                errors.join("; ")
+            )
+        };
+        self.set_feedback_message(message, !errors.is_empty());
+    }
+
+    /// Local ports of the SOCKS proxies running: those of the sessions in tabs opened with `D`,
+    /// and the ones in the background.
+    #[must_use]
+    pub fn socks_ports(&self) -> Vec<u16> {
+        let mut ports: Vec<u16> = self
+            .tab_manager
+            .sessions()
+            .iter()
+            .filter(|session| session.is_connected())
+            .filter_map(|session| session.options.dynamic_forward)
+            .chain(
+                self.socks_units
+                    .iter()
+                    .filter_map(|unit| background_tunnels::socks_port(unit)),
+            )
+            .collect();
+        ports.sort_unstable();
+        ports.dedup();
+        ports
+    }
+
+    fn open_background_tunnels_view(&mut self) {
+        match background_tunnels::list() {
+            Ok(units) if units.is_empty() => {
+                self.socks_units.clear();
+                self.set_feedback_message("No background tunnel running".to_string(), false);
             }
-            Char('3') => {
-                // Ctrl+3 to switch to third tab
-                self.tab_manager.switch_to_session(3);
-                AppKeyAction::Ok
+            Ok(units) => {
+                self.socks_units = background_tunnels::socks_units(&units);
+                self.background_tunnels = Some(TunnelsView::new(units));
             }
-            _ => AppKeyAction::Continue,
+            Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
         }
     }
 
-    #[allow(clippy::too_many_lines)]
-    fn on_form_key_press(&mut self, key: KeyEvent) -> Result<AppKeyAction> {
+    fn handle_background_tunnels_keys(&mut self, key: KeyEvent) -> AppKeyAction {
         #[allow(clippy::enum_glob_use)]
         use KeyCode::*;
 
-        // If we're in confirmation mode, handle that first
-        if self.form_state == FormState::Confirming {
-            match key.code {
-                Esc | Char('n' | 'N') => {
-                    // Cancel the confirmation
-                    self.form_state = FormState::Active;
-                    self.confirm_message = None;
-                    self.confirm_action = None;
-                    return Ok(AppKeyAction::Ok);
+        let Some(view) = &mut self.background_tunnels else {
+            return AppKeyAction::Continue;
+        };
+
+        match key.code {
+            Esc | Char('q') => self.background_tunnels = None,
+            Up | Char('k') => view.previous(),
+            Down | Char('j') => view.next(),
+            Char('r') => match background_tunnels::list() {
+                Ok(units) => {
+                    self.socks_units = background_tunnels::socks_units(&units);
+                    view.set_units(units);
                 }
-                Enter | Char('y' | 'Y') => {
-                    // Check if this is a delete confirmation
-                    if let Some(action) = &self.confirm_action {
-                        if action == "Delete" {
-                            // Handle host deletion
-                            self.form_state = FormState::Hidden;
-                            let result = self.delete_selected_host();
+                Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+            },
+            Char('x' | 'd') => {
+                let Some(unit) = view.selected_unit().map(|unit| unit.name.clone()) else {
+                    return AppKeyAction::Ok;
+                };
+                let result = background_tunnels::stop(&unit).and_then(|()| {
+                    let units = background_tunnels::list()?;
+                    self.socks_units = background_tunnels::socks_units(&units);
+                    view.set_units(units);
+                    Ok(())
+                });
+                match result {
+                    Ok(()) => self.set_feedback_message(format!("Stopped {unit}"), false),
+                    Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+                }
+            }
+            _ => {}
+        }
 
-                            match result {
-                                Ok(()) => {
-                                    self.confirm_message = None;
-                                    self.confirm_action = None;
-                                    self.editing_host_index = None;
-                                    return Ok(AppKeyAction::Ok);
-                                }
-                                Err(e) => {
-                                    self.set_feedback_message(
-                                        format!("Error deleting host: {e}"),
-                                        true,
-                                    );
-                                    self.confirm_message = None;
-                                    self.confirm_action = None;
-                                    self.editing_host_index = None;
-                                    return Ok(AppKeyAction::Ok);
-                                }
-                            }
-                        }
-                    }
+        AppKeyAction::Ok
+    }
 
-                    // Proceed with saving (existing functionality)
-                    self.form_state = FormState::Active;
+    /// Marks the selected host, or unmarks it.
+    fn toggle_mark(&mut self) {
+        let selected = self.table_state.selected().unwrap_or(0);
+        let Some(host) = self.hosts.iter().nth(selected) else {
+            return;
+        };
+        if !self.marked_hosts.remove(&host.name) {
+            self.marked_hosts.insert(host.name.clone());
+        }
+        self.next();
+    }
 
-                    // Save the host (we already validated it's valid)
-                    let result = if self.is_edit_mode {
-                        self.update_existing_host()
-                    } else {
-                        self.save_new_host()
-                    };
+    /// Starts a visual range at the selected row, or marks the hosts of the current one.
+    fn toggle_visual_range(&mut self) {
+        let selected = self.table_state.selected().unwrap_or(0);
+        let Some(anchor) = self.visual_anchor.take() else {
+            self.visual_anchor = Some(selected);
+            return;
+        };
 
-                    match result {
-                        Ok(()) => {
-                            let message = if self.is_edit_mode {
-                                "Host updated successfully!"
-                            } else {
-                                "Host added successfully!"
-                            };
-                            self.set_feedback_message(message.to_string(), false);
-                            self.form_state = FormState::Hidden;
-                            self.add_host_form = None;
-                            self.confirm_message = None;
-                            self.confirm_action = None;
-                            self.is_edit_mode = false;
-                            self.editing_host_index = None;
+        let range = anchor.min(selected)..=anchor.max(selected);
+        let names = self
+            .hosts
+            .iter()
+            .skip(*range.start())
+            .take(range.count())
+            .map(|host| host.name.clone())
+            .collect::<Vec<_>>();
+        self.marked_hosts.extend(names);
+    }
 
-                            // Reload the hosts
-                            self.reload_hosts()?;
+    /// Whether the host of row `index` is marked, or in the visual range.
+    #[must_use]
+    pub fn is_marked(&self, index: usize, host: &ssh::Host) -> bool {
+        let selected = self.table_state.selected().unwrap_or(0);
+        let in_range = self
+            .visual_anchor
+            .is_some_and(|anchor| (anchor.min(selected)..=anchor.max(selected)).contains(&index));
 
-                            return Ok(AppKeyAction::Ok);
-                        }
-                        Err(e) => {
-                            self.set_feedback_message(format!("Error: {e}"), true);
-                            self.confirm_message = None;
-                            self.confirm_action = None;
-                            return Ok(AppKeyAction::Ok);
-                        }
-                    }
+        in_range || self.marked_hosts.contains(&host.name)
+    }
+
+    /// Hosts a batch run goes through: the marked ones in the order of the list, or the
+    /// selected one when none is marked. Template hosts are left out.
+    fn batch_run_targets(&self) -> Vec<ssh::Host> {
+        let selected = self.table_state.selected().unwrap_or(0);
+        let hosts = if self.marked_hosts.is_empty() {
+            self.hosts.iter().skip(selected).take(1).collect::<Vec<_>>()
+        } else {
+            self.hosts
+                .non_filtered_iter()
+                .filter(|host| self.marked_hosts.contains(&host.name))
+                .collect()
+        };
+
+        hosts
+            .into_iter()
+            .map(|host| host.clone().for_connection())
+            .filter(|host| !host.is_template())
+            .map(|mut host| {
+                if host.user.is_none() {
+                    host.user.clone_from(&self.config.default_user);
+                }
+                host
+            })
+            .collect()
+    }
+
+    /// Runs `command` on the marked hosts in the background, and opens the results pane.
+    fn start_batch_run(&mut self, command: &str, mode: batch_run::Mode) {
+        if let Err(e) = ssh::validate_command_template(command) {
+            self.set_feedback_message(format!("Error: {e}"), true);
+            return;
+        }
+        let hosts = self.batch_run_targets();
+        if hosts.is_empty() {
+            self.set_feedback_message("No host to run on".to_string(), true);
+            return;
+        }
+
+        self.state.record_batch_command(command);
+        self.state.mark_changed();
+        self.batch_run = Some(BatchRun::start(&hosts, command, mode));
+    }
+
+    /// Reports the batch run once every host is done.
+    fn poll_batch_run(&mut self) {
+        let Some(run) = &mut self.batch_run else {
+            return;
+        };
+        if run.poll() {
+            let is_error = !run.hosts.iter().all(batch_run::HostRun::succeeded);
+            let message = format!("Ran '{}': {}", run.command, run.status());
+            self.set_feedback_message(message, is_error);
+        }
+    }
+
+    /// Looks at the routes every few seconds. After a change, the destinations are resolved
+    /// again, and the hosts with a fallback are checked, recorded in the timeline and switched
+    /// to or from their fallback, with a message saying so.
+    fn poll_network(&mut self) {
+        let Some(watch) = &mut self.network else {
+            return;
+        };
+
+        let now = Instant::now();
+        if watch.is_due(now) {
+            let hosts: Vec<ssh::Host> = self.hosts.non_filtered_iter().cloned().collect();
+            if watch.update(Network::current(), &hosts, now) {
+                if let Some(dns) = &mut self.dns {
+                    dns.forget();
                 }
-                _ => return Ok(AppKeyAction::Continue),
             }
         }
 
-        // Normal form handling
+        let Some(outcome) = watch.poll() else {
+            return;
+        };
+        let time = u64::try_from(certificate::now()).unwrap_or_default();
+        for result in &outcome.results {
+            let reachable = if result.reachable {
+                "reachable"
+            } else {
+                "unreachable"
+            };
+            if self
+                .state
+                .record_health(&result.host, &result.check, reachable, time)
+            {
+                self.state.mark_changed();
+            }
+        }
+        if let Some(summary) = outcome.summary {
+            self.set_feedback_message(summary, false);
+        }
+    }
+
+    /// Starts the round of health checks when due, and records the answers of the hosts in
+    /// their timeline.
+    fn poll_health(&mut self) {
+        let Some(checker) = &mut self.health else {
+            return;
+        };
+
+        let now = Instant::now();
+        if checker.is_due(now) {
+            let hosts: Vec<ssh::Host> = self.hosts.non_filtered_iter().cloned().collect();
+            checker.refresh(&hosts, now);
+        }
+
+        let time = u64::try_from(certificate::now()).unwrap_or_default();
+        let results = checker.poll();
+        let answered = !results.is_empty();
+        for result in results {
+            let reachable = if result.health.is_reachable() {
+                "reachable"
+            } else {
+                "unreachable"
+            };
+            let recorded = self
+                .state
+                .record_health(&result.host, &result.check, reachable, time);
+            let sampled =
+                self.state
+                    .record_health_sample(&result.host, result.health.is_reachable(), time);
+            if recorded || sampled {
+                self.state.mark_changed();
+            }
+        }
+        // The states and their counts changed
+        if answered {
+            self.refresh_search();
+        }
+    }
+
+    /// Checks the reachability of the listed hosts now, showing the column of the results the
+    /// first time.
+    fn check_health(&mut self) {
+        if self.health.is_none() {
+            self.health = Some(HealthChecker::new(None));
+            self.calculate_table_columns_constraints();
+        }
+        let Some(checker) = &mut self.health else {
+            return;
+        };
+
+        let hosts: Vec<ssh::Host> = self.hosts.iter().cloned().collect();
+        let queued = checker.refresh(&hosts, Instant::now());
+        let message = if queued == 0 {
+            "No host to check, hosts behind a proxy cannot be checked from here".to_string()
+        } else {
+            format!("Checking the SSH port of {queued} hosts...")
+        };
+        self.set_feedback_message(message, queued == 0);
+    }
+
+    /// `host` as connected to, its fallback when its destination cannot be reached from the
+    /// current network.
+    fn through_fallback(&self, host: ssh::Host) -> ssh::Host {
+        let fallback = self
+            .network
+            .as_ref()
+            .and_then(|network| network.fallback_of(&host.name));
+
+        fallback
+            .and_then(|name| {
+                self.hosts
+                    .non_filtered_iter()
+                    .find(|other| other.name == name)
+            })
+            .map_or(host, |fallback| fallback.clone().for_connection())
+    }
+
+    fn handle_batch_run_keys(&mut self, key: KeyEvent) -> AppKeyAction {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
+
+        let Some(run) = &mut self.batch_run else {
+            return AppKeyAction::Continue;
+        };
+
         match key.code {
-            Esc => Ok(AppKeyAction::Stop),
-            Enter => {
-                if let Some(form) = &self.add_host_form {
-                    if form.is_valid() {
-                        // Check if the host already exists
-                        let config_path =
-                            shellexpand::tilde(&self.config.config_paths[1]).to_string();
-                        match form.check_duplicate(&config_path) {
-                            Ok(true) => {
-                                // Host exists, show confirmation dialog
-                                self.confirm_message = Some(format!(
-                                    "Host '{}' already exists. Overwrite?",
-                                    form.host_name.value().trim()
-                                ));
-                                self.confirm_action = Some("Overwrite".to_string());
-                                self.form_state = FormState::Confirming;
-                                return Ok(AppKeyAction::Confirm);
-                            }
-                            Ok(false) => {
-                                // No duplicate, proceed with saving
-                                let result = if self.is_edit_mode {
-                                    self.update_existing_host()
-                                } else {
-                                    self.save_new_host()
-                                };
+            // The hosts still running finish unseen
+            Esc | Char('q') => self.batch_run = None,
+            Up | Char('k') => run.previous(),
+            Down | Char('j') => run.next(),
+            _ => {}
+        }
 
-                                match result {
-                                    Ok(()) => {
-                                        let message = if self.is_edit_mode {
-                                            "Host updated successfully!"
-                                        } else {
-                                            "Host added successfully!"
-                                        };
-                                        self.set_feedback_message(message.to_string(), false);
-                                        self.form_state = FormState::Hidden;
-                                        self.add_host_form = None;
-                                        self.is_edit_mode = false;
-                                        self.editing_host_index = None;
+        AppKeyAction::Ok
+    }
 
-                                        // Reload the hosts
-                                        self.reload_hosts()?;
+    /// Opens the SFTP browser on the current directory and the home directory of the selected
+    /// host.
+    fn open_sftp_browser(&mut self) {
+        let Some(host) = self.selected_connection_host() else {
+            return;
+        };
+        let local_path = std::env::current_dir()
+            .map_or_else(|_| "/".to_string(), |path| path.display().to_string());
 
-                                        return Ok(AppKeyAction::Ok);
-                                    }
-                                    Err(e) => {
-                                        self.set_feedback_message(format!("Error: {e}"), true);
-                                        return Ok(AppKeyAction::Ok);
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                // Error checking for duplicates
-                                self.set_feedback_message(
-                                    format!("Error checking for duplicates: {e}"),
-                                    true,
-                                );
-                                return Ok(AppKeyAction::Ok);
-                            }
-                        }
-                    }
+        match SftpView::open(self.backend.as_ref(), host, local_path) {
+            Ok(view) => self.sftp = Some(view),
+            Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+        }
+    }
 
-                    // Show specific validation error message
-                    if let Some(error_message) = form.validation_error() {
-                        self.set_feedback_message(error_message, true);
-                    } else {
-                        self.set_feedback_message("Invalid form data".to_string(), true);
-                    }
+    fn handle_sftp_keys(&mut self, key: KeyEvent) -> AppKeyAction {
+        #[allow(clippy::enum_glob_use)]
+        use KeyCode::*;
 
-                    return Ok(AppKeyAction::Ok);
-                }
-                Ok(AppKeyAction::Continue)
+        let Some(view) = &mut self.sftp else {
+            return AppKeyAction::Continue;
+        };
+
+        let result = match key.code {
+            Esc | Char('q') => {
+                self.sftp = None;
+                Ok(())
             }
-            Tab => {
-                if let Some(form) = &mut self.add_host_form {
-                    form.next_field();
-                    return Ok(AppKeyAction::Ok);
-                }
-                Ok(AppKeyAction::Continue)
+            Tab | BackTab => {
+                view.switch_side();
+                Ok(())
             }
-            BackTab => {
-                if let Some(form) = &mut self.add_host_form {
-                    form.previous_field();
-                    return Ok(AppKeyAction::Ok);
+            Up | Char('k') => {
+                view.pane().previous();
+                Ok(())
+            }
+            Down | Char('j') => {
+                view.pane().next();
+                Ok(())
+            }
+            Enter | Right | Char('l') => {
+                let pane = view.pane();
+                match pane.selected_entry().filter(|entry| entry.is_dir) {
+                    Some(entry) => {
+                        let path = sftp::join(&pane.path, &entry.name);
+                        view.change_dir(self.backend.as_ref(), &path)
+                    }
+                    None => Ok(()),
                 }
-                Ok(AppKeyAction::Continue)
             }
-            _ => Ok(AppKeyAction::Continue),
+            Backspace | Left | Char('h') => {
+                let path = sftp::parent(&view.pane().path);
+                view.change_dir(self.backend.as_ref(), &path)
+            }
+            Char('r') => view.refresh(self.backend.as_ref(), view.side),
+            Char('d') => self.start_transfer(Direction::Download),
+            Char('u') => self.start_transfer(Direction::Upload),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            self.set_feedback_message(format!("Error: {e}"), true);
         }
+
+        AppKeyAction::Ok
     }
 
-    fn next(&mut self) {
-        let i = match self.table_state.selected() {
-            Some(i) => {
-                if self.hosts.is_empty() || i >= self.hosts.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
+    /// Copies the file selected on the remote side to the local directory, or the other way
+    /// around. Files are not overwritten.
+    fn start_transfer(&mut self, direction: Direction) -> Result<()> {
+        if let Some(transfer) = &self.transfer {
+            bail!("Wait for {} to complete", transfer.name);
+        }
+        let Some(view) = &self.sftp else {
+            return Ok(());
         };
-        self.table_state.select(Some(i));
+
+        let (pane, target) = match direction {
+            Direction::Download => (&view.remote, &view.local),
+            Direction::Upload => (&view.local, &view.remote),
+        };
+        let Some(entry) = pane.selected_entry().filter(|entry| !entry.is_dir) else {
+            bail!("Select a file first");
+        };
+        if target.entries.iter().any(|other| other.name == entry.name) {
+            bail!("{} already exists", sftp::join(&target.path, &entry.name));
+        }
+
+        let source = sftp::join(&pane.path, &entry.name);
+        let destination = sftp::join(&target.path, &entry.name);
+        let (remote, local) = match direction {
+            Direction::Download => (source, destination),
+            Direction::Upload => (destination, source),
+        };
+        self.transfer = Some(Transfer::start(
+            &view.host, direction, &remote, &local, entry.size,
+        ));
+
+        Ok(())
     }
 
-    fn previous(&mut self) {
-        let i = match self.table_state.selected() {
-            Some(i) => {
-                if self.hosts.is_empty() {
-                    0
-                } else if i == 0 {
-                    self.hosts.len() - 1
-                } else {
-                    i - 1
+    /// Reports the file transfer once it is done, and lists the directory it went to again.
+    fn poll_transfer(&mut self) {
+        let Some(result) = self.transfer.as_mut().and_then(Transfer::poll) else {
+            return;
+        };
+        let Some(transfer) = self.transfer.take() else {
+            return;
+        };
+
+        let (side, done) = match transfer.direction {
+            Direction::Download => (Side::Local, "Downloaded"),
+            Direction::Upload => (Side::Remote, "Uploaded"),
+        };
+        match result {
+            Ok(()) => {
+                if let Some(view) = &mut self.sftp {
+                    let _ = view.refresh(self.backend.as_ref(), side);
                 }
+                self.set_feedback_message(format!("{done} {}", transfer.name), false);
             }
-            None => 0,
-        };
-        self.table_state.select(Some(i));
+            Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+        }
     }
 
-    pub fn calculate_table_columns_constraints(&mut self) {
-        let mut lengths = Vec::new();
+    fn open_backups_view(&mut self) {
+        let view = BackupsView::new(backups::find(self.config_files()));
+        if view.backups.is_empty() {
+            self.set_feedback_message("No configuration backups found".to_string(), false);
+            return;
+        }
 
-        let name_len = self
-            .hosts
+        self.backups = Some(view);
+    }
+
+    /// Every configuration file sshs may edit: the configured paths, the managed file and the
+    /// files hosts were declared in.
+    fn config_files(&self) -> Vec<PathBuf> {
+        self.config
+            .config_paths
             .iter()
-            .map(|d| d.name.as_str())
-            .map(UnicodeWidthStr::width)
-            .max()
-            .unwrap_or(0);
-        lengths.push(name_len);
+            .chain(&self.config.managed_config_path)
+            .map(|path| PathBuf::from(shellexpand::tilde(path).to_string()))
+            .chain(
+                self.hosts
+                    .non_filtered_iter()
+                    .filter_map(|host| host.source_file.clone()),
+            )
+            .collect()
+    }
 
-        let aliases_len = self
-            .hosts
-            .non_filtered_iter()
-            .map(|d| d.aliases.as_str())
-            .map(UnicodeWidthStr::width)
-            .max()
-            .unwrap_or(0);
-        lengths.push(aliases_len);
+    fn open_delete_host_confirmation(&mut self) {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            self.set_feedback_message("No host selected for deletion".to_string(), true);
+            return;
+        }
 
-        let user_len = self
-            .hosts
-            .non_filtered_iter()
-            .map(|d| match &d.user {
-                Some(user) => user.as_str(),
-                None => "",
-            })
-            .map(UnicodeWidthStr::width)
-            .max()
-            .unwrap_or(0);
-        lengths.push(user_len);
+        let host = &self.hosts[selected];
+        self.confirm_message = Some(format!(
+            "Delete host '{}'? This action cannot be undone.",
+            host.name
+        ));
+        self.confirm_action = Some("Delete".to_string());
+        self.form_state = FormState::Confirming;
+        self.editing_host_index = Some(selected);
+    }
 
-        let destination_len = self
-            .hosts
-            .non_filtered_iter()
-            .map(|d| d.destination.as_str())
-            .map(UnicodeWidthStr::width)
-            .max()
-            .unwrap_or(0);
-        lengths.push(destination_len);
+    fn expired_hosts(&self) -> Vec<ssh::Host> {
+        let today = ssh::today();
 
-        let port_len = self
-            .hosts
+        self.hosts
             .non_filtered_iter()
-            .map(|d| match &d.port {
-                Some(port) => port.as_str(),
-                None => "",
-            })
-            .map(UnicodeWidthStr::width)
-            .max()
-            .unwrap_or(0);
-        lengths.push(port_len);
+            .filter(|host| host.is_expired(today))
+            .cloned()
+            .collect()
+    }
 
-        if self.config.show_proxy_command {
-            let proxy_len = self
-                .hosts
-                .non_filtered_iter()
-                .map(|d| match &d.proxy_command {
-                    Some(proxy) => proxy.as_str(),
-                    None => "",
-                })
-                .map(UnicodeWidthStr::width)
-                .max()
-                .unwrap_or(0);
-            lengths.push(proxy_len);
+    /// Lists the hosts past their expiry date and offers to remove them all.
+    fn open_expired_hosts_report(&mut self) {
+        let expired = self.expired_hosts();
+        if expired.is_empty() {
+            self.set_feedback_message("No expired hosts".to_string(), false);
+            return;
         }
 
-        self.table_columns_constraints = vec![
-            // +COLUMN_PADDING for padding
-            Constraint::Length(u16::try_from(lengths[0]).unwrap_or_default() + COLUMN_PADDING),
-        ];
-        self.table_columns_constraints.extend(
-            lengths.iter().skip(1).map(|len| {
-                Constraint::Min(u16::try_from(*len).unwrap_or_default() + COLUMN_PADDING)
-            }),
-        );
+        let mut message = format!("{} expired host(s):\n", expired.len());
+        for host in expired.iter().take(REPORT_MAX_HOSTS) {
+            let date = host.metadata_value("expires").unwrap_or_default();
+            let _ = writeln!(message, "  {} (expired {date})", host.name);
+        }
+        if expired.len() > REPORT_MAX_HOSTS {
+            let _ = writeln!(
+                message,
+                "  ... and {} more",
+                expired.len() - REPORT_MAX_HOSTS
+            );
+        }
+        message.push_str("\nRemove them from the SSH configuration?");
+
+        self.confirm_message = Some(message);
+        self.confirm_action = Some(EXPIRED_CLEANUP_ACTION.to_string());
+        self.form_state = FormState::Confirming;
     }
 
-    fn open_add_host_form(&mut self) {
-        self.add_host_form = Some(AddHostForm::new());
-        self.form_state = FormState::Active;
-        self.feedback_message = None;
-        self.feedback_timeout = None;
-        self.is_edit_mode = false;
-        self.editing_host_index = None;
+    /// `hosts` by configuration file. Hosts declared in system configuration files are counted
+    /// apart, they are left alone.
+    fn hosts_by_file<'a>(
+        &self,
+        hosts: impl IntoIterator<Item = &'a ssh::Host>,
+    ) -> (BTreeMap<String, Vec<&'a ssh::Host>>, usize) {
+        let mut by_file: BTreeMap<String, Vec<&ssh::Host>> = BTreeMap::new();
+        let mut skipped = 0;
+        for host in hosts {
+            let config_path = self.host_config_path(host);
+            if is_system_config(Path::new(&config_path)) {
+                skipped += 1;
+                continue;
+            }
+            by_file.entry(config_path).or_default().push(host);
+        }
+
+        (by_file, skipped)
     }
 
-    fn open_new_session(&mut self) {
-        // For MVP, Ctrl+N creates a new session with the currently selected host
-        let selected = self.table_state.selected().unwrap_or(0);
-        if selected >= self.hosts.len() {
-            self.set_feedback_message("No host selected for new session".to_string(), true);
+    /// Lists the listed hosts whose tags `edit` changes, and asks to apply it.
+    fn open_tags_preview(&mut self, edit: TagEdit) {
+        let (by_file, skipped) = self.hosts_by_file(&self.hosts);
+        let mut changed = Vec::new();
+        for (config_path, hosts) in &by_file {
+            let content = std::fs::read_to_string(config_path).unwrap_or_default();
+            changed.extend(bulk_tags::rewrite_tags(&content, hosts, &edit).1);
+        }
+        if changed.is_empty() {
+            self.set_feedback_message("No host tags would change".to_string(), false);
             return;
         }
 
-        let host = self.hosts[selected].clone();
-        match self.tab_manager.add_session(host) {
-            Ok(session_id) => {
-                self.set_feedback_message(format!("New session {session_id} created"), false);
-            }
-            Err(e) => {
-                self.set_feedback_message(format!("Error: {e}"), true);
-            }
+        let mut message = format!("Tags of {} host(s) change:\n", changed.len());
+        for name in changed.iter().take(REPORT_MAX_HOSTS) {
+            let _ = writeln!(message, "  {name}");
         }
+        if changed.len() > REPORT_MAX_HOSTS {
+            let _ = writeln!(
+                message,
+                "  ... and {} more",
+                changed.len() - REPORT_MAX_HOSTS
+            );
+        }
+        if skipped > 0 {
+            let _ = writeln!(message, "{skipped} host(s) left in system configuration");
+        }
+        let signed = edit
+            .add
+            .iter()
+            .map(|tag| format!("+{tag}"))
+            .chain(edit.remove.iter().map(|tag| format!("-{tag}")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let _ = write!(message, "\nApply {signed}?");
+
+        self.confirm_message = Some(message);
+        self.confirm_action = Some(BULK_TAGS_ACTION.to_string());
+        self.form_state = FormState::Confirming;
+        self.tag_edit = Some(edit);
     }
 
-    fn open_edit_host_form(&mut self) {
-        let selected = self.table_state.selected().unwrap_or(0);
-        if selected >= self.hosts.len() {
-            self.set_feedback_message("No host selected for editing".to_string(), true);
-            return;
+    /// Applies `edit` to the hosts named `names`, with one write and one backup per
+    /// configuration file, and reloads the hosts once.
+    fn apply_tag_edit(&mut self, names: &[String], edit: &TagEdit) -> Result<()> {
+        let hosts = self
+            .hosts
+            .non_filtered_iter()
+            .filter(|host| names.contains(&host.name));
+        let (by_file, _) = self.hosts_by_file(hosts);
+        let log = self.config.audit_log.as_deref();
+
+        let mut tagged = 0;
+        let mut error = None;
+        for (config_path, hosts) in &by_file {
+            let before = audit_log::snapshot(log, config_path);
+            let result = bulk_tags::write_tags(config_path, hosts, edit).and_then(|changed| {
+                tagged += changed.len();
+                if changed.is_empty() {
+                    return Ok(());
+                }
+                audit_log::record_change(log, "edit tags", &changed.join(", "), config_path, before)
+            });
+            if let Err(e) = result {
+                error = Some(e);
+                break;
+            }
         }
 
-        let host = &self.hosts[selected];
-        let mut form = AddHostForm::new();
+        self.reload_hosts()?;
 
-        // Pre-populate the form with existing host data
-        form.populate_from_host(host);
+        match error {
+            Some(e) => self.set_feedback_message(format!("Error editing tags: {e}"), true),
+            None => self.set_feedback_message(format!("Tagged {tagged} host(s)"), false),
+        }
 
-        self.add_host_form = Some(form);
-        self.form_state = FormState::Active;
-        self.feedback_message = None;
-        self.feedback_timeout = None;
-        self.is_edit_mode = true;
-        self.editing_host_index = Some(selected);
+        Ok(())
     }
 
-    fn save_new_host(&self) -> Result<()> {
-        if let Some(form) = &self.add_host_form {
-            let config_path = shellexpand::tilde(&self.config.config_paths[1]).to_string();
-            form.save_to_config(&config_path)
-        } else {
-            Err(anyhow::anyhow!("Form is not initialized"))
+    fn unused_hosts(&self) -> Vec<UnusedHost> {
+        unused_hosts::find(
+            self.hosts.non_filtered_iter(),
+            &self.state,
+            self.config.unused_after_months,
+            ssh::today(),
+        )
+    }
+
+    fn unused_host_names(&self) -> Vec<String> {
+        self.unused_hosts()
+            .into_iter()
+            .map(|host| host.name)
+            .collect()
+    }
+
+    /// Lists the hosts not connected to for months, and offers to tag them, comment them out or
+    /// export them.
+    fn open_unused_hosts_report(&mut self) {
+        let months = self.config.unused_after_months;
+        let unused = self.unused_hosts();
+        if unused.is_empty() {
+            let mut message = format!("No host unused for {months} months");
+            if let Some(since) = self.state.connections_recorded_since {
+                let _ = write!(
+                    message,
+                    " (connections recorded since {})",
+                    ssh::format_date(since)
+                );
+            }
+            self.set_feedback_message(message, false);
+            return;
+        }
+
+        let mut message = format!("{} host(s) unused for {months} months:\n", unused.len());
+        for host in unused.iter().take(REPORT_MAX_HOSTS) {
+            let _ = writeln!(message, "  {}", host.describe());
+        }
+        if unused.len() > REPORT_MAX_HOSTS {
+            let _ = writeln!(
+                message,
+                "  ... and {} more",
+                unused.len() - REPORT_MAX_HOSTS
+            );
         }
+        let _ = write!(
+            message,
+            "\nTag them '{STALE_TAG}', comment them out or export the list?"
+        );
+
+        self.confirm_message = Some(message);
+        self.confirm_action = Some(UNUSED_HOSTS_ACTION.to_string());
+        self.form_state = FormState::Confirming;
     }
 
-    fn update_existing_host(&self) -> Result<()> {
-        if let Some(form) = &self.add_host_form {
-            if let Some(host_index) = self.editing_host_index {
-                let config_path = shellexpand::tilde(&self.config.config_paths[1]).to_string();
-                let original_host = &self.hosts[host_index];
-                form.update_host_in_config(&config_path, original_host)
-            } else {
-                Err(anyhow::anyhow!("No host selected for editing"))
+    /// Comments the unused hosts out, so that they stay in the files as a record, with one
+    /// backup per configuration file.
+    fn comment_out_unused_hosts(&mut self) -> Result<()> {
+        let names = self.unused_host_names();
+        let hosts = self
+            .hosts
+            .non_filtered_iter()
+            .filter(|host| names.contains(&host.name));
+        let (by_file, skipped) = self.hosts_by_file(hosts);
+        let log = self.config.audit_log.as_deref();
+
+        let mut commented_out = 0;
+        let mut error = None;
+        for (config_path, hosts) in &by_file {
+            let names = hosts
+                .iter()
+                .map(|host| host.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let before = audit_log::snapshot(log, config_path);
+            let result = Self::comment_out_hosts_in_config(config_path, hosts).and_then(|()| {
+                audit_log::record_change(
+                    log,
+                    "comment out unused hosts",
+                    &names,
+                    config_path,
+                    before,
+                )
+            });
+            if let Err(e) = result {
+                error = Some(e);
+                break;
             }
-        } else {
-            Err(anyhow::anyhow!("Form is not initialized"))
+            commented_out += hosts.len();
+        }
+
+        self.reload_hosts()?;
+
+        if let Some(e) = error {
+            self.set_feedback_message(format!("Error commenting out unused hosts: {e}"), true);
+            return Ok(());
         }
+        let mut message = format!("Commented out {commented_out} unused host(s)");
+        if skipped > 0 {
+            let _ = write!(message, ", {skipped} left in system configuration");
+        }
+        self.set_feedback_message(message, false);
+
+        Ok(())
     }
 
-    fn open_delete_host_confirmation(&mut self) {
-        let selected = self.table_state.selected().unwrap_or(0);
-        if selected >= self.hosts.len() {
-            self.set_feedback_message("No host selected for deletion".to_string(), true);
-            return;
+    /// Removes every expired host, with one backup per configuration file. Hosts declared in
+    /// system configuration files are left alone.
+    fn remove_expired_hosts(&mut self) -> Result<()> {
+        let mut by_file: BTreeMap<String, Vec<ssh::Host>> = BTreeMap::new();
+        let mut skipped = 0;
+        for host in self.expired_hosts() {
+            let config_path = self.host_config_path(&host);
+            if is_system_config(Path::new(&config_path)) {
+                skipped += 1;
+                continue;
+            }
+            by_file.entry(config_path).or_default().push(host);
         }
 
-        let host = &self.hosts[selected];
-        self.confirm_message = Some(format!(
-            "Delete host '{}'? This action cannot be undone.",
-            host.name
-        ));
-        self.confirm_action = Some("Delete".to_string());
-        self.form_state = FormState::Confirming;
-        self.editing_host_index = Some(selected);
+        let mut removed = 0;
+        let log = self.config.audit_log.as_deref();
+        for (config_path, hosts) in &by_file {
+            let names = hosts
+                .iter()
+                .map(|host| host.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let before = audit_log::snapshot(log, config_path);
+            let result = Self::delete_hosts_from_config(config_path, hosts).and_then(|()| {
+                audit_log::record_change(log, "remove expired hosts", &names, config_path, before)
+            });
+            if let Err(e) = result {
+                self.reload_hosts()?;
+                self.set_feedback_message(format!("Error removing expired hosts: {e}"), true);
+                return Ok(());
+            }
+            removed += hosts.len();
+        }
+
+        self.reload_hosts()?;
+
+        let mut message = format!("Removed {removed} expired host(s)");
+        if skipped > 0 {
+            let _ = write!(message, ", {skipped} left in system configuration");
+        }
+        self.set_feedback_message(message, false);
+
+        Ok(())
     }
 
     fn delete_selected_host(&mut self) -> Result<()> {
@@ -886,31 +4931,181 @@ impl App {
             }
 
             let host = self.hosts[host_index].clone();
-            let config_path = shellexpand::tilde(&self.config.config_paths[1]).to_string();
+            let config_path = self.host_config_path(&host);
 
             // Delete the host from SSH config file
-            Self::delete_host_from_config(&config_path, &host)?;
+            let log = self.config.audit_log.as_deref();
+            let before = audit_log::snapshot(log, &config_path);
+            Self::delete_hosts_from_config(&config_path, std::slice::from_ref(&host))?;
+            audit_log::record_change(log, "delete host", &host.name, &config_path, before)?;
 
             // Reload hosts to refresh the list
             self.reload_hosts()?;
 
-            // Adjust selection if necessary
-            if host_index >= self.hosts.len() && !self.hosts.is_empty() {
-                self.table_state.select(Some(self.hosts.len() - 1));
-            } else if self.hosts.is_empty() {
-                self.table_state.select(Some(0));
+            // Adjust selection if necessary
+            if host_index >= self.hosts.len() && !self.hosts.is_empty() {
+                self.table_state.select(Some(self.hosts.len() - 1));
+            } else if self.hosts.is_empty() {
+                self.table_state.select(Some(0));
+            }
+
+            // Show success message
+            self.set_feedback_message(format!("Host '{}' deleted successfully", host.name), false);
+
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("No host selected for deletion"))
+        }
+    }
+
+    /// Comments the selected host out of its configuration file, without asking. The previous
+    /// content is kept so that `u` can restore it while the undo toast is shown.
+    fn soft_delete_selected_host(&mut self) -> Result<()> {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            self.set_feedback_message("No host selected for deletion".to_string(), true);
+            return Ok(());
+        }
+        let host = self.hosts[selected].clone();
+
+        let config_path = self.host_config_path(&host);
+        let previous_content = std::fs::read_to_string(&config_path)
+            .map_err(|e| anyhow::anyhow!("Failed to read SSH config file: {e}"))?;
+        let content = Self::comment_out_host_entry(&previous_content, &host, DELETED_HOST_PREFIX)?;
+        std::fs::write(&config_path, &content)
+            .map_err(|e| anyhow::anyhow!("Failed to write updated SSH config file: {e}"))?;
+
+        // There is no backup, the previous content is put back when the write went wrong
+        let expected = [Expected::Absent(&host.name)];
+        if let Err(problem) = backups::check_write(Path::new(&config_path), None, &expected) {
+            std::fs::write(&config_path, &previous_content).map_err(|e| {
+                anyhow::anyhow!("{problem}, and restoring {config_path} failed: {e}")
+            })?;
+            anyhow::bail!("{problem}. The previous version was restored");
+        }
+        audit_log::record(
+            self.config.audit_log.as_deref(),
+            "delete host",
+            &host.name,
+            &config_path,
+            &previous_content,
+            &content,
+        )?;
+
+        self.reload_hosts()?;
+        if selected >= self.hosts.len() {
+            self.table_state
+                .select(Some(self.hosts.len().saturating_sub(1)));
+        }
+
+        // The undo toast replaces any other message
+        self.feedback_message = None;
+        self.feedback_timeout = None;
+        self.deleted_host = Some(DeletedHost {
+            name: host.name,
+            config_path,
+            previous_content,
+            content,
+            deleted_at: Instant::now(),
+        });
+
+        Ok(())
+    }
+
+    /// Brings back the host deleted with `dd`, unless its file changed since.
+    fn undo_delete(&mut self) {
+        let Some(deleted) = self.deleted_host.take() else {
+            self.set_feedback_message("Nothing to undo".to_string(), true);
+            return;
+        };
+
+        let unchanged = std::fs::read_to_string(&deleted.config_path)
+            .is_ok_and(|current| current == deleted.content);
+        if !unchanged {
+            self.set_feedback_message(
+                format!("Cannot undo, {} changed since", deleted.config_path),
+                true,
+            );
+            return;
+        }
+
+        let result = std::fs::write(&deleted.config_path, &deleted.previous_content)
+            .map_err(anyhow::Error::from)
+            .and_then(|()| {
+                audit_log::record(
+                    self.config.audit_log.as_deref(),
+                    "undo delete",
+                    &deleted.name,
+                    &deleted.config_path,
+                    &deleted.content,
+                    &deleted.previous_content,
+                )
+            })
+            .and_then(|()| self.reload_hosts());
+        match result {
+            Ok(()) => self.set_feedback_message(format!("Restored '{}'", deleted.name), false),
+            Err(e) => self.set_feedback_message(format!("Error: {e}"), true),
+        }
+    }
+
+    /// Comments out the block of `host_to_delete` with `prefix`, the block then stays in the file
+    /// as a record.
+    fn comment_out_host_entry(
+        content: &str,
+        host_to_delete: &ssh::Host,
+        prefix: &str,
+    ) -> Result<String> {
+        let mut result = Vec::new();
+        let mut in_host = false;
+        let mut found_host = false;
+
+        for line in content.lines() {
+            let trimmed = line.trim();
+            if let Some(stripped) = trimmed.strip_prefix("Host ") {
+                let pattern = stripped.split_whitespace().next().unwrap_or_default();
+                in_host = pattern.trim_matches('"') == host_to_delete.name;
+                found_host |= in_host;
+            }
+
+            if in_host && !trimmed.is_empty() {
+                result.push(format!("{prefix}{line}"));
+            } else {
+                result.push(line.to_string());
             }
+        }
 
-            // Show success message
-            self.set_feedback_message(format!("Host '{}' deleted successfully", host.name), false);
+        if !found_host {
+            return Err(anyhow::anyhow!(
+                "Host '{}' not found in SSH config file",
+                host_to_delete.name
+            ));
+        }
 
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("No host selected for deletion"))
+        Ok(result.join("\n"))
+    }
+
+    fn comment_out_hosts_in_config(config_path: &str, hosts: &[&ssh::Host]) -> Result<()> {
+        let mut content = std::fs::read_to_string(config_path)
+            .map_err(|e| anyhow::anyhow!("Failed to read SSH config file: {e}"))?;
+        std::fs::copy(config_path, format!("{config_path}.bak"))
+            .map_err(|e| anyhow::anyhow!("Failed to create backup of SSH config file: {e}"))?;
+
+        for host in hosts {
+            content = Self::comment_out_host_entry(&content, host, UNUSED_HOST_PREFIX)?;
         }
+        content.push('\n');
+
+        std::fs::write(config_path, content)
+            .map_err(|e| anyhow::anyhow!("Failed to write updated SSH config file: {e}"))?;
+
+        let expected = hosts
+            .iter()
+            .map(|host| Expected::Absent(&host.name))
+            .collect::<Vec<_>>();
+        backups::verify_write(Path::new(config_path), &expected)
     }
 
-    fn delete_host_from_config(config_path: &str, host_to_delete: &ssh::Host) -> Result<()> {
+    fn delete_hosts_from_config(config_path: &str, hosts_to_delete: &[ssh::Host]) -> Result<()> {
         use std::fs;
 
         // Read the current config file
@@ -922,60 +5117,40 @@ impl App {
         fs::copy(config_path, &backup_path)
             .map_err(|e| anyhow::anyhow!("Failed to create backup of SSH config file: {}", e))?;
 
-        // Parse and remove the host entry
-        let updated_content = Self::remove_host_entry(&content, host_to_delete)?;
+        // Parse and remove the host entries
+        let mut updated_content = content;
+        for host in hosts_to_delete {
+            updated_content = Self::remove_host_entry(&updated_content, host)?;
+        }
 
         // Write the updated content back to the file
         fs::write(config_path, updated_content)
             .map_err(|e| anyhow::anyhow!("Failed to write updated SSH config file: {}", e))?;
 
-        Ok(())
+        let expected = hosts_to_delete
+            .iter()
+            .map(|host| Expected::Absent(&host.name))
+            .collect::<Vec<_>>();
+        backups::verify_write(Path::new(config_path), &expected)
     }
 
+    /// Removes the block of `host_to_delete` with the comments documenting it, leaving the
+    /// comments of the next block and a single blank line between the blocks around it.
     fn remove_host_entry(content: &str, host_to_delete: &ssh::Host) -> Result<String> {
         let lines: Vec<&str> = content.lines().collect();
-        let mut result = Vec::new();
-        let mut i = 0;
-        let mut found_host = false;
-
-        while i < lines.len() {
-            let line = lines[i].trim();
-
-            // Look for Host lines that match our target host name
-            if let Some(stripped) = line.strip_prefix("Host ") {
-                let pattern = stripped.trim();
-                let clean_pattern = pattern.trim_matches('"');
-
-                if clean_pattern == host_to_delete.name {
-                    found_host = true;
-                    // Skip this host block
-                    i += 1;
-
-                    // Skip all lines until the next Host block or end of file
-                    while i < lines.len() {
-                        let next_line = lines[i].trim();
-                        if next_line.starts_with("Host ") && !next_line.is_empty() {
-                            break;
-                        }
-                        i += 1;
-                    }
-
-                    continue;
-                }
-            }
-
-            result.push(lines[i].to_string());
-            i += 1;
-        }
-
-        if !found_host {
+        let Some(range) = form::host_block_range(&lines, &host_to_delete.name) else {
             return Err(anyhow::anyhow!(
                 "Host '{}' not found in SSH config file",
                 host_to_delete.name
             ));
+        };
+
+        let mut result = form::remove_lines(&lines, range.start, range.end).join("\n");
+        if !result.is_empty() {
+            result.push('\n');
         }
 
-        Ok(result.join("\n"))
+        Ok(result)
     }
 
     fn set_feedback_message(&mut self, message: String, is_error: bool) {
@@ -984,7 +5159,34 @@ impl App {
         self.feedback_timeout = Some(Instant::now());
     }
 
-    fn check_feedback_timeout(&mut self) {
+    /// Writes the pending state changes once their batch is due.
+    pub(crate) fn flush_state_if_due(&mut self) {
+        if let Err(e) = self.state.flush_if_due() {
+            self.set_feedback_message(format!("Error saving state: {e}"), true);
+        }
+    }
+
+    /// Moves the tutorial on once its current step is done.
+    pub(crate) fn update_tutorial(&mut self) {
+        let Some(mut tutorial) = self.tutorial.take() else {
+            return;
+        };
+
+        if let Some(step) = tutorial.update(self) {
+            self.set_feedback_message(format!("✓ {step} done"), false);
+        }
+        self.tutorial = Some(tutorial);
+    }
+
+    pub(crate) fn check_feedback_timeout(&mut self) {
+        if self
+            .deleted_host
+            .as_ref()
+            .is_some_and(|deleted| deleted.deleted_at.elapsed() > UNDO_DELETE_DELAY)
+        {
+            self.deleted_host = None;
+        }
+
         if let Some(timeout) = self.feedback_timeout {
             // Clear feedback message after 3 seconds
             if timeout.elapsed() > Duration::from_secs(3) {
@@ -994,93 +5196,291 @@ impl App {
         }
     }
 
-    fn reload_hosts(&mut self) -> Result<()> {
-        let mut hosts = Vec::new();
-
-        for path in &self.config.config_paths {
-            let parsed_hosts = match ssh::parse_config(path) {
-                Ok(hosts) => hosts,
-                Err(err) => {
-                    if path == "/etc/ssh/ssh_config" {
-                        if let ssh::ParseConfigError::Io(io_err) = &err {
-                            // Ignore missing system-wide SSH configuration file
-                            if io_err.kind() == std::io::ErrorKind::NotFound {
-                                continue;
-                            }
-                        }
-                    }
-
-                    anyhow::bail!("Failed to parse SSH configuration file '{}': {}", path, err);
-                }
-            };
+    pub(crate) fn reload_hosts(&mut self) -> Result<()> {
+        let (mut hosts, config_problems) =
+            load_config_hosts(&self.config.config_paths, self.settings.skip_system_config)?;
+        self.config_errors =
+            (!config_problems.is_empty()).then(|| ConfigErrorsView::new(config_problems));
 
-            hosts.extend(parsed_hosts);
+        if let Some(path) = &self.config.shared_metadata_path {
+            SharedMetadata::load(path)?.apply(&mut hosts);
         }
-
-        if self.config.sort_by_name {
-            hosts.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        if self.settings.fact_tags {
+            facts::apply(&mut hosts, &self.state.host_facts);
         }
 
-        let search_input = self.search.value();
-        let matcher = SkimMatcherV2::default();
+        let mut hosts = self.settings.with_alias_rows(hosts);
+        self.settings.sort_hosts(&mut hosts);
+        if self.recent_view {
+            recent::sort_hosts(&mut hosts, &self.state);
+        }
+        if self.incident.is_some() {
+            incident::sort_hosts(&mut hosts, &self.state);
+        }
+        if self.network.is_none() {
+            self.network = NetworkWatch::start(&hosts);
+        }
 
         self.hosts = Searchable::new(
             hosts,
-            search_input,
-            move |host: &&ssh::Host, search_value: &str| -> bool {
-                search_value.is_empty()
-                    || matcher.fuzzy_match(&host.name, search_value).is_some()
-                    || matcher
-                        .fuzzy_match(&host.destination, search_value)
-                        .is_some()
-                    || matcher.fuzzy_match(&host.aliases, search_value).is_some()
-            },
+            self.search.value(),
+            host_search_predicate(self.settings.matcher),
         );
+        self.refresh_search();
+
+        // Certificates may have been renewed since they were inspected
+        self.certificates.clear();
+        self.inspect_certificates();
 
         self.calculate_table_columns_constraints();
         Ok(())
     }
 
-    fn connect_to_selected_host<B>(
+    fn run_pending_action<B>(
+        &mut self,
+        terminal: &Rc<RefCell<Terminal<B>>>,
+        pending: PendingAction,
+    ) -> Result<bool>
+    where
+        B: Backend + std::io::Write,
+    {
+        match pending {
+            PendingAction::Connect(options) => {
+                let action = self.connect_to_selected_host(terminal, &options)?;
+                Ok(action == AppKeyAction::Stop)
+            }
+            PendingAction::SudoEdit(path) => {
+                self.sudoedit(terminal, &path)?;
+                Ok(false)
+            }
+            PendingAction::EditConfig(problem) => {
+                self.edit_config(terminal, &problem.path, problem.editor_command())?;
+                Ok(false)
+            }
+            PendingAction::EditOption(path, line) => {
+                let editor = config_errors::editor_command(&path, Some(line));
+                self.edit_config(terminal, &path, editor)?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Runs the editor on a configuration file, then reloads the hosts. The problems panel
+    /// shows up again if some remain.
+    fn edit_config<B>(
+        &mut self,
+        terminal: &Rc<RefCell<Terminal<B>>>,
+        path: &Path,
+        mut editor: std::process::Command,
+    ) -> Result<()>
+    where
+        B: Backend + std::io::Write,
+    {
+        if let Err(e) = safe_restore_terminal(terminal) {
+            eprintln!("Warning: Failed to restore terminal: {e}");
+        }
+
+        let file = path.display().to_string();
+        let log = self.config.audit_log.as_deref();
+        let before = audit_log::snapshot(log, &file);
+        let status = exec::status(&mut editor);
+
+        safe_setup_terminal(terminal, self.config.capabilities.mouse)?;
+        terminal.borrow_mut().clear()?;
+
+        match status {
+            Ok(_) => {
+                let result = audit_log::record_change(log, "edit in editor", "", &file, before)
+                    .and_then(|()| self.reload_hosts());
+                if let Err(e) = result {
+                    self.set_feedback_message(format!("Error: {e}"), true);
+                }
+            }
+            Err(e) => self.set_feedback_message(format!("Failed to run the editor: {e}"), true),
+        }
+
+        Ok(())
+    }
+
+    fn sudoedit<B>(&mut self, terminal: &Rc<RefCell<Terminal<B>>>, path: &Path) -> Result<()>
+    where
+        B: Backend + std::io::Write,
+    {
+        if let Err(e) = safe_restore_terminal(terminal) {
+            eprintln!("Warning: Failed to restore terminal: {e}");
+        }
+
+        let file = path.display().to_string();
+        let log = self.config.audit_log.as_deref();
+        let before = audit_log::snapshot(log, &file);
+        let status = exec::status(Command::new("sudoedit").arg(path));
+
+        safe_setup_terminal(terminal, self.config.capabilities.mouse)?;
+        terminal.borrow_mut().clear()?;
+
+        match status {
+            Ok(status) if status.success() => {
+                audit_log::record_change(log, "sudoedit", "", &file, before)?;
+                self.reload_hosts()?;
+                self.set_feedback_message(format!("{} updated", path.display()), false);
+            }
+            Ok(status) => self.set_feedback_message(format!("sudoedit failed: {status}"), true),
+            Err(e) => self.set_feedback_message(format!("Failed to run sudoedit: {e}"), true),
+        }
+
+        Ok(())
+    }
+
+    /// Connects to the selected host, asking for confirmation first when the SSH agent would be
+    /// forwarded to a host that is not trusted yet.
+    fn request_connection<B>(
         &mut self,
         terminal: &Rc<RefCell<Terminal<B>>>,
+        options: ssh::ConnectOptions,
     ) -> Result<AppKeyAction>
     where
         B: Backend + std::io::Write,
     {
+        match self.prepare_connection(options) {
+            Some(options) => self.connect_to_selected_host(terminal, &options),
+            None => Ok(AppKeyAction::Ok),
+        }
+    }
+
+    /// Resolves the effective connection options for the selected host. Returns `None` when the
+    /// connection has been deferred to the template value prompt, the important banner or the
+    /// agent forwarding confirmation dialog.
+    fn prepare_connection(
+        &mut self,
+        mut options: ssh::ConnectOptions,
+    ) -> Option<ssh::ConnectOptions> {
         let selected = self.table_state.selected().unwrap_or(0);
         if selected >= self.hosts.len() {
-            return Ok(AppKeyAction::Ok);
+            return None;
         }
 
-        let host = self.hosts[selected].clone();
+        let host = &self.hosts[selected];
+        if host.is_template() && options.template_value.is_none() {
+            let history = self.state.template_values(&host.name);
+            self.prompt = Some(Prompt::new(PromptKind::TemplateValue(options), history));
+            return None;
+        }
+
+        if !options.banner_acknowledged && host.has_important_banner() {
+            self.confirm_message = Some(format!(
+                "{}\n\nConnect to '{}'?",
+                host.banner().unwrap_or_default(),
+                host.name
+            ));
+            self.confirm_action = Some(BANNER_ACTION.to_string());
+            self.form_state = FormState::Confirming;
+            self.pending_action = Some(PendingAction::Connect(options));
+            return None;
+        }
 
-        // Show styled connection box
-        self.show_connection_screen(terminal, &host)?;
+        options.forward_agent |= host.forward_agent;
 
-        // Restore terminal for SSH session
-        if let Err(e) = safe_restore_terminal(terminal) {
-            // Even if restore fails, we should try to continue
-            eprintln!("Warning: Failed to restore terminal: {e}");
+        if !options.forward_agent || self.state.is_agent_trusted(&host.name) {
+            return Some(options);
         }
 
-        // Execute pre-session commands
-        if let Some(template) = &self.config.command_template_on_session_start {
-            host.run_command_template(template)?;
+        self.confirm_message = Some(format!(
+            "Forward your SSH agent to '{}'? Its admins could use your keys.",
+            host.name
+        ));
+        self.confirm_action = Some(AGENT_FORWARDING_ACTION.to_string());
+        self.form_state = FormState::Confirming;
+        self.pending_action = Some(PendingAction::Connect(options));
+
+        None
+    }
+
+    fn connect_to_selected_host<B>(
+        &mut self,
+        terminal: &Rc<RefCell<Terminal<B>>>,
+        options: &ssh::ConnectOptions,
+    ) -> Result<AppKeyAction>
+    where
+        B: Backend + std::io::Write,
+    {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            return Ok(AppKeyAction::Ok);
         }
 
-        // Connect to SSH with clean output
-        let ssh_result = Self::connect_to_ssh_host(terminal, &host);
+        let host = self.connection_host(selected, options)?;
+        if options.in_tab {
+            self.open_session_tab(host, options);
+            return Ok(AppKeyAction::Ok);
+        }
 
-        // Execute post-session commands
-        if let Some(template) = &self.config.command_template_on_session_end {
-            host.run_command_template(template)?;
+        // Safe mode: hand the command over instead of running it
+        if self.config.no_exec {
+            let command = host.build_ssh_command(options);
+            let command = shlex::try_join(command.iter().map(String::as_str)).unwrap_or_default();
+            clipboard::copy(terminal.borrow_mut().backend_mut(), &command)?;
+            self.set_feedback_message(format!("Copied (--no-exec): {command}"), false);
+            return Ok(AppKeyAction::Ok);
         }
 
-        // Show return message and restore TUI
-        self.show_session_ended_screen(terminal, &host, ssh_result)?;
+        loop {
+            // Show styled connection box
+            self.show_connection_screen(terminal, &host, options)?;
+
+            // Restore terminal for SSH session
+            if let Err(e) = safe_restore_terminal(terminal) {
+                // Even if restore fails, we should try to continue
+                eprintln!("Warning: Failed to restore terminal: {e}");
+            }
+
+            // Sessions can last long, do not keep the state changes waiting for them
+            if let Err(e) = self.state.flush() {
+                eprintln!("Warning: Failed to save state: {e}");
+            }
+
+            let options = &self.with_session_log(&host, options);
+
+            // Execute pre-session commands
+            if let Some(template) = &self.config.command_template_on_session_start {
+                host.run_command_template(template)?;
+            }
+            self.run_socks_hook(&host, options, "on")?;
+
+            // Only interactive shells get the dotfiles
+            if host.wants_dotfiles() && options.remote_command.is_none() {
+                self.push_dotfiles(&host);
+            }
+
+            // Connect to SSH with clean output
+            let summary =
+                Self::connect_to_ssh_host(terminal, self.backend.as_ref(), &host, options);
+            self.record_usage(&summary);
+
+            // Execute post-session commands
+            self.run_socks_hook(&host, options, "off")?;
+            let values = summary.template_values(
+                self.tab_manager
+                    .custom_names()
+                    .get(&host.name)
+                    .map(String::as_str),
+            );
+            if let Some(template) = &self.config.command_template_on_connection_failure {
+                if summary.is_connection_error() {
+                    host.run_command_template_with_values(template, &values)?;
+                }
+            }
+            if let Some(template) = &self.config.command_template_on_session_end {
+                host.run_command_template_with_values(template, &values)?;
+            }
+
+            // Show the session summary, until the user stops reconnecting
+            let next = self.show_session_ended_screen(terminal, &summary)?;
+            if next != SummaryAction::Reconnect {
+                break;
+            }
+        }
 
-        if let Err(e) = safe_setup_terminal(terminal) {
+        if let Err(e) = safe_setup_terminal(terminal, self.config.capabilities.mouse) {
             // If we can't restore the terminal, we should exit
             eprintln!("Fatal error: Failed to setup terminal: {e}");
             return Err(e);
@@ -1093,21 +5493,215 @@ impl App {
         Ok(AppKeyAction::Ok)
     }
 
+    /// The host connected to for the host at `selected`: the template host instantiated, or
+    /// its fallback when it cannot be reached, with the default user.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the value given to a template host is invalid.
+    fn connection_host(&self, selected: usize, options: &ssh::ConnectOptions) -> Result<ssh::Host> {
+        let mut host = match &options.template_value {
+            Some(value) => self.hosts[selected].instantiate(value)?,
+            None => self.through_fallback(self.hosts[selected].clone().for_connection()),
+        };
+        if host.user.is_none() {
+            host.user.clone_from(&self.config.default_user);
+        }
+
+        Ok(host)
+    }
+
+    /// `options` with a new log file for the session, when the sessions of `host` are recorded.
+    /// A log that cannot be created is reported, the session goes on without it.
+    fn with_session_log(
+        &self,
+        host: &ssh::Host,
+        options: &ssh::ConnectOptions,
+    ) -> ssh::ConnectOptions {
+        let mut options = options.clone();
+        if !host.logs_sessions(self.config.log_sessions) {
+            return options;
+        }
+
+        match session_log::new_log(&self.config.session_log_dir, &host.name, certificate::now()) {
+            Ok(path) => options.session_log = Some(path),
+            Err(e) => {
+                eprintln!("Warning: session not recorded: {e:#}");
+                thread::sleep(Duration::from_millis(1500));
+            }
+        }
+        options
+    }
+
+    /// Opens the log of the last recorded session of the selected host in `$PAGER`.
+    fn open_session_log<B>(&mut self, terminal: &Rc<RefCell<Terminal<B>>>) -> Result<()>
+    where
+        B: Backend + std::io::Write,
+    {
+        let selected = self.table_state.selected().unwrap_or(0);
+        if selected >= self.hosts.len() {
+            return Ok(());
+        }
+        let name = self.hosts[selected].name.clone();
+        let Some(path) = session_log::latest(&self.config.session_log_dir, &name) else {
+            let hint = if self.config.log_sessions {
+                ""
+            } else {
+                ", record them with --log-sessions or # sshs:log=yes"
+            };
+            self.set_feedback_message(format!("No recorded session of {name}{hint}"), true);
+            return Ok(());
+        };
+
+        if let Err(e) = safe_restore_terminal(terminal) {
+            eprintln!("Warning: Failed to restore terminal: {e}");
+        }
+        let status = exec::status(&mut session_log::pager_command(&path));
+        safe_setup_terminal(terminal, self.config.capabilities.mouse)?;
+        terminal.borrow_mut().clear()?;
+
+        if let Err(e) = status {
+            self.set_feedback_message(format!("Failed to run the pager: {e}"), true);
+        }
+        Ok(())
+    }
+
+    /// Remembers the connection for the unused hosts report, then counts the session in the
+    /// usage statistics and exports them, when enabled. Failures are only reported, the summary
+    /// screen follows.
+    fn record_usage(&mut self, summary: &SessionSummary) {
+        if !summary.is_connection_error() {
+            self.state.record_connection(&summary.host, ssh::today());
+        }
+        self.state.record_host_event(
+            &summary.host,
+            HostEvent {
+                time: u64::try_from(certificate::now()).unwrap_or_default(),
+                kind: if summary.is_connection_error() {
+                    HostEventKind::Failure
+                } else {
+                    HostEventKind::Connection
+                },
+                detail: format!(
+                    "session of {}, exit code {}",
+                    summary.formatted_duration(),
+                    summary.formatted_exit_code()
+                ),
+            },
+        );
+        self.state.mark_changed();
+
+        let Some(path) = &self.config.stats_file else {
+            return;
+        };
+
+        self.state.usage_stats.record(
+            &summary.host,
+            summary.duration,
+            summary.is_connection_error(),
+        );
+        self.state.mark_changed();
+        if let Err(e) = self.state.usage_stats.export(path) {
+            eprintln!("Warning: Failed to update usage statistics: {e}");
+        }
+    }
+
+    /// Pushes the configured dotfiles to `host`. Failures are reported but never prevent the
+    /// connection.
+    fn push_dotfiles(&self, host: &ssh::Host) {
+        if self.config.dotfiles.is_empty() {
+            return;
+        }
+
+        let result = dotfiles::load(&self.config.dotfiles).and_then(|dotfiles| {
+            log::info(format_args!(
+                "Pushing {} dotfile(s) to {}...",
+                dotfiles.len(),
+                host.name
+            ));
+
+            let script = dotfiles::bootstrap_script(&dotfiles);
+            self.backend.batch(host, &script).map(|_| ())
+        });
+
+        if let Err(e) = result {
+            eprintln!("Warning: dotfiles not pushed: {e}");
+            thread::sleep(Duration::from_millis(1500));
+        }
+    }
+
+    /// Runs the SOCKS hook template (browser-profile helpers and the like) when the connection
+    /// opens a dynamic forward. The state (`on`/`off`) and port are exported as environment
+    /// variables.
+    fn run_socks_hook(
+        &self,
+        host: &ssh::Host,
+        options: &ssh::ConnectOptions,
+        state: &str,
+    ) -> Result<()> {
+        let (Some(template), Some(port)) = (
+            &self.config.command_template_on_socks,
+            options.dynamic_forward,
+        ) else {
+            return Ok(());
+        };
+
+        host.run_command_template_with_env(
+            template,
+            &[
+                ("SSHS_SOCKS_STATE", state.to_string()),
+                ("SSHS_SOCKS_PORT", port.to_string()),
+            ],
+        )
+    }
+
     fn show_connection_screen<B>(
         &self,
         terminal: &Rc<RefCell<Terminal<B>>>,
         host: &ssh::Host,
+        options: &ssh::ConnectOptions,
     ) -> Result<()>
     where
         B: Backend + std::io::Write,
     {
+        let socks_endpoint = options.socks_endpoint();
+        let banner = host.banner();
+        let banner_lines: Vec<&str> = banner
+            .as_deref()
+            .map(|b| b.lines().collect())
+            .unwrap_or_default();
+
+        // Banners stay up longer, even when the screen is otherwise skipped
+        let pause = if banner_lines.is_empty() {
+            self.config.connect_delay
+        } else {
+            self.config.connect_delay.max(BANNER_DELAY)
+        };
+        if pause.is_zero() || log::is_quiet() {
+            return Ok(());
+        }
+
         // Render connection box
         terminal.borrow_mut().draw(|f| {
             let area = f.area();
 
-            // Create centered box
-            let box_width = 50;
-            let box_height = 8;
+            // Create centered box, large enough for the banner
+            let banner_width = banner_lines
+                .iter()
+                .map(|line| UnicodeWidthStr::width(*line))
+                .max()
+                .unwrap_or_default();
+            let box_width = u16::try_from(banner_width + 4)
+                .unwrap_or(u16::MAX)
+                .clamp(50, area.width.max(50));
+            let banner_height = match banner_lines.len() {
+                0 => 0,
+                lines => u16::try_from(lines + 1).unwrap_or_default(),
+            };
+            let box_height = 8
+                + u16::from(socks_endpoint.is_some())
+                + u16::from(options.jump_host.is_some())
+                + banner_height;
             let x = (area.width.saturating_sub(box_width)) / 2;
             let y = (area.height.saturating_sub(box_height)) / 2;
 
@@ -1137,12 +5731,36 @@ impl App {
                 Line::from(vec![
                     Span::styled("   User: ", Style::new().fg(self.palette.c300)),
                     Span::styled(
-                        host.user.as_deref().unwrap_or("default"),
+                        host.user.as_deref().unwrap_or("local account"),
                         Style::new().fg(Color::White),
                     ),
                 ]),
-                Line::from(""),
             ];
+            let mut connection_text = connection_text;
+            if let Some(endpoint) = &socks_endpoint {
+                connection_text.push(Line::from(vec![
+                    Span::styled("   SOCKS: ", Style::new().fg(self.palette.c300)),
+                    Span::styled(endpoint, Style::new().fg(tailwind::AMBER.c400)),
+                ]));
+            }
+            if let Some(jump_host) = &options.jump_host {
+                connection_text.push(Line::from(vec![
+                    Span::styled("   Via: ", Style::new().fg(self.palette.c300)),
+                    Span::styled(
+                        format!("{jump_host} (overrides ProxyJump)"),
+                        Style::new()
+                            .fg(tailwind::AMBER.c400)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                ]));
+            }
+            if !banner_lines.is_empty() {
+                connection_text.push(Line::from(""));
+                connection_text.extend(banner_lines.iter().map(|line| {
+                    Line::from(Span::styled(*line, Style::new().fg(tailwind::AMBER.c400)))
+                }));
+            }
+            connection_text.push(Line::from(""));
 
             let connection_paragraph = Paragraph::new(connection_text)
                 .block(
@@ -1160,169 +5778,293 @@ impl App {
                 .alignment(Alignment::Center);
 
             f.render_widget(connection_paragraph, box_area);
+            self.config.capabilities.degrade(f.buffer_mut());
         })?;
 
-        // Brief pause for user to read
-        thread::sleep(Duration::from_millis(800));
+        // Brief pause for user to read, cut short by any key
+        wait_for_key(Some(Instant::now() + pause))?;
 
         Ok(())
     }
 
     fn connect_to_ssh_host<B>(
         _terminal: &Rc<RefCell<Terminal<B>>>,
+        backend: &dyn ConnectionBackend,
         host: &ssh::Host,
-    ) -> Result<(), String>
+        options: &ssh::ConnectOptions,
+    ) -> SessionSummary
     where
         B: Backend + std::io::Write,
     {
         // Clear screen completely before SSH
-        print!("\x1b[2J\x1b[H");
-
-        // Build SSH command with normal authentication flow
-        let user = host.user.as_deref().unwrap_or("root");
-        let port = host.port.as_deref().unwrap_or("22");
-
-        let ssh_command = format!(
-            "ssh -o LogLevel=ERROR -o StrictHostKeyChecking=accept-new -p {} {}@{}",
-            port, user, &host.destination
-        );
+        if !log::is_quiet() {
+            print!("\x1b[2J\x1b[H");
+        }
 
-        // Execute SSH command normally - let SSH handle authentication
-        let result = Command::new("sh").arg("-c").arg(&ssh_command).status();
+        // Let the backend handle authentication
+        let started = Instant::now();
+        let (exit_code, error) = match backend.interactive(host, options) {
+            Ok(exit_code) => (exit_code, None),
+            Err(e) => (
+                None,
+                Some(format!("Failed to start {} session: {e}", backend.name())),
+            ),
+        };
 
-        match result {
-            Ok(status) if status.success() => Ok(()),
-            Ok(status) => Err(format!(
-                "SSH connection failed with exit code: {}",
-                status.code().unwrap_or(-1)
-            )),
-            Err(e) => Err(format!("Failed to execute SSH command: {e}")),
+        SessionSummary {
+            host: host.name.clone(),
+            destination: host.destination.clone(),
+            duration: started.elapsed(),
+            exit_code,
+            error,
         }
     }
 
+    /// Shows the summary of the session that just ended and waits for the user to pick what's
+    /// next: reconnect, copy the summary, or return to the host list.
     fn show_session_ended_screen<B>(
         &self,
         terminal: &Rc<RefCell<Terminal<B>>>,
-        _host: &ssh::Host,
-        ssh_result: Result<(), String>,
-    ) -> Result<()>
+        summary: &SessionSummary,
+    ) -> Result<SummaryAction>
     where
         B: Backend + std::io::Write,
     {
+        // Nothing to come back to when sshs exits with the session
+        let delay = match self.config.end_screen_delay {
+            _ if log::is_quiet() => Some(Duration::ZERO),
+            None if self.config.exit_after_ssh_session_ends => Some(Duration::ZERO),
+            delay => delay,
+        };
+        if delay.is_some_and(|delay| delay.is_zero()) {
+            return Ok(SummaryAction::Return);
+        }
+
         // Set up terminal for our UI
-        if let Err(e) = safe_setup_terminal(terminal) {
+        if let Err(e) = safe_setup_terminal(terminal, self.config.capabilities.mouse) {
             eprintln!("Warning: Failed to setup terminal for end screen: {e}");
             thread::sleep(Duration::from_millis(1000));
-            return Ok(());
+            return Ok(SummaryAction::Return);
+        }
+
+        let deadline = delay.map(|delay| Instant::now() + delay);
+        let mut copied = false;
+        loop {
+            self.draw_session_summary(terminal, summary, copied)?;
+
+            let Some(key) = wait_for_key(deadline)? else {
+                return Ok(SummaryAction::Return);
+            };
+
+            match key.code {
+                KeyCode::Char('r') => return Ok(SummaryAction::Reconnect),
+                KeyCode::Char('c') => {
+                    clipboard::copy(terminal.borrow_mut().backend_mut(), &summary.text())?;
+                    copied = true;
+                }
+                _ => return Ok(SummaryAction::Return),
+            }
+        }
+    }
+
+    fn draw_session_summary<B>(
+        &self,
+        terminal: &Rc<RefCell<Terminal<B>>>,
+        summary: &SessionSummary,
+        copied: bool,
+    ) -> Result<()>
+    where
+        B: Backend + std::io::Write,
+    {
+        let (color, icon, headline, title) = if summary.is_connection_error() {
+            (
+                Color::Red,
+                "❌ ",
+                "SSH Connection Failed",
+                " Connection Error ",
+            )
+        } else if summary.is_success() {
+            (
+                Color::Green,
+                "↩️  ",
+                "SSH session ended",
+                " Session Complete ",
+            )
+        } else {
+            (
+                tailwind::AMBER.c400,
+                "⚠ ",
+                "SSH session ended with an error",
+                " Session Complete ",
+            )
+        };
+
+        let field = |label: &'static str, value: String| {
+            Line::from(vec![
+                Span::styled(label, Style::new().fg(self.palette.c300)),
+                Span::styled(value, Style::new().fg(Color::White)),
+            ])
+        };
+
+        let mut text = vec![
+            Line::from(""),
+            Line::from(vec![
+                Span::styled(icon, Style::new().fg(color)),
+                Span::styled(
+                    headline,
+                    Style::new().fg(Color::White).add_modifier(Modifier::BOLD),
+                ),
+            ]),
+            Line::from(""),
+            field(
+                "Host: ",
+                format!("{} ({})", summary.host, summary.destination),
+            ),
+            field("Duration: ", summary.formatted_duration()),
+            field("Exit code: ", summary.formatted_exit_code()),
+            Line::from(""),
+        ];
+        if summary.is_connection_error() {
+            text.extend([
+                Line::from(Span::styled(
+                    "• Check host connectivity",
+                    Style::new().fg(self.palette.c300),
+                )),
+                Line::from(Span::styled(
+                    "• Verify SSH service status",
+                    Style::new().fg(self.palette.c300),
+                )),
+                Line::from(""),
+            ]);
         }
+        text.push(if copied {
+            Line::from(Span::styled(
+                "Summary copied to the clipboard",
+                Style::new().fg(Color::Green),
+            ))
+        } else {
+            Line::from("")
+        });
+        text.push(Line::from(vec![
+            Span::styled("(r) reconnect", Style::new().fg(self.palette.c400)),
+            Span::styled(" | ", Style::new().fg(Color::DarkGray)),
+            Span::styled("(c) copy summary", Style::new().fg(self.palette.c400)),
+            Span::styled(" | ", Style::new().fg(Color::DarkGray)),
+            Span::styled("any key: back", Style::new().fg(self.palette.c400)),
+        ]));
 
-        // Render session ended or error box
         terminal.borrow_mut().draw(|f| {
             let area = f.area();
 
             // Create centered box
-            let box_width = 50;
-            let box_height = match ssh_result {
-                Ok(()) => 6,
-                Err(_) => 10,
-            };
+            let box_width = 60.min(area.width);
+            let box_height = u16::try_from(text.len() + 3).unwrap_or(u16::MAX);
             let x = (area.width.saturating_sub(box_width)) / 2;
             let y = (area.height.saturating_sub(box_height)) / 2;
 
-            let box_area = Rect::new(x, y, box_width, box_height);
+            let box_area = Rect::new(x, y, box_width, box_height.min(area.height));
 
             // Clear background
             f.render_widget(Clear, box_area);
 
-            match ssh_result {
-                Ok(()) => {
-                    // Success - session ended normally
-                    let end_text = vec![
-                        Line::from(""),
-                        Line::from(vec![
-                            Span::styled("↩️  ", Style::new().fg(Color::Green)),
-                            Span::styled("SSH session ended", Style::new().fg(Color::White)),
-                        ]),
-                        Line::from(""),
-                        Line::from(vec![Span::styled(
-                            "   Returning to SSHS...",
-                            Style::new().fg(self.palette.c300),
-                        )]),
-                    ];
-
-                    let end_paragraph = Paragraph::new(end_text)
-                        .block(
-                            Block::default()
-                                .borders(Borders::ALL)
-                                .border_style(Style::new().fg(Color::Green))
-                                .border_type(BorderType::Rounded)
-                                .title(" Session Complete ")
-                                .title_style(
-                                    Style::new().fg(Color::Green).add_modifier(Modifier::BOLD),
-                                ),
-                        )
-                        .alignment(Alignment::Center);
-
-                    f.render_widget(end_paragraph, box_area);
-                }
-                Err(error_msg) => {
-                    // Error occurred
-                    let error_text = vec![
-                        Line::from(""),
-                        Line::from(vec![
-                            Span::styled("❌ ", Style::new().fg(Color::Red)),
-                            Span::styled(
-                                "SSH Connection Failed",
-                                Style::new().fg(Color::White).add_modifier(Modifier::BOLD),
-                            ),
-                        ]),
-                        Line::from(""),
-                        Line::from(vec![
-                            Span::styled("   Error: ", Style::new().fg(Color::Red)),
-                            Span::styled(&error_msg, Style::new().fg(Color::White)),
-                        ]),
-                        Line::from(""),
-                        Line::from(vec![Span::styled(
-                            "   • Check host connectivity",
-                            Style::new().fg(self.palette.c300),
-                        )]),
-                        Line::from(vec![Span::styled(
-                            "   • Verify SSH service status",
-                            Style::new().fg(self.palette.c300),
-                        )]),
-                        Line::from(""),
-                    ];
-
-                    let error_paragraph = Paragraph::new(error_text)
-                        .block(
-                            Block::default()
-                                .borders(Borders::ALL)
-                                .border_style(Style::new().fg(Color::Red))
-                                .border_type(BorderType::Rounded)
-                                .title(" Connection Error ")
-                                .title_style(
-                                    Style::new().fg(Color::Red).add_modifier(Modifier::BOLD),
-                                ),
-                        )
-                        .alignment(Alignment::Center);
+            let paragraph = Paragraph::new(text)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_style(Style::new().fg(color))
+                        .border_type(BorderType::Rounded)
+                        .title(title)
+                        .title_style(Style::new().fg(color).add_modifier(Modifier::BOLD)),
+                )
+                .alignment(Alignment::Center);
 
-                    f.render_widget(error_paragraph, box_area);
-                }
-            }
+            f.render_widget(paragraph, box_area);
+            self.config.capabilities.degrade(f.buffer_mut());
         })?;
 
-        // Brief pause for user to read
-        thread::sleep(Duration::from_millis(1500));
-
         Ok(())
     }
 }
 
+/// Waits for a key press until `deadline` (forever when `None`), discarding other events.
+/// Returns `None` when the deadline passes first.
+fn wait_for_key(deadline: Option<Instant>) -> Result<Option<KeyEvent>> {
+    loop {
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || !event::poll(remaining)? {
+                return Ok(None);
+            }
+        }
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                return Ok(Some(key));
+            }
+        }
+    }
+}
+
+/// Loads the hosts of the configuration files, along with the problems found in them. The
+/// system-wide files are left out when `skip_system` is set. Fails only when there are problems
+/// and not a single host could be loaded.
+fn load_config_hosts(
+    config_paths: &[String],
+    skip_system: bool,
+) -> Result<(Vec<ssh::Host>, Vec<ConfigProblem>)> {
+    let config_paths: Vec<String> = config_paths
+        .iter()
+        .filter(|path| !(skip_system && is_system_config(Path::new(path))))
+        .cloned()
+        .collect();
+    let (hosts, problems) = config_errors::load_hosts(&config_paths);
+
+    if hosts.is_empty() && !problems.is_empty() {
+        anyhow::bail!(
+            "Failed to parse the SSH configuration:\n{}",
+            problems
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    Ok((hosts, problems))
+}
+
+/// Whether a configuration file is system-wide (root-owned), like `/etc/ssh/ssh_config`.
+fn is_system_config(path: &Path) -> bool {
+    path.starts_with("/etc")
+}
+
+fn system_edit_message(host: &ssh::Host) -> String {
+    let source = host
+        .source_file
+        .as_ref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_default();
+
+    format!(
+        "'{}' is defined in {source}. Override it in your config or (S) sudoedit?",
+        host.name
+    )
+}
+
+/// Builds the search predicate used to filter the host list.
+fn host_search_predicate(matcher: Matcher) -> impl FnMut(&&ssh::Host, &str) -> bool + 'static {
+    let mut backend = matcher.backend();
+
+    move |host: &&ssh::Host, search_value: &str| -> bool {
+        search_value.is_empty() || SearchQuery::parse(search_value).is_match(backend.as_mut(), host)
+    }
+}
+
 // Better error handling for terminal setup/teardown
 /// # Errors
 ///
 /// Will return `Err` if the terminal cannot be configured properly.
-pub fn safe_setup_terminal<B>(terminal: &Rc<RefCell<Terminal<B>>>) -> Result<()>
+pub fn safe_setup_terminal<B>(terminal: &Rc<RefCell<Terminal<B>>>, mouse: bool) -> Result<()>
 where
     B: Backend + std::io::Write,
 {
@@ -1351,8 +6093,15 @@ where
     execute!(terminal_ref.backend_mut(), EnterAlternateScreen)
         .map_err(|e| anyhow::anyhow!("Failed to enter alternate screen: {}", e))?;
 
-    execute!(terminal_ref.backend_mut(), EnableMouseCapture)
-        .map_err(|e| anyhow::anyhow!("Failed to enable mouse capture: {}", e))?;
+    // Pastes arrive at once, so that the ones into a session can be confirmed
+    execute!(terminal_ref.backend_mut(), EnableBracketedPaste)
+        .map_err(|e| anyhow::anyhow!("Failed to enable bracketed paste: {e}"))?;
+
+    // Terminals with broken mouse reporting would print the events as text
+    if mouse {
+        execute!(terminal_ref.backend_mut(), EnableMouseCapture)
+            .map_err(|e| anyhow::anyhow!("Failed to enable mouse capture: {}", e))?;
+    }
 
     Ok(())
 }
@@ -1395,6 +6144,10 @@ where
         if let Err(e) = execute!(terminal_ref.backend_mut(), DisableMouseCapture) {
             errors.push(format!("Failed to disable mouse capture: {e}"));
         }
+
+        if let Err(e) = execute!(terminal_ref.backend_mut(), DisableBracketedPaste) {
+            errors.push(format!("Failed to disable bracketed paste: {e}"));
+        }
     }
 
     if errors.is_empty() {
@@ -1411,6 +6164,7 @@ where
 mod tests {
     use super::*;
     use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
     use std::time::Duration;
 
     /// Helper function to create a test app
@@ -1418,12 +6172,42 @@ mod tests {
         let config = AppConfig {
             config_paths: vec!["/test".to_string()],
             search_filter: None,
+            connect_if_unique: false,
             sort_by_name: false,
             show_proxy_command: false,
+            sort_given: false,
+            show_proxy_command_given: false,
             command_template: "ssh {destination}".to_string(),
             command_template_on_session_start: None,
             command_template_on_session_end: None,
+            command_template_on_connection_failure: None,
+            command_template_on_socks: None,
             exit_after_ssh_session_ends: false,
+            socks_port: 1080,
+            default_user: None,
+            dotfiles: Vec::new(),
+            shared_metadata_path: None,
+            connect_delay: Duration::ZERO,
+            end_screen_delay: None,
+            tab_naming: TabNaming::default(),
+            workspace: None,
+            stats_file: None,
+            audit_log: None,
+            no_exec: false,
+            dns_prefetch: false,
+            health_check_interval: None,
+            unused_after_months: 6,
+            leader: crate::ui::actions::DEFAULT_LEADER,
+            display: config_file::Display::default(),
+            keymap: Keymap::default(),
+            log_sessions: false,
+            session_log_dir: "/test/logs".to_string(),
+            capabilities: Capabilities::default(),
+            triggers: Vec::new(),
+            backend: BackendKind::Exec,
+            state_path: "/test/state.json".to_string(),
+            vault: None,
+            managed_config_path: None,
         };
 
         App {
@@ -1442,10 +6226,55 @@ mod tests {
             editing_host_index: None,
             confirm_message: None,
             confirm_action: None,
+            pending_action: None,
+            state: State::default(),
             focus_state: FocusState::Normal,
             last_key_time: None,
             pending_g: false,
+            pending_d: false,
+            leader_keys: None,
             tab_manager: TabManager::new(),
+            session_shown: false,
+            session_size: None,
+            resized_at: None,
+            pending_paste: None,
+            backend: Box::new(crate::backend::ExecBackend),
+            menu: None,
+            prompt: None,
+            jump_target: None,
+            backups: None,
+            audit_log: None,
+            timeline: None,
+            background_tunnels: None,
+            socks_units: BTreeSet::new(),
+            sftp: None,
+            transfer: None,
+            network: None,
+            marked_hosts: BTreeSet::new(),
+            visual_anchor: None,
+            batch_run: None,
+            config_errors: None,
+            settings: Settings::from_flags(false, false),
+            settings_panel: None,
+            collapsed_groups: BTreeSet::new(),
+            recent_view: false,
+            state_filter: StateFilter::All,
+            state_counts: StateCounts::default(),
+            incident: None,
+            presentation: false,
+            authorized_keys: None,
+            deleted_host: None,
+            show_details: false,
+            details_option: 0,
+            details_help: false,
+            certificates: BTreeMap::new(),
+            dns: None,
+            health: None,
+            tag_edit: None,
+            tutorial: None,
+            update_checker: None,
+            available_update: None,
+            show_changelog: false,
         }
     }
 
@@ -1498,6 +6327,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
             Host {
                 name: "host2".to_string(),
@@ -1506,6 +6336,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
             Host {
                 name: "host3".to_string(),
@@ -1514,6 +6345,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
         ];
 
@@ -1549,6 +6381,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
             Host {
                 name: "host2".to_string(),
@@ -1557,6 +6390,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
         ];
 
@@ -1630,6 +6464,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
             Host {
                 name: "prod-host".to_string(),
@@ -1638,6 +6473,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
         ];
         // Create proper search closure that mimics the real search behavior
@@ -1688,6 +6524,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
             Host {
                 name: "prod-host".to_string(),
@@ -1696,6 +6533,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
         ];
         // Create proper search closure that mimics the real search behavior
@@ -1746,6 +6584,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
             Host {
                 name: "test-host-2".to_string(),
@@ -1754,6 +6593,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
         ];
 
@@ -1786,6 +6626,110 @@ mod tests {
         assert!(confirm_msg.contains("cannot be undone"));
     }
 
+    #[test]
+    fn test_remove_host_entry_keeps_comments_and_spacing() {
+        // (config, host to remove, expected config)
+        let fixtures = [
+            // The comment above the next block documents it
+            (
+                "Host web\n  Hostname web.example.com\n\n# Database\nHost db\n  User postgres\n",
+                "web",
+                "# Database\nHost db\n  User postgres\n",
+            ),
+            // The comment above the block goes with it, the blank lines around it merge
+            (
+                "Host web\n  Port 22\n\n\n# Database\n# (primary)\nHost db\n  User postgres\n\n\nHost gw\n",
+                "db",
+                "Host web\n  Port 22\n\nHost gw\n",
+            ),
+            // Indented comments, e.g. metadata, belong to the block above them
+            (
+                "Host web\n  Port 22\n  # sshs:tags=prod\nHost db\n  User postgres\n",
+                "db",
+                "Host web\n  Port 22\n  # sshs:tags=prod\n",
+            ),
+            // A comment separated from the block by a blank line stays
+            (
+                "# Global settings\n\nHost web\n  Port 22\n\nHost db\n",
+                "web",
+                "# Global settings\n\nHost db\n",
+            ),
+            // Last block: no blank lines left at the end
+            (
+                "Host web\n  Port 22\n\nHost db\n  User postgres\n\n",
+                "db",
+                "Host web\n  Port 22\n",
+            ),
+            // Blocks without blank lines between them stay that way
+            (
+                "Host web\n  Port 22\nHost db\n  User postgres\nHost gw\n",
+                "db",
+                "Host web\n  Port 22\nHost gw\n",
+            ),
+            // Match blocks end a host block too
+            (
+                "Host web\n  Port 22\n\n# Production\nMatch host *.prod\n  User deploy\n",
+                "web",
+                "# Production\nMatch host *.prod\n  User deploy\n",
+            ),
+            ("Host web\n  Port 22\n", "web", ""),
+        ];
+
+        for (config, name, expected) in fixtures {
+            let host = ssh::Host {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            assert_eq!(
+                App::remove_host_entry(config, &host).unwrap(),
+                expected,
+                "removing {name} from {config:?}"
+            );
+        }
+
+        let host = ssh::Host {
+            name: "missing".to_string(),
+            ..Default::default()
+        };
+        assert!(App::remove_host_entry("Host web\n", &host).is_err());
+    }
+
+    #[test]
+    fn test_removed_block_takes_the_metadata_the_parser_gave_it() {
+        let tags = |config: &str| {
+            ssh_config::Parser::new()
+                .parse(&mut std::io::Cursor::new(config))
+                .unwrap()
+                .iter()
+                .map(|host| (host.get_patterns()[0].clone(), host.tags()))
+                .collect::<Vec<_>>()
+        };
+        let config = "Host a\n  Port 22\n  # sshs:tags=a\n# sshs:tags=b\nHost b\n  User postgres\n";
+        assert_eq!(
+            tags(config),
+            vec![
+                ("a".to_string(), vec!["a".to_string()]),
+                ("b".to_string(), vec!["b".to_string()])
+            ]
+        );
+
+        let remove = |name: &str| {
+            let host = ssh::Host {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            App::remove_host_entry(config, &host).unwrap()
+        };
+        assert_eq!(
+            tags(&remove("a")),
+            vec![("b".to_string(), vec!["b".to_string()])]
+        );
+        assert_eq!(
+            tags(&remove("b")),
+            vec![("a".to_string(), vec!["a".to_string()])]
+        );
+    }
+
     #[test]
     fn test_feedback_message_timeout() {
         let mut app = create_test_app();
@@ -1838,6 +6782,7 @@ mod tests {
             port: None,
             aliases: String::new(),
             proxy_command: None,
+            ..Default::default()
         }];
         // Create proper search closure
         let matcher = SkimMatcherV2::default();
@@ -1885,6 +6830,7 @@ mod tests {
             port: None,
             aliases: String::new(),
             proxy_command: None,
+            ..Default::default()
         }];
 
         let matcher = SkimMatcherV2::default();
@@ -1911,6 +6857,330 @@ mod tests {
         assert!(!app.is_feedback_error);
     }
 
+    /// Backend running a local command in the tabs, e.g. `cat` which echoes what is typed
+    struct TabBackend(&'static str);
+
+    impl ConnectionBackend for TabBackend {
+        fn name(&self) -> &'static str {
+            "tab"
+        }
+
+        fn interactive(
+            &self,
+            _host: &ssh::Host,
+            _options: &ssh::ConnectOptions,
+        ) -> Result<Option<i32>> {
+            Ok(Some(0))
+        }
+
+        fn spawn_tab(
+            &self,
+            _host: &ssh::Host,
+            _options: &ssh::ConnectOptions,
+            rows: u16,
+            cols: u16,
+        ) -> Result<session::PtySession> {
+            session::PtySession::spawn(&[self.0.to_string()], rows, cols)
+        }
+
+        fn batch(&self, _host: &ssh::Host, _script: &str) -> Result<String> {
+            Ok(String::new())
+        }
+    }
+
+    #[test]
+    fn test_shown_session_gets_the_keys() {
+        let mut app = create_test_app();
+        let host = ssh::Host {
+            name: "web".to_string(),
+            ..Default::default()
+        };
+        let press = |app: &mut App, code, modifiers| {
+            app.handle_session_keys(KeyEvent::new(code, modifiers));
+        };
+        let wait_until = |app: &mut App, done: &dyn Fn(&App) -> bool| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !done(app) {
+                assert!(Instant::now() < deadline, "timed out");
+                thread::sleep(Duration::from_millis(10));
+                app.poll_sessions(5, 20);
+            }
+        };
+        let screen = |app: &App| {
+            app.tab_manager
+                .current_session()
+                .unwrap()
+                .pty
+                .as_ref()
+                .unwrap()
+                .parser()
+                .screen()
+                .contents()
+        };
+
+        // A session that cannot start closes its tab
+        app.backend = Box::new(TabBackend("sshs-missing-command"));
+        app.tab_manager.add_session(host.clone()).unwrap();
+        app.session_shown = true;
+        app.poll_sessions(5, 20);
+        assert!(!app.tab_manager.has_sessions());
+        assert!(!app.session_shown);
+        assert!(app.is_feedback_error);
+        assert!(app
+            .feedback_message
+            .as_ref()
+            .unwrap()
+            .contains("could not start"));
+
+        app.backend = Box::new(TabBackend("cat"));
+        app.tab_manager.add_session(host).unwrap();
+        app.session_shown = true;
+        app.poll_sessions(5, 20);
+        assert!(app.tab_manager.current_session().unwrap().is_connected());
+
+        press(&mut app, KeyCode::Char('h'), KeyModifiers::NONE);
+        press(&mut app, KeyCode::Char('i'), KeyModifiers::NONE);
+        wait_until(&mut app, &|app| screen(app).contains("hi"));
+
+        // Nothing reaches a locked tab
+        press(&mut app, KeyCode::Char('l'), KeyModifiers::CONTROL);
+        press(&mut app, KeyCode::Char('x'), KeyModifiers::NONE);
+        assert!(app.is_feedback_error);
+        app.tab_manager.current_session_mut().unwrap().locked = false;
+
+        press(&mut app, KeyCode::Char('q'), KeyModifiers::CONTROL);
+        assert!(!app.session_shown);
+        press(&mut app, KeyCode::Char('1'), KeyModifiers::CONTROL);
+        assert!(app.session_shown);
+
+        // Ending the session closes its tab
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        wait_until(&mut app, &|app| !app.tab_manager.has_sessions());
+        assert!(!app.session_shown);
+        assert_eq!(
+            app.feedback_message.as_deref(),
+            Some("Session 2 on web ended (exit 0)")
+        );
+        assert!(!app.is_feedback_error);
+
+        // Both are in the history of the host, as connections taking over the terminal are
+        let kinds: Vec<_> = app.state.host_events["web"]
+            .iter()
+            .map(|event| event.kind)
+            .collect();
+        assert_eq!(kinds, [HostEventKind::Failure, HostEventKind::Connection]);
+    }
+
+    #[test]
+    fn test_multi_line_paste_is_confirmed() {
+        let mut app = create_test_app();
+        let mut host = ssh::Host {
+            name: "web".to_string(),
+            ..Default::default()
+        };
+        let screen = |app: &App| {
+            app.tab_manager
+                .current_session()
+                .unwrap()
+                .pty
+                .as_ref()
+                .unwrap()
+                .parser()
+                .screen()
+                .contents()
+        };
+        let wait_until = |app: &mut App, text: &str| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !screen(app).contains(text) {
+                assert!(Instant::now() < deadline, "timed out");
+                thread::sleep(Duration::from_millis(10));
+                app.poll_sessions(5, 20);
+            }
+        };
+
+        app.backend = Box::new(TabBackend("cat"));
+        app.tab_manager.add_session(host.clone()).unwrap();
+        app.session_shown = true;
+        app.poll_sessions(5, 20);
+
+        // A single line is sent straight away
+        app.paste_into_session("uptime");
+        assert!(app.pending_paste.is_none());
+        wait_until(&mut app, "uptime");
+
+        app.paste_into_session("ls\nreboot\n");
+        assert_eq!(app.pending_paste.as_deref(), Some("ls\nreboot\n"));
+        // Other keys leave the question open
+        app.handle_paste_keys(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert!(app.pending_paste.is_some());
+        app.handle_paste_keys(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(app.pending_paste.is_none());
+        assert_eq!(app.feedback_message.as_deref(), Some("Paste cancelled"));
+
+        app.paste_into_session("echo one\necho two\n");
+        app.handle_paste_keys(KeyEvent::new(KeyCode::Char('t'), KeyModifiers::NONE));
+        wait_until(&mut app, "echo two");
+
+        // Trusted hosts skip the question
+        host.metadata
+            .insert("paste".to_string(), "allow".to_string());
+        app.tab_manager.current_session_mut().unwrap().host = host;
+        app.paste_into_session("date\n");
+        assert!(app.pending_paste.is_none());
+        wait_until(&mut app, "date");
+    }
+
+    #[test]
+    fn test_every_session_is_resized_once_resizing_stops() {
+        let mut app = create_test_app();
+        app.backend = Box::new(TabBackend("cat"));
+        for name in ["web", "db"] {
+            let host = ssh::Host {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            app.tab_manager.add_session(host).unwrap();
+        }
+        app.poll_sessions(5, 20);
+        let sizes = |app: &App| {
+            app.tab_manager
+                .sessions()
+                .iter()
+                .map(|session| session.pty.as_ref().unwrap().parser().screen().size())
+                .collect::<Vec<_>>()
+        };
+
+        app.resized_at = Some(Instant::now());
+        app.resize_sessions(10, 40);
+        assert_eq!(sizes(&app), [(5, 20), (5, 20)]);
+
+        app.resized_at = Instant::now().checked_sub(RESIZE_DEBOUNCE);
+        app.resize_sessions(10, 40);
+        assert_eq!(sizes(&app), [(10, 40), (10, 40)]);
+        assert_eq!(app.session_size, Some((10, 40)));
+        assert!(app.resized_at.is_none());
+    }
+
+    #[test]
+    fn test_new_session_asks_what_a_connection_asks() {
+        let mut app = create_test_app();
+        app.hosts = Searchable::new(
+            vec![ssh::Host {
+                name: "legacy".to_string(),
+                metadata: [
+                    ("banner".to_string(), "Decommissioned on Friday".to_string()),
+                    ("banner-important".to_string(), "yes".to_string()),
+                ]
+                .into(),
+                ..Default::default()
+            }],
+            "",
+            |_, _| true,
+        );
+        app.table_state.select(Some(0));
+
+        app.open_new_session();
+        assert!(!app.tab_manager.has_sessions());
+        assert_eq!(app.confirm_action.as_deref(), Some(BANNER_ACTION));
+
+        let proceed = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        app.on_form_key_press(proceed).unwrap();
+        let Some(PendingAction::Connect(options)) = app.pending_action.take() else {
+            panic!("the session is not pending");
+        };
+        assert!(options.in_tab && options.banner_acknowledged);
+
+        let host = app.connection_host(0, &options).unwrap();
+        app.open_session_tab(host, &options);
+        assert!(app.session_shown);
+        assert_eq!(app.tab_manager.current_session().unwrap().options, options);
+        assert_eq!(
+            app.feedback_message.as_deref(),
+            Some("legacy: Decommissioned on Friday")
+        );
+    }
+
+    #[test]
+    fn test_state_filter_combines_with_the_search() {
+        let mut app = create_test_app();
+        let host = |name: &str| ssh::Host {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        app.hosts = Searchable::new(
+            vec![host("web-1"), host("web-2"), host("db-1")],
+            "",
+            host_search_predicate(Matcher::Substring),
+        );
+        app.tab_manager.add_session(host("web-2")).unwrap();
+        app.tab_manager.add_session(host("db-1")).unwrap();
+
+        app.search = Input::new("web".to_string());
+        app.refresh_search();
+        assert_eq!(app.hosts.len(), 2);
+        assert_eq!(
+            app.state_counts,
+            StateCounts {
+                reachable: 0,
+                unreachable: 0,
+                sessions: 1,
+            }
+        );
+
+        // Unchecked hosts are neither reachable nor unreachable
+        app.cycle_state_filter();
+        assert_eq!(app.state_filter, StateFilter::Reachable);
+        assert!(app.hosts.is_empty());
+
+        app.state_filter = StateFilter::Unreachable;
+        app.cycle_state_filter();
+        assert_eq!(app.state_filter, StateFilter::WithSession);
+        assert_eq!(
+            app.hosts
+                .iter()
+                .map(|host| host.name.as_str())
+                .collect::<Vec<_>>(),
+            ["web-2"]
+        );
+
+        app.cycle_state_filter();
+        assert_eq!(app.hosts.len(), 2);
+    }
+
+    #[test]
+    fn test_incident_mode_puts_things_back() {
+        let mut app = create_test_app();
+        app.toggle_incident_mode();
+        assert_eq!(app.state_filter, StateFilter::Unreachable);
+        assert_eq!(app.group_mode(), GroupMode::None);
+        assert_eq!(
+            app.health.as_ref().and_then(HealthChecker::interval),
+            Some(incident::CHECK_INTERVAL)
+        );
+
+        // Health checks started for the incident stop with it
+        app.toggle_incident_mode();
+        assert!(app.incident.is_none());
+        assert_eq!(app.state_filter, StateFilter::All);
+        assert!(app.health.is_none());
+
+        let interval = Some(Duration::from_secs(30));
+        app.health = Some(HealthChecker::new(interval));
+        app.state_filter = StateFilter::WithSession;
+        app.toggle_incident_mode();
+        assert_eq!(
+            app.health.as_ref().and_then(HealthChecker::interval),
+            Some(incident::CHECK_INTERVAL)
+        );
+        app.toggle_incident_mode();
+        assert_eq!(app.state_filter, StateFilter::WithSession);
+        assert_eq!(
+            app.health.as_ref().and_then(HealthChecker::interval),
+            interval
+        );
+    }
+
     #[test]
     fn test_open_new_session_without_host() {
         let mut app = create_test_app();
@@ -1938,6 +7208,7 @@ mod tests {
             port: None,
             aliases: String::new(),
             proxy_command: None,
+            ..Default::default()
         }];
 
         let matcher = SkimMatcherV2::default();
@@ -1979,6 +7250,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
             Host {
                 name: "host2".to_string(),
@@ -1987,6 +7259,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
         ];
 
@@ -2042,6 +7315,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
             Host {
                 name: "host2".to_string(),
@@ -2050,6 +7324,7 @@ mod tests {
                 port: None,
                 aliases: String::new(),
                 proxy_command: None,
+                ..Default::default()
             },
         ];
 
@@ -2081,4 +7356,200 @@ mod tests {
         assert!(app.feedback_message.is_some());
         assert!(app.is_feedback_error); // Should show error message
     }
+
+    #[test]
+    fn test_connect_if_unique_needs_a_single_match() {
+        let mut app = create_test_app();
+        let hosts = ["web-1", "web-2", "db"].map(|name| ssh::Host {
+            name: name.to_string(),
+            ..Default::default()
+        });
+        let predicate = |host: &&ssh::Host, value: &str| host.name.contains(value);
+
+        app.config.connect_if_unique = true;
+        app.config.search_filter = Some("db".to_string());
+        app.hosts = Searchable::new(hosts.to_vec(), "db", predicate);
+        assert!(app.connects_at_startup());
+
+        // Several matches open the filtered list instead
+        app.config.search_filter = Some("web".to_string());
+        app.hosts = Searchable::new(hosts.to_vec(), "web", predicate);
+        assert!(!app.connects_at_startup());
+
+        // So does the flag alone, without a filter to narrow the list with
+        app.config.search_filter = None;
+        app.hosts = Searchable::new(hosts[..1].to_vec(), "", predicate);
+        assert!(!app.connects_at_startup());
+    }
+
+    #[test]
+    fn test_agent_forwarding_requires_confirmation() {
+        let mut app = create_test_app();
+        app.hosts = Searchable::new(
+            vec![ssh::Host {
+                name: "bastion".to_string(),
+                destination: "bastion.example.com".to_string(),
+                ..Default::default()
+            }],
+            "",
+            |_, _| true,
+        );
+        app.table_state.select(Some(0));
+
+        // Plain connections go straight through
+        assert_eq!(
+            app.prepare_connection(ssh::ConnectOptions::default()),
+            Some(ssh::ConnectOptions::default())
+        );
+
+        // Agent forwarding to an untrusted host is deferred to a confirmation
+        let options = ssh::ConnectOptions {
+            forward_agent: true,
+            ..Default::default()
+        };
+        assert_eq!(app.prepare_connection(options.clone()), None);
+        assert_eq!(app.form_state, FormState::Confirming);
+        assert_eq!(app.confirm_action.as_deref(), Some(AGENT_FORWARDING_ACTION));
+
+        // Trusting the host keeps the pending connection and skips the prompt next time
+        let trust = KeyEvent::new(KeyCode::Char('t'), KeyModifiers::NONE);
+        assert_eq!(app.on_form_key_press(trust).unwrap(), AppKeyAction::Ok);
+        assert_eq!(app.form_state, FormState::Hidden);
+        assert_eq!(
+            app.pending_action,
+            Some(PendingAction::Connect(options.clone()))
+        );
+        assert!(app.state.is_agent_trusted("bastion"));
+
+        app.pending_action = None;
+        assert_eq!(app.prepare_connection(options.clone()), Some(options));
+    }
+
+    #[test]
+    fn test_important_banner_requires_acknowledgement() {
+        let mut app = create_test_app();
+        app.hosts = Searchable::new(
+            vec![ssh::Host {
+                name: "legacy".to_string(),
+                destination: "legacy.example.com".to_string(),
+                forward_agent: true,
+                metadata: [
+                    ("banner", "Decommissioned on Friday"),
+                    ("banner-important", "yes"),
+                ]
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+                ..Default::default()
+            }],
+            "",
+            |_, _| true,
+        );
+        app.table_state.select(Some(0));
+
+        assert_eq!(app.prepare_connection(ssh::ConnectOptions::default()), None);
+        assert_eq!(app.confirm_action.as_deref(), Some(BANNER_ACTION));
+        assert!(app
+            .confirm_message
+            .as_deref()
+            .is_some_and(|message| message.starts_with("Decommissioned on Friday")));
+
+        // Acknowledging the banner moves on to the agent forwarding confirmation
+        let proceed = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(app.on_form_key_press(proceed).unwrap(), AppKeyAction::Ok);
+        assert_eq!(app.confirm_action.as_deref(), Some(AGENT_FORWARDING_ACTION));
+        assert_eq!(
+            app.pending_action,
+            Some(PendingAction::Connect(ssh::ConnectOptions {
+                forward_agent: true,
+                banner_acknowledged: true,
+                ..Default::default()
+            }))
+        );
+
+        // Cancelling at the banner drops the connection
+        app.form_state = FormState::Hidden;
+        app.pending_action = None;
+        assert_eq!(app.prepare_connection(ssh::ConnectOptions::default()), None);
+        let cancel = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(app.on_form_key_press(cancel).unwrap(), AppKeyAction::Ok);
+        assert_eq!(app.form_state, FormState::Hidden);
+        assert!(app.pending_action.is_none());
+    }
+
+    #[test]
+    fn test_agent_forwarding_confirmation_cancel() {
+        let mut app = create_test_app();
+        app.hosts = Searchable::new(
+            vec![ssh::Host {
+                name: "shared".to_string(),
+                destination: "shared.example.com".to_string(),
+                forward_agent: true,
+                ..Default::default()
+            }],
+            "",
+            |_, _| true,
+        );
+        app.table_state.select(Some(0));
+
+        // ForwardAgent from the SSH configuration triggers the prompt as well
+        assert_eq!(app.prepare_connection(ssh::ConnectOptions::default()), None);
+
+        let cancel = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(app.on_form_key_press(cancel).unwrap(), AppKeyAction::Ok);
+        assert_eq!(app.form_state, FormState::Hidden);
+        assert!(app.pending_action.is_none());
+        assert!(!app.state.is_agent_trusted("shared"));
+    }
+
+    #[test]
+    fn test_editing_system_host_offers_override_or_sudoedit() {
+        let mut app = create_test_app();
+        app.hosts = Searchable::new(
+            vec![ssh::Host {
+                name: "gateway".to_string(),
+                destination: "gw.example.com".to_string(),
+                source_file: Some(PathBuf::from("/etc/ssh/ssh_config")),
+                ..Default::default()
+            }],
+            "",
+            |_, _| true,
+        );
+        app.table_state.select(Some(0));
+
+        app.open_edit_host_form();
+        assert_eq!(app.form_state, FormState::Confirming);
+        assert_eq!(app.confirm_action.as_deref(), Some(SYSTEM_EDIT_ACTION));
+
+        // Unrelated keys keep the dialog open
+        let other = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE);
+        assert_eq!(
+            app.on_form_key_press(other).unwrap(),
+            AppKeyAction::Continue
+        );
+        assert_eq!(app.form_state, FormState::Confirming);
+
+        let sudoedit = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::NONE);
+        assert_eq!(app.on_form_key_press(sudoedit).unwrap(), AppKeyAction::Ok);
+        assert_eq!(app.form_state, FormState::Hidden);
+        assert_eq!(
+            app.pending_action,
+            Some(PendingAction::SudoEdit(PathBuf::from(
+                "/etc/ssh/ssh_config"
+            )))
+        );
+
+        // Overriding opens a prefilled form adding a new block to the user configuration
+        app.pending_action = None;
+        app.open_edit_host_form();
+        let override_key = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(
+            app.on_form_key_press(override_key).unwrap(),
+            AppKeyAction::Ok
+        );
+        assert_eq!(app.form_state, FormState::Active);
+        assert!(!app.is_edit_mode);
+        assert!(app.add_host_form.is_some());
+        assert!(app.pending_action.is_none());
+    }
 }
@@ -0,0 +1,272 @@
+//! Opt-in audit log (`--audit-log`) of the changes sshs makes to the SSH configuration: who
+//! changed which file, when, and the lines added and removed. Entries are appended as JSON
+//! lines, the log is never rewritten.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::backups::{diff_lines, format_age, DiffLine};
+use crate::ssh;
+
+/// One change made through sshs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Unix time of the change
+    pub time: u64,
+    pub user: String,
+    /// What was done, e.g. `delete host`
+    pub action: String,
+    /// Hosts the change is about, comma separated, empty for whole-file changes
+    pub hosts: String,
+    pub file: String,
+    /// Changed lines, prefixed with `+` when added and `-` when removed
+    pub diff: Vec<String>,
+}
+
+impl Entry {
+    #[must_use]
+    pub fn diff_lines(&self) -> Vec<DiffLine> {
+        self.diff
+            .iter()
+            .map(|line| match line.split_at(line.len().min(1)) {
+                ("+", text) => DiffLine::Added(text.to_string()),
+                ("-", text) => DiffLine::Removed(text.to_string()),
+                _ => DiffLine::Unchanged(line.clone()),
+            })
+            .collect()
+    }
+
+    /// Age of the change relative to `now`, e.g. `5m ago`.
+    #[must_use]
+    pub fn age(&self, now: SystemTime) -> String {
+        now.duration_since(UNIX_EPOCH + Duration::from_secs(self.time))
+            .map_or_else(|_| "just now".to_string(), format_age)
+    }
+}
+
+fn open(path: &str) -> io::Result<fs::File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(shellexpand::tilde(path).as_ref())
+}
+
+/// Makes sure the log can be appended to, so that sshs does not start making changes it
+/// cannot record.
+///
+/// # Errors
+///
+/// Will return `Err` if the log cannot be created or opened for writing.
+pub fn check(path: &str) -> anyhow::Result<()> {
+    open(path).with_context(|| format!("Cannot write to the audit log '{path}'"))?;
+    Ok(())
+}
+
+/// Appends the change from `before` to `after` that `action` made to `file` to `log`. Nothing
+/// is recorded without a log, or when the content did not change.
+///
+/// # Errors
+///
+/// Will return `Err` if the log cannot be written.
+pub fn record(
+    log: Option<&str>,
+    action: &str,
+    hosts: &str,
+    file: &str,
+    before: &str,
+    after: &str,
+) -> anyhow::Result<()> {
+    let Some(path) = log else {
+        return Ok(());
+    };
+    if before == after {
+        return Ok(());
+    }
+
+    let entry = Entry {
+        time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs()),
+        user: ssh::local_username().unwrap_or_else(|| "unknown".to_string()),
+        action: action.to_string(),
+        hosts: hosts.to_string(),
+        file: file.to_string(),
+        diff: diff_lines(before, after)
+            .into_iter()
+            .filter_map(|line| match line {
+                DiffLine::Added(text) => Some(format!("+{text}")),
+                DiffLine::Removed(text) => Some(format!("-{text}")),
+                DiffLine::Unchanged(_) => None,
+            })
+            .collect(),
+    };
+
+    // A single write per entry, so concurrent instances do not interleave their lines
+    let line = format!("{}\n", serde_json::to_string(&entry)?);
+    open(path)
+        .and_then(|mut log| log.write_all(line.as_bytes()))
+        .with_context(|| format!("Failed to write to the audit log '{path}'"))
+}
+
+/// Content of `file` before a change, `None` when there is no `log` to record it in.
+#[must_use]
+pub fn snapshot(log: Option<&str>, file: &str) -> Option<String> {
+    log.map(|_| fs::read_to_string(file).unwrap_or_default())
+}
+
+/// Records the change `action` made to `file` since `before` was taken with [`snapshot`].
+///
+/// # Errors
+///
+/// Will return `Err` if the log cannot be written.
+pub fn record_change(
+    log: Option<&str>,
+    action: &str,
+    hosts: &str,
+    file: &str,
+    before: Option<String>,
+) -> anyhow::Result<()> {
+    let Some(before) = before else {
+        return Ok(());
+    };
+    let after = fs::read_to_string(file).unwrap_or_default();
+
+    record(log, action, hosts, file, &before, &after)
+}
+
+/// Reads the log, most recent change first.
+///
+/// # Errors
+///
+/// Will return `Err` if the log cannot be read or has a line that is not an entry.
+pub fn read(path: &str) -> anyhow::Result<Vec<Entry>> {
+    let content = match fs::read_to_string(shellexpand::tilde(path).as_ref()) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        result => result.with_context(|| format!("Failed to read the audit log '{path}'"))?,
+    };
+
+    let mut entries = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid audit log entry at line {}", index + 1))
+        })
+        .collect::<anyhow::Result<Vec<Entry>>>()?;
+    entries.reverse();
+
+    Ok(entries)
+}
+
+/// Exports the whole log as a JSON array to `<log>.json`, oldest change first. Returns the
+/// path of the export.
+///
+/// # Errors
+///
+/// Will return `Err` if the log cannot be read or the export cannot be written.
+pub fn export(path: &str) -> anyhow::Result<PathBuf> {
+    let mut entries = read(path)?;
+    entries.reverse();
+
+    let export = PathBuf::from(format!("{}.json", shellexpand::tilde(path)));
+    fs::write(&export, serde_json::to_string_pretty(&entries)?)
+        .with_context(|| format!("Failed to write '{}'", export.display()))?;
+
+    Ok(export)
+}
+
+/// Audit log view: the changes, and the diff of the selected one.
+#[derive(Debug, Default)]
+pub struct AuditLogView {
+    pub entries: Vec<Entry>,
+    pub selected: usize,
+    /// Whether the diff of the selected change is shown
+    pub show_diff: bool,
+    pub scroll: u16,
+}
+
+impl AuditLogView {
+    #[must_use]
+    pub fn new(entries: Vec<Entry>) -> Self {
+        Self {
+            entries,
+            ..Default::default()
+        }
+    }
+
+    #[must_use]
+    pub fn selected_entry(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_changes_are_appended_and_read_back() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("audit.log");
+        let log = log.to_str().unwrap();
+
+        check(log).unwrap();
+        assert!(read(log).unwrap().is_empty());
+
+        let before = "Host web\n  Hostname web.example.com\n\nHost db\n  Hostname db\n";
+        let after = "Host db\n  Hostname db\n";
+        record(
+            Some(log),
+            "delete host",
+            "web",
+            "/ssh/config",
+            before,
+            after,
+        )
+        .unwrap();
+        record(Some(log), "edit host", "db", "/ssh/config", after, after).unwrap();
+        let edited = "Host db\n  Port 2222\n";
+        record(Some(log), "edit host", "db", "/ssh/config", after, edited).unwrap();
+
+        let entries = read(log).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].action, "edit host");
+        assert_eq!(entries[1].action, "delete host");
+        assert_eq!(entries[1].hosts, "web");
+        assert_eq!(
+            entries[1].diff,
+            ["-Host web", "-  Hostname web.example.com", "-"]
+        );
+        assert_eq!(
+            entries[0].diff_lines(),
+            [
+                DiffLine::Removed("  Hostname db".to_string()),
+                DiffLine::Added("  Port 2222".to_string()),
+            ]
+        );
+
+        let export = export(log).unwrap();
+        let exported: Vec<Entry> =
+            serde_json::from_str(&fs::read_to_string(export).unwrap()).unwrap();
+        assert_eq!(exported[0].action, "delete host");
+
+        fs::write(log, "not json\n").unwrap();
+        assert!(read(log).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+//! `~/.ssh/authorized_keys` of a remote host, fetched over a non-interactive `ssh` call and
+//! annotated with the matching local keys.
+
+use std::process::Command;
+
+use crate::backend::ConnectionBackend;
+use crate::exec;
+use crate::ssh;
+
+const AUTHORIZED_KEYS: &str = "~/.ssh/authorized_keys";
+
+/// An entry of an `authorized_keys` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorizedKey {
+    /// The whole line, used to remove the entry
+    pub line: String,
+    /// Options before the key (`from="10.0.0.0/8",no-pty`), if any
+    pub options: Option<String>,
+    pub key_type: String,
+    pub blob: String,
+    pub comment: String,
+    /// Local key (file or agent) matching this entry
+    pub local_match: Option<String>,
+}
+
+impl AuthorizedKey {
+    /// End of the key blob, enough to tell the keys apart.
+    #[must_use]
+    pub fn short_blob(&self) -> &str {
+        let start = self.blob.len().saturating_sub(12);
+        self.blob.get(start..).unwrap_or(&self.blob)
+    }
+}
+
+/// Returns the type and blob of a public key line, skipping leading options.
+fn split_key(line: &str) -> Option<(Option<String>, &str, &str, String)> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let key_index = tokens.iter().position(|token| is_key_type(token))?;
+    let blob = tokens.get(key_index + 1)?;
+
+    let options = (key_index > 0).then(|| tokens[..key_index].join(" "));
+    let comment = tokens[key_index + 2..].join(" ");
+
+    Some((options, tokens[key_index], blob, comment))
+}
+
+fn is_key_type(token: &str) -> bool {
+    token.starts_with("ssh-")
+        || token.starts_with("ecdsa-")
+        || token.starts_with("sk-ssh-")
+        || token.starts_with("sk-ecdsa-")
+}
+
+/// Parses `authorized_keys` content, matching the entries against `local_keys` (public key line,
+/// source) pairs.
+#[must_use]
+pub fn parse(content: &str, local_keys: &[(String, String)]) -> Vec<AuthorizedKey> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (options, key_type, blob, comment) = split_key(line)?;
+            let local_match = local_keys
+                .iter()
+                .find(|(local, _)| {
+                    split_key(local).is_some_and(|(_, local_type, local_blob, _)| {
+                        local_type == key_type && local_blob == blob
+                    })
+                })
+                .map(|(_, source)| source.clone());
+
+            Some(AuthorizedKey {
+                line: line.to_string(),
+                options,
+                key_type: key_type.to_string(),
+                blob: blob.to_string(),
+                comment,
+                local_match,
+            })
+        })
+        .collect()
+}
+
+/// Public keys of this machine: the `~/.ssh/*.pub` files and the keys loaded in the agent.
+#[must_use]
+pub fn local_public_keys() -> Vec<(String, String)> {
+    let mut keys = Vec::new();
+
+    let pattern = shellexpand::tilde("~/.ssh/*.pub").to_string();
+    for path in glob::glob(&pattern).into_iter().flatten().flatten() {
+        if let Ok(key) = std::fs::read_to_string(&path) {
+            let source = path
+                .file_name()
+                .map(|name| format!("~/.ssh/{}", name.to_string_lossy()))
+                .unwrap_or_default();
+            keys.push((key.trim().to_string(), source));
+        }
+    }
+
+    if let Ok(output) = exec::output(Command::new("ssh-add").arg("-L")) {
+        if output.status.success() {
+            for key in String::from_utf8_lossy(&output.stdout).lines() {
+                keys.push((key.trim().to_string(), "agent".to_string()));
+            }
+        }
+    }
+
+    keys
+}
+
+/// Fetches the `authorized_keys` of the user `host` connects as. A missing file is empty.
+///
+/// # Errors
+///
+/// Will return `Err` if the host cannot be reached without a password prompt.
+pub fn fetch(backend: &dyn ConnectionBackend, host: &ssh::Host) -> anyhow::Result<String> {
+    backend.batch(host, &format!("cat {AUTHORIZED_KEYS} 2>/dev/null || true"))
+}
+
+/// Removes the entry from the remote `authorized_keys`, keeping the previous version in
+/// `authorized_keys.sshs.bak`.
+///
+/// # Errors
+///
+/// Will return `Err` if the host cannot be reached or the file cannot be rewritten.
+pub fn remove(
+    backend: &dyn ConnectionBackend,
+    host: &ssh::Host,
+    key: &AuthorizedKey,
+) -> anyhow::Result<()> {
+    backend.batch(host, &removal_script(key)).map(|_| ())
+}
+
+fn removal_script(key: &AuthorizedKey) -> String {
+    let line = shlex::try_quote(&key.line).unwrap_or_default();
+
+    // Rewriting the file in place keeps its permissions
+    format!(
+        "cp {AUTHORIZED_KEYS} {AUTHORIZED_KEYS}.sshs.bak && \
+         {{ grep -vxF -e {line} {AUTHORIZED_KEYS}.sshs.bak || true; }} > {AUTHORIZED_KEYS}"
+    )
+}
+
+/// State of the authorized keys view.
+#[derive(Debug)]
+pub struct AuthorizedKeysView {
+    pub host: ssh::Host,
+    pub keys: Vec<AuthorizedKey>,
+    pub selected: usize,
+    /// Waiting for the user to confirm removing the selected entry
+    pub confirm_remove: bool,
+}
+
+impl AuthorizedKeysView {
+    #[must_use]
+    pub fn new(host: ssh::Host, keys: Vec<AuthorizedKey>) -> Self {
+        Self {
+            host,
+            keys,
+            selected: 0,
+            confirm_remove: false,
+        }
+    }
+
+    #[must_use]
+    pub fn selected_key(&self) -> Option<&AuthorizedKey> {
+        self.keys.get(self.selected)
+    }
+
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.keys.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Replaces the entries after the file changed, keeping the selection in range.
+    pub fn replace_keys(&mut self, keys: Vec<AuthorizedKey>) {
+        self.keys = keys;
+        self.selected = self.selected.min(self.keys.len().saturating_sub(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "# managed by ops
+ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIMe me@laptop
+from=\"10.0.0.0/8\",no-pty ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQC ci@build
+
+ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTY old key
+";
+
+    #[test]
+    fn test_parse_and_match_local_keys() {
+        let local_keys = vec![
+            (
+                "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIMe other-comment".to_string(),
+                "~/.ssh/id_ed25519.pub".to_string(),
+            ),
+            (
+                "ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQC".to_string(),
+                "agent".to_string(),
+            ),
+        ];
+
+        let keys = parse(CONTENT, &local_keys);
+
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys[0].comment, "me@laptop");
+        assert_eq!(
+            keys[0].local_match.as_deref(),
+            Some("~/.ssh/id_ed25519.pub")
+        );
+        assert_eq!(
+            keys[1].options.as_deref(),
+            Some("from=\"10.0.0.0/8\",no-pty")
+        );
+        assert_eq!(keys[1].key_type, "ssh-rsa");
+        assert_eq!(keys[1].local_match.as_deref(), Some("agent"));
+        assert_eq!(keys[2].comment, "old key");
+        assert_eq!(keys[2].local_match, None);
+        assert_eq!(keys[2].short_blob(), "tbmlzdHAyNTY");
+    }
+
+    #[test]
+    fn test_removal_script_matches_whole_line() {
+        let keys = parse(CONTENT, &[]);
+
+        assert_eq!(
+            removal_script(&keys[2]),
+            "cp ~/.ssh/authorized_keys ~/.ssh/authorized_keys.sshs.bak && \
+             { grep -vxF -e 'ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTY old key' \
+             ~/.ssh/authorized_keys.sshs.bak || true; } > ~/.ssh/authorized_keys"
+        );
+    }
+
+    /// Backend serving a fixed `authorized_keys` and recording the scripts it runs
+    struct FakeBackend {
+        scripts: std::cell::RefCell<Vec<String>>,
+    }
+
+    impl ConnectionBackend for FakeBackend {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn interactive(
+            &self,
+            _host: &ssh::Host,
+            _options: &ssh::ConnectOptions,
+        ) -> anyhow::Result<Option<i32>> {
+            Ok(Some(0))
+        }
+
+        fn batch(&self, _host: &ssh::Host, script: &str) -> anyhow::Result<String> {
+            self.scripts.borrow_mut().push(script.to_string());
+            Ok(CONTENT.to_string())
+        }
+    }
+
+    #[test]
+    fn test_fetch_and_remove_through_backend() {
+        let backend = FakeBackend {
+            scripts: std::cell::RefCell::default(),
+        };
+        let host = ssh::Host::default();
+
+        let keys = parse(&fetch(&backend, &host).unwrap(), &[]);
+        assert_eq!(keys.len(), 3);
+        remove(&backend, &host, &keys[2]).unwrap();
+
+        let scripts = backend.scripts.borrow();
+        assert_eq!(scripts[0], "cat ~/.ssh/authorized_keys 2>/dev/null || true");
+        assert_eq!(scripts[1], removal_script(&keys[2]));
+    }
+
+    #[test]
+    fn test_view_selection() {
+        let mut view = AuthorizedKeysView::new(ssh::Host::default(), parse(CONTENT, &[]));
+
+        view.previous();
+        assert_eq!(view.selected, 0);
+        view.next();
+        view.next();
+        view.next();
+        assert_eq!(view.selected, 2);
+
+        let mut keys = view.keys.clone();
+        keys.pop();
+        view.replace_keys(keys);
+        assert_eq!(view.selected, 1);
+        assert_eq!(view.keys.len(), 2);
+    }
+}
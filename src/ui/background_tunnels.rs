@@ -0,0 +1,480 @@
+//! Forwards run in the background as transient systemd user units (`systemd-run --user`), so
+//! that they outlive sshs: `S` starts a SOCKS proxy through the selected host, `t` any local,
+//! remote or dynamic forward (remembered per host, `L` starts them again), and `B` lists the
+//! units started by sshs, to stop them.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::process::Command;
+
+use crate::exec;
+use crate::ssh;
+
+/// Prefix of the units started by sshs, which tells them apart from the others
+pub const UNIT_PREFIX: &str = "sshs-";
+
+/// A port forwarded by a background tunnel, written as for `ssh` without the dash:
+/// `L 8080:db.internal:5432`, `R 9000:localhost:3000` or `D 1080`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Forward {
+    /// A port of this machine to an address as reached from the host (`-L`)
+    Local {
+        port: u16,
+        address: String,
+        target_port: u16,
+    },
+    /// A port of the host to an address as reached from this machine (`-R`)
+    Remote {
+        port: u16,
+        address: String,
+        target_port: u16,
+    },
+    /// A SOCKS proxy on a port of this machine (`-D`)
+    Dynamic { port: u16 },
+}
+
+impl fmt::Display for Forward {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Forward::Local { port, .. } => write!(f, "L {port}:{}", self.target()),
+            Forward::Remote { port, .. } => write!(f, "R {port}:{}", self.target()),
+            Forward::Dynamic { port } => write!(f, "D {port}"),
+        }
+    }
+}
+
+impl Forward {
+    /// Parses a forward as shown by `Display`, the dash of the `ssh` flag being allowed.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the kind, a port or the address is missing or invalid.
+    pub fn parse(input: &str) -> Result<Forward> {
+        let input = input.trim().trim_start_matches('-');
+        let mut chars = input.chars();
+        let kind = chars.next().map(|c| c.to_ascii_uppercase());
+        let spec = chars.as_str().trim();
+        let parse_port = |port: &str| {
+            port.trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|port| *port != 0)
+                .ok_or_else(|| anyhow!("Invalid port '{}'", port.trim()))
+        };
+
+        if kind == Some('D') {
+            return Ok(Forward::Dynamic {
+                port: parse_port(spec)?,
+            });
+        }
+        if !matches!(kind, Some('L' | 'R')) {
+            bail!("Expected L port:address:port, R port:address:port or D port");
+        }
+
+        let (port, target) = spec
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Expected port:address:port"))?;
+        let (address, target_port) = match target.strip_prefix('[') {
+            Some(rest) => rest
+                .split_once("]:")
+                .ok_or_else(|| anyhow!("Missing ']:' after the IPv6 address"))?,
+            None => target
+                .rsplit_once(':')
+                .ok_or_else(|| anyhow!("Expected port:address:port"))?,
+        };
+        if address.is_empty()
+            || !address
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':'))
+        {
+            bail!("Invalid address '{address}'");
+        }
+
+        let (port, address, target_port) = (
+            parse_port(port)?,
+            address.to_string(),
+            parse_port(target_port)?,
+        );
+        Ok(if kind == Some('L') {
+            Forward::Local {
+                port,
+                address,
+                target_port,
+            }
+        } else {
+            Forward::Remote {
+                port,
+                address,
+                target_port,
+            }
+        })
+    }
+
+    /// Port the forward listens on, here or on the host for remote forwards.
+    #[must_use]
+    pub fn port(&self) -> u16 {
+        match self {
+            Forward::Local { port, .. }
+            | Forward::Remote { port, .. }
+            | Forward::Dynamic { port } => *port,
+        }
+    }
+
+    /// `address:port` the connections are forwarded to, IPv6 addresses in brackets.
+    fn target(&self) -> String {
+        match self {
+            Forward::Local {
+                address,
+                target_port,
+                ..
+            }
+            | Forward::Remote {
+                address,
+                target_port,
+                ..
+            } if address.contains(':') => format!("[{address}]:{target_port}"),
+            Forward::Local {
+                address,
+                target_port,
+                ..
+            }
+            | Forward::Remote {
+                address,
+                target_port,
+                ..
+            } => format!("{address}:{target_port}"),
+            Forward::Dynamic { .. } => String::new(),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Forward::Local { .. } => "local",
+            Forward::Remote { .. } => "remote",
+            Forward::Dynamic { .. } => "socks",
+        }
+    }
+
+    /// Arguments of `ssh` setting up the forward.
+    #[must_use]
+    pub fn ssh_args(&self) -> [String; 2] {
+        match self {
+            Forward::Local { port, .. } => ["-L".to_string(), format!("{port}:{}", self.target())],
+            Forward::Remote { port, .. } => ["-R".to_string(), format!("{port}:{}", self.target())],
+            Forward::Dynamic { port } => ["-D".to_string(), port.to_string()],
+        }
+    }
+
+    /// e.g. `localhost:8080 to db.internal:5432 through web`.
+    #[must_use]
+    pub fn describe(&self, host: &str) -> String {
+        match self {
+            Forward::Local { port, .. } => {
+                format!("localhost:{port} to {} through {host}", self.target())
+            }
+            Forward::Remote { port, .. } => format!("{host}:{port} to {} here", self.target()),
+            Forward::Dynamic { port } => format!("SOCKS proxy on localhost:{port} through {host}"),
+        }
+    }
+}
+
+/// Name of the unit of `forward` through `host`, e.g. `sshs-socks-web-1080` or
+/// `sshs-local-web-8080`. Characters systemd does not allow in unit names are replaced.
+#[must_use]
+pub fn unit_name(host: &str, forward: &Forward) -> String {
+    let host: String = host
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    format!("{UNIT_PREFIX}{}-{host}-{}", forward.kind(), forward.port())
+}
+
+/// Arguments of the `systemd-run` command starting `forward` through `host`. `ssh` runs
+/// without a shell (`-N`) and cannot prompt for a password, and fails when the port cannot be
+/// forwarded, so that a failed forward shows as a failed unit.
+#[must_use]
+pub fn start_command(host: &ssh::Host, forward: &Forward) -> Vec<String> {
+    let mut ssh_command = host.build_ssh_command(&ssh::ConnectOptions::default());
+    let mut args = [
+        "-N",
+        "-o",
+        "BatchMode=yes",
+        "-o",
+        "ExitOnForwardFailure=yes",
+    ]
+    .map(str::to_string)
+    .to_vec();
+    args.extend(forward.ssh_args());
+    ssh_command.splice(1..1, args);
+
+    let mut command = vec![
+        "systemd-run".to_string(),
+        "--user".to_string(),
+        "--collect".to_string(),
+        format!("--unit={}", unit_name(&host.name, forward)),
+        format!("--description=sshs: {}", forward.describe(&host.name)),
+    ];
+    command.extend(ssh_command);
+
+    command
+}
+
+/// Starts `forward` through `host` in the background. Returns the unit name.
+///
+/// # Errors
+///
+/// Will return `Err` if `systemd-run` cannot be run or fails, e.g. when the unit already runs.
+pub fn start(host: &ssh::Host, forward: &Forward) -> Result<String> {
+    let command = start_command(host, forward);
+    let output = exec::output(Command::new(&command[0]).args(&command[1..]))
+        .context("Failed to run systemd-run")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to start the background tunnel: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(unit_name(&host.name, forward))
+}
+
+/// A unit started by sshs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unit {
+    /// e.g. `sshs-socks-web-1080.service`
+    pub name: String,
+    /// e.g. `active`, `failed`
+    pub state: String,
+    pub description: String,
+}
+
+/// Reads the units of `systemctl list-units --plain --no-legend`: name, load state, active
+/// state, sub state and description, separated by spaces.
+#[must_use]
+pub fn parse_units(output: &str) -> Vec<Unit> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?;
+            let state = fields.nth(1)?;
+            let description = fields.skip(1).collect::<Vec<_>>().join(" ");
+
+            name.starts_with(UNIT_PREFIX).then(|| Unit {
+                name: name.to_string(),
+                state: state.to_string(),
+                description,
+            })
+        })
+        .collect()
+}
+
+/// The units started by sshs, failed ones included.
+///
+/// # Errors
+///
+/// Will return `Err` if `systemctl` cannot be run or fails.
+pub fn list() -> Result<Vec<Unit>> {
+    let output = exec::output(Command::new("systemctl").args([
+        "--user",
+        "list-units",
+        "--all",
+        "--plain",
+        "--no-legend",
+        "--full",
+        &format!("{UNIT_PREFIX}*"),
+    ]))
+    .context("Failed to run systemctl")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to list the background tunnels: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(parse_units(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Local port of the SOCKS proxy run by `unit`, e.g. 1080 for `sshs-socks-web-1080.service`.
+#[must_use]
+pub fn socks_port(unit: &str) -> Option<u16> {
+    let name = unit.strip_prefix(UNIT_PREFIX)?.strip_prefix("socks-")?;
+    let name = name.strip_suffix(".service").unwrap_or(name);
+    name.rsplit_once('-')?.1.parse().ok()
+}
+
+/// The units of `units` running a SOCKS proxy, by the name they were started with.
+#[must_use]
+pub fn socks_units(units: &[Unit]) -> BTreeSet<String> {
+    units
+        .iter()
+        .filter(|unit| unit.state == "active" && socks_port(&unit.name).is_some())
+        .map(|unit| unit.name.trim_end_matches(".service").to_string())
+        .collect()
+}
+
+/// Stops `unit`, and forgets it when it failed.
+///
+/// # Errors
+///
+/// Will return `Err` if `systemctl` cannot be run or fails.
+pub fn stop(unit: &str) -> Result<()> {
+    let output = exec::output(Command::new("systemctl").args(["--user", "stop", unit]))
+        .context("Failed to run systemctl")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to stop {unit}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    // Failed units stay listed until reset
+    let _ = exec::output(Command::new("systemctl").args(["--user", "reset-failed", unit]));
+
+    Ok(())
+}
+
+/// List of the background tunnels.
+#[derive(Debug, Default)]
+pub struct TunnelsView {
+    pub units: Vec<Unit>,
+    pub selected: usize,
+}
+
+impl TunnelsView {
+    #[must_use]
+    pub fn new(units: Vec<Unit>) -> Self {
+        Self { units, selected: 0 }
+    }
+
+    #[must_use]
+    pub fn selected_unit(&self) -> Option<&Unit> {
+        self.units.get(self.selected)
+    }
+
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.units.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Replaces the units, keeping the selection within them.
+    pub fn set_units(&mut self, units: Vec<Unit>) {
+        self.selected = self.selected.min(units.len().saturating_sub(1));
+        self.units = units;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_command_and_units() {
+        let host = ssh::Host {
+            name: "web eu".to_string(),
+            destination: "web.example.com".to_string(),
+            ..Default::default()
+        };
+        let socks = Forward::Dynamic { port: 1080 };
+        assert_eq!(unit_name(&host.name, &socks), "sshs-socks-web_eu-1080");
+
+        let command = start_command(&host, &socks);
+        assert_eq!(
+            command[..5],
+            [
+                "systemd-run",
+                "--user",
+                "--collect",
+                "--unit=sshs-socks-web_eu-1080",
+                "--description=sshs: SOCKS proxy on localhost:1080 through web eu",
+            ]
+        );
+        assert_eq!(command[5..8], ["ssh", "-N", "-o"]);
+        assert!(command.windows(2).any(|args| args == ["-D", "1080"]));
+        assert_eq!(command.last().unwrap(), "web.example.com");
+
+        let units = parse_units(
+            "sshs-socks-web-1080.service loaded active running sshs: SOCKS proxy on localhost:1080 through web\n\
+             other.service loaded active running Other\n\
+             sshs-socks-db-1081.service loaded failed failed sshs: SOCKS proxy on localhost:1081 through db\n",
+        );
+        assert_eq!(
+            units[0],
+            Unit {
+                name: "sshs-socks-web-1080.service".to_string(),
+                state: "active".to_string(),
+                description: "sshs: SOCKS proxy on localhost:1080 through web".to_string(),
+            }
+        );
+        assert_eq!(units.len(), 2);
+        assert_eq!(units[1].state, "failed");
+        // Only the running one still proxies
+        assert_eq!(
+            socks_units(&units).into_iter().collect::<Vec<_>>(),
+            ["sshs-socks-web-1080"]
+        );
+        assert_eq!(socks_port("sshs-socks-web-eu-1080"), Some(1080));
+        assert_eq!(socks_port("sshs-local-web-8080.service"), None);
+
+        let mut view = TunnelsView::new(units);
+        view.next();
+        view.next();
+        assert_eq!(view.selected, 1);
+        view.set_units(Vec::new());
+        assert_eq!(view.selected_unit(), None);
+    }
+
+    #[test]
+    fn test_parse_forwards() {
+        let local = Forward::parse("-L 8080:db.internal:5432").unwrap();
+        assert_eq!(
+            local,
+            Forward::Local {
+                port: 8080,
+                address: "db.internal".to_string(),
+                target_port: 5432,
+            }
+        );
+        assert_eq!(local.to_string(), "L 8080:db.internal:5432");
+        assert_eq!(
+            local.describe("web"),
+            "localhost:8080 to db.internal:5432 through web"
+        );
+
+        let remote = Forward::parse("r 9000:[fd00::5]:22").unwrap();
+        assert_eq!(remote.to_string(), "R 9000:[fd00::5]:22");
+        assert_eq!(remote.ssh_args(), ["-R", "9000:[fd00::5]:22"]);
+        assert_eq!(
+            Forward::parse("D 1080").unwrap(),
+            Forward::Dynamic { port: 1080 }
+        );
+
+        assert!(Forward::parse("X 1080").is_err());
+        assert!(Forward::parse("L 8080").is_err());
+        assert!(Forward::parse("L 8080:db:0").is_err());
+        assert!(Forward::parse("L 8080:db;rm:22").is_err());
+
+        let host = ssh::Host {
+            name: "web".to_string(),
+            destination: "web.example.com".to_string(),
+            ..Default::default()
+        };
+        let command = start_command(&host, &local);
+        assert_eq!(command[3], "--unit=sshs-local-web-8080");
+        assert!(command
+            .windows(2)
+            .any(|args| args == ["-L", "8080:db.internal:5432"]));
+    }
+}
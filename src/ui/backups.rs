@@ -0,0 +1,566 @@
+//! Backups (`<file>.bak`) written next to the configuration files before sshs edits them.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::ssh;
+use crate::ssh_config::diff::{self, HostChange, HostDiff};
+
+/// A backup file and the configuration file it was copied from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub original: PathBuf,
+    pub modified: Option<SystemTime>,
+}
+
+/// One line of a backup-to-current diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Unchanged(String),
+    /// Only in the current file
+    Added(String),
+    /// Only in the backup
+    Removed(String),
+}
+
+impl fmt::Display for DiffLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffLine::Unchanged(text) => write!(f, "  {text}"),
+            DiffLine::Added(text) => write!(f, "+ {text}"),
+            DiffLine::Removed(text) => write!(f, "- {text}"),
+        }
+    }
+}
+
+/// Lists the existing backups of `config_files`, most recent first.
+pub fn find(config_files: impl IntoIterator<Item = PathBuf>) -> Vec<Backup> {
+    let mut backups: Vec<Backup> = Vec::new();
+
+    for original in config_files {
+        let path = backup_path(&original);
+        if backups.iter().any(|backup| backup.path == path) {
+            continue;
+        }
+
+        if let Ok(metadata) = fs::metadata(&path) {
+            backups.push(Backup {
+                path,
+                original,
+                modified: metadata.modified().ok(),
+            });
+        }
+    }
+
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.modified));
+    backups
+}
+
+/// Path of the backup sshs writes for `config_file`.
+#[must_use]
+pub fn backup_path(config_file: &Path) -> PathBuf {
+    let mut path = config_file.as_os_str().to_owned();
+    path.push(".bak");
+    PathBuf::from(path)
+}
+
+impl Backup {
+    /// Line diff from the backup to the current configuration file.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the backup cannot be read. A missing configuration file is
+    /// considered empty.
+    pub fn diff(&self) -> io::Result<Vec<DiffLine>> {
+        let backup = fs::read_to_string(&self.path)?;
+        let current = match fs::read_to_string(&self.original) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            result => result?,
+        };
+
+        Ok(diff_lines(&backup, &current))
+    }
+
+    /// Host by host diff from the backup to the current configuration file, see
+    /// [`diff::diff`]. A missing configuration file is considered empty.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the backup cannot be read.
+    pub fn host_diff(&self) -> anyhow::Result<Vec<DiffLine>> {
+        let backup = diff::parse_hosts(&self.path)?;
+        let current = if self.original.exists() {
+            diff::parse_hosts(&self.original)?
+        } else {
+            Vec::new()
+        };
+
+        Ok(host_diff_lines(&diff::diff(&backup, &current)))
+    }
+
+    /// Restores the backup. The backup and the configuration file are swapped, so restoring
+    /// again undoes the restore.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if either file cannot be read or written.
+    pub fn restore(&self) -> io::Result<()> {
+        let backup = fs::read(&self.path)?;
+        let current = fs::read(&self.original)?;
+
+        fs::write(&self.original, backup)?;
+        fs::write(&self.path, current)
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the backup cannot be removed.
+    pub fn delete(&self) -> io::Result<()> {
+        fs::remove_file(&self.path)
+    }
+
+    /// Age of the backup relative to `now`, e.g. `5m ago`.
+    #[must_use]
+    pub fn age(&self, now: SystemTime) -> String {
+        let Some(elapsed) = self
+            .modified
+            .and_then(|modified| now.duration_since(modified).ok())
+        else {
+            return "unknown".to_string();
+        };
+
+        format_age(elapsed)
+    }
+}
+
+/// What a write to a configuration file should have done to a host, checked by re-parsing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected<'a> {
+    /// Declared in the file, e.g. after it was added, edited or tagged
+    Present(&'a str),
+    /// No longer declared in the file, e.g. after it was deleted or commented out
+    Absent(&'a str),
+}
+
+/// Names of the hosts declared in `config_file` itself (not in the files it includes), with
+/// their aliases since blocks with the same settings are merged, and the number of lines that
+/// cannot be parsed.
+fn parse(config_file: &Path) -> Result<(Vec<String>, usize), String> {
+    let (hosts, diagnostics) = ssh::parse_config_lenient(&config_file.display().to_string())
+        .map_err(|e| format!("{} can no longer be parsed: {e}", config_file.display()))?;
+    let path = fs::canonicalize(config_file).ok();
+
+    let names = hosts
+        .into_iter()
+        .filter(|host| host.source_file.is_some() && host.source_file == path)
+        .flat_map(|host| {
+            let aliases = host
+                .aliases
+                .split(", ")
+                .filter(|alias| !alias.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            std::iter::once(host.name).chain(aliases)
+        })
+        .collect();
+
+    Ok((names, diagnostics.len()))
+}
+
+/// Re-parses `config_file` after sshs wrote it, and tells what is wrong: lines that no longer
+/// parse (more than `previous_problems`, when known) or a host not in the `expected` state.
+///
+/// # Errors
+///
+/// Will return `Err` describing the first problem found.
+pub fn check_write(
+    config_file: &Path,
+    previous_problems: Option<usize>,
+    expected: &[Expected],
+) -> Result<(), String> {
+    let (names, problems) = parse(config_file)?;
+    if let Some(previous) = previous_problems.filter(|previous| problems > *previous) {
+        return Err(format!(
+            "{} has {} unparsable line(s) after the write, {previous} before",
+            config_file.display(),
+            problems
+        ));
+    }
+
+    for expected in expected {
+        match *expected {
+            Expected::Present(name) if !names.iter().any(|n| n == name) => {
+                return Err(format!(
+                    "'{name}' is missing from {} after the write",
+                    config_file.display()
+                ));
+            }
+            Expected::Absent(name) if names.iter().any(|n| n == name) => {
+                return Err(format!(
+                    "'{name}' is still in {} after the write",
+                    config_file.display()
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks a write to `config_file` made after its backup was written. When it is not as
+/// `expected`, the backup is restored, and the failed write kept in its place for inspection.
+///
+/// # Errors
+///
+/// Will return `Err` describing the problem and the restore when the write is not as expected.
+pub fn verify_write(config_file: &Path, expected: &[Expected]) -> anyhow::Result<()> {
+    let path = backup_path(config_file);
+    let previous_problems = parse(&path).ok().map(|(_, problems)| problems);
+    let Err(problem) = check_write(config_file, previous_problems, expected) else {
+        return Ok(());
+    };
+
+    let backup = Backup {
+        path,
+        original: config_file.to_path_buf(),
+        modified: None,
+    };
+    match backup.restore() {
+        Ok(()) => anyhow::bail!(
+            "{problem}. The previous version was restored, the failed write is in {}",
+            backup.path.display()
+        ),
+        Err(e) => anyhow::bail!(
+            "{problem}, and restoring {} failed: {e}",
+            backup.path.display()
+        ),
+    }
+}
+
+/// Replaces the content of `config_file` atomically: it is written to `<file>.tmp`, renamed over
+/// the file once complete, so that an interrupted write leaves the previous version. A symbolic
+/// link is followed, the file it points to is replaced rather than the link, and the
+/// permissions of the file are kept.
+///
+/// # Errors
+///
+/// Will return `Err` if the temporary file cannot be written or renamed.
+pub fn write_config(config_file: &Path, content: &str) -> io::Result<()> {
+    let path = fs::canonicalize(config_file).unwrap_or_else(|_| config_file.to_path_buf());
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+
+    let result = fs::write(&temporary, content)
+        .and_then(|()| match fs::metadata(&path) {
+            Ok(metadata) => fs::set_permissions(&temporary, metadata.permissions()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        })
+        .and_then(|()| fs::rename(&temporary, &path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+
+    result
+}
+
+pub(crate) fn format_age(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86_399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86_400),
+    }
+}
+
+/// Longest common subsequence diff, fine for configuration-sized files.
+pub(crate) fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // common[i][j] is the LCS length of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Unchanged(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    lines.extend(
+        old[i..]
+            .iter()
+            .map(|line| DiffLine::Removed((*line).to_string())),
+    );
+    lines.extend(
+        new[j..]
+            .iter()
+            .map(|line| DiffLine::Added((*line).to_string())),
+    );
+
+    lines
+}
+
+/// Lines of a host by host diff: a `Host` line per host, followed by its settings.
+#[must_use]
+pub fn host_diff_lines(hosts: &[HostDiff]) -> Vec<DiffLine> {
+    let mut lines = Vec::new();
+
+    for host in hosts {
+        let header = format!("Host {}", host.host);
+        lines.push(match host.change {
+            HostChange::Added => DiffLine::Added(header),
+            HostChange::Removed => DiffLine::Removed(header),
+            HostChange::Changed => DiffLine::Unchanged(header),
+        });
+
+        for change in &host.settings {
+            if let Some(old) = &change.old {
+                lines.push(DiffLine::Removed(format!("  {}", change.setting.line(old))));
+            }
+            if let Some(new) = &change.new {
+                lines.push(DiffLine::Added(format!("  {}", change.setting.line(new))));
+            }
+        }
+    }
+
+    lines
+}
+
+/// State of the backups view.
+#[derive(Debug, Default)]
+pub struct BackupsView {
+    pub backups: Vec<Backup>,
+    pub selected: usize,
+    /// Diff of the selected backup, when shown
+    pub diff: Option<Vec<DiffLine>>,
+    /// The diff is host by host rather than line by line
+    pub by_host: bool,
+    pub scroll: u16,
+    /// Waiting for the user to confirm deleting the selected backup
+    pub confirm_delete: bool,
+}
+
+impl BackupsView {
+    #[must_use]
+    pub fn new(backups: Vec<Backup>) -> Self {
+        Self {
+            backups,
+            ..Default::default()
+        }
+    }
+
+    #[must_use]
+    pub fn selected_backup(&self) -> Option<&Backup> {
+        self.backups.get(self.selected)
+    }
+
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.backups.len() {
+            self.selected += 1;
+            self.diff = None;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if self.selected > 0 {
+            self.selected -= 1;
+            self.diff = None;
+        }
+    }
+
+    /// Shows the diff of the selected backup, host by host or line by line.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the backup cannot be read.
+    pub fn load_diff(&mut self) -> anyhow::Result<()> {
+        let Some(backup) = self.selected_backup() else {
+            return Ok(());
+        };
+        let diff = if self.by_host {
+            backup.host_diff()?
+        } else {
+            backup.diff()?
+        };
+
+        self.diff = Some(diff);
+        self.scroll = 0;
+        Ok(())
+    }
+
+    /// Removes the selected backup from the list, after it was deleted.
+    pub fn remove_selected(&mut self) {
+        if self.selected < self.backups.len() {
+            self.backups.remove(self.selected);
+        }
+        self.selected = self.selected.min(self.backups.len().saturating_sub(1));
+        self.diff = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_diff_lines() {
+        let diff = diff_lines("Host a\nHost b\nHost c\n", "Host a\nHost c\nHost d\n");
+
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Unchanged("Host a".to_string()),
+                DiffLine::Removed("Host b".to_string()),
+                DiffLine::Unchanged("Host c".to_string()),
+                DiffLine::Added("Host d".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_restore_and_delete() {
+        let dir = TempDir::new().unwrap();
+        let config = dir.path().join("config");
+        let without_backup = dir.path().join("other");
+        fs::write(&config, "Host new\n").unwrap();
+        fs::write(backup_path(&config), "Host old\n").unwrap();
+
+        let backups = find([config.clone(), without_backup, config.clone()]);
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].original, config);
+
+        // Restoring swaps the files so it can be undone
+        backups[0].restore().unwrap();
+        assert_eq!(fs::read_to_string(&config).unwrap(), "Host old\n");
+        assert_eq!(
+            fs::read_to_string(backup_path(&config)).unwrap(),
+            "Host new\n"
+        );
+
+        backups[0].delete().unwrap();
+        assert!(find([config]).is_empty());
+    }
+
+    #[test]
+    fn test_write_config() {
+        let dir = TempDir::new().unwrap();
+        let config = dir.path().join("config");
+
+        write_config(&config, "Host web\n").unwrap();
+        assert_eq!(fs::read_to_string(&config).unwrap(), "Host web\n");
+        assert!(!dir.path().join("config.tmp").exists());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::set_permissions(&config, fs::Permissions::from_mode(0o600)).unwrap();
+            let link = dir.path().join("link");
+            std::os::unix::fs::symlink(&config, &link).unwrap();
+
+            write_config(&link, "Host db\n").unwrap();
+            assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+            assert_eq!(fs::read_to_string(&config).unwrap(), "Host db\n");
+            let mode = fs::metadata(&config).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // A failed write leaves the file as it was
+        assert!(write_config(&dir.path().join("missing").join("config"), "Host web\n").is_err());
+        assert!(!dir.path().join("missing").exists());
+    }
+
+    #[test]
+    fn test_verify_write_restores_the_backup() {
+        let dir = TempDir::new().unwrap();
+        let config = dir.path().join("config");
+        fs::write(
+            backup_path(&config),
+            "Host web\n  Hostname web.example.com\n",
+        )
+        .unwrap();
+        fs::write(
+            &config,
+            "Host web\n  Hostname web.example.com\n\nHost db\n  Hostname db.example.com\n",
+        )
+        .unwrap();
+
+        verify_write(
+            &config,
+            &[Expected::Present("db"), Expected::Present("web")],
+        )
+        .unwrap();
+
+        // A write that lost a host
+        fs::write(&config, "Host db\n  Hostname db.example.com\n").unwrap();
+        fs::write(
+            backup_path(&config),
+            "Host web\n  Hostname web.example.com\n",
+        )
+        .unwrap();
+        let error = verify_write(
+            &config,
+            &[Expected::Present("db"), Expected::Present("web")],
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.starts_with("'web' is missing from"), "{error}");
+        assert_eq!(
+            fs::read_to_string(&config).unwrap(),
+            "Host web\n  Hostname web.example.com\n"
+        );
+        assert_eq!(
+            fs::read_to_string(backup_path(&config)).unwrap(),
+            "Host db\n  Hostname db.example.com\n"
+        );
+
+        // A write that broke a line
+        fs::write(
+            backup_path(&config),
+            "Host web\n  Hostname web.example.com\n",
+        )
+        .unwrap();
+        fs::write(&config, "Host web\n  Hostname web.example.com\n  Port\n").unwrap();
+        assert!(check_write(&config, None, &[Expected::Absent("db")]).is_ok());
+        let error = verify_write(&config, &[]).unwrap_err().to_string();
+        assert!(
+            error.contains("1 unparsable line(s) after the write, 0 before"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(Duration::from_secs(5)), "just now");
+        assert_eq!(format_age(Duration::from_secs(150)), "2m ago");
+        assert_eq!(format_age(Duration::from_secs(7300)), "2h ago");
+        assert_eq!(format_age(Duration::from_secs(3 * 86_400 + 5)), "3d ago");
+    }
+}
@@ -0,0 +1,251 @@
+//! A command run on several hosts, one after the other or in parallel, with the exit code and
+//! output of each host. The hosts are the ones marked in the list (Space, or `v` for a range).
+
+use std::fmt::Write;
+use std::process::Stdio;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use crate::exec;
+use crate::ssh;
+
+/// How the command goes through the hosts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// One host after the other, in the order of the list
+    Sequential,
+    /// Every host at once
+    Parallel,
+}
+
+impl Mode {
+    #[must_use]
+    pub fn toggle(self) -> Mode {
+        match self {
+            Mode::Sequential => Mode::Parallel,
+            Mode::Parallel => Mode::Sequential,
+        }
+    }
+
+    #[must_use]
+    pub fn describe(self) -> &'static str {
+        match self {
+            Mode::Sequential => "one by one",
+            Mode::Parallel => "in parallel",
+        }
+    }
+}
+
+/// Where the command is on a host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// Waiting for the hosts before it
+    Pending,
+    Running,
+    /// Exit code of the command, `None` when it was killed by a signal. `ssh` exits with 255
+    /// when it cannot reach the host.
+    Exited(Option<i32>),
+    /// The command could not be rendered for the host, or `ssh` not started
+    Failed(String),
+}
+
+/// The command on one host
+#[derive(Debug, Clone)]
+pub struct HostRun {
+    pub host: String,
+    pub status: Status,
+    /// Standard output, followed by the standard error
+    pub output: String,
+}
+
+impl HostRun {
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        !matches!(self.status, Status::Pending | Status::Running)
+    }
+
+    #[must_use]
+    pub fn succeeded(&self) -> bool {
+        self.status == Status::Exited(Some(0))
+    }
+
+    /// e.g. `exit 0`, `signal`, `running`.
+    #[must_use]
+    pub fn status_label(&self) -> String {
+        match &self.status {
+            Status::Pending => "pending".to_string(),
+            Status::Running => "running".to_string(),
+            Status::Exited(Some(code)) => format!("exit {code}"),
+            Status::Exited(None) => "signal".to_string(),
+            Status::Failed(_) => "error".to_string(),
+        }
+    }
+
+    /// Last line of the output, or why the command did not run.
+    #[must_use]
+    pub fn summary(&self) -> &str {
+        match &self.status {
+            Status::Failed(error) => error,
+            _ => self
+                .output
+                .lines()
+                .rev()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+enum Event {
+    Started(usize),
+    Finished(usize, Status, String),
+}
+
+/// A command running on several hosts in the background, shown in the results pane
+pub struct BatchRun {
+    pub command: String,
+    pub mode: Mode,
+    pub hosts: Vec<HostRun>,
+    /// Host whose output is shown under the results
+    pub selected: usize,
+    receiver: Receiver<Event>,
+}
+
+impl BatchRun {
+    /// Starts `command`, a template of the remote command (`{{name}}`...), on `hosts`.
+    #[must_use]
+    pub fn start(hosts: &[ssh::Host], command: &str, mode: Mode) -> BatchRun {
+        let mut runs = Vec::new();
+        let mut jobs = Vec::new();
+        for (index, host) in hosts.iter().enumerate() {
+            let status = match host.render_remote_command(command) {
+                Ok(script) => {
+                    jobs.push((index, host.build_batch_command(&script)));
+                    Status::Pending
+                }
+                Err(e) => Status::Failed(format!("{e:#}")),
+            };
+            runs.push(HostRun {
+                host: host.name.clone(),
+                status,
+                output: String::new(),
+            });
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        match mode {
+            Mode::Sequential => {
+                thread::spawn(move || {
+                    for (index, args) in jobs {
+                        run(&sender, index, &args);
+                    }
+                });
+            }
+            Mode::Parallel => {
+                for (index, args) in jobs {
+                    let sender = sender.clone();
+                    thread::spawn(move || run(&sender, index, &args));
+                }
+            }
+        }
+
+        BatchRun {
+            command: command.to_string(),
+            mode,
+            hosts: runs,
+            selected: 0,
+            receiver,
+        }
+    }
+
+    /// Applies the progress made in the background. Returns whether the run just finished.
+    pub fn poll(&mut self) -> bool {
+        let was_running = self.is_running();
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                Event::Started(index) => self.hosts[index].status = Status::Running,
+                Event::Finished(index, status, output) => {
+                    self.hosts[index].status = status;
+                    self.hosts[index].output = output;
+                }
+            }
+        }
+
+        was_running && !self.is_running()
+    }
+
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        !self.hosts.iter().all(HostRun::is_done)
+    }
+
+    /// e.g. `3/5 hosts done, 1 failed`.
+    #[must_use]
+    pub fn status(&self) -> String {
+        let done = self.hosts.iter().filter(|run| run.is_done()).count();
+        let failed = self
+            .hosts
+            .iter()
+            .filter(|run| run.is_done() && !run.succeeded())
+            .count();
+
+        let mut status = format!("{done}/{} hosts done", self.hosts.len());
+        if failed > 0 {
+            let _ = write!(status, ", {failed} failed");
+        }
+        status
+    }
+
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.hosts.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+/// Runs the `ssh` command `args` on the host `index`, reporting to `sender`.
+fn run(sender: &Sender<Event>, index: usize, args: &[String]) {
+    let _ = sender.send(Event::Started(index));
+
+    let (status, output) = match exec::output(ssh::command(args).stdin(Stdio::null())) {
+        Ok(output) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            (Status::Exited(output.status.code()), text)
+        }
+        Err(e) => (
+            Status::Failed(format!("Failed to run ssh: {e}")),
+            String::new(),
+        ),
+    };
+    let _ = sender.send(Event::Finished(index, status, output));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summaries() {
+        let run = HostRun {
+            host: "web".to_string(),
+            status: Status::Exited(Some(1)),
+            output: "checking\ndisk full\n\n".to_string(),
+        };
+        assert_eq!(run.summary(), "disk full");
+        assert_eq!(run.status_label(), "exit 1");
+        assert!(!run.succeeded());
+
+        let run = HostRun {
+            status: Status::Failed("Unknown placeholder".to_string()),
+            ..run
+        };
+        assert_eq!(run.summary(), "Unknown placeholder");
+        assert_eq!(Mode::Sequential.toggle(), Mode::Parallel);
+    }
+}
@@ -0,0 +1,222 @@
+//! Tags added to or removed from all the listed hosts at once (`T`), with one write and one
+//! backup per configuration file.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs;
+use std::path::Path;
+
+use super::backups;
+use super::form::{declares_host, starts_block};
+use crate::ssh;
+use crate::ssh_config::host::TAGS_METADATA_KEY;
+use crate::ssh_config::parser::parse_metadata_comment;
+
+/// Tags to add and to remove, typed as `+prod -staging` (a tag without a sign is added).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagEdit {
+    pub add: Vec<String>,
+    pub remove: Vec<String>,
+}
+
+impl TagEdit {
+    /// # Errors
+    ///
+    /// Will return `Err` if there is no tag, or a tag has a character metadata cannot hold.
+    pub fn parse(input: &str) -> Result<TagEdit> {
+        let mut edit = TagEdit::default();
+
+        for word in input
+            .split([' ', '\t', ','])
+            .filter(|word| !word.is_empty())
+        {
+            let (tags, tag) = match word.strip_prefix('-') {
+                Some(tag) => (&mut edit.remove, tag),
+                None => (&mut edit.add, word.strip_prefix('+').unwrap_or(word)),
+            };
+            if tag.is_empty() || tag.contains(['#', '=', '"']) {
+                bail!("Invalid tag '{word}'");
+            }
+            if !tags.iter().any(|existing| existing == tag) {
+                tags.push(tag.to_string());
+            }
+        }
+
+        if edit.add.is_empty() && edit.remove.is_empty() {
+            bail!("Type the tags to add (+tag) or remove (-tag)");
+        }
+        Ok(edit)
+    }
+
+    /// Own tags of a host once edited. Tags it already has (inherited) are not added again.
+    fn apply(&self, own: &[String], effective: &[String]) -> Vec<String> {
+        let mut tags = own
+            .iter()
+            .filter(|tag| !self.remove.contains(tag))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for tag in &self.add {
+            if !tags.contains(tag) && !effective.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+
+        tags
+    }
+}
+
+/// Rewrites the tags line of the blocks of `hosts` in `content`. Returns the new content and
+/// the names of the hosts whose tags changed. Tags inherited from the file or from a wildcard
+/// block are not removed.
+#[must_use]
+pub fn rewrite_tags(content: &str, hosts: &[&ssh::Host], edit: &TagEdit) -> (String, Vec<String>) {
+    let lines = content.lines().collect::<Vec<_>>();
+    let mut result = Vec::with_capacity(lines.len() + hosts.len());
+    let mut changed = Vec::new();
+
+    let mut i = 0;
+    while i < lines.len() {
+        result.push(lines[i].to_string());
+        let host = hosts
+            .iter()
+            .find(|host| !changed.contains(&host.name) && declares_host(lines[i], &host.name));
+        i += 1;
+        let Some(host) = host else {
+            continue;
+        };
+
+        let block_end = lines[i..]
+            .iter()
+            .position(|line| starts_block(line))
+            .map_or(lines.len(), |offset| i + offset);
+        let tags_line = (i..block_end).find(|line| tags_of_line(lines[*line]).is_some());
+        let own = tags_line
+            .and_then(|line| tags_of_line(lines[line]))
+            .unwrap_or_default();
+
+        let tags = edit.apply(&own, &host.tags);
+        if tags == own {
+            continue;
+        }
+        changed.push(host.name.clone());
+
+        let new_line = (!tags.is_empty()).then(|| format!("# sshs:tags={}", tags.join(",")));
+        match tags_line {
+            Some(line) => {
+                result.extend(lines[i..line].iter().map(ToString::to_string));
+                if let Some(new_line) = new_line {
+                    let indent = &lines[line][..lines[line].len() - lines[line].trim_start().len()];
+                    result.push(format!("{indent}{new_line}"));
+                }
+                i = line + 1;
+            }
+            None => result.extend(new_line.map(|new_line| format!("  {new_line}"))),
+        }
+    }
+
+    let mut content = result.join("\n");
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    (content, changed)
+}
+
+/// Edits the tags of `hosts`, all declared in `config_path`, after backing the file up.
+/// Returns the names of the hosts whose tags changed.
+///
+/// # Errors
+///
+/// Will return `Err` if the file cannot be read, backed up or written, or if the write is not
+/// as expected (the backup is then restored).
+pub fn write_tags(config_path: &str, hosts: &[&ssh::Host], edit: &TagEdit) -> Result<Vec<String>> {
+    let content = fs::read_to_string(config_path)
+        .with_context(|| format!("Failed to read SSH config file {config_path}"))?;
+    let (updated, changed) = rewrite_tags(&content, hosts, edit);
+    if changed.is_empty() {
+        return Ok(changed);
+    }
+
+    fs::copy(config_path, backups::backup_path(Path::new(config_path)))
+        .map_err(|e| anyhow!("Failed to create backup of SSH config file: {e}"))?;
+    fs::write(config_path, updated)
+        .map_err(|e| anyhow!("Failed to write updated SSH config file: {e}"))?;
+
+    let expected = changed
+        .iter()
+        .map(|name| backups::Expected::Present(name))
+        .collect::<Vec<_>>();
+    backups::verify_write(Path::new(config_path), &expected)?;
+
+    Ok(changed)
+}
+
+/// Tags of a `# sshs:tags=...` (or `#_tags ...`) line, `None` for other lines.
+pub(crate) fn tags_of_line(line: &str) -> Option<Vec<String>> {
+    let (key, value) = parse_metadata_comment(line)?;
+    if key != TAGS_METADATA_KEY {
+        return None;
+    }
+
+    Some(
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(name: &str, tags: &[&str]) -> ssh::Host {
+        ssh::Host {
+            name: name.to_string(),
+            tags: tags.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_tag_edit() {
+        let edit = TagEdit::parse("+prod, eu -staging prod").unwrap();
+        assert_eq!(edit.add, ["prod", "eu"]);
+        assert_eq!(edit.remove, ["staging"]);
+
+        assert!(TagEdit::parse("  ").is_err());
+        assert!(TagEdit::parse("+ok -").is_err());
+        assert!(TagEdit::parse("a=b").is_err());
+    }
+
+    #[test]
+    fn test_rewrite_tags() {
+        let content = "# sshs:tags=team\n\n\
+                       Host web web-alias\n  Hostname web.example.com\n  # sshs:tags=staging,web\n\n\
+                       Host db\n    Hostname db.example.com\n\n\
+                       Host cache\n  #_tags staging\n\n\
+                       Host other\n  # sshs:tags=staging\n";
+        let web = host("web", &["staging", "web", "team"]);
+        let db = host("db", &["team"]);
+        let cache = host("cache", &["staging", "team"]);
+        let edit = TagEdit::parse("+prod +team -staging").unwrap();
+
+        let (updated, changed) = rewrite_tags(content, &[&web, &db, &cache], &edit);
+        assert_eq!(changed, ["web", "db", "cache"]);
+        assert_eq!(
+            updated,
+            "# sshs:tags=team\n\n\
+             Host web web-alias\n  Hostname web.example.com\n  # sshs:tags=web,prod\n\n\
+             Host db\n  # sshs:tags=prod\n    Hostname db.example.com\n\n\
+             Host cache\n  # sshs:tags=prod\n\n\
+             Host other\n  # sshs:tags=staging\n"
+        );
+
+        // Removing the last tag drops the line, inherited tags stay
+        let edit = TagEdit::parse("-web -team").unwrap();
+        let (updated, changed) = rewrite_tags("Host web\n  # sshs:tags=web\n", &[&web, &db], &edit);
+        assert_eq!(changed, ["web"]);
+        assert_eq!(updated, "Host web\n");
+    }
+}
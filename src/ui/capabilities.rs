@@ -0,0 +1,325 @@
+//! What the terminal can display and report, detected at startup from the environment, and the
+//! fallbacks drawn when it cannot: 16 colors instead of truecolor, no colors at all, ASCII
+//! instead of box drawing and symbols, no mouse capture.
+
+use clap::ValueEnum;
+use ratatui::buffer::Buffer;
+use ratatui::style::{Color, Modifier};
+
+/// Terminals without usable mouse reporting, which print the escape sequences instead
+const NO_MOUSE_TERMS: [&str; 4] = ["dumb", "linux", "vt100", "vt220"];
+
+/// The 16 ANSI colors, with their usual (xterm) values
+const ANSI_COLORS: [(Color, [u8; 3]); 16] = [
+    (Color::Black, [0, 0, 0]),
+    (Color::Red, [205, 0, 0]),
+    (Color::Green, [0, 205, 0]),
+    (Color::Yellow, [205, 205, 0]),
+    (Color::Blue, [0, 0, 238]),
+    (Color::Magenta, [205, 0, 205]),
+    (Color::Cyan, [0, 205, 205]),
+    (Color::Gray, [229, 229, 229]),
+    (Color::DarkGray, [127, 127, 127]),
+    (Color::LightRed, [255, 0, 0]),
+    (Color::LightGreen, [0, 255, 0]),
+    (Color::LightYellow, [255, 255, 0]),
+    (Color::LightBlue, [92, 92, 255]),
+    (Color::LightMagenta, [255, 0, 255]),
+    (Color::LightCyan, [0, 255, 255]),
+    (Color::White, [255, 255, 255]),
+];
+
+/// A capability forced on or off, or detected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Override {
+    #[default]
+    Auto,
+    On,
+    Off,
+}
+
+impl Override {
+    fn or_detected(self, detected: impl FnOnce() -> bool) -> bool {
+        match self {
+            Override::Auto => detected(),
+            Override::On => true,
+            Override::Off => false,
+        }
+    }
+}
+
+/// Whether to draw colors, as `--color` of the usual command line tools
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// Unless `NO_COLOR` is set or `TERM` is `dumb`, `CLICOLOR_FORCE` overriding both
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Colors, drawn in the default colors of the terminal otherwise
+    pub color: bool,
+    /// 24-bit colors, mapped to the 16 ANSI colors otherwise
+    pub truecolor: bool,
+    /// Box drawing characters and symbols, replaced with ASCII otherwise
+    pub unicode: bool,
+    /// Mouse capture, left off otherwise
+    pub mouse: bool,
+}
+
+impl Default for Capabilities {
+    /// Everything supported, as in modern terminal emulators.
+    fn default() -> Self {
+        Self {
+            color: true,
+            truecolor: true,
+            unicode: true,
+            mouse: true,
+        }
+    }
+}
+
+impl Capabilities {
+    /// Detects the capabilities of the terminal sshs runs in, unless overridden.
+    #[must_use]
+    pub fn detect(
+        color: ColorMode,
+        truecolor: Override,
+        unicode: Override,
+        mouse: Override,
+    ) -> Self {
+        let detected = Self::from_env(|name| std::env::var(name).ok());
+
+        Self {
+            color: match color {
+                ColorMode::Auto => detected.color,
+                ColorMode::Always => true,
+                ColorMode::Never => false,
+            },
+            truecolor: truecolor.or_detected(|| detected.truecolor),
+            unicode: unicode.or_detected(|| detected.unicode),
+            mouse: mouse.or_detected(|| detected.mouse),
+        }
+    }
+
+    /// Capabilities told by the environment: `NO_COLOR` and `CLICOLOR_FORCE` for colors
+    /// (<https://no-color.org>, <https://bixense.com/clicolors>), `COLORTERM` for truecolor, the
+    /// locale for unicode and `TERM` for the mouse.
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        let term = var("TERM").unwrap_or_default();
+
+        let is_set = |name| var(name).is_some_and(|value| !value.is_empty());
+        let color = if is_set("CLICOLOR_FORCE") {
+            var("CLICOLOR_FORCE").as_deref() != Some("0")
+        } else {
+            !is_set("NO_COLOR") && term != "dumb"
+        };
+
+        let truecolor = var("COLORTERM")
+            .is_some_and(|value| matches!(value.as_str(), "truecolor" | "24bit"))
+            || term.ends_with("-direct")
+            // Windows Terminal does not set COLORTERM
+            || var("WT_SESSION").is_some();
+
+        // The first locale variable set wins, as with setlocale(3)
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .into_iter()
+            .filter_map(&var)
+            .find(|value| !value.is_empty());
+        let unicode = term != "linux"
+            && locale.map_or(cfg!(windows), |locale| {
+                let locale = locale.to_lowercase();
+                locale.contains("utf-8") || locale.contains("utf8")
+            });
+
+        // Emacs terminals forward mouse events unreliably
+        let mouse = !NO_MOUSE_TERMS.contains(&term.as_str()) && var("INSIDE_EMACS").is_none();
+
+        Self {
+            color,
+            truecolor,
+            unicode,
+            mouse,
+        }
+    }
+
+    /// Replaces, in a drawn frame, what the terminal cannot display with its fallback.
+    pub fn degrade(self, buffer: &mut Buffer) {
+        if self.color && self.truecolor && self.unicode {
+            return;
+        }
+
+        for cell in &mut buffer.content {
+            if !self.color {
+                // Highlights drawn dark on light, like the selected entries, stay highlighted
+                if brightness(cell.bg, 0) > brightness(cell.fg, 255) {
+                    cell.modifier.toggle(Modifier::REVERSED);
+                }
+                cell.fg = Color::Reset;
+                cell.bg = Color::Reset;
+            } else if !self.truecolor {
+                cell.fg = ansi_color(cell.fg);
+                cell.bg = ansi_color(cell.bg);
+            }
+            if !self.unicode {
+                if let Some(symbol) = ascii_symbol(cell.symbol()) {
+                    cell.set_symbol(symbol);
+                }
+            }
+        }
+    }
+}
+
+/// Perceived brightness of `color` out of 255, `default` for the default color of the terminal
+/// and the indexed colors.
+fn brightness(color: Color, default: u8) -> u32 {
+    let rgb = match color {
+        Color::Rgb(r, g, b) => [r, g, b],
+        _ => ANSI_COLORS
+            .into_iter()
+            .find(|(ansi, _)| *ansi == color)
+            .map_or([default; 3], |(_, rgb)| rgb),
+    };
+
+    (299 * u32::from(rgb[0]) + 587 * u32::from(rgb[1]) + 114 * u32::from(rgb[2])) / 1000
+}
+
+/// Closest of the 16 ANSI colors to an RGB color, other colors unchanged.
+fn ansi_color(color: Color) -> Color {
+    let Color::Rgb(r, g, b) = color else {
+        return color;
+    };
+
+    let distance = |[ar, ag, ab]: [u8; 3]| {
+        [(r, ar), (g, ag), (b, ab)]
+            .into_iter()
+            .map(|(a, b)| (i32::from(a) - i32::from(b)).pow(2))
+            .sum::<i32>()
+    };
+    ANSI_COLORS
+        .into_iter()
+        .min_by_key(|(_, rgb)| distance(*rgb))
+        .map_or(color, |(ansi, _)| ansi)
+}
+
+/// ASCII stand-in of a non-ASCII symbol, `None` for ASCII ones.
+fn ascii_symbol(symbol: &str) -> Option<&'static str> {
+    if symbol.is_ascii() {
+        return None;
+    }
+
+    Some(match symbol.chars().next()? {
+        '─' | '━' | '═' => "-",
+        '│' | '┃' | '║' => "|",
+        '╭' | '╮' | '╰' | '╯' | '┌' | '┐' | '└' | '┘' | '├' | '┤' | '┬' | '┴' | '┼' | '╔' | '╗'
+        | '╚' | '╝' => "+",
+        '▶' | '►' | '→' => ">",
+        '◀' | '←' => "<",
+        '↑' => "^",
+        '↓' => "v",
+        '⚠' => "!",
+        '•' | '●' => "*",
+        '…' => ".",
+        '⏎' => "$",
+        '✓' | '✔' => "v",
+        '✗' | '✘' | '❌' => "x",
+        '█' | '▓' => "#",
+        _ => "?",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::layout::Rect;
+    use ratatui::style::palette::tailwind;
+    use std::collections::HashMap;
+
+    fn from_env(vars: &[(&str, &str)]) -> Capabilities {
+        let vars = vars.iter().copied().collect::<HashMap<_, _>>();
+        Capabilities::from_env(|name| vars.get(name).map(ToString::to_string))
+    }
+
+    #[test]
+    fn test_detect_from_env() {
+        let modern = from_env(&[
+            ("TERM", "xterm-256color"),
+            ("COLORTERM", "truecolor"),
+            ("LANG", "en_US.UTF-8"),
+        ]);
+        assert_eq!(modern, Capabilities::default());
+
+        // Linux console, C locale overriding LANG
+        let console = from_env(&[("TERM", "linux"), ("LC_ALL", "C"), ("LANG", "en_US.UTF-8")]);
+        assert!(!console.truecolor);
+        assert!(!console.unicode);
+        assert!(!console.mouse);
+
+        let legacy = from_env(&[("TERM", "xterm"), ("LC_CTYPE", "fr_FR.utf8")]);
+        assert!(!legacy.truecolor);
+        assert!(legacy.unicode);
+        assert!(legacy.mouse);
+
+        assert!(!from_env(&[("TERM", "dumb")]).color);
+        assert!(!from_env(&[("NO_COLOR", "1")]).color);
+        assert!(from_env(&[("NO_COLOR", "")]).color);
+        assert!(from_env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]).color);
+        assert!(!from_env(&[("CLICOLOR_FORCE", "0")]).color);
+
+        assert!(!Override::Off.or_detected(|| true));
+        assert!(Override::On.or_detected(|| false));
+    }
+
+    #[test]
+    fn test_degrade() {
+        let mut buffer = Buffer::with_lines(["╭─▶ web…"]);
+        buffer.set_style(
+            Rect::new(0, 0, 3, 1),
+            ratatui::style::Style::new().fg(tailwind::BLUE.c400),
+        );
+
+        let mut colors_only = buffer.clone();
+        Capabilities {
+            truecolor: false,
+            ..Default::default()
+        }
+        .degrade(&mut colors_only);
+        assert_eq!(colors_only.content[0].fg, Color::LightBlue);
+        assert_eq!(colors_only.content[0].symbol(), "╭");
+
+        // Without colors, a selection drawn black on a light color is reversed instead
+        let mut monochrome = buffer.clone();
+        monochrome.set_style(
+            Rect::new(4, 0, 3, 1),
+            ratatui::style::Style::new()
+                .fg(Color::Black)
+                .bg(tailwind::BLUE.c400),
+        );
+        Capabilities {
+            color: false,
+            ..Default::default()
+        }
+        .degrade(&mut monochrome);
+        assert_eq!(monochrome.content[0].fg, Color::Reset);
+        assert!(!monochrome.content[0].modifier.contains(Modifier::REVERSED));
+        assert_eq!(monochrome.content[4].bg, Color::Reset);
+        assert!(monochrome.content[4].modifier.contains(Modifier::REVERSED));
+
+        Capabilities {
+            unicode: false,
+            ..Default::default()
+        }
+        .degrade(&mut buffer);
+        assert_eq!(buffer, {
+            let mut expected = Buffer::with_lines(["+-> web."]);
+            expected.set_style(
+                Rect::new(0, 0, 3, 1),
+                ratatui::style::Style::new().fg(tailwind::BLUE.c400),
+            );
+            expected
+        });
+    }
+}
@@ -0,0 +1,70 @@
+//! Clipboard access through the OSC 52 terminal escape sequence, which also works over SSH and
+//! inside tmux (with `set-clipboard on`) without any platform clipboard library.
+
+use std::io::{self, Write};
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Asks the terminal behind `writer` to put `text` in the system clipboard.
+///
+/// # Errors
+///
+/// Will return `Err` if the escape sequence cannot be written.
+pub fn copy(writer: &mut impl Write, text: &str) -> io::Result<()> {
+    write!(writer, "\x1b]52;c;{}\x07", base64_encode(text.as_bytes()))?;
+    writer.flush()
+}
+
+fn base64_encode(input: &[u8]) -> String {
+    let mut output = String::with_capacity(input.len().div_ceil(3) * 4);
+
+    for chunk in input.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or_default(),
+            chunk.get(2).copied().unwrap_or_default(),
+        ];
+        let indexes = [
+            bytes[0] >> 2,
+            ((bytes[0] & 0b11) << 4) | (bytes[1] >> 4),
+            ((bytes[1] & 0b1111) << 2) | (bytes[2] >> 6),
+            bytes[2] & 0b11_1111,
+        ];
+
+        for (position, index) in indexes.iter().enumerate() {
+            if position <= chunk.len() {
+                output.push(char::from(BASE64_ALPHABET[usize::from(*index)]));
+            } else {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_copy_writes_osc52_sequence() {
+        let mut output = Vec::new();
+        copy(&mut output, "ssh-ed25519 AAAA").unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\x1b]52;c;c3NoLWVkMjU1MTkgQUFBQQ==\x07"
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! Problems found while loading the SSH configuration. The hosts that parsed are still shown,
+//! the problems are listed in a panel from which the offending line can be opened in `$EDITOR`.
+//! Along with them, the warnings about what loads but likely does not do what was meant: a
+//! host declared by several blocks, whose later blocks cannot override the first one.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::ssh::{self, Host};
+use crate::ssh_config::host::{duplicate_patterns, PatternDeclaration};
+
+/// Time a configuration file, includes included, may take to load. Files on a network mount
+/// can hang forever.
+const LOAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A configuration file, or one of its lines, that could not be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProblem {
+    pub path: PathBuf,
+    /// 1-based line number, `None` when the whole file could not be read
+    pub line: Option<usize>,
+    pub message: String,
+    /// The configuration loads, but likely not as meant
+    pub is_warning: bool,
+}
+
+impl std::fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}: {}", self.path.display(), self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl ConfigProblem {
+    /// Command opening the file in the editor, at the line.
+    #[must_use]
+    pub fn editor_command(&self) -> Command {
+        editor_command(&self.path, self.line)
+    }
+}
+
+/// Command opening `path` in `$VISUAL` or `$EDITOR` (`vi` by default), at `line` if known.
+#[must_use]
+pub fn editor_command(path: &Path, line: Option<usize>) -> Command {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_default();
+    let mut words = shlex::split(&editor)
+        .filter(|words| !words.is_empty())
+        .unwrap_or_else(|| vec!["vi".to_string()]);
+
+    let mut command = Command::new(words.remove(0));
+    command.args(words);
+    if let Some(line) = line {
+        // Understood by vi, vim, nano, emacs, micro...
+        command.arg(format!("+{line}"));
+    }
+    command.arg(path);
+
+    command
+}
+
+/// Loads the hosts of every configuration file, skipping what cannot be parsed. A missing
+/// system-wide configuration is not a problem. The hosts declared more than once across the
+/// files come last, as warnings.
+#[must_use]
+pub fn load_hosts(config_paths: &[String]) -> (Vec<Host>, Vec<ConfigProblem>) {
+    let mut hosts = Vec::new();
+    let mut problems = Vec::new();
+    let mut declarations = Vec::new();
+
+    for path in config_paths {
+        let Some(parsed) = parse_with_timeout(path) else {
+            problems.push(ConfigProblem {
+                path: PathBuf::from(shellexpand::tilde(path).to_string()),
+                line: None,
+                message: format!(
+                    "Not loaded, reading it took more than {}s",
+                    LOAD_TIMEOUT.as_secs()
+                ),
+                is_warning: false,
+            });
+            continue;
+        };
+
+        match parsed {
+            Ok(parsed) => {
+                hosts.extend(parsed.hosts);
+                declarations.extend(parsed.declarations);
+                problems.extend(parsed.diagnostics.into_iter().map(|diagnostic| {
+                    ConfigProblem {
+                        path: diagnostic
+                            .path
+                            .unwrap_or_else(|| PathBuf::from(shellexpand::tilde(path).to_string())),
+                        line: Some(diagnostic.line),
+                        message: diagnostic.error.to_string(),
+                        is_warning: false,
+                    }
+                }));
+            }
+            Err(ssh::ParseConfigError::Io(e))
+                if path == "/etc/ssh/ssh_config" && e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => problems.push(ConfigProblem {
+                path: PathBuf::from(shellexpand::tilde(path).to_string()),
+                line: None,
+                message: e.to_string(),
+                is_warning: false,
+            }),
+        }
+    }
+
+    problems.extend(duplicate_warnings(&declarations));
+    (hosts, problems)
+}
+
+/// Warns about the blocks declaring a host already declared before them: `ssh` uses the first
+/// value of each option, so the ones set again there are ignored.
+fn duplicate_warnings(declarations: &[PatternDeclaration]) -> Vec<ConfigProblem> {
+    duplicate_patterns(declarations)
+        .into_iter()
+        .map(|(duplicate, first)| {
+            let first_path = first
+                .path
+                .as_deref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            ConfigProblem {
+                path: duplicate.path.clone().unwrap_or_default(),
+                line: Some(duplicate.line),
+                message: format!(
+                    "Host {} is already declared at {first_path}:{}, the options set there win",
+                    duplicate.pattern, first.line
+                ),
+                is_warning: true,
+            }
+        })
+        .collect()
+}
+
+/// A configuration file parsed, or why it was not read
+type Parsed = Result<ssh::ParsedConfig, ssh::ParseConfigError>;
+
+/// Parses the file in a thread, `None` when it does not finish in time. The thread stuck on
+/// the file is left behind, it does not keep sshs from exiting.
+fn parse_with_timeout(path: &str) -> Option<Parsed> {
+    let (sender, receiver) = mpsc::channel();
+    let path = path.to_string();
+    std::thread::spawn(move || {
+        let _ = sender.send(ssh::parse_config_with_declarations(&path));
+    });
+
+    receiver.recv_timeout(LOAD_TIMEOUT).ok()
+}
+
+/// State of the configuration problems panel.
+#[derive(Debug, Default)]
+pub struct ConfigErrorsView {
+    pub problems: Vec<ConfigProblem>,
+    pub selected: usize,
+}
+
+impl ConfigErrorsView {
+    #[must_use]
+    pub fn new(problems: Vec<ConfigProblem>) -> Self {
+        Self {
+            problems,
+            selected: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn selected_problem(&self) -> Option<&ConfigProblem> {
+        self.problems.get(self.selected)
+    }
+
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.problems.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_hosts_skips_broken_lines() {
+        let dir = TempDir::new().unwrap();
+        let config = dir.path().join("config");
+        std::fs::write(
+            &config,
+            "Host web\n  HostName web.example.com\n  User\n\nHost db\n  Include missing-*.conf[\n",
+        )
+        .unwrap();
+        let missing = dir.path().join("missing");
+
+        let (hosts, problems) = load_hosts(&[
+            config.to_str().unwrap().to_string(),
+            missing.to_str().unwrap().to_string(),
+        ]);
+
+        assert_eq!(
+            hosts
+                .iter()
+                .map(|host| host.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["web", "db"]
+        );
+        assert_eq!(hosts[0].destination, "web.example.com");
+
+        let locations: Vec<_> = problems
+            .iter()
+            .map(|problem| (problem.path.file_name().unwrap().to_owned(), problem.line))
+            .collect();
+        assert_eq!(
+            locations,
+            vec![
+                ("config".into(), Some(3)),
+                ("config".into(), Some(6)),
+                ("missing".into(), None),
+            ]
+        );
+        assert!(problems[0]
+            .to_string()
+            .ends_with("config:3: Unable to parse line: 'User'"));
+        assert!(problems.iter().all(|problem| !problem.is_warning));
+    }
+
+    #[test]
+    fn test_load_hosts_warns_about_duplicate_hosts() {
+        let dir = TempDir::new().unwrap();
+        let config = dir.path().join("config");
+        let included = dir.path().join("work.conf");
+        std::fs::write(
+            &config,
+            format!(
+                "Host web\n  User deploy\n\nInclude {}\n",
+                included.display()
+            ),
+        )
+        .unwrap();
+        std::fs::write(&included, "Host db web\n  User root\n").unwrap();
+
+        let (hosts, problems) = load_hosts(&[config.to_str().unwrap().to_string()]);
+
+        assert_eq!(
+            hosts
+                .iter()
+                .find(|host| host.name == "web")
+                .unwrap()
+                .user
+                .as_deref(),
+            Some("deploy")
+        );
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].is_warning);
+        assert_eq!(problems[0].path, std::fs::canonicalize(&included).unwrap());
+        assert_eq!(problems[0].line, Some(1));
+        assert!(problems[0]
+            .message
+            .starts_with("Host web is already declared at "));
+        assert!(problems[0].message.contains("config:1,"));
+    }
+}
@@ -0,0 +1,208 @@
+//! Background resolution of the destinations of the listed hosts (`--dns-prefetch`), cached so
+//! that the host details never wait on the resolver.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, ToSocketAddrs};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Lookups running at the same time
+const MAX_LOOKUPS: usize = 4;
+
+/// The system resolver does not tell how long its answers live, they are trusted this long
+const FRESH_FOR: Duration = Duration::from_mins(5);
+
+/// Failures are retried sooner, the network may be back
+const FAILURE_FRESH_FOR: Duration = Duration::from_secs(30);
+
+/// Addresses of a name, or why it did not resolve
+type Addresses = Result<Vec<IpAddr>, String>;
+
+/// Result of a lookup, and when it was made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    pub addresses: Addresses,
+    pub resolved_at: Instant,
+}
+
+impl Resolution {
+    /// Whether the resolution is too old to be trusted. It is still shown until the new lookup
+    /// completes.
+    #[must_use]
+    pub fn is_stale(&self, now: Instant) -> bool {
+        let fresh_for = if self.addresses.is_ok() {
+            FRESH_FOR
+        } else {
+            FAILURE_FRESH_FOR
+        };
+
+        now.duration_since(self.resolved_at) > fresh_for
+    }
+}
+
+/// Resolutions of the host destinations, and the lookups waiting to run.
+#[derive(Debug)]
+pub struct DnsCache {
+    resolutions: HashMap<String, Resolution>,
+    queue: VecDeque<String>,
+    in_flight: HashSet<String>,
+    sender: mpsc::Sender<(String, Addresses)>,
+    receiver: mpsc::Receiver<(String, Addresses)>,
+    resolve: fn(&str) -> Addresses,
+}
+
+impl Default for DnsCache {
+    fn default() -> Self {
+        Self::with_resolver(lookup)
+    }
+}
+
+impl DnsCache {
+    fn with_resolver(resolve: fn(&str) -> Addresses) -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        Self {
+            resolutions: HashMap::new(),
+            queue: VecDeque::new(),
+            in_flight: HashSet::new(),
+            sender,
+            receiver,
+            resolve,
+        }
+    }
+
+    /// Queues the lookups of the `names` not resolved yet, or stale at `now`, in order. The
+    /// previous queue is dropped, so that the hosts no longer listed are not looked up; lookups
+    /// already running complete and are cached.
+    pub fn prefetch<'a>(&mut self, names: impl IntoIterator<Item = &'a str>, now: Instant) {
+        self.queue.clear();
+        for name in names {
+            let needed = self
+                .resolutions
+                .get(name)
+                .is_none_or(|resolution| resolution.is_stale(now));
+
+            if needed && !self.in_flight.contains(name) && !self.queue.iter().any(|n| n == name) {
+                self.queue.push_back(name.to_string());
+            }
+        }
+    }
+
+    /// Caches the finished lookups and starts the queued ones, a few at a time.
+    pub fn poll(&mut self) {
+        while let Ok((name, addresses)) = self.receiver.try_recv() {
+            self.in_flight.remove(&name);
+            self.resolutions.insert(
+                name,
+                Resolution {
+                    addresses,
+                    resolved_at: Instant::now(),
+                },
+            );
+        }
+
+        while self.in_flight.len() < MAX_LOOKUPS {
+            let Some(name) = self.queue.pop_front() else {
+                break;
+            };
+
+            let sender = self.sender.clone();
+            let resolve = self.resolve;
+            self.in_flight.insert(name.clone());
+            // A lookup stuck on the resolver is left behind, it does not keep sshs from exiting
+            std::thread::spawn(move || {
+                let addresses = resolve(&name);
+                let _ = sender.send((name, addresses));
+            });
+        }
+    }
+
+    /// Whether lookups are running or waiting to run.
+    #[must_use]
+    pub fn is_busy(&self) -> bool {
+        !self.in_flight.is_empty() || !self.queue.is_empty()
+    }
+
+    /// Whether `name` is being looked up, or waiting to be.
+    #[must_use]
+    pub fn is_pending(&self, name: &str) -> bool {
+        self.in_flight.contains(name) || self.queue.iter().any(|n| n == name)
+    }
+
+    /// Drops the resolutions, which may not hold on another network (split DNS of a VPN).
+    /// Lookups already running complete and are cached.
+    pub fn forget(&mut self) {
+        self.resolutions.clear();
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Resolution> {
+        self.resolutions.get(name)
+    }
+}
+
+/// Resolves `name` with the system resolver (`/etc/hosts`, DNS...).
+fn lookup(name: &str) -> Addresses {
+    let mut addresses = Vec::new();
+    for address in (name, 0).to_socket_addrs().map_err(|e| e.to_string())? {
+        if !addresses.contains(&address.ip()) {
+            addresses.push(address.ip());
+        }
+    }
+
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_lookup(name: &str) -> Addresses {
+        match name {
+            "unknown.example.com" => Err("Name or service not known".to_string()),
+            _ => Ok(vec![IpAddr::from([
+                10,
+                0,
+                0,
+                u8::try_from(name.len()).unwrap(),
+            ])]),
+        }
+    }
+
+    #[test]
+    fn test_prefetch_is_bounded_and_cancellable() {
+        let mut dns = DnsCache::with_resolver(fake_lookup);
+        let names = ["a", "bb", "ccc", "dddd", "eeeee", "unknown.example.com"];
+
+        let now = Instant::now();
+        dns.prefetch(names, now);
+        dns.poll();
+        assert_eq!(dns.in_flight.len(), MAX_LOOKUPS);
+        assert!(dns.is_pending("unknown.example.com"));
+
+        // The filter changed: the lookups not started yet are dropped
+        dns.prefetch(["a", "ffffff"], now);
+        assert!(!dns.is_pending("unknown.example.com"));
+        assert_eq!(dns.queue, ["ffffff"]);
+
+        while dns.is_busy() {
+            dns.poll();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(
+            dns.get("ffffff").unwrap().addresses,
+            Ok(vec![IpAddr::from([10, 0, 0, 6])])
+        );
+        assert!(dns.get("eeeee").is_none());
+
+        // Fresh resolutions are not looked up again, stale ones are
+        dns.prefetch(["a", "ffffff"], Instant::now());
+        assert!(!dns.is_busy());
+
+        let later = Instant::now() + FRESH_FOR + Duration::from_secs(1);
+        assert!(dns.get("a").unwrap().is_stale(later));
+        dns.prefetch(["a", "ffffff"], later);
+        assert!(dns.is_pending("a"));
+        assert!(dns.is_pending("ffffff"));
+    }
+}
@@ -0,0 +1,1306 @@
+//! Scriptable UI driver: feeds synthetic events to the [`App`] and captures the rendered
+//! screen, for end-to-end tests and the `--headless-script` mode.
+
+use anyhow::{anyhow, Result};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use ratatui::{
+    backend::CrosstermBackend, buffer::Buffer, layout::Rect, Terminal, TerminalOptions, Viewport,
+};
+use std::{cell::RefCell, io, rc::Rc};
+
+use super::app::App;
+
+/// Backend discarding the escape codes; the screen is read from the terminal buffer instead.
+pub type HeadlessBackend = CrosstermBackend<io::Sink>;
+
+pub const DEFAULT_WIDTH: u16 = 120;
+pub const DEFAULT_HEIGHT: u16 = 30;
+
+pub struct UiDriver {
+    pub app: App,
+    terminal: Rc<RefCell<Terminal<HeadlessBackend>>>,
+    screen: Buffer,
+    stopped: bool,
+}
+
+impl UiDriver {
+    /// Creates a driver rendering the app in a fixed `width`x`height` viewport.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the first frame cannot be rendered.
+    pub fn new(app: App, width: u16, height: u16) -> Result<UiDriver> {
+        let area = Rect::new(0, 0, width, height);
+        let terminal = Terminal::with_options(
+            CrosstermBackend::new(io::sink()),
+            TerminalOptions {
+                viewport: Viewport::Fixed(area),
+            },
+        )?;
+
+        let mut driver = UiDriver {
+            app,
+            terminal: Rc::new(RefCell::new(terminal)),
+            screen: Buffer::empty(area),
+            stopped: false,
+        };
+        driver.render()?;
+
+        Ok(driver)
+    }
+
+    /// Sends an event through the same path as the interactive loop, then renders a frame.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the app fails to handle the event or the frame cannot be rendered.
+    pub fn send(&mut self, event: &Event) -> Result<()> {
+        if self.stopped {
+            return Err(anyhow!("The application has already stopped"));
+        }
+
+        self.stopped = self.app.handle_event(&self.terminal, event)?;
+        self.render()
+    }
+
+    /// # Errors
+    ///
+    /// See [`UiDriver::send`].
+    pub fn press(&mut self, code: KeyCode) -> Result<()> {
+        self.press_with(code, KeyModifiers::NONE)
+    }
+
+    /// # Errors
+    ///
+    /// See [`UiDriver::send`].
+    pub fn press_with(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Result<()> {
+        self.send(&Event::Key(KeyEvent::new(code, modifiers)))
+    }
+
+    /// Clicks the left mouse button at `column`, `row`.
+    ///
+    /// # Errors
+    ///
+    /// See [`UiDriver::send`].
+    pub fn click(&mut self, column: u16, row: u16) -> Result<()> {
+        self.send(&Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }))
+    }
+
+    /// Types `text` one character at a time.
+    ///
+    /// # Errors
+    ///
+    /// See [`UiDriver::send`].
+    pub fn type_text(&mut self, text: &str) -> Result<()> {
+        for c in text.chars() {
+            self.press(KeyCode::Char(c))?;
+        }
+
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the state cannot be saved or the frame cannot be rendered.
+    pub fn render(&mut self) -> Result<()> {
+        self.app.check_feedback_timeout();
+        self.app.update_tutorial();
+        // There is no idle time to batch the state changes in, they are written right away
+        self.app.state.flush()?;
+
+        let app = &mut self.app;
+        let frame = self
+            .terminal
+            .borrow_mut()
+            .draw(|f| super::render::ui(f, app))?
+            .buffer
+            .clone();
+        self.screen = frame;
+
+        Ok(())
+    }
+
+    /// Returns the last rendered screen as text, one line per row, trailing spaces removed.
+    #[must_use]
+    pub fn screen(&self) -> String {
+        let area = self.screen.area;
+
+        (area.top()..area.bottom())
+            .map(|y| {
+                (area.left()..area.right())
+                    .map(|x| self.screen[(x, y)].symbol())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[must_use]
+    pub fn screen_contains(&self, text: &str) -> bool {
+        self.screen().contains(text)
+    }
+
+    /// Whether a key made the application quit.
+    #[must_use]
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Runs a script of actions, one per line:
+    ///
+    /// - `key <name>`: presses a key (`enter`, `esc`, `tab`, `up`, `ctrl+n`, `j`, ...)
+    /// - `type <text>`: types the text
+    /// - `click <column> <row>`: clicks there, from 0
+    /// - `expect <text>`: fails unless the screen contains the text
+    /// - `reject <text>`: fails if the screen contains the text
+    /// - `screen`: prints the current screen to stdout
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` on an invalid line, a failed expectation or a failing action.
+    pub fn run_script(&mut self, script: &str) -> Result<()> {
+        for (index, line) in script.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+            match command {
+                "key" => {
+                    let (code, modifiers) = parse_key(argument)
+                        .ok_or_else(|| anyhow!("line {line_number}: unknown key '{argument}'"))?;
+                    self.press_with(code, modifiers)?;
+                }
+                "type" => self.type_text(argument)?,
+                "click" => {
+                    let (column, row) = argument
+                        .split_once(' ')
+                        .and_then(|(column, row)| Some((column.parse().ok()?, row.parse().ok()?)))
+                        .ok_or_else(|| {
+                            anyhow!("line {line_number}: invalid position '{argument}'")
+                        })?;
+                    self.click(column, row)?;
+                }
+                "expect" => {
+                    if !self.screen_contains(argument) {
+                        anyhow::bail!(
+                            "line {line_number}: expected '{argument}' on screen:\n{}",
+                            self.screen()
+                        );
+                    }
+                }
+                "reject" => {
+                    if self.screen_contains(argument) {
+                        anyhow::bail!(
+                            "line {line_number}: unexpected '{argument}' on screen:\n{}",
+                            self.screen()
+                        );
+                    }
+                }
+                "screen" => println!("{}", self.screen()),
+                _ => anyhow::bail!("line {line_number}: unknown command '{command}'"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a key name such as `enter`, `ctrl+n` or `j`.
+fn parse_key(name: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let (modifiers, name) = match name.strip_prefix("ctrl+") {
+        Some(name) => (KeyModifiers::CONTROL, name),
+        None => (KeyModifiers::NONE, name),
+    };
+
+    let code = match name.to_lowercase().as_str() {
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "space" => KeyCode::Char(' '),
+        key => {
+            if let Some(number) = key.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                return Some((KeyCode::F(number), modifiers));
+            }
+
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return None,
+            }
+        }
+    };
+
+    Some((code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::GroupMode;
+    use crate::ui::app::AppConfig;
+    use crate::ui::audit_log;
+    use crate::ui::capabilities::Capabilities;
+    use crate::ui::prompt::{Prompt, PromptKind};
+    use crate::ui::tutorial;
+    use std::fs;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn create_driver(dir: &TempDir, config: &str) -> UiDriver {
+        let system_config = dir.path().join("ssh_config");
+        let user_config = dir.path().join("config");
+        fs::write(&system_config, "").unwrap();
+        fs::write(&user_config, config).unwrap();
+
+        let app = App::new(&AppConfig {
+            config_paths: vec![
+                system_config.display().to_string(),
+                user_config.display().to_string(),
+            ],
+            state_path: dir.path().join("state.json").display().to_string(),
+            vault: None,
+            managed_config_path: None,
+            search_filter: None,
+            connect_if_unique: false,
+            sort_by_name: true,
+            show_proxy_command: false,
+            sort_given: false,
+            show_proxy_command_given: false,
+            command_template: String::new(),
+            command_template_on_session_start: None,
+            command_template_on_session_end: None,
+            command_template_on_connection_failure: None,
+            command_template_on_socks: None,
+            exit_after_ssh_session_ends: false,
+            socks_port: 1080,
+            default_user: None,
+            dotfiles: Vec::new(),
+            shared_metadata_path: None,
+            connect_delay: Duration::ZERO,
+            end_screen_delay: None,
+            tab_naming: crate::ui::tabs::TabNaming::default(),
+            workspace: None,
+            stats_file: None,
+            audit_log: None,
+            no_exec: false,
+            dns_prefetch: false,
+            health_check_interval: None,
+            unused_after_months: 6,
+            leader: crate::ui::actions::DEFAULT_LEADER,
+            display: crate::config_file::Display::default(),
+            keymap: crate::ui::keymap::Keymap::default(),
+            log_sessions: false,
+            session_log_dir: "/test/logs".to_string(),
+            capabilities: Capabilities::default(),
+            triggers: Vec::new(),
+            backend: crate::backend::BackendKind::Exec,
+        })
+        .unwrap();
+
+        UiDriver::new(app, DEFAULT_WIDTH, DEFAULT_HEIGHT).unwrap()
+    }
+
+    #[test]
+    fn test_search_filters_rendered_hosts() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(
+            &dir,
+            "Host web\n  Hostname web.example.com\n\nHost db\n  Hostname db.example.com\n",
+        );
+
+        assert!(driver.screen_contains("web.example.com"));
+        assert!(driver.screen_contains("db.example.com"));
+
+        driver.press(KeyCode::Char('/')).unwrap();
+        driver.type_text("db").unwrap();
+        assert!(driver.screen_contains("db.example.com"));
+        assert!(!driver.screen_contains("web.example.com"));
+
+        driver.press(KeyCode::Esc).unwrap();
+        assert!(driver.screen_contains("web.example.com"));
+    }
+
+    #[test]
+    fn test_add_host_flow_updates_config_and_list() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host web\n  Hostname web.example.com\n");
+
+        driver
+            .run_script(
+                "key n\n\
+                 expect Add New SSH Host\n\
+                 type staging\n\
+                 key tab\n\
+                 type staging.example.com\n\
+                 key enter\n\
+                 reject Add New SSH Host\n\
+                 expect Host added successfully!\n\
+                 expect staging\n",
+            )
+            .unwrap();
+
+        let content = fs::read_to_string(dir.path().join("config")).unwrap();
+        assert!(content.contains("Host staging"));
+    }
+
+    #[test]
+    fn test_hostname_field_suggests_known_destinations() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host web\n  Hostname web.example.com\n");
+
+        driver
+            .run_script(
+                "key n\n\
+                 type web2\n\
+                 key tab\n\
+                 type web.exa\n\
+                 expect Tab takes it\n\
+                 key down\n\
+                 key tab\n\
+                 reject Tab takes it\n\
+                 key enter\n\
+                 expect Host added successfully!\n",
+            )
+            .unwrap();
+
+        let content = fs::read_to_string(dir.path().join("config")).unwrap();
+        assert!(content.contains("Host web2\n  Hostname web.example.com\n"));
+    }
+
+    #[test]
+    fn test_ipv6_only_hosts() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(
+            &dir,
+            "Host v6\n  Hostname 2001:db8:85a3::8a2e:370:7334\n\nHost link\n  Hostname fe80::1%eth0\n",
+        );
+
+        assert!(driver.screen_contains("2001:db8:85a3::8a2e:370:7334"));
+        assert!(driver.screen_contains("fe80::1%eth0"));
+
+        driver
+            .run_script(
+                "key n\n\
+                 type lab\n\
+                 key tab\n\
+                 type [2001:db8::10]:2222\n\
+                 key enter\n\
+                 expect Host added successfully!\n",
+            )
+            .unwrap();
+
+        let content = fs::read_to_string(dir.path().join("config")).unwrap();
+        assert!(content.contains("Host lab\n  Hostname 2001:db8::10\n  Port 2222\n"));
+    }
+
+    #[test]
+    fn test_config_problems_panel_keeps_parsed_hosts() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(
+            &dir,
+            "Host web\n  Hostname web.example.com\n  Port\n\nHost db\n  Hostname db.example.com\n",
+        );
+
+        driver
+            .run_script(
+                "expect SSH configuration problems (2 hosts loaded)\n\
+                 expect config:3  Unable to parse line: 'Port'\n\
+                 key esc\n\
+                 reject SSH configuration problems\n\
+                 expect web.example.com\n\
+                 expect db.example.com\n",
+            )
+            .unwrap();
+
+        let empty = TempDir::new().unwrap();
+        fs::write(empty.path().join("config"), "Port\n").unwrap();
+        let error = App::new(&AppConfig {
+            config_paths: vec![empty.path().join("config").display().to_string()],
+            ..driver.app.config.clone()
+        })
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("Unable to parse line: 'Port'"));
+    }
+
+    #[test]
+    fn test_backups_view_shows_diff_and_restores() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host web\n  Hostname web.example.com\n");
+        fs::write(
+            dir.path().join("config.bak"),
+            "Host old\n  Hostname old.example.com\n",
+        )
+        .unwrap();
+
+        driver
+            .run_script(
+                "key b\n\
+                 expect Configuration backups\n\
+                 expect config.bak\n\
+                 key enter\n\
+                 expect - Host old\n\
+                 expect + Host web\n\
+                 key h\n\
+                 expect (h) line diff\n\
+                 expect -   Hostname old.example.com\n\
+                 expect +   Hostname web.example.com\n\
+                 key r\n\
+                 expect Restored\n\
+                 key esc\n\
+                 key esc\n\
+                 reject Configuration backups\n\
+                 reject web\n",
+            )
+            .unwrap();
+
+        assert!(fs::read_to_string(dir.path().join("config.bak"))
+            .unwrap()
+            .contains("Host web"));
+    }
+
+    #[test]
+    fn test_expired_hosts_are_cleaned_up() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(
+            &dir,
+            "Host old old-alias\n  #_Expires 2020-01-31\n  Hostname old.example.com\n\n\
+             Host web\n  #_Expires 2999-12-31\n  Hostname web.example.com\n",
+        );
+
+        driver
+            .run_script(
+                "key X\n\
+                 expect 1 expired host(s)\n\
+                 expect old (expired 2020-01-31)\n\
+                 key y\n\
+                 expect Removed 1 expired host(s)\n\
+                 reject old.example.com\n\
+                 expect web\n\
+                 key X\n\
+                 expect No expired hosts\n",
+            )
+            .unwrap();
+
+        let config = fs::read_to_string(dir.path().join("config")).unwrap();
+        assert!(!config.contains("old"));
+        assert!(config.contains("Host web"));
+    }
+
+    #[test]
+    fn test_tags_are_edited_on_listed_hosts() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(
+            &dir,
+            "Host web\n  # sshs:tags=old\n  Hostname web.example.com\n\n\
+             Host web2\n  Hostname web2.example.com\n\n\
+             Host db\n  # sshs:tags=old\n  Hostname db.example.com\n",
+        );
+
+        driver
+            .run_script(
+                "key /\n\
+                 type web\n\
+                 key enter\n\
+                 key T\n\
+                 expect Tags of the listed hosts\n\
+                 type +prod -old\n\
+                 key enter\n\
+                 expect Tags of 2 host(s) change\n\
+                 expect Apply +prod -old?\n\
+                 key y\n\
+                 expect Tagged 2 host(s)\n",
+            )
+            .unwrap();
+
+        let config = fs::read_to_string(dir.path().join("config")).unwrap();
+        assert_eq!(
+            config,
+            "Host web\n  # sshs:tags=prod\n  Hostname web.example.com\n\n\
+             Host web2\n  # sshs:tags=prod\n  Hostname web2.example.com\n\n\
+             Host db\n  # sshs:tags=old\n  Hostname db.example.com\n"
+        );
+        assert!(dir.path().join("config.bak").exists());
+    }
+
+    #[test]
+    fn test_unused_hosts_are_commented_out() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(
+            &dir,
+            "Host web\n  Hostname web.example.com\n\nHost db\n  Hostname db.example.com\n",
+        );
+        let today = crate::ssh::today();
+        driver.app.state.connections_recorded_since = Some(today - 400);
+        driver.app.state.record_connection("db", today - 10);
+
+        driver
+            .run_script(
+                "key U\n\
+                 expect 1 host(s) unused for 6 months\n\
+                 expect web (never connected)\n\
+                 expect (C) Comment out\n\
+                 key c\n\
+                 expect Commented out 1 unused host(s)\n\
+                 reject web.example.com\n\
+                 key U\n\
+                 expect No host unused for 6 months\n",
+            )
+            .unwrap();
+
+        let config = fs::read_to_string(dir.path().join("config")).unwrap();
+        assert!(config.starts_with("# [unused] Host web\n# [unused]   Hostname web.example.com\n"));
+        assert!(config.contains("\nHost db\n"));
+    }
+
+    #[test]
+    fn test_tutorial_checks_each_step() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, tutorial::SAMPLE_CONFIG);
+        let connections = tutorial::Connections::default();
+        driver.app.tutorial = Some(tutorial::Tutorial::new(Rc::clone(&connections)));
+        driver.render().unwrap();
+
+        driver
+            .run_script(
+                "expect Tutorial 1/5: Search\n\
+                 key /\n\
+                 type web\n\
+                 key enter\n\
+                 expect ✓ Search done\n\
+                 expect Tutorial 2/5: Connect\n",
+            )
+            .unwrap();
+
+        // Connections are recorded by the tutorial backend instead of running ssh
+        connections
+            .borrow_mut()
+            .push(("web-01".to_string(), crate::ssh::ConnectOptions::default()));
+        driver.render().unwrap();
+        driver
+            .run_script(
+                "expect Tutorial 3/5: Edit\n\
+                 key e\n\
+                 key tab\n\
+                 key tab\n\
+                 type admin\n\
+                 key enter\n\
+                 key y\n\
+                 expect Tutorial 4/5: Tabs\n\
+                 key ctrl+n\n\
+                 expect Tutorial 4/5: Tabs\n\
+                 key ctrl+q\n\
+                 expect Tutorial 5/5: Tunnels\n",
+            )
+            .unwrap();
+
+        let config = fs::read_to_string(dir.path().join("config")).unwrap();
+        assert!(config.contains("User admin"));
+    }
+
+    #[test]
+    fn test_update_badge_and_changelog() {
+        use crate::ui::updates::{Release, UpdateCheck};
+
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host web\n  Hostname web.example.com\n");
+        driver
+            .run_script("key V\nexpect Turn the update check on in the settings (,)\n")
+            .unwrap();
+
+        // Checked today already: the release found then is used, GitHub is not asked again
+        driver.app.settings.check_updates = true;
+        driver.app.state.update_check = Some(UpdateCheck {
+            day: crate::ssh::today(),
+            latest: Some(Release {
+                version: "99.0.0".to_string(),
+                changelog: "- Match blocks in the edit form\n- Quiet mode".to_string(),
+                url: "https://github.com/imrellx/sshs/releases/tag/v99.0.0".to_string(),
+            }),
+        });
+        driver.app.start_update_check();
+        assert!(driver.app.update_checker.is_none());
+        driver.app.feedback_message = None;
+        driver.render().unwrap();
+
+        driver
+            .run_script(
+                "expect sshs 99.0.0 available (V)\n\
+                 key V\n\
+                 expect What's new\n\
+                 expect Upgrade with: cargo install sshs --locked\n\
+                 expect - Quiet mode\n\
+                 key esc\n\
+                 reject What's new\n",
+            )
+            .unwrap();
+
+        driver.app.settings.check_updates = false;
+        driver.app.start_update_check();
+        driver.render().unwrap();
+        driver.run_script("reject available (V)\n").unwrap();
+    }
+
+    #[test]
+    fn test_timeline_lists_and_searches_host_events() {
+        use crate::state::{HostEvent, HostEventKind};
+
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host web\n  Hostname web.example.com\n");
+        for (time, kind, detail) in [
+            (
+                1_792_159_380,
+                HostEventKind::Connection,
+                "session of 5s, exit code 0",
+            ),
+            (
+                1_792_162_980,
+                HostEventKind::Failure,
+                "session of 1s, exit code 255",
+            ),
+        ] {
+            driver.app.state.record_host_event(
+                "web",
+                HostEvent {
+                    time,
+                    kind,
+                    detail: detail.to_string(),
+                },
+            );
+        }
+
+        driver
+            .run_script(
+                "key H\n\
+                 expect Timeline of web (UTC)\n\
+                 expect 2026-10-16 14:03  connection     session of 5s, exit code 0\n\
+                 expect 2026-10-16 15:03  failure        session of 1s, exit code 255\n\
+                 key /\n\
+                 type fail\n\
+                 key enter\n\
+                 expect Timeline of web (UTC) - /fail\n\
+                 reject exit code 0\n\
+                 key esc\n\
+                 expect exit code 0\n\
+                 key esc\n\
+                 reject Timeline of web\n",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_settings_panel_applies_and_saves() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(
+            &dir,
+            "Host web\n  Hostname a.example.com\n\nHost db\n  Hostname db.example.com\n",
+        );
+        assert_eq!(driver.app.hosts[0].name, "db");
+
+        // Sort by name -> sort by destination, then hide the port column
+        driver
+            .run_script(
+                "key ,\n\
+                 expect Settings\n\
+                 expect Sort by\n\
+                 key right\n\
+                 expect destination\n\
+                 key down\n\
+                 key down\n\
+                 key down\n\
+                 key down\n\
+                 key down\n\
+                 key space\n\
+                 key esc\n\
+                 reject Sort by\n\
+                 reject Port\n",
+            )
+            .unwrap();
+        assert_eq!(driver.app.hosts[0].name, "web");
+
+        let state = fs::read_to_string(dir.path().join("state.json")).unwrap();
+        assert!(state.contains("\"destination\""));
+        assert!(state.contains("\"port\""));
+    }
+
+    #[test]
+    fn test_quick_delete_comments_out_and_undoes() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(
+            &dir,
+            "Host web\n  Hostname web.example.com\n\nHost db\n  Hostname db.example.com\n",
+        );
+        driver.app.settings.quick_delete = true;
+
+        driver
+            .run_script(
+                "key d\n\
+                 key d\n\
+                 expect Deleted 'db' - (u) undo\n",
+            )
+            .unwrap();
+        assert_eq!(driver.app.hosts.len(), 1);
+        let config = fs::read_to_string(dir.path().join("config")).unwrap();
+        assert!(config.contains("# [deleted] Host db\n# [deleted]   Hostname db.example.com"));
+
+        driver.run_script("key u\nexpect Restored 'db'\n").unwrap();
+        assert_eq!(driver.app.hosts.len(), 2);
+        let config = fs::read_to_string(dir.path().join("config")).unwrap();
+        assert!(!config.contains("[deleted]"));
+    }
+
+    #[test]
+    fn test_audit_log_records_changes() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(
+            &dir,
+            "Host web\n  Hostname web.example.com\n\nHost db\n  Hostname db.example.com\n",
+        );
+        let log = dir.path().join("audit.log").display().to_string();
+        driver.app.config.audit_log = Some(log.clone());
+        driver.app.settings.quick_delete = true;
+
+        driver.run_script("key d\nkey d\nkey u\n").unwrap();
+
+        let entries = audit_log::read(&log).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].action, "undo delete");
+        assert_eq!(entries[1].action, "delete host");
+        assert_eq!(entries[1].hosts, "db");
+        assert!(entries[1]
+            .diff
+            .contains(&"+# [deleted] Host db".to_string()));
+
+        driver
+            .run_script(
+                "key f10\n\
+                 key down\n\
+                 key down\n\
+                 key enter\n\
+                 expect Audit log\n\
+                 expect undo delete\n\
+                 key down\n\
+                 key enter\n\
+                 expect delete host db\n\
+                 expect + # [deleted] Host db\n\
+                 key esc\n\
+                 key esc\n\
+                 reject Audit log\n",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_no_exec_copies_the_command_instead_of_connecting() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host web\n  Hostname web.example.com\n");
+        driver.app.config.no_exec = true;
+
+        driver
+            .run_script(
+                "key enter\n\
+                 expect Copied (--no-exec): ssh -o\n\
+                 expect -p 22 -- web.example.com\n\
+                 key ctrl+n\n\
+                 expect Sessions are disabled with --no-exec\n",
+            )
+            .unwrap();
+        assert!(!driver.is_stopped());
+        assert!(!driver.app.tab_manager.has_sessions());
+    }
+
+    #[test]
+    fn test_details_show_where_options_come_from() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(
+            &dir,
+            "Host web\n  Hostname web.example.com\n\nHost *\n  User deploy\n",
+        );
+
+        driver
+            .run_script(
+                "key i\n\
+                 expect config:2\n\
+                 expect config:5 (Host *)\n\
+                 key tab\n",
+            )
+            .unwrap();
+        assert_eq!(driver.app.details_option, 1);
+
+        driver
+            .run_script(
+                "key ?\n\
+                 expect User: User to log in as\n",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_saved_searches() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(
+            &dir,
+            "Host web\n  Hostname web.example.com\n  User root\n  # sshs:tags=prod\n\n\
+             Host db\n  Hostname db.example.com\n  # sshs:tags=prod\n",
+        );
+
+        driver
+            .run_script(
+                "key M\n\
+                 expect Type a search to save first\n\
+                 key /\n\
+                 type tag:prod user:root\n\
+                 key enter\n\
+                 reject db.example.com\n\
+                 key M\n\
+                 type prod roots\n\
+                 key enter\n\
+                 expect Search 'prod roots' saved\n\
+                 key /\n\
+                 key esc\n",
+            )
+            .unwrap();
+        assert_eq!(driver.app.hosts.len(), 2);
+
+        driver
+            .run_script(
+                "key 1\n\
+                 expect Search 'prod roots': 1 host(s)\n\
+                 key 2\n\
+                 expect No saved search 2\n",
+            )
+            .unwrap();
+        assert_eq!(driver.app.hosts.len(), 1);
+        assert_eq!(
+            driver.app.state.saved_search("prod roots"),
+            Some("tag:prod user:root")
+        );
+    }
+
+    #[test]
+    fn test_collapse_included_file_group() {
+        let dir = TempDir::new().unwrap();
+        let work = dir.path().join("work.conf");
+        fs::write(
+            &work,
+            "Host work-web\n  Hostname web.work.example.com\n\n\
+             Host work-db\n  Hostname db.work.example.com\n",
+        )
+        .unwrap();
+        let mut driver = create_driver(
+            &dir,
+            &format!(
+                "Include {}\n\nHost home\n  Hostname home.example.com\n",
+                work.display()
+            ),
+        );
+
+        driver
+            .run_script("key z\nexpect Group the hosts first\n")
+            .unwrap();
+        driver.app.feedback_message = None;
+        driver.app.settings.group = GroupMode::File;
+        driver.app.reload_hosts().unwrap();
+        driver.render().unwrap();
+        driver.run_script("expect ▾ work.conf\n").unwrap();
+        assert_eq!(driver.app.hosts.len(), 3);
+
+        let selected = driver
+            .app
+            .hosts
+            .iter()
+            .position(|host| host.name == "work-db")
+            .unwrap();
+        driver.app.table_state.select(Some(selected));
+        driver
+            .run_script(
+                "key z\n\
+                 expect ▸ work.conf (2)\n\
+                 reject web.work.example.com\n",
+            )
+            .unwrap();
+        assert_eq!(driver.app.hosts.len(), 2);
+
+        // Searching lists the hosts of collapsed groups
+        driver.run_script("key /\ntype web\n").unwrap();
+        assert_eq!(driver.app.hosts.len(), 1);
+        driver.run_script("key esc\n").unwrap();
+        assert_eq!(driver.app.hosts.len(), 2);
+
+        driver.app.table_state.select(Some(selected));
+        driver.run_script("key z\n").unwrap();
+        assert_eq!(driver.app.hosts.len(), 3);
+    }
+
+    #[test]
+    fn test_menu_bar_runs_actions() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host web\n  Hostname web.example.com\n");
+
+        driver
+            .run_script(
+                "key f10\n\
+                 expect File\n\
+                 expect Reload SSH config\n\
+                 key right\n\
+                 expect Connect with SOCKS proxy\n\
+                 key down\n\
+                 key down\n\
+                 key down\n\
+                 key down\n\
+                 key down\n\
+                 key enter\n\
+                 reject Connect with SOCKS proxy\n\
+                 expect Add New SSH Host\n",
+            )
+            .unwrap();
+        assert!(driver.app.menu.is_none());
+
+        // Esc closes the menu without running anything
+        driver
+            .run_script("key esc\nkey f10\nkey esc\nreject Reload SSH config")
+            .unwrap();
+        assert!(!driver.is_stopped());
+
+        // With the mouse: " File " then " Hosts ", whose sixth action adds a host
+        driver
+            .run_script(
+                "key esc\n\
+                 key f10\n\
+                 click 8 0\n\
+                 expect Connect with SOCKS proxy\n\
+                 click 8 7\n\
+                 reject Connect with SOCKS proxy\n\
+                 expect Add New SSH Host\n",
+            )
+            .unwrap();
+
+        // A click outside the menu closes it
+        driver
+            .run_script("key esc\nkey f10\nclick 100 20\nreject Reload SSH config")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_workspace_saved_from_menu_reopens_tabs() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(
+            &dir,
+            "Host web\n  Hostname web.example.com\n\nHost db\n  Hostname db.example.com\n",
+        );
+
+        driver
+            .run_script(
+                "key ctrl+n\n\
+                 expect -- SESSION --\n\
+                 key ctrl+q\n\
+                 key down\n\
+                 key ctrl+n\n\
+                 expect [1:db]\n\
+                 key ctrl+q\n\
+                 key f10\n\
+                 key right\n\
+                 key right\n\
+                 key down\n\
+                 key down\n\
+                 key enter\n\
+                 expect Save workspace\n\
+                 type oncall\n\
+                 key enter\n\
+                 expect Workspace 'oncall' saved (2 sessions)\n",
+            )
+            .unwrap();
+
+        let app = App::new(&AppConfig {
+            workspace: Some("oncall".to_string()),
+            ..driver.app.config.clone()
+        })
+        .unwrap();
+        assert_eq!(app.tab_manager.host_names(), vec!["db", "web"]);
+
+        assert!(App::new(&AppConfig {
+            workspace: Some("weekend".to_string()),
+            ..driver.app.config.clone()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_flags_given_win_over_file_and_saved_settings() {
+        use crate::config_file::Display;
+        use crate::settings::{Column, Settings, SortMode, Theme};
+
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host web\n");
+        let config = AppConfig {
+            display: Display {
+                sort: Some(SortMode::Config),
+                ..Display::default()
+            },
+            ..driver.app.config.clone()
+        };
+        assert_eq!(App::new(&config).unwrap().settings.sort, SortMode::Config);
+
+        // The file only overrides what it sets
+        let mut saved = Settings {
+            sort: SortMode::Destination,
+            theme: Theme::Rose,
+            ..Settings::default()
+        };
+        saved.hidden_columns.insert(Column::Proxy);
+        driver.app.state.settings = Some(saved);
+        driver.app.state.save().unwrap();
+        let app = App::new(&config).unwrap();
+        assert_eq!(app.settings.sort, SortMode::Config);
+        assert_eq!(app.settings.theme, Theme::Rose);
+
+        let app = App::new(&AppConfig {
+            sort_given: true,
+            show_proxy_command: true,
+            show_proxy_command_given: true,
+            ..config
+        })
+        .unwrap();
+        assert_eq!(app.settings.sort, SortMode::Name);
+        assert!(app.settings.is_column_shown(Column::Proxy));
+    }
+
+    #[test]
+    fn test_locked_tab_needs_its_host_name() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host web\n  Hostname web.example.com\n");
+
+        driver
+            .run_script(
+                "key ctrl+n\n\
+                 key ctrl+l\n\
+                 expect ▶[🔒\n\
+                 key ctrl+t\n\
+                 expect The tab is locked\n\
+                 key ctrl+l\n\
+                 expect Unlock tab\n\
+                 type wbe\n\
+                 key enter\n\
+                 expect Tab still locked, type 'web'\n\
+                 key ctrl+l\n\
+                 type web\n\
+                 key enter\n\
+                 expect Tab unlocked\n\
+                 expect ▶[1:web]\n",
+            )
+            .unwrap();
+        assert!(!driver.app.tab_manager.has_locked_sessions());
+    }
+
+    #[test]
+    fn test_hosts_are_marked_for_a_batch_run() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host a\nHost b\nHost c\nHost d\n");
+
+        driver
+            .run_script(
+                "key space\n\
+                 expect 1 marked\n\
+                 key v\n\
+                 expect -- VISUAL --\n\
+                 key j\n\
+                 key v\n\
+                 expect 3 marked\n\
+                 key E\n\
+                 expect Run on the marked hosts one by one\n\
+                 key tab\n\
+                 expect Run on the marked hosts in parallel\n\
+                 key esc\n\
+                 key esc\n\
+                 reject marked\n",
+            )
+            .unwrap();
+        assert!(driver.app.marked_hosts.is_empty());
+    }
+
+    #[test]
+    fn test_leader_sequences_run_actions() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host web\n  Hostname web.example.com\n");
+
+        driver
+            .run_script(
+                "key \\\n\
+                 expect -- LEADER --\n\
+                 expect +tabs\n\
+                 key r\n\
+                 expect Reload SSH config\n\
+                 reject +tabs\n\
+                 key backspace\n\
+                 expect +config\n\
+                 key z\n\
+                 expect Nothing on \\z\n\
+                 reject -- LEADER --\n\
+                 key \\\n\
+                 key r\n\
+                 key s\n\
+                 expect Sort by\n\
+                 reject -- LEADER --\n",
+            )
+            .unwrap();
+        assert_eq!(driver.app.leader_keys, None);
+    }
+
+    #[test]
+    fn test_agent_key_is_picked() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host web\n  Hostname web.example.com\n");
+
+        // As opened when the agent holds several keys
+        let mut prompt = Prompt::new(
+            PromptKind::AgentKey,
+            vec![
+                "ssh-ed25519 AAAAC3Nz work@laptop".to_string(),
+                "ssh-rsa AAAAB3Nz home@laptop".to_string(),
+            ],
+        );
+        prompt.history_previous();
+        driver.app.prompt = Some(prompt);
+        driver.render().unwrap();
+
+        driver
+            .run_script(
+                "expect Copy public key\n\
+                 expect work@laptop\n\
+                 key up\n\
+                 expect home@laptop\n\
+                 key enter\n\
+                 expect Copied public key (SSH agent)\n",
+            )
+            .unwrap();
+        assert!(driver.app.prompt.is_none());
+    }
+
+    #[test]
+    fn test_jump_host_is_picked_from_the_list() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(
+            &dir,
+            "Host web\n  Hostname web.example.com\n\nHost db\n  Hostname db.example.com\n",
+        );
+
+        driver
+            .run_script(
+                "key J\n\
+                 expect -- JUMP HOST --\n\
+                 key enter\n\
+                 expect db cannot be its own jump host\n\
+                 reject -- JUMP HOST --\n\
+                 key J\n\
+                 key down\n\
+                 key esc\n\
+                 expect -- NORMAL --\n",
+            )
+            .unwrap();
+        assert_eq!(driver.app.jump_target, None);
+    }
+
+    #[test]
+    fn test_remote_command_prompt_recalls_history() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host web\n  Hostname web.example.com\n");
+        driver.app.state.record_remote_command("web", "htop");
+
+        driver
+            .run_script(
+                "key !\n\
+                 expect Run on connect\n\
+                 type uptime\n\
+                 expect uptime\n\
+                 key up\n\
+                 expect htop\n\
+                 reject uptime\n\
+                 key esc\n\
+                 reject Run on connect\n",
+            )
+            .unwrap();
+        assert!(driver.app.prompt.is_none());
+    }
+
+    #[test]
+    fn test_template_host_prompts_for_value() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host gw-%d\n  Hostname 10.20.0.%d\n");
+        driver.app.state.record_template_value("gw-%d", "12");
+
+        driver
+            .run_script(
+                "key enter\n\
+                 expect Template value\n\
+                 expect (enter) connect\n\
+                 key up\n\
+                 expect 12\n\
+                 key esc\n\
+                 key enter\n\
+                 type 1a\n\
+                 key enter\n\
+                 reject Template value\n\
+                 expect expects a number\n",
+            )
+            .unwrap();
+        assert!(driver.app.prompt.is_none());
+    }
+
+    #[test]
+    fn test_script_errors() {
+        let dir = TempDir::new().unwrap();
+        let mut driver = create_driver(&dir, "Host web\n  Hostname web.example.com\n");
+
+        let error = driver.run_script("expect nowhere.example.com").unwrap_err();
+        assert!(error.to_string().starts_with("line 1: expected"));
+
+        assert!(driver.run_script("key hyperspace").is_err());
+        assert!(driver.run_script("fly away").is_err());
+
+        driver.run_script("# quit\nkey q").unwrap();
+        assert!(driver.is_stopped());
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(
+            parse_key("enter"),
+            Some((KeyCode::Enter, KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key("ctrl+n"),
+            Some((KeyCode::Char('n'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse_key("G"),
+            Some((KeyCode::Char('G'), KeyModifiers::NONE))
+        );
+        assert_eq!(parse_key("F10"), Some((KeyCode::F(10), KeyModifiers::NONE)));
+        assert_eq!(parse_key("nope"), None);
+    }
+}
@@ -0,0 +1,96 @@
+//! Environment of a host (production, staging, development), told by its tags or its name, and
+//! the color it is drawn with in the session tabs, so that a production session stands out.
+
+use ratatui::style::{palette::tailwind, Color};
+use std::str::FromStr;
+
+use crate::ssh;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Environment {
+    Production,
+    Staging,
+    Development,
+}
+
+impl Environment {
+    /// Environment named by a tag or a part of a host name, e.g. `prod` or `stg`.
+    fn from_word(word: &str) -> Option<Self> {
+        match word.to_lowercase().as_str() {
+            "prod" | "production" | "prd" | "live" => Some(Environment::Production),
+            "staging" | "stage" | "stg" | "preprod" | "uat" => Some(Environment::Staging),
+            "dev" | "development" | "test" | "qa" | "sandbox" => Some(Environment::Development),
+            _ => None,
+        }
+    }
+
+    /// Environment of `host`, from its tags first, then from the parts of its name
+    /// (`db-prod-01` is a production host).
+    #[must_use]
+    pub fn of(host: &ssh::Host) -> Option<Self> {
+        host.tags
+            .iter()
+            .find_map(|tag| Self::from_word(tag))
+            .or_else(|| {
+                host.name
+                    .split(|c: char| !c.is_ascii_alphanumeric())
+                    .find_map(Self::from_word)
+            })
+    }
+
+    /// prod=red, staging=amber, dev=green.
+    #[must_use]
+    pub fn color(self) -> Color {
+        match self {
+            Environment::Production => tailwind::RED.c500,
+            Environment::Staging => tailwind::AMBER.c500,
+            Environment::Development => tailwind::GREEN.c500,
+        }
+    }
+}
+
+/// Color of the sessions on `host`: its `# sshs:color=<color>` directive, or the color of its
+/// environment.
+#[must_use]
+pub fn session_color(host: &ssh::Host) -> Option<Color> {
+    host.metadata_value("color")
+        .and_then(|color| Color::from_str(color).ok())
+        .or_else(|| Environment::of(host).map(Environment::color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(name: &str, tags: &[&str]) -> ssh::Host {
+        ssh::Host {
+            name: name.to_string(),
+            tags: tags.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_environment_of_host() {
+        assert_eq!(
+            Environment::of(&host("web", &["eu", "PROD"])),
+            Some(Environment::Production)
+        );
+        assert_eq!(
+            Environment::of(&host("db-stg-01", &["eu"])),
+            Some(Environment::Staging)
+        );
+        // Tags win over the name
+        assert_eq!(
+            Environment::of(&host("prod-mirror", &["dev"])),
+            Some(Environment::Development)
+        );
+        assert_eq!(Environment::of(&host("product-web", &[])), None);
+
+        assert_eq!(
+            session_color(&host("web-prod", &[])),
+            Some(tailwind::RED.c500)
+        );
+        assert_eq!(session_color(&host("web", &[])), None);
+    }
+}
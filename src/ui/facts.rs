@@ -0,0 +1,203 @@
+//! Facts about the hosts (distribution, cloud provider, virtualization), gathered over SSH with
+//! `I` and cached in the state file for a week. With the Fact tags setting they are added to
+//! the tags of the hosts, so that `tag:ubuntu` or `aws` finds them without tagging by hand.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::backend::ConnectionBackend;
+use crate::ssh;
+
+/// Seconds the facts of a host are kept before `I` gathers them again
+pub const FACTS_TTL_SECONDS: u64 = 7 * 86_400;
+
+/// Prints one `key=value` line per fact. The metadata services are only asked when the DMI
+/// vendor does not tell the cloud provider, each for a second at most.
+const SCRIPT: &str = r#"(. /etc/os-release 2>/dev/null && echo "distro=$ID")
+echo "kernel=$(uname -s)"
+echo "virt=$(systemd-detect-virt 2>/dev/null)"
+vendor=$(cat /sys/class/dmi/id/sys_vendor 2>/dev/null)
+echo "vendor=$vendor"
+echo "product=$(cat /sys/class/dmi/id/product_name 2>/dev/null)"
+case "$vendor" in
+  *Amazon*|*Google*|*Microsoft*|*DigitalOcean*|*Hetzner*) ;;
+  *) if command -v curl >/dev/null 2>&1; then
+       if curl -sf -m 1 -H 'Metadata-Flavor: Google' http://169.254.169.254/computeMetadata/v1/ >/dev/null 2>&1; then echo metadata=gcp
+       elif curl -sf -m 1 -H 'Metadata: true' 'http://169.254.169.254/metadata/instance?api-version=2021-02-01' >/dev/null 2>&1; then echo metadata=azure
+       elif curl -sf -m 1 -X PUT -H 'X-aws-ec2-metadata-token-ttl-seconds: 60' http://169.254.169.254/latest/api/token >/dev/null 2>&1; then echo metadata=aws
+       fi
+     fi ;;
+esac
+true"#;
+
+/// Facts gathered from a host.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostFacts {
+    /// Unix time they were gathered
+    pub time: u64,
+    /// e.g. `ubuntu`, or the kernel (`freebsd`) without `/etc/os-release`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distro: Option<String>,
+    /// e.g. `aws`, `gcp`, `azure`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloud: Option<String>,
+    /// As told by `systemd-detect-virt`, e.g. `kvm`, `lxc`, or `none` on bare metal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub virtualization: Option<String>,
+}
+
+impl HostFacts {
+    /// Tags derived from the facts, e.g. `ubuntu`, `aws`, `kvm`.
+    #[must_use]
+    pub fn tags(&self) -> Vec<String> {
+        let virtualization = self.virtualization.as_deref().map(|virtualization| {
+            if virtualization == "none" {
+                "bare-metal"
+            } else {
+                virtualization
+            }
+        });
+
+        [
+            self.distro.as_deref(),
+            self.cloud.as_deref(),
+            virtualization,
+        ]
+        .into_iter()
+        .flatten()
+        .map(tag)
+        .filter(|tag| !tag.is_empty())
+        .collect()
+    }
+
+    /// Whether the facts are older than [`FACTS_TTL_SECONDS`] at `now`.
+    #[must_use]
+    pub fn is_stale(&self, now: u64) -> bool {
+        now.saturating_sub(self.time) >= FACTS_TTL_SECONDS
+    }
+}
+
+/// `value` as a tag: lowercase, with the characters a tag cannot have replaced by `-`.
+fn tag(value: &str) -> String {
+    value
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Cloud provider told by the DMI vendor and product of the machine.
+fn cloud_of(vendor: &str, product: &str) -> Option<&'static str> {
+    let vendor = vendor.to_lowercase();
+    let product = product.to_lowercase();
+
+    if vendor.contains("amazon") || product.contains("amazon ec2") {
+        Some("aws")
+    } else if vendor.contains("google") {
+        Some("gcp")
+    } else if vendor.contains("microsoft") && product.contains("virtual machine") {
+        Some("azure")
+    } else if vendor.contains("digitalocean") {
+        Some("digitalocean")
+    } else if vendor.contains("hetzner") {
+        Some("hetzner")
+    } else if product.contains("openstack") {
+        Some("openstack")
+    } else {
+        None
+    }
+}
+
+/// Reads the output of the facts script, gathered at `time`.
+#[must_use]
+pub fn parse(output: &str, time: u64) -> HostFacts {
+    let values = output
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .filter(|(_, value)| !value.is_empty())
+        .collect::<BTreeMap<_, _>>();
+    let value = |key: &str| values.get(key).map(ToString::to_string);
+
+    HostFacts {
+        time,
+        distro: value("distro").or_else(|| value("kernel").map(|kernel| kernel.to_lowercase())),
+        cloud: cloud_of(
+            values.get("vendor").unwrap_or(&""),
+            values.get("product").unwrap_or(&""),
+        )
+        .map(str::to_string)
+        .or_else(|| value("metadata")),
+        virtualization: value("virt"),
+    }
+}
+
+/// Gathers the facts of `host`, at `now`.
+///
+/// # Errors
+///
+/// Will return `Err` if `host` cannot be reached without a prompt.
+pub fn gather(
+    backend: &dyn ConnectionBackend,
+    host: &ssh::Host,
+    now: u64,
+) -> anyhow::Result<HostFacts> {
+    Ok(parse(&backend.batch(host, SCRIPT)?, now))
+}
+
+/// Adds the tags derived from the facts to `hosts`, after their own.
+pub fn apply(hosts: &mut [ssh::Host], facts: &BTreeMap<String, HostFacts>) {
+    for host in hosts {
+        let Some(facts) = facts.get(&host.name) else {
+            continue;
+        };
+
+        for tag in facts.tags() {
+            if !host.tags.contains(&tag) {
+                host.tags.push(tag);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_facts_into_tags() {
+        let facts = parse(
+            "distro=ubuntu\nkernel=Linux\nvirt=kvm\nvendor=Amazon EC2\nproduct=m5.large\n",
+            1000,
+        );
+        assert_eq!(facts.cloud.as_deref(), Some("aws"));
+        assert_eq!(facts.tags(), ["ubuntu", "aws", "kvm"]);
+
+        // No os-release, no DMI: the kernel, and the metadata service
+        let facts = parse(
+            "kernel=FreeBSD\nvirt=\nvendor=\nproduct=\nmetadata=gcp\n",
+            1000,
+        );
+        assert_eq!(facts.tags(), ["freebsd", "gcp"]);
+
+        let facts = parse("distro=rhel\nvirt=none\nvendor=Dell Inc.\n", 1000);
+        assert_eq!(facts.tags(), ["rhel", "bare-metal"]);
+        assert!(!facts.is_stale(1000 + FACTS_TTL_SECONDS - 1));
+        assert!(facts.is_stale(1000 + FACTS_TTL_SECONDS));
+
+        let mut hosts = vec![ssh::Host {
+            name: "db".to_string(),
+            tags: vec!["prod".to_string(), "rhel".to_string()],
+            ..Default::default()
+        }];
+        apply(&mut hosts, &BTreeMap::from([("db".to_string(), facts)]));
+        assert_eq!(hosts[0].tags, ["prod", "rhel", "bare-metal"]);
+    }
+}
@@ -0,0 +1,472 @@
+//! Reachability of the hosts, shown as a dot in the host list: the SSH port of every host is
+//! connected to by a pool of worker threads, every `--health-check-interval` seconds or on `C`.
+//! Nothing is sent, the connections are closed once open.
+//!
+//! The answers are kept for a week in the state file, for the uptime and the sparkline of the
+//! last day shown in the host details.
+//!
+//! The list can also be cut down to the hosts in one state (`c`): reachable, unreachable, or
+//! with a session open in a tab.
+
+use std::collections::{HashMap, HashSet};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::network;
+use crate::ssh;
+
+/// Title of the column of the dots
+pub const COLUMN_TITLE: &str = "Up";
+
+/// Connections running at once
+const WORKERS: usize = 8;
+
+/// Time a destination has to accept the connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Connections slower than this show a yellow dot
+pub const SLOW_AFTER: Duration = Duration::from_millis(300);
+
+/// How often the results are looked for while checks run
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How a host answered the last check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    /// The SSH port accepted the connection, after this long
+    Reachable(Duration),
+    Unreachable,
+}
+
+impl Health {
+    #[must_use]
+    pub fn is_reachable(self) -> bool {
+        matches!(self, Health::Reachable(_))
+    }
+
+    /// Reachable, but slowly.
+    #[must_use]
+    pub fn is_slow(self) -> bool {
+        matches!(self, Health::Reachable(latency) if latency > SLOW_AFTER)
+    }
+}
+
+/// An answer counts for this long at most, the time sshs is not running is not counted
+const SAMPLE_VALIDITY: u64 = 20 * 60;
+
+/// Levels of the sparkline, from down to up
+pub(crate) const SPARKLINE_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Share of the time between `from` and `to` (unix times) the host was reachable, from the
+/// answers of its checks (`(time, reachable)`, oldest first). An answer holds until the next
+/// one, for [`SAMPLE_VALIDITY`] at most. `None` without any answer in that time.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn uptime(samples: &[(u64, bool)], from: u64, to: u64) -> Option<f64> {
+    let (mut up, mut total) = (0, 0);
+    for (index, &(time, reachable)) in samples.iter().enumerate() {
+        let next = samples.get(index + 1).map_or(to, |&(next, _)| next);
+        let end = next.min(to).min(time + SAMPLE_VALIDITY);
+        let start = time.max(from);
+        if end <= start {
+            continue;
+        }
+
+        total += end - start;
+        if reachable {
+            up += end - start;
+        }
+    }
+
+    (total > 0).then(|| up as f64 / total as f64)
+}
+
+/// Uptime of every hour of the last day before `now`, oldest first: a block as high as the
+/// uptime, or `·` for an hour without answers.
+#[must_use]
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+pub fn sparkline(samples: &[(u64, bool)], now: u64) -> String {
+    (0..24)
+        .rev()
+        .map(|hours_ago: u64| {
+            let to = now.saturating_sub(hours_ago * 3600);
+            match uptime(samples, to.saturating_sub(3600), to) {
+                Some(ratio) => {
+                    let last = SPARKLINE_LEVELS.len() - 1;
+                    SPARKLINE_LEVELS[(ratio * last as f64).round() as usize]
+                }
+                None => '·',
+            }
+        })
+        .collect()
+}
+
+/// Which hosts the list shows, by state, on top of the search.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StateFilter {
+    #[default]
+    All,
+    Reachable,
+    Unreachable,
+    WithSession,
+}
+
+impl StateFilter {
+    /// The filter after this one, back to [`StateFilter::All`] after the last.
+    #[must_use]
+    pub fn next(self) -> StateFilter {
+        match self {
+            StateFilter::All => StateFilter::Reachable,
+            StateFilter::Reachable => StateFilter::Unreachable,
+            StateFilter::Unreachable => StateFilter::WithSession,
+            StateFilter::WithSession => StateFilter::All,
+        }
+    }
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            StateFilter::All => "all hosts",
+            StateFilter::Reachable => "reachable hosts",
+            StateFilter::Unreachable => "unreachable hosts",
+            StateFilter::WithSession => "hosts with a session",
+        }
+    }
+
+    /// Whether a host answering the last check with `health` (`None` until checked), and with
+    /// a session open or not, is shown.
+    #[must_use]
+    pub fn matches(self, health: Option<Health>, has_session: bool) -> bool {
+        match self {
+            StateFilter::All => true,
+            StateFilter::Reachable => health.is_some_and(Health::is_reachable),
+            StateFilter::Unreachable => health == Some(Health::Unreachable),
+            StateFilter::WithSession => has_session,
+        }
+    }
+}
+
+/// Hosts in each state, among those matching the search.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateCounts {
+    pub reachable: usize,
+    pub unreachable: usize,
+    pub sessions: usize,
+}
+
+impl StateCounts {
+    pub fn add(&mut self, health: Option<Health>, has_session: bool) {
+        match health {
+            Some(Health::Reachable(_)) => self.reachable += 1,
+            Some(Health::Unreachable) => self.unreachable += 1,
+            None => {}
+        }
+        if has_session {
+            self.sessions += 1;
+        }
+    }
+
+    /// The counts, e.g. `12 up · 3 down · 1 session`.
+    #[must_use]
+    pub fn text(self) -> String {
+        let sessions = if self.sessions == 1 {
+            "session"
+        } else {
+            "sessions"
+        };
+        format!(
+            "{} up · {} down · {} {sessions}",
+            self.reachable, self.unreachable, self.sessions
+        )
+    }
+}
+
+/// Connects to `address:port`, and closes the connection right away.
+#[must_use]
+pub fn probe(address: &str, port: u16) -> Health {
+    let start = Instant::now();
+    let reachable = (address, port)
+        .to_socket_addrs()
+        .is_ok_and(|mut addresses| {
+            addresses.any(|address| TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).is_ok())
+        });
+
+    if reachable {
+        Health::Reachable(start.elapsed())
+    } else {
+        Health::Unreachable
+    }
+}
+
+/// A host to check
+struct Job {
+    host: String,
+    address: String,
+    port: u16,
+}
+
+/// The answer of a host, for the timeline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub host: String,
+    /// `destination:port`
+    pub check: String,
+    pub health: Health,
+}
+
+/// Checks the hosts in the background, and keeps their last answers.
+pub struct HealthChecker {
+    /// Time between two rounds of checks, only on request when `None`
+    interval: Option<Duration>,
+    started_at: Option<Instant>,
+    health: HashMap<String, Health>,
+    pending: HashSet<String>,
+    jobs: Sender<Job>,
+    results: Receiver<CheckResult>,
+}
+
+impl HealthChecker {
+    /// Starts the workers, which wait for the first round of checks.
+    #[must_use]
+    pub fn new(interval: Option<Duration>) -> HealthChecker {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let (sender, results) = mpsc::channel();
+
+        // The workers stop with the checker, once the queue is closed
+        let queue = Arc::new(Mutex::new(queue));
+        for _ in 0..WORKERS {
+            let queue = Arc::clone(&queue);
+            let sender = sender.clone();
+            std::thread::spawn(move || loop {
+                let Ok(job) = queue
+                    .lock()
+                    .map_err(drop)
+                    .and_then(|queue| queue.recv().map_err(drop))
+                else {
+                    return;
+                };
+                let health = probe(&job.address, job.port);
+                let check = if job.address.contains(':') {
+                    format!("[{}]:{}", job.address, job.port)
+                } else {
+                    format!("{}:{}", job.address, job.port)
+                };
+                if sender
+                    .send(CheckResult {
+                        host: job.host,
+                        check,
+                        health,
+                    })
+                    .is_err()
+                {
+                    return;
+                }
+            });
+        }
+
+        HealthChecker {
+            interval,
+            started_at: None,
+            health: HashMap::new(),
+            pending: HashSet::new(),
+            jobs,
+            results,
+        }
+    }
+
+    /// Time between two rounds of checks, only on request when `None`.
+    #[must_use]
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    pub fn set_interval(&mut self, interval: Option<Duration>) {
+        self.interval = interval;
+    }
+
+    /// Whether the next round of checks is due at `now`.
+    #[must_use]
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.interval.is_some_and(|interval| {
+            self.started_at
+                .is_none_or(|started_at| now.duration_since(started_at) >= interval)
+        })
+    }
+
+    /// Starts checking `hosts`, except those still being checked. The hosts reached through a
+    /// proxy cannot be checked from here and are left out, as are the templates. Returns the
+    /// number of hosts queued.
+    pub fn refresh(&mut self, hosts: &[ssh::Host], now: Instant) -> usize {
+        self.started_at = Some(now);
+
+        let mut queued = 0;
+        for host in hosts.iter().filter(|host| !host.is_template()) {
+            let Some((address, port)) = network::direct_destination(host) else {
+                continue;
+            };
+            if !self.pending.insert(host.name.clone()) {
+                continue;
+            }
+            let job = Job {
+                host: host.name.clone(),
+                address,
+                port,
+            };
+            if self.jobs.send(job).is_ok() {
+                queued += 1;
+            }
+        }
+
+        queued
+    }
+
+    /// Takes the answers received since the last call.
+    pub fn poll(&mut self) -> Vec<CheckResult> {
+        let results: Vec<CheckResult> = self.results.try_iter().collect();
+        for result in &results {
+            self.pending.remove(&result.host);
+            self.health.insert(result.host.clone(), result.health);
+        }
+
+        results
+    }
+
+    /// Last answer of `host_name`, `None` until it answers once.
+    #[must_use]
+    pub fn get(&self, host_name: &str) -> Option<Health> {
+        self.health.get(host_name).copied()
+    }
+
+    /// Whether `host_name` is being checked.
+    #[must_use]
+    pub fn is_pending(&self, host_name: &str) -> bool {
+        self.pending.contains(host_name)
+    }
+
+    /// Time until the answers are looked for, or the next round starts.
+    #[must_use]
+    pub fn wake_in(&self, now: Instant) -> Option<Duration> {
+        if !self.pending.is_empty() {
+            return Some(POLL_INTERVAL);
+        }
+
+        let interval = self.interval?;
+        Some(self.started_at.map_or(Duration::ZERO, |started_at| {
+            interval.saturating_sub(now.duration_since(started_at))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_checks_run_in_the_background() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap().port();
+        let closed = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let host = |name: &str, port: u16| ssh::Host {
+            name: name.to_string(),
+            destination: "127.0.0.1".to_string(),
+            port: Some(port.to_string()),
+            ..Default::default()
+        };
+        let hosts = [
+            host("web", open),
+            host("db", closed),
+            ssh::Host {
+                proxy_jump: Some("bastion".to_string()),
+                ..host("behind-bastion", open)
+            },
+        ];
+
+        let start = Instant::now();
+        let mut checker = HealthChecker::new(Some(Duration::from_secs(90)));
+        assert!(checker.is_due(start));
+        assert_eq!(checker.refresh(&hosts, start), 2);
+        assert!(!checker.is_due(start));
+        assert!(checker.is_pending("web"));
+        // Hosts still being checked are not queued again
+        assert_eq!(checker.refresh(&hosts, start), 0);
+
+        let mut results = Vec::new();
+        while results.len() < 2 && start.elapsed() < Duration::from_secs(10) {
+            results.extend(checker.poll());
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(checker.get("web").is_some_and(Health::is_reachable));
+        assert_eq!(checker.get("db"), Some(Health::Unreachable));
+        assert_eq!(checker.get("behind-bastion"), None);
+        assert!(results
+            .iter()
+            .any(|result| result.check == format!("127.0.0.1:{closed}")));
+        assert_eq!(
+            checker.wake_in(start + Duration::from_secs(45)),
+            Some(Duration::from_secs(45))
+        );
+    }
+
+    #[test]
+    fn test_uptime() {
+        let hour = 3600;
+        let now = 100 * hour;
+        let samples = [
+            (now - 2 * hour, true),
+            (now - 2 * hour + 600, false),
+            (now - 2 * hour + 1200, true),
+            (now - hour + 1800, true),
+        ];
+
+        // 30 minutes up and 10 down, then sshs was not running until the last answer
+        let ratio = uptime(&samples, now - 2 * hour, now - hour).unwrap();
+        assert!((ratio - 0.75).abs() < 1e-9);
+        assert_eq!(uptime(&samples, now - hour, now), Some(1.0));
+        assert_eq!(uptime(&samples, 0, hour), None);
+        assert_eq!(uptime(&[], 0, now), None);
+
+        let sparkline = sparkline(&samples, now);
+        assert_eq!(sparkline.chars().count(), 24);
+        assert!(sparkline.ends_with("▆█"));
+        assert!(sparkline.starts_with("······"));
+    }
+
+    #[test]
+    fn test_state_filter() {
+        let up = Some(Health::Reachable(Duration::from_millis(20)));
+        let down = Some(Health::Unreachable);
+
+        assert!(StateFilter::All.matches(None, false));
+        assert!(StateFilter::Reachable.matches(up, false));
+        assert!(!StateFilter::Reachable.matches(None, true));
+        assert!(StateFilter::Unreachable.matches(down, false));
+        assert!(!StateFilter::Unreachable.matches(None, false));
+        assert!(StateFilter::WithSession.matches(down, true));
+        assert!(!StateFilter::WithSession.matches(up, false));
+
+        let mut filter = StateFilter::All;
+        for _ in 0..4 {
+            filter = filter.next();
+        }
+        assert_eq!(filter, StateFilter::All);
+
+        let mut counts = StateCounts::default();
+        counts.add(up, true);
+        counts.add(down, false);
+        counts.add(down, false);
+        counts.add(None, false);
+        assert_eq!(counts.text(), "1 up · 2 down · 1 session");
+    }
+}
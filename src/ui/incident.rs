@@ -0,0 +1,97 @@
+//! Incident mode (`x`), a triage board for outages: only the unreachable hosts are listed, the
+//! longest without a connection first, the health checks run every [`CHECK_INTERVAL`], and a
+//! Since column tells when each host last changed state. Leaving the mode restores the filter
+//! and the checks as they were.
+
+use std::time::Duration;
+
+use super::backups::format_age;
+use super::health::StateFilter;
+use super::recent;
+
+use crate::ssh;
+use crate::state::State;
+
+/// Title of the column showing when each host last changed state
+pub const COLUMN_TITLE: &str = "Since";
+
+/// Time between two rounds of health checks during an incident
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What incident mode changed, put back when it ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Incident {
+    pub state_filter: StateFilter,
+    /// Interval of the health checks, `None` when they were not running
+    pub check_interval: Option<Option<Duration>>,
+}
+
+/// Unix time `host_name` entered its current state: the first of its last health check
+/// answers that agree.
+#[must_use]
+pub fn state_changed_at(state: &State, host_name: &str) -> Option<u64> {
+    let samples = state.health_samples.get(host_name)?;
+    let &(_, current) = samples.last()?;
+
+    samples
+        .iter()
+        .rev()
+        .take_while(|&&(_, reachable)| reachable == current)
+        .last()
+        .map(|&(time, _)| time)
+}
+
+/// e.g. `5m ago`, `-` for a host never checked.
+#[must_use]
+pub fn format_since(time: Option<u64>, now: u64) -> String {
+    time.map_or("-".to_string(), |time| {
+        format_age(Duration::from_secs(now.saturating_sub(time)))
+    })
+}
+
+/// Sorts `hosts` by their last connection, the hosts never connected to first, then the
+/// longest without a connection.
+pub fn sort_hosts(hosts: &mut [ssh::Host], state: &State) {
+    hosts.sort_by_cached_key(|host| recent::last_used(state, host));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{HostEvent, HostEventKind};
+
+    #[test]
+    fn test_state_changed_at() {
+        let mut state = State::default();
+        state.health_samples.insert(
+            "web".to_string(),
+            vec![(100, true), (700, true), (800, false), (1400, false)],
+        );
+        assert_eq!(state_changed_at(&state, "web"), Some(800));
+        assert_eq!(state_changed_at(&state, "db"), None);
+        assert_eq!(format_since(Some(800), 800 + 7200), "2h ago");
+        assert_eq!(format_since(None, 800), "-");
+    }
+
+    #[test]
+    fn test_sort_hosts() {
+        let mut state = State::default();
+        for (name, time) in [("web", 200), ("db", 100)] {
+            state.record_host_event(
+                name,
+                HostEvent {
+                    time,
+                    kind: HostEventKind::Connection,
+                    detail: String::new(),
+                },
+            );
+        }
+        let mut hosts = ["web", "db", "cache"].map(|name| ssh::Host {
+            name: name.to_string(),
+            ..Default::default()
+        });
+
+        sort_hosts(&mut hosts, &state);
+        assert_eq!(hosts.map(|host| host.name), ["cache", "db", "web"]);
+    }
+}
@@ -0,0 +1,178 @@
+//! Keys of the actions of the host list: the ones shown by the menu bar ([`Action::key_hint`]),
+//! each replaceable from the `[keys.actions]` section of the configuration file. Navigation,
+//! marks and the saved search digits keep their keys, as do the overlays.
+
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+use super::actions::Action;
+
+/// Actions of the host list by their name in the configuration file
+pub const ACTIONS: &[(&str, Action)] = &[
+    ("connect-socks", Action::ConnectSocks),
+    ("connect-agent", Action::ConnectAgent),
+    ("connect-with-command", Action::ConnectWithCommand),
+    ("connect-jump", Action::ConnectJump),
+    ("background-socks", Action::BackgroundSocks),
+    ("background-tunnels", Action::BackgroundTunnels),
+    ("forward", Action::Forward),
+    ("saved-forwards", Action::SavedForwards),
+    ("browse-files", Action::BrowseFiles),
+    ("session-log", Action::SessionLog),
+    ("add-host", Action::AddHost),
+    ("edit-host", Action::EditHost),
+    ("delete-host", Action::DeleteHost),
+    ("expired-hosts", Action::ExpiredHosts),
+    ("unused-hosts", Action::UnusedHosts),
+    ("edit-tags", Action::EditTags),
+    ("host-details", Action::HostDetails),
+    ("timeline", Action::Timeline),
+    ("authorized-keys", Action::AuthorizedKeys),
+    ("copy-public-key", Action::CopyPublicKey),
+    ("copy-config-block", Action::CopyConfigBlock),
+    ("batch-run", Action::BatchRun),
+    ("port-check", Action::PortCheck),
+    ("health-check", Action::HealthCheck),
+    ("state-filter", Action::StateFilter),
+    ("incident-mode", Action::IncidentMode),
+    ("gather-facts", Action::GatherFacts),
+    ("web-console", Action::WebConsole),
+    ("open-links", Action::OpenLinks),
+    ("backups", Action::Backups),
+    ("settings", Action::Settings),
+    ("search", Action::Search),
+    ("save-search", Action::SaveSearch),
+    ("saved-searches", Action::SavedSearches),
+    ("toggle-group", Action::ToggleGroup),
+    ("recent-hosts", Action::RecentHosts),
+    ("presentation", Action::Presentation),
+    ("whats-new", Action::WhatsNew),
+    ("quit", Action::Quit),
+];
+
+/// Keys of the host list that cannot be given to an action: navigation, undo, marks and the
+/// saved searches
+const FIXED_KEYS: &str = "jkhlgGuv 123456789";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    keys: Vec<(Action, char)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            keys: ACTIONS
+                .iter()
+                .filter_map(|&(_, action)| {
+                    let mut hint = action.key_hint().chars();
+                    match (hint.next(), hint.next()) {
+                        (Some(key), None) => Some((action, key)),
+                        _ => None,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// The default keys, with `bindings` (action name to key) over them.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an action is unknown, or a key is fixed or bound to two actions.
+    pub fn new(bindings: &BTreeMap<String, char>) -> Result<Keymap> {
+        let mut keymap = Keymap::default();
+        for (name, &key) in bindings {
+            let Some(&(_, action)) = ACTIONS.iter().find(|(known, _)| known == name) else {
+                bail!("Unknown action '{name}'");
+            };
+            if FIXED_KEYS.contains(key) {
+                bail!("'{key}' of {name} is a fixed key of the host list");
+            }
+            if let Some(entry) = keymap.keys.iter_mut().find(|(bound, _)| *bound == action) {
+                entry.1 = key;
+            }
+        }
+
+        for (index, &(action, key)) in keymap.keys.iter().enumerate() {
+            if let Some(&(other, _)) = keymap.keys[index + 1..]
+                .iter()
+                .find(|(_, other_key)| *other_key == key)
+            {
+                bail!(
+                    "'{key}' is bound to both {} and {}",
+                    name(action),
+                    name(other)
+                );
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    /// Action of `key` in the host list.
+    #[must_use]
+    pub fn action(&self, key: char) -> Option<Action> {
+        self.keys
+            .iter()
+            .find(|(_, bound)| *bound == key)
+            .map(|&(action, _)| action)
+    }
+
+    /// Key of `action`, as shown next to its label.
+    #[must_use]
+    pub fn hint(&self, action: Action) -> String {
+        self.keys
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .map_or_else(|| action.key_hint().to_string(), |(_, key)| key.to_string())
+    }
+}
+
+fn name(action: Action) -> &'static str {
+    ACTIONS
+        .iter()
+        .find(|(_, known)| *known == action)
+        .map_or("", |(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keymap() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.action('D'), Some(Action::ConnectSocks));
+        assert_eq!(keymap.hint(Action::Quit), "q");
+        assert_eq!(keymap.hint(Action::Connect), "Enter");
+
+        let bindings = |pairs: &[(&str, char)]| {
+            pairs
+                .iter()
+                .map(|&(name, key)| (name.to_string(), key))
+                .collect::<BTreeMap<_, _>>()
+        };
+        // Swapping two keys
+        let keymap =
+            Keymap::new(&bindings(&[("connect-socks", 's'), ("state-filter", 'D')])).unwrap();
+        assert_eq!(keymap.action('s'), Some(Action::ConnectSocks));
+        assert_eq!(keymap.action('D'), Some(Action::StateFilter));
+        assert_eq!(keymap.action('c'), None);
+        assert_eq!(keymap.hint(Action::ConnectSocks), "s");
+
+        for (pairs, error) in [
+            (&[("connect", 'c')][..], "Unknown action 'connect'"),
+            (&[("quit", 'j')], "fixed key"),
+            (
+                &[("quit", 'D')],
+                "'D' is bound to both connect-socks and quit",
+            ),
+        ] {
+            let message = Keymap::new(&bindings(pairs)).unwrap_err().to_string();
+            assert!(message.contains(error), "{message}");
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Dedicated configuration file for hosts created by sshs (`~/.ssh/config.d/sshs.conf` by
+//! default), pulled into the main SSH configuration through an `Include` directive.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use super::backups;
+use crate::ssh_config::parser::resolve_include_path;
+
+/// Header written at the top of a freshly created managed file
+const MANAGED_FILE_HEADER: &str = "# Hosts managed by sshs\n";
+
+/// Checks whether the main configuration already has an `Include` directive covering the
+/// managed file (globs such as `Include config.d/*` count), directly or through the files it
+/// includes. A missing main configuration includes nothing.
+///
+/// # Errors
+///
+/// Will return `Err` if the main configuration cannot be read.
+pub fn is_included(config_path: &str, managed_path: &str) -> Result<bool> {
+    let content = match fs::read_to_string(config_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(anyhow!("Failed to read SSH config file: {e}")),
+    };
+    let managed_path = shellexpand::tilde(managed_path).to_string();
+    let mut visited = fs::canonicalize(config_path).into_iter().collect();
+
+    Ok(includes(&content, &managed_path, &mut visited))
+}
+
+/// Whether the `Include` directives of `content`, or of the files they pull in, cover
+/// `managed_path`. `visited` holds the files already read, so that include loops end. Included
+/// files that cannot be read are skipped, as the parser does.
+fn includes(content: &str, managed_path: &str, visited: &mut HashSet<PathBuf>) -> bool {
+    include_patterns(content).any(|include| {
+        let include = resolve_include_path(include);
+        if glob::Pattern::new(&include).is_ok_and(|pattern| pattern.matches(managed_path)) {
+            return true;
+        }
+
+        glob::glob(&include)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .any(|path| {
+                fs::canonicalize(&path).is_ok_and(|path| visited.insert(path))
+                    && fs::read_to_string(&path)
+                        .is_ok_and(|content| includes(&content, managed_path, visited))
+            })
+    })
+}
+
+/// Arguments of the `Include` directives of `content`.
+fn include_patterns(content: &str) -> impl Iterator<Item = &str> {
+    content.lines().flat_map(|line| {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        line.split_once([' ', '\t', '='])
+            .filter(|(key, _)| key.eq_ignore_ascii_case("include"))
+            .map_or("", |(_, value)| value)
+            .split_whitespace()
+    })
+}
+
+/// Prepends an `Include` directive for the managed file to the main configuration. The
+/// directive must come before the first `Host` block, otherwise it only applies to that block.
+/// A missing main configuration (and its directory) is created with only the directive.
+///
+/// # Errors
+///
+/// Will return `Err` if the main configuration cannot be backed up or written.
+pub fn add_include(config_path: &str, managed_path: &str) -> Result<()> {
+    let content = match fs::read_to_string(config_path) {
+        Ok(content) => {
+            // Create a backup of the original config file
+            let backup_path = format!("{config_path}.bak");
+            fs::copy(config_path, &backup_path)
+                .map_err(|e| anyhow!("Failed to create backup of SSH config file: {e}"))?;
+
+            content
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            if let Some(parent) = Path::new(config_path).parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| anyhow!("Failed to create '{}': {e}", parent.display()))?;
+            }
+
+            String::new()
+        }
+        Err(e) => return Err(anyhow!("Failed to read SSH config file: {e}")),
+    };
+
+    backups::write_config(
+        Path::new(config_path),
+        &format!("Include {managed_path}\n\n{content}"),
+    )
+    .map_err(|e| anyhow!("Failed to write updated SSH config file: {e}"))?;
+
+    Ok(())
+}
+
+/// Creates the managed file (and its directory) when it does not exist yet.
+///
+/// # Errors
+///
+/// Will return `Err` if the file or its directory cannot be created.
+pub fn ensure_exists(managed_path: &str) -> Result<()> {
+    let path = shellexpand::tilde(managed_path).to_string();
+    let path = Path::new(&path);
+
+    if path.exists() {
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| anyhow!("Failed to create '{}': {e}", parent.display()))?;
+    }
+    fs::write(path, MANAGED_FILE_HEADER)
+        .map_err(|e| anyhow!("Failed to create '{}': {e}", path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_include_is_added_once() {
+        let dir = TempDir::new().unwrap();
+        let config = dir.path().join("config");
+        let managed = dir.path().join("config.d").join("sshs.conf");
+        fs::write(&config, "Host web\n  Hostname web.example.com\n").unwrap();
+
+        let config = config.to_str().unwrap();
+        let managed = managed.to_str().unwrap();
+
+        assert!(!is_included(config, managed).unwrap());
+
+        add_include(config, managed).unwrap();
+        assert!(is_included(config, managed).unwrap());
+
+        let content = fs::read_to_string(config).unwrap();
+        assert!(content.starts_with(&format!("Include {managed}\n")));
+        assert!(content.contains("Host web"));
+    }
+
+    #[test]
+    fn test_include_creates_missing_config() {
+        let dir = TempDir::new().unwrap();
+        let config = dir.path().join(".ssh").join("config");
+        let managed = dir.path().join(".ssh").join("config.d").join("sshs.conf");
+
+        let config = config.to_str().unwrap();
+        let managed = managed.to_str().unwrap();
+
+        assert!(!is_included(config, managed).unwrap());
+
+        add_include(config, managed).unwrap();
+        assert_eq!(
+            fs::read_to_string(config).unwrap(),
+            format!("Include {managed}\n\n")
+        );
+        assert!(is_included(config, managed).unwrap());
+    }
+
+    #[test]
+    fn test_glob_include_covers_managed_file() {
+        let dir = TempDir::new().unwrap();
+        let config = dir.path().join("config");
+        fs::write(
+            &config,
+            format!("Include {}/config.d/*\n", dir.path().display()),
+        )
+        .unwrap();
+
+        let managed = dir.path().join("config.d").join("sshs.conf");
+        assert!(is_included(config.to_str().unwrap(), managed.to_str().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_nested_include_covers_managed_file() {
+        let dir = TempDir::new().unwrap();
+        let config = dir.path().join("config");
+        let common = dir.path().join("common");
+        let managed = dir.path().join("config.d").join("sshs.conf");
+        fs::write(&config, format!("Include {}\n", common.display())).unwrap();
+        // An include loop ends
+        fs::write(&common, format!("Include {}\n", config.display())).unwrap();
+
+        let config = config.to_str().unwrap();
+        let managed = managed.to_str().unwrap();
+        assert!(!is_included(config, managed).unwrap());
+
+        fs::write(&common, format!("Include {managed}\n")).unwrap();
+        assert!(is_included(config, managed).unwrap());
+    }
+
+    #[test]
+    fn test_ensure_exists_creates_directory() {
+        let dir = TempDir::new().unwrap();
+        let managed = dir.path().join("config.d").join("sshs.conf");
+
+        ensure_exists(managed.to_str().unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(&managed).unwrap(), MANAGED_FILE_HEADER);
+
+        // An existing file is left untouched
+        fs::write(&managed, "Host kept\n").unwrap();
+        ensure_exists(managed.to_str().unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(&managed).unwrap(), "Host kept\n");
+    }
+}
@@ -1,7 +1,40 @@
+pub mod actions;
 pub mod app;
+pub mod audit_log;
+pub mod authorized_keys;
+pub mod background_tunnels;
+pub mod backups;
+pub mod batch_run;
+pub mod bulk_tags;
+pub mod capabilities;
+pub mod clipboard;
+pub mod config_errors;
+pub mod dns;
+pub mod driver;
+pub mod environment;
+pub mod facts;
 pub mod form;
+pub mod health;
+pub mod incident;
+pub mod keymap;
+pub mod managed_config;
+pub mod network;
+pub mod port_check;
+pub mod prompt;
+pub mod recent;
 pub mod render;
+pub mod session;
+pub mod session_log;
+pub mod session_summary;
+pub mod settings_panel;
+pub mod sftp;
 pub mod tabs;
+pub mod timeline;
+pub mod triggers;
+pub mod tutorial;
+pub mod unused_hosts;
+pub mod updates;
 pub mod utils;
+pub mod web_console;
 
 pub use app::{App, AppConfig, AppKeyAction};
@@ -0,0 +1,397 @@
+//! Network changes of roaming laptops (another Wi-Fi, the VPN coming up or down), and the
+//! fallback of the hosts: with `# sshs:fallback=web-bastion`, `web-bastion` is connected to
+//! instead of `web` while the destination of `web` cannot be reached from the current network.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use super::health;
+use crate::ssh;
+
+/// How often the routes are looked at
+pub const NETWORK_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Metadata key naming the host connected to when the destination cannot be reached
+pub const FALLBACK_METADATA_KEY: &str = "fallback";
+
+/// How often the results of the checks are looked for
+const CHECK_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The way out of the machine: the default route, and the local address of the route to the
+/// internet, which a VPN routing everything changes without touching the default route.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Network {
+    pub interface: Option<String>,
+    pub gateway: Option<Ipv4Addr>,
+    pub local_address: Option<IpAddr>,
+}
+
+impl Network {
+    /// Looks at the routes, nothing is sent.
+    #[must_use]
+    pub fn current() -> Network {
+        let (interface, gateway) = std::fs::read_to_string("/proc/net/route")
+            .ok()
+            .and_then(|table| default_route(&table))
+            .unzip();
+
+        Network {
+            interface,
+            gateway,
+            local_address: local_address(),
+        }
+    }
+
+    /// e.g. `wlan0 (192.168.1.23)`, `offline`.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        match (&self.interface, self.local_address) {
+            (Some(interface), Some(address)) => format!("{interface} ({address})"),
+            (Some(interface), None) => interface.clone(),
+            (None, Some(address)) => address.to_string(),
+            (None, None) => "offline".to_string(),
+        }
+    }
+}
+
+/// Interface and gateway of the default route with the lowest metric, from the table of
+/// `/proc/net/route`.
+fn default_route(table: &str) -> Option<(String, Ipv4Addr)> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.get(1) != Some(&"00000000") {
+                return None;
+            }
+            // In network order, read as a little-endian number
+            let gateway = u32::from_str_radix(fields.get(2)?, 16).ok()?;
+            let metric = fields.get(6)?.parse::<u32>().ok()?;
+
+            Some((
+                metric,
+                fields[0].to_string(),
+                Ipv4Addr::from(gateway.to_le_bytes()),
+            ))
+        })
+        .min_by_key(|(metric, _, _)| *metric)
+        .map(|(_, interface, gateway)| (interface, gateway))
+}
+
+/// Local address of the route to the internet. Connecting a UDP socket only picks the route.
+fn local_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9)).ok()?;
+
+    socket.local_addr().ok().map(|address| address.ip())
+}
+
+/// A host with a fallback, and where to reach them. A destination behind a proxy cannot be
+/// checked from here and is `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallbackCheck {
+    pub host: String,
+    pub destination: (String, u16),
+    pub fallback: String,
+    pub fallback_destination: Option<(String, u16)>,
+}
+
+/// The hosts with a fallback among `hosts`. The hosts reached through a proxy are left out, as
+/// are the fallbacks that are not hosts.
+#[must_use]
+pub fn fallback_checks(hosts: &[ssh::Host]) -> Vec<FallbackCheck> {
+    hosts
+        .iter()
+        .filter_map(|host| {
+            let fallback = host.metadata_value(FALLBACK_METADATA_KEY)?;
+            let fallback = hosts.iter().find(|other| other.name == fallback)?;
+
+            Some(FallbackCheck {
+                host: host.name.clone(),
+                destination: direct_destination(host)?,
+                fallback: fallback.name.clone(),
+                fallback_destination: direct_destination(fallback),
+            })
+        })
+        .collect()
+}
+
+/// Address and port `ssh` connects to, `None` for the hosts reached through a proxy.
+#[must_use]
+pub fn direct_destination(host: &ssh::Host) -> Option<(String, u16)> {
+    if host.proxy_command.is_some() || host.proxy_jump.as_ref().is_some_and(|jump| jump != "none") {
+        return None;
+    }
+    let port = host
+        .port
+        .as_deref()
+        .map_or(Some(22), |port| port.parse().ok())?;
+
+    Some((ssh::unbracket(&host.destination).to_string(), port))
+}
+
+/// How a host with a fallback is reached from the current network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallbackResult {
+    pub host: String,
+    /// `destination:port`, the health check recorded in the timeline
+    pub check: String,
+    pub reachable: bool,
+    /// The fallback connected to instead, when the destination cannot be reached and the
+    /// fallback can (or is behind a proxy)
+    pub fallback: Option<String>,
+}
+
+/// Checks the destination of the host, then the one of its fallback if needed.
+#[must_use]
+pub fn run_check(check: &FallbackCheck) -> FallbackResult {
+    let (address, port) = &check.destination;
+    let reachable = can_connect(address, *port);
+    let fallback = (!reachable
+        && check
+            .fallback_destination
+            .as_ref()
+            .is_none_or(|(address, port)| can_connect(address, *port)))
+    .then(|| check.fallback.clone());
+
+    FallbackResult {
+        host: check.host.clone(),
+        check: if address.contains(':') {
+            format!("[{address}]:{port}")
+        } else {
+            format!("{address}:{port}")
+        },
+        reachable,
+        fallback,
+    }
+}
+
+fn can_connect(address: &str, port: u16) -> bool {
+    health::probe(address, port).is_reachable()
+}
+
+/// The checks of a network, done in the background.
+struct RunningCheck {
+    /// The network before, `None` for the first check
+    changed_from: Option<Network>,
+    receiver: Receiver<Vec<FallbackResult>>,
+}
+
+/// What the checks made after a network change found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckOutcome {
+    pub results: Vec<FallbackResult>,
+    /// The toast summarizing the change, `None` for the first check
+    pub summary: Option<String>,
+}
+
+/// Follows the network and how the hosts with a fallback are reached from it.
+pub struct NetworkWatch {
+    pub network: Network,
+    checked_at: Instant,
+    /// Hosts connected to through their fallback, with the fallback
+    fallbacks: BTreeMap<String, String>,
+    check: Option<RunningCheck>,
+}
+
+impl NetworkWatch {
+    /// Looks at the network, and starts checking the hosts with a fallback from it. `None`
+    /// when no host has a fallback: the network is not followed.
+    #[must_use]
+    pub fn start(hosts: &[ssh::Host]) -> Option<NetworkWatch> {
+        let checks = fallback_checks(hosts);
+        if checks.is_empty() {
+            return None;
+        }
+
+        let mut watch = NetworkWatch {
+            network: Network::current(),
+            checked_at: Instant::now(),
+            fallbacks: BTreeMap::new(),
+            check: None,
+        };
+        watch.start_check(checks, None);
+
+        Some(watch)
+    }
+
+    /// Fallback `host_name` is connected to through, if its destination cannot be reached.
+    #[must_use]
+    pub fn fallback_of(&self, host_name: &str) -> Option<&str> {
+        self.fallbacks.get(host_name).map(String::as_str)
+    }
+
+    #[must_use]
+    pub fn is_due(&self, now: Instant) -> bool {
+        now.duration_since(self.checked_at) >= NETWORK_POLL_INTERVAL
+    }
+
+    /// Takes `network` as the current one. When it changed, starts the checks of `hosts` and
+    /// returns `true`.
+    pub fn update(&mut self, network: Network, hosts: &[ssh::Host], now: Instant) -> bool {
+        self.checked_at = now;
+        if network == self.network {
+            return false;
+        }
+
+        let previous = std::mem::replace(&mut self.network, network);
+        self.start_check(fallback_checks(hosts), Some(previous));
+        true
+    }
+
+    fn start_check(&mut self, checks: Vec<FallbackCheck>, changed_from: Option<Network>) {
+        let (sender, receiver) = mpsc::channel();
+        // A check stuck on the resolver is left behind, it does not keep sshs from exiting
+        std::thread::spawn(move || {
+            let results = std::thread::scope(|scope| {
+                let handles: Vec<_> = checks
+                    .iter()
+                    .map(|check| scope.spawn(|| run_check(check)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .filter_map(|handle| handle.join().ok())
+                    .collect()
+            });
+            let _ = sender.send(results);
+        });
+
+        // The summary of a change still being checked compares with the network before it
+        let changed_from = match self.check.take() {
+            Some(running) if changed_from.is_some() => running.changed_from.or(changed_from),
+            _ => changed_from,
+        };
+        self.check = Some(RunningCheck {
+            changed_from,
+            receiver,
+        });
+    }
+
+    /// Takes the results of the checks once done, and switches the hosts to or from their
+    /// fallback.
+    pub fn poll(&mut self) -> Option<CheckOutcome> {
+        let results = self.check.as_ref()?.receiver.try_recv().ok()?;
+        let changed_from = self.check.take().and_then(|check| check.changed_from);
+
+        let previous = std::mem::take(&mut self.fallbacks);
+        self.fallbacks = results
+            .iter()
+            .filter_map(|result| Some((result.host.clone(), result.fallback.clone()?)))
+            .collect();
+        let summary = changed_from.map(|_| self.summary(&previous));
+
+        Some(CheckOutcome { results, summary })
+    }
+
+    /// e.g. `Network changed to tun0 (10.8.0.2): web through web-vpn, db back on its
+    /// destination`.
+    fn summary(&self, previous: &BTreeMap<String, String>) -> String {
+        let mut summary = format!("Network changed to {}", self.network.describe());
+        let mut changes = Vec::new();
+        for (host, fallback) in &self.fallbacks {
+            if previous.get(host) != Some(fallback) {
+                changes.push(format!("{host} through {fallback}"));
+            }
+        }
+        for host in previous.keys() {
+            if !self.fallbacks.contains_key(host) {
+                changes.push(format!("{host} back on its destination"));
+            }
+        }
+        if !changes.is_empty() {
+            let _ = write!(summary, ": {}", changes.join(", "));
+        }
+
+        summary
+    }
+
+    /// Time until the routes are looked at again, or the results of the checks are.
+    #[must_use]
+    pub fn wake_in(&self, now: Instant) -> Duration {
+        if self.check.is_some() {
+            return CHECK_POLL_INTERVAL;
+        }
+        NETWORK_POLL_INTERVAL.saturating_sub(now.duration_since(self.checked_at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_default_route() {
+        let table = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\n\
+                     wlan0\t0000A8C0\t00000000\t0001\t0\t0\t600\t00FFFFFF\n\
+                     wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\n\
+                     eth0\t00000000\t01000A0A\t0003\t0\t0\t100\t00000000\n";
+        assert_eq!(
+            default_route(table),
+            Some(("eth0".to_string(), Ipv4Addr::new(10, 10, 0, 1)))
+        );
+        assert_eq!(default_route("Iface\tDestination\tGateway\n"), None);
+    }
+
+    #[test]
+    fn test_hosts_switch_to_their_fallback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap().port();
+        let closed = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let host = |name: &str, port: u16, fallback: Option<&str>| ssh::Host {
+            name: name.to_string(),
+            destination: "127.0.0.1".to_string(),
+            port: Some(port.to_string()),
+            metadata: fallback
+                .map(|fallback| (FALLBACK_METADATA_KEY.to_string(), fallback.to_string()))
+                .into_iter()
+                .collect::<BTreeMap<_, _>>(),
+            ..Default::default()
+        };
+        let hosts = [
+            host("web", closed, Some("web-vpn")),
+            host("web-vpn", open, None),
+            host("db", open, Some("db-vpn")),
+            host("db-vpn", open, None),
+            host("orphan", closed, Some("missing")),
+        ];
+
+        let checks = fallback_checks(&hosts);
+        assert_eq!(
+            checks
+                .iter()
+                .map(|check| check.host.as_str())
+                .collect::<Vec<_>>(),
+            ["web", "db"]
+        );
+        let results: Vec<_> = checks.iter().map(run_check).collect();
+        assert_eq!(results[0].fallback.as_deref(), Some("web-vpn"));
+        assert_eq!(results[0].check, format!("127.0.0.1:{closed}"));
+        assert!(!results[0].reachable);
+        assert_eq!(results[1].fallback, None);
+
+        let mut watch = NetworkWatch {
+            network: Network::default(),
+            checked_at: Instant::now(),
+            fallbacks: BTreeMap::from([("db".to_string(), "db-vpn".to_string())]),
+            check: None,
+        };
+        let previous = watch.fallbacks.clone();
+        watch.network.interface = Some("tun0".to_string());
+        watch.fallbacks = BTreeMap::from([("web".to_string(), "web-vpn".to_string())]);
+        assert_eq!(
+            watch.summary(&previous),
+            "Network changed to tun0: web through web-vpn, db back on its destination"
+        );
+        assert_eq!(watch.fallback_of("web"), Some("web-vpn"));
+    }
+}
@@ -0,0 +1,180 @@
+//! Checks whether a host can reach a TCP port elsewhere ("can web reach the database?"), by
+//! running `nc` (or bash's `/dev/tcp`) on the host.
+
+use anyhow::{anyhow, bail};
+use std::fmt;
+
+use crate::backend::ConnectionBackend;
+use crate::ssh;
+
+/// Seconds the host waits for the connection before reporting the port as closed
+const CONNECT_TIMEOUT: u32 = 5;
+
+/// Address and port to check, as typed in the prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub address: String,
+    pub port: u16,
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.address.contains(':') {
+            write!(f, "[{}]:{}", self.address, self.port)
+        } else {
+            write!(f, "{}:{}", self.address, self.port)
+        }
+    }
+}
+
+impl Target {
+    /// Parses `address:port`, `[ipv6]:port` or `address port`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the port is missing or invalid, or if the address has characters
+    /// a host name or an IP address cannot have.
+    pub fn parse(input: &str) -> anyhow::Result<Target> {
+        let input = input.trim();
+        let (address, port) = if let Some(rest) = input.strip_prefix('[') {
+            let (address, port) = rest
+                .split_once(']')
+                .ok_or_else(|| anyhow!("Missing ']' after the IPv6 address"))?;
+            (address, port.trim_start_matches(':'))
+        } else if let Some((address, port)) = input.split_once(char::is_whitespace) {
+            (address, port)
+        } else if input.matches(':').count() == 1 {
+            input.split_once(':').unwrap_or_default()
+        } else {
+            bail!("Expected address:port");
+        };
+
+        let address = address.trim();
+        if address.is_empty()
+            || !address
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':'))
+        {
+            bail!("Invalid address '{address}'");
+        }
+        let port = port
+            .trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|port| *port != 0)
+            .ok_or_else(|| anyhow!("Invalid port '{}'", port.trim()))?;
+
+        Ok(Target {
+            address: address.to_string(),
+            port,
+        })
+    }
+
+    /// Script printing `open`, `closed` or `unsupported` (neither `nc` nor `bash` on the host).
+    /// It always succeeds, so that a failure means the host itself could not be reached.
+    fn script(&self) -> String {
+        let Target { address, port } = self;
+
+        format!(
+            "if command -v nc >/dev/null 2>&1; then \
+             nc -z -w {CONNECT_TIMEOUT} {address} {port} >/dev/null 2>&1 && echo open || echo closed; \
+             elif command -v bash >/dev/null 2>&1; then \
+             timeout {CONNECT_TIMEOUT} bash -c 'exec 3<>/dev/tcp/{address}/{port}' >/dev/null 2>&1 \
+             && echo open || echo closed; \
+             else echo unsupported; fi"
+        )
+    }
+}
+
+/// Outcome of a port check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortStatus {
+    Open,
+    Closed,
+    /// The host has no tool to check with
+    Unsupported,
+}
+
+/// Checks from `host` whether `target` accepts TCP connections.
+///
+/// # Errors
+///
+/// Will return `Err` if `host` cannot be reached without a prompt.
+pub fn check(
+    backend: &dyn ConnectionBackend,
+    host: &ssh::Host,
+    target: &Target,
+) -> anyhow::Result<PortStatus> {
+    let output = backend.batch(host, &target.script())?;
+
+    match output.trim() {
+        "open" => Ok(PortStatus::Open),
+        "closed" => Ok(PortStatus::Closed),
+        "unsupported" => Ok(PortStatus::Unsupported),
+        other => Err(anyhow!("Unexpected output from {}: {other}", host.name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Backend answering every script with the same output
+    struct FakeBackend(&'static str);
+
+    impl ConnectionBackend for FakeBackend {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn interactive(
+            &self,
+            _host: &ssh::Host,
+            _options: &ssh::ConnectOptions,
+        ) -> anyhow::Result<Option<i32>> {
+            Ok(Some(0))
+        }
+
+        fn batch(&self, _host: &ssh::Host, _script: &str) -> anyhow::Result<String> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    #[test]
+    fn test_parse_targets() {
+        let target = Target::parse("db.internal:5432").unwrap();
+        assert_eq!(target.address, "db.internal");
+        assert_eq!(target.port, 5432);
+        assert_eq!(
+            Target::parse(" 10.0.0.5 443 ").unwrap().to_string(),
+            "10.0.0.5:443"
+        );
+        assert_eq!(
+            Target::parse("[2001:db8::1]:22").unwrap().to_string(),
+            "[2001:db8::1]:22"
+        );
+
+        assert!(Target::parse("db.internal").is_err());
+        assert!(Target::parse("db.internal:0").is_err());
+        assert!(Target::parse("db;reboot:22").is_err());
+        assert!(Target::parse("2001:db8::1:22").is_err());
+    }
+
+    #[test]
+    fn test_check_reads_status() {
+        let host = ssh::Host::default();
+        let target = Target::parse("db:5432").unwrap();
+        assert!(target.script().contains("nc -z -w 5 db 5432"));
+        assert!(target.script().contains("/dev/tcp/db/5432"));
+
+        assert_eq!(
+            check(&FakeBackend("open\n"), &host, &target).unwrap(),
+            PortStatus::Open
+        );
+        assert_eq!(
+            check(&FakeBackend("closed\n"), &host, &target).unwrap(),
+            PortStatus::Closed
+        );
+        assert!(check(&FakeBackend("motd\n"), &host, &target).is_err());
+    }
+}
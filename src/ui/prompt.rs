@@ -0,0 +1,201 @@
+use crossterm::event::Event;
+use tui_input::{backend::crossterm::EventHandler, Input};
+
+use super::batch_run::Mode;
+use crate::ssh;
+
+/// What a submitted prompt is used for
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromptKind {
+    /// One-shot command run on the selected host right after connecting
+    RemoteCommand,
+    /// Value of the placeholder of the selected template host, the connection continues with
+    /// these options once it is known
+    TemplateValue(ssh::ConnectOptions),
+    /// Name of the current session tab, empty for the automatic name
+    TabName,
+    /// Host name of the current session tab, typed to unlock it
+    UnlockTab,
+    /// Name under which the open tabs are saved
+    SaveWorkspace,
+    /// Name of the saved workspace replacing the open tabs
+    OpenWorkspace,
+    /// Name under which the current search is saved
+    SaveSearch,
+    /// Name of the saved search to apply
+    OpenSearch,
+    /// `address:port` the selected host should be able to reach
+    PortCheck,
+    /// Forward started in the background through the selected host (`L 8080:db:5432`)
+    Forward,
+    /// URL of the web console of the selected host to open
+    WebConsole,
+    /// Link of the selected host to open
+    Link,
+    /// Key of the SSH agent copied to the clipboard
+    AgentKey,
+    /// File the unused hosts are exported to, as CSV
+    ExportUnusedHosts,
+    /// Tags added (`+tag`) and removed (`-tag`) on every listed host
+    BulkTags,
+    /// Command run on the marked hosts, Tab switches how
+    BatchCommand(Mode),
+    /// File the output of the current session is exported to, with its colors when set (Tab
+    /// switches), to the clipboard when empty
+    ExportScrollback(bool),
+}
+
+impl PromptKind {
+    #[must_use]
+    pub fn title(&self) -> &'static str {
+        match self {
+            PromptKind::RemoteCommand => "Run on connect",
+            PromptKind::TemplateValue(_) => "Template value",
+            PromptKind::TabName => "Rename tab",
+            PromptKind::UnlockTab => "Unlock tab (type its host name)",
+            PromptKind::SaveWorkspace => "Save workspace",
+            PromptKind::OpenWorkspace => "Open workspace",
+            PromptKind::SaveSearch => "Save search as",
+            PromptKind::OpenSearch => "Saved search (↑/↓: saved searches)",
+            PromptKind::PortCheck => "Check port from host (address:port)",
+            PromptKind::Forward => {
+                "Forward (L 8080:db:5432, R 9000:localhost:3000, D 1080; ↑/↓: saved)"
+            }
+            PromptKind::WebConsole => "Open web console (↑/↓: consoles of the host)",
+            PromptKind::Link => "Open link (↑/↓: links of the host)",
+            PromptKind::AgentKey => "Copy public key (↑/↓: keys of the SSH agent)",
+            PromptKind::ExportUnusedHosts => "Export the unused hosts to (CSV)",
+            PromptKind::BulkTags => "Tags of the listed hosts (+add -remove)",
+            PromptKind::BatchCommand(Mode::Sequential) => {
+                "Run on the marked hosts one by one (Tab: in parallel)"
+            }
+            PromptKind::BatchCommand(Mode::Parallel) => {
+                "Run on the marked hosts in parallel (Tab: one by one)"
+            }
+            PromptKind::ExportScrollback(false) => {
+                "Export the session output as text (empty: clipboard, Tab: with colors)"
+            }
+            PromptKind::ExportScrollback(true) => {
+                "Export the session output with colors (empty: clipboard, Tab: as text)"
+            }
+        }
+    }
+
+    /// Action shown for the Enter key
+    #[must_use]
+    pub fn submit_label(&self) -> &'static str {
+        match self {
+            PromptKind::RemoteCommand => "run",
+            PromptKind::TemplateValue(_) => "connect",
+            PromptKind::TabName => "rename",
+            PromptKind::UnlockTab => "unlock",
+            PromptKind::SaveWorkspace => "save",
+            PromptKind::OpenWorkspace => "open",
+            PromptKind::SaveSearch => "save",
+            PromptKind::OpenSearch => "search",
+            PromptKind::PortCheck => "check",
+            PromptKind::Forward => "start",
+            PromptKind::WebConsole => "open",
+            PromptKind::Link => "open",
+            PromptKind::AgentKey => "copy",
+            PromptKind::ExportUnusedHosts => "export",
+            PromptKind::BulkTags => "preview",
+            PromptKind::BatchCommand(_) => "run",
+            PromptKind::ExportScrollback(_) => "export",
+        }
+    }
+}
+
+/// Single-line text prompt with history recall (↑/↓)
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub input: Input,
+    /// Previous values, most recent first
+    history: Vec<String>,
+    history_index: Option<usize>,
+}
+
+impl Prompt {
+    #[must_use]
+    pub fn new(kind: PromptKind, history: Vec<String>) -> Self {
+        Self {
+            kind,
+            input: Input::default(),
+            history,
+            history_index: None,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        self.input.handle_event(event);
+    }
+
+    /// Recalls the previous (older) history entry.
+    pub fn history_previous(&mut self) {
+        let index = match self.history_index {
+            Some(index) if index + 1 < self.history.len() => index + 1,
+            Some(index) => index,
+            None if self.history.is_empty() => return,
+            None => 0,
+        };
+
+        self.history_index = Some(index);
+        self.input = Input::from(self.history[index].clone());
+    }
+
+    /// Recalls the next (more recent) history entry, back to an empty input.
+    pub fn history_next(&mut self) {
+        match self.history_index {
+            Some(0) | None => {
+                self.history_index = None;
+                self.input = Input::default();
+            }
+            Some(index) => {
+                self.history_index = Some(index - 1);
+                self.input = Input::from(self.history[index - 1].clone());
+            }
+        }
+    }
+
+    /// Returns the trimmed value, `None` when empty.
+    #[must_use]
+    pub fn value(&self) -> Option<String> {
+        let value = self.input.value().trim();
+        (!value.is_empty()).then(|| value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_navigation() {
+        let mut prompt = Prompt::new(
+            PromptKind::RemoteCommand,
+            vec!["htop".to_string(), "df -h".to_string()],
+        );
+        assert_eq!(prompt.value(), None);
+
+        prompt.history_previous();
+        assert_eq!(prompt.value().as_deref(), Some("htop"));
+
+        prompt.history_previous();
+        prompt.history_previous();
+        assert_eq!(prompt.value().as_deref(), Some("df -h"));
+
+        prompt.history_next();
+        assert_eq!(prompt.value().as_deref(), Some("htop"));
+
+        prompt.history_next();
+        assert_eq!(prompt.value(), None);
+    }
+
+    #[test]
+    fn test_empty_history() {
+        let mut prompt = Prompt::new(PromptKind::RemoteCommand, Vec::new());
+        prompt.history_previous();
+        prompt.history_next();
+        assert_eq!(prompt.value(), None);
+    }
+}
@@ -0,0 +1,111 @@
+//! Recent view (`R`): the hosts sorted by their last connection, most recent first, with a Last
+//! used column. Connections are the ones recorded in the state file for the timelines, with
+//! their time, duration and exit code.
+
+use std::cmp::Reverse;
+use std::time::Duration;
+
+use super::backups::format_age;
+
+use crate::certificate;
+use crate::ssh::{self, format_date};
+use crate::state::{HostEventKind, State};
+
+/// Title of the column showing when each host was last connected to
+pub const COLUMN_TITLE: &str = "Last used";
+
+/// Unix time of the last connection to `host`. Alias rows count the connections made by their
+/// alias.
+#[must_use]
+pub fn last_used(state: &State, host: &ssh::Host) -> Option<u64> {
+    let name = host.connect_as.as_deref().unwrap_or(&host.name);
+
+    state
+        .host_events
+        .get(name)?
+        .iter()
+        .rev()
+        .find(|event| event.kind == HostEventKind::Connection)
+        .map(|event| event.time)
+}
+
+/// Sorts `hosts` by their last connection, most recent first. Hosts never connected to keep
+/// their order, last.
+pub fn sort_hosts(hosts: &mut [ssh::Host], state: &State) {
+    hosts.sort_by_cached_key(|host| Reverse(last_used(state, host)));
+}
+
+/// Current Unix time.
+#[must_use]
+pub fn now() -> u64 {
+    u64::try_from(certificate::now()).unwrap_or_default()
+}
+
+/// e.g. `5m ago`, `3h ago`, `12d ago`, then the date past a month.
+#[must_use]
+pub fn format_last_used(time: Option<u64>, now: u64) -> String {
+    let Some(time) = time else {
+        return "never".to_string();
+    };
+
+    let elapsed = now.saturating_sub(time);
+    if elapsed < 30 * 86_400 {
+        format_age(Duration::from_secs(elapsed))
+    } else {
+        format_date(i64::try_from(time / 86_400).unwrap_or(i64::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::HostEvent;
+
+    fn host(name: &str) -> ssh::Host {
+        ssh::Host {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn record(state: &mut State, host: &str, time: u64, kind: HostEventKind) {
+        state.record_host_event(
+            host,
+            HostEvent {
+                time,
+                kind,
+                detail: "session of 5s, exit code 0".to_string(),
+            },
+        );
+    }
+
+    #[test]
+    fn test_sort_hosts_by_last_connection() {
+        let mut state = State::default();
+        record(&mut state, "web", 1000, HostEventKind::Connection);
+        record(&mut state, "db", 2000, HostEventKind::Connection);
+        // Failed connections do not count
+        record(&mut state, "web", 3000, HostEventKind::Failure);
+        record(&mut state, "api", 3000, HostEventKind::Failure);
+
+        let mut hosts = vec![host("api"), host("web"), host("gw"), host("db")];
+        sort_hosts(&mut hosts, &state);
+        assert_eq!(
+            hosts.iter().map(|h| h.name.as_str()).collect::<Vec<_>>(),
+            ["db", "web", "api", "gw"]
+        );
+
+        let now = 2000 + 3 * 3600;
+        assert_eq!(
+            format_last_used(last_used(&state, &hosts[0]), now),
+            "3h ago"
+        );
+        assert_eq!(format_last_used(last_used(&state, &hosts[2]), now), "never");
+        assert_eq!(format_last_used(Some(now - 90), now), "1m ago");
+        assert_eq!(format_last_used(Some(0), 3 * 86_400 + 5), "3d ago");
+        assert_eq!(
+            format_last_used(Some(1_792_159_380), 1_800_000_000),
+            "2026-10-16"
+        );
+    }
+}
@@ -1,18 +1,53 @@
+use itertools::Itertools;
 use ratatui::{
     layout::Margin,
     prelude::*,
     text::{Line, Span, Text},
     widgets::{
         Block, BorderType, Borders, Cell, Clear, HighlightSpacing, Padding, Paragraph, Row, Table,
+        Wrap,
     },
 };
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::rc::Rc;
+use std::str::FromStr;
 use style::palette::tailwind;
+use tui_input::Input;
 
+use super::actions::{Action, LeaderGroup, MenuState, LEADER_GROUPS, MENUS};
 use super::app::{
-    App, CURSOR_HORIZONTAL_PADDING, CURSOR_VERTICAL_OFFSET, FOOTER_HEIGHT,
-    SEARCHBAR_HORIZONTAL_PADDING, SEARCH_BAR_HEIGHT, TABLE_HEADER_HEIGHT, TABLE_MIN_HEIGHT,
+    App, AGENT_FORWARDING_ACTION, CURSOR_HORIZONTAL_PADDING, CURSOR_VERTICAL_OFFSET, FOOTER_HEIGHT,
+    PRESENTATION_MASK, SEARCHBAR_HORIZONTAL_PADDING, SEARCH_BAR_HEIGHT, SYSTEM_EDIT_ACTION,
+    TABLE_HEADER_HEIGHT, TABLE_MIN_HEIGHT, UNDO_DELETE_DELAY, UNUSED_HOSTS_ACTION,
 };
-use super::form::FormState;
+use super::audit_log::AuditLogView;
+use super::authorized_keys::AuthorizedKeysView;
+use super::background_tunnels::TunnelsView;
+use super::backups::{format_age, BackupsView, DiffLine};
+use super::batch_run::BatchRun;
+use super::config_errors::ConfigErrorsView;
+use super::dns::DnsCache;
+use super::environment::{session_color, Environment};
+use super::form::{
+    AddHostForm, FormState, FIELD_OPTIONS, OPTIONS_FIELD, PROXY_JUMP_FIELD, TAGS_FIELD,
+};
+use super::health::{self, HealthChecker, StateFilter};
+use super::incident;
+use super::prompt::Prompt;
+use super::recent;
+use super::settings_panel::{SettingsPanel, FIELDS};
+use super::sftp::{self, SftpView, Side};
+use super::timeline::{self, TimelineView};
+use super::triggers;
+use super::tutorial::Tutorial;
+use super::updates::{Release, UPGRADE_COMMAND};
+use crate::certificate::CertificateStatus;
+use crate::matcher::{FieldMatch, SearchQuery};
+use crate::settings::{Column, GroupMode};
+use crate::ssh;
+use crate::ssh_config::{host::Origin, EntryType};
+use crate::state::{HostEventKind, HEALTH_HISTORY_SECONDS};
 
 /// Render the UI
 pub fn ui(f: &mut Frame, app: &mut App) {
@@ -21,27 +56,60 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         FormState::Active => render_form_ui(f, app),
         FormState::Confirming => render_confirmation_ui(f, app),
     }
+
+    app.config.capabilities.degrade(f.buffer_mut());
 }
 
-/// Render the main UI
-fn render_main_ui(f: &mut Frame, app: &mut App) {
-    // Create layout based on whether tabs exist
-    let rects = if app.tab_manager.has_sessions() {
+/// Thick red border framing the whole interface while typing into a production session is one
+/// key away
+fn prod_border(app: &App) -> Option<Block<'static>> {
+    let current = app.tab_manager.current_session()?;
+    (app.settings.prod_border && Environment::of(&current.host) == Some(Environment::Production))
+        .then(|| {
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(Environment::Production.color()))
+                .border_type(BorderType::Thick)
+        })
+}
+
+/// The tab bar when `with_tabs`, the search bar, the hosts and the footer.
+fn main_layout(area: Rect, with_tabs: bool) -> Rc<[Rect]> {
+    if with_tabs {
         Layout::vertical([
             Constraint::Length(1), // Tab bar
             Constraint::Length(SEARCH_BAR_HEIGHT),
             Constraint::Min(TABLE_MIN_HEIGHT),
             Constraint::Length(FOOTER_HEIGHT),
         ])
-        .split(f.area())
+        .split(area)
     } else {
         Layout::vertical([
             Constraint::Length(SEARCH_BAR_HEIGHT),
             Constraint::Min(TABLE_MIN_HEIGHT),
             Constraint::Length(FOOTER_HEIGHT),
         ])
-        .split(f.area())
-    };
+        .split(area)
+    }
+}
+
+/// Where the shown tab is drawn in a frame of `area`: in place of the search bar and the hosts.
+#[must_use]
+pub fn session_area(app: &App, area: Rect) -> Rect {
+    let area = prod_border(app).map_or(area, |border| border.inner(area));
+    let rects = main_layout(area, true);
+    rects[1].union(rects[2])
+}
+
+/// Render the main UI
+fn render_main_ui(f: &mut Frame, app: &mut App) {
+    let mut area = f.area();
+    if let Some(border) = prod_border(app) {
+        area = border.inner(area);
+        f.render_widget(border, f.area());
+    }
+
+    let rects = main_layout(area, app.tab_manager.has_sessions());
 
     let mut rect_index = 0;
 
@@ -55,9 +123,91 @@ fn render_main_ui(f: &mut Frame, app: &mut App) {
     render_table(f, app, rects[rect_index + 1]);
     render_footer_with_mode(f, app, rects[rect_index + 2]);
 
-    // Show feedback message if present
+    // The shown tab takes the place of the search bar and the hosts
+    if app.session_shown {
+        render_session(f, app, session_area(app, f.area()));
+    }
+
+    // The SFTP browser takes the place of the search bar and the hosts
+    if let Some(view) = &app.sftp {
+        render_sftp(f, app, view, rects[rect_index].union(rects[rect_index + 1]));
+    }
+
+    // As do the results of a batch run
+    if let Some(run) = &app.batch_run {
+        render_batch_run(f, app, run, rects[rect_index].union(rects[rect_index + 1]));
+    }
+
+    if let Some(view) = &app.backups {
+        render_backups(f, app, view);
+    }
+
+    if let Some(view) = &app.audit_log {
+        render_audit_log(f, app, view);
+    }
+
+    if let Some(view) = &app.timeline {
+        render_timeline(f, app, view);
+    }
+
+    if let Some(view) = &app.background_tunnels {
+        render_background_tunnels(f, app, view);
+    }
+
+    if let Some(release) = app.available_update.as_ref().filter(|_| app.show_changelog) {
+        render_changelog(f, app, release);
+    }
+
+    if let Some(panel) = app.settings_panel {
+        render_settings_panel(f, app, panel);
+    }
+
+    if app.show_details {
+        render_host_details(f, app);
+    }
+
+    if let Some(view) = &app.authorized_keys {
+        render_authorized_keys(f, app, view);
+    }
+
+    if let Some(view) = &app.config_errors {
+        render_config_errors(f, app, view);
+    }
+
+    if let Some(tutorial) = &app.tutorial {
+        render_tutorial(f, app, tutorial, rects[rect_index + 2]);
+    }
+
+    if let Some(typed) = &app.leader_keys {
+        render_leader_popup(f, app, typed, rects[rect_index + 1]);
+    }
+
+    if let Some(text) = &app.pending_paste {
+        render_paste_preview(f, app, text);
+    }
+
+    // Show feedback message if present, or the undo toast of a host deleted with `dd`
     if let Some(message) = &app.feedback_message {
         render_feedback(f, message, app.is_feedback_error);
+    } else if let Some(deleted) = &app.deleted_host {
+        let seconds_left = UNDO_DELETE_DELAY
+            .saturating_sub(deleted.deleted_at.elapsed())
+            .as_secs()
+            + 1;
+        render_feedback(
+            f,
+            &format!("Deleted '{}' - (u) undo ({seconds_left}s)", deleted.name),
+            false,
+        );
+    }
+
+    if let Some(menu) = app.menu {
+        render_menu_bar(f, app, menu);
+    }
+
+    if let Some(prompt) = &app.prompt {
+        render_prompt(f, app, prompt);
+        return;
     }
 
     // Show cursor only in search mode
@@ -70,6 +220,1124 @@ fn render_main_ui(f: &mut Frame, app: &mut App) {
     }
 }
 
+/// Render the current step of the tutorial at the bottom of the table, above the footer
+fn render_tutorial(f: &mut Frame, app: &App, tutorial: &Tutorial, footer: Rect) {
+    let height = 3;
+    let area = Rect::new(
+        footer.x + 1,
+        footer.y.saturating_sub(height + 1),
+        footer.width.saturating_sub(2),
+        height,
+    );
+
+    let block = Block::default()
+        .title(tutorial.title())
+        .borders(Borders::ALL)
+        .border_style(Style::new().fg(if tutorial.is_finished() {
+            tailwind::GREEN.c500
+        } else {
+            app.palette.c400
+        }))
+        .border_type(BorderType::Rounded);
+
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(tutorial.instruction()).block(block), area);
+}
+
+/// Render a single-line prompt in a centered box, with the cursor in its input
+fn render_prompt(f: &mut Frame, app: &App, prompt: &Prompt) {
+    let area = f.area();
+
+    let width = 60.min(area.width);
+    let height = 4;
+    let prompt_area = Rect::new(
+        (area.width.saturating_sub(width)) / 2,
+        (area.height.saturating_sub(height)) / 2,
+        width,
+        height.min(area.height),
+    );
+
+    let block = Block::default()
+        .title(prompt.kind.title())
+        .title_bottom(
+            Line::from(format!(
+                "(enter) {} | (↑/↓) history | (esc) cancel",
+                prompt.kind.submit_label()
+            ))
+            .centered(),
+        )
+        .borders(Borders::ALL)
+        .border_style(Style::new().fg(app.palette.c400))
+        .border_type(BorderType::Rounded);
+
+    let inner = prompt_area.inner(Margin::new(1, 1));
+    let scroll = prompt
+        .input
+        .visual_scroll(usize::from(inner.width.saturating_sub(1)));
+
+    f.render_widget(Clear, prompt_area);
+    f.render_widget(block, prompt_area);
+    f.render_widget(
+        Paragraph::new(prompt.input.value())
+            .style(Style::new().fg(Color::White))
+            .scroll((0, u16::try_from(scroll).unwrap_or_default())),
+        inner,
+    );
+
+    let cursor = prompt.input.visual_cursor().saturating_sub(scroll);
+    f.set_cursor_position((inner.x + u16::try_from(cursor).unwrap_or_default(), inner.y));
+}
+
+/// Render the backups view: the list of backups, or the diff of the selected one
+/// Warning shown next to the name of hosts whose certificate is expired or expiring soon.
+fn certificate_badge(status: Option<CertificateStatus>) -> Option<Span<'static>> {
+    match status? {
+        CertificateStatus::Expired => Some(Span::styled(" ⚠", Style::new().fg(tailwind::RED.c500))),
+        CertificateStatus::ExpiringSoon(_) => {
+            Some(Span::styled(" ⚠", Style::new().fg(tailwind::AMBER.c400)))
+        }
+        CertificateStatus::Valid => None,
+    }
+}
+
+fn format_remaining(seconds: i64) -> String {
+    if seconds < 86_400 {
+        format!("{}h", seconds / 3600)
+    } else {
+        format!("{}d", seconds / 86_400)
+    }
+}
+
+/// Addresses the destination of `host` resolved to, `None` when it is an IP address.
+fn address_line(dns: &DnsCache, host: &ssh::Host, label_style: Style) -> Option<Line<'static>> {
+    let destination = ssh::unbracket(&host.destination);
+    if destination.parse::<std::net::IpAddr>().is_ok() {
+        return None;
+    }
+
+    let mut spans = vec![Span::styled(format!("{:<13}", "Address"), label_style)];
+    match dns.get(destination) {
+        _ if host.proxy_command.is_some() => {
+            spans.push(Span::styled("resolved by the ProxyCommand", label_style));
+        }
+        None if dns.is_pending(destination) => spans.push(Span::raw("resolving…")),
+        None => spans.push(Span::styled("not resolved", label_style)),
+        Some(resolution) => {
+            spans.push(match &resolution.addresses {
+                Ok(addresses) => Span::raw(addresses.iter().join(", ")),
+                Err(e) => Span::styled(e.clone(), Style::new().fg(tailwind::RED.c400)),
+            });
+            if resolution.is_stale(std::time::Instant::now()) {
+                spans.push(Span::styled(
+                    format!(
+                        "  ⚠ stale, resolved {}",
+                        format_age(resolution.resolved_at.elapsed())
+                    ),
+                    Style::new().fg(tailwind::AMBER.c400),
+                ));
+            }
+        }
+    }
+
+    Some(Line::from(spans))
+}
+
+/// Render the details of the selected host, including its certificate
+fn render_host_details(f: &mut Frame, app: &App) {
+    let Some(host) = app
+        .table_state
+        .selected()
+        .filter(|selected| *selected < app.hosts.len())
+        .map(|selected| &app.hosts[selected])
+    else {
+        return;
+    };
+
+    let label_style = Style::new().fg(app.palette.c300);
+    let origins = host.option_origins();
+    let field = |label: &str, value: String| {
+        let mut spans = vec![
+            Span::styled(format!("{label:<13}"), label_style),
+            Span::raw(value),
+        ];
+
+        // Where the option comes from, the selected one can be opened in the editor
+        if let Some(index) = origins.iter().position(|(option, _)| *option == label) {
+            let mut style = Style::new().fg(app.palette.c400);
+            if index == app.details_option {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            spans.push(Span::raw("  "));
+            spans.push(Span::styled(describe_origin(origins[index].1, host), style));
+        }
+
+        Line::from(spans)
+    };
+
+    let user = host.user.clone().unwrap_or_else(|| {
+        let inferred = app.inferred_user().unwrap_or_default();
+        format!("{inferred} (not configured)")
+    });
+    // The presentation mode masks what tells where the host is
+    let masked = |value: String| {
+        if app.presentation {
+            PRESENTATION_MASK.to_string()
+        } else {
+            value
+        }
+    };
+    let mut lines = vec![
+        field("Destination", masked(host.destination.clone())),
+        field("User", masked(user)),
+        field(
+            "Port",
+            masked(host.port.clone().unwrap_or_else(|| "22".to_string())),
+        ),
+        field("Aliases", host.aliases.clone()),
+        field("Tags", host.tags.join(", ")),
+        field(
+            "Description",
+            host.metadata_value("description")
+                .unwrap_or_default()
+                .to_string(),
+        ),
+        field(
+            "Config file",
+            host.source_file
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+        ),
+        field("Identity", host.identity_file.clone().unwrap_or_default()),
+    ];
+    if let Some(proxy_command) = host.expanded_proxy_command() {
+        lines.push(field("Proxy", masked(proxy_command)));
+    }
+    // `ssh` refuses to connect with a token it cannot expand
+    for (option, token) in host.unexpandable_tokens() {
+        lines.push(Line::styled(
+            format!("{:<13}⚠ {token} in {option} cannot be expanded", ""),
+            Style::new().fg(tailwind::RED.c400),
+        ));
+    }
+    lines.push(Line::default());
+    if let Some(dns) = app.dns.as_ref().filter(|_| !app.presentation) {
+        if let Some(address) = address_line(dns, host, label_style) {
+            lines.insert(1, address);
+        }
+    }
+
+    match app.certificate_of(host) {
+        None => lines.push(field("Certificate", "none".to_string())),
+        Some((path, Err(e))) => {
+            lines.push(field("Certificate", path));
+            lines.push(Line::styled(
+                format!("{:<13}{e}", ""),
+                Style::new().fg(tailwind::RED.c400),
+            ));
+        }
+        Some((path, Ok(certificate))) => {
+            let (status, color) = match certificate.status(crate::certificate::now()) {
+                CertificateStatus::Valid => ("valid".to_string(), tailwind::GREEN.c400),
+                CertificateStatus::ExpiringSoon(seconds) => (
+                    format!("⚠ expires in {}", format_remaining(seconds)),
+                    tailwind::AMBER.c400,
+                ),
+                CertificateStatus::Expired => ("⚠ expired".to_string(), tailwind::RED.c500),
+            };
+
+            lines.push(field("Certificate", path));
+            lines.push(field("Key ID", certificate.key_id.clone()));
+            lines.push(field("Principals", certificate.principals.join(", ")));
+            lines.push(field("Valid", certificate.validity.clone()));
+            lines.push(Line::from(vec![
+                Span::styled(format!("{:<13}", "Status"), label_style),
+                Span::styled(status, Style::new().fg(color).add_modifier(Modifier::BOLD)),
+            ]));
+        }
+    }
+
+    // Availability, from the health checks of the last week
+    if let Some(samples) = app.state.health_samples.get(&host.name) {
+        let now = u64::try_from(crate::certificate::now()).unwrap_or_default();
+        let percent = |seconds: u64| {
+            health::uptime(samples, now.saturating_sub(seconds), now)
+                .map_or("-".to_string(), |ratio| format!("{:.1}%", ratio * 100.0))
+        };
+        lines.push(Line::default());
+        lines.push(field(
+            "Uptime",
+            format!(
+                "{} last day · {} last week",
+                percent(24 * 3600),
+                percent(HEALTH_HISTORY_SECONDS)
+            ),
+        ));
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:<13}", "Last 24h"), label_style),
+            Span::styled(
+                health::sparkline(samples, now),
+                Style::new().fg(tailwind::GREEN.c400),
+            ),
+        ]));
+    }
+
+    // Description of the selected option, from the bundled reference
+    if app.details_help {
+        let selected = origins.get(app.details_option).and_then(|(label, _)| {
+            ssh::DETAILED_OPTIONS
+                .iter()
+                .find(|(option, _)| option == label)
+        });
+        lines.push(Line::default());
+        lines.push(match selected {
+            Some((_, entry_type)) => Line::from(vec![
+                Span::styled(
+                    format!("{entry_type}: "),
+                    label_style.add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(entry_type.description().unwrap_or_default()),
+            ]),
+            None => Line::styled("No option to describe, (tab) selects one", label_style),
+        });
+    }
+
+    let area = f.area();
+    let width = 80.min(area.width);
+    let height = (u16::try_from(lines.len()).unwrap_or_default() + 2).min(area.height);
+    let details_area = Rect::new(
+        area.width.saturating_sub(width) / 2,
+        area.height.saturating_sub(height) / 2,
+        width,
+        height,
+    );
+
+    f.render_widget(Clear, details_area);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .title(host.name.as_str())
+                .title_bottom(
+                    Line::from(
+                        "(j/k) previous/next host | (tab) option | (e) edit | (?) help | (esc) close",
+                    )
+                        .centered(),
+                )
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(app.palette.c400))
+                .border_type(BorderType::Rounded)
+                .padding(Padding::horizontal(1)),
+        ),
+        details_area,
+    );
+}
+
+/// `file:line` of an option, with the `Host` block when it is not the host's own.
+fn describe_origin(origin: &Origin, host: &ssh::Host) -> String {
+    let mut text = format!(
+        "{}:{}",
+        origin
+            .path
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default(),
+        origin.line
+    );
+
+    if let Some(criteria) = &origin.match_criteria {
+        let _ = write!(text, " (Match {criteria})");
+        return text;
+    }
+
+    match origin.block.as_slice() {
+        [] => text.push_str(" (top level)"),
+        [name, ..] if *name == host.name => {}
+        patterns => {
+            let _ = write!(text, " (Host {})", patterns.join(" "));
+        }
+    }
+
+    text
+}
+
+/// Render the settings panel on the right, so the host list stays visible while the settings
+/// change.
+fn render_settings_panel(f: &mut Frame, app: &App, panel: SettingsPanel) {
+    let area = f.area();
+    let width = 36.min(area.width);
+    let height = (u16::try_from(FIELDS.len()).unwrap_or_default() + 2).min(area.height);
+    let panel_area = Rect::new(
+        area.width.saturating_sub(width + 2),
+        SEARCH_BAR_HEIGHT.min(area.height.saturating_sub(height)),
+        width,
+        height,
+    );
+
+    let lines = FIELDS
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let text = format!(" {:<18} ‹ {} ›", field.label(), field.value(&app.settings));
+            if index == panel.selected {
+                Line::styled(text, Style::new().fg(Color::Black).bg(app.palette.c400))
+            } else {
+                Line::styled(text, Style::new().fg(Color::White))
+            }
+        })
+        .collect_vec();
+
+    f.render_widget(Clear, panel_area);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .title("Settings")
+                .title_bottom(Line::from("(←/→) change | (esc) close").centered())
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(app.palette.c400))
+                .border_type(BorderType::Rounded),
+        ),
+        panel_area,
+    );
+}
+
+/// Render the remote `authorized_keys`, flagging the entries matching a local key
+fn render_authorized_keys(f: &mut Frame, app: &App, view: &AuthorizedKeysView) {
+    let area = f.area().inner(Margin::new(2, 1));
+
+    let lines = if view.keys.is_empty() {
+        vec![Line::styled(
+            "No authorized keys",
+            Style::new().add_modifier(Modifier::DIM),
+        )]
+    } else {
+        view.keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| {
+                let options = key
+                    .options
+                    .as_ref()
+                    .map(|options| format!(" [{options}]"))
+                    .unwrap_or_default();
+                let text = format!(
+                    "{:<20} …{} {}{options}",
+                    key.key_type,
+                    key.short_blob(),
+                    key.comment
+                );
+                let local_match = key
+                    .local_match
+                    .as_ref()
+                    .map(|source| format!("  ← {source}"))
+                    .unwrap_or_default();
+
+                if index == view.selected {
+                    let style = Style::new().fg(Color::Black).bg(app.palette.c400);
+                    Line::styled(format!("{text}{local_match}"), style)
+                } else {
+                    Line::from(vec![
+                        Span::styled(text, Style::new().fg(Color::White)),
+                        Span::styled(local_match, Style::new().fg(tailwind::GREEN.c400)),
+                    ])
+                }
+            })
+            .collect()
+    };
+
+    let help = match view.selected_key() {
+        Some(key) if view.confirm_remove && key.local_match.is_some() => {
+            "This is one of your keys, remove it? (y) yes | any other key cancels"
+        }
+        Some(_) if view.confirm_remove => "Remove this key? (y) yes | any other key cancels",
+        _ => "(j/k) navigate | (d) remove | (esc) close",
+    };
+
+    let user = view.host.user.clone().or_else(|| app.inferred_user());
+    let title = match user {
+        Some(user) => format!("authorized_keys of {user}@{}", view.host.name),
+        None => format!("authorized_keys on {}", view.host.name),
+    };
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .title(title)
+                .title_bottom(Line::from(help).centered())
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(app.palette.c400))
+                .border_type(BorderType::Rounded),
+        ),
+        area,
+    );
+}
+
+/// Render the problems found in the SSH configuration, the hosts listed below are the ones
+/// that could be loaded. Warnings are in amber, and the panel too when there are only warnings.
+fn render_config_errors(f: &mut Frame, app: &App, view: &ConfigErrorsView) {
+    let area = f.area().inner(Margin::new(2, 1));
+    let color = |is_warning: bool| {
+        if is_warning {
+            tailwind::AMBER.c400
+        } else {
+            tailwind::RED.c400
+        }
+    };
+
+    let lines: Vec<Line> = view
+        .problems
+        .iter()
+        .enumerate()
+        .map(|(index, problem)| {
+            let location = match problem.line {
+                Some(line) => format!("{}:{line}", problem.path.display()),
+                None => problem.path.display().to_string(),
+            };
+            let style = if index == view.selected {
+                Style::new().fg(Color::Black).bg(app.palette.c400)
+            } else {
+                Style::new().fg(Color::White)
+            };
+
+            Line::from(vec![
+                Span::styled(location, style),
+                Span::styled(
+                    format!("  {}", problem.message),
+                    Style::new().fg(color(problem.is_warning)),
+                ),
+            ])
+        })
+        .collect();
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            Block::default()
+                .title(format!(
+                    "SSH configuration problems ({} hosts loaded)",
+                    app.hosts.non_filtered_iter().count()
+                ))
+                .title_bottom(Line::from("(enter) open in $EDITOR | (esc) close").centered())
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(color(
+                    view.problems.iter().all(|problem| problem.is_warning),
+                )))
+                .border_type(BorderType::Rounded),
+        ),
+        area,
+    );
+}
+
+fn render_backups(f: &mut Frame, app: &App, view: &BackupsView) {
+    let area = f.area().inner(Margin::new(2, 1));
+
+    let (title, lines, help) = if let Some(diff) = &view.diff {
+        let lines: Vec<Line> = diff.iter().map(diff_line).collect();
+        let title = view
+            .selected_backup()
+            .map(|backup| format!("Backup → {}", backup.original.display()))
+            .unwrap_or_default();
+        let help = if view.by_host {
+            "(j/k) scroll | (h) line diff | (r) restore | (esc) back"
+        } else {
+            "(j/k) scroll | (h) host diff | (r) restore | (esc) back"
+        };
+        (title, lines, help)
+    } else {
+        let now = std::time::SystemTime::now();
+        let lines = view
+            .backups
+            .iter()
+            .enumerate()
+            .map(|(index, backup)| {
+                let text = format!("{:<10} {}", backup.age(now), backup.path.display());
+                if index == view.selected {
+                    Line::styled(text, Style::new().fg(Color::Black).bg(app.palette.c400))
+                } else {
+                    Line::styled(text, Style::new().fg(Color::White))
+                }
+            })
+            .collect();
+        (
+            "Configuration backups".to_string(),
+            lines,
+            "(enter) diff | (r) restore | (d) delete | (esc) close",
+        )
+    };
+
+    let help = if view.confirm_delete {
+        "Delete this backup? (y) yes | any other key cancels"
+    } else {
+        help
+    };
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .scroll((if view.diff.is_some() { view.scroll } else { 0 }, 0))
+            .block(
+                Block::default()
+                    .title(title)
+                    .title_bottom(Line::from(help).centered())
+                    .borders(Borders::ALL)
+                    .border_style(Style::new().fg(app.palette.c400))
+                    .border_type(BorderType::Rounded),
+            ),
+        area,
+    );
+}
+
+fn diff_line(line: &DiffLine) -> Line<'static> {
+    match line {
+        DiffLine::Unchanged(_) => Line::from(line.to_string()),
+        DiffLine::Added(_) => Line::styled(line.to_string(), Style::new().fg(tailwind::GREEN.c400)),
+        DiffLine::Removed(_) => Line::styled(line.to_string(), Style::new().fg(tailwind::RED.c400)),
+    }
+}
+
+fn render_audit_log(f: &mut Frame, app: &App, view: &AuditLogView) {
+    let area = f.area().inner(Margin::new(2, 1));
+
+    let (title, lines, help) = match view.selected_entry() {
+        Some(entry) if view.show_diff => (
+            format!("{} {} - {}", entry.action, entry.hosts, entry.file),
+            entry.diff_lines().iter().map(diff_line).collect(),
+            "(j/k) scroll | (esc) back",
+        ),
+        _ => {
+            let now = std::time::SystemTime::now();
+            let lines: Vec<Line> = view
+                .entries
+                .iter()
+                .enumerate()
+                .map(|(index, entry)| {
+                    let text = format!(
+                        "{:<10} {:<12} {:<20} {:<20} {}",
+                        entry.age(now),
+                        entry.user,
+                        entry.action,
+                        entry.hosts,
+                        entry.file
+                    );
+                    if index == view.selected {
+                        Line::styled(text, Style::new().fg(Color::Black).bg(app.palette.c400))
+                    } else {
+                        Line::styled(text, Style::new().fg(Color::White))
+                    }
+                })
+                .collect();
+            (
+                "Audit log".to_string(),
+                lines,
+                "(enter) diff | (x) export as JSON | (esc) close",
+            )
+        }
+    };
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .scroll((if view.show_diff { view.scroll } else { 0 }, 0))
+            .block(
+                Block::default()
+                    .title(title)
+                    .title_bottom(Line::from(help).centered())
+                    .borders(Borders::ALL)
+                    .border_style(Style::new().fg(app.palette.c400))
+                    .border_type(BorderType::Rounded),
+            ),
+        area,
+    );
+}
+
+/// Render the timeline of a host, the selected event kept in view
+fn render_timeline(f: &mut Frame, app: &App, view: &TimelineView) {
+    let area = f.area().inner(Margin::new(2, 1));
+
+    let lines: Vec<Line> = view
+        .visible()
+        .into_iter()
+        .enumerate()
+        .map(|(index, event)| {
+            let text = format!(
+                "{}  {:<14} {}",
+                timeline::format_time(event.time),
+                event.kind.label(),
+                event.detail
+            );
+            if index == view.selected {
+                Line::styled(text, Style::new().fg(Color::Black).bg(app.palette.c400))
+            } else {
+                let color = match event.kind {
+                    HostEventKind::Failure => tailwind::RED.c400,
+                    HostEventKind::ConfigChange => tailwind::AMBER.c400,
+                    HostEventKind::Connection | HostEventKind::Health => Color::White,
+                };
+                Line::styled(text, Style::new().fg(color))
+            }
+        })
+        .collect();
+
+    let title = if view.search.is_empty() && !view.searching {
+        format!("Timeline of {} (UTC)", view.host)
+    } else {
+        format!("Timeline of {} (UTC) - /{}", view.host, view.search)
+    };
+    let help = if view.searching {
+        "(enter/esc) done searching"
+    } else {
+        "(j/k) move | (/) search | (x) export as CSV | (esc) close"
+    };
+
+    // Scroll so that the selected event is on the last line at most
+    let height = area.height.saturating_sub(2);
+    let scroll = u16::try_from(view.selected)
+        .unwrap_or(u16::MAX)
+        .saturating_sub(height.saturating_sub(1));
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).scroll((scroll, 0)).block(
+            Block::default()
+                .title(title)
+                .title_bottom(Line::from(help).centered())
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(app.palette.c400))
+                .border_type(BorderType::Rounded),
+        ),
+        area,
+    );
+}
+
+/// Render the tunnels running as systemd user units, failed ones in red
+fn render_background_tunnels(f: &mut Frame, app: &App, view: &TunnelsView) {
+    let area = f.area().inner(Margin::new(4, 2));
+
+    let lines: Vec<Line> = view
+        .units
+        .iter()
+        .enumerate()
+        .map(|(index, unit)| {
+            let text = format!("{:<8} {}  {}", unit.state, unit.name, unit.description);
+            if index == view.selected {
+                Line::styled(text, Style::new().fg(Color::Black).bg(app.palette.c400))
+            } else if unit.state == "failed" {
+                Line::styled(text, Style::new().fg(tailwind::RED.c400))
+            } else {
+                Line::styled(text, Style::new().fg(Color::White))
+            }
+        })
+        .collect();
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .title("Background tunnels (systemd user units)")
+                .title_bottom(
+                    Line::from("(j/k) move | (x) stop | (r) refresh | (esc) close").centered(),
+                )
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(app.palette.c400))
+                .border_type(BorderType::Rounded),
+        ),
+        area,
+    );
+}
+
+/// Local and remote directories of the SFTP browser side by side, the focused one highlighted.
+fn render_sftp(f: &mut Frame, app: &App, view: &SftpView, area: Rect) {
+    let [local_area, remote_area] =
+        Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(area);
+
+    let panes = [
+        (Side::Local, &view.local, "Local".to_string(), local_area),
+        (
+            Side::Remote,
+            &view.remote,
+            view.host.name.clone(),
+            remote_area,
+        ),
+    ];
+    for (side, pane, title, pane_area) in panes {
+        let focused = side == view.side;
+        let width = usize::from(pane_area.width.saturating_sub(2));
+        let lines: Vec<Line> = pane
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let (name, size) = if entry.is_dir {
+                    (format!("{}/", entry.name), String::new())
+                } else {
+                    (entry.name.clone(), sftp::format_size(entry.size))
+                };
+                let text = format!(
+                    "{name:<name_width$} {size:>9}",
+                    name_width = width.saturating_sub(10)
+                );
+
+                if focused && index == pane.selected {
+                    Line::styled(text, Style::new().fg(Color::Black).bg(app.palette.c400))
+                } else if entry.is_dir {
+                    Line::styled(text, Style::new().fg(app.palette.c300))
+                } else {
+                    Line::styled(text, Style::new().fg(Color::White))
+                }
+            })
+            .collect();
+
+        // Keep the selected entry in sight
+        let height = usize::from(pane_area.height.saturating_sub(2)).max(1);
+        let scroll = u16::try_from((pane.selected + 1).saturating_sub(height)).unwrap_or_default();
+        let border_style = if focused {
+            Style::new().fg(app.palette.c400)
+        } else {
+            Style::new()
+                .fg(app.palette.c400)
+                .add_modifier(Modifier::DIM)
+        };
+
+        f.render_widget(Clear, pane_area);
+        f.render_widget(
+            Paragraph::new(lines).scroll((scroll, 0)).block(
+                Block::default()
+                    .title(format!("{title}: {}", pane.path))
+                    .borders(Borders::ALL)
+                    .border_style(border_style)
+                    .border_type(BorderType::Rounded),
+            ),
+            pane_area,
+        );
+    }
+}
+
+/// Results of a batch run: the exit code and last output line of each host, and the whole
+/// output of the selected one under them.
+fn render_batch_run(f: &mut Frame, app: &App, run: &BatchRun, area: Rect) {
+    let list_height = u16::try_from(run.hosts.len() + 2).unwrap_or(u16::MAX);
+    let [list_area, output_area] = Layout::vertical([
+        Constraint::Length(list_height.min(area.height / 2)),
+        Constraint::Fill(1),
+    ])
+    .areas(area);
+
+    let name_width = run
+        .hosts
+        .iter()
+        .map(|host_run| host_run.host.chars().count())
+        .max()
+        .unwrap_or_default();
+    let lines: Vec<Line> = run
+        .hosts
+        .iter()
+        .enumerate()
+        .map(|(index, host_run)| {
+            let status_style = if !host_run.is_done() {
+                Style::new().fg(app.palette.c300)
+            } else if host_run.succeeded() {
+                Style::new().fg(tailwind::GREEN.c400)
+            } else {
+                Style::new().fg(tailwind::RED.c400)
+            };
+            let line = Line::from(vec![
+                Span::styled(
+                    format!("{:<name_width$}  ", host_run.host),
+                    Style::new().fg(Color::White),
+                ),
+                Span::styled(format!("{:<8}  ", host_run.status_label()), status_style),
+                Span::styled(
+                    host_run.summary().to_string(),
+                    Style::new().fg(app.palette.c300),
+                ),
+            ]);
+
+            if index == run.selected {
+                line.style(Style::new().add_modifier(Modifier::REVERSED))
+            } else {
+                line
+            }
+        })
+        .collect();
+
+    // Keep the selected host in sight
+    let height = usize::from(list_area.height.saturating_sub(2)).max(1);
+    let scroll = u16::try_from((run.selected + 1).saturating_sub(height)).unwrap_or_default();
+    f.render_widget(Clear, list_area);
+    f.render_widget(
+        Paragraph::new(lines).scroll((scroll, 0)).block(
+            Block::default()
+                .title(format!(
+                    "{} ({}): {}",
+                    run.command,
+                    run.mode.describe(),
+                    run.status()
+                ))
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(app.palette.c400))
+                .border_type(BorderType::Rounded),
+        ),
+        list_area,
+    );
+
+    let (title, output) = match run.hosts.get(run.selected) {
+        Some(host_run) if host_run.is_done() => (host_run.host.clone(), host_run.output.clone()),
+        Some(host_run) => (host_run.host.clone(), host_run.status_label()),
+        None => (String::new(), String::new()),
+    };
+    f.render_widget(Clear, output_area);
+    f.render_widget(
+        Paragraph::new(output)
+            .style(Style::new().fg(Color::White))
+            .wrap(Wrap { trim: false })
+            .block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_style(Style::new().fg(app.palette.c400))
+                    .border_type(BorderType::Rounded),
+            ),
+        output_area,
+    );
+}
+
+/// Changelog of the available update, with the command to upgrade first so that a long
+/// changelog does not hide it.
+fn render_changelog(f: &mut Frame, app: &App, release: &Release) {
+    let area = f.area().inner(Margin::new(4, 2));
+
+    let mut lines = vec![
+        Line::styled(
+            format!(
+                "sshs {} is out, {} is installed",
+                release.version,
+                env!("CARGO_PKG_VERSION")
+            ),
+            Style::new().fg(Color::White).add_modifier(Modifier::BOLD),
+        ),
+        Line::from(vec![
+            Span::styled("Upgrade with: ", Style::new().fg(app.palette.c300)),
+            Span::styled(UPGRADE_COMMAND, Style::new().fg(tailwind::AMBER.c400)),
+        ]),
+        Line::styled(release.url.clone(), Style::new().fg(app.palette.c300)),
+        Line::from(""),
+    ];
+    lines.extend(
+        release
+            .changelog
+            .lines()
+            .map(|line| Line::styled(line.to_string(), Style::new().fg(Color::White))),
+    );
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            Block::default()
+                .title("What's new")
+                .title_bottom(Line::from("(esc) close").centered())
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(app.palette.c400))
+                .border_type(BorderType::Rounded),
+        ),
+        area,
+    );
+}
+
+/// Render what can follow the keys typed after the leader, in the bottom right corner of the
+/// table: the groups, then the actions of the chosen group
+fn render_leader_popup(f: &mut Frame, app: &App, typed: &str, area: Rect) {
+    let key_style = Style::new()
+        .fg(app.palette.c400)
+        .add_modifier(Modifier::BOLD);
+    let entries: Vec<(char, String, Style)> = match typed.chars().next().and_then(LeaderGroup::find)
+    {
+        None => LEADER_GROUPS
+            .iter()
+            .map(|group| {
+                (
+                    group.key,
+                    format!("+{}", group.title),
+                    Style::new().fg(app.palette.c300),
+                )
+            })
+            .collect(),
+        Some(group) => group
+            .bindings
+            .iter()
+            .map(|(key, action)| {
+                (
+                    *key,
+                    action.label().to_string(),
+                    Style::new().fg(Color::White),
+                )
+            })
+            .collect(),
+    };
+
+    let width = entries
+        .iter()
+        .map(|(_, label, _)| label.chars().count() + 6)
+        .max()
+        .unwrap_or_default()
+        .max(20);
+    let width = u16::try_from(width).unwrap_or(40).min(area.width);
+    let height = (u16::try_from(entries.len()).unwrap_or_default() + 2).min(area.height);
+    let popup_area = Rect::new(
+        area.right().saturating_sub(width),
+        area.bottom().saturating_sub(height),
+        width,
+        height,
+    );
+
+    let lines: Vec<Line> = entries
+        .into_iter()
+        .map(|(key, label, style)| {
+            Line::from(vec![
+                Span::styled(format!(" {key} "), key_style),
+                Span::raw(" "),
+                Span::styled(label, style),
+            ])
+        })
+        .collect();
+
+    f.render_widget(Clear, popup_area);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .title(format!(" {}{typed} ", app.config.leader))
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(app.palette.c400))
+                .border_type(BorderType::Rounded),
+        ),
+        popup_area,
+    );
+}
+
+/// The lines of a paste waiting for a confirmation, their ends marked, as many as fit.
+fn render_paste_preview(f: &mut Frame, app: &App, text: &str) {
+    let area = f.area().inner(Margin::new(4, 2));
+    let host = app
+        .tab_manager
+        .current_session()
+        .map_or("the session", |session| session.host.name.as_str());
+
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let pasted: Vec<&str> = text.split_inclusive('\n').collect();
+    let shown = usize::from(area.height.saturating_sub(2)).min(pasted.len());
+    // The last row tells how many lines do not fit
+    let shown = if shown < pasted.len() {
+        shown.saturating_sub(1)
+    } else {
+        shown
+    };
+
+    let mut lines: Vec<Line> = pasted[..shown]
+        .iter()
+        .map(|line| {
+            let mut content = String::new();
+            for c in line.trim_end_matches('\n').chars() {
+                match c {
+                    '\t' => content.push_str("    "),
+                    c if c.is_control() => content.extend(c.escape_default()),
+                    c => content.push(c),
+                }
+            }
+            let mut spans = vec![Span::styled(content, Style::new().fg(Color::White))];
+            if line.ends_with('\n') {
+                spans.push(Span::styled("⏎", Style::new().fg(app.palette.c400)));
+            }
+            Line::from(spans)
+        })
+        .collect();
+    if shown < pasted.len() {
+        lines.push(Line::styled(
+            format!("… {} more lines", pasted.len() - shown),
+            Style::new().fg(app.palette.c300),
+        ));
+    }
+
+    let count = text.lines().count();
+    let title = format!(
+        " Paste {count} line{} into {host}? ",
+        if count == 1 { "" } else { "s" }
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .title(title)
+                .title_bottom(
+                    Line::from("(enter) send · (t) without the last newline · (esc) cancel")
+                        .centered(),
+                )
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(tailwind::AMBER.c400))
+                .border_type(BorderType::Rounded),
+        ),
+        area,
+    );
+}
+
+/// Render the menu bar on the first row and the dropdown of the open menu below it
+fn render_menu_bar(f: &mut Frame, app: &App, menu: MenuState) {
+    let area = f.area();
+
+    let mut title_spans = Vec::new();
+    for (index, entry) in MENUS.iter().enumerate() {
+        let title = format!(" {} ", entry.title);
+        if index == menu.menu {
+            title_spans.push(Span::styled(
+                title,
+                Style::new()
+                    .fg(Color::Black)
+                    .bg(app.palette.c400)
+                    .add_modifier(Modifier::BOLD),
+            ));
+        } else {
+            title_spans.push(Span::styled(title, Style::new().fg(app.palette.c300)));
+        }
+    }
+
+    let bar_area = Rect::new(area.x, area.y, area.width, 1.min(area.height));
+    f.render_widget(Clear, bar_area);
+    f.render_widget(Paragraph::new(Line::from(title_spans)), bar_area);
+
+    // Dropdown with the actions of the open menu
+    let current = menu.current_menu();
+    let dropdown_area = menu.dropdown_area(area);
+
+    let inner_width = usize::from(dropdown_area.width.saturating_sub(2));
+    let lines: Vec<Line> = current
+        .actions
+        .iter()
+        .enumerate()
+        .map(|(index, action)| {
+            let hint = app.config.keymap.hint(*action);
+            let padding = inner_width.saturating_sub(action.label().len() + hint.len() + 1);
+            let text = format!(" {}{}{hint}", action.label(), " ".repeat(padding));
+            if index == menu.item {
+                Line::from(Span::styled(
+                    text,
+                    Style::new().fg(Color::Black).bg(app.palette.c400),
+                ))
+            } else {
+                Line::from(Span::styled(text, Style::new().fg(Color::White)))
+            }
+        })
+        .collect();
+
+    f.render_widget(Clear, dropdown_area);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(app.palette.c400))
+                .border_type(BorderType::Rounded),
+        ),
+        dropdown_area,
+    );
+}
+
 /// Render the form UI
 #[allow(clippy::too_many_lines)]
 fn render_form_ui(f: &mut Frame, app: &mut App) {
@@ -77,8 +1345,8 @@ fn render_form_ui(f: &mut Frame, app: &mut App) {
 
     // Create a centered box for the form with additional space
     let form_width = 60;
-    let form_height = 14; // Base height for the form
-    let total_height = form_height + 2; // Add space for help text and field hints
+    let form_height = 27; // Base height for the form
+    let total_height = form_height + 3; // Add space for help text and field hints
     let horizontal_margin = (area.width.saturating_sub(form_width)) / 2;
     let vertical_margin = (area.height.saturating_sub(total_height)) / 2;
 
@@ -120,14 +1388,32 @@ fn render_form_ui(f: &mut Frame, app: &mut App) {
     // Create inner area for form fields with proper margins
     let inner_area = form_area.inner(Margin::new(2, 1));
 
-    // Split the inner area into form fields with spacing between fields
-    let chunks = Layout::vertical([
+    // Split the inner area into form fields with spacing between fields, the username and port
+    // sharing a row
+    let rows = Layout::vertical([
         Constraint::Length(3), // Host name
         Constraint::Length(3), // Hostname/IP
-        Constraint::Length(3), // Username
-        Constraint::Length(3), // Port
+        Constraint::Length(3), // Username and port
+        Constraint::Length(3), // Aliases
+        Constraint::Length(3), // Identity file
+        Constraint::Length(3), // Proxy jump
+        Constraint::Length(3), // Tags
+        Constraint::Length(4), // Additional options
     ])
     .split(inner_area);
+    let [username_row, port_row] =
+        Layout::horizontal([Constraint::Min(0), Constraint::Length(20)]).areas(rows[2]);
+    let chunks = [
+        rows[0],
+        rows[1],
+        username_row,
+        port_row,
+        rows[3],
+        rows[4],
+        rows[5],
+        rows[6],
+        rows[7],
+    ];
 
     if let Some(form) = &app.add_host_form {
         // Render host name field
@@ -167,63 +1453,191 @@ fn render_form_ui(f: &mut Frame, app: &mut App) {
 
         // Render the actual text content inside the block
         let ip_inner = ip_area.inner(Margin::new(1, 1));
-        let ip_text =
-            Paragraph::new(form.hostname.value()).style(Style::default().fg(Color::White));
+        let ip_text = field_text(app, form, &form.hostname, &EntryType::Hostname);
         f.render_widget(Clear, ip_inner); // Clear the inner area first
         f.render_widget(ip_text, ip_inner);
 
-        // Render username field
-        let username_block = Block::default()
+        // Render username field
+        let username_block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::new().fg(if form.active_field == 2 {
+                app.palette.c500
+            } else {
+                app.palette.c300
+            }))
+            .title("Username (optional)");
+
+        let username_area = chunks[2];
+        f.render_widget(username_block, username_area);
+
+        // Render the actual text content inside the block
+        let username_inner = username_area.inner(Margin::new(1, 1));
+        let username_text = field_text(app, form, &form.username, &EntryType::User);
+        f.render_widget(Clear, username_inner); // Clear the inner area first
+        f.render_widget(username_text, username_inner);
+
+        // Render port field
+        let port_block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::new().fg(if form.active_field == 3 {
+                app.palette.c500
+            } else {
+                app.palette.c300
+            }))
+            .title("Port (optional)");
+
+        let port_area = chunks[3];
+        f.render_widget(port_block, port_area);
+
+        // Render the actual text content inside the block
+        let port_inner = port_area.inner(Margin::new(1, 1));
+        let port_text = field_text(app, form, &form.port, &EntryType::Port);
+        f.render_widget(Clear, port_inner); // Clear the inner area first
+        f.render_widget(port_text, port_inner);
+
+        // Render aliases field
+        let aliases_block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::new().fg(if form.active_field == 4 {
+                app.palette.c500
+            } else {
+                app.palette.c300
+            }))
+            .title("Aliases (optional, space separated)");
+
+        let aliases_area = chunks[4];
+        f.render_widget(aliases_block, aliases_area);
+
+        // Render the actual text content inside the block
+        let aliases_inner = aliases_area.inner(Margin::new(1, 1));
+        let aliases_text =
+            Paragraph::new(form.aliases.value()).style(Style::default().fg(Color::White));
+        f.render_widget(Clear, aliases_inner); // Clear the inner area first
+        f.render_widget(aliases_text, aliases_inner);
+
+        // Render identity file field
+        let identity_block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::new().fg(if form.active_field == 5 {
+                app.palette.c500
+            } else {
+                app.palette.c300
+            }))
+            .title("Identity File (optional, Tab completes)");
+
+        let identity_area = chunks[5];
+        f.render_widget(identity_block, identity_area);
+
+        // Render the actual text content inside the block
+        let identity_inner = identity_area.inner(Margin::new(1, 1));
+        let identity_text = field_text(app, form, &form.identity_file, &EntryType::IdentityFile);
+        f.render_widget(Clear, identity_inner); // Clear the inner area first
+        f.render_widget(identity_text, identity_inner);
+
+        // Render proxy jump field
+        let proxy_jump_block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::new().fg(if form.active_field == PROXY_JUMP_FIELD {
+                app.palette.c500
+            } else {
+                app.palette.c300
+            }))
+            .title("Proxy Jump (optional, comma separated)");
+
+        let proxy_jump_area = chunks[6];
+        f.render_widget(proxy_jump_block, proxy_jump_area);
+
+        let proxy_jump_inner = proxy_jump_area.inner(Margin::new(1, 1));
+        let proxy_jump_text = field_text(app, form, &form.proxy_jump, &EntryType::ProxyJump);
+        f.render_widget(Clear, proxy_jump_inner);
+        f.render_widget(proxy_jump_text, proxy_jump_inner);
+
+        // Render tags field
+        let tags_block = Block::default()
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded)
-            .border_style(Style::new().fg(if form.active_field == 2 {
+            .border_style(Style::new().fg(if form.active_field == TAGS_FIELD {
                 app.palette.c500
             } else {
                 app.palette.c300
             }))
-            .title("Username (optional)");
+            .title("Tags (optional, space separated)");
 
-        let username_area = chunks[2];
-        f.render_widget(username_block, username_area);
+        let tags_area = chunks[7];
+        f.render_widget(tags_block, tags_area);
 
-        // Render the actual text content inside the block
-        let username_inner = username_area.inner(Margin::new(1, 1));
-        let username_text =
-            Paragraph::new(form.username.value()).style(Style::default().fg(Color::White));
-        f.render_widget(Clear, username_inner); // Clear the inner area first
-        f.render_widget(username_text, username_inner);
+        let tags_inner = tags_area.inner(Margin::new(1, 1));
+        let tags_text = Paragraph::new(form.tags.value()).style(Style::default().fg(Color::White));
+        f.render_widget(Clear, tags_inner);
+        f.render_widget(tags_text, tags_inner);
 
-        // Render port field
-        let port_block = Block::default()
+        // Render additional options field, scrolled to the line of the cursor, with the other
+        // options of the Match blocks under it
+        let match_options = form
+            .match_options
+            .iter()
+            .filter(|option| !FIELD_OPTIONS.contains(&option.entry_type))
+            .map(|option| option.line.as_str())
+            .join(", ");
+        let mut options_block = Block::default()
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded)
-            .border_style(Style::new().fg(if form.active_field == 3 {
+            .border_style(Style::new().fg(if form.active_field == OPTIONS_FIELD {
                 app.palette.c500
             } else {
                 app.palette.c300
             }))
-            .title("Port (optional, numbers only)");
+            .title("Options (optional, one `Key Value` per line)");
+        if !match_options.is_empty() {
+            options_block = options_block.title_bottom(Line::styled(
+                format!(" From Match: {match_options} "),
+                Style::new()
+                    .fg(app.palette.c300)
+                    .add_modifier(Modifier::ITALIC),
+            ));
+        }
 
-        let port_area = chunks[3];
-        f.render_widget(port_block, port_area);
+        let options_area = chunks[8];
+        f.render_widget(options_block, options_area);
 
-        // Render the actual text content inside the block
-        let port_inner = port_area.inner(Margin::new(1, 1));
-        let port_text = Paragraph::new(form.port.value()).style(Style::default().fg(Color::White));
-        f.render_widget(Clear, port_inner); // Clear the inner area first
-        f.render_widget(port_text, port_inner);
+        let options_inner = options_area.inner(Margin::new(1, 1));
+        let (cursor_line, cursor_column) =
+            multiline_cursor(form.options.value(), form.options.cursor());
+        let options_scroll = cursor_line.saturating_sub(options_inner.height.saturating_sub(1));
+        let options_text = Paragraph::new(form.options.value())
+            .style(Style::default().fg(Color::White))
+            .scroll((options_scroll, 0));
+        f.render_widget(Clear, options_inner);
+        f.render_widget(options_text, options_inner);
+
+        render_hostname_suggestions(f, app, form, chunks[1]);
 
         // Position cursor in active field
         let active_inner = match form.active_field {
             1 => chunks[1].inner(Margin::new(1, 1)),
             2 => chunks[2].inner(Margin::new(1, 1)),
             3 => chunks[3].inner(Margin::new(1, 1)),
+            4 => chunks[4].inner(Margin::new(1, 1)),
+            5 => chunks[5].inner(Margin::new(1, 1)),
+            6 => chunks[6].inner(Margin::new(1, 1)),
+            7 => chunks[7].inner(Margin::new(1, 1)),
+            8 => chunks[8].inner(Margin::new(1, 1)),
             _ => chunks[0].inner(Margin::new(1, 1)),
         };
 
         // Set cursor position with proper offset
         let mut cursor_position = active_inner.as_position();
-        cursor_position.x += u16::try_from(form.active_input().cursor()).unwrap_or_default();
+        if form.active_field == OPTIONS_FIELD {
+            cursor_position.x += cursor_column;
+            cursor_position.y += cursor_line - options_scroll;
+        } else {
+            cursor_position.x += u16::try_from(form.active_input().cursor()).unwrap_or_default();
+        }
 
         // Show cursor explicitly
         f.set_cursor_position(cursor_position);
@@ -272,25 +1686,53 @@ fn render_form_ui(f: &mut Frame, app: &mut App) {
 
     f.render_widget(help_paragraph, help_area);
 
-    // Add field-specific hints
+    // Add field-specific hints, warnings take precedence as they are checked live
     if let Some(form) = &app.add_host_form {
-        let hint_text = match form.active_field {
-            0 => "Host name used to identify this connection (required)",
-            1 => "IP address or domain name to connect to (required)",
-            2 => "SSH username (optional, will use system default if empty)",
-            3 => "SSH port (optional, defaults to 22 if empty)",
-            _ => "",
-        };
-
-        let hint_paragraph = Paragraph::new(Line::from(hint_text))
-            .alignment(Alignment::Center)
-            .style(Style::new().fg(app.palette.c200));
+        let warnings = form.warnings();
+        let hint_paragraph = if let Some(warning) = warnings.first() {
+            let more = match warnings.len() {
+                1 => String::new(),
+                count => format!(" (+{} more)", count - 1),
+            };
+            Paragraph::new(Line::from(format!("⚠ {warning}{more}")))
+                .style(Style::new().fg(tailwind::AMBER.c400))
+        } else {
+            let hint_text = match form.active_field {
+                0 => "Host name used to identify this connection (required)".to_string(),
+                1 if !form.hostname_suggestions().is_empty() => {
+                    "↑/↓ pick a known destination, Tab takes it".to_string()
+                }
+                1 => "IP address or domain name to connect to (required)".to_string(),
+                2 => "SSH username (optional, will use system default if empty)".to_string(),
+                3 => "SSH port (optional, defaults to 22 if empty)".to_string(),
+                4 => "Other names or patterns for this host (optional)".to_string(),
+                5 => identity_file_hint(form),
+                PROXY_JUMP_FIELD => {
+                    "Hosts to connect through first, e.g. bastion or user@gw:2222".to_string()
+                }
+                TAGS_FIELD => "Tags shown in the Tags column and searched with tag:".to_string(),
+                OPTIONS_FIELD => match form
+                    .cursor_option()
+                    .and_then(|entry_type| Some((entry_type.description()?, entry_type)))
+                {
+                    Some((description, entry_type)) => format!("{entry_type}: {description}"),
+                    None => {
+                        "ssh_config options or comments, Enter adds a line, Tab leaves".to_string()
+                    }
+                },
+                _ => String::new(),
+            };
+            Paragraph::new(Line::from(hint_text)).style(Style::new().fg(app.palette.c200))
+        }
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
 
+        // Two lines, for the descriptions of the options
         let hint_area = Rect::new(
             horizontal_margin,
             vertical_margin + form_height + 1,
             form_width,
-            1,
+            2,
         );
 
         f.render_widget(hint_paragraph, hint_area);
@@ -302,6 +1744,105 @@ fn render_form_ui(f: &mut Frame, app: &mut App) {
     }
 }
 
+/// Text of a form field: its value or, when it is empty, the value a `Match` block sets for it
+/// in italics.
+fn field_text<'a>(
+    app: &App,
+    form: &'a AddHostForm,
+    input: &'a Input,
+    entry_type: &EntryType,
+) -> Paragraph<'a> {
+    match form
+        .match_option(entry_type)
+        .filter(|_| input.value().is_empty())
+    {
+        Some(option) => Paragraph::new(option.describe()).style(
+            Style::new()
+                .fg(app.palette.c300)
+                .add_modifier(Modifier::ITALIC),
+        ),
+        None => Paragraph::new(input.value()).style(Style::default().fg(Color::White)),
+    }
+}
+
+/// Known destinations matching the hostname being typed, in a list dropping down from the
+/// field.
+fn render_hostname_suggestions(f: &mut Frame, app: &App, form: &AddHostForm, field_area: Rect) {
+    let suggestions = form.hostname_suggestions();
+    if suggestions.is_empty() {
+        return;
+    }
+
+    let lines: Vec<Line> = suggestions
+        .iter()
+        .enumerate()
+        .map(|(index, suggestion)| {
+            let style = if form.selected_suggestion == Some(index) {
+                Style::new()
+                    .fg(app.palette.c200)
+                    .add_modifier(Modifier::REVERSED)
+            } else {
+                Style::new().fg(app.palette.c200)
+            };
+            Line::styled(format!(" {suggestion}"), style)
+        })
+        .collect();
+
+    let height = u16::try_from(lines.len()).unwrap_or_default() + 2;
+    let area = Rect::new(
+        field_area.x + 1,
+        field_area.bottom().saturating_sub(1),
+        field_area.width.saturating_sub(2),
+        height,
+    )
+    .intersection(f.area());
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::new().fg(app.palette.c500));
+
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Line and column of the cursor at character `cursor` of a multi-line `value`.
+fn multiline_cursor(value: &str, cursor: usize) -> (u16, u16) {
+    let before = value.chars().take(cursor).collect::<String>();
+    let line = before.matches('\n').count();
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map_or(0, |line| line.chars().count());
+
+    (
+        u16::try_from(line).unwrap_or_default(),
+        u16::try_from(column).unwrap_or_default(),
+    )
+}
+
+/// Hint of the identity file field: the matching keys of `~/.ssh`, or how to generate one.
+fn identity_file_hint(form: &AddHostForm) -> String {
+    if form.identity_file.value().trim().is_empty() {
+        return "No key yet? Ctrl+G generates one for this host".to_string();
+    }
+
+    let candidates = form.identity_file_candidates();
+    match candidates.as_slice() {
+        [] => "Private key file (optional)".to_string(),
+        [only] if only == form.identity_file.value().trim() => {
+            "Private key file (optional)".to_string()
+        }
+        _ => {
+            let names: Vec<&str> = candidates
+                .iter()
+                .filter_map(|candidate| candidate.rsplit('/').next())
+                .collect();
+            format!("Keys: {}", names.join(", "))
+        }
+    }
+}
+
 /// Render a confirmation dialog
 fn render_confirmation_ui(f: &mut Frame, app: &mut App) {
     // First render the form UI in the background
@@ -311,8 +1852,10 @@ fn render_confirmation_ui(f: &mut Frame, app: &mut App) {
 
     // Create a centered box for the confirmation dialog
     let message = app.confirm_message.as_deref().unwrap_or("Confirm?");
-    let dialog_width = 50.max(u16::try_from(message.len()).unwrap_or(50) + 4);
-    let dialog_height = 7; // Increased height for buttons
+    let message_width = message.lines().map(str::len).max().unwrap_or_default();
+    let message_height = u16::try_from(message.lines().count().max(1)).unwrap_or(1);
+    let dialog_width = 50.max(u16::try_from(message_width).unwrap_or(50) + 4);
+    let dialog_height = 6 + message_height; // Increased height for buttons
     let horizontal_margin = (area.width.saturating_sub(dialog_width)) / 2;
     let vertical_margin = (area.height.saturating_sub(dialog_height)) / 2;
 
@@ -338,14 +1881,14 @@ fn render_confirmation_ui(f: &mut Frame, app: &mut App) {
     // Split the inner area into message and buttons
     let inner_area = dialog_area.inner(Margin::new(2, 1));
     let chunks = Layout::vertical([
-        Constraint::Length(1), // Message
-        Constraint::Length(1), // Spacing
-        Constraint::Length(1), // Buttons
+        Constraint::Length(message_height), // Message
+        Constraint::Length(1),              // Spacing
+        Constraint::Length(1),              // Buttons
     ])
     .split(inner_area);
 
     // Render message
-    let message_paragraph = Paragraph::new(Line::from(message))
+    let message_paragraph = Paragraph::new(Text::from(message))
         .alignment(Alignment::Center)
         .style(Style::new().fg(Color::White));
 
@@ -354,7 +1897,7 @@ fn render_confirmation_ui(f: &mut Frame, app: &mut App) {
     // Render buttons with styled keyboard shortcuts
     let action_text = app.confirm_action.as_deref().unwrap_or("Yes");
 
-    let button_spans = vec![
+    let mut button_spans = vec![
         // Yes button
         Span::styled("(", Style::new().fg(tailwind::BLUE.c400)),
         Span::styled(
@@ -379,6 +1922,53 @@ fn render_confirmation_ui(f: &mut Frame, app: &mut App) {
         Span::styled("Cancel", Style::new().fg(tailwind::RED.c500)),
     ];
 
+    if action_text == SYSTEM_EDIT_ACTION {
+        button_spans.extend([
+            Span::styled(" | ", Style::new().fg(tailwind::BLUE.c400)),
+            Span::styled("(", Style::new().fg(tailwind::BLUE.c400)),
+            Span::styled(
+                "S",
+                Style::new()
+                    .fg(tailwind::AMBER.c400)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(") ", Style::new().fg(tailwind::BLUE.c400)),
+            Span::styled("sudoedit", Style::new().fg(tailwind::AMBER.c400)),
+        ]);
+    }
+
+    if action_text == AGENT_FORWARDING_ACTION {
+        button_spans.extend([
+            Span::styled(" | ", Style::new().fg(tailwind::BLUE.c400)),
+            Span::styled("(", Style::new().fg(tailwind::BLUE.c400)),
+            Span::styled(
+                "T",
+                Style::new()
+                    .fg(tailwind::AMBER.c400)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(") ", Style::new().fg(tailwind::BLUE.c400)),
+            Span::styled("Trust host", Style::new().fg(tailwind::AMBER.c400)),
+        ]);
+    }
+
+    if action_text == UNUSED_HOSTS_ACTION {
+        for (key, label) in [("C", "Comment out"), ("X", "Export")] {
+            button_spans.extend([
+                Span::styled(" | ", Style::new().fg(tailwind::BLUE.c400)),
+                Span::styled("(", Style::new().fg(tailwind::BLUE.c400)),
+                Span::styled(
+                    key,
+                    Style::new()
+                        .fg(tailwind::AMBER.c400)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(") ", Style::new().fg(tailwind::BLUE.c400)),
+                Span::styled(label, Style::new().fg(tailwind::AMBER.c400)),
+            ]);
+        }
+    }
+
     let buttons_line = Line::from(button_spans);
     let buttons_paragraph = Paragraph::new(buttons_line).alignment(Alignment::Center);
 
@@ -390,7 +1980,9 @@ fn render_feedback(f: &mut Frame, message: &str, is_error: bool) {
     let area = f.area();
 
     // Create a centered box for the message
-    let message_width = 40.max(u16::try_from(message.len()).unwrap_or(40) + 4);
+    let message_width = 40
+        .max(u16::try_from(message.len()).unwrap_or(40) + 4)
+        .min(area.width);
     let message_height = 3;
     let horizontal_margin = (area.width.saturating_sub(message_width)) / 2;
     let vertical_margin = (area.height.saturating_sub(message_height)) / 2;
@@ -427,6 +2019,84 @@ fn render_feedback(f: &mut Frame, message: &str, is_error: bool) {
     f.render_widget(message_paragraph, message_area);
 }
 
+/// Render the screen of the current tab's session, with the cursor where the remote one is.
+/// The session is resized to `area` first.
+fn render_session(f: &mut Frame, app: &mut App, area: Rect) {
+    f.render_widget(Clear, area);
+    let Some((pty, triggers)) = app
+        .tab_manager
+        .current_session()
+        .and_then(|session| Some((session.pty.as_ref()?, &session.triggers)))
+    else {
+        let starting = Paragraph::new("Starting the session...")
+            .alignment(Alignment::Center)
+            .style(Style::new().fg(app.palette.c300));
+        f.render_widget(starting, area);
+        return;
+    };
+
+    // Resized by the event loop
+    let parser = pty.parser();
+    let screen = parser.screen();
+    let buffer = f.buffer_mut();
+    // Lines matching a highlight trigger stand out
+    let highlighted: Vec<bool> = screen
+        .rows(0, area.width)
+        .map(|line| triggers::highlights(triggers, &line))
+        .collect();
+    for row in 0..area.height {
+        let highlight = highlighted
+            .get(usize::from(row))
+            .copied()
+            .unwrap_or_default();
+        for col in 0..area.width {
+            // The cell of a wide character covers the next one
+            let Some(cell) = screen
+                .cell(row, col)
+                .filter(|cell| !cell.is_wide_continuation())
+            else {
+                continue;
+            };
+            let contents = cell.contents();
+            let mut style = cell_style(cell);
+            if highlight {
+                style = style.fg(Color::Black).bg(Color::Yellow);
+            }
+            buffer[(area.x + col, area.y + row)]
+                .set_symbol(if contents.is_empty() { " " } else { &contents })
+                .set_style(style);
+        }
+    }
+
+    let (row, col) = screen.cursor_position();
+    if !screen.hide_cursor() && row < area.height && col < area.width {
+        f.set_cursor_position((area.x + col, area.y + row));
+    }
+}
+
+fn cell_style(cell: &vt100::Cell) -> Style {
+    let color = |color| match color {
+        vt100::Color::Default => Color::Reset,
+        vt100::Color::Idx(index) => Color::Indexed(index),
+        vt100::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
+    };
+
+    let mut style = Style::new()
+        .fg(color(cell.fgcolor()))
+        .bg(color(cell.bgcolor()));
+    for (on, modifier) in [
+        (cell.bold(), Modifier::BOLD),
+        (cell.italic(), Modifier::ITALIC),
+        (cell.underline(), Modifier::UNDERLINED),
+        (cell.inverse(), Modifier::REVERSED),
+    ] {
+        if on {
+            style = style.add_modifier(modifier);
+        }
+    }
+    style
+}
+
 /// Render the tab bar
 pub fn render_tab_bar(f: &mut Frame, app: &mut App, area: Rect) {
     if !app.tab_manager.has_sessions() {
@@ -440,7 +2110,15 @@ pub fn render_tab_bar(f: &mut Frame, app: &mut App, area: Rect) {
     let mut tab_spans = Vec::new();
 
     for (index, session) in sessions.iter().enumerate() {
-        let tab_text = session.tab_display_name();
+        let mut tab_text = session.tab_display_name(app.tab_manager.naming());
+        // Which session is streaming, e.g. `[1:web]▁▃█ 1.2 MB/s`
+        let sparkline = session.throughput.sparkline();
+        if !sparkline.is_empty() {
+            let rate = session.throughput.rate();
+            tab_text = format!("{tab_text}{sparkline} {}/s ", sftp::format_size(rate));
+        }
+        // Tabs take the color of their host environment (prod=red) or `# sshs:color`
+        let color = session_color(&session.host);
 
         if index == current_index {
             // Current tab - highlighted
@@ -448,14 +2126,16 @@ pub fn render_tab_bar(f: &mut Frame, app: &mut App, area: Rect) {
                 format!("▶{tab_text}"),
                 Style::default()
                     .fg(Color::White)
-                    .bg(app.palette.c600)
+                    .bg(color.unwrap_or(app.palette.c600))
                     .add_modifier(Modifier::BOLD),
             ));
         } else {
             // Inactive tab
             tab_spans.push(Span::styled(
                 tab_text,
-                Style::default().fg(app.palette.c400).bg(app.palette.c950),
+                Style::default()
+                    .fg(color.unwrap_or(app.palette.c400))
+                    .bg(app.palette.c950),
             ));
         }
     }
@@ -483,13 +2163,24 @@ pub fn render_searchbar(f: &mut Frame, app: &mut App, area: Rect) {
         Style::new().fg(app.palette.c300) // Dimmer when not focused
     };
 
-    let info_footer = Paragraph::new(Line::from(app.search.value())).block(
-        Block::default()
-            .borders(Borders::ALL)
-            .border_style(border_style)
-            .border_type(BorderType::Rounded)
-            .padding(Padding::horizontal(SEARCHBAR_HORIZONTAL_PADDING)),
-    );
+    let mut block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(border_style)
+        .border_type(BorderType::Rounded)
+        .padding(Padding::horizontal(SEARCHBAR_HORIZONTAL_PADDING));
+    // Counts per state once there are states to count
+    if app.health.is_some() || app.tab_manager.has_sessions() {
+        let mut title = app.state_counts.text();
+        if app.state_filter != StateFilter::All {
+            title = format!("showing {}: {title}", app.state_filter.label());
+        }
+        if app.incident.is_some() {
+            title = format!("INCIDENT · {title}");
+        }
+        block = block.title(Line::from(format!(" {title} ")).right_aligned());
+    }
+
+    let info_footer = Paragraph::new(Line::from(app.search.value())).block(block);
     f.render_widget(info_footer, area);
 }
 
@@ -498,9 +2189,21 @@ pub fn render_table(f: &mut Frame, app: &mut App, area: Rect) {
     let header_style = Style::default().fg(tailwind::CYAN.c500);
     let selected_style = Style::default().add_modifier(Modifier::REVERSED);
 
-    let mut header_names = vec!["Name", "Aliases", "User", "Destination", "Port"];
-    if app.config.show_proxy_command {
-        header_names.push("Proxy");
+    let columns = app.visible_columns();
+    let group_mode = app.group_mode();
+
+    let mut header_names = columns.iter().map(|column| column.title()).collect_vec();
+    if group_mode != GroupMode::None {
+        header_names.insert(0, "Group");
+    }
+    if app.health.is_some() {
+        header_names.insert(0, health::COLUMN_TITLE);
+    }
+    if app.recent_view {
+        header_names.push(recent::COLUMN_TITLE);
+    }
+    if app.incident.is_some() {
+        header_names.push(incident::COLUMN_TITLE);
     }
 
     let header = header_names
@@ -511,22 +2214,119 @@ pub fn render_table(f: &mut Frame, app: &mut App, area: Rect) {
         .style(header_style)
         .height(TABLE_HEADER_HEIGHT);
 
-    let rows = app.hosts.iter().map(|host| {
-        let mut content = vec![
-            host.name.clone(),
-            host.aliases.clone(),
-            host.user.clone().unwrap_or_default(),
-            host.destination.clone(),
-            host.port.clone().unwrap_or_default(),
-        ];
-        if app.config.show_proxy_command {
-            content.push(host.proxy_command.clone().unwrap_or_default());
-        }
+    let inferred_user = app.inferred_user().unwrap_or_default();
+    let today = crate::ssh::today();
+    let now = recent::now();
+    let mut previous_group = None;
+    let group_sizes = if group_mode == GroupMode::None {
+        BTreeMap::new()
+    } else {
+        app.group_sizes()
+    };
 
-        content
+    // Field-scoped terms (`user:deploy`) show why each host is listed: what they matched is
+    // highlighted, and the other columns dimmed
+    let query = SearchQuery::parse(app.search.value());
+    let mut backend = (!query.scoped.is_empty()).then(|| app.settings.matcher.backend());
+    let match_style = Style::new()
+        .fg(app.palette.c300)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+
+    let rows = app.hosts.iter().enumerate().map(|(index, host)| {
+        // A `# sshs:color=<color>` directive colors the host name
+        let name_style = host
+            .metadata_value("color")
+            .and_then(|color| Color::from_str(color).ok())
+            .map_or_else(Style::default, |color| Style::default().fg(color));
+        let field_matches = backend
+            .as_mut()
+            .map(|backend| query.field_matches(backend.as_mut(), host));
+
+        let mut cells = columns
             .iter()
-            .map(|content| Cell::from(Text::from(content.to_string())))
-            .collect::<Row>()
+            .map(|column| {
+                let value = column.value(host, &inferred_user);
+                let matches = field_matches
+                    .iter()
+                    .flatten()
+                    .filter(|field_match| field_match.column == *column)
+                    .collect_vec();
+                let dim = if field_matches.is_some() && matches.is_empty() {
+                    Style::new().add_modifier(Modifier::DIM)
+                } else {
+                    Style::new()
+                };
+
+                match column {
+                    // Marked hosts, the targets of a batch run, are checked
+                    Column::Name if app.is_marked(index, host) => {
+                        let mut spans = vec![Span::styled(
+                            "✓ ",
+                            Style::new()
+                                .fg(app.palette.c400)
+                                .add_modifier(Modifier::BOLD),
+                        )];
+                        spans.extend(highlight_matches(value, &matches, name_style, match_style));
+                        spans.extend(certificate_badge(app.certificate_status(host)));
+                        Cell::from(Line::from(spans)).style(dim)
+                    }
+                    Column::Name => {
+                        let mut spans = highlight_matches(value, &matches, name_style, match_style);
+                        spans.extend(certificate_badge(app.certificate_status(host)));
+                        Cell::from(Line::from(spans)).style(name_style.patch(dim))
+                    }
+                    // Dim users that are not set in the SSH configuration
+                    Column::User if host.user.is_none() => {
+                        Cell::from(value).style(Style::new().add_modifier(Modifier::DIM))
+                    }
+                    _ if matches.is_empty() => Cell::from(Text::from(value)).style(dim),
+                    _ => Cell::from(Line::from(highlight_matches(
+                        value,
+                        &matches,
+                        Style::new(),
+                        match_style,
+                    ))),
+                }
+            })
+            .collect_vec();
+
+        // The group name is only shown on the first host of each group
+        if group_mode != GroupMode::None {
+            let group = group_mode.group_of(host);
+            let is_first = previous_group.as_ref() != Some(&group);
+            let cell = if is_first {
+                let size = group_sizes.get(&group).copied().unwrap_or_default();
+                Cell::from(app.group_label(&group, size)).style(Style::new().fg(app.palette.c300))
+            } else {
+                Cell::from("")
+            };
+            cells.insert(0, cell);
+            previous_group = Some(group);
+        }
+        if let Some(checker) = &app.health {
+            cells.insert(0, health_dot(checker, host));
+        }
+        if app.recent_view {
+            let last_used = recent::last_used(&app.state, host);
+            cells.push(Cell::from(recent::format_last_used(last_used, now)));
+        }
+        if app.incident.is_some() {
+            let since = incident::state_changed_at(&app.state, &host.name);
+            cells.push(Cell::from(incident::format_since(since, now)));
+        }
+
+        let row = Row::new(cells);
+
+        // Hosts past their `#_Expires` date are kept visible until they are cleaned up
+        if host.is_expired(today) {
+            row.style(
+                Style::new()
+                    .add_modifier(Modifier::DIM)
+                    .add_modifier(Modifier::CROSSED_OUT),
+            )
+        } else {
+            row
+        }
     });
 
     let bar = " █ ";
@@ -550,18 +2350,117 @@ pub fn render_table(f: &mut Frame, app: &mut App, area: Rect) {
     f.render_stateful_widget(t, area, &mut app.table_state);
 }
 
+/// Reachability of `host`: green, yellow when slow, red when unreachable, hollow until it answers.
+fn health_dot(checker: &HealthChecker, host: &ssh::Host) -> Cell<'static> {
+    let (symbol, color) = match checker.get(&host.name) {
+        Some(health) if health.is_slow() => ("●", tailwind::YELLOW.c500),
+        Some(health) if health.is_reachable() => ("●", tailwind::GREEN.c500),
+        Some(_) => ("●", tailwind::RED.c500),
+        None if checker.is_pending(&host.name) => ("○", tailwind::SLATE.c500),
+        None => ("", Color::Reset),
+    };
+
+    Cell::from(symbol).style(Style::new().fg(color))
+}
+
+/// `value` in `style`, with the characters of the `matches` found in it in `match_style` on top.
+fn highlight_matches(
+    value: String,
+    matches: &[&FieldMatch],
+    style: Style,
+    match_style: Style,
+) -> Vec<Span<'static>> {
+    let mut highlighted = BTreeSet::new();
+    for field_match in matches {
+        // The cell shows the value, or a list of values (the tags)
+        if let Some(position) = value.find(&field_match.value) {
+            let start = value[..position].chars().count();
+            highlighted.extend(field_match.indices.iter().map(|index| start + index));
+        }
+    }
+    if highlighted.is_empty() {
+        return vec![Span::styled(value, style)];
+    }
+
+    value
+        .chars()
+        .enumerate()
+        .chunk_by(|(index, _)| highlighted.contains(index))
+        .into_iter()
+        .map(|(is_match, chars)| {
+            let text: String = chars.map(|(_, c)| c).collect();
+            if is_match {
+                Span::styled(text, style.patch(match_style))
+            } else {
+                Span::styled(text, style)
+            }
+        })
+        .collect()
+}
+
 /// Render the footer with mode indicator
 pub fn render_footer_with_mode(f: &mut Frame, app: &mut App, area: Rect) {
-    let (mode_text, shortcuts_text) = match app.focus_state {
-        crate::ui::app::FocusState::Normal => {
+    let (mode_text, shortcuts_text) = match (app.focus_state, &app.jump_target) {
+        _ if app.session_shown => {
+            let mode = "-- SESSION --";
+            let shortcuts = "keys go to the session | (Ctrl+Q) hosts | (Ctrl+1/2/3) switch tab | (Ctrl+T) rename | (Ctrl+L) lock";
+            (mode, shortcuts.to_string())
+        }
+        _ if app.batch_run.is_some() => {
+            let mode = "-- RUN --";
+            let shortcuts = "(j/k/↑/↓) pick a host to see its output | (esc) close";
+            (mode, shortcuts.to_string())
+        }
+        _ if app.leader_keys.is_some() => {
+            let mode = "-- LEADER --";
+            let shortcuts = "(key) pick from the popup | (backspace) back | (esc) cancel";
+            (mode, shortcuts.to_string())
+        }
+        _ if app.visual_anchor.is_some() => {
+            let mode = "-- VISUAL --";
+            let shortcuts = "(j/k/↑/↓) extend the range | (v) mark it | (esc) cancel";
+            (mode, shortcuts.to_string())
+        }
+        _ if app.sftp.is_some() => {
+            let mode = "-- SFTP --";
+            let shortcuts = "(j/k/↑/↓) navigate | (tab) other side | (enter/l) open | (h) parent | (d) download | (u) upload | (r) refresh | (esc) close";
+            (mode, shortcuts.to_string())
+        }
+        (crate::ui::app::FocusState::Normal, Some(target)) => {
+            let mode = "-- JUMP HOST --";
+            let shortcuts = format!(
+                "pick the jump host for {target} | (j/k/↑/↓) navigate | (/) search | (enter) connect through it | (esc) cancel"
+            );
+            (mode, shortcuts)
+        }
+        (crate::ui::app::FocusState::Normal, None) => {
             let mode = "-- NORMAL --";
-            let shortcuts = "(j/k/↑/↓) navigate | (/) search | (enter) connect | (n) new | (e) edit | (d) delete | (q) quit";
+            let keys = [
+                (Action::AddHost, "new"),
+                (Action::EditHost, "edit"),
+                (Action::DeleteHost, "delete"),
+                (Action::HostDetails, "details"),
+                (Action::CopyPublicKey, "copy key"),
+                (Action::ConnectSocks, "SOCKS"),
+                (Action::ConnectAgent, "agent"),
+                (Action::ConnectJump, "jump"),
+                (Action::ConnectWithCommand, "run"),
+                (Action::Backups, "backups"),
+                (Action::Settings, "settings"),
+            ]
+            .map(|(action, label)| format!("({}) {label}", app.config.keymap.hint(action)));
+            let shortcuts = format!(
+                "(j/k/↑/↓) navigate | ({}) search | (enter) connect | {} | (F10) menu | ({}) quit",
+                app.config.keymap.hint(Action::Search),
+                keys.join(" | "),
+                app.config.keymap.hint(Action::Quit)
+            );
             (mode, shortcuts)
         }
-        crate::ui::app::FocusState::Search => {
+        (crate::ui::app::FocusState::Search, _) => {
             let mode = "-- SEARCH --";
             let shortcuts = "(type to search) | (enter) keep filter | (esc) clear & exit | (Ctrl+F) also opens search";
-            (mode, shortcuts)
+            (mode, shortcuts.to_string())
         }
     };
 
@@ -577,12 +2476,51 @@ pub fn render_footer_with_mode(f: &mut Frame, app: &mut App, area: Rect) {
         Span::styled(shortcuts_text, Style::new().fg(app.palette.c300)),
     ]);
 
-    let info_footer = Paragraph::new(footer_line).centered().block(
-        Block::default()
-            .borders(Borders::ALL)
-            .border_style(Style::new().fg(app.palette.c400))
-            .border_type(BorderType::Rounded),
-    );
+    let mut footer_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::new().fg(app.palette.c400))
+        .border_type(BorderType::Rounded);
+    if let Some(transfer) = &app.transfer {
+        footer_block = footer_block.title(Line::styled(
+            format!(" {} ", transfer.status()),
+            Style::new().fg(tailwind::AMBER.c400),
+        ));
+    }
+    if !app.marked_hosts.is_empty() {
+        footer_block = footer_block.title(Line::styled(
+            format!(" {} marked (E runs, esc clears) ", app.marked_hosts.len()),
+            Style::new().fg(app.palette.c400),
+        ));
+    }
+    let socks_ports = app.socks_ports();
+    if !socks_ports.is_empty() {
+        let endpoints = socks_ports
+            .iter()
+            .map(|port| format!("127.0.0.1:{port}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        footer_block = footer_block.title(Line::styled(
+            format!(" SOCKS {endpoints} "),
+            Style::new().fg(tailwind::EMERALD.c400),
+        ));
+    }
+    if app.presentation {
+        footer_block = footer_block.title(Line::styled(
+            " presentation (p) ",
+            Style::new().fg(app.palette.c400),
+        ));
+    }
+    if let Some(release) = &app.available_update {
+        footer_block = footer_block.title(
+            Line::styled(
+                format!(" sshs {} available (V) ", release.version),
+                Style::new().fg(tailwind::AMBER.c400),
+            )
+            .right_aligned(),
+        );
+    }
+
+    let info_footer = Paragraph::new(footer_line).centered().block(footer_block);
     f.render_widget(info_footer, area);
 }
 
@@ -595,12 +2533,14 @@ pub fn render_footer(f: &mut Frame, app: &mut App, area: Rect) {
 mod tests {
     use super::*;
     use crate::searchable::Searchable;
+    use crate::settings::Settings;
     use crate::ui::app::{App, AppConfig, FocusState};
     use crate::ui::form::AddHostForm;
     use crate::ui::tabs::TabManager;
     use ratatui::backend::TestBackend;
     use ratatui::buffer::Buffer;
     use ratatui::widgets::TableState;
+    use std::time::Duration;
     use tui_input::Input;
 
     /// Test helper to create a minimal app for rendering tests
@@ -611,12 +2551,42 @@ mod tests {
                 "~/.ssh/config".to_string(),
             ],
             search_filter: None,
+            connect_if_unique: false,
             sort_by_name: true,
             show_proxy_command: false,
+            sort_given: false,
+            show_proxy_command_given: false,
             command_template: "ssh {destination}".to_string(),
             command_template_on_session_start: None,
             command_template_on_session_end: None,
+            command_template_on_connection_failure: None,
+            command_template_on_socks: None,
             exit_after_ssh_session_ends: false,
+            socks_port: 1080,
+            default_user: None,
+            dotfiles: Vec::new(),
+            shared_metadata_path: None,
+            connect_delay: Duration::ZERO,
+            end_screen_delay: None,
+            tab_naming: crate::ui::tabs::TabNaming::default(),
+            workspace: None,
+            stats_file: None,
+            audit_log: None,
+            no_exec: false,
+            dns_prefetch: false,
+            health_check_interval: None,
+            unused_after_months: 6,
+            leader: crate::ui::actions::DEFAULT_LEADER,
+            display: crate::config_file::Display::default(),
+            keymap: crate::ui::keymap::Keymap::default(),
+            log_sessions: false,
+            session_log_dir: "/test/logs".to_string(),
+            capabilities: crate::ui::capabilities::Capabilities::default(),
+            triggers: Vec::new(),
+            backend: crate::backend::BackendKind::Exec,
+            state_path: "/test/state.json".to_string(),
+            vault: None,
+            managed_config_path: None,
         };
 
         App {
@@ -641,10 +2611,55 @@ mod tests {
             editing_host_index: None,
             confirm_message: None,
             confirm_action: None,
+            pending_action: None,
+            state: crate::state::State::default(),
             focus_state: FocusState::Normal,
             last_key_time: None,
             pending_g: false,
+            pending_d: false,
+            leader_keys: None,
             tab_manager: TabManager::new(),
+            session_shown: false,
+            session_size: None,
+            resized_at: None,
+            pending_paste: None,
+            backend: Box::new(crate::backend::ExecBackend),
+            menu: None,
+            prompt: None,
+            jump_target: None,
+            backups: None,
+            audit_log: None,
+            timeline: None,
+            background_tunnels: None,
+            socks_units: std::collections::BTreeSet::new(),
+            sftp: None,
+            transfer: None,
+            network: None,
+            marked_hosts: std::collections::BTreeSet::new(),
+            visual_anchor: None,
+            batch_run: None,
+            config_errors: None,
+            settings: Settings::from_flags(true, false),
+            settings_panel: None,
+            collapsed_groups: std::collections::BTreeSet::new(),
+            recent_view: false,
+            state_filter: StateFilter::All,
+            state_counts: health::StateCounts::default(),
+            incident: None,
+            presentation: false,
+            authorized_keys: None,
+            deleted_host: None,
+            show_details: false,
+            details_option: 0,
+            details_help: false,
+            certificates: std::collections::BTreeMap::new(),
+            dns: None,
+            health: None,
+            tag_edit: None,
+            tutorial: None,
+            update_checker: None,
+            available_update: None,
+            show_changelog: false,
         }
     }
 
@@ -807,6 +2822,250 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_inferred_user_is_dimmed() {
+        use crate::ssh::Host;
+
+        let backend = TestBackend::new(80, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        let mut app = create_test_app();
+        app.config.default_user = Some("alice".to_string());
+        app.hosts = Searchable::new(
+            vec![
+                Host {
+                    name: "web".to_string(),
+                    destination: "web.example.com".to_string(),
+                    ..Default::default()
+                },
+                Host {
+                    name: "db".to_string(),
+                    user: Some("postgres".to_string()),
+                    destination: "db.example.com".to_string(),
+                    ..Default::default()
+                },
+            ],
+            "",
+            |_, _| true,
+        );
+
+        terminal
+            .draw(|f| render_table(f, &mut app, f.area()))
+            .unwrap();
+        let buffer = terminal.backend().buffer().clone();
+
+        let content: String = buffer
+            .content
+            .iter()
+            .map(ratatui::buffer::Cell::symbol)
+            .collect();
+        let style_of = |text: &str| {
+            // Borders are multi-byte, count characters to get the cell index
+            let index = content[..content.find(text).unwrap()].chars().count();
+            buffer.content[index].modifier
+        };
+        assert!(style_of("alice").contains(Modifier::DIM));
+        assert!(!style_of("postgres").contains(Modifier::DIM));
+    }
+
+    #[test]
+    fn test_socks_endpoint_in_the_status_bar() {
+        let backend = TestBackend::new(120, 3);
+        let mut terminal = Terminal::new(backend).unwrap();
+        let mut app = create_test_app();
+
+        terminal
+            .draw(|f| render_footer_with_mode(f, &mut app, f.area()))
+            .unwrap();
+        assert!(!buffer_contains_text(
+            terminal.backend().buffer(),
+            "SOCKS 127"
+        ));
+
+        app.socks_units.insert("sshs-socks-web-eu-1081".to_string());
+        terminal
+            .draw(|f| render_footer_with_mode(f, &mut app, f.area()))
+            .unwrap();
+        assert!(buffer_contains_text(
+            terminal.backend().buffer(),
+            "SOCKS 127.0.0.1:1081"
+        ));
+    }
+
+    #[test]
+    fn test_health_column() {
+        use crate::ssh::Host;
+
+        let backend = TestBackend::new(80, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        let mut app = create_test_app();
+        let hosts = vec![Host {
+            name: "web".to_string(),
+            destination: "127.0.0.1".to_string(),
+            port: Some("1".to_string()),
+            ..Default::default()
+        }];
+        let mut checker = HealthChecker::new(None);
+        checker.refresh(&hosts, std::time::Instant::now());
+        app.health = Some(checker);
+        app.hosts = Searchable::new(hosts, "", |_, _| true);
+        app.calculate_table_columns_constraints();
+
+        terminal
+            .draw(|f| render_table(f, &mut app, f.area()))
+            .unwrap();
+        let content: String = terminal
+            .backend()
+            .buffer()
+            .content
+            .iter()
+            .map(ratatui::buffer::Cell::symbol)
+            .collect();
+        assert!(content.contains("Up  Name"));
+        assert!(content.contains("○   web"));
+    }
+
+    #[test]
+    fn test_field_scoped_search_highlights_the_matched_cells() {
+        use crate::ssh::Host;
+
+        let backend = TestBackend::new(80, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        let mut app = create_test_app();
+        app.hosts = Searchable::new(
+            vec![Host {
+                name: "web".to_string(),
+                user: Some("deploy".to_string()),
+                destination: "10.0.0.5".to_string(),
+                ..Default::default()
+            }],
+            "",
+            |_, _| true,
+        );
+
+        let mut modifier_of = |search: &str, text: &str| {
+            app.search = search.to_string().into();
+            terminal
+                .draw(|f| render_table(f, &mut app, f.area()))
+                .unwrap();
+            let buffer = terminal.backend().buffer().clone();
+            let content: String = buffer
+                .content
+                .iter()
+                .map(ratatui::buffer::Cell::symbol)
+                .collect();
+            let index = content[..content.find(text).unwrap()].chars().count();
+            buffer.content[index].modifier
+        };
+
+        assert!(modifier_of("user:dep", "deploy").contains(Modifier::UNDERLINED));
+        assert!(!modifier_of("user:dep", "loy").contains(Modifier::UNDERLINED));
+        assert!(modifier_of("user:dep", "10.0.0.5").contains(Modifier::DIM));
+        assert!(!modifier_of("user:dep", "deploy").contains(Modifier::DIM));
+
+        // Cleared with the filter
+        assert!(modifier_of("", "deploy").is_empty());
+        assert!(modifier_of("", "10.0.0.5").is_empty());
+    }
+
+    #[test]
+    fn test_host_details_show_certificate() {
+        use crate::certificate::Certificate;
+        use crate::ssh::Host;
+
+        let backend = TestBackend::new(100, 30);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        let mut app = create_test_app();
+        app.hosts = Searchable::new(
+            vec![Host {
+                name: "bastion".to_string(),
+                destination: "bastion.example.com".to_string(),
+                certificate_file: Some("~/.ssh/ca-cert.pub".to_string()),
+                ..Default::default()
+            }],
+            "",
+            |_, _| true,
+        );
+        app.certificates.insert(
+            "~/.ssh/ca-cert.pub".to_string(),
+            Ok(Certificate {
+                key_id: "me@example.com".to_string(),
+                principals: vec!["me".to_string(), "deploy".to_string()],
+                validity: "from 2020-01-01T00:00:00 to 2020-02-01T00:00:00".to_string(),
+                valid_until: Some(0),
+            }),
+        );
+        app.table_state.select(Some(0));
+        app.show_details = true;
+
+        terminal.draw(|f| render_main_ui(f, &mut app)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        assert!(buffer_contains_text(buffer, "bastion ⚠"));
+        assert!(buffer_contains_text(buffer, "~/.ssh/ca-cert.pub"));
+        assert!(buffer_contains_text(buffer, "me, deploy"));
+        assert!(buffer_contains_text(buffer, "⚠ expired"));
+
+        app.hosts = Searchable::new(
+            vec![Host {
+                name: "web".to_string(),
+                destination: "web.example.com".to_string(),
+                proxy_command: Some("ssh -W %h:%p %j".to_string()),
+                ..Default::default()
+            }],
+            "",
+            |_, _| true,
+        );
+        terminal.draw(|f| render_main_ui(f, &mut app)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        assert!(buffer_contains_text(buffer, "ssh -W web.example.com:22 %j"));
+        assert!(buffer_contains_text(
+            buffer,
+            "⚠ %j in ProxyCommand cannot be expanded"
+        ));
+    }
+
+    #[test]
+    fn test_presentation_hides_destinations() {
+        use crate::ssh::Host;
+
+        let backend = TestBackend::new(100, 30);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        let mut app = create_test_app();
+        app.hosts = Searchable::new(
+            vec![Host {
+                name: "web".to_string(),
+                destination: "web.example.com".to_string(),
+                user: Some("deploy".to_string()),
+                tags: vec!["prod".to_string()],
+                ..Default::default()
+            }],
+            "",
+            |_, _| true,
+        );
+        app.table_state.select(Some(0));
+        app.presentation = true;
+        app.calculate_table_columns_constraints();
+
+        terminal.draw(|f| render_main_ui(f, &mut app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        assert!(buffer_contains_text(buffer, "prod"));
+        assert!(buffer_contains_text(buffer, "presentation (p)"));
+        assert!(!buffer_contains_text(buffer, "web.example.com"));
+        assert!(!buffer_contains_text(buffer, "deploy"));
+
+        app.show_details = true;
+        terminal.draw(|f| render_main_ui(f, &mut app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        assert!(buffer_contains_text(buffer, PRESENTATION_MASK));
+        assert!(!buffer_contains_text(buffer, "web.example.com"));
+    }
+
     /// Helper function to check if a buffer contains specific text
     fn buffer_contains_text(buffer: &Buffer, text: &str) -> bool {
         let content: String = buffer
@@ -835,6 +3094,7 @@ mod tests {
             port: None,
             aliases: String::new(),
             proxy_command: None,
+            ..Default::default()
         };
         let host2 = Host {
             name: "dev-db".to_string(),
@@ -843,6 +3103,7 @@ mod tests {
             port: None,
             aliases: String::new(),
             proxy_command: None,
+            ..Default::default()
         };
 
         app.tab_manager.add_session(host1).unwrap();
@@ -871,6 +3132,24 @@ mod tests {
         assert!(content.contains("[2:dev-db]"), "Should contain second tab");
         assert!(content.contains("▶"), "Should show current tab indicator");
         assert!(content.contains("Ctrl+N"), "Should show instructions");
+
+        // Tabs are colored by environment: prod-web red, dev-db green
+        assert_eq!(buffer[(0, 0)].bg, tailwind::RED.c500);
+        assert_eq!(buffer[(13, 0)].symbol(), "[");
+        assert_eq!(buffer[(13, 0)].fg, tailwind::GREEN.c500);
+
+        // The production session is the current tab: the whole interface is framed in red
+        app.settings.prod_border = true;
+        let mut terminal = Terminal::new(TestBackend::new(80, 14)).unwrap();
+        terminal.draw(|f| super::ui(f, &mut app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        assert_eq!(buffer[(0, 0)].symbol(), "┏");
+        assert_eq!(buffer[(0, 0)].fg, tailwind::RED.c500);
+        assert_eq!(buffer[(1, 1)].symbol(), "▶");
+
+        app.tab_manager.switch_to_session(2);
+        terminal.draw(|f| super::ui(f, &mut app)).unwrap();
+        assert_ne!(terminal.backend().buffer()[(0, 0)].symbol(), "┏");
     }
 
     #[test]
@@ -0,0 +1,693 @@
+//! Sessions of the tabs, run in a pseudo-terminal owned by sshs so that several of them run at
+//! once, or in a channel of a connection made by sshs itself (`backend::InProcessBackend`). What a session prints goes through a vt100 parser, whose screen is drawn under the tab
+//! bar; the keys pressed while its tab is shown are written to it. The bytes it prints are
+//! counted for the throughput sparkline of its tab, and its lines matched against the patterns
+//! of the triggers.
+
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use regex::RegexSet;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::health::SPARKLINE_LEVELS;
+
+/// Lines kept above the screen by the parser
+const SCROLLBACK_LINES: usize = 1000;
+
+/// Seconds of throughput shown by the sparkline of a tab
+const THROUGHPUT_SAMPLES: usize = 8;
+
+/// Longest line matched against the triggers, the rest of a longer line is ignored
+const MAX_LINE_BYTES: usize = 4096;
+
+/// What runs behind a session: a command in a pseudo-terminal, or a channel of a connection.
+pub trait SessionProcess: Send + fmt::Debug {
+    /// Tells the program of the session that its terminal is now `rows` x `cols`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the session is no longer running.
+    fn resize(&mut self, rows: u16, cols: u16) -> Result<()>;
+
+    /// Exit code once the session has ended, as [`PtySession::poll`].
+    fn try_wait(&mut self) -> Option<Option<i32>>;
+
+    /// Ends the session.
+    fn kill(&mut self);
+}
+
+/// A command running in a pseudo-terminal, e.g. `ssh`.
+struct PtyCommand {
+    master: Box<dyn MasterPty + Send>,
+    child: Box<dyn Child + Send + Sync>,
+}
+
+impl fmt::Debug for PtyCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PtyCommand")
+            .field("child", &self.child)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SessionProcess for PtyCommand {
+    fn resize(&mut self, rows: u16, cols: u16) -> Result<()> {
+        self.master.resize(size(rows, cols))
+    }
+
+    fn try_wait(&mut self) -> Option<Option<i32>> {
+        let status = self.child.try_wait().ok()??;
+        Some(if status.signal().is_some() {
+            None
+        } else {
+            i32::try_from(status.exit_code()).ok()
+        })
+    }
+
+    fn kill(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+/// A session drawn in a tab: what it prints goes through the parser, the keys are written to it.
+pub struct PtySession {
+    parser: Arc<Mutex<vt100::Parser>>,
+    /// Bytes printed so far, counted by the reader thread
+    received: Arc<AtomicU64>,
+    lines: Arc<Mutex<LineWatch>>,
+    /// Lines matching a pattern of [`PtySession::watch`], with the index of the pattern
+    matched_lines: Receiver<(usize, String)>,
+    writer: Box<dyn Write + Send>,
+    process: Box<dyn SessionProcess>,
+    /// Told by the reader thread when the output ends
+    closed: Receiver<()>,
+    output_ended: bool,
+}
+
+impl fmt::Debug for PtySession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PtySession")
+            .field("process", &self.process)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PtySession {
+    /// Starts `args` in a new pseudo-terminal of `rows` x `cols`, with a thread copying what it
+    /// prints to the parser.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the pseudo-terminal cannot be opened or the command cannot start.
+    pub fn spawn(args: &[String], rows: u16, cols: u16) -> Result<PtySession> {
+        let (program, args) = args
+            .split_first()
+            .ok_or_else(|| anyhow!("No command to run"))?;
+        let pair = native_pty_system().openpty(size(rows, cols))?;
+
+        let mut command = CommandBuilder::new(program);
+        command.args(args);
+        // What the parser understands, whatever sshs itself runs in
+        command.env("TERM", "xterm-256color");
+        if let Ok(dir) = std::env::current_dir() {
+            command.cwd(dir);
+        }
+        let child = pair.slave.spawn_command(command)?;
+        // The output only ends once the command holds the last end of the terminal
+        drop(pair.slave);
+
+        let reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.take_writer()?;
+        let process = PtyCommand {
+            master: pair.master,
+            child,
+        };
+        Ok(PtySession::new(
+            reader,
+            writer,
+            Box::new(process),
+            rows,
+            cols,
+        ))
+    }
+
+    /// A session of `rows` x `cols` printing what `reader` reads, and getting the keys through
+    /// `writer`, with a thread copying the output to the parser.
+    #[must_use]
+    pub fn new(
+        mut reader: Box<dyn Read + Send>,
+        writer: Box<dyn Write + Send>,
+        process: Box<dyn SessionProcess>,
+        rows: u16,
+        cols: u16,
+    ) -> PtySession {
+        let parser = Arc::new(Mutex::new(vt100::Parser::new(rows, cols, SCROLLBACK_LINES)));
+        let (sender, closed) = mpsc::channel();
+        let output = Arc::clone(&parser);
+        let received = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&received);
+        let (matches, matched_lines) = mpsc::channel();
+        let lines = Arc::new(Mutex::new(LineWatch::new(matches)));
+        let watch = Arc::clone(&lines);
+        thread::spawn(move || {
+            let mut buffer = [0; 8192];
+            loop {
+                match reader.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => {
+                        lock(&output).process(&buffer[..read]);
+                        counter.fetch_add(read as u64, Ordering::Relaxed);
+                        lock(&watch).feed(&buffer[..read]);
+                    }
+                }
+            }
+            let _ = sender.send(());
+        });
+
+        PtySession {
+            parser,
+            received,
+            lines,
+            matched_lines,
+            writer,
+            process,
+            closed,
+            output_ended: false,
+        }
+    }
+
+    /// The terminal of the session, with the screen to draw.
+    pub fn parser(&self) -> MutexGuard<'_, vt100::Parser> {
+        lock(&self.parser)
+    }
+
+    /// What the session printed that the parser still holds, the scrollback then the screen,
+    /// a line per row, without the empty rows at the bottom. With `formatted`, the lines keep
+    /// their colors as escape sequences.
+    #[must_use]
+    pub fn scrollback(&self, formatted: bool) -> Vec<u8> {
+        let mut parser = self.parser();
+        let shown = parser.screen().scrollback();
+        let (rows, cols) = parser.screen().size();
+
+        // The parser shows one screen at a time, scrolled back no further than its height: a
+        // screen as high as the scrollback shows all of it at once
+        parser.set_scrollback(usize::MAX);
+        let depth = parser.screen().scrollback();
+        parser.set_size(
+            rows.saturating_add(u16::try_from(depth).unwrap_or(u16::MAX)),
+            cols,
+        );
+        parser.set_scrollback(depth);
+        let screen = parser.screen();
+        let mut lines: Vec<Vec<u8>> = if formatted {
+            screen.rows_formatted(0, cols).collect()
+        } else {
+            screen.rows(0, cols).map(String::into_bytes).collect()
+        };
+        parser.set_size(rows, cols);
+        parser.set_scrollback(shown);
+
+        while lines
+            .last()
+            .is_some_and(|line| line.trim_ascii().is_empty())
+        {
+            lines.pop();
+        }
+        let mut output = lines.join(&b'\n');
+        output.push(b'\n');
+        if formatted {
+            // Back to the default colors
+            output.extend_from_slice(b"\x1b[m");
+        }
+        output
+    }
+
+    /// Bytes the session printed since it started.
+    #[must_use]
+    pub fn bytes_received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    /// Matches the lines the session prints from now on against `patterns`.
+    pub fn watch(&mut self, patterns: RegexSet) {
+        lock(&self.lines).patterns = Some(patterns);
+    }
+
+    /// The lines printed since the last call that match a pattern of [`PtySession::watch`],
+    /// with the index of the pattern, once per pattern matched.
+    #[must_use]
+    pub fn matched_lines(&self) -> Vec<(usize, String)> {
+        self.matched_lines.try_iter().collect()
+    }
+
+    /// Writes `bytes` to the session, as if typed.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the session is no longer running.
+    pub fn send_input(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes)?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Writes `text` to the session as pasted: its newlines as the Enter key, between the
+    /// bracketed paste markers when the program in the session asked for them.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the session is no longer running.
+    pub fn paste(&mut self, text: &str) -> Result<()> {
+        let bracketed = self.parser().screen().bracketed_paste();
+        let text = text.replace("\r\n", "\r").replace('\n', "\r");
+
+        let mut bytes = Vec::new();
+        if bracketed {
+            bytes.extend_from_slice(b"\x1b[200~");
+        }
+        bytes.extend_from_slice(text.as_bytes());
+        if bracketed {
+            bytes.extend_from_slice(b"\x1b[201~");
+        }
+        self.send_input(&bytes)
+    }
+
+    /// Resizes the terminal of the session, which is told about it, when it is not `rows` x
+    /// `cols` already.
+    pub fn resize(&mut self, rows: u16, cols: u16) {
+        if rows == 0 || cols == 0 || self.parser().screen().size() == (rows, cols) {
+            return;
+        }
+
+        self.parser().set_size(rows, cols);
+        let _ = self.process.resize(rows, cols);
+    }
+
+    /// Exit code of the session once it has ended, `None` in the inner option when it was
+    /// killed by a signal.
+    pub fn poll(&mut self) -> Option<Option<i32>> {
+        if !self.output_ended {
+            self.output_ended = self.closed.try_recv().is_ok();
+            if !self.output_ended {
+                return None;
+            }
+        }
+
+        self.process.try_wait()
+    }
+}
+
+impl Drop for PtySession {
+    fn drop(&mut self) {
+        // Closing a tab ends its session
+        self.process.kill();
+    }
+}
+
+/// Bytes per second a session printed, over its last seconds.
+#[derive(Debug, Default)]
+pub struct Throughput {
+    /// Bytes per second, oldest first
+    samples: VecDeque<u64>,
+    total: u64,
+    sampled_at: Option<Instant>,
+}
+
+impl Throughput {
+    /// Adds the rate since the previous sample, from the `total` bytes printed at `now`, once a
+    /// second at most.
+    pub fn sample(&mut self, total: u64, now: Instant) {
+        if let Some(sampled_at) = self.sampled_at {
+            let elapsed = now.duration_since(sampled_at);
+            if elapsed < Duration::from_secs(1) {
+                return;
+            }
+
+            let bytes = u128::from(total.saturating_sub(self.total));
+            let rate = bytes * 1000 / elapsed.as_millis().max(1);
+            self.samples
+                .push_back(u64::try_from(rate).unwrap_or(u64::MAX));
+            if self.samples.len() > THROUGHPUT_SAMPLES {
+                self.samples.pop_front();
+            }
+        }
+
+        self.total = total;
+        self.sampled_at = Some(now);
+    }
+
+    /// Bytes per second during the last second.
+    #[must_use]
+    pub fn rate(&self) -> u64 {
+        self.samples.back().copied().unwrap_or_default()
+    }
+
+    /// A block per second, as high as its rate next to the highest one, empty while the
+    /// session printed nothing.
+    #[must_use]
+    pub fn sparkline(&self) -> String {
+        let max = self.samples.iter().copied().max().unwrap_or_default();
+        if max == 0 {
+            return String::new();
+        }
+
+        let last = SPARKLINE_LEVELS.len() as u64 - 1;
+        self.samples
+            .iter()
+            .map(|&rate| {
+                let level = u128::from(rate) * u128::from(last) / u128::from(max);
+                SPARKLINE_LEVELS[usize::try_from(level).unwrap_or_default()]
+            })
+            .collect()
+    }
+}
+
+fn size(rows: u16, cols: u16) -> PtySize {
+    PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Where the reader thread is in an escape sequence, which the triggers do not see
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    Start,
+    /// `ESC [`, up to a final byte
+    Csi,
+    /// `ESC ]`, up to `BEL` or `ESC \`
+    Osc,
+    OscEnd,
+    /// `ESC (` and the like, followed by one byte
+    Charset,
+}
+
+/// Splits the output in lines, without their escape sequences, and sends the ones matching the
+/// patterns.
+struct LineWatch {
+    patterns: Option<RegexSet>,
+    line: Vec<u8>,
+    escape: Escape,
+    matches: Sender<(usize, String)>,
+}
+
+impl LineWatch {
+    fn new(matches: Sender<(usize, String)>) -> LineWatch {
+        LineWatch {
+            patterns: None,
+            line: Vec::new(),
+            escape: Escape::None,
+            matches,
+        }
+    }
+
+    fn feed(&mut self, bytes: &[u8]) {
+        if self.patterns.is_none() {
+            return;
+        }
+
+        for &byte in bytes {
+            self.escape = match (self.escape, byte) {
+                (Escape::None, 0x1b) => Escape::Start,
+                (Escape::None, b'\n') => {
+                    self.end_line();
+                    Escape::None
+                }
+                (Escape::None, b'\t' | 0x20..) => {
+                    if self.line.len() < MAX_LINE_BYTES {
+                        self.line.push(byte);
+                    }
+                    Escape::None
+                }
+                (Escape::Start, b'[') => Escape::Csi,
+                (Escape::Start, b']') => Escape::Osc,
+                (Escape::Start, b'(' | b')' | b'*' | b'+' | b'#') => Escape::Charset,
+                (Escape::Csi, 0x40..=0x7e)
+                | (Escape::None | Escape::Start | Escape::Charset | Escape::OscEnd, _) => {
+                    Escape::None
+                }
+                (Escape::Osc, 0x07) => Escape::None,
+                (Escape::Osc, 0x1b) => Escape::OscEnd,
+                (state @ (Escape::Csi | Escape::Osc), _) => state,
+            };
+        }
+    }
+
+    fn end_line(&mut self) {
+        let line = String::from_utf8_lossy(&self.line).into_owned();
+        self.line.clear();
+        if let Some(patterns) = &self.patterns {
+            for index in patterns.matches(&line) {
+                let _ = self.matches.send((index, line.clone()));
+            }
+        }
+    }
+}
+
+/// What a terminal sends for `key`, `None` for the keys it has no sequence for. With
+/// `application_cursor`, the arrows are sent the way full screen programs asked for them.
+#[must_use]
+pub fn encode_key(key: KeyEvent, application_cursor: bool) -> Option<Vec<u8>> {
+    let cursor = |code: u8| {
+        if application_cursor {
+            vec![0x1b, b'O', code]
+        } else {
+            vec![0x1b, b'[', code]
+        }
+    };
+
+    let mut bytes = match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            vec![control_code(c)?]
+        }
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => cursor(b'A'),
+        KeyCode::Down => cursor(b'B'),
+        KeyCode::Right => cursor(b'C'),
+        KeyCode::Left => cursor(b'D'),
+        KeyCode::Home => cursor(b'H'),
+        KeyCode::End => cursor(b'F'),
+        KeyCode::Insert => b"\x1b[2~".to_vec(),
+        KeyCode::Delete => b"\x1b[3~".to_vec(),
+        KeyCode::PageUp => b"\x1b[5~".to_vec(),
+        KeyCode::PageDown => b"\x1b[6~".to_vec(),
+        KeyCode::F(n @ 1..=4) => vec![0x1b, b'O', b'P' + n - 1],
+        KeyCode::F(n) => {
+            let code = match n {
+                5 => 15,
+                6 => 17,
+                7 => 18,
+                8 => 19,
+                9 => 20,
+                10 => 21,
+                11 => 23,
+                12 => 24,
+                _ => return None,
+            };
+            format!("\x1b[{code}~").into_bytes()
+        }
+        _ => return None,
+    };
+
+    // Alt sends an escape first
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        bytes.insert(0, 0x1b);
+    }
+
+    Some(bytes)
+}
+
+/// Byte sent by `Ctrl` and `c`, e.g. 0x03 for `Ctrl+C`.
+fn control_code(c: char) -> Option<u8> {
+    match c {
+        'a'..='z' | 'A'..='Z' => u8::try_from(c.to_ascii_lowercase())
+            .ok()
+            .map(|c| c - b'a' + 1),
+        ' ' | '@' | '2' => Some(0),
+        '[' | '3' => Some(0x1b),
+        '\\' | '4' => Some(0x1c),
+        ']' | '5' => Some(0x1d),
+        '^' | '6' => Some(0x1e),
+        '_' | '-' | '7' => Some(0x1f),
+        '?' | '8' => Some(0x7f),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn wait_for(session: &mut PtySession, mut done: impl FnMut(&mut PtySession) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(session) {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_encode_key() {
+        let key = |code, modifiers| encode_key(KeyEvent::new(code, modifiers), false);
+
+        assert_eq!(
+            key(KeyCode::Char('é'), KeyModifiers::NONE).unwrap(),
+            "é".as_bytes()
+        );
+        assert_eq!(key(KeyCode::Char('c'), KeyModifiers::CONTROL).unwrap(), [3]);
+        assert_eq!(key(KeyCode::Char('D'), KeyModifiers::CONTROL).unwrap(), [4]);
+        assert_eq!(
+            key(KeyCode::Char('b'), KeyModifiers::ALT).unwrap(),
+            b"\x1bb"
+        );
+        assert_eq!(key(KeyCode::Enter, KeyModifiers::NONE).unwrap(), b"\r");
+        assert_eq!(key(KeyCode::Up, KeyModifiers::NONE).unwrap(), b"\x1b[A");
+        assert_eq!(key(KeyCode::F(2), KeyModifiers::NONE).unwrap(), b"\x1bOQ");
+        assert_eq!(
+            key(KeyCode::F(12), KeyModifiers::NONE).unwrap(),
+            b"\x1b[24~"
+        );
+        assert_eq!(key(KeyCode::CapsLock, KeyModifiers::NONE), None);
+
+        assert_eq!(
+            encode_key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE), true).unwrap(),
+            b"\x1bOA"
+        );
+    }
+
+    #[test]
+    fn test_scrollback() {
+        let args = ["sh", "-c", "seq 1 30; printf '\\033[31mred\\033[0m\\n'"].map(String::from);
+        let mut session = PtySession::spawn(&args, 10, 40).unwrap();
+        wait_for(&mut session, |session| session.poll().is_some());
+
+        let plain = String::from_utf8(session.scrollback(false)).unwrap();
+        let expected = (1..=30)
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(plain, format!("{expected}\nred\n"));
+        // The screen is shown as it was
+        assert_eq!(session.parser().screen().scrollback(), 0);
+
+        let formatted = session.scrollback(true);
+        assert!(formatted.windows(5).any(|bytes| bytes == b"\x1b[31m"));
+    }
+
+    #[test]
+    fn test_throughput() {
+        let start = Instant::now();
+        let mut throughput = Throughput::default();
+        assert_eq!(throughput.sparkline(), "");
+
+        throughput.sample(0, start);
+        // Not a second yet
+        throughput.sample(500, start + Duration::from_millis(500));
+        throughput.sample(1000, start + Duration::from_secs(1));
+        throughput.sample(1000, start + Duration::from_secs(2));
+        throughput.sample(9000, start + Duration::from_secs(4));
+        assert_eq!(throughput.rate(), 4000);
+        assert_eq!(throughput.sparkline(), "▂▁█");
+
+        for second in 5..20 {
+            throughput.sample(9000, start + Duration::from_secs(second));
+        }
+        assert_eq!(throughput.rate(), 0);
+        assert_eq!(throughput.sparkline(), "");
+    }
+
+    #[test]
+    fn test_line_watch() {
+        let (matches, matched_lines) = mpsc::channel();
+        let mut watch = LineWatch::new(matches);
+        watch.feed(b"ERROR before the patterns\n");
+        watch.patterns = Some(RegexSet::new(["^ERROR", "panic"]).unwrap());
+
+        watch.feed(b"\x1b[31mERR");
+        watch.feed(b"OR\x1b[0m: disk full\r\n\x1b]0;title\x07ok\n");
+        watch.feed(b"\x1b(Bkernel panic\n");
+        assert_eq!(
+            matched_lines.try_iter().collect::<Vec<_>>(),
+            [
+                (0, "ERROR: disk full".to_string()),
+                (1, "kernel panic".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_paste() {
+        let args = [
+            "sh",
+            "-c",
+            "read a; read b; printf 'got %s %s\\n' \"$a\" \"$b\"",
+        ];
+        let mut session = PtySession::spawn(&args.map(String::from), 10, 40).unwrap();
+        session.paste("one\r\ntwo\n").unwrap();
+        wait_for(&mut session, |session| {
+            session.parser().screen().contents().contains("got one two")
+        });
+
+        // Programs asking for bracketed paste get the markers around the text
+        let args = ["sh", "-c", "printf '\\033[?2004h'; cat -v"];
+        let mut session = PtySession::spawn(&args.map(String::from), 10, 40).unwrap();
+        wait_for(&mut session, |session| {
+            session.parser().screen().bracketed_paste()
+        });
+        session.paste("hi\n").unwrap();
+        wait_for(&mut session, |session| {
+            session.parser().screen().contents().contains("^[[200~hi")
+        });
+    }
+
+    #[test]
+    fn test_session_runs_in_a_terminal() {
+        let args = [
+            "sh",
+            "-c",
+            "read line; printf 'got %s\\n' \"$line\"; exit 3",
+        ]
+        .map(String::from);
+        let mut session = PtySession::spawn(&args, 10, 40).unwrap();
+
+        session.send_input(b"hello\r").unwrap();
+        wait_for(&mut session, |session| {
+            session.parser().screen().contents().contains("got hello")
+        });
+
+        let mut exit = None;
+        wait_for(&mut session, |session| {
+            exit = session.poll();
+            exit.is_some()
+        });
+        assert_eq!(exit, Some(Some(3)));
+        assert!(session.bytes_received() > 0);
+        assert_eq!(session.scrollback(false), b"hello\ngot hello\n");
+
+        session.resize(20, 60);
+        assert_eq!(session.parser().screen().size(), (20, 60));
+    }
+}
@@ -0,0 +1,164 @@
+//! Recorded sessions: with `--log-sessions` (or `# sshs:log=yes`), everything a session prints is
+//! copied to a log file of its host, `<dir>/<host>/<date>_<time>.log`, opened later in `$PAGER`.
+//! Without it, what a session still has on screen and in its scrollback can be exported.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::ssh;
+
+/// Directory of the session logs, one subdirectory per host
+pub const DEFAULT_SESSION_LOG_DIR: &str = "~/.local/share/sshs/logs";
+
+/// Directory of the logs of `host_name` in `dir`. The characters a file name cannot hold are
+/// replaced.
+#[must_use]
+pub fn host_dir(dir: &str, host_name: &str) -> PathBuf {
+    PathBuf::from(shellexpand::tilde(dir).as_ref()).join(file_name(host_name))
+}
+
+fn file_name(host_name: &str) -> String {
+    let name: String = host_name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | '@') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    // No hidden file, nor `..`
+    match name.trim_start_matches('.') {
+        "" => "_".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// Log file of a session of `host_name` started at `time` (seconds since the epoch, in UTC), its
+/// directory created.
+///
+/// # Errors
+///
+/// Will return `Err` if the directory cannot be created.
+pub fn new_log(dir: &str, host_name: &str, time: i64) -> Result<PathBuf> {
+    let dir = host_dir(dir, host_name);
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    Ok(dir.join(format!("{}.log", file_stamp(time))))
+}
+
+/// File the output of a session of `host_name` is exported to by default at `time`, e.g.
+/// `~/sshs-web-2026-10-16_09-30-00.log`.
+#[must_use]
+pub fn export_path(host_name: &str, time: i64) -> String {
+    format!("~/sshs-{}-{}.log", file_name(host_name), file_stamp(time))
+}
+
+/// Writes the exported `output` of a session to `path`, readable by the user only since it can
+/// hold secrets. Returns the path, `~` expanded.
+///
+/// # Errors
+///
+/// Will return `Err` if the file cannot be written.
+pub fn write_export(path: &str, output: &[u8]) -> Result<PathBuf> {
+    let path = PathBuf::from(shellexpand::tilde(path).as_ref());
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(output))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    Ok(path)
+}
+
+/// e.g. `2026-10-16_09-30-00`, sorted in the order of the sessions.
+fn file_stamp(time: i64) -> String {
+    let seconds = time.rem_euclid(86_400);
+
+    format!(
+        "{}_{:02}-{:02}-{:02}",
+        ssh::format_date(time.div_euclid(86_400)),
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Log of the last recorded session of `host_name`.
+#[must_use]
+pub fn latest(dir: &str, host_name: &str) -> Option<PathBuf> {
+    std::fs::read_dir(host_dir(dir, host_name))
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "log"))
+        .max()
+}
+
+/// Command opening `path` in `$PAGER` (`less -R` by default, which shows the colors of the
+/// session).
+#[must_use]
+pub fn pager_command(path: &Path) -> Command {
+    let pager = std::env::var("PAGER").unwrap_or_default();
+    let mut words = shlex::split(&pager)
+        .filter(|words| !words.is_empty())
+        .unwrap_or_else(|| vec!["less".to_string(), "-R".to_string()]);
+
+    let mut command = Command::new(words.remove(0));
+    command.args(words);
+    command.arg(path);
+
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logs_of_a_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().to_str().unwrap();
+
+        assert_eq!(latest(dir, "web"), None);
+        let first = new_log(dir, "web", 1_792_143_000).unwrap();
+        assert!(first.ends_with("web/2026-10-16_09-30-00.log"));
+        let second = new_log(dir, "web", 1_792_143_061).unwrap();
+        for log in [&first, &second] {
+            std::fs::write(log, "$ uptime\n").unwrap();
+        }
+        std::fs::write(second.with_extension("txt"), "").unwrap();
+        assert_eq!(latest(dir, "web"), Some(second));
+
+        assert!(host_dir(dir, "../db prod").ends_with("_db_prod"));
+        assert_eq!(latest(dir, "db"), None);
+    }
+
+    #[test]
+    fn test_export() {
+        assert_eq!(
+            export_path("db/prod", 1_792_143_000),
+            "~/sshs-db_prod-2026-10-16_09-30-00.log"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.log");
+        let written = write_export(path.to_str().unwrap(), b"$ uptime\n").unwrap();
+        assert_eq!(std::fs::read(&written).unwrap(), b"$ uptime\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&written).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Summary of an SSH session, shown once it ends.
+
+use std::time::Duration;
+
+/// `ssh` exits with 255 when the connection itself fails; other codes come from the remote shell
+const SSH_CONNECTION_ERROR: i32 = 255;
+
+/// Values of the session given to the session end and connection failure templates, as
+/// `{{exit_code}}` and as `SSHS_EXIT_CODE` in their environment
+pub const TEMPLATE_VALUES: [&str; 5] = [
+    "exit_code",
+    "duration",
+    "duration_seconds",
+    "error",
+    "tab_name",
+];
+
+/// What to do after the session summary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryAction {
+    Return,
+    Reconnect,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionSummary {
+    pub host: String,
+    pub destination: String,
+    pub duration: Duration,
+    /// `None` when `ssh` could not be run, or was killed by a signal
+    pub exit_code: Option<i32>,
+    /// Why `ssh` could not be run
+    pub error: Option<String>,
+}
+
+impl SessionSummary {
+    /// The connection failed, as opposed to a session ending with a non-zero exit code.
+    #[must_use]
+    pub fn is_connection_error(&self) -> bool {
+        self.error.is_some() || self.exit_code == Some(SSH_CONNECTION_ERROR)
+    }
+
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// Session duration, e.g. `1h 02m 03s`.
+    #[must_use]
+    pub fn formatted_duration(&self) -> String {
+        let seconds = self.duration.as_secs();
+        let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+        if hours > 0 {
+            format!("{hours}h {minutes:02}m {seconds:02}s")
+        } else if minutes > 0 {
+            format!("{minutes}m {seconds:02}s")
+        } else {
+            format!("{seconds}s")
+        }
+    }
+
+    #[must_use]
+    pub fn formatted_exit_code(&self) -> String {
+        match (&self.error, self.exit_code) {
+            (Some(error), _) => error.clone(),
+            (None, Some(code)) => code.to_string(),
+            (None, None) => "killed by a signal".to_string(),
+        }
+    }
+
+    /// The [`TEMPLATE_VALUES`] of the session. `exit_code` and `error` are empty when unknown,
+    /// and `tab_name` is the name given to the tabs of the host, if any.
+    #[must_use]
+    pub fn template_values(&self, tab_name: Option<&str>) -> Vec<(&'static str, String)> {
+        let values = [
+            self.exit_code
+                .map(|code| code.to_string())
+                .unwrap_or_default(),
+            self.formatted_duration(),
+            self.duration.as_secs().to_string(),
+            self.error.clone().unwrap_or_default(),
+            tab_name.unwrap_or_default().to_string(),
+        ];
+
+        TEMPLATE_VALUES.into_iter().zip(values).collect()
+    }
+
+    /// One-line summary, copied to the clipboard.
+    #[must_use]
+    pub fn text(&self) -> String {
+        format!(
+            "ssh {} ({}): {}, exit code {}",
+            self.host,
+            self.destination,
+            self.formatted_duration(),
+            self.formatted_exit_code()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(duration: u64, exit_code: Option<i32>) -> SessionSummary {
+        SessionSummary {
+            host: "web".to_string(),
+            destination: "web.example.com".to_string(),
+            duration: Duration::from_secs(duration),
+            exit_code,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_session_summary() {
+        assert_eq!(summary(7, Some(0)).formatted_duration(), "7s");
+        assert_eq!(summary(125, Some(0)).formatted_duration(), "2m 05s");
+        assert_eq!(summary(3723, Some(0)).formatted_duration(), "1h 02m 03s");
+
+        let ended = summary(125, Some(130));
+        assert!(!ended.is_success());
+        assert!(!ended.is_connection_error());
+        assert_eq!(
+            ended.text(),
+            "ssh web (web.example.com): 2m 05s, exit code 130"
+        );
+
+        assert!(summary(1, Some(255)).is_connection_error());
+        assert_eq!(summary(1, None).formatted_exit_code(), "killed by a signal");
+
+        assert_eq!(
+            ended.template_values(Some("api")),
+            [
+                ("exit_code", "130".to_string()),
+                ("duration", "2m 05s".to_string()),
+                ("duration_seconds", "125".to_string()),
+                ("error", String::new()),
+                ("tab_name", "api".to_string()),
+            ]
+        );
+    }
+}
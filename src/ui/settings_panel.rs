@@ -0,0 +1,162 @@
+//! Settings overlay, changing the display preferences at runtime.
+
+use crate::settings::{cycle, Column, GroupMode, Matcher, Settings, SortMode, Theme};
+
+/// A line of the settings panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Sort,
+    Group,
+    Column(Column),
+    AliasRows,
+    FactTags,
+    SystemHosts,
+    QuickDelete,
+    ProdBorder,
+    UpdateCheck,
+    Theme,
+    Matcher,
+}
+
+pub const FIELDS: &[Field] = &[
+    Field::Sort,
+    Field::Group,
+    Field::Column(Column::Aliases),
+    Field::Column(Column::User),
+    Field::Column(Column::Destination),
+    Field::Column(Column::Port),
+    Field::Column(Column::Tags),
+    Field::Column(Column::Proxy),
+    Field::Column(Column::Identity),
+    Field::Column(Column::Jump),
+    Field::AliasRows,
+    Field::FactTags,
+    Field::SystemHosts,
+    Field::QuickDelete,
+    Field::ProdBorder,
+    Field::UpdateCheck,
+    Field::Theme,
+    Field::Matcher,
+];
+
+impl Field {
+    #[must_use]
+    pub fn label(self) -> String {
+        match self {
+            Field::Sort => "Sort by".to_string(),
+            Field::Group => "Group by".to_string(),
+            Field::AliasRows => "Alias rows".to_string(),
+            Field::FactTags => "Fact tags".to_string(),
+            Field::Column(column) => format!("{} column", column.title()),
+            Field::SystemHosts => "System hosts".to_string(),
+            Field::QuickDelete => "Delete with dd".to_string(),
+            Field::ProdBorder => "Prod border".to_string(),
+            Field::UpdateCheck => "Update check".to_string(),
+            Field::Theme => "Theme".to_string(),
+            Field::Matcher => "Search".to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn value(self, settings: &Settings) -> &'static str {
+        match self {
+            Field::Sort => settings.sort.label(),
+            Field::Group => settings.group.label(),
+            Field::AliasRows if settings.alias_rows => "one per alias",
+            Field::FactTags if settings.fact_tags => "added (I gathers the facts)",
+            Field::Column(column) if settings.is_column_shown(column) => "shown",
+            Field::Column(_) => "hidden",
+            Field::SystemHosts if settings.skip_system_config => "not loaded",
+            Field::SystemHosts => "loaded",
+            Field::QuickDelete if settings.quick_delete => "at once, undo with u",
+            Field::ProdBorder if settings.prod_border => "in production tabs",
+            Field::UpdateCheck if settings.check_updates => "daily",
+            Field::AliasRows
+            | Field::FactTags
+            | Field::QuickDelete
+            | Field::ProdBorder
+            | Field::UpdateCheck => "off",
+            Field::Theme => settings.theme.label(),
+            Field::Matcher => settings.matcher.label(),
+        }
+    }
+
+    /// Switches the field to its next (or previous) value.
+    pub fn change(self, settings: &mut Settings, forward: bool) {
+        match self {
+            Field::Sort => settings.sort = cycle(SortMode::ALL, settings.sort, forward),
+            Field::Group => settings.group = cycle(GroupMode::ALL, settings.group, forward),
+            Field::AliasRows => settings.alias_rows = !settings.alias_rows,
+            Field::FactTags => settings.fact_tags = !settings.fact_tags,
+            Field::Column(column) => settings.toggle_column(column),
+            Field::SystemHosts => settings.skip_system_config = !settings.skip_system_config,
+            Field::QuickDelete => settings.quick_delete = !settings.quick_delete,
+            Field::ProdBorder => settings.prod_border = !settings.prod_border,
+            Field::UpdateCheck => settings.check_updates = !settings.check_updates,
+            Field::Theme => settings.theme = cycle(Theme::ALL, settings.theme, forward),
+            Field::Matcher => settings.matcher = cycle(Matcher::ALL, settings.matcher, forward),
+        }
+    }
+}
+
+/// State of the settings panel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SettingsPanel {
+    pub selected: usize,
+}
+
+impl SettingsPanel {
+    #[must_use]
+    pub fn selected_field(&self) -> Field {
+        FIELDS[self.selected]
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1) % FIELDS.len();
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = (self.selected + FIELDS.len() - 1) % FIELDS.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields_change_settings() {
+        let mut settings = Settings::default();
+        let mut panel = SettingsPanel::default();
+
+        panel.previous();
+        assert_eq!(panel.selected_field(), Field::Matcher);
+        panel.selected_field().change(&mut settings, true);
+        assert_eq!(Field::Matcher.value(&settings), "substring");
+
+        panel.next();
+        panel.next();
+        assert_eq!(panel.selected_field(), Field::Group);
+        panel.selected_field().change(&mut settings, false);
+        assert_eq!(settings.group, GroupMode::File);
+
+        let proxy = Field::Column(Column::Proxy);
+        assert_eq!(proxy.label(), "Proxy column");
+        assert_eq!(proxy.value(&settings), "shown");
+        proxy.change(&mut settings, true);
+        assert_eq!(proxy.value(&settings), "hidden");
+
+        Field::QuickDelete.change(&mut settings, true);
+        assert!(settings.quick_delete);
+        Field::SystemHosts.change(&mut settings, true);
+        assert_eq!(Field::SystemHosts.value(&settings), "not loaded");
+        Field::ProdBorder.change(&mut settings, true);
+        assert_eq!(Field::ProdBorder.value(&settings), "in production tabs");
+        assert_eq!(Field::UpdateCheck.value(&settings), "off");
+        Field::UpdateCheck.change(&mut settings, true);
+        assert!(settings.check_updates);
+        assert_eq!(Field::AliasRows.value(&settings), "off");
+        Field::AliasRows.change(&mut settings, true);
+        assert_eq!(Field::AliasRows.value(&settings), "one per alias");
+    }
+}
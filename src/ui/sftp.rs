@@ -0,0 +1,531 @@
+//! SFTP browser (`f`): the local directory next to a directory of the selected host, to download
+//! and upload files. Directories are listed over a non-interactive `ssh` call; files are copied
+//! by the `sftp` client of the system, in the background, with their progress in the footer.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs;
+use std::io::Write;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::backend::ConnectionBackend;
+use crate::exec;
+use crate::ssh;
+
+/// How often the size of a file being downloaded is read
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A file or directory of a listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub is_dir: bool,
+    /// Size in bytes, 0 for directories
+    pub size: u64,
+}
+
+/// `path` quoted for a POSIX shell.
+fn quote(path: &str) -> String {
+    format!("'{}'", path.replace('\'', r"'\''"))
+}
+
+/// `path` quoted for an `sftp` batch command.
+fn batch_quote(path: &str) -> String {
+    format!("\"{}\"", path.replace('\\', r"\\").replace('"', "\\\""))
+}
+
+/// `name` in the directory `dir`.
+#[must_use]
+pub fn join(dir: &str, name: &str) -> String {
+    if dir.ends_with('/') {
+        format!("{dir}{name}")
+    } else {
+        format!("{dir}/{name}")
+    }
+}
+
+/// Directory containing `path`, `path` itself for the root.
+#[must_use]
+pub fn parent(path: &str) -> String {
+    match path.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) | None => "/".to_string(),
+        Some((parent, _)) => parent.to_string(),
+    }
+}
+
+/// e.g. `512 B`, `1.5 KB`, `12.0 MB`.
+#[must_use]
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Directories first, then by name.
+fn sort(entries: &mut [Entry]) {
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+}
+
+/// Script printing the absolute path of `path`, then a `d 0 name` or `f size name` line per
+/// directory and regular file in it.
+fn list_script(path: &str) -> String {
+    format!(
+        "cd -- {} || exit 1; pwd; \
+         for entry in * .[!.]* ..?*; do \
+         if [ -d \"$entry\" ]; then echo \"d 0 $entry\"; \
+         elif [ -f \"$entry\" ]; then echo \"f $(wc -c < \"$entry\" | tr -d ' ') $entry\"; fi; \
+         done",
+        quote(path)
+    )
+}
+
+/// Reads the output of the listing script: the absolute path and the entries.
+///
+/// # Errors
+///
+/// Will return `Err` if the output does not start with an absolute path.
+pub fn parse_listing(output: &str) -> Result<(String, Vec<Entry>)> {
+    let mut lines = output.lines();
+    let path = lines
+        .next()
+        .filter(|path| path.starts_with('/'))
+        .ok_or_else(|| anyhow!("Unexpected listing: {}", output.trim()))?;
+
+    let mut entries = lines
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ' ');
+            let kind = fields.next()?;
+            let size = fields.next()?.parse().unwrap_or_default();
+            let name = fields.next().filter(|name| !name.is_empty())?;
+
+            Some(Entry {
+                name: name.to_string(),
+                is_dir: kind == "d",
+                size,
+            })
+        })
+        .collect::<Vec<_>>();
+    sort(&mut entries);
+
+    Ok((path.to_string(), entries))
+}
+
+/// Lists `path` on `host`, `.` being the home directory. Returns the absolute path too.
+///
+/// # Errors
+///
+/// Will return `Err` if `host` cannot be reached without a prompt or `path` cannot be listed.
+pub fn list_remote(
+    backend: &dyn ConnectionBackend,
+    host: &ssh::Host,
+    path: &str,
+) -> Result<(String, Vec<Entry>)> {
+    parse_listing(&backend.batch(host, &list_script(path))?)
+}
+
+/// Lists the directories and files of the local directory `path`.
+///
+/// # Errors
+///
+/// Will return `Err` if `path` cannot be read.
+pub fn list_local(path: &str) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(path).with_context(|| format!("Failed to read {path}"))? {
+        let entry = entry?;
+        // Symbolic links are listed as what they point to
+        let Ok(metadata) = fs::metadata(entry.path()) else {
+            continue;
+        };
+        if !metadata.is_dir() && !metadata.is_file() {
+            continue;
+        }
+
+        entries.push(Entry {
+            name: entry.file_name().to_string_lossy().to_string(),
+            is_dir: metadata.is_dir(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+        });
+    }
+    sort(&mut entries);
+
+    Ok(entries)
+}
+
+/// Arguments of the `sftp` command running the batch commands read from its input on `host`.
+/// It fails instead of prompting for a password.
+#[must_use]
+pub fn command(host: &ssh::Host) -> Vec<String> {
+    let address = ssh::unbracket(&host.destination);
+    let address = if ssh::is_ipv6(address) {
+        format!("[{address}]")
+    } else {
+        address.to_string()
+    };
+    let destination = match &host.user {
+        Some(user) => format!("{user}@{address}"),
+        None => address,
+    };
+
+    [
+        "sftp",
+        "-o",
+        "LogLevel=ERROR",
+        "-o",
+        "BatchMode=yes",
+        "-o",
+        "StrictHostKeyChecking=accept-new",
+        "-P",
+        host.port.as_deref().unwrap_or("22"),
+        "-b",
+        "-",
+        "--",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .chain([destination])
+    .collect()
+}
+
+/// Which way a file is copied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Download,
+    Upload,
+}
+
+impl Direction {
+    /// `sftp` batch command copying `remote` to `local`, or the other way around.
+    #[must_use]
+    pub fn batch_command(self, remote: &str, local: &str) -> String {
+        match self {
+            Direction::Download => format!("get {} {}\n", batch_quote(remote), batch_quote(local)),
+            Direction::Upload => format!("put {} {}\n", batch_quote(local), batch_quote(remote)),
+        }
+    }
+}
+
+enum TransferEvent {
+    /// Bytes copied so far
+    Progress(u64),
+    Done(Result<(), String>),
+}
+
+/// A file copied by `sftp` in the background.
+#[derive(Debug)]
+pub struct Transfer {
+    pub direction: Direction,
+    pub name: String,
+    pub size: u64,
+    /// Bytes copied so far, only known for downloads
+    pub transferred: Option<u64>,
+    receiver: mpsc::Receiver<TransferEvent>,
+}
+
+impl Transfer {
+    /// Starts copying the file `name` of `size` bytes between `remote` on `host` and `local`.
+    #[must_use]
+    pub fn start(
+        host: &ssh::Host,
+        direction: Direction,
+        remote: &str,
+        local: &str,
+        size: u64,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let args = command(host);
+        let batch = direction.batch_command(remote, local);
+        let local = local.to_string();
+
+        thread::spawn(move || {
+            let result = run(&args, &batch, || {
+                if direction == Direction::Download {
+                    let written = fs::metadata(&local).map_or(0, |metadata| metadata.len());
+                    let _ = sender.send(TransferEvent::Progress(written));
+                }
+            });
+            let _ = sender.send(TransferEvent::Done(result.map_err(|e| format!("{e:#}"))));
+        });
+
+        Self {
+            direction,
+            name: remote.rsplit('/').next().unwrap_or(remote).to_string(),
+            size,
+            transferred: (direction == Direction::Download).then_some(0),
+            receiver,
+        }
+    }
+
+    /// Takes the progress reported so far. Returns the result once the copy is done.
+    pub fn poll(&mut self) -> Option<Result<(), String>> {
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                TransferEvent::Progress(bytes) => self.transferred = Some(bytes.min(self.size)),
+                TransferEvent::Done(result) => return Some(result),
+            }
+        }
+
+        None
+    }
+
+    /// e.g. `↓ backup.tar.gz 45% of 12.0 MB`, `↑ notes.txt 1.5 KB`.
+    #[must_use]
+    pub fn status(&self) -> String {
+        let arrow = match self.direction {
+            Direction::Download => '↓',
+            Direction::Upload => '↑',
+        };
+
+        match self.transferred {
+            Some(transferred) if self.size > 0 => format!(
+                "{arrow} {} {}% of {}",
+                self.name,
+                transferred * 100 / self.size,
+                format_size(self.size)
+            ),
+            _ => format!("{arrow} {} {}", self.name, format_size(self.size)),
+        }
+    }
+}
+
+/// Runs `sftp` with the `batch` commands, calling `on_tick` every [`PROGRESS_INTERVAL`] while
+/// it runs.
+fn run(args: &[String], batch: &str, mut on_tick: impl FnMut()) -> Result<()> {
+    let mut child = exec::spawn(
+        ssh::command(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped()),
+    )
+    .context("Failed to run sftp")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(batch.as_bytes())?;
+    }
+
+    while child.try_wait()?.is_none() {
+        on_tick();
+        thread::sleep(PROGRESS_INTERVAL);
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "sftp failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+/// One side of the browser.
+#[derive(Debug, Default)]
+pub struct Pane {
+    pub path: String,
+    pub entries: Vec<Entry>,
+    pub selected: usize,
+}
+
+impl Pane {
+    #[must_use]
+    pub fn new(path: String, entries: Vec<Entry>) -> Self {
+        Self {
+            path,
+            entries,
+            selected: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn selected_entry(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Shows the directory `path`, the selection going back to the top unless it stays the same
+    /// directory.
+    pub fn set(&mut self, path: String, entries: Vec<Entry>) {
+        if path == self.path {
+            self.selected = self.selected.min(entries.len().saturating_sub(1));
+        } else {
+            self.selected = 0;
+        }
+        self.path = path;
+        self.entries = entries;
+    }
+}
+
+/// Which side of the browser has the focus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Local,
+    Remote,
+}
+
+/// Local and remote directories side by side.
+#[derive(Debug)]
+pub struct SftpView {
+    pub host: ssh::Host,
+    pub local: Pane,
+    pub remote: Pane,
+    pub side: Side,
+}
+
+impl SftpView {
+    /// Opens the local directory `local_path` and the home directory of `host`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if either directory cannot be listed.
+    pub fn open(
+        backend: &dyn ConnectionBackend,
+        host: ssh::Host,
+        local_path: String,
+    ) -> Result<Self> {
+        let (remote_path, remote_entries) = list_remote(backend, &host, ".")?;
+        let local_entries = list_local(&local_path)?;
+
+        Ok(Self {
+            host,
+            local: Pane::new(local_path, local_entries),
+            remote: Pane::new(remote_path, remote_entries),
+            side: Side::Remote,
+        })
+    }
+
+    pub fn pane(&mut self) -> &mut Pane {
+        match self.side {
+            Side::Local => &mut self.local,
+            Side::Remote => &mut self.remote,
+        }
+    }
+
+    pub fn switch_side(&mut self) {
+        self.side = match self.side {
+            Side::Local => Side::Remote,
+            Side::Remote => Side::Local,
+        };
+    }
+
+    /// Lists the directory `path` on the focused side.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the directory cannot be listed.
+    pub fn change_dir(&mut self, backend: &dyn ConnectionBackend, path: &str) -> Result<()> {
+        match self.side {
+            Side::Local => {
+                let entries = list_local(path)?;
+                self.local.set(path.to_string(), entries);
+            }
+            Side::Remote => {
+                let (path, entries) = list_remote(backend, &self.host, path)?;
+                self.remote.set(path, entries);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lists the current directory of `side` again.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the directory cannot be listed.
+    pub fn refresh(&mut self, backend: &dyn ConnectionBackend, side: Side) -> Result<()> {
+        let focused = self.side;
+        self.side = side;
+        let path = self.pane().path.clone();
+        let result = self.change_dir(backend, &path);
+        self.side = focused;
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listing_and_transfer_commands() {
+        assert_eq!(
+            list_script("it's"),
+            "cd -- 'it'\\''s' || exit 1; pwd; \
+             for entry in * .[!.]* ..?*; do \
+             if [ -d \"$entry\" ]; then echo \"d 0 $entry\"; \
+             elif [ -f \"$entry\" ]; then echo \"f $(wc -c < \"$entry\" | tr -d ' ') $entry\"; fi; \
+             done"
+        );
+
+        let (path, entries) =
+            parse_listing("/home/me\nf 2048 notes.txt\nd 0 logs\nf 12 my file\nf  unreadable\n")
+                .unwrap();
+        assert_eq!(path, "/home/me");
+        assert_eq!(
+            entries.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(),
+            ["logs", "my file", "notes.txt", "unreadable"]
+        );
+        assert!(entries[0].is_dir);
+        assert_eq!(entries[2].size, 2048);
+        assert!(parse_listing("cd: no such directory").is_err());
+
+        assert_eq!(join("/home/me", "logs"), "/home/me/logs");
+        assert_eq!(join("/", "etc"), "/etc");
+        assert_eq!(parent("/home/me/"), "/home");
+        assert_eq!(parent("/home"), "/");
+        assert_eq!(parent("/"), "/");
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(12 * 1024 * 1024 + 1), "12.0 MB");
+
+        let host = ssh::Host {
+            name: "db".to_string(),
+            destination: "fd00::5".to_string(),
+            user: Some("me".to_string()),
+            port: Some("2222".to_string()),
+            ..Default::default()
+        };
+        let args = command(&host);
+        assert_eq!(args[7..9], ["-P", "2222"]);
+        assert_eq!(args.last().unwrap(), "me@[fd00::5]");
+        assert_eq!(
+            Direction::Download.batch_command("/srv/a \"b\".log", "/tmp/a.log"),
+            "get \"/srv/a \\\"b\\\".log\" \"/tmp/a.log\"\n"
+        );
+        assert_eq!(
+            Direction::Upload.batch_command("/srv/a.log", "/tmp/a.log"),
+            "put \"/tmp/a.log\" \"/srv/a.log\"\n"
+        );
+
+        let (_, receiver) = mpsc::channel();
+        let transfer = Transfer {
+            direction: Direction::Download,
+            name: "a.log".to_string(),
+            size: 2048,
+            transferred: Some(512),
+            receiver,
+        };
+        assert_eq!(transfer.status(), "↓ a.log 25% of 2.0 KB");
+    }
+}
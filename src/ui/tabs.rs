@@ -1,17 +1,138 @@
-use crate::ssh::Host;
+use super::session::{PtySession, Throughput};
+use super::triggers::Trigger;
+use crate::ssh::{ConnectOptions, Host};
 use anyhow::Result;
-use std::process::Child;
+use clap::ValueEnum;
+use handlebars::Handlebars;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::Instant;
+use unicode_width::UnicodeWidthChar;
 
 /// Maximum number of concurrent sessions for MVP
 pub const MAX_SESSIONS: usize = 3;
 
+/// Handlebars template of the automatic tab names
+pub const DEFAULT_TAB_NAME_TEMPLATE: &str = "{{index}}:{{name}}";
+
+/// Which part of a tab name too long for the tab is cut
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Truncation {
+    /// Keep the beginning, e.g. `prod-data…`
+    End,
+    /// Keep both ends, which tells similar host names apart, e.g. `prod-…-eu-01`
+    #[default]
+    Middle,
+}
+
+/// How tabs are named: the template of the automatic names and their maximum width
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabNaming {
+    /// Handlebars template, with `index`, `name`, `user`, `destination` and `port`
+    pub template: String,
+    /// Maximum width of a name in columns, `None` for no limit
+    pub max_width: Option<usize>,
+    pub truncation: Truncation,
+}
+
+impl Default for TabNaming {
+    fn default() -> Self {
+        Self {
+            template: DEFAULT_TAB_NAME_TEMPLATE.to_string(),
+            max_width: None,
+            truncation: Truncation::default(),
+        }
+    }
+}
+
+impl TabNaming {
+    /// Automatic name of the tab of session `index` on `host`. An invalid template falls back
+    /// to the default one.
+    #[must_use]
+    pub fn render(&self, index: usize, host: &Host) -> String {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+
+        let data = json!({
+            "index": index,
+            "name": host.name,
+            "user": host.user.as_deref().unwrap_or_default(),
+            "destination": host.destination,
+            "port": host.port.as_deref().unwrap_or_default(),
+        });
+
+        handlebars
+            .render_template(&self.template, &data)
+            .unwrap_or_else(|_| format!("{index}:{}", host.name))
+    }
+
+    /// Cuts `name` down to the maximum width, marking the cut with `…`.
+    #[must_use]
+    pub fn truncate(&self, name: &str) -> String {
+        let Some(max_width) = self.max_width else {
+            return name.to_string();
+        };
+        let chars: Vec<(char, usize)> = name.chars().map(|c| (c, c.width().unwrap_or(0))).collect();
+        if chars.iter().map(|(_, width)| width).sum::<usize>() <= max_width {
+            return name.to_string();
+        }
+
+        // One column for the ellipsis
+        let budget = max_width.saturating_sub(1);
+        let (head_width, tail_width) = match self.truncation {
+            Truncation::End => (budget, 0),
+            Truncation::Middle => (budget - budget / 2, budget / 2),
+        };
+
+        let head = take_width(chars.iter().copied(), head_width);
+        let mut tail = take_width(chars.iter().rev().copied(), tail_width);
+        tail.reverse();
+
+        head.into_iter()
+            .chain(std::iter::once('…'))
+            .chain(tail)
+            .collect()
+    }
+}
+
+fn take_width(chars: impl Iterator<Item = (char, usize)>, max_width: usize) -> Vec<char> {
+    let mut width = 0;
+    chars
+        .take_while(|(_, char_width)| {
+            width += char_width;
+            width <= max_width
+        })
+        .map(|(c, _)| c)
+        .collect()
+}
+
 /// Represents a single SSH session tab
 #[derive(Debug)]
 pub struct Session {
     pub id: usize,
     pub host: Host,
-    pub ssh_process: Option<Child>,
+    /// The session running in the tab, once started by the event loop
+    pub pty: Option<PtySession>,
+    /// Options of the connection, as confirmed when the tab was opened
+    pub options: ConnectOptions,
+    /// When the session started, for its duration once it ends
+    pub started_at: Option<Instant>,
+    /// What the session printed over its last seconds
+    pub throughput: Throughput,
+    /// Triggers on the lines the session prints
+    pub triggers: Vec<Trigger>,
+    /// A trigger matched since the tab was last shown
+    pub marked: bool,
+    /// When a trigger last rang the bell or ran a hook
+    pub alerted_at: Option<Instant>,
     pub is_active: bool,
+    /// Name given by the user, replacing the automatic one
+    pub custom_name: Option<String>,
+    /// Keystrokes meant for the session are ignored until it is unlocked (`Ctrl+L`, then the
+    /// host name)
+    pub locked: bool,
+    /// The session is recorded to a log file
+    pub logged: bool,
 }
 
 impl Session {
@@ -21,21 +142,40 @@ impl Session {
         Self {
             id,
             host,
-            ssh_process: None,
+            pty: None,
+            options: ConnectOptions::default(),
+            started_at: None,
+            throughput: Throughput::default(),
+            triggers: Vec::new(),
+            marked: false,
+            alerted_at: None,
             is_active: false,
+            custom_name: None,
+            locked: false,
+            logged: false,
         }
     }
 
-    /// Get the display name for the tab
+    /// Get the display name for the tab: the custom name, or the automatic one from the
+    /// template, cut to the maximum width. Locked sessions show a padlock, recorded ones a dot,
+    /// marked ones a `!`
     #[must_use]
-    pub fn tab_display_name(&self) -> String {
-        format!("[{}:{}]", self.id, self.host.name)
+    pub fn tab_display_name(&self, naming: &TabNaming) -> String {
+        let name = match &self.custom_name {
+            Some(name) => name.clone(),
+            None => naming.render(self.id, &self.host),
+        };
+        let mark = if self.marked { "!" } else { "" };
+        let lock = if self.locked { "🔒" } else { "" };
+        let log = if self.logged { "⏺" } else { "" };
+
+        format!("[{mark}{lock}{log}{}]", naming.truncate(&name))
     }
 
     /// Check if this session has an active SSH connection
     #[must_use]
     pub fn is_connected(&self) -> bool {
-        self.ssh_process.is_some()
+        self.pty.is_some()
     }
 }
 
@@ -45,19 +185,38 @@ pub struct TabManager {
     sessions: Vec<Session>,
     current_session_index: usize,
     next_session_id: usize,
+    naming: TabNaming,
+    /// Names given by the user, by host name, so that they survive reconnects
+    custom_names: BTreeMap<String, String>,
+    /// Whether sessions are recorded unless their host says otherwise (`--log-sessions`)
+    log_sessions: bool,
 }
 
 impl TabManager {
     /// Create a new tab manager
     #[must_use]
     pub fn new() -> Self {
+        Self::with_naming(TabNaming::default(), BTreeMap::new())
+    }
+
+    /// Create a tab manager naming its tabs with `naming`, or with the saved `custom_names`
+    #[must_use]
+    pub fn with_naming(naming: TabNaming, custom_names: BTreeMap<String, String>) -> Self {
         Self {
             sessions: Vec::new(),
             current_session_index: 0,
             next_session_id: 1,
+            naming,
+            custom_names,
+            log_sessions: false,
         }
     }
 
+    /// Records the sessions of the hosts not saying otherwise
+    pub fn set_log_sessions(&mut self, log_sessions: bool) {
+        self.log_sessions = log_sessions;
+    }
+
     /// Add a new session if under the limit
     ///
     /// # Errors
@@ -69,7 +228,9 @@ impl TabManager {
         }
 
         let session_id = self.next_session_id;
-        let session = Session::new(session_id, host);
+        let mut session = Session::new(session_id, host);
+        session.custom_name = self.custom_names.get(&session.host.name).cloned();
+        session.logged = session.host.logs_sessions(self.log_sessions);
         self.sessions.push(session);
         self.next_session_id += 1;
 
@@ -89,18 +250,108 @@ impl TabManager {
         true
     }
 
+    /// Closes the session at `index` (0-based), ending it. The tab after it becomes the current
+    /// one if it was.
+    pub fn close_session(&mut self, index: usize) -> Session {
+        let session = self.sessions.remove(index);
+        if self.current_session_index > index || self.current_session_index >= self.sessions.len() {
+            self.current_session_index = self.current_session_index.saturating_sub(1);
+        }
+
+        session
+    }
+
+    /// Closes the sessions that have ended, returning them with their exit code (`None` when
+    /// killed by a signal).
+    pub fn close_ended_sessions(&mut self) -> Vec<(Session, Option<i32>)> {
+        let mut ended = Vec::new();
+        let mut index = 0;
+        while index < self.sessions.len() {
+            match self.sessions[index].pty.as_mut().and_then(PtySession::poll) {
+                Some(exit) => ended.push((self.close_session(index), exit)),
+                None => index += 1,
+            }
+        }
+
+        ended
+    }
+
+    /// Closes every session, the next one gets id 1 again
+    pub fn close_all(&mut self) {
+        self.sessions.clear();
+        self.current_session_index = 0;
+        self.next_session_id = 1;
+    }
+
+    /// Host names of the sessions, in tab order
+    #[must_use]
+    pub fn host_names(&self) -> Vec<String> {
+        self.sessions
+            .iter()
+            .map(|session| session.host.name.clone())
+            .collect()
+    }
+
+    /// Renames the tabs of the current session's host, now and on later connections. An empty
+    /// name goes back to the automatic one. Returns the name of the renamed host.
+    pub fn rename_current_session(&mut self, name: &str) -> Option<String> {
+        let host_name = self.current_session()?.host.name.clone();
+        let name = name.trim();
+        let custom_name = (!name.is_empty()).then(|| name.to_string());
+
+        match &custom_name {
+            Some(name) => self.custom_names.insert(host_name.clone(), name.clone()),
+            None => self.custom_names.remove(&host_name),
+        };
+        for session in &mut self.sessions {
+            if session.host.name == host_name {
+                session.custom_name.clone_from(&custom_name);
+            }
+        }
+
+        Some(host_name)
+    }
+
+    /// Names given by the user, by host name
+    #[must_use]
+    pub fn custom_names(&self) -> &BTreeMap<String, String> {
+        &self.custom_names
+    }
+
+    /// How tabs are named
+    #[must_use]
+    pub fn naming(&self) -> &TabNaming {
+        &self.naming
+    }
+
     /// Get the current active session
     #[must_use]
     pub fn current_session(&self) -> Option<&Session> {
         self.sessions.get(self.current_session_index)
     }
 
+    /// Get the current active session, to change it
+    pub fn current_session_mut(&mut self) -> Option<&mut Session> {
+        self.sessions.get_mut(self.current_session_index)
+    }
+
+    /// Check if any session is locked
+    #[must_use]
+    pub fn has_locked_sessions(&self) -> bool {
+        self.sessions.iter().any(|session| session.locked)
+    }
+
     /// Get all sessions for tab display
     #[must_use]
     pub fn sessions(&self) -> &[Session] {
         &self.sessions
     }
 
+    /// Get all sessions, to start them
+    pub fn sessions_mut(&mut self) -> &mut [Session] {
+        &mut self.sessions
+    }
+
     /// Get the current session index (0-based)
     #[must_use]
     pub fn current_session_index(&self) -> usize {
@@ -130,7 +381,7 @@ impl TabManager {
             .iter()
             .enumerate()
             .map(|(index, session)| {
-                let display = session.tab_display_name();
+                let display = session.tab_display_name(&self.naming);
                 if index == self.current_session_index {
                     format!("▶{display}") // Highlight current tab
                 } else {
@@ -159,6 +410,7 @@ mod tests {
             port: Some("22".to_string()),
             aliases: String::new(),
             proxy_command: None,
+            ..Default::default()
         }
     }
 
@@ -203,6 +455,28 @@ mod tests {
         assert_eq!(current.host.name, "host3");
     }
 
+    #[test]
+    fn test_close_session_keeps_a_current_tab() {
+        let mut manager = TabManager::new();
+        for name in ["host1", "host2", "host3"] {
+            manager.add_session(create_test_host(name)).unwrap();
+        }
+
+        // Closing a tab before the current one
+        manager.switch_to_session(2);
+        assert_eq!(manager.close_session(0).host.name, "host1");
+        assert_eq!(manager.current_session().unwrap().host.name, "host2");
+
+        // Closing the current last tab
+        manager.switch_to_session(2);
+        assert_eq!(manager.close_session(1).host.name, "host3");
+        assert_eq!(manager.current_session().unwrap().host.name, "host2");
+
+        manager.close_session(0);
+        assert!(manager.current_session().is_none());
+        assert_eq!(manager.current_session_index(), 0);
+    }
+
     #[test]
     fn test_maximum_sessions_limit() {
         let mut manager = TabManager::new();
@@ -260,9 +534,92 @@ mod tests {
     #[test]
     fn test_session_tab_display_name() {
         let host = create_test_host("prod-web");
-        let session = Session::new(1, host);
+        let mut session = Session::new(1, host);
+
+        assert_eq!(
+            session.tab_display_name(&TabNaming::default()),
+            "[1:prod-web]"
+        );
+
+        session.marked = true;
+        assert_eq!(
+            session.tab_display_name(&TabNaming::default()),
+            "[!1:prod-web]"
+        );
+    }
+
+    #[test]
+    fn test_locked_session_shows_padlock() {
+        let mut manager = TabManager::new();
+        manager.add_session(create_test_host("prod-web")).unwrap();
+        assert!(!manager.has_locked_sessions());
+
+        manager.current_session_mut().unwrap().locked = true;
+        assert!(manager.has_locked_sessions());
+        assert_eq!(
+            manager.sessions()[0].tab_display_name(manager.naming()),
+            "[🔒1:prod-web]"
+        );
+    }
+
+    #[test]
+    fn test_recorded_session_shows_a_dot() {
+        let mut manager = TabManager::new();
+        manager.set_log_sessions(true);
+        manager.add_session(create_test_host("prod-web")).unwrap();
+
+        let mut host = create_test_host("scratch");
+        host.metadata.insert("log".to_string(), "no".to_string());
+        manager.add_session(host).unwrap();
+
+        assert_eq!(manager.tab_bar_display(), "[⏺1:prod-web]▶[2:scratch]");
+    }
+
+    #[test]
+    fn test_tab_name_template_and_truncation() {
+        let session = Session::new(2, create_test_host("prod-db-eu-01"));
+
+        let mut naming = TabNaming {
+            template: "{{index}}:{{user}}@{{name}}".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(session.tab_display_name(&naming), "[2:root@prod-db-eu-01]");
+
+        naming.max_width = Some(13);
+        assert_eq!(session.tab_display_name(&naming), "[2:root…-eu-01]");
+
+        naming.truncation = Truncation::End;
+        assert_eq!(session.tab_display_name(&naming), "[2:root@prod-…]");
+
+        naming.template = "{{#if}}".to_string();
+        naming.max_width = None;
+        assert_eq!(session.tab_display_name(&naming), "[2:prod-db-eu-01]");
+    }
+
+    #[test]
+    fn test_custom_names_survive_reconnects() {
+        let mut manager = TabManager::new();
+        manager.add_session(create_test_host("host1")).unwrap();
+        manager.add_session(create_test_host("host2")).unwrap();
+
+        assert_eq!(
+            manager.rename_current_session(" api "),
+            Some("host2".to_string())
+        );
+        assert_eq!(manager.tab_bar_display(), "[1:host1]▶[api]");
+
+        // A new session on the same host, or a new manager with the saved names
+        manager.add_session(create_test_host("host2")).unwrap();
+        assert_eq!(manager.tab_bar_display(), "[1:host1][api]▶[api]");
+
+        let mut restored =
+            TabManager::with_naming(TabNaming::default(), manager.custom_names().clone());
+        restored.add_session(create_test_host("host2")).unwrap();
+        assert_eq!(restored.tab_bar_display(), "▶[api]");
 
-        assert_eq!(session.tab_display_name(), "[1:prod-web]");
+        restored.rename_current_session("");
+        assert_eq!(restored.tab_bar_display(), "▶[1:host2]");
+        assert!(restored.custom_names().is_empty());
     }
 
     #[test]
@@ -0,0 +1,209 @@
+//! Timeline of a host (`H`): its connections, failures and health changes from the state file,
+//! and the changes to its configuration from the audit log, in chronological order. The view
+//! can be searched, and exported as CSV.
+
+use anyhow::Context;
+use std::fs;
+
+use super::audit_log;
+use crate::host_keys::csv_field;
+use crate::ssh::format_date;
+use crate::state::{HostEvent, HostEventKind, State};
+
+const CSV_HEADER: &str = "time,kind,detail";
+
+/// File the timeline of `host` is exported to.
+#[must_use]
+pub fn export_path(host: &str) -> String {
+    format!("~/sshs-timeline-{host}.csv")
+}
+
+/// Events of `host`, oldest first: the ones recorded in `state`, and the changes of the audit
+/// log `entries` that are about it.
+#[must_use]
+pub fn collect(host: &str, state: &State, entries: &[audit_log::Entry]) -> Vec<HostEvent> {
+    let config_changes = entries
+        .iter()
+        .filter(|entry| entry.hosts.split(',').any(|name| name.trim() == host))
+        .map(|entry| HostEvent {
+            time: entry.time,
+            kind: HostEventKind::ConfigChange,
+            detail: format!(
+                "{} by {} in {} ({} lines changed)",
+                entry.action,
+                entry.user,
+                entry.file,
+                entry.diff.len()
+            ),
+        });
+
+    let mut events = state
+        .host_events
+        .get(host)
+        .into_iter()
+        .flatten()
+        .cloned()
+        .chain(config_changes)
+        .collect::<Vec<_>>();
+    events.sort_by_key(|event| event.time);
+
+    events
+}
+
+/// UTC date and time of a Unix time, e.g. `2026-10-16 14:03`.
+#[must_use]
+pub fn format_time(time: u64) -> String {
+    let day = i64::try_from(time / 86_400).unwrap_or(i64::MAX);
+    let seconds = time % 86_400;
+
+    format!(
+        "{} {:02}:{:02}",
+        format_date(day),
+        seconds / 3600,
+        seconds / 60 % 60
+    )
+}
+
+#[must_use]
+pub fn to_csv(events: &[&HostEvent]) -> String {
+    let mut csv = format!("{CSV_HEADER}\n");
+
+    for event in events {
+        let time = format_time(event.time);
+        let fields = [time.as_str(), event.kind.label(), &event.detail];
+
+        csv.push_str(&fields.map(csv_field).join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// Timeline view: the events of a host, filtered by the search.
+#[derive(Debug, Default)]
+pub struct TimelineView {
+    pub host: String,
+    pub events: Vec<HostEvent>,
+    pub selected: usize,
+    pub search: String,
+    /// Whether keys are typed into the search
+    pub searching: bool,
+}
+
+impl TimelineView {
+    /// The view of `events`, with the most recent one selected.
+    #[must_use]
+    pub fn new(host: String, events: Vec<HostEvent>) -> Self {
+        Self {
+            host,
+            selected: events.len().saturating_sub(1),
+            events,
+            ..Default::default()
+        }
+    }
+
+    /// Events matching the search (in their kind or detail, ignoring case).
+    #[must_use]
+    pub fn visible(&self) -> Vec<&HostEvent> {
+        let search = self.search.to_lowercase();
+
+        self.events
+            .iter()
+            .filter(|event| {
+                event.kind.label().contains(&search)
+                    || event.detail.to_lowercase().contains(&search)
+            })
+            .collect()
+    }
+
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.visible().len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Changes the search, selecting the most recent match.
+    pub fn set_search(&mut self, search: String) {
+        self.search = search;
+        self.selected = self.visible().len().saturating_sub(1);
+    }
+
+    /// Writes the visible events to `path`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file cannot be written.
+    pub fn export(&self, path: &str) -> anyhow::Result<()> {
+        fs::write(shellexpand::tilde(path).as_ref(), to_csv(&self.visible()))
+            .with_context(|| format!("Failed to write the timeline '{path}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(time: u64, kind: HostEventKind, detail: &str) -> HostEvent {
+        HostEvent {
+            time,
+            kind,
+            detail: detail.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_timeline_merges_state_and_audit_log() {
+        let mut state = State::default();
+        state.record_host_event(
+            "web",
+            event(100, HostEventKind::Connection, "session of 5s, exit code 0"),
+        );
+        state.record_host_event("web", event(300, HostEventKind::Failure, "exit code 255"));
+        state.record_host_event("db", event(150, HostEventKind::Connection, ""));
+
+        let entries = [
+            audit_log::Entry {
+                time: 200,
+                user: "alice".to_string(),
+                action: "edit host".to_string(),
+                hosts: "web".to_string(),
+                file: "/ssh/config".to_string(),
+                diff: vec!["-  Port 22".to_string(), "+  Port 2222".to_string()],
+            },
+            audit_log::Entry {
+                time: 250,
+                user: "alice".to_string(),
+                action: "delete host".to_string(),
+                hosts: "web2".to_string(),
+                file: "/ssh/config".to_string(),
+                diff: Vec::new(),
+            },
+        ];
+
+        let mut view = TimelineView::new("web".to_string(), collect("web", &state, &entries));
+        assert_eq!(
+            view.events.iter().map(|e| e.time).collect::<Vec<_>>(),
+            [100, 200, 300]
+        );
+        assert_eq!(
+            view.events[1].detail,
+            "edit host by alice in /ssh/config (2 lines changed)"
+        );
+        assert_eq!(view.selected, 2);
+
+        view.set_search("CONFIG".to_string());
+        assert_eq!(view.visible().len(), 1);
+        assert_eq!(view.selected, 0);
+        assert_eq!(
+            to_csv(&view.visible()),
+            "time,kind,detail\n\
+             1970-01-01 00:03,config change,edit host by alice in /ssh/config (2 lines changed)\n"
+        );
+
+        assert_eq!(format_time(1_792_159_380), "2026-10-16 14:03");
+    }
+}
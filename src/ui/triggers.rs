@@ -0,0 +1,130 @@
+//! Triggers on what the sessions print. A line matching the pattern of a trigger is highlighted
+//! on screen, rings the bell, marks its tab with `!` until the tab is shown, or runs a hook.
+//! They come from the `[[triggers]]` of the configuration file, and per host from
+//! `# sshs:trigger-highlight=<pattern>`, `# sshs:trigger-beep=<pattern>` and
+//! `# sshs:trigger-mark=<pattern>`.
+
+use anyhow::{Context, Result};
+use regex::{Regex, RegexSet};
+use std::time::Duration;
+
+use crate::config_file;
+use crate::ssh;
+
+/// A session rings the bell and runs hooks once in this time at most, so that a flood of
+/// matching lines does not run a hook per line
+pub const ALERT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What a line of session output matching `pattern` does
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    pub pattern: Regex,
+    /// The line is highlighted while on screen
+    pub highlight: bool,
+    pub beep: bool,
+    /// The tab of the session is marked with `!` until shown
+    pub mark: bool,
+    /// Command template, with the line in `{{trigger_line}}` and `$SSHS_TRIGGER_LINE`
+    pub hook: Option<String>,
+}
+
+impl Trigger {
+    /// # Errors
+    ///
+    /// Will return `Err` if the pattern is not a valid regular expression.
+    pub fn from_config(trigger: &config_file::Trigger) -> Result<Trigger> {
+        Ok(Trigger {
+            pattern: compile(&trigger.pattern)?,
+            highlight: trigger.highlight,
+            beep: trigger.beep,
+            mark: trigger.mark,
+            hook: trigger.hook.clone(),
+        })
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).with_context(|| format!("Invalid trigger pattern {pattern:?}"))
+}
+
+/// The triggers of the sessions on `host`: the `global` ones, then the ones of its metadata.
+///
+/// # Errors
+///
+/// Will return `Err` if a pattern of the host is not a valid regular expression.
+pub fn for_host(global: &[Trigger], host: &ssh::Host) -> Result<Vec<Trigger>> {
+    let mut triggers = global.to_vec();
+    for key in ["trigger-highlight", "trigger-beep", "trigger-mark"] {
+        let Some(pattern) = host.metadata_value(key) else {
+            continue;
+        };
+
+        triggers.push(Trigger {
+            pattern: compile(pattern)?,
+            highlight: key == "trigger-highlight",
+            beep: key == "trigger-beep",
+            mark: key == "trigger-mark",
+            hook: None,
+        });
+    }
+
+    Ok(triggers)
+}
+
+/// The patterns of `triggers`, matched all at once against each line.
+///
+/// # Errors
+///
+/// Will return `Err` if the patterns are too large together.
+pub fn pattern_set(triggers: &[Trigger]) -> Result<RegexSet> {
+    Ok(RegexSet::new(
+        triggers.iter().map(|trigger| trigger.pattern.as_str()),
+    )?)
+}
+
+/// Whether `line` is highlighted by one of `triggers`.
+#[must_use]
+pub fn highlights(triggers: &[Trigger], line: &str) -> bool {
+    triggers
+        .iter()
+        .any(|trigger| trigger.highlight && trigger.pattern.is_match(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_host_triggers() {
+        let global = Trigger::from_config(&config_file::Trigger {
+            pattern: "ERROR".to_string(),
+            highlight: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let mut host = ssh::Host {
+            name: "web".to_string(),
+            metadata: BTreeMap::from([("trigger-mark".to_string(), "kernel panic".to_string())]),
+            ..Default::default()
+        };
+
+        let triggers = for_host(&[global], &host).unwrap();
+        assert_eq!(triggers.len(), 2);
+        assert!(triggers[1].mark && !triggers[1].beep);
+        assert!(highlights(&triggers, "12:00 ERROR disk full"));
+        assert!(!highlights(&triggers, "kernel panic"));
+        assert_eq!(
+            pattern_set(&triggers)
+                .unwrap()
+                .matches("kernel panic")
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [1]
+        );
+
+        host.metadata
+            .insert("trigger-beep".to_string(), "(".to_string());
+        assert!(for_host(&[], &host).is_err());
+    }
+}
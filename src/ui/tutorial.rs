@@ -0,0 +1,268 @@
+//! Guided walkthrough (`sshs tutorial`): the host list of a sample configuration in a temporary
+//! directory, with one instruction at a time teaching search, connect, edit, tabs and tunnels.
+//! Each step is checked against what was done before moving on to the next one, and connections
+//! are simulated instead of running `ssh`.
+
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use super::app::{App, AppConfig};
+use super::session::PtySession;
+use crate::backend::ConnectionBackend;
+use crate::ssh;
+
+/// Configuration the tutorial starts from
+pub const SAMPLE_CONFIG: &str = "\
+Host web-01
+  Hostname web-01.example.com
+  # sshs:tags=web,prod
+
+Host web-02
+  Hostname web-02.example.com
+  # sshs:tags=web,staging
+
+Host db-01
+  Hostname db-01.example.com
+  User postgres
+  # sshs:tags=db,prod
+
+Host bastion
+  Hostname bastion.example.com
+  # sshs:tags=gateway
+";
+
+/// User the edit step asks to set
+const EDITED_USER: &str = "admin";
+
+/// Connections made during the tutorial, with their options, shared with the simulated backend
+pub type Connections = Rc<RefCell<Vec<(String, ssh::ConnectOptions)>>>;
+
+struct Step {
+    title: &'static str,
+    instruction: &'static str,
+    done: fn(&App, &[(String, ssh::ConnectOptions)]) -> bool,
+}
+
+const STEPS: [Step; 5] = [
+    Step {
+        title: "Search",
+        instruction: "Press / and type web to only list the web servers, then Enter",
+        done: |app, _| app.search.value().contains("web"),
+    },
+    Step {
+        title: "Connect",
+        instruction: "Select a host with ↑/↓ and press Enter to connect to it",
+        done: |_, connections| !connections.is_empty(),
+    },
+    Step {
+        title: "Edit",
+        instruction:
+            "Select web-01, press e, set its Username to admin, save with Enter and confirm with Y",
+        done: |app, _| {
+            app.hosts
+                .non_filtered_iter()
+                .any(|host| host.user.as_deref() == Some(EDITED_USER))
+        },
+    },
+    Step {
+        title: "Tabs",
+        instruction:
+            "Press Ctrl+N to open the selected host in a new tab, then Ctrl+Q to go back to the hosts",
+        done: |app, _| app.tab_manager.has_sessions() && !app.session_shown,
+    },
+    Step {
+        title: "Tunnels",
+        instruction: "Press D to connect with a SOCKS proxy (a dynamic forward) to the host",
+        done: |_, connections| {
+            connections
+                .iter()
+                .any(|(_, options)| options.dynamic_forward.is_some())
+        },
+    },
+];
+
+/// Progress through the steps of the tutorial.
+pub struct Tutorial {
+    step: usize,
+    connections: Connections,
+}
+
+impl Tutorial {
+    #[must_use]
+    pub fn new(connections: Connections) -> Self {
+        Self {
+            step: 0,
+            connections,
+        }
+    }
+
+    /// Moves on past the steps done in `app`, in order. Returns the title of the last step
+    /// completed, if any.
+    pub fn update(&mut self, app: &App) -> Option<&'static str> {
+        let mut completed = None;
+        while let Some(step) = STEPS.get(self.step) {
+            if !(step.done)(app, &self.connections.borrow()) {
+                break;
+            }
+            completed = Some(step.title);
+            self.step += 1;
+        }
+
+        completed
+    }
+
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.step >= STEPS.len()
+    }
+
+    /// Title of the panel, e.g. `Tutorial 2/5: Connect`.
+    #[must_use]
+    pub fn title(&self) -> String {
+        match STEPS.get(self.step) {
+            Some(step) => format!("Tutorial {}/{}: {}", self.step + 1, STEPS.len(), step.title),
+            None => "Tutorial done".to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn instruction(&self) -> &'static str {
+        STEPS.get(self.step).map_or(
+            "You know the basics! Press Esc to quit the tutorial",
+            |step| step.instruction,
+        )
+    }
+}
+
+/// Backend standing in for `ssh`: records the connection and shows what would have happened.
+struct TutorialBackend {
+    connections: Connections,
+}
+
+impl ConnectionBackend for TutorialBackend {
+    fn name(&self) -> &'static str {
+        "tutorial"
+    }
+
+    fn interactive(&self, host: &ssh::Host, options: &ssh::ConnectOptions) -> Result<Option<i32>> {
+        self.connections
+            .borrow_mut()
+            .push((host.name.clone(), options.clone()));
+
+        let mut stdout = io::stdout();
+        writeln!(
+            stdout,
+            "Tutorial: this is where the SSH session would run:\n"
+        )?;
+        writeln!(stdout, "  {}\n", host.build_ssh_command(options).join(" "))?;
+        if let Some(endpoint) = options.socks_endpoint() {
+            writeln!(
+                stdout,
+                "Applications set to use the proxy {endpoint} reach the network of {}.\n",
+                host.name
+            )?;
+        }
+        write!(stdout, "Press Enter to end the session...")?;
+        stdout.flush()?;
+        io::stdin().lock().read_line(&mut String::new())?;
+
+        Ok(Some(0))
+    }
+
+    /// A local shell echoing what is typed, under a note of what the session would be.
+    fn spawn_tab(
+        &self,
+        host: &ssh::Host,
+        options: &ssh::ConnectOptions,
+        rows: u16,
+        cols: u16,
+    ) -> Result<PtySession> {
+        let note = format!(
+            "Tutorial: this is where the SSH session would run:\n\n  {}\n\n\
+             What you type is echoed back, Ctrl+D ends the session.\n",
+            host.build_ssh_command(options).join(" ")
+        );
+        let args =
+            ["sh", "-c", "printf '%s\\n' \"$0\"; exec cat", note.as_str()].map(str::to_string);
+
+        PtySession::spawn(&args, rows, cols)
+    }
+
+    fn batch(&self, host: &ssh::Host, _script: &str) -> Result<String> {
+        anyhow::bail!("{} is a sample host of the tutorial", host.name)
+    }
+}
+
+/// Temporary directory holding the sample configuration and the state, removed on drop.
+struct Sandbox {
+    dir: PathBuf,
+}
+
+impl Sandbox {
+    fn create() -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("sshs-tutorial-{}", std::process::id()));
+        fs::create_dir_all(&dir).with_context(|| {
+            format!("Failed to create the tutorial directory {}", dir.display())
+        })?;
+        fs::write(dir.join("config"), SAMPLE_CONFIG)
+            .context("Failed to write the tutorial configuration")?;
+
+        Ok(Self { dir })
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.dir.join(name).display().to_string()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Sandboxed copy of `config`: the sample configuration and nothing of the user's own files.
+fn sandboxed_config(config: &AppConfig, sandbox: &Sandbox) -> AppConfig {
+    AppConfig {
+        config_paths: vec![sandbox.path("config")],
+        state_path: sandbox.path("state.json"),
+        vault: None,
+        managed_config_path: None,
+        search_filter: None,
+        connect_if_unique: false,
+        command_template_on_session_start: None,
+        command_template_on_session_end: None,
+        command_template_on_connection_failure: None,
+        command_template_on_socks: None,
+        exit_after_ssh_session_ends: false,
+        dotfiles: Vec::new(),
+        shared_metadata_path: None,
+        workspace: None,
+        stats_file: None,
+        audit_log: None,
+        no_exec: false,
+        ..config.clone()
+    }
+}
+
+/// Runs the tutorial in the terminal, with the display settings of `config`.
+///
+/// # Errors
+///
+/// Will return `Err` if the sample configuration cannot be written or the app cannot start.
+pub fn run(config: &AppConfig) -> Result<()> {
+    let sandbox = Sandbox::create()?;
+    let connections = Connections::default();
+
+    let mut app = App::new(&sandboxed_config(config, &sandbox))?;
+    app.backend = Box::new(TutorialBackend {
+        connections: Rc::clone(&connections),
+    });
+    app.tutorial = Some(Tutorial::new(connections));
+
+    app.start()
+}
@@ -0,0 +1,141 @@
+//! Hosts not connected to for months (`U`), from the connections recorded in the state file,
+//! to tag, comment out or export for review.
+
+use anyhow::Context;
+use std::fs;
+
+use crate::host_keys::csv_field;
+use crate::ssh::{self, format_date};
+use crate::state::State;
+
+/// Months without a connection after which a host is unused, unless set with `--unused-after`
+pub const DEFAULT_UNUSED_AFTER_MONTHS: u32 = 6;
+
+/// Tag added to the unused hosts
+pub const STALE_TAG: &str = "stale";
+
+/// File the unused hosts are exported to, unless changed in the prompt
+pub const DEFAULT_UNUSED_HOSTS_EXPORT: &str = "~/sshs-unused-hosts.csv";
+
+const CSV_HEADER: &str = "host,destination,last_connection";
+
+/// A host not connected to since the cutoff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnusedHost {
+    pub name: String,
+    pub destination: String,
+    /// Day of the last connection, `None` when it was before sshs recorded them
+    pub last_connection: Option<i64>,
+}
+
+impl UnusedHost {
+    /// e.g. `web (last connection 2025-01-31)`, or `web (never connected)`.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        match self.last_connection {
+            Some(day) => format!("{} (last connection {})", self.name, format_date(day)),
+            None => format!("{} (never connected)", self.name),
+        }
+    }
+}
+
+/// Hosts without a connection in the last `months` months (of 30 days) before `today`. Hosts
+/// never connected to only count once the connections have been recorded for that long, and
+/// template hosts are left out.
+#[must_use]
+pub fn find<'a>(
+    hosts: impl IntoIterator<Item = &'a ssh::Host>,
+    state: &State,
+    months: u32,
+    today: i64,
+) -> Vec<UnusedHost> {
+    let cutoff = today - i64::from(months) * 30;
+    let recorded_long_enough = state
+        .connections_recorded_since
+        .is_some_and(|since| since <= cutoff);
+
+    hosts
+        .into_iter()
+        .filter(|host| !host.is_template())
+        .filter_map(|host| {
+            let last_connection = state.last_connections.get(&host.name).copied();
+            let unused = match last_connection {
+                Some(day) => day < cutoff,
+                None => recorded_long_enough,
+            };
+
+            unused.then(|| UnusedHost {
+                name: host.name.clone(),
+                destination: host.destination.clone(),
+                last_connection,
+            })
+        })
+        .collect()
+}
+
+/// CSV listing of the unused hosts, for review.
+#[must_use]
+pub fn to_csv(hosts: &[UnusedHost]) -> String {
+    let mut csv = format!("{CSV_HEADER}\n");
+
+    for host in hosts {
+        let last_connection = host.last_connection.map(format_date).unwrap_or_default();
+        let fields = [host.name.as_str(), &host.destination, &last_connection];
+
+        csv.push_str(&fields.map(csv_field).join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// # Errors
+///
+/// Will return `Err` if the file cannot be written.
+pub fn write_csv(path: &str, hosts: &[UnusedHost]) -> anyhow::Result<()> {
+    fs::write(shellexpand::tilde(path).as_ref(), to_csv(hosts))
+        .with_context(|| format!("Failed to write the unused hosts '{path}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh::parse_date;
+
+    fn host(name: &str) -> ssh::Host {
+        ssh::Host {
+            name: name.to_string(),
+            destination: format!("{name}.example.com"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_unused_hosts() {
+        let hosts = [host("web"), host("db"), host("new"), host("gw-%d")];
+        let today = parse_date("2026-10-16").unwrap();
+        let mut state = State::default();
+        state.record_connection("web", parse_date("2026-09-01").unwrap());
+        state.record_connection("db", parse_date("2025-01-31").unwrap());
+        state.connections_recorded_since = parse_date("2026-06-01");
+
+        // Recorded for 4 months: hosts never connected to are not known to be unused yet
+        let unused = find(&hosts, &state, 6, today);
+        assert_eq!(
+            unused.iter().map(UnusedHost::describe).collect::<Vec<_>>(),
+            ["db (last connection 2025-01-31)"]
+        );
+
+        let unused = find(&hosts, &state, 3, today);
+        assert_eq!(
+            unused.iter().map(UnusedHost::describe).collect::<Vec<_>>(),
+            ["db (last connection 2025-01-31)", "new (never connected)"]
+        );
+        assert_eq!(
+            to_csv(&unused),
+            "host,destination,last_connection\n\
+             db,db.example.com,2025-01-31\n\
+             new,new.example.com,\n"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Opt-in check for new releases (the Update check setting): GitHub is asked for the latest
+//! release at most once a day, in the background, and a newer version shows a badge in the
+//! footer. `V` shows its changelog and the command to upgrade; nothing is ever downloaded.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::exec;
+
+/// Latest release of sshs, from the GitHub API
+pub const RELEASES_API: &str = "https://api.github.com/repos/imrellx/sshs/releases/latest";
+
+/// Command upgrading sshs, shown with the changelog
+pub const UPGRADE_COMMAND: &str = "cargo install sshs --locked";
+
+/// Seconds the check waits for GitHub
+const TIMEOUT_SECONDS: &str = "10";
+
+/// A release of sshs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Release {
+    /// e.g. `4.9.0`, without the `v` of the tag
+    pub version: String,
+    /// Release notes, in Markdown
+    pub changelog: String,
+    pub url: String,
+}
+
+/// Result of the last check, kept in the state file so that it runs once a day at most.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdateCheck {
+    /// Day of the check (days since 1970-01-01)
+    pub day: i64,
+    /// Latest release found, kept when a check fails
+    pub latest: Option<Release>,
+}
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    #[serde(default)]
+    body: Option<String>,
+    html_url: String,
+}
+
+/// Reads the answer of the GitHub releases API.
+///
+/// # Errors
+///
+/// Will return `Err` if `json` is not a release.
+pub fn parse_release(json: &str) -> Result<Release> {
+    let release: GithubRelease =
+        serde_json::from_str(json).context("Unexpected answer from GitHub")?;
+
+    Ok(Release {
+        version: release.tag_name.trim_start_matches('v').to_string(),
+        changelog: release.body.unwrap_or_default().replace("\r\n", "\n"),
+        url: release.html_url,
+    })
+}
+
+/// Whether `version` is newer than `current`, comparing their numbers one by one (`4.10.0` is
+/// newer than `4.9.2`).
+#[must_use]
+pub fn is_newer(version: &str, current: &str) -> bool {
+    let numbers = |version: &str| -> Vec<u64> {
+        version
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|number| number.parse().unwrap_or_default())
+            .collect()
+    };
+
+    numbers(version) > numbers(current)
+}
+
+/// Asks GitHub for the latest release, with `curl`.
+///
+/// # Errors
+///
+/// Will return `Err` if `curl` cannot be run or GitHub cannot be reached.
+pub fn fetch() -> Result<Release> {
+    let output = exec::output(Command::new("curl").args([
+        "--silent",
+        "--show-error",
+        "--fail",
+        "--location",
+        "--max-time",
+        TIMEOUT_SECONDS,
+        "--header",
+        "Accept: application/vnd.github+json",
+        RELEASES_API,
+    ]))
+    .context("Failed to run curl")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to check for updates: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    parse_release(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Check running in the background.
+#[derive(Debug)]
+pub struct UpdateChecker {
+    receiver: mpsc::Receiver<Result<Release, String>>,
+}
+
+impl UpdateChecker {
+    #[must_use]
+    pub fn start() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(fetch().map_err(|e| format!("{e:#}")));
+        });
+
+        Self { receiver }
+    }
+
+    /// The result of the check, once it is done.
+    #[must_use]
+    pub fn poll(&self) -> Option<Result<Release, String>> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_release_and_compare_versions() {
+        let release = parse_release(
+            r#"{"tag_name": "v4.10.0", "body": "- Match blocks\r\n- Quiet mode",
+                "html_url": "https://github.com/imrellx/sshs/releases/tag/v4.10.0",
+                "assets": []}"#,
+        )
+        .unwrap();
+        assert_eq!(release.version, "4.10.0");
+        assert_eq!(release.changelog, "- Match blocks\n- Quiet mode");
+        assert!(parse_release(r#"{"message": "Not Found"}"#).is_err());
+
+        assert!(is_newer(&release.version, "4.9.2"));
+        assert!(is_newer("4.8.1", "4.8.0"));
+        assert!(is_newer("5.0", "4.8.0"));
+        assert!(!is_newer("4.8.0", "4.8.0"));
+        assert!(!is_newer("4.8.0-rc.1", "4.8.0"));
+        assert!(!is_newer("4.7.9", "4.8.0"));
+    }
+}
@@ -0,0 +1,278 @@
+//! Web consoles next to the SSH service (`#_Web https://host:8443`), opened in the local
+//! browser. Consoles only reachable from the host go through an SSH tunnel.
+
+use anyhow::{anyhow, bail, Context};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, TcpListener};
+use std::process::{Command, Stdio};
+
+use crate::{exec, ssh};
+
+/// URL of a web console.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    /// `http` or `https`
+    pub scheme: String,
+    pub address: String,
+    pub port: u16,
+    /// Path, query and fragment, empty or starting with `/`
+    pub path: String,
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://", self.scheme)?;
+        if self.address.contains(':') {
+            write!(f, "[{}]", self.address)?;
+        } else {
+            write!(f, "{}", self.address)?;
+        }
+        if self.port != default_port(&self.scheme) {
+            write!(f, ":{}", self.port)?;
+        }
+
+        write!(f, "{}", self.path)
+    }
+}
+
+fn default_port(scheme: &str) -> u16 {
+    if scheme == "https" {
+        443
+    } else {
+        80
+    }
+}
+
+impl Endpoint {
+    /// Parses an `http://` or `https://` URL.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the URL has another scheme, an invalid port, or an address with
+    /// characters a host name or an IP address cannot have.
+    pub fn parse(url: &str) -> anyhow::Result<Endpoint> {
+        let (scheme, rest) = url
+            .trim()
+            .split_once("://")
+            .ok_or_else(|| anyhow!("Expected an http:// or https:// URL, got '{url}'"))?;
+        let scheme = scheme.to_lowercase();
+        if scheme != "http" && scheme != "https" {
+            bail!("Unsupported URL scheme '{scheme}'");
+        }
+
+        let (authority, path) = rest.split_at(rest.find(['/', '?', '#']).unwrap_or(rest.len()));
+        let (address, port) = if let Some(rest) = authority.strip_prefix('[') {
+            let (address, port) = rest
+                .split_once(']')
+                .ok_or_else(|| anyhow!("Missing ']' after the IPv6 address"))?;
+            (address, port.strip_prefix(':'))
+        } else {
+            match authority.split_once(':') {
+                Some((address, port)) => (address, Some(port)),
+                None => (authority, None),
+            }
+        };
+
+        if address.is_empty()
+            || !address
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':'))
+        {
+            bail!("Invalid address '{address}'");
+        }
+        let port = match port {
+            Some(port) => port
+                .parse::<u16>()
+                .ok()
+                .filter(|port| *port != 0)
+                .ok_or_else(|| anyhow!("Invalid port '{port}'"))?,
+            None => default_port(&scheme),
+        };
+
+        Ok(Endpoint {
+            scheme,
+            address: address.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// Whether the address is only reachable from inside the host's network: loopback,
+    /// private and link-local addresses, and single-label names resolved by the host.
+    #[must_use]
+    pub fn is_private(&self) -> bool {
+        match self.address.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+            Ok(IpAddr::V6(ip)) => {
+                let first = ip.segments()[0];
+                // Unique local (fc00::/7) and link-local (fe80::/10) addresses
+                ip.is_loopback() || first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80
+            }
+            Err(_) => !self.address.contains('.'),
+        }
+    }
+
+    /// The same console, reached through a tunnel listening on `local_port`.
+    fn through_tunnel(&self, local_port: u16) -> Endpoint {
+        Endpoint {
+            address: Ipv4Addr::LOCALHOST.to_string(),
+            port: local_port,
+            ..self.clone()
+        }
+    }
+}
+
+/// Whether `endpoint` is opened through a tunnel: as set on the host, or when it is private.
+#[must_use]
+pub fn wants_tunnel(host: &ssh::Host, endpoint: &Endpoint) -> bool {
+    host.web_tunnel().unwrap_or_else(|| endpoint.is_private())
+}
+
+/// Opens `endpoint` in the local browser, through a tunnel across `host` when needed. Returns
+/// the URL given to the browser.
+///
+/// # Errors
+///
+/// Will return `Err` if the tunnel cannot be set up without a prompt, or if there is no
+/// browser to open the URL with.
+pub fn open(host: &ssh::Host, endpoint: &Endpoint) -> anyhow::Result<Endpoint> {
+    let endpoint = if wants_tunnel(host, endpoint) {
+        let local_port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .context("No free local port for the tunnel")?
+            .port();
+
+        let status = exec::status(
+            ssh::command(&host.build_tunnel_command(local_port, &endpoint.address, endpoint.port))
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null()),
+        )?;
+        if !status.success() {
+            bail!("Failed to open a tunnel through {}: {status}", host.name);
+        }
+
+        endpoint.through_tunnel(local_port)
+    } else {
+        endpoint.clone()
+    };
+
+    open_in_browser(&endpoint.to_string())?;
+
+    Ok(endpoint)
+}
+
+/// Opens a link about a host (`#_Links`), only when it is an `http://` or `https://` URL so that
+/// an untrusted configuration cannot have the opener run a local file.
+///
+/// # Errors
+///
+/// Will return `Err` if the link is not a web URL, or if there is no browser to open it with.
+pub fn open_link(link: &str) -> anyhow::Result<()> {
+    let scheme = link
+        .split_once("://")
+        .map(|(scheme, _)| scheme.to_lowercase());
+    if !matches!(scheme.as_deref(), Some("http" | "https")) {
+        bail!("Expected an http:// or https:// link, got '{link}'");
+    }
+
+    open_in_browser(link)
+}
+
+/// Opens `url` in the local browser, without waiting for it.
+fn open_in_browser(url: &str) -> anyhow::Result<()> {
+    let mut browser = exec::spawn(
+        browser_command(url)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null()),
+    )
+    .context("Failed to start the browser")?;
+    // Some openers only return once the browser exits, do not wait for them
+    std::thread::spawn(move || browser.wait());
+
+    Ok(())
+}
+
+/// Command opening `url`: `$BROWSER`, or the opener of the desktop.
+fn browser_command(url: &str) -> Command {
+    let program = std::env::var("BROWSER").unwrap_or_else(|_| {
+        if cfg!(target_os = "macos") {
+            "open".to_string()
+        } else {
+            "xdg-open".to_string()
+        }
+    });
+
+    let mut command = Command::new(program);
+    command.arg(url);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_endpoints() {
+        let endpoint = Endpoint::parse("https://web.example.com:8443/admin?tab=1").unwrap();
+        assert_eq!(endpoint.scheme, "https");
+        assert_eq!(endpoint.address, "web.example.com");
+        assert_eq!(endpoint.port, 8443);
+        assert_eq!(endpoint.path, "/admin?tab=1");
+        assert_eq!(
+            endpoint.to_string(),
+            "https://web.example.com:8443/admin?tab=1"
+        );
+
+        assert_eq!(Endpoint::parse("HTTP://grafana").unwrap().port, 80);
+        assert_eq!(
+            Endpoint::parse("https://[fd00::5]/").unwrap().to_string(),
+            "https://[fd00::5]/"
+        );
+
+        assert!(Endpoint::parse("web.example.com:8443").is_err());
+        assert!(Endpoint::parse("ftp://web.example.com").is_err());
+        assert!(Endpoint::parse("http://web.example.com:http").is_err());
+        assert!(Endpoint::parse("http://web;reboot").is_err());
+
+        // Links are never handed to the opener unless they are web URLs
+        assert!(open_link("file:///etc/passwd").is_err());
+        assert!(open_link("/usr/bin/xterm").is_err());
+    }
+
+    #[test]
+    fn test_private_endpoints_are_tunnelled() {
+        let private = [
+            "http://127.0.0.1:9090",
+            "https://10.1.2.3",
+            "http://[fd00::5]",
+        ];
+        let public = ["https://web.example.com", "https://203.0.113.7:8443"];
+        let mut host = ssh::Host::default();
+
+        for url in private {
+            assert!(wants_tunnel(&host, &Endpoint::parse(url).unwrap()), "{url}");
+        }
+        for url in public {
+            assert!(
+                !wants_tunnel(&host, &Endpoint::parse(url).unwrap()),
+                "{url}"
+            );
+        }
+        assert!(Endpoint::parse("http://grafana:3000").unwrap().is_private());
+
+        // The host setting wins
+        host.metadata
+            .insert("web-tunnel".to_string(), "yes".to_string());
+        assert!(wants_tunnel(&host, &Endpoint::parse(public[0]).unwrap()));
+        host.metadata
+            .insert("web-tunnel".to_string(), "no".to_string());
+        assert!(!wants_tunnel(&host, &Endpoint::parse(private[0]).unwrap()));
+
+        let tunnelled = Endpoint::parse("https://10.1.2.3:8443/ui")
+            .unwrap()
+            .through_tunnel(50123);
+        assert_eq!(tunnelled.to_string(), "https://127.0.0.1:50123/ui");
+    }
+}
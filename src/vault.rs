@@ -0,0 +1,291 @@
+//! Encrypted state (`--vault`), for shared machines: the state file (histories, tab names,
+//! workspaces, saved searches, settings, host facts...) is kept in `<state file>.vault`,
+//! encrypted with XChaCha20-Poly1305 under a key derived from a passphrase with Argon2id.
+//!
+//! The passphrase is asked before the interface starts, unless `SSHS_VAULT_PASSPHRASE` is set or
+//! it is found in the OS keychain (`--vault-keychain`). The SSH configuration, and the host
+//! metadata it holds, stay as they are: other tools read them too. So do the session logs, the
+//! audit log and the statistics file.
+
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Start of every vault file, authenticated along with the salt
+const MAGIC: &[u8] = b"sshs-vault-1\n";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Passphrase used instead of asking for it, for scripts
+pub const PASSPHRASE_ENV: &str = "SSHS_VAULT_PASSPHRASE";
+
+/// Service of the keychain entries, one per vault file
+const KEYCHAIN_SERVICE: &str = "sshs";
+
+/// Wrong passphrases typed before giving up
+const ATTEMPTS: usize = 3;
+
+/// Key of a vault, derived from its passphrase and salt.
+#[derive(Clone)]
+pub struct Vault {
+    cipher: XChaCha20Poly1305,
+    salt: [u8; SALT_LEN],
+}
+
+impl fmt::Debug for Vault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Vault")
+    }
+}
+
+impl Vault {
+    /// Key of a new vault, with a random salt.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the key cannot be derived.
+    pub fn create(passphrase: &str) -> Result<Vault> {
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+
+        Vault::derive(passphrase, salt)
+    }
+
+    /// Key of the vault `data` was read from, checked against it.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `data` is not a vault, or `passphrase` does not open it.
+    pub fn unlock(passphrase: &str, data: &[u8]) -> Result<Vault> {
+        let salt = data
+            .strip_prefix(MAGIC)
+            .and_then(|rest| rest.get(..SALT_LEN))
+            .ok_or_else(|| anyhow!("Not an sshs vault"))?;
+
+        let vault = Vault::derive(passphrase, salt.try_into()?)?;
+        vault.decrypt(data)?;
+
+        Ok(vault)
+    }
+
+    fn derive(passphrase: &str, salt: [u8; SALT_LEN]) -> Result<Vault> {
+        let mut key = [0; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| anyhow!("Failed to derive the vault key: {e}"))?;
+
+        Ok(Vault {
+            cipher: XChaCha20Poly1305::new(&key.into()),
+            salt,
+        })
+    }
+
+    /// The vault file holding `plaintext`, under a fresh nonce.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the encryption fails.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let header = [MAGIC, &self.salt].concat();
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| anyhow!("Failed to encrypt the vault"))?;
+
+        Ok([header.as_slice(), nonce.as_slice(), &ciphertext].concat())
+    }
+
+    /// What the vault file `data` holds.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `data` was not written with this key, or was modified since.
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let header_len = MAGIC.len() + SALT_LEN;
+        if data.len() < header_len + NONCE_LEN || !data.starts_with(MAGIC) {
+            bail!("Not an sshs vault");
+        }
+        let (header, rest) = data.split_at(header_len);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        self.cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| anyhow!("Wrong passphrase, or the vault was modified"))
+    }
+}
+
+/// Vault of the state file at `state_path`.
+#[must_use]
+pub fn vault_path(state_path: &Path) -> PathBuf {
+    let mut path = state_path.as_os_str().to_owned();
+    path.push(".vault");
+
+    PathBuf::from(path)
+}
+
+/// Gets the key of the vault of the state file at `raw_state_path`: from
+/// [`PASSPHRASE_ENV`], the keychain when `keychain` is set, or by asking for the passphrase on
+/// the terminal. A new vault asks for it twice. Passphrases typed are saved to the keychain
+/// when `keychain` is set.
+///
+/// # Errors
+///
+/// Will return `Err` if the vault cannot be read, or the passphrase is wrong [`ATTEMPTS`]
+/// times.
+pub fn open(raw_state_path: &str, keychain: bool) -> Result<Vault> {
+    let path = vault_path(Path::new(shellexpand::tilde(raw_state_path).as_ref()));
+    let entry = keychain
+        .then(|| keyring::Entry::new(KEYCHAIN_SERVICE, &path.display().to_string()))
+        .transpose()
+        .context("Failed to open the keychain")?;
+    let known = std::env::var(PASSPHRASE_ENV)
+        .ok()
+        .or_else(|| entry.as_ref()?.get_password().ok());
+
+    let data = match fs::read(&path) {
+        Ok(data) => Some(data),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read vault '{}'", path.display()))
+        }
+    };
+
+    let Some(data) = data else {
+        let passphrase = match known {
+            Some(passphrase) => passphrase,
+            None => new_passphrase(&path)?,
+        };
+        remember(entry.as_ref(), &passphrase);
+        return Vault::create(&passphrase);
+    };
+
+    // A passphrase changed since it was saved is asked for again
+    if let Some(vault) = known.and_then(|passphrase| Vault::unlock(&passphrase, &data).ok()) {
+        return Ok(vault);
+    }
+    for attempt in 1..=ATTEMPTS {
+        let passphrase = rpassword::prompt_password(format!("Passphrase of {}: ", path.display()))?;
+        match Vault::unlock(&passphrase, &data) {
+            Ok(vault) => {
+                remember(entry.as_ref(), &passphrase);
+                return Ok(vault);
+            }
+            Err(e) if attempt < ATTEMPTS => eprintln!("{e}"),
+            Err(e) => return Err(e),
+        }
+    }
+
+    unreachable!("the last attempt returns")
+}
+
+fn new_passphrase(path: &Path) -> Result<String> {
+    let passphrase =
+        rpassword::prompt_password(format!("Passphrase of the new vault {}: ", path.display()))?;
+    if passphrase.is_empty() {
+        bail!("The passphrase of the vault cannot be empty");
+    }
+    if rpassword::prompt_password("Passphrase again: ")? != passphrase {
+        bail!("The passphrases do not match");
+    }
+
+    Ok(passphrase)
+}
+
+/// Saves the passphrase to the keychain. Only a convenience, the vault opens without it.
+fn remember(entry: Option<&keyring::Entry>, passphrase: &str) {
+    if let Some(Err(e)) = entry.map(|entry| entry.set_password(passphrase)) {
+        eprintln!("The passphrase could not be saved to the keychain: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_and_unlock() {
+        let vault = Vault::create("correct horse").unwrap();
+        let data = vault.encrypt(b"{\"version\":1}").unwrap();
+        assert!(data.starts_with(MAGIC));
+        assert!(!data.windows(7).any(|window| window == b"version"));
+        // A fresh nonce every time
+        assert_ne!(vault.encrypt(b"{\"version\":1}").unwrap(), data);
+
+        let unlocked = Vault::unlock("correct horse", &data).unwrap();
+        assert_eq!(unlocked.decrypt(&data).unwrap(), b"{\"version\":1}");
+
+        assert!(Vault::unlock("wrong horse", &data).is_err());
+        assert!(Vault::unlock("correct horse", b"{\"version\":1}").is_err());
+
+        let mut modified = data.clone();
+        *modified.last_mut().unwrap() ^= 1;
+        assert!(vault.decrypt(&modified).is_err());
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let data = Vault::create("correct horse")
+            .unwrap()
+            .encrypt(b"{\"version\":1}")
+            .unwrap();
+
+        for passphrase in ["wrong horse", "", "correct horse "] {
+            let message = Vault::unlock(passphrase, &data).unwrap_err().to_string();
+            assert_eq!(message, "Wrong passphrase, or the vault was modified");
+        }
+
+        // Another vault under the same passphrase has its own salt, so its own key
+        let other = Vault::create("correct horse").unwrap();
+        assert!(other.decrypt(&data).is_err());
+    }
+
+    #[test]
+    fn test_corrupted_vault() {
+        let vault = Vault::create("correct horse").unwrap();
+        let data = vault.encrypt(b"{\"version\":1}").unwrap();
+        let header_len = MAGIC.len() + SALT_LEN;
+
+        // The salt and the magic are authenticated, the nonce and the ciphertext checked
+        for index in [
+            MAGIC.len() - 1,
+            MAGIC.len(),
+            header_len,
+            header_len + NONCE_LEN,
+        ] {
+            let mut modified = data.clone();
+            modified[index] ^= 1;
+            assert!(vault.decrypt(&modified).is_err(), "byte {index}");
+        }
+
+        let mut salt_changed = data.clone();
+        salt_changed[MAGIC.len()] ^= 1;
+        assert!(Vault::unlock("correct horse", &salt_changed).is_err());
+
+        for truncated in [&data[..header_len + NONCE_LEN - 1], &data[..data.len() - 1]] {
+            assert!(vault.decrypt(truncated).is_err());
+        }
+        let mut extended = data.clone();
+        extended.push(0);
+        assert!(vault.decrypt(&extended).is_err());
+    }
+}
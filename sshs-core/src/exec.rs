@@ -0,0 +1,75 @@
+//! Every process sshs starts goes through here, so that the safe mode (`--no-exec`) refuses
+//! them all in one place: `ssh`, the hooks, editors, `ssh-keygen`, `ssh-add`, the browser...
+
+use std::io;
+use std::process::{Child, Command, ExitStatus, Output};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Refuses every process started from now on, until sshs exits.
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+fn check(command: &Command, disabled: bool) -> io::Result<()> {
+    if !disabled {
+        return Ok(());
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!(
+            "not running '{}', process execution is disabled (--no-exec)",
+            command.get_program().to_string_lossy()
+        ),
+    ))
+}
+
+/// [`Command::status`], unless process execution is disabled.
+///
+/// # Errors
+///
+/// Will return `Err` if process execution is disabled or the command cannot be started.
+pub fn status(command: &mut Command) -> io::Result<ExitStatus> {
+    check(command, DISABLED.load(Ordering::Relaxed))?;
+    command.status()
+}
+
+/// [`Command::output`], unless process execution is disabled.
+///
+/// # Errors
+///
+/// Will return `Err` if process execution is disabled or the command cannot be started.
+pub fn output(command: &mut Command) -> io::Result<Output> {
+    check(command, DISABLED.load(Ordering::Relaxed))?;
+    command.output()
+}
+
+/// [`Command::spawn`], unless process execution is disabled.
+///
+/// # Errors
+///
+/// Will return `Err` if process execution is disabled or the command cannot be started.
+pub fn spawn(command: &mut Command) -> io::Result<Child> {
+    check(command, DISABLED.load(Ordering::Relaxed))?;
+    command.spawn()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_execution_is_refused() {
+        let command = Command::new("ssh");
+        assert!(check(&command, false).is_ok());
+
+        let error = check(&command, true).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            error.to_string(),
+            "not running 'ssh', process execution is disabled (--no-exec)"
+        );
+    }
+}
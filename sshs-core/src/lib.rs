@@ -0,0 +1,34 @@
+//! Host inventory behind sshs, without any terminal UI: other tools (launchers, editor
+//! plugins...) can list and search the same hosts sshs shows.
+//!
+//! - [`ssh_config`] parses an SSH configuration, following its `Include` directives, into
+//!   [`ssh_config::Host`] blocks.
+//! - [`ssh::parse_config`] turns those blocks into [`ssh::Host`]s: one per connectable host,
+//!   with its `#_` metadata (tags, descriptions, colors...).
+//! - [`ssh_config::diff`] compares two configurations host by host, options resolved.
+//! - [`shared_metadata`] merges the metadata a team keeps outside of the configuration.
+//! - [`searchable::Searchable`] filters a list with a search predicate, as the host list does.
+//! - [`exec`] runs the processes (`ssh`, hooks...), and refuses them all in safe mode.
+//! - [`log`] writes the informational messages to stderr, unless in quiet mode.
+//!
+//! ```no_run
+//! use sshs_core::{searchable::Searchable, ssh};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let hosts = ssh::parse_config(&"~/.ssh/config".to_string())?;
+//! let matches = Searchable::new(hosts, "prod", |host: &&ssh::Host, value: &str| {
+//!     host.name.contains(value)
+//! });
+//! for host in matches.iter() {
+//!     println!("{} ({})", host.name, host.destination);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod exec;
+pub mod log;
+pub mod searchable;
+pub mod shared_metadata;
+pub mod ssh;
+pub mod ssh_config;
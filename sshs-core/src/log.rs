@@ -0,0 +1,25 @@
+//! Informational messages (the command of a hook being run, dotfiles being pushed...), written
+//! to stderr so that stdout only carries what was asked for, and silenced in quiet mode
+//! (`--quiet`). Errors do not go through here: they are always reported.
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Silences the informational messages from now on, until sshs exits.
+pub fn set_quiet() {
+    QUIET.store(true, Ordering::Relaxed);
+}
+
+#[must_use]
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Writes `message` to stderr, unless in quiet mode.
+pub fn info(message: impl Display) {
+    if !is_quiet() {
+        eprintln!("{message}");
+    }
+}
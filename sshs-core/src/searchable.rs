@@ -57,6 +57,11 @@ where
             .collect();
     }
 
+    /// Hides the filtered items for which `keep` is false, until the next search.
+    pub fn retain(&mut self, keep: impl FnMut(&T) -> bool) {
+        self.filtered.retain(keep);
+    }
+
     #[allow(clippy::must_use_candidate)]
     pub fn len(&self) -> usize {
         self.filtered.len()
@@ -0,0 +1,131 @@
+//! Host metadata shared by a team (tags, descriptions, colors...), kept in a JSON file outside
+//! of the SSH configuration, e.g. checked into a team repository:
+//!
+//! ```json
+//! {
+//!   "web": { "tags": ["prod", "frontend"], "description": "Public site", "color": "red" },
+//!   "db": { "tags": ["prod"] }
+//! }
+//! ```
+//!
+//! It is merged into the hosts at load; the personal metadata of the SSH configuration wins.
+
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::ssh::Host;
+
+/// Metadata of a host, as written in the shared file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct SharedHostMetadata {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Any other `# sshs:key=value` metadata
+    #[serde(flatten)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// Shared metadata, by host name.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct SharedMetadata {
+    pub hosts: BTreeMap<String, SharedHostMetadata>,
+}
+
+impl SharedMetadata {
+    /// Loads the shared metadata file. A missing file (e.g. a repository not cloned yet) is
+    /// empty.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file cannot be read or is not valid.
+    pub fn load(raw_path: &str) -> anyhow::Result<SharedMetadata> {
+        let path = shellexpand::tilde(raw_path).to_string();
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(SharedMetadata::default())
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read shared metadata '{path}'"))
+            }
+        };
+
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse shared metadata '{path}'"))
+    }
+
+    /// Merges the shared metadata into `hosts`. Values set in the SSH configuration are kept,
+    /// shared tags are added after the host's own.
+    pub fn apply(&self, hosts: &mut [Host]) {
+        for host in hosts {
+            let Some(shared) = self.hosts.get(&host.name) else {
+                continue;
+            };
+
+            for tag in &shared.tags {
+                if !host.tags.contains(tag) {
+                    host.tags.push(tag.clone());
+                }
+            }
+            for (key, value) in &shared.metadata {
+                host.metadata
+                    .entry(key.to_lowercase())
+                    .or_insert_with(|| value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_shared_metadata_merges_under_personal_metadata() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("hosts.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "web": { "tags": ["prod", "frontend"], "description": "Public site", "color": "red" },
+                "unknown": { "tags": ["legacy"] }
+            }"#,
+        )
+        .unwrap();
+
+        let mut hosts = vec![
+            Host {
+                name: "web".to_string(),
+                tags: vec!["frontend".to_string(), "mine".to_string()],
+                metadata: [("color".to_string(), "green".to_string())].into(),
+                ..Default::default()
+            },
+            Host {
+                name: "db".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let shared = SharedMetadata::load(path.to_str().unwrap()).unwrap();
+        shared.apply(&mut hosts);
+
+        assert_eq!(hosts[0].tags, vec!["frontend", "mine", "prod"]);
+        assert_eq!(hosts[0].metadata_value("color"), Some("green"));
+        assert_eq!(hosts[0].metadata_value("description"), Some("Public site"));
+        assert!(hosts[1].tags.is_empty());
+        assert!(hosts[1].metadata.is_empty());
+
+        let missing = dir.path().join("missing.json");
+        assert_eq!(
+            SharedMetadata::load(missing.to_str().unwrap()).unwrap(),
+            SharedMetadata::default()
+        );
+
+        std::fs::write(&path, "{ \"web\": [] }").unwrap();
+        assert!(SharedMetadata::load(path.to_str().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,1758 @@
+use anyhow::anyhow;
+use handlebars::Handlebars;
+use itertools::Itertools;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::exec;
+use crate::ssh_config::{
+    self,
+    host::{literal_patterns, Origin, PatternDeclaration},
+    parser_error::{ConfigDiagnostic, ParseError},
+    HostVecExt,
+};
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct Host {
+    pub name: String,
+    pub aliases: String,
+    pub user: Option<String>,
+    pub destination: String,
+    pub port: Option<String>,
+    pub proxy_command: Option<String>,
+    /// Hosts `ssh` jumps through, comma-separated (`ProxyJump`)
+    pub proxy_jump: Option<String>,
+    /// `ForwardAgent` is enabled for this host in the SSH configuration
+    pub forward_agent: bool,
+    pub identity_file: Option<String>,
+    pub certificate_file: Option<String>,
+    pub tags: Vec<String>,
+    /// sshs-specific metadata read from `# sshs:key=value` comments (tags excluded)
+    pub metadata: BTreeMap<String, String>,
+    /// Configuration file the host is declared in (may be an included file)
+    pub source_file: Option<PathBuf>,
+    /// Where the options come from: file, line and `Host` block
+    #[serde(skip)]
+    pub origins: HashMap<ssh_config::EntryType, Origin>,
+    /// Alias the host is listed by in the alias rows, and connected to by, so that `ssh`
+    /// applies the configuration rules of that name
+    #[serde(skip)]
+    pub connect_as: Option<String>,
+}
+
+/// Options shown in the host details with where they come from, by label
+pub const DETAILED_OPTIONS: [(&str, ssh_config::EntryType); 6] = [
+    ("Destination", ssh_config::EntryType::Hostname),
+    ("User", ssh_config::EntryType::User),
+    ("Port", ssh_config::EntryType::Port),
+    ("Identity", ssh_config::EntryType::IdentityFile),
+    ("Certificate", ssh_config::EntryType::CertificateFile),
+    ("Jump", ssh_config::EntryType::ProxyJump),
+];
+
+/// Public keys tried, in order, for hosts without an `IdentityFile`
+const DEFAULT_PUBLIC_KEYS: &[&str] = &[
+    "~/.ssh/id_ed25519.pub",
+    "~/.ssh/id_ecdsa.pub",
+    "~/.ssh/id_rsa.pub",
+];
+
+/// Stand-ins for the spaces and quotes of template expressions while the template is split
+const PROTECTED_SPACE: &str = "\u{1f}";
+const PROTECTED_QUOTE: &str = "\u{1e}";
+
+/// Separates the arguments of a block (`{{#if port}}-p {{port}}{{/if}}`), rendered as a whole
+/// and split afterwards
+const ARGUMENT_SEPARATOR: &str = "\u{1d}";
+
+/// Maximum number of lines of a pre-connect banner
+const BANNER_MAX_LINES: usize = 8;
+
+/// Seconds a web console tunnel stays up at least, it then lasts until the browser closes its
+/// last connection through it
+const TUNNEL_MIN_SECONDS: u32 = 30;
+
+/// Seconds a host key scan waits for the host to answer
+const HOST_KEY_TIMEOUT_SECONDS: u32 = 5;
+
+/// Placeholders of template hosts (`Host gw-%d`), replaced by a value asked for when connecting:
+/// `%d` accepts a number, `%s` a word.
+const TEMPLATE_PLACEHOLDERS: &[&str] = &["%d", "%s"];
+
+/// Percent tokens `ssh` expands in a `ProxyCommand`: the destination, the host name, the port
+/// and the user
+const PROXY_COMMAND_TOKENS: [char; 4] = ['h', 'n', 'p', 'r'];
+
+/// Expands the OpenSSH percent tokens (`%h`, `%p`, ...) of `text` with their value in `tokens`,
+/// and `%%` to `%`. Returns the expanded text and the tokens without a value, which are left as
+/// they are.
+#[must_use]
+pub fn expand_percent_tokens(text: &str, tokens: &[(char, &str)]) -> (String, Vec<String>) {
+    let mut expanded = String::with_capacity(text.len());
+    let mut unknown = Vec::new();
+
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+
+        let token = chars.next();
+        match (token, tokens.iter().find(|(name, _)| Some(*name) == token)) {
+            (Some('%'), _) => expanded.push('%'),
+            (_, Some((_, value))) => expanded.push_str(value),
+            (token, None) => {
+                let token = token.map_or("%".to_string(), |token| format!("%{token}"));
+                expanded.push_str(&token);
+                unknown.push(token);
+            }
+        }
+    }
+
+    (expanded, unknown)
+}
+
+/// Value of `Hostname` as `ssh` reads it: `%h` is the host name. IPv6 literals are left as
+/// they are, their zone (`fe80::1%eth0`) is not a token.
+fn expand_hostname(hostname: &str, name: &str) -> String {
+    if is_ipv6(unbracket(hostname)) {
+        return hostname.to_string();
+    }
+
+    expand_percent_tokens(hostname, &[('h', name)]).0
+}
+
+/// Splits a command template into the templates of its arguments. The arguments between the
+/// start and the end of a block (`{{#if port}}-p {{port}}{{/if}}`) stay in one template, joined
+/// by [`ARGUMENT_SEPARATOR`].
+fn split_command_template(pattern: &str) -> anyhow::Result<Vec<String>> {
+    // Expressions may contain spaces and quotes (`{{#if user}}`), keep them in one argument
+    let expression = regex::Regex::new(r"\{\{.*?\}\}")?;
+    let block_start = regex::Regex::new(r"\{\{~?#")?;
+    let block_end = regex::Regex::new(r"\{\{~?/")?;
+    let protected = expression.replace_all(pattern, |captures: &regex::Captures| {
+        captures[0]
+            .replace(' ', PROTECTED_SPACE)
+            .replace('"', PROTECTED_QUOTE)
+    });
+    let args =
+        shlex::split(&protected).ok_or(anyhow!("Failed to parse command template: {pattern}"))?;
+
+    let mut templates: Vec<String> = Vec::new();
+    let mut open_blocks = 0;
+    for arg in args {
+        let arg = arg
+            .replace(PROTECTED_SPACE, " ")
+            .replace(PROTECTED_QUOTE, "\"");
+        let starts = block_start.find_iter(&arg).count();
+        let ends = block_end.find_iter(&arg).count();
+        match templates.last_mut() {
+            Some(template) if open_blocks > 0 => {
+                template.push_str(ARGUMENT_SEPARATOR);
+                template.push_str(&arg);
+            }
+            _ => templates.push(arg),
+        }
+        open_blocks = (open_blocks + starts).saturating_sub(ends);
+    }
+
+    Ok(templates)
+}
+
+/// Values of the command templates, see [`Host::template_context`].
+#[derive(Serialize)]
+struct TemplateContext<'a> {
+    #[serde(flatten)]
+    host: &'a Host,
+    /// `-p<port>`, a single argument; empty without a `Port`
+    port_flag: String,
+    /// `<user>@`, to put before the destination; empty without a `User`
+    user_at: String,
+}
+
+handlebars::handlebars_helper!(default_helper: |value: Json, fallback: Json| {
+    match value {
+        serde_json::Value::Null => fallback.clone(),
+        serde_json::Value::String(s) if s.is_empty() => fallback.clone(),
+        value => value.clone(),
+    }
+});
+
+/// Handlebars registry of the command templates: nothing is escaped, and
+/// `{{default value "fallback"}}` replaces a missing or empty value.
+fn template_registry() -> Handlebars<'static> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars.register_helper("default", Box::new(default_helper));
+
+    handlebars
+}
+
+/// Names of the values available in the command templates, e.g. `{{destination}}`.
+/// `metadata` holds the `# sshs:key=value` metadata, used as `{{metadata.key}}`.
+#[must_use]
+pub fn template_placeholders() -> Vec<String> {
+    match serde_json::to_value(Host::default().template_context()) {
+        Ok(serde_json::Value::Object(fields)) => fields.keys().cloned().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Checks a command template before it is used: it must split into arguments, parse, and
+/// only use known placeholders. Every unknown placeholder is listed in the error.
+///
+/// # Errors
+///
+/// Will return `Err` if the template is invalid or uses unknown placeholders.
+pub fn validate_command_template(pattern: &str) -> anyhow::Result<()> {
+    validate_command_template_with(pattern, &[])
+}
+
+/// Same as [`validate_command_template`], for a template also given the extra `values` of
+/// [`Host::run_command_template_with_values`].
+///
+/// # Errors
+///
+/// Will return `Err` if the template is invalid or uses unknown placeholders.
+pub fn validate_command_template_with(pattern: &str, values: &[&str]) -> anyhow::Result<()> {
+    let sample = Host {
+        name: "sample".to_string(),
+        aliases: "sample-alias".to_string(),
+        user: Some("user".to_string()),
+        destination: "sample.example.com".to_string(),
+        port: Some("22".to_string()),
+        proxy_command: Some("nc %h %p".to_string()),
+        proxy_jump: Some("bastion".to_string()),
+        forward_agent: true,
+        identity_file: Some("~/.ssh/id_ed25519".to_string()),
+        certificate_file: Some("~/.ssh/id_ed25519-cert.pub".to_string()),
+        tags: vec!["tag".to_string()],
+        metadata: BTreeMap::new(),
+        source_file: Some(PathBuf::from("~/.ssh/config")),
+        origins: HashMap::new(),
+        connect_as: None,
+    };
+    let mut data = serde_json::to_value(sample.template_context())?;
+    for value in values {
+        insert_placeholder(&mut data, value);
+    }
+
+    // Strict mode fails on the first missing value: give it one and render again
+    let mut handlebars = template_registry();
+    handlebars.set_strict_mode(true);
+    let mut unknown: Vec<String> = Vec::new();
+    for arg in split_command_template(pattern)? {
+        loop {
+            let Err(e) = handlebars.render_template(&arg, &data) else {
+                break;
+            };
+            match e.reason() {
+                handlebars::RenderErrorReason::MissingVariable(Some(path))
+                    if !unknown.contains(path) =>
+                {
+                    insert_placeholder(&mut data, path);
+                    unknown.push(path.clone());
+                }
+                _ => return Err(anyhow!("Invalid command template '{pattern}': {e}")),
+            }
+        }
+    }
+
+    // Any metadata key may be used, hosts without it render it empty
+    unknown.retain(|path| !path.starts_with("metadata."));
+    if !unknown.is_empty() {
+        return Err(anyhow!(
+            "Unknown placeholders in command template '{pattern}': {}. Available: {}, \
+             metadata.<key>",
+            unknown.join(", "),
+            template_placeholders()
+                .iter()
+                .map(String::as_str)
+                .chain(values.iter().copied())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// Sets `path` (`a.b.c`) to an empty string in `data`, creating the objects on the way.
+fn insert_placeholder(data: &mut serde_json::Value, path: &str) {
+    let mut value = data;
+    for key in path.split('.') {
+        if !value.is_object() {
+            *value = serde_json::Value::Object(serde_json::Map::new());
+        }
+        let serde_json::Value::Object(object) = value else {
+            return;
+        };
+        value = object.entry(key).or_insert(serde_json::Value::Null);
+    }
+    *value = serde_json::Value::String(String::new());
+}
+
+/// Environment variables of the extra template values, e.g. `SSHS_EXIT_CODE` for `exit_code`.
+fn values_env(values: &[(&str, String)]) -> Vec<(String, String)> {
+    values
+        .iter()
+        .map(|(name, value)| (format!("SSHS_{}", name.to_uppercase()), value.clone()))
+        .collect()
+}
+
+/// Returns the name of the local account, the user `ssh` connects as when none is configured.
+#[must_use]
+pub fn local_username() -> Option<String> {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .ok()
+        .filter(|user| !user.is_empty())
+}
+
+/// Prepares the command built by [`Host::build_ssh_command`] or [`Host::build_batch_command`].
+#[must_use]
+pub fn command(args: &[String]) -> Command {
+    let mut command = Command::new(&args[0]);
+    command.args(&args[1..]);
+    command
+}
+
+/// Wraps the `ssh` command `args` in `script`, which copies everything the session prints to
+/// `log` and exits with the code of `ssh`. The util-linux and BSD versions of `script` take
+/// their arguments differently.
+#[must_use]
+pub fn recorded_command(args: &[String], log: &Path) -> Vec<String> {
+    let log = log.display().to_string();
+    if cfg!(target_os = "linux") {
+        let command = shlex::try_join(args.iter().map(String::as_str)).unwrap_or_default();
+        ["script", "--quiet", "--flush", "--return", "--command"]
+            .map(String::from)
+            .into_iter()
+            .chain([command, log])
+            .collect()
+    } else {
+        ["script", "-q", "-F"]
+            .map(String::from)
+            .into_iter()
+            .chain(std::iter::once(log))
+            .chain(args.iter().cloned())
+            .collect()
+    }
+}
+
+/// Strips the brackets of an IPv6 literal (`[2001:db8::1]`), which `ssh` does not accept as a
+/// destination.
+#[must_use]
+pub fn unbracket(destination: &str) -> &str {
+    destination
+        .strip_prefix('[')
+        .and_then(|address| address.strip_suffix(']'))
+        .unwrap_or(destination)
+}
+
+/// Whether `address` is an IPv6 literal, optionally with a zone (`fe80::1%eth0`).
+#[must_use]
+pub fn is_ipv6(address: &str) -> bool {
+    let address = address
+        .split_once('%')
+        .map_or(address, |(address, _)| address);
+    address.parse::<std::net::Ipv6Addr>().is_ok()
+}
+
+/// Returns the current day, as a number of days since 1970-01-01 (UTC).
+#[must_use]
+pub fn today() -> i64 {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+
+    i64::try_from(seconds / 86_400).unwrap_or(i64::MAX)
+}
+
+/// Parses a `YYYY-MM-DD` date into a number of days since 1970-01-01.
+#[must_use]
+pub fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.trim().splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_length = match month {
+        2 if is_leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if !(1..=12).contains(&month) || !(1..=month_length).contains(&day) {
+        return None;
+    }
+
+    // Days from civil, with years starting in March so the leap day comes last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+/// Formats a number of days since 1970-01-01 as `YYYY-MM-DD`, the reverse of [`parse_date`].
+#[must_use]
+pub fn format_date(days: i64) -> String {
+    // Civil from days, with years starting in March as in parse_date
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Per-connection overrides applied on top of the host configuration.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConnectOptions {
+    /// Local port of an ad-hoc dynamic (SOCKS) forward (`-D <port>`)
+    pub dynamic_forward: Option<u16>,
+    /// Forward the local SSH agent to the remote host (`-A`)
+    pub forward_agent: bool,
+    /// Command run on the remote host instead of a login shell (with a TTY, `-t`)
+    pub remote_command: Option<String>,
+    /// Value substituted into the placeholders of a template host
+    pub template_value: Option<String>,
+    /// The host's important banner has been shown and acknowledged
+    pub banner_acknowledged: bool,
+    /// Jump host replacing the configured `ProxyJump` for this connection only (`-J <host>`)
+    pub jump_host: Option<String>,
+    /// File the output of the session is copied to, see [`recorded_command`]
+    pub session_log: Option<PathBuf>,
+    /// The session runs in a new tab instead of taking over the terminal
+    pub in_tab: bool,
+}
+
+impl ConnectOptions {
+    /// Returns the SOCKS endpoint exposed by this connection, if any.
+    #[must_use]
+    pub fn socks_endpoint(&self) -> Option<String> {
+        self.dynamic_forward
+            .map(|port| format!("socks5://localhost:{port}"))
+    }
+}
+
+impl Host {
+    /// Builds the arguments of the interactive `ssh` command used to connect to this host, run
+    /// without a shell so no value needs quoting.
+    ///
+    /// Without a `User`, the user is left to `ssh`, which uses the local account.
+    #[must_use]
+    pub fn build_ssh_command(&self, options: &ConnectOptions) -> Vec<String> {
+        let mut command = vec![
+            "ssh".to_string(),
+            "-o".to_string(),
+            "LogLevel=ERROR".to_string(),
+            "-o".to_string(),
+            "StrictHostKeyChecking=accept-new".to_string(),
+        ];
+
+        if options.forward_agent {
+            command.push("-A".to_string());
+        }
+
+        if let Some(socks_port) = options.dynamic_forward {
+            command.extend(["-D".to_string(), socks_port.to_string()]);
+        }
+
+        // The first value obtained wins in `ssh`, so `-J` takes precedence over the
+        // configuration's `ProxyJump` (and `ProxyCommand`)
+        if let Some(jump_host) = &options.jump_host {
+            command.extend(["-J".to_string(), jump_host.clone()]);
+        }
+
+        if options.remote_command.is_some() {
+            command.push("-t".to_string());
+        }
+
+        command.extend(self.ssh_destination());
+
+        // `ssh` hands the remote command to the remote shell as is
+        if let Some(remote_command) = &options.remote_command {
+            command.push(remote_command.clone());
+        }
+
+        command
+    }
+
+    /// Builds the arguments of a non-interactive `ssh` command running `script` on this host.
+    /// It fails instead of prompting for a password.
+    #[must_use]
+    pub fn build_batch_command(&self, script: &str) -> Vec<String> {
+        let mut command = vec![
+            "ssh".to_string(),
+            "-o".to_string(),
+            "LogLevel=ERROR".to_string(),
+            "-o".to_string(),
+            "BatchMode=yes".to_string(),
+            "-o".to_string(),
+            "StrictHostKeyChecking=accept-new".to_string(),
+        ];
+        command.extend(self.ssh_destination());
+        command.push(script.to_string());
+
+        command
+    }
+
+    /// Builds the arguments of an `ssh` command forwarding `local_port` of the loopback
+    /// interface to `address:port` as reached from this host. `ssh` goes to the background once
+    /// the forward is up, and fails instead of prompting for a password.
+    #[must_use]
+    pub fn build_tunnel_command(&self, local_port: u16, address: &str, port: u16) -> Vec<String> {
+        let address = if address.contains(':') {
+            format!("[{address}]")
+        } else {
+            address.to_string()
+        };
+
+        let mut command = self.build_batch_command(&format!("sleep {TUNNEL_MIN_SECONDS}"));
+        command.splice(
+            1..1,
+            [
+                "-f".to_string(),
+                "-o".to_string(),
+                "ExitOnForwardFailure=yes".to_string(),
+                "-L".to_string(),
+                format!("127.0.0.1:{local_port}:{address}:{port}"),
+            ],
+        );
+
+        command
+    }
+
+    /// Builds the arguments of an `ssh` command that stops right after the key exchange, so
+    /// that the host key it logs (`Server host key: ...`) can be read from its error output.
+    /// It never authenticates, and leaves the known hosts files untouched.
+    #[must_use]
+    pub fn build_host_key_command(&self) -> Vec<String> {
+        let mut command = vec![
+            "ssh".to_string(),
+            "-v".to_string(),
+            "-o".to_string(),
+            "BatchMode=yes".to_string(),
+            "-o".to_string(),
+            format!("ConnectTimeout={HOST_KEY_TIMEOUT_SECONDS}"),
+            "-o".to_string(),
+            "PreferredAuthentications=none".to_string(),
+            "-o".to_string(),
+            "StrictHostKeyChecking=no".to_string(),
+            "-o".to_string(),
+            "UserKnownHostsFile=/dev/null".to_string(),
+        ];
+        command.extend(self.ssh_destination());
+        command.push("true".to_string());
+
+        command
+    }
+
+    /// `-p <port> -- [user@]destination` arguments of the `ssh` commands. `--` keeps a
+    /// destination starting with `-` from being read as an option.
+    fn ssh_destination(&self) -> [String; 4] {
+        let port = self.port.as_deref().unwrap_or("22");
+        let destination = unbracket(&self.destination);
+        let destination = match &self.user {
+            Some(user) => format!("{user}@{destination}"),
+            None => destination.to_string(),
+        };
+
+        [
+            "-p".to_string(),
+            port.to_string(),
+            "--".to_string(),
+            destination,
+        ]
+    }
+
+    /// Reads the public key matching the host's `IdentityFile`, or the first default identity
+    /// (`~/.ssh/id_ed25519.pub`, ...) when none is configured. Falls back to the keys loaded in
+    /// the SSH agent, the only source holding more than one key.
+    ///
+    /// Returns the keys together with a short description of where they came from.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if no public key can be found.
+    pub fn public_keys(&self) -> anyhow::Result<(Vec<String>, String)> {
+        let candidates = match &self.identity_file {
+            Some(identity_file) => vec![format!("{identity_file}.pub")],
+            None => DEFAULT_PUBLIC_KEYS
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        };
+
+        for candidate in candidates {
+            let path = shellexpand::tilde(&candidate).to_string();
+            if let Ok(key) = std::fs::read_to_string(&path) {
+                return Ok((vec![key.trim().to_string()], candidate));
+            }
+        }
+
+        let agent_keys = exec::output(Command::new("ssh-add").arg("-L"))?;
+        let keys: Vec<String> = String::from_utf8_lossy(&agent_keys.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|key| agent_keys.status.success() && !key.is_empty())
+            .map(str::to_string)
+            .collect();
+        if keys.is_empty() {
+            return Err(anyhow!("No public key found for '{}'", self.name));
+        }
+
+        Ok((keys, "SSH agent".to_string()))
+    }
+
+    /// Certificate `ssh` presents for this host: the `CertificateFile`, or the `-cert.pub`
+    /// file next to the `IdentityFile`, which `ssh` loads on its own.
+    #[must_use]
+    pub fn certificate_path(&self) -> Option<String> {
+        if let Some(certificate_file) = &self.certificate_file {
+            return Some(certificate_file.clone());
+        }
+
+        let implicit = format!("{}-cert.pub", self.identity_file.as_ref()?);
+        std::path::Path::new(shellexpand::tilde(&implicit).as_ref())
+            .exists()
+            .then_some(implicit)
+    }
+
+    /// Whether the host is a template (`Host gw-%d`) that needs a value before connecting.
+    #[must_use]
+    pub fn is_template(&self) -> bool {
+        TEMPLATE_PLACEHOLDERS
+            .iter()
+            .any(|placeholder| self.name.contains(placeholder))
+    }
+
+    /// A copy of the host for each of its aliases, connected to by that alias. Patterns
+    /// (`*.prod`, `!db`) are not names to connect by and are left out.
+    #[must_use]
+    pub fn alias_rows(&self) -> Vec<Host> {
+        self.aliases
+            .split(", ")
+            .filter(|alias| !alias.is_empty() && !alias.contains(['*', '?', '!']))
+            .map(|alias| Host {
+                connect_as: Some(alias.to_string()),
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// The host as `ssh` is run for: named by the alias it was selected by, if any, which is
+    /// also the destination given to `ssh`, for it to match the rules of that name.
+    #[must_use]
+    pub fn for_connection(mut self) -> Host {
+        if let Some(alias) = self.connect_as.take() {
+            self.destination.clone_from(&alias);
+            self.name = alias;
+        }
+
+        self
+    }
+
+    /// Returns the host with the template placeholders of its name, hostname, user and port
+    /// replaced by `value`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the value is empty, is not a number for a `%d` placeholder, or
+    /// contains characters other than letters, digits, `.`, `-` and `_`.
+    pub fn instantiate(&self, value: &str) -> anyhow::Result<Host> {
+        if self.name.contains("%d") && !value.chars().all(|c| c.is_ascii_digit()) {
+            return Err(anyhow!("'{}' expects a number, got '{value}'", self.name));
+        }
+        if value.is_empty()
+            || !value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        {
+            return Err(anyhow!("Invalid template value '{value}'"));
+        }
+
+        let substitute = |text: &str| {
+            TEMPLATE_PLACEHOLDERS
+                .iter()
+                .fold(text.to_string(), |text, placeholder| {
+                    text.replace(placeholder, value)
+                })
+        };
+
+        Ok(Host {
+            name: substitute(&self.name),
+            destination: substitute(&self.destination),
+            user: self.user.as_deref().map(substitute),
+            port: self.port.as_deref().map(substitute),
+            ..self.clone()
+        })
+    }
+
+    /// Pre-connect banner (change window, on-call contact, ...) declared with
+    /// `# sshs:banner=<text>` (`\n` starts a new line) or read from the file in
+    /// `# sshs:banner-file=<path>`. Long banners are cut to a few lines.
+    #[must_use]
+    pub fn banner(&self) -> Option<String> {
+        let text = match (
+            self.metadata_value("banner"),
+            self.metadata_value("banner-file"),
+        ) {
+            (Some(text), _) => text.replace("\\n", "\n"),
+            (None, Some(path)) => {
+                std::fs::read_to_string(shellexpand::tilde(path).as_ref()).ok()?
+            }
+            (None, None) => return None,
+        };
+
+        let banner = text
+            .lines()
+            .take(BANNER_MAX_LINES)
+            .map(str::trim_end)
+            .join("\n");
+        let banner = banner.trim();
+
+        (!banner.is_empty()).then(|| banner.to_string())
+    }
+
+    /// Whether the banner must be acknowledged before connecting
+    /// (`# sshs:banner-important=yes`).
+    #[must_use]
+    pub fn has_important_banner(&self) -> bool {
+        self.metadata_flag("banner-important") && self.banner().is_some()
+    }
+
+    /// Whether the configured dotfiles are pushed to the host before opening the shell
+    /// (`# sshs:dotfiles=yes`).
+    #[must_use]
+    pub fn wants_dotfiles(&self) -> bool {
+        self.metadata_flag("dotfiles")
+    }
+
+    /// Whether the sessions are recorded to a log file: `# sshs:log=yes` (or `no`) overrides
+    /// `default`, given by `--log-sessions`.
+    #[must_use]
+    pub fn logs_sessions(&self, default: bool) -> bool {
+        self.metadata_value("log")
+            .map_or(default, |_| self.metadata_flag("log"))
+    }
+
+    /// URLs of the web consoles of the host (`#_Web https://host:8443`), separated by spaces or
+    /// commas.
+    #[must_use]
+    pub fn web_endpoints(&self) -> Vec<&str> {
+        self.metadata_list("web")
+    }
+
+    /// Links about the host, such as tickets and runbooks (`#_Links https://wiki/runbook`),
+    /// separated by spaces or commas.
+    #[must_use]
+    pub fn links(&self) -> Vec<&str> {
+        self.metadata_list("links")
+    }
+
+    /// Values of a metadata list, separated by spaces or commas.
+    fn metadata_list(&self, key: &str) -> Vec<&str> {
+        self.metadata_value(key)
+            .map(|values| {
+                values
+                    .split([' ', '\t', ','])
+                    .filter(|value| !value.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Whether the web consoles are opened through an SSH tunnel (`# sshs:web-tunnel=yes`),
+    /// `None` to decide from the address of each console.
+    #[must_use]
+    pub fn web_tunnel(&self) -> Option<bool> {
+        self.metadata_value("web-tunnel")
+            .map(|_| self.metadata_flag("web-tunnel"))
+    }
+
+    /// Whether a yes/no metadata value is set to yes (`yes`, `true` or `1`).
+    fn metadata_flag(&self, key: &str) -> bool {
+        self.metadata_value(key)
+            .is_some_and(|value| matches!(value.to_lowercase().as_str(), "yes" | "true" | "1"))
+    }
+
+    /// Day the host is decommissioned (`#_Expires 2025-01-31`), see [`parse_date`].
+    #[must_use]
+    pub fn expires_on(&self) -> Option<i64> {
+        self.metadata_value("expires").and_then(parse_date)
+    }
+
+    /// Whether the host is past its expiry date on `today`.
+    #[must_use]
+    pub fn is_expired(&self, today: i64) -> bool {
+        self.expires_on()
+            .is_some_and(|expires_on| expires_on < today)
+    }
+
+    /// The detailed options read from a configuration file, with where they come from.
+    #[must_use]
+    pub fn option_origins(&self) -> Vec<(&'static str, &Origin)> {
+        DETAILED_OPTIONS
+            .iter()
+            .filter_map(|(label, entry)| Some((*label, self.origins.get(entry)?)))
+            .filter(|(_, origin)| origin.path.is_some())
+            .collect()
+    }
+
+    /// `ProxyCommand` with its percent tokens expanded as `ssh` would, the user being the local
+    /// one without a `User`.
+    #[must_use]
+    pub fn expanded_proxy_command(&self) -> Option<String> {
+        let proxy_command = self.proxy_command.as_ref()?;
+        let user = self
+            .user
+            .clone()
+            .or_else(local_username)
+            .unwrap_or_default();
+        let values = [
+            unbracket(&self.destination),
+            &self.name,
+            self.port.as_deref().unwrap_or("22"),
+            &user,
+        ];
+        let tokens = PROXY_COMMAND_TOKENS
+            .into_iter()
+            .zip(values)
+            .collect::<Vec<_>>();
+
+        Some(expand_percent_tokens(proxy_command, &tokens).0)
+    }
+
+    /// Percent tokens `ssh` cannot expand, with the option they are in, e.g.
+    /// `("ProxyCommand", "%x")`. The placeholders of template hosts are not tokens.
+    #[must_use]
+    pub fn unexpandable_tokens(&self) -> Vec<(&'static str, String)> {
+        let mut tokens = Vec::new();
+
+        if !is_ipv6(unbracket(&self.destination)) {
+            let (_, unknown) = expand_percent_tokens(&self.destination, &[]);
+            tokens.extend(
+                unknown
+                    .into_iter()
+                    .filter(|token| {
+                        !(self.is_template() && TEMPLATE_PLACEHOLDERS.contains(&token.as_str()))
+                    })
+                    .map(|token| ("Hostname", token)),
+            );
+        }
+
+        if let Some(proxy_command) = &self.proxy_command {
+            let tokens_with_values = PROXY_COMMAND_TOKENS.map(|token| (token, ""));
+            let (_, unknown) = expand_percent_tokens(proxy_command, &tokens_with_values);
+            tokens.extend(unknown.into_iter().map(|token| ("ProxyCommand", token)));
+        }
+
+        tokens
+    }
+
+    /// Returns a metadata value declared with a `# sshs:key=value` comment.
+    #[must_use]
+    pub fn metadata_value(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(String::as_str)
+    }
+
+    /// Values of the command templates: the host's fields, plus ready-made pieces for the
+    /// optional ones.
+    fn template_context(&self) -> TemplateContext<'_> {
+        TemplateContext {
+            host: self,
+            port_flag: self
+                .port
+                .as_ref()
+                .map(|port| format!("-p{port}"))
+                .unwrap_or_default(),
+            user_at: self
+                .user
+                .as_ref()
+                .map(|user| format!("{user}@"))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Renders a Handlebars command template into the arguments of the command.
+    ///
+    /// The template is split into arguments before the host values are substituted, so a value
+    /// always stays within its argument whatever it contains (spaces, quotes, `$`...), and no
+    /// shell ever sees it. A block (`{{#if port}}-p {{port}}{{/if}}`) is rendered as a whole,
+    /// then split on the spaces written in the template. An argument rendering to nothing
+    /// (`{{port_flag}}` without a port) is dropped, unless it is written as an empty string
+    /// (`''`).
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the template has unbalanced quotes or cannot be rendered.
+    pub fn render_command_template(&self, pattern: &str) -> anyhow::Result<Vec<String>> {
+        self.render_command_template_with(pattern, &[])
+    }
+
+    /// Same as [`Host::render_command_template`], with extra `values` next to the host fields,
+    /// e.g. `("exit_code", "0")` for `{{exit_code}}`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the template is invalid.
+    pub fn render_command_template_with(
+        &self,
+        pattern: &str,
+        values: &[(&str, String)],
+    ) -> anyhow::Result<Vec<String>> {
+        let handlebars = template_registry();
+        let mut context = serde_json::to_value(self.template_context())?;
+        if let serde_json::Value::Object(fields) = &mut context {
+            for (name, value) in values {
+                fields.insert((*name).to_string(), value.clone().into());
+            }
+        }
+
+        let mut args = Vec::new();
+        for arg in split_command_template(pattern)? {
+            let rendered = handlebars.render_template(&arg, &context)?;
+            if !arg.contains(ARGUMENT_SEPARATOR) {
+                if !rendered.is_empty() || arg.is_empty() {
+                    args.push(rendered);
+                }
+                continue;
+            }
+            args.extend(
+                rendered
+                    .split(ARGUMENT_SEPARATOR)
+                    .filter(|rendered| !rendered.is_empty())
+                    .map(str::to_string),
+            );
+        }
+
+        Ok(args)
+    }
+
+    /// Renders a Handlebars template of a command run by the remote shell, e.g.
+    /// `grep {{name}} /etc/hosts && uptime`. Unlike [`Host::render_command_template`] the
+    /// command is not split into arguments: it is kept as typed, and the host values are
+    /// quoted for the shell instead.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the template cannot be rendered.
+    pub fn render_remote_command(&self, pattern: &str) -> anyhow::Result<String> {
+        let mut handlebars = template_registry();
+        handlebars.register_escape_fn(|value| {
+            shlex::try_quote(value).map_or_else(|_| String::new(), std::borrow::Cow::into_owned)
+        });
+
+        Ok(handlebars.render_template(pattern, &self.template_context())?)
+    }
+
+    /// Uses the provided Handlebars template to run a command.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the template is invalid or the command cannot be executed.
+    pub fn run_command_template(&self, pattern: &str) -> anyhow::Result<()> {
+        self.run_command_template_with_env(pattern, &[])
+    }
+
+    /// Same as [`Host::run_command_template`], exporting extra environment variables to the
+    /// spawned command.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the template is invalid or the command cannot be executed.
+    pub fn run_command_template_with_env(
+        &self,
+        pattern: &str,
+        env: &[(&str, String)],
+    ) -> anyhow::Result<()> {
+        Self::run_command(self.render_command_template(pattern)?, env)
+    }
+
+    /// Same as [`Host::run_command_template`], with extra `values` both in the template
+    /// (`{{exit_code}}`) and in the environment of the command (`SSHS_EXIT_CODE`).
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the template is invalid or the command cannot be executed.
+    pub fn run_command_template_with_values(
+        &self,
+        pattern: &str,
+        values: &[(&str, String)],
+    ) -> anyhow::Result<()> {
+        let env = values_env(values);
+        let env = env
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect::<Vec<_>>();
+
+        Self::run_command(self.render_command_template_with(pattern, values)?, &env)
+    }
+
+    /// Same as [`Host::run_command_template_with_values`], for the hooks run while the
+    /// interface is drawn: the command runs in the background, without a terminal, and its exit
+    /// code is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the template is invalid or the command cannot be started.
+    pub fn spawn_command_template_with_values(
+        &self,
+        pattern: &str,
+        values: &[(&str, String)],
+    ) -> anyhow::Result<()> {
+        let mut args = VecDeque::from(self.render_command_template_with(pattern, values)?);
+        let command = args.pop_front().ok_or(anyhow!("Failed to get command"))?;
+
+        let mut child = exec::spawn(
+            Command::new(command)
+                .args(args)
+                .envs(values_env(values))
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null()),
+        )?;
+        std::thread::spawn(move || child.wait());
+
+        Ok(())
+    }
+
+    fn run_command(args: Vec<String>, env: &[(&str, String)]) -> anyhow::Result<()> {
+        let mut args = VecDeque::from(args);
+
+        crate::log::info(format_args!(
+            "Running command: {}",
+            shlex::try_join(args.iter().map(String::as_str)).unwrap_or_default()
+        ));
+
+        let command = args.pop_front().ok_or(anyhow!("Failed to get command"))?;
+
+        let status = exec::spawn(
+            Command::new(command)
+                .args(args)
+                .envs(env.iter().map(|(key, value)| (key, value))),
+        )?
+        .wait()?;
+        if !status.success() {
+            // Only exit the process when not running in test mode
+            std::process::exit(status.code().unwrap_or(1));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseConfigError {
+    Io(std::io::Error),
+    SshConfig(ParseError),
+}
+
+impl std::fmt::Display for ParseConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseConfigError::Io(e) => write!(f, "IO error: {e}"),
+            ParseConfigError::SshConfig(e) => write!(f, "SSH config parsing error: {e:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseConfigError::Io(e) => Some(e),
+            ParseConfigError::SshConfig(e) => Some(e), // Now this works since ParseError implements Error
+        }
+    }
+}
+
+impl From<std::io::Error> for ParseConfigError {
+    fn from(e: std::io::Error) -> Self {
+        ParseConfigError::Io(e)
+    }
+}
+
+impl From<ParseError> for ParseConfigError {
+    fn from(e: ParseError) -> Self {
+        ParseConfigError::SshConfig(e)
+    }
+}
+
+/// # Errors
+///
+/// Will return `Err` if the SSH configuration file cannot be parsed.
+pub fn parse_config(raw_path: &String) -> Result<Vec<Host>, ParseConfigError> {
+    let (hosts, diagnostics) = parse_config_lenient(raw_path)?;
+
+    match diagnostics.into_iter().next() {
+        Some(diagnostic) => Err(diagnostic.error.into()),
+        None => Ok(hosts),
+    }
+}
+
+/// A configuration file parsed leniently
+#[derive(Debug)]
+pub struct ParsedConfig {
+    pub hosts: Vec<Host>,
+    /// The lines that could not be parsed
+    pub diagnostics: Vec<ConfigDiagnostic>,
+    /// Where the literal `Host` patterns are declared, the file and the files it includes
+    pub declarations: Vec<PatternDeclaration>,
+}
+
+/// Parses the configuration file, skipping the lines that cannot be parsed. They are returned
+/// along with the hosts.
+///
+/// # Errors
+///
+/// Will return `Err` if the SSH configuration file cannot be read.
+pub fn parse_config_lenient(
+    raw_path: &String,
+) -> Result<(Vec<Host>, Vec<ConfigDiagnostic>), ParseConfigError> {
+    parse_config_with_declarations(raw_path).map(|parsed| (parsed.hosts, parsed.diagnostics))
+}
+
+/// Like [`parse_config_lenient`], also returning where the `Host` patterns are declared, before
+/// the blocks of the same host are merged.
+///
+/// # Errors
+///
+/// Will return `Err` if the SSH configuration file cannot be read.
+pub fn parse_config_with_declarations(raw_path: &String) -> Result<ParsedConfig, ParseConfigError> {
+    let normalized_path = shellexpand::tilde(&raw_path).to_string();
+    let path = std::fs::canonicalize(normalized_path)?;
+
+    let (parsed_hosts, diagnostics) = ssh_config::Parser::new().parse_file_lenient(path)?;
+    let declarations = literal_patterns(&parsed_hosts);
+    let hosts = parsed_hosts
+        .apply_patterns()
+        .apply_match_blocks()
+        .apply_name_to_empty_hostname()
+        .merge_same_hosts()
+        .iter()
+        .map(|host| Host {
+            name: host
+                .get_patterns()
+                .first()
+                .unwrap_or(&String::new())
+                .clone(),
+            aliases: host.get_patterns().iter().skip(1).join(", "),
+            user: host.get(&ssh_config::EntryType::User),
+            destination: expand_hostname(
+                &host
+                    .get(&ssh_config::EntryType::Hostname)
+                    .unwrap_or_default(),
+                host.get_patterns().first().map_or("", String::as_str),
+            ),
+            port: host.get(&ssh_config::EntryType::Port),
+            proxy_command: host.get(&ssh_config::EntryType::ProxyCommand),
+            proxy_jump: host.get(&ssh_config::EntryType::ProxyJump),
+            identity_file: host.get(&ssh_config::EntryType::IdentityFile),
+            certificate_file: host.get(&ssh_config::EntryType::CertificateFile),
+            forward_agent: host
+                .get(&ssh_config::EntryType::ForwardAgent)
+                .is_some_and(|value| !value.eq_ignore_ascii_case("no")),
+            tags: host.tags(),
+            metadata: host
+                .get_metadata()
+                .iter()
+                .filter(|(key, _)| key.as_str() != ssh_config::host::TAGS_METADATA_KEY)
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            source_file: host.source_file().map(std::path::Path::to_path_buf),
+            origins: host.get_origins().clone(),
+            connect_as: None,
+        })
+        .collect();
+
+    Ok(ParsedConfig {
+        hosts,
+        diagnostics,
+        declarations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command_line(args: &[String]) -> String {
+        args.join(" ")
+    }
+
+    fn create_test_host() -> Host {
+        Host {
+            name: "web".to_string(),
+            destination: "web.example.com".to_string(),
+            user: Some("deploy".to_string()),
+            port: Some("2222".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_instantiate_template_host() {
+        let template = Host {
+            name: "gw-%d".to_string(),
+            destination: "10.20.0.%d".to_string(),
+            port: Some("22%d".to_string()),
+            ..Default::default()
+        };
+        assert!(template.is_template());
+        assert!(!create_test_host().is_template());
+
+        let host = template.instantiate("12").unwrap();
+        assert_eq!(host.name, "gw-12");
+        assert_eq!(host.destination, "10.20.0.12");
+        assert_eq!(host.port.as_deref(), Some("2212"));
+        assert!(!host.is_template());
+
+        assert!(template.instantiate("12a").is_err());
+        assert!(template.instantiate("").is_err());
+
+        let words = Host {
+            name: "%s-prod".to_string(),
+            destination: "%s.prod.example.com".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            words.instantiate("billing").unwrap().destination,
+            "billing.prod.example.com"
+        );
+        assert!(words.instantiate("a;rm -rf").is_err());
+    }
+
+    #[test]
+    fn test_banner_from_metadata_and_file() {
+        let mut host = create_test_host();
+        assert_eq!(host.banner(), None);
+
+        host.metadata.insert(
+            "banner".to_string(),
+            "Change window Tue 02:00 UTC\\nOn call: @ops".to_string(),
+        );
+        assert_eq!(
+            host.banner().as_deref(),
+            Some("Change window Tue 02:00 UTC\nOn call: @ops")
+        );
+        assert!(!host.has_important_banner());
+
+        host.metadata
+            .insert("banner-important".to_string(), "yes".to_string());
+        assert!(host.has_important_banner());
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("banner.txt");
+        let long_banner = (1..=20).map(|i| format!("line {i}")).join("\n");
+        std::fs::write(&path, long_banner).unwrap();
+
+        host.metadata.remove("banner");
+        host.metadata
+            .insert("banner-file".to_string(), path.display().to_string());
+        let banner = host.banner().unwrap();
+        assert!(banner.starts_with("line 1\n"));
+        assert_eq!(banner.lines().count(), BANNER_MAX_LINES);
+
+        // A missing banner file shows nothing and never blocks the connection
+        host.metadata
+            .insert("banner-file".to_string(), "/nonexistent/banner".to_string());
+        assert_eq!(host.banner(), None);
+        assert!(!host.has_important_banner());
+    }
+
+    #[test]
+    fn test_host_expiry() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2000-03-01"), Some(11_017));
+        assert_eq!(parse_date("2024-02-29"), Some(19_782));
+        assert_eq!(parse_date("2025-13-01"), None);
+        // Days past the end of the month do not roll into the next one
+        assert_eq!(parse_date("2025-02-31"), None);
+        assert_eq!(parse_date("2025-04-31"), None);
+        assert_eq!(parse_date("2025-02-29"), None);
+        assert_eq!(parse_date("1900-02-29"), None);
+        assert_eq!(parse_date("2000-02-29"), Some(11_016));
+        assert_eq!(parse_date("2025-04-30"), Some(20_208));
+        assert_eq!(parse_date("next week"), None);
+        for date in ["1970-01-01", "2000-02-29", "2024-12-31", "2025-03-01"] {
+            assert_eq!(format_date(parse_date(date).unwrap()), date);
+        }
+
+        let mut host = create_test_host();
+        assert!(!host.is_expired(today()));
+
+        host.metadata
+            .insert("expires".to_string(), "2025-01-31".to_string());
+        let expires_on = parse_date("2025-01-31").unwrap();
+        assert!(!host.is_expired(expires_on));
+        assert!(host.is_expired(expires_on + 1));
+
+        // An unreadable date never hides a host
+        host.metadata
+            .insert("expires".to_string(), "soon".to_string());
+        assert!(!host.is_expired(today()));
+    }
+
+    #[test]
+    fn test_build_ssh_command_defaults() {
+        let host = Host {
+            name: "web".to_string(),
+            destination: "web.example.com".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            command_line(&host.build_ssh_command(&ConnectOptions::default())),
+            "ssh -o LogLevel=ERROR -o StrictHostKeyChecking=accept-new -p 22 -- web.example.com"
+        );
+        assert!(
+            command_line(&create_test_host().build_ssh_command(&ConnectOptions::default()))
+                .ends_with(" -p 2222 -- deploy@web.example.com")
+        );
+    }
+
+    #[test]
+    fn test_build_ssh_command_with_dynamic_forward() {
+        let options = ConnectOptions {
+            dynamic_forward: Some(1080),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            command_line(&create_test_host().build_ssh_command(&options)),
+            "ssh -o LogLevel=ERROR -o StrictHostKeyChecking=accept-new -D 1080 -p 2222 -- deploy@web.example.com"
+        );
+        assert_eq!(
+            options.socks_endpoint(),
+            Some("socks5://localhost:1080".to_string())
+        );
+        assert_eq!(ConnectOptions::default().socks_endpoint(), None);
+    }
+
+    #[test]
+    fn test_build_ssh_command_with_agent_forwarding() {
+        let options = ConnectOptions {
+            forward_agent: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            command_line(&create_test_host().build_ssh_command(&options)),
+            "ssh -o LogLevel=ERROR -o StrictHostKeyChecking=accept-new -A -p 2222 -- deploy@web.example.com"
+        );
+    }
+
+    #[test]
+    fn test_build_ssh_command_with_jump_host_override() {
+        let options = ConnectOptions {
+            jump_host: Some("bastion-2".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            command_line(&create_test_host().build_ssh_command(&options)),
+            "ssh -o LogLevel=ERROR -o StrictHostKeyChecking=accept-new -J bastion-2 -p 2222 -- deploy@web.example.com"
+        );
+    }
+
+    #[test]
+    fn test_build_ssh_command_with_remote_command() {
+        let options = ConnectOptions {
+            remote_command: Some("tail -f '/var/log/app.log'".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            command_line(&create_test_host().build_ssh_command(&options)),
+            "ssh -o LogLevel=ERROR -o StrictHostKeyChecking=accept-new -t -p 2222 -- deploy@web.example.com tail -f '/var/log/app.log'"
+        );
+        assert_eq!(
+            create_test_host().build_ssh_command(&options).last(),
+            options.remote_command.as_ref()
+        );
+    }
+
+    #[test]
+    fn test_recorded_sessions() {
+        let mut host = create_test_host();
+        assert!(host.logs_sessions(true));
+        host.metadata.insert("log".to_string(), "no".to_string());
+        assert!(!host.logs_sessions(true));
+        host.metadata.insert("log".to_string(), "yes".to_string());
+        assert!(host.logs_sessions(false));
+
+        let args = host.build_ssh_command(&ConnectOptions::default());
+        let command = recorded_command(&args, Path::new("/logs/web/2026-10-16_09-30-00.log"));
+        if cfg!(target_os = "linux") {
+            assert_eq!(
+                command[..5],
+                ["script", "--quiet", "--flush", "--return", "--command"]
+            );
+            assert_eq!(shlex::split(&command[5]).unwrap(), args);
+            assert_eq!(command[6], "/logs/web/2026-10-16_09-30-00.log");
+        } else {
+            assert_eq!(command[4..], args);
+        }
+    }
+
+    #[test]
+    fn test_commands_keep_values_in_one_argument() {
+        let host = Host {
+            name: "db \"primary\"; rm -rf ~".to_string(),
+            destination: "fe80::1%eth0".to_string(),
+            user: Some("ops team".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            host.build_ssh_command(&ConnectOptions::default())[5..],
+            ["-p", "22", "--", "ops team@fe80::1%eth0"]
+        );
+
+        let host = Host {
+            destination: "bücher.example".to_string(),
+            user: None,
+            ..host
+        };
+        assert_eq!(
+            host.render_command_template("ssh \"{{{name}}}\"").unwrap(),
+            vec!["ssh", "db \"primary\"; rm -rf ~"]
+        );
+        assert_eq!(
+            host.render_command_template(
+                "ssh -o 'SetEnv HOST={{name}}' {{#if user}}{{user}}@{{/if}}{{destination}}"
+            )
+            .unwrap(),
+            vec![
+                "ssh",
+                "-o",
+                "SetEnv HOST=db \"primary\"; rm -rf ~",
+                "bücher.example"
+            ]
+        );
+        assert!(host.render_command_template("ssh \"{{name}}").is_err());
+    }
+
+    #[test]
+    fn test_alias_rows() {
+        let host = Host {
+            aliases: "www, web-*.example.com, web-eu".to_string(),
+            ..create_test_host()
+        };
+        let rows = host.alias_rows();
+        assert_eq!(
+            rows.iter()
+                .map(|row| row.connect_as.as_deref())
+                .collect::<Vec<_>>(),
+            [Some("www"), Some("web-eu")]
+        );
+        assert_eq!(rows[1].name, "web");
+        assert_eq!(
+            rows[1]
+                .clone()
+                .for_connection()
+                .render_command_template("ssh {{name}}")
+                .unwrap(),
+            ["ssh", "web-eu"]
+        );
+        assert_eq!(
+            rows[0]
+                .clone()
+                .for_connection()
+                .build_ssh_command(&ConnectOptions::default()),
+            [
+                "ssh",
+                "-o",
+                "LogLevel=ERROR",
+                "-o",
+                "StrictHostKeyChecking=accept-new",
+                "-p",
+                "2222",
+                "--",
+                "deploy@www"
+            ]
+        );
+        assert_eq!(host.clone().for_connection().name, "web");
+        assert!(create_test_host().alias_rows().is_empty());
+    }
+
+    #[test]
+    fn test_template_optional_fields() {
+        let host = create_test_host();
+        let pattern = "ssh {{port_flag}} {{user_at}}{{destination}}";
+        assert_eq!(
+            host.render_command_template(pattern).unwrap(),
+            vec!["ssh", "-p2222", "deploy@web.example.com"]
+        );
+
+        let host = Host {
+            user: None,
+            port: None,
+            ..host
+        };
+        assert_eq!(
+            host.render_command_template(pattern).unwrap(),
+            vec!["ssh", "web.example.com"]
+        );
+        assert_eq!(
+            host.render_command_template(
+                "ssh {{#if port}}-p{{port}}{{/if}} -l {{default user \"root\"}} '' {{destination}}"
+            )
+            .unwrap(),
+            vec!["ssh", "-l", "root", "", "web.example.com"]
+        );
+        assert!(validate_command_template(pattern).is_ok());
+
+        let pattern = "ssh {{#if port}}-p {{port}}{{/if}} {{destination}}";
+        assert_eq!(
+            create_test_host().render_command_template(pattern).unwrap(),
+            vec!["ssh", "-p", "2222", "web.example.com"]
+        );
+        assert_eq!(
+            host.render_command_template(pattern).unwrap(),
+            vec!["ssh", "web.example.com"]
+        );
+        assert!(validate_command_template(pattern).is_ok());
+
+        // A block keeps a flag and its value together, values still stay within their argument
+        let pattern = "ssh {{#if proxy_jump}}-J {{proxy_jump}}{{/if}} '{{name}}'";
+        assert_eq!(
+            host.render_command_template(pattern).unwrap(),
+            vec!["ssh", "web"]
+        );
+        let host = Host {
+            proxy_jump: Some("jump host".to_string()),
+            ..host
+        };
+        assert_eq!(
+            host.render_command_template(pattern).unwrap(),
+            vec!["ssh", "-J", "jump host", "web"]
+        );
+        assert!(validate_command_template(pattern).is_ok());
+    }
+
+    #[test]
+    fn test_validate_command_template() {
+        assert!(validate_command_template("ssh \"{{{name}}}\"").is_ok());
+        assert!(validate_command_template(
+            "notify-send '{{name}} {{metadata.env}}' {{#each tags}}{{this}}{{/each}}"
+        )
+        .is_ok());
+
+        let error = validate_command_template("ssh {{hostname}} -p {{prot}} {{destination}}")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(": hostname, prot. Available: "));
+        assert!(error.contains("destination"));
+
+        assert!(validate_command_template("ssh {{#if user}}{{user}}").is_err());
+        assert!(validate_command_template("ssh '{{name}}").is_err());
+        assert!(template_placeholders().contains(&"metadata".to_string()));
+
+        let pattern = "notify-send '{{name}} exited with {{exit_code}}'";
+        assert!(validate_command_template(pattern).is_err());
+        assert!(validate_command_template_with(pattern, &["exit_code"]).is_ok());
+        assert_eq!(
+            create_test_host()
+                .render_command_template_with(pattern, &[("exit_code", "3".to_string())])
+                .unwrap(),
+            vec!["notify-send", "web exited with 3"]
+        );
+
+        let host = Host {
+            name: "web 1".to_string(),
+            ..create_test_host()
+        };
+        assert_eq!(
+            host.render_remote_command("grep {{name}} /etc/hosts && uptime")
+                .unwrap(),
+            "grep 'web 1' /etc/hosts && uptime"
+        );
+    }
+
+    #[test]
+    fn test_ipv6_destinations() {
+        assert!(is_ipv6("::1"));
+        assert!(is_ipv6("2001:db8::10"));
+        assert!(is_ipv6("fe80::1%eth0"));
+        assert!(!is_ipv6("[::1]"));
+        assert!(!is_ipv6("web.example.com"));
+        assert!(!is_ipv6("10.0.0.1"));
+
+        assert_eq!(unbracket("[2001:db8::10]"), "2001:db8::10");
+        assert_eq!(unbracket("2001:db8::10"), "2001:db8::10");
+        assert_eq!(unbracket("[web"), "[web");
+
+        let host = Host {
+            destination: "[2001:db8::10]".to_string(),
+            ..create_test_host()
+        };
+        assert!(
+            command_line(&host.build_ssh_command(&ConnectOptions::default()))
+                .ends_with(" -p 2222 -- deploy@2001:db8::10")
+        );
+        assert!(command_line(&host.build_batch_command("true"))
+            .ends_with(" -p 2222 -- deploy@2001:db8::10 true"));
+    }
+
+    #[test]
+    fn test_build_batch_command() {
+        let host = create_test_host();
+        assert!(!host.wants_dotfiles());
+
+        let command = command_line(&host.build_batch_command("cd \"$HOME\"; true"));
+        assert_eq!(
+            command,
+            "ssh -o LogLevel=ERROR -o BatchMode=yes -o StrictHostKeyChecking=accept-new -p 2222 -- deploy@web.example.com cd \"$HOME\"; true"
+        );
+        assert_eq!(
+            host.build_batch_command("cd \"$HOME\"; true")
+                .last()
+                .unwrap(),
+            "cd \"$HOME\"; true"
+        );
+
+        let host = Host {
+            metadata: [("dotfiles".to_string(), "Yes".to_string())].into(),
+            ..create_test_host()
+        };
+        assert!(host.wants_dotfiles());
+
+        assert_eq!(
+            command_line(&host.build_tunnel_command(8443, "fd00::5", 443)),
+            "ssh -f -o ExitOnForwardFailure=yes -L 127.0.0.1:8443:[fd00::5]:443 -o LogLevel=ERROR -o BatchMode=yes -o StrictHostKeyChecking=accept-new -p 2222 -- deploy@web.example.com sleep 30"
+        );
+        assert_eq!(
+            command_line(&host.build_host_key_command()),
+            "ssh -v -o BatchMode=yes -o ConnectTimeout=5 -o PreferredAuthentications=none -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null -p 2222 -- deploy@web.example.com true"
+        );
+
+        let host = Host {
+            metadata: [
+                (
+                    "web".to_string(),
+                    "https://web:8443, http://web:9090/metrics".to_string(),
+                ),
+                (
+                    "links".to_string(),
+                    "https://tickets/INC-42 https://wiki/runbooks/web".to_string(),
+                ),
+            ]
+            .into(),
+            ..create_test_host()
+        };
+        assert_eq!(
+            host.web_endpoints(),
+            ["https://web:8443", "http://web:9090/metrics"]
+        );
+        assert_eq!(host.web_tunnel(), None);
+        assert_eq!(
+            host.links(),
+            ["https://tickets/INC-42", "https://wiki/runbooks/web"]
+        );
+    }
+
+    #[test]
+    fn test_public_key_from_identity_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let identity = dir.path().join("deploy_key");
+        std::fs::write(
+            identity.with_extension("pub"),
+            "ssh-ed25519 AAAAC3Nz deploy@laptop\n",
+        )
+        .unwrap();
+
+        let host = Host {
+            identity_file: Some(identity.display().to_string()),
+            ..create_test_host()
+        };
+
+        let (keys, source) = host.public_keys().unwrap();
+        assert_eq!(keys, ["ssh-ed25519 AAAAC3Nz deploy@laptop"]);
+        assert_eq!(source, format!("{}.pub", identity.display()));
+    }
+
+    #[test]
+    fn test_percent_tokens() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config");
+        std::fs::write(
+            &path,
+            "Host web\n  Hostname %h.internal.example.com\n  \
+             ProxyCommand nc -X 5 -x proxy:1080 %h %p %r%%\n\n\
+             Host gw-%d\n  Hostname 10.0.0.%d\n  ProxyCommand ssh -W %h:%p %x\n",
+        )
+        .unwrap();
+
+        let hosts = parse_config(&path.display().to_string()).unwrap();
+        let [web, gw] = hosts.as_slice() else {
+            panic!("expected 2 hosts, got {hosts:?}");
+        };
+        assert_eq!(web.destination, "web.internal.example.com");
+        assert_eq!(
+            Host {
+                user: Some("ops".to_string()),
+                ..web.clone()
+            }
+            .expanded_proxy_command()
+            .as_deref(),
+            Some("nc -X 5 -x proxy:1080 web.internal.example.com 22 ops%")
+        );
+        assert!(web.unexpandable_tokens().is_empty());
+
+        // Template placeholders are not tokens, an unknown token is left as it is
+        assert_eq!(gw.destination, "10.0.0.%d");
+        assert_eq!(
+            gw.unexpandable_tokens(),
+            [("ProxyCommand", "%x".to_string())]
+        );
+        let gw = gw.instantiate("7").unwrap();
+        assert_eq!(
+            gw.expanded_proxy_command().as_deref(),
+            Some("ssh -W 10.0.0.7:22 %x")
+        );
+
+        let (expanded, unknown) = expand_percent_tokens("a%hb%", &[('h', "-")]);
+        assert_eq!(expanded, "a-b%");
+        assert_eq!(unknown, ["%"]);
+    }
+
+    #[test]
+    fn test_certificate_path() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let identity = dir.path().join("deploy_key").display().to_string();
+
+        let mut host = Host {
+            identity_file: Some(identity.clone()),
+            ..create_test_host()
+        };
+        assert_eq!(host.certificate_path(), None);
+
+        // ssh picks up the certificate next to the identity
+        std::fs::write(format!("{identity}-cert.pub"), "").unwrap();
+        assert_eq!(
+            host.certificate_path(),
+            Some(format!("{identity}-cert.pub"))
+        );
+
+        host.certificate_file = Some("~/.ssh/ca-signed-cert.pub".to_string());
+        assert_eq!(
+            host.certificate_path().as_deref(),
+            Some("~/.ssh/ca-signed-cert.pub")
+        );
+    }
+}
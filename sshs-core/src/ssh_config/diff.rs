@@ -0,0 +1,245 @@
+//! Host by host differences between two SSH configurations: hosts added and removed, and the
+//! options changed, as `ssh` resolves them (wildcard blocks applied), rather than a text diff.
+
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::{EntryType, Host, HostVecExt, Parser};
+
+/// A setting of a host.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Setting {
+    /// `ssh_config` option, e.g. `User`
+    Option(String),
+    /// sshs metadata, e.g. `tags`
+    Metadata(String),
+}
+
+impl Setting {
+    /// Configuration line giving the setting `value`.
+    #[must_use]
+    pub fn line(&self, value: &str) -> String {
+        match self {
+            Setting::Option(name) => format!("{name} {value}"),
+            Setting::Metadata(key) => format!("# sshs:{key}={value}"),
+        }
+    }
+}
+
+/// Values of a setting in the old and the new configuration, `None` when unset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingChange {
+    pub setting: Setting,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostChange {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Differences of one host. The settings of added and removed hosts are all listed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostDiff {
+    pub host: String,
+    pub change: HostChange,
+    pub settings: Vec<SettingChange>,
+}
+
+/// Compares the hosts parsed from two configurations, by host name.
+#[must_use]
+pub fn diff(old: &[Host], new: &[Host]) -> Vec<HostDiff> {
+    let old = settings_by_host(old);
+    let new = settings_by_host(new);
+    let no_settings = BTreeMap::new();
+
+    let mut names = old.keys().chain(new.keys()).collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let change = match (old.contains_key(name), new.contains_key(name)) {
+                (false, _) => HostChange::Added,
+                (_, false) => HostChange::Removed,
+                _ => HostChange::Changed,
+            };
+            let old = old.get(name).unwrap_or(&no_settings);
+            let new = new.get(name).unwrap_or(&no_settings);
+
+            let mut settings = old.keys().chain(new.keys()).collect::<Vec<_>>();
+            settings.sort();
+            settings.dedup();
+            let settings = settings
+                .into_iter()
+                .filter(|setting| old.get(*setting) != new.get(*setting))
+                .map(|setting| SettingChange {
+                    setting: setting.clone(),
+                    old: old.get(setting).cloned(),
+                    new: new.get(setting).cloned(),
+                })
+                .collect::<Vec<_>>();
+
+            (change != HostChange::Changed || !settings.is_empty()).then(|| HostDiff {
+                host: name.clone(),
+                change,
+                settings,
+            })
+        })
+        .collect()
+}
+
+/// Compares two configuration files. Lines that cannot be parsed are skipped.
+///
+/// # Errors
+///
+/// Will return `Err` if either file cannot be read.
+pub fn diff_files(old: &Path, new: &Path) -> anyhow::Result<Vec<HostDiff>> {
+    Ok(diff(&parse_hosts(old)?, &parse_hosts(new)?))
+}
+
+/// Hosts of the configuration file, lines that cannot be parsed skipped.
+///
+/// # Errors
+///
+/// Will return `Err` if the file cannot be read.
+pub fn parse_hosts(path: &Path) -> anyhow::Result<Vec<Host>> {
+    let (hosts, _) = Parser::new()
+        .parse_file_lenient(path)
+        .with_context(|| format!("Failed to read '{}'", path.display()))?;
+
+    Ok(hosts)
+}
+
+/// Settings of every host, once the wildcard and `Match` blocks are applied. When a host is
+/// declared more than once, its first values win, as with `ssh`.
+fn settings_by_host(hosts: &[Host]) -> BTreeMap<String, BTreeMap<Setting, String>> {
+    let mut settings: BTreeMap<String, BTreeMap<Setting, String>> = BTreeMap::new();
+
+    for host in hosts
+        .to_vec()
+        .apply_patterns()
+        .apply_match_blocks()
+        .apply_name_to_empty_hostname()
+    {
+        let Some(name) = host.get_patterns().first() else {
+            continue;
+        };
+        let host_settings = settings.entry(name.clone()).or_default();
+
+        let options = host.get_entries().iter().map(|(entry, value)| {
+            let name = match entry {
+                EntryType::Unknown(name) => name.clone(),
+                entry => entry.to_string(),
+            };
+            (Setting::Option(name), value)
+        });
+        let metadata = host
+            .get_metadata()
+            .iter()
+            .map(|(key, value)| (Setting::Metadata(key.clone()), value));
+
+        for (setting, value) in options.chain(metadata) {
+            host_settings
+                .entry(setting)
+                .or_insert_with(|| value.clone());
+        }
+    }
+
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn parse(config: &str) -> Vec<Host> {
+        Parser::new().parse(&mut Cursor::new(config)).unwrap()
+    }
+
+    fn change(setting: Setting, old: Option<&str>, new: Option<&str>) -> SettingChange {
+        SettingChange {
+            setting,
+            old: old.map(str::to_string),
+            new: new.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = parse(
+            "Host *\n  User deploy\n\n\
+             Host web\n  Hostname web.example.com\n\n\
+             Host db\n  Hostname db.example.com\n  Port 5432\n\n\
+             Host old\n",
+        );
+        let new = parse(
+            "# sshs:tags=prod\n\n\
+             Host *\n  User admin\n\n\
+             Host web\n  HostName web.example.com\n\n\
+             Host db\n  Hostname db.example.com\n  User deploy\n  Port 5433\n\n\
+             Host new\n  Hostname 10.0.0.1\n",
+        );
+
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                HostDiff {
+                    host: "db".to_string(),
+                    change: HostChange::Changed,
+                    settings: vec![
+                        change(
+                            Setting::Option("Port".to_string()),
+                            Some("5432"),
+                            Some("5433")
+                        ),
+                        change(Setting::Metadata("tags".to_string()), None, Some("prod")),
+                    ],
+                },
+                HostDiff {
+                    host: "new".to_string(),
+                    change: HostChange::Added,
+                    settings: vec![
+                        change(
+                            Setting::Option("Hostname".to_string()),
+                            None,
+                            Some("10.0.0.1")
+                        ),
+                        change(Setting::Option("User".to_string()), None, Some("admin")),
+                        change(Setting::Metadata("tags".to_string()), None, Some("prod")),
+                    ],
+                },
+                HostDiff {
+                    host: "old".to_string(),
+                    change: HostChange::Removed,
+                    settings: vec![
+                        change(Setting::Option("Hostname".to_string()), Some("old"), None),
+                        change(Setting::Option("User".to_string()), Some("deploy"), None),
+                    ],
+                },
+                HostDiff {
+                    host: "web".to_string(),
+                    change: HostChange::Changed,
+                    settings: vec![
+                        change(
+                            Setting::Option("User".to_string()),
+                            Some("deploy"),
+                            Some("admin")
+                        ),
+                        change(Setting::Metadata("tags".to_string()), None, Some("prod")),
+                    ],
+                },
+            ]
+        );
+        assert_eq!(
+            Setting::Metadata("tags".to_string()).line("prod"),
+            "# sshs:tags=prod"
+        );
+    }
+}
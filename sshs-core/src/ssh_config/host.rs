@@ -0,0 +1,610 @@
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use super::EntryType;
+
+pub(crate) type Entry = (EntryType, String);
+
+/// Metadata key whose comma-separated values are merged instead of overridden.
+pub const TAGS_METADATA_KEY: &str = "tags";
+
+/// Where an entry was declared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    pub path: Option<PathBuf>,
+    /// 1-based line number
+    pub line: usize,
+    /// Patterns of the `Host` block declaring the entry, empty before the first block and in
+    /// `Match` blocks
+    pub block: Vec<String>,
+    /// Criteria of the `Match` block declaring the entry, if it was one
+    pub match_criteria: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Host {
+    patterns: Vec<String>,
+    entries: HashMap<EntryType, String>,
+    /// Where the entries come from, missing for the implicit ones (`Hostname` defaulting to the
+    /// host name)
+    origins: HashMap<EntryType, Origin>,
+    metadata: BTreeMap<String, String>,
+    source_file: Option<PathBuf>,
+    /// 1-based line of the `Host` or `Match` line, if parsed from a file
+    line: Option<usize>,
+    /// Criteria of a `Match` block (`host *.prod user deploy`), `None` for a `Host` block
+    match_criteria: Option<String>,
+}
+
+impl Host {
+    #[must_use]
+    pub fn new(patterns: Vec<String>) -> Host {
+        Host {
+            patterns,
+            entries: HashMap::new(),
+            origins: HashMap::new(),
+            metadata: BTreeMap::new(),
+            source_file: None,
+            line: None,
+            match_criteria: None,
+        }
+    }
+
+    /// A `Match` block, applying its entries to the hosts matching `criteria`.
+    #[must_use]
+    pub fn new_match(criteria: String) -> Host {
+        Host {
+            match_criteria: Some(criteria),
+            ..Host::new(Vec::new())
+        }
+    }
+
+    /// Criteria of a `Match` block, `None` for a `Host` block.
+    #[must_use]
+    pub fn match_criteria(&self) -> Option<&str> {
+        self.match_criteria.as_deref()
+    }
+
+    /// Records the configuration file this host was declared in.
+    pub fn set_source_file(&mut self, path: &Path) {
+        self.source_file = Some(path.to_path_buf());
+    }
+
+    /// Returns the configuration file this host was declared in, if parsed from a file.
+    #[must_use]
+    pub fn source_file(&self) -> Option<&Path> {
+        self.source_file.as_deref()
+    }
+
+    /// Records the line of the `Host` or `Match` line declaring this block.
+    pub fn set_line(&mut self, line: usize) {
+        self.line = Some(line);
+    }
+
+    /// Returns the 1-based line declaring this block, if parsed from a file.
+    #[must_use]
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    pub fn update(&mut self, entry: Entry) {
+        self.entries.insert(entry.0, entry.1);
+    }
+
+    /// Sets an entry read from a configuration file, remembering where.
+    pub fn update_from(&mut self, entry: Entry, origin: Origin) {
+        self.origins.insert(entry.0.clone(), origin);
+        self.update(entry);
+    }
+
+    /// Where the entry was declared, `None` when it was not read from a file.
+    #[must_use]
+    pub fn origin(&self, entry: &EntryType) -> Option<&Origin> {
+        self.origins.get(entry)
+    }
+
+    #[allow(clippy::must_use_candidate)]
+    pub fn get_entries(&self) -> &HashMap<EntryType, String> {
+        &self.entries
+    }
+
+    #[allow(clippy::must_use_candidate)]
+    pub fn get_origins(&self) -> &HashMap<EntryType, Origin> {
+        &self.origins
+    }
+
+    /// Sets a sshs-specific metadata value (from a `# sshs:key=value` comment).
+    pub fn set_metadata(&mut self, key: &str, value: &str) {
+        self.metadata.insert(key.to_string(), value.to_string());
+    }
+
+    /// Applies inherited metadata defaults without overriding the host's own values.
+    ///
+    /// Tags are the exception: inherited tags are appended to the host's tags.
+    pub(crate) fn inherit_metadata(&mut self, defaults: &BTreeMap<String, String>) {
+        for (key, value) in defaults {
+            match self.metadata.get_mut(key) {
+                Some(current) if key == TAGS_METADATA_KEY => {
+                    let mut tags = split_tags(current);
+                    for tag in split_tags(value) {
+                        if !tags.contains(&tag) {
+                            tags.push(tag);
+                        }
+                    }
+                    *current = tags.join(",");
+                }
+                Some(_) => {}
+                None => {
+                    self.metadata.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::must_use_candidate)]
+    pub fn get_metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    /// Returns the tags attached to this host through metadata.
+    #[must_use]
+    pub fn tags(&self) -> Vec<String> {
+        self.metadata
+            .get(TAGS_METADATA_KEY)
+            .map(|tags| split_tags(tags))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn extend_patterns(&mut self, host: &Host) {
+        self.patterns.extend(host.patterns.clone());
+    }
+
+    pub(crate) fn extend_entries(&mut self, host: &Host) {
+        self.entries.extend(host.entries.clone());
+        self.origins.extend(host.origins.clone());
+    }
+
+    /// Applies the entries of a `Match` block. `ssh` keeps the first value it reads, so they
+    /// replace the host's own values only when the block comes before them in the same file.
+    pub(crate) fn extend_from_match(&mut self, block: &Host) {
+        for (key, value) in &block.entries {
+            let origin = block.origins.get(key);
+            let read_first = match (self.origins.get(key), origin) {
+                (Some(current), Some(origin)) => {
+                    current.path == origin.path && origin.line < current.line
+                }
+                _ => false,
+            };
+
+            if read_first || !self.entries.contains_key(key) {
+                self.entries.insert(key.clone(), value.clone());
+                if let Some(origin) = origin {
+                    self.origins.insert(key.clone(), origin.clone());
+                }
+            }
+        }
+    }
+
+    /// Whether the criteria of this `Match` block match `host`, `None` when they depend on what
+    /// is only known when connecting (`exec`, `localnetwork`, `canonical`, ...).
+    fn match_applies_to(&self, host: &Host) -> Option<bool> {
+        let mut words = self.match_criteria.as_deref()?.split_whitespace();
+        let name = host.patterns.first().cloned().unwrap_or_default();
+        let mut applies = true;
+
+        while let Some(word) = words.next() {
+            let (negated, keyword) = match word.strip_prefix('!') {
+                Some(keyword) => (true, keyword),
+                None => (false, word),
+            };
+
+            let value = match keyword.to_lowercase().as_str() {
+                "all" => {
+                    applies &= !negated;
+                    continue;
+                }
+                "host" => host
+                    .get(&EntryType::Hostname)
+                    .unwrap_or_else(|| name.clone()),
+                "originalhost" => name.clone(),
+                "user" => host.get(&EntryType::User).or_else(local_user)?,
+                "localuser" => local_user()?,
+                _ => return None,
+            };
+
+            applies &= matches_pattern_list(words.next()?, &value) != negated;
+        }
+
+        Some(applies)
+    }
+
+    pub(crate) fn extend_if_not_contained(&mut self, host: &Host) {
+        for (key, value) in &host.entries {
+            if !self.entries.contains_key(key) {
+                self.entries.insert(key.clone(), value.clone());
+                if let Some(origin) = host.origins.get(key) {
+                    self.origins.insert(key.clone(), origin.clone());
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::must_use_candidate)]
+    pub fn get_patterns(&self) -> &Vec<String> {
+        &self.patterns
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if the regex cannot be compiled.
+    #[allow(clippy::must_use_candidate)]
+    pub fn matching_pattern_regexes(&self) -> Vec<(Regex, bool)> {
+        if self.patterns.is_empty() {
+            return Vec::new();
+        }
+
+        self.patterns
+            .iter()
+            .filter_map(|pattern| pattern_regex(pattern))
+            .collect()
+    }
+
+    /// Returns the wildcard pattern through which this block applies to `name`, if any.
+    fn matching_wildcard(&self, name: &str) -> Option<&String> {
+        let mut matching = None;
+
+        for pattern in &self.patterns {
+            let Some((regex, is_negated)) = pattern_regex(pattern) else {
+                continue;
+            };
+
+            if regex.is_match(name) {
+                if is_negated {
+                    return None;
+                }
+                matching = matching.or(Some(pattern));
+            }
+        }
+
+        matching
+    }
+
+    #[allow(clippy::must_use_candidate)]
+    pub fn get(&self, entry: &EntryType) -> Option<String> {
+        self.entries.get(entry).cloned()
+    }
+
+    #[allow(clippy::must_use_candidate)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Compiles a wildcard `Host` pattern into a regex, with whether the pattern is negated.
+///
+/// Returns `None` for plain host names. Every character other than the wildcards matches
+/// itself, so any pattern typed by the user compiles.
+#[must_use]
+pub fn pattern_regex(pattern: &str) -> Option<(Regex, bool)> {
+    let contains_wildcard = pattern.contains('*') || pattern.contains('?') || pattern.contains('!');
+    if !contains_wildcard {
+        return None;
+    }
+
+    let (pattern, is_negated) = match pattern.strip_prefix('!') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let pattern = regex::escape(pattern)
+        .replace(r"\*", ".*")
+        .replace(r"\?", ".");
+
+    Regex::new(&format!("^{pattern}$"))
+        .ok()
+        .map(|regex| (regex, is_negated))
+}
+
+/// How a new `Host` pattern overlaps with a pattern of an existing block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternCollision {
+    /// The exact same pattern is already declared
+    Duplicate(String),
+    /// The new pattern is matched by an existing wildcard pattern
+    ShadowedBy(String),
+    /// The new wildcard pattern matches an existing host
+    Shadows(String),
+}
+
+/// Checks a new `Host` pattern against the patterns of existing blocks.
+///
+/// Like `ssh`, a block applies to a host name when one of its patterns matches it and none of
+/// its negated patterns do.
+#[must_use]
+pub fn pattern_collisions(pattern: &str, hosts: &[Host]) -> Vec<PatternCollision> {
+    let new_regex = pattern_regex(pattern);
+    let mut collisions = Vec::new();
+
+    for host in hosts {
+        let mut found = Vec::new();
+
+        if host.patterns.iter().any(|existing| existing == pattern) {
+            found.push(PatternCollision::Duplicate(pattern.to_string()));
+        } else {
+            match &new_regex {
+                None => {
+                    if let Some(existing) = host.matching_wildcard(pattern) {
+                        found.push(PatternCollision::ShadowedBy(existing.clone()));
+                    }
+                }
+                Some((regex, false)) => found.extend(
+                    host.patterns
+                        .iter()
+                        .filter(|existing| pattern_regex(existing).is_none())
+                        .filter(|existing| regex.is_match(existing))
+                        .map(|existing| PatternCollision::Shadows(existing.clone())),
+                ),
+                Some((_, true)) => {}
+            }
+        }
+
+        for collision in found {
+            if !collisions.contains(&collision) {
+                collisions.push(collision);
+            }
+        }
+    }
+
+    collisions
+}
+
+/// Where a literal `Host` pattern is declared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternDeclaration {
+    pub pattern: String,
+    pub path: Option<PathBuf>,
+    /// 1-based line of the `Host` line
+    pub line: usize,
+}
+
+/// The literal patterns of the `Host` blocks, in the order `ssh` reads them. Wildcards and
+/// `Match` blocks are left out.
+#[must_use]
+pub fn literal_patterns(hosts: &[Host]) -> Vec<PatternDeclaration> {
+    hosts
+        .iter()
+        .filter(|host| host.match_criteria.is_none())
+        .flat_map(|host| {
+            host.patterns
+                .iter()
+                .filter(|pattern| pattern_regex(pattern).is_none())
+                .map(|pattern| PatternDeclaration {
+                    pattern: pattern.clone(),
+                    path: host.source_file.clone(),
+                    line: host.line.unwrap_or_default(),
+                })
+        })
+        .collect()
+}
+
+/// The declarations of a pattern already declared by another block, each with the first
+/// declaration. `ssh` takes each option from the first block setting it, so the options of the
+/// first block win over the ones of the later blocks.
+#[must_use]
+pub fn duplicate_patterns(
+    declarations: &[PatternDeclaration],
+) -> Vec<(&PatternDeclaration, &PatternDeclaration)> {
+    let mut first: HashMap<&str, &PatternDeclaration> = HashMap::new();
+    let mut duplicates = Vec::new();
+
+    for declaration in declarations {
+        match first.get(declaration.pattern.as_str()) {
+            None => {
+                first.insert(&declaration.pattern, declaration);
+            }
+            // `Host web web` declares it once
+            Some(original)
+                if original.path == declaration.path && original.line == declaration.line => {}
+            Some(original) => duplicates.push((declaration, *original)),
+        }
+    }
+
+    duplicates
+}
+
+/// Whether `value` matches a comma-separated list of `Match` patterns: one of them, and none of
+/// the negated ones.
+fn matches_pattern_list(patterns: &str, value: &str) -> bool {
+    let mut matched = false;
+
+    for pattern in patterns.split(',') {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let is_match = match pattern_regex(pattern) {
+            Some((regex, _)) => regex.is_match(value),
+            None => pattern == value,
+        };
+
+        if is_match {
+            if negated {
+                return false;
+            }
+            matched = true;
+        }
+    }
+
+    matched
+}
+
+fn local_user() -> Option<String> {
+    std::env::var("USER").ok()
+}
+
+fn split_tags(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[allow(clippy::module_name_repetitions)]
+pub trait HostVecExt {
+    /// Apply the name entry to the hostname entry if the hostname entry is empty.
+    #[must_use]
+    fn apply_name_to_empty_hostname(&self) -> Self;
+
+    /// Merges the hosts with the same entries into one host.
+    #[must_use]
+    fn merge_same_hosts(&self) -> Self;
+
+    /// Spreads the hosts with multiple patterns into multiple hosts with one pattern.
+    #[must_use]
+    fn spread(&self) -> Self;
+
+    /// Apply patterns entries to non-pattern hosts and remove the pattern hosts.
+    #[must_use]
+    fn apply_patterns(&self) -> Self;
+
+    /// Apply the entries of the `Match` blocks to the hosts they match and remove the `Match`
+    /// blocks. Blocks whose criteria cannot be evaluated without connecting are not applied.
+    #[must_use]
+    fn apply_match_blocks(&self) -> Self;
+}
+
+impl HostVecExt for Vec<Host> {
+    fn apply_name_to_empty_hostname(&self) -> Self {
+        let mut hosts = self.clone();
+
+        for host in &mut hosts {
+            if host.get(&EntryType::Hostname).is_none() {
+                let name = host.patterns.first().unwrap().clone();
+                host.update((EntryType::Hostname, name));
+            }
+        }
+
+        hosts
+    }
+
+    fn merge_same_hosts(&self) -> Self {
+        let mut hosts = self.clone();
+
+        for i in (0..hosts.len()).rev() {
+            let (left, right) = hosts.split_at_mut(i); // Split into left and right parts
+
+            let current_host = &right[0];
+
+            for j in (0..i).rev() {
+                let target_host = &mut left[j];
+
+                if current_host.entries != target_host.entries
+                    || current_host.metadata != target_host.metadata
+                    || current_host.source_file != target_host.source_file
+                {
+                    continue;
+                }
+
+                if current_host
+                    .entries
+                    .values()
+                    .any(|value| value.contains("%h"))
+                {
+                    continue;
+                }
+
+                target_host.extend_patterns(current_host);
+                target_host.extend_entries(current_host);
+                hosts.remove(i);
+                break;
+            }
+        }
+
+        hosts
+    }
+
+    fn spread(&self) -> Vec<Host> {
+        let mut hosts = Vec::new();
+
+        for host in self {
+            let patterns = host.get_patterns();
+            if patterns.is_empty() {
+                hosts.push(host.clone());
+                continue;
+            }
+
+            for pattern in patterns {
+                let mut new_host = host.clone();
+                new_host.patterns = vec![pattern.clone()];
+                hosts.push(new_host);
+            }
+        }
+
+        hosts
+    }
+
+    /// Apply patterns entries to non-pattern hosts and remove the pattern hosts.
+    ///
+    /// You might want to call [`HostVecExt::merge_same_hosts`] after this.
+    fn apply_patterns(&self) -> Self {
+        let mut hosts = self.spread();
+        let mut pattern_indexes = Vec::new();
+
+        for i in 0..hosts.len() {
+            let matching_pattern_regexes = hosts[i].matching_pattern_regexes();
+            if matching_pattern_regexes.is_empty() {
+                continue;
+            }
+
+            pattern_indexes.push(i);
+
+            for j in 0..hosts.len() {
+                if i == j {
+                    continue;
+                }
+
+                if !hosts[j].matching_pattern_regexes().is_empty()
+                    || hosts[j].match_criteria.is_some()
+                {
+                    continue;
+                }
+
+                for (regex, is_negated) in &matching_pattern_regexes {
+                    if regex.is_match(&hosts[j].patterns[0]) == *is_negated {
+                        continue;
+                    }
+
+                    let host = hosts[i].clone();
+                    hosts[j].extend_if_not_contained(&host);
+                    break;
+                }
+            }
+        }
+
+        for i in pattern_indexes.into_iter().rev() {
+            hosts.remove(i);
+        }
+
+        hosts
+    }
+
+    fn apply_match_blocks(&self) -> Self {
+        let (blocks, mut hosts): (Vec<Host>, Vec<Host>) = self
+            .iter()
+            .cloned()
+            .partition(|host| host.match_criteria.is_some());
+
+        // In file order, as a block can set what the criteria of the next ones look at
+        for host in &mut hosts {
+            for block in &blocks {
+                if block.match_applies_to(host) == Some(true) {
+                    host.extend_from_match(block);
+                }
+            }
+        }
+
+        hosts
+    }
+}
@@ -1,7 +1,10 @@
+pub mod diff;
 pub mod host;
 mod host_entry;
 pub mod parser;
 pub mod parser_error;
+mod reference;
+pub mod writer;
 
 pub use host::Host;
 pub use host::HostVecExt;
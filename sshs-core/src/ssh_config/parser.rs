@@ -0,0 +1,697 @@
+use glob::glob;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::host::{Entry, Origin};
+use super::parser_error::ConfigDiagnostic;
+use super::parser_error::InvalidIncludeError;
+use super::parser_error::InvalidIncludeErrorDetails;
+use super::parser_error::ParseError;
+use super::parser_error::UnknownEntryError;
+use super::{EntryType, Host};
+
+#[derive(Debug)]
+pub struct Parser {
+    ignore_unknown_entries: bool,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser {
+    #[must_use]
+    pub fn new() -> Parser {
+        Parser {
+            ignore_unknown_entries: true,
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the SSH configuration cannot be parsed.
+    pub fn parse_file<P>(&self, path: P) -> Result<Vec<Host>, ParseError>
+    where
+        P: AsRef<Path>,
+    {
+        let (hosts, diagnostics) = self.parse_file_lenient(path)?;
+        strict(hosts, diagnostics)
+    }
+
+    /// Parses the file, skipping the lines that cannot be parsed. They are returned along with
+    /// the hosts.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file (but not one of its includes) cannot be read.
+    pub fn parse_file_lenient<P>(
+        &self,
+        path: P,
+    ) -> Result<(Vec<Host>, Vec<ConfigDiagnostic>), ParseError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut diagnostics = Vec::new();
+        let hosts = self.parse_from(&mut reader, Some(path), &mut diagnostics)?;
+
+        Ok((hosts, diagnostics))
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the SSH configuration cannot be parsed.
+    pub fn parse(&self, reader: &mut impl BufRead) -> Result<Vec<Host>, ParseError> {
+        let mut diagnostics = Vec::new();
+        let hosts = self.parse_from(reader, None, &mut diagnostics)?;
+        strict(hosts, diagnostics)
+    }
+
+    fn parse_from(
+        &self,
+        reader: &mut impl BufRead,
+        source: Option<&Path>,
+        diagnostics: &mut Vec<ConfigDiagnostic>,
+    ) -> Result<Vec<Host>, ParseError> {
+        let (global_host, mut hosts) = self.parse_raw(reader, source, diagnostics)?;
+
+        if !global_host.is_empty() {
+            for host in hosts
+                .iter_mut()
+                .filter(|host| host.match_criteria().is_none())
+            {
+                host.extend_if_not_contained(&global_host);
+            }
+        }
+
+        Ok(hosts)
+    }
+
+    /// Parses `reader`. Lines that cannot be parsed (and includes that cannot be read) are
+    /// added to `diagnostics` and skipped; only read errors of `reader` itself are returned.
+    #[allow(clippy::too_many_lines)]
+    fn parse_raw(
+        &self,
+        reader: &mut impl BufRead,
+        source: Option<&Path>,
+        diagnostics: &mut Vec<ConfigDiagnostic>,
+    ) -> Result<(Host, Vec<Host>), ParseError> {
+        let skipped = |line: usize, error: ParseError| ConfigDiagnostic {
+            path: source.map(Path::to_path_buf),
+            line,
+            error,
+        };
+
+        let mut parent_host = Host::new(Vec::new());
+        let mut hosts: Vec<Host> = Vec::new();
+
+        // sshs metadata declared before the first Host block (and not right above it) applies to
+        // every host of this file, including the ones pulled in through Include directives
+        let mut file_metadata = BTreeMap::new();
+        let mut seen_host = false;
+
+        // Comments are only given to a block once the line after them is known: the ones right
+        // above a Host or Match line, at its indentation, document that block, the way the
+        // configuration editor moves and removes them along with it. The others belong to the
+        // block they are in.
+        let mut comments: Vec<Comment> = Vec::new();
+
+        let mut line = String::new();
+        let mut line_number = 0;
+        while reader.read_line(&mut line)? > 0 {
+            line_number += 1;
+
+            let line_indentation = indentation(&line);
+            if line.trim_start().starts_with('#') {
+                comments.push((line_indentation, parse_metadata_comment(&line)));
+                line.clear();
+                continue;
+            }
+
+            // We separate parts that contain comments with #
+            line = line.split('#').next().unwrap().trim().to_string();
+            if line.is_empty() {
+                add_metadata(&mut comments, &mut hosts, seen_host, &mut file_metadata);
+                continue;
+            }
+
+            let text = std::mem::take(&mut line);
+            let entry = parse_line(&text);
+            let documented = match &entry {
+                Ok((EntryType::Host | EntryType::Match, _)) => {
+                    let attached = comments
+                        .iter()
+                        .rev()
+                        .take_while(|(indentation, _)| *indentation == line_indentation)
+                        .count();
+                    comments.split_off(comments.len() - attached)
+                }
+                _ => Vec::new(),
+            };
+            add_metadata(&mut comments, &mut hosts, seen_host, &mut file_metadata);
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    diagnostics.push(skipped(line_number, e));
+                    continue;
+                }
+            };
+
+            match entry.0 {
+                EntryType::Unknown(_) => {
+                    if !self.ignore_unknown_entries {
+                        diagnostics.push(skipped(
+                            line_number,
+                            UnknownEntryError {
+                                line: text.clone(),
+                                entry: entry.0.to_string(),
+                            }
+                            .into(),
+                        ));
+                        continue;
+                    }
+                }
+                EntryType::Host => {
+                    let mut host = Host::new(parse_patterns(&entry.1));
+                    if let Some(source) = source {
+                        host.set_source_file(source);
+                    }
+                    host.set_line(line_number);
+                    for (key, value) in documented.into_iter().filter_map(|(_, metadata)| metadata)
+                    {
+                        host.set_metadata(&key, &value);
+                    }
+                    hosts.push(host);
+                    seen_host = true;
+
+                    continue;
+                }
+                EntryType::Match => {
+                    let mut block = Host::new_match(entry.1);
+                    if let Some(source) = source {
+                        block.set_source_file(source);
+                    }
+                    block.set_line(line_number);
+                    for (key, value) in documented.into_iter().filter_map(|(_, metadata)| metadata)
+                    {
+                        block.set_metadata(&key, &value);
+                    }
+                    hosts.push(block);
+                    seen_host = true;
+
+                    continue;
+                }
+                EntryType::Include => {
+                    let include_path = resolve_include_path(&entry.1);
+
+                    let paths = match glob(&include_path) {
+                        Ok(paths) => paths,
+                        Err(e) => {
+                            diagnostics.push(skipped(
+                                line_number,
+                                InvalidIncludeError {
+                                    line: text.clone(),
+                                    details: InvalidIncludeErrorDetails::Pattern(e),
+                                }
+                                .into(),
+                            ));
+                            continue;
+                        }
+                    };
+
+                    for path in paths {
+                        let details = match path {
+                            Ok(path) => match File::open(&path) {
+                                Ok(file) => Ok((path, file)),
+                                Err(e) => Err(InvalidIncludeErrorDetails::Io(e)),
+                            },
+                            Err(e) => Err(InvalidIncludeErrorDetails::Glob(e)),
+                        };
+                        let (path, file) = match details {
+                            Ok(opened) => opened,
+                            Err(details) => {
+                                diagnostics.push(skipped(
+                                    line_number,
+                                    InvalidIncludeError {
+                                        line: text.clone(),
+                                        details,
+                                    }
+                                    .into(),
+                                ));
+                                continue;
+                            }
+                        };
+
+                        let mut file = BufReader::new(file);
+                        let (included_parent_host, included_hosts) =
+                            self.parse_raw(&mut file, Some(&path), diagnostics)?;
+
+                        if hosts.is_empty() {
+                            parent_host.extend_entries(&included_parent_host);
+                        } else {
+                            hosts
+                                .last_mut()
+                                .unwrap()
+                                .extend_entries(&included_parent_host);
+                        }
+
+                        hosts.extend(included_hosts);
+                    }
+
+                    continue;
+                }
+                _ => {}
+            }
+
+            let block = hosts.last_mut().unwrap_or(&mut parent_host);
+            let origin = Origin {
+                path: source.map(Path::to_path_buf),
+                line: line_number,
+                block: block.get_patterns().clone(),
+                match_criteria: block.match_criteria().map(str::to_string),
+            };
+            block.update_from(entry, origin);
+        }
+        add_metadata(&mut comments, &mut hosts, seen_host, &mut file_metadata);
+
+        if !file_metadata.is_empty() {
+            for host in &mut hosts {
+                host.inherit_metadata(&file_metadata);
+            }
+        }
+
+        Ok((parent_host, hosts))
+    }
+}
+
+/// A comment line: its indentation, and the sshs metadata it declares.
+type Comment = (usize, Option<(String, String)>);
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Gives the metadata of `comments` to the block they are in: inside a Host block, it only
+/// applies to that host; before the first one, to the whole file.
+fn add_metadata(
+    comments: &mut Vec<Comment>,
+    hosts: &mut [Host],
+    seen_host: bool,
+    file_metadata: &mut BTreeMap<String, String>,
+) {
+    for (key, value) in comments.drain(..).filter_map(|(_, metadata)| metadata) {
+        match hosts.last_mut() {
+            Some(host) if seen_host => host.set_metadata(&key, &value),
+            _ => {
+                file_metadata.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Fails on the first skipped line, the way the parser did before it could skip lines.
+fn strict(hosts: Vec<Host>, diagnostics: Vec<ConfigDiagnostic>) -> Result<Vec<Host>, ParseError> {
+    match diagnostics.into_iter().next() {
+        Some(diagnostic) => Err(diagnostic.error),
+        None => Ok(hosts),
+    }
+}
+
+/// Resolves an `Include` argument the way `ssh` does: `~` is expanded and relative paths are
+/// relative to `~/.ssh`.
+#[must_use]
+pub fn resolve_include_path(raw_path: &str) -> String {
+    let include_path = shellexpand::tilde(raw_path).to_string();
+
+    if include_path.starts_with('/') {
+        return include_path;
+    }
+
+    let ssh_config_directory = shellexpand::tilde("~/.ssh").to_string();
+    format!("{ssh_config_directory}/{include_path}")
+}
+
+/// Parses a `# sshs:key=value` metadata comment, or its `#_Key value` shorthand.
+///
+/// Regular comments (and metadata comments without a value) return `None`.
+#[must_use]
+pub fn parse_metadata_comment(line: &str) -> Option<(String, String)> {
+    let comment = line.trim().strip_prefix('#')?;
+    let (key, value) = match comment.strip_prefix('_') {
+        Some(directive) => directive.split_once([' ', '\t', '='])?,
+        None => comment
+            .trim_start()
+            .strip_prefix("sshs:")?
+            .split_once('=')?,
+    };
+
+    let key = key.trim().to_lowercase();
+    let value = value.trim();
+    if key.is_empty() || value.is_empty() {
+        return None;
+    }
+
+    Some((key, value.to_string()))
+}
+
+fn parse_line(line: &str) -> Result<Entry, ParseError> {
+    let (mut key, mut value) = line
+        .trim()
+        .split_once([' ', '\t', '='])
+        .map(|(k, v)| (k.trim_end(), v.trim_start()))
+        .ok_or(ParseError::UnparseableLine(line.to_string()))?;
+
+    // Format can be key=value with whitespaces around the equal sign, strip the equal sign and whitespaces
+    if key.ends_with('=') {
+        key = key.trim_end_matches('=').trim_end();
+    }
+    if value.starts_with('=') {
+        value = value.trim_start_matches('=').trim_start();
+    }
+
+    Ok((
+        EntryType::from_str(key).unwrap_or(EntryType::Unknown(key.to_string())),
+        value.to_string(),
+    ))
+}
+
+fn parse_patterns(entry_value: &str) -> Vec<String> {
+    let mut patterns = Vec::new();
+    let mut pattern = String::new();
+
+    let mut in_double_quotes = false;
+
+    for c in entry_value.chars() {
+        if c == '"' {
+            if in_double_quotes {
+                patterns.push(pattern.trim().to_string());
+                pattern.clear();
+
+                in_double_quotes = false;
+            } else {
+                in_double_quotes = true;
+            }
+        } else if c.is_whitespace() {
+            if in_double_quotes {
+                pattern.push(c);
+            } else if !pattern.is_empty() {
+                patterns.push(pattern.trim().to_string());
+                pattern.clear();
+            }
+        } else {
+            pattern.push(c);
+        }
+    }
+
+    if !pattern.is_empty() {
+        patterns.push(pattern.trim().to_string());
+    }
+
+    patterns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    fn tags_of(hosts: &[Host], name: &str) -> Vec<String> {
+        hosts
+            .iter()
+            .find(|host| host.get_patterns().first().is_some_and(|p| p == name))
+            .map(Host::tags)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_file_metadata_applies_to_every_host() {
+        let config = "# sshs:tags=clientA\n# sshs:color=yellow\n\nHost web\n  Hostname web.example.com\n\nHost db\n  Hostname db.example.com\n";
+
+        let hosts = Parser::new().parse(&mut Cursor::new(config)).unwrap();
+
+        assert_eq!(tags_of(&hosts, "web"), vec!["clientA"]);
+        assert_eq!(tags_of(&hosts, "db"), vec!["clientA"]);
+        assert_eq!(
+            hosts[0].get_metadata().get("color"),
+            Some(&"yellow".to_string())
+        );
+    }
+
+    #[test]
+    fn test_metadata_inside_host_block_is_not_file_wide() {
+        let config = "Host web\n  # sshs:tags=clientA\n  #_Expires 2025-01-31\n  Hostname web.example.com\n\nHost db\n  Hostname db.example.com\n";
+
+        let hosts = Parser::new().parse(&mut Cursor::new(config)).unwrap();
+
+        assert_eq!(tags_of(&hosts, "web"), vec!["clientA"]);
+        assert_eq!(
+            hosts[0].get_metadata().get("expires").map(String::as_str),
+            Some("2025-01-31")
+        );
+        assert!(tags_of(&hosts, "db").is_empty());
+        assert!(hosts[1].get_metadata().is_empty());
+    }
+
+    #[test]
+    fn test_comments_right_above_a_host_document_it() {
+        let config = "# sshs:color=red\nHost a\n  Hostname a.example.com\n  # sshs:tags=a\n# sshs:tags=b\n# Database\nHost b\n  Hostname b.example.com\n\n# sshs:color=blue\n\nHost c\n";
+
+        let hosts = Parser::new().parse(&mut Cursor::new(config)).unwrap();
+
+        assert_eq!(tags_of(&hosts, "a"), vec!["a"]);
+        assert_eq!(tags_of(&hosts, "b"), vec!["b"]);
+        // Right above the first block, not file-wide
+        assert_eq!(
+            hosts[0].get_metadata().get("color"),
+            Some(&"red".to_string())
+        );
+        // Separated from the next block by a blank line: in the block above
+        assert_eq!(
+            hosts[1].get_metadata().get("color"),
+            Some(&"blue".to_string())
+        );
+        assert!(hosts[2].get_metadata().is_empty());
+    }
+
+    #[test]
+    fn test_included_hosts_inherit_and_merge_tags() {
+        let dir = TempDir::new().unwrap();
+        let client_dir = dir.path().join("clientA");
+        std::fs::create_dir(&client_dir).unwrap();
+        std::fs::write(
+            client_dir.join("web.conf"),
+            "# sshs:tags=web\n# sshs:color=green\nHost client-web\n  Hostname 10.0.0.1\n",
+        )
+        .unwrap();
+        std::fs::write(
+            client_dir.join("db.conf"),
+            "Host client-db\n  Hostname 10.0.0.2\n",
+        )
+        .unwrap();
+
+        let config = format!(
+            "# sshs:tags=clientA\n# sshs:color=red\nInclude {}/*.conf\n\nHost personal\n  Hostname example.com\n",
+            client_dir.display()
+        );
+
+        let hosts = Parser::new().parse(&mut Cursor::new(config)).unwrap();
+
+        assert_eq!(tags_of(&hosts, "client-web"), vec!["web", "clientA"]);
+        assert_eq!(tags_of(&hosts, "client-db"), vec!["clientA"]);
+        assert_eq!(tags_of(&hosts, "personal"), vec!["clientA"]);
+
+        // The closest declaration wins for non-tag metadata
+        let client_web = hosts
+            .iter()
+            .find(|host| host.get_patterns()[0] == "client-web")
+            .unwrap();
+        assert_eq!(
+            client_web.get_metadata().get("color"),
+            Some(&"green".to_string())
+        );
+    }
+
+    #[test]
+    fn test_hosts_remember_their_source_file() {
+        let dir = TempDir::new().unwrap();
+        let included = dir.path().join("sshs.conf");
+        std::fs::write(&included, "Host managed\n  Hostname 10.0.0.3\n").unwrap();
+
+        let main = dir.path().join("config");
+        std::fs::write(
+            &main,
+            format!(
+                "Include {}\n\nHost manual\n  Hostname example.com\n",
+                included.display()
+            ),
+        )
+        .unwrap();
+
+        let hosts = Parser::new().parse_file(&main).unwrap();
+        let source_of = |name: &str| {
+            hosts
+                .iter()
+                .find(|host| host.get_patterns()[0] == name)
+                .and_then(Host::source_file)
+                .map(Path::to_path_buf)
+        };
+
+        assert_eq!(source_of("managed"), Some(included.clone()));
+        assert_eq!(source_of("manual"), Some(main.clone()));
+    }
+
+    #[test]
+    fn test_entries_remember_their_origin() {
+        use crate::ssh_config::HostVecExt;
+
+        let config = "User admin\n\nHost web\n  Hostname web.example.com\n\nHost w*\n  Port 2222\n  User deploy\n";
+        let hosts = Parser::new()
+            .parse(&mut Cursor::new(config))
+            .unwrap()
+            .apply_patterns()
+            .apply_name_to_empty_hostname();
+        let web = &hosts[0];
+
+        let origin = |entry: EntryType| web.origin(&entry).map(|o| (o.line, o.block.join(" ")));
+        assert_eq!(origin(EntryType::Hostname), Some((4, "web".to_string())));
+        assert_eq!(origin(EntryType::Port), Some((7, "w*".to_string())));
+        assert_eq!(origin(EntryType::User), Some((1, String::new())));
+        assert_eq!(web.get(&EntryType::User).as_deref(), Some("admin"));
+    }
+
+    #[test]
+    fn test_match_blocks() {
+        use crate::ssh_config::HostVecExt;
+
+        let config = "Match host *.prod,!db.prod\n  User deploy\n  Port 2222\n\n\
+                      Host web\n  Hostname web.prod\n  Port 22\n  User admin\n\n\
+                      Host db\n  Hostname db.prod\n\n\
+                      Match originalhost web\n  ForwardAgent yes\n  User nobody\n\n\
+                      Match exec \"test -f /tmp/vpn\"\n  ProxyJump bastion\n";
+        let hosts = Parser::new()
+            .parse(&mut Cursor::new(config))
+            .unwrap()
+            .apply_patterns()
+            .apply_match_blocks();
+        assert_eq!(hosts.len(), 2);
+        let (web, db) = (&hosts[0], &hosts[1]);
+
+        // The first Match comes before the Host block: ssh reads its values first
+        assert_eq!(web.get(&EntryType::User).as_deref(), Some("deploy"));
+        assert_eq!(web.get(&EntryType::Port).as_deref(), Some("2222"));
+        let origin = web.origin(&EntryType::User).unwrap();
+        assert_eq!(origin.line, 2);
+        assert_eq!(
+            origin.match_criteria.as_deref(),
+            Some("host *.prod,!db.prod")
+        );
+        assert!(origin.block.is_empty());
+
+        // The last one only adds what is not set yet
+        assert_eq!(web.get(&EntryType::ForwardAgent).as_deref(), Some("yes"));
+
+        // Excluded by a negated pattern, and `exec` cannot be evaluated
+        assert_eq!(db.get(&EntryType::User), None);
+        assert_eq!(db.get(&EntryType::ProxyJump), None);
+    }
+
+    #[test]
+    fn test_pattern_collisions() {
+        use crate::ssh_config::host::{pattern_collisions, PatternCollision};
+
+        let config = "Host web web-alias\n  Hostname web.example.com\n\nHost *.internal !db.internal\n  User admin\n\nHost db.internal\n  Hostname 10.0.0.2\n";
+        let hosts = Parser::new().parse(&mut Cursor::new(config)).unwrap();
+
+        assert_eq!(
+            pattern_collisions("web-alias", &hosts),
+            vec![PatternCollision::Duplicate("web-alias".to_string())]
+        );
+        assert_eq!(
+            pattern_collisions("cache.internal", &hosts),
+            vec![PatternCollision::ShadowedBy("*.internal".to_string())]
+        );
+        assert_eq!(
+            pattern_collisions("web*", &hosts),
+            vec![
+                PatternCollision::Shadows("web".to_string()),
+                PatternCollision::Shadows("web-alias".to_string())
+            ]
+        );
+        // The wildcard block excludes db.internal
+        assert_eq!(
+            pattern_collisions("db.internal", &hosts),
+            vec![PatternCollision::Duplicate("db.internal".to_string())]
+        );
+        assert!(pattern_collisions("unrelated", &hosts).is_empty());
+    }
+
+    #[test]
+    fn test_pattern_regex_metacharacters() {
+        use crate::ssh_config::host::{pattern_collisions, pattern_regex, PatternCollision};
+
+        let config = "Host a(b web[1] +xy\n  Hostname 10.0.0.1\n";
+        let hosts = Parser::new().parse(&mut Cursor::new(config)).unwrap();
+
+        // Typed in the aliases field, one key at a time
+        assert_eq!(
+            pattern_collisions("a(*", &hosts),
+            vec![PatternCollision::Shadows("a(b".to_string())]
+        );
+        assert_eq!(
+            pattern_collisions("web[1]", &hosts),
+            vec![PatternCollision::Duplicate("web[1]".to_string())]
+        );
+        assert_eq!(
+            pattern_collisions("+x?", &hosts),
+            vec![PatternCollision::Shadows("+xy".to_string())]
+        );
+
+        let (regex, is_negated) = pattern_regex("!web[1]*").unwrap();
+        assert!(is_negated);
+        assert!(regex.is_match("web[1].example.com"));
+        assert!(!regex.is_match("web1"));
+    }
+
+    #[test]
+    fn test_duplicate_patterns() {
+        use crate::ssh_config::host::{duplicate_patterns, literal_patterns};
+
+        let config = "Host web web\n  Hostname web.example.com\n\nHost web-* db\n  User admin\n\nMatch host web\n  User root\n\nHost db web\n  Port 2222\n";
+        let hosts = Parser::new().parse(&mut Cursor::new(config)).unwrap();
+
+        let declarations = literal_patterns(&hosts);
+        let duplicates: Vec<_> = duplicate_patterns(&declarations)
+            .into_iter()
+            .map(|(duplicate, first)| (duplicate.pattern.as_str(), duplicate.line, first.line))
+            .collect();
+        assert_eq!(duplicates, vec![("db", 10, 4), ("web", 10, 1)]);
+    }
+
+    #[test]
+    fn test_parse_metadata_comment() {
+        assert_eq!(
+            parse_metadata_comment("# sshs:tags=a,b\n"),
+            Some(("tags".to_string(), "a,b".to_string()))
+        );
+        assert_eq!(
+            parse_metadata_comment("  #sshs:Color = red"),
+            Some(("color".to_string(), "red".to_string()))
+        );
+        assert_eq!(
+            parse_metadata_comment("#_Expires 2025-01-31\n"),
+            Some(("expires".to_string(), "2025-01-31".to_string()))
+        );
+        assert_eq!(parse_metadata_comment("# just a comment"), None);
+        assert_eq!(parse_metadata_comment("#_Expires"), None);
+        assert_eq!(parse_metadata_comment("# sshs:tags="), None);
+        assert_eq!(parse_metadata_comment("Hostname example.com"), None);
+    }
+}
@@ -66,6 +66,25 @@ impl std::error::Error for InvalidIncludeError {
     }
 }
 
+/// A line of a configuration file that could not be parsed, and was skipped.
+#[derive(Debug)]
+pub struct ConfigDiagnostic {
+    /// File of the line, `None` when parsing from a reader
+    pub path: Option<std::path::PathBuf>,
+    /// 1-based line number
+    pub line: usize,
+    pub error: ParseError,
+}
+
+impl std::fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}:{}: {}", path.display(), self.line, self.error),
+            None => write!(f, "line {}: {}", self.line, self.error),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ParseError {
     Io(std::io::Error),
@@ -0,0 +1,159 @@
+//! Short offline reference of the `ssh_config` options, summarized from `ssh_config(5)`.
+
+use super::EntryType;
+
+impl EntryType {
+    /// One line description of the option, `None` for unknown options.
+    #[must_use]
+    #[allow(clippy::too_many_lines)]
+    pub fn description(&self) -> Option<&'static str> {
+        let description = match self {
+            EntryType::Unknown(_) => return None,
+            EntryType::Host => "Starts a block applying to the hosts matching its patterns",
+            EntryType::Match => "Starts a block applying when all its criteria match",
+            EntryType::AddKeysToAgent => {
+                "Adds the keys used to the running ssh-agent (yes, confirm, ask, no)"
+            }
+            EntryType::AddressFamily => {
+                "IP version used to connect: any, inet (IPv4) or inet6 (IPv6)"
+            }
+            EntryType::BatchMode => "Never prompts for a password or passphrase, fails instead",
+            EntryType::BindAddress => "Local address the connection goes out from",
+            EntryType::BindInterface => "Local interface the connection goes out from",
+            EntryType::CanonicalDomains => "Domains searched to complete unqualified host names",
+            EntryType::CanonicalizeFallbackLocal => {
+                "Falls back on the system resolver when canonicalization fails"
+            }
+            EntryType::CanonicalizeHostname => "Completes host names with CanonicalDomains",
+            EntryType::CanonicalizeMaxDots => "Most dots a host name can have to be canonicalized",
+            EntryType::CanonicalizePermittedCNAMEs => {
+                "CNAMEs followed when canonicalizing host names"
+            }
+            EntryType::CASignatureAlgorithms => {
+                "Algorithms accepted for certificate authority signatures"
+            }
+            EntryType::CertificateFile => "User certificate presented along with the identity",
+            EntryType::ChannelTimeout => "Closes channels that stay idle longer than the timeout",
+            EntryType::CheckHostIP => "Also checks the host IP address in known_hosts",
+            EntryType::Ciphers => "Ciphers allowed for the connection, in order of preference",
+            EntryType::ClearAllForwardings => "Drops every port forwarding of the configuration",
+            EntryType::Compression => "Compresses the connection, useful on slow links",
+            EntryType::ConnectionAttempts => "Number of attempts, one per second, before giving up",
+            EntryType::ConnectTimeout => "Seconds to wait for the connection before giving up",
+            EntryType::ControlMaster => "Shares one connection between sessions (yes, auto, ask)",
+            EntryType::ControlPath => "Socket of the shared connection, e.g. ~/.ssh/cm-%r@%h:%p",
+            EntryType::ControlPersist => "Keeps the shared connection open after the last session",
+            EntryType::DynamicForward => "Opens a SOCKS proxy on a local port through the host",
+            EntryType::EnableEscapeCommandline => "Enables the ~C command line in sessions",
+            EntryType::EnableSSHKeysign => "Uses ssh-keysign for host based authentication",
+            EntryType::EscapeChar => "Escape character of the sessions, ~ by default",
+            EntryType::ExitOnForwardFailure => "Exits when a port forwarding cannot be set up",
+            EntryType::FingerprintHash => "Hash shown in key fingerprints: sha256 or md5",
+            EntryType::ForkAfterAuthentication => {
+                "Goes to the background after authenticating (-f)"
+            }
+            EntryType::ForwardAgent => {
+                "Lets the host use the local ssh-agent, only for trusted hosts"
+            }
+            EntryType::ForwardX11 => "Forwards X11 so graphical programs show up locally",
+            EntryType::ForwardX11Timeout => "Time after which untrusted X11 forwarding is refused",
+            EntryType::ForwardX11Trusted => {
+                "Gives forwarded X11 clients full access to the display"
+            }
+            EntryType::GatewayPorts => "Lets other machines connect to the local forwarded ports",
+            EntryType::GlobalKnownHostsFile => "System wide known_hosts files",
+            EntryType::GSSAPIAuthentication => "Allows GSSAPI (Kerberos) authentication",
+            EntryType::GSSAPIDelegateCredentials => "Forwards the Kerberos credentials to the host",
+            EntryType::HashKnownHosts => "Hashes the host names added to known_hosts",
+            EntryType::HostbasedAcceptedAlgorithms => {
+                "Algorithms used for host based authentication"
+            }
+            EntryType::HostbasedAuthentication => "Allows host based authentication",
+            EntryType::HostKeyAlgorithms => "Host key algorithms accepted, in order of preference",
+            EntryType::HostKeyAlias => {
+                "Name the host key is looked up and saved under in known_hosts"
+            }
+            EntryType::Hostname => "Real host name or IP address to connect to",
+            EntryType::IdentitiesOnly => {
+                "Only offers the configured identities, not every agent key"
+            }
+            EntryType::IdentityAgent => "Socket of the agent to use, or none",
+            EntryType::IdentityFile => "Private key to authenticate with, can be repeated",
+            EntryType::IgnoreUnknown => "Unknown options matching these patterns are ignored",
+            EntryType::Include => "Reads other configuration files, relative to ~/.ssh",
+            EntryType::IPQoS => "IPv4 type of service or DSCP class of the connection",
+            EntryType::KbdInteractiveAuthentication => "Allows keyboard interactive authentication",
+            EntryType::KbdInteractiveDevices => {
+                "Methods used for keyboard interactive authentication"
+            }
+            EntryType::KexAlgorithms => "Key exchange algorithms, in order of preference",
+            EntryType::KnownHostsCommand => "Command printing more known_hosts lines for the host",
+            EntryType::LocalCommand => "Command run locally once connected (PermitLocalCommand)",
+            EntryType::LocalForward => "Forwards a local port to an address reached from the host",
+            EntryType::LogLevel => "Verbosity of the messages of ssh, QUIET to DEBUG3",
+            EntryType::LogVerbose => "Source locations whose debug logging is raised",
+            EntryType::MACs => "Message authentication codes, in order of preference",
+            EntryType::NoHostAuthenticationForLocalhost => "Skips the host key check for localhost",
+            EntryType::NumberOfPasswordPrompts => "Password attempts before giving up",
+            EntryType::ObscureKeystrokeTiming => "Hides the timing of keystrokes from observers",
+            EntryType::PasswordAuthentication => "Allows password authentication",
+            EntryType::PermitLocalCommand => "Allows LocalCommand and the !command escape",
+            EntryType::PermitRemoteOpen => "Destinations allowed through a remote SOCKS forwarding",
+            EntryType::PKCS11Provider => "PKCS#11 library providing keys (smart cards, tokens)",
+            EntryType::Port => "Port of the SSH server, 22 by default",
+            EntryType::PreferredAuthentications => "Authentication methods tried, in order",
+            EntryType::ProxyCommand => "Command the connection goes through, e.g. nc or a jump",
+            EntryType::ProxyJump => "Jump hosts the connection goes through, comma separated",
+            EntryType::ProxyUseFdpass => "ProxyCommand hands over a connected descriptor",
+            EntryType::PubkeyAcceptedAlgorithms => "Public key algorithms used to authenticate",
+            EntryType::PubkeyAuthentication => "Allows public key authentication",
+            EntryType::RekeyLimit => "Data or time after which the session keys are renewed",
+            EntryType::RemoteCommand => "Command run on the host instead of a shell",
+            EntryType::RemoteForward => "Forwards a port of the host to a local address",
+            EntryType::RequestTTY => "Asks for a terminal on the host (yes, no, force, auto)",
+            EntryType::RequiredRSASize => "Smallest RSA key size accepted, in bits",
+            EntryType::RevokedHostKeys => "File of host keys that are refused",
+            EntryType::SecurityKeyProvider => "Library used for FIDO security keys",
+            EntryType::SendEnv => "Local environment variables sent to the host",
+            EntryType::ServerAliveCountMax => "Unanswered keepalives before disconnecting",
+            EntryType::ServerAliveInterval => "Seconds of silence before sending a keepalive",
+            EntryType::SessionType => "Session requested: default shell, none or subsystem",
+            EntryType::SetEnv => "Environment variables set on the host, NAME=value",
+            EntryType::StdinNull => "Reads standard input from /dev/null (-n)",
+            EntryType::StreamLocalBindMask => "Permissions of the Unix sockets of forwardings",
+            EntryType::StreamLocalBindUnlink => {
+                "Removes a stale Unix socket before forwarding to it"
+            }
+            EntryType::StrictHostKeyChecking => "How unknown and changed host keys are handled",
+            EntryType::SyslogFacility => "Syslog facility of the messages of ssh",
+            EntryType::TCPKeepAlive => "Sends TCP keepalives to detect dead connections",
+            EntryType::Tag => "Tag matched by Match tag blocks",
+            EntryType::Tunnel => "Forwards a tun device (yes, point-to-point, ethernet)",
+            EntryType::TunnelDevice => "tun devices opened on both ends, local:remote",
+            EntryType::UpdateHostKeys => "Learns the other host keys the server offers",
+            EntryType::User => "User to log in as",
+            EntryType::UserKnownHostsFile => "known_hosts files of the user",
+            EntryType::VerifyHostKeyDNS => "Checks the host key against SSHFP DNS records",
+            EntryType::VisualHostKey => "Shows an ASCII art of the host key when connecting",
+            EntryType::XAuthLocation => "Path of the xauth program used for X11 forwarding",
+        };
+
+        Some(description)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_descriptions() {
+        let entry_type = EntryType::from_str("controlpersist").unwrap();
+        assert_eq!(
+            entry_type.description(),
+            Some("Keeps the shared connection open after the last session")
+        );
+        assert_eq!(EntryType::Unknown("Foo".to_string()).description(), None);
+    }
+}
@@ -0,0 +1,251 @@
+//! Lossless edits of a `Host` block: the lines that are not edited stay as they are, with their
+//! indentation, their spelling (`HostName`, `Port=22`), the comments and the options sshs does
+//! not know.
+
+use std::str::FromStr;
+
+use super::EntryType;
+
+/// Lines of a block after its `Host` line, edited in place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockWriter {
+    lines: Vec<String>,
+    /// Indentation of the options of the block, given to the added lines
+    indent: String,
+}
+
+impl BlockWriter {
+    #[must_use]
+    pub fn new(lines: &[&str]) -> Self {
+        let indent = lines
+            .iter()
+            .find(|line| !line.trim().is_empty())
+            .map_or("  ", |line| &line[..line.len() - line.trim_start().len()])
+            .to_string();
+
+        Self {
+            lines: lines.iter().map(ToString::to_string).collect(),
+            indent,
+        }
+    }
+
+    /// Sets the first `entry_type` option to `value`. Its value is replaced in place, keeping
+    /// the key, the separator and a trailing comment (`# primary`) as written, and left alone
+    /// when it does not change (quotes aside).
+    /// A missing option is added after the last line; `None` removes the option and its
+    /// duplicates.
+    pub fn set(&mut self, entry_type: &EntryType, value: Option<&str>) {
+        let Some(value) = value else {
+            self.lines
+                .retain(|line| option_type(line).as_ref() != Some(entry_type));
+            return;
+        };
+
+        let Some(line) = self
+            .lines
+            .iter_mut()
+            .find(|line| option_type(line).as_ref() == Some(entry_type))
+        else {
+            self.lines
+                .push(format!("{}{entry_type} {value}", self.indent));
+            return;
+        };
+
+        let (prefix, current) = split_value(line);
+        let (current, comment) = split_comment(current);
+        if current.trim_matches('"') != value.trim_matches('"') {
+            *line = format!("{prefix}{value}{comment}");
+        }
+    }
+
+    /// Makes the lines `is_edited` accepts the `new` ones (compared trimmed). The lines still
+    /// in `new` stay where they are, the first added lines take the place of the removed ones,
+    /// and the others are added after the last line.
+    pub fn replace_lines(&mut self, is_edited: impl Fn(&str) -> bool, new: &[&str]) {
+        let mut added = new.to_vec();
+        let mut freed = Vec::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            if line.trim().is_empty() || !is_edited(line) {
+                continue;
+            }
+            match added.iter().position(|new_line| *new_line == line.trim()) {
+                Some(position) => {
+                    added.remove(position);
+                }
+                None => freed.push(index),
+            }
+        }
+
+        let mut added = added.into_iter();
+        let mut removed = Vec::new();
+        for index in freed {
+            match added.next() {
+                Some(new_line) => self.lines[index] = format!("{}{new_line}", self.indent),
+                None => removed.push(index),
+            }
+        }
+        for index in removed.into_iter().rev() {
+            self.lines.remove(index);
+        }
+        self.lines
+            .extend(added.map(|new_line| format!("{}{new_line}", self.indent)));
+    }
+
+    #[must_use]
+    pub fn into_lines(self) -> Vec<String> {
+        self.lines
+    }
+}
+
+/// Option of a `Key Value` (or `Key=Value`) line, `None` for comments and blank lines.
+fn option_type(line: &str) -> Option<EntryType> {
+    let line = line.trim();
+    if line.starts_with('#') {
+        return None;
+    }
+    let key = line
+        .split(|c: char| c.is_whitespace() || c == '=')
+        .next()
+        .filter(|key| !key.is_empty())?;
+
+    Some(EntryType::from_str(key).unwrap_or_else(|_| EntryType::Unknown(key.to_string())))
+}
+
+/// Splits an option line into what comes before its value (indentation, key and separator)
+/// and the value.
+fn split_value(line: &str) -> (&str, &str) {
+    let key_end = line
+        .trim_start()
+        .find(|c: char| c.is_whitespace() || c == '=');
+    let key_end = key_end.map_or(line.len(), |end| line.len() - line.trim_start().len() + end);
+    let value_start = line[key_end..]
+        .find(|c: char| !c.is_whitespace() && c != '=')
+        .map_or(line.len(), |start| key_end + start);
+
+    line.split_at(value_start)
+}
+
+/// Splits the value of an option line from its trailing comment, which starts with a `#` after
+/// a space, outside of quotes. The comment keeps the spaces before it.
+fn split_comment(value: &str) -> (&str, &str) {
+    let mut quoted = false;
+    let mut previous = ' ';
+    for (index, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted && previous.is_whitespace() && index > 0 => {
+                let end = value[..index].trim_end().len();
+                return value.split_at(end);
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+
+    (value, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edits_keep_the_other_lines() {
+        let lines = [
+            "    HostName=old.example.com",
+            "    # jump through the bastion",
+            "    ProxyJump bastion",
+            "",
+            "    Port 22",
+            "    ForwardAgent yes",
+        ];
+        let mut block = BlockWriter::new(&lines);
+
+        block.set(&EntryType::Hostname, Some("new.example.com"));
+        block.set(&EntryType::Port, None);
+        block.set(&EntryType::User, Some("deploy"));
+        block.set(&EntryType::ProxyJump, Some("\"bastion\""));
+        assert_eq!(
+            block.clone().into_lines(),
+            [
+                "    HostName=new.example.com",
+                "    # jump through the bastion",
+                "    ProxyJump bastion",
+                "",
+                "    ForwardAgent yes",
+                "    User deploy",
+            ]
+        );
+
+        // The other lines: a changed one takes the place of the removed one
+        let is_other = |line: &str| {
+            !matches!(
+                option_type(line),
+                Some(EntryType::Hostname | EntryType::User)
+            )
+        };
+        block.replace_lines(
+            is_other,
+            &[
+                "# jump through the bastion",
+                "ProxyJump bastion2",
+                "ForwardAgent yes",
+                "Compression yes",
+            ],
+        );
+        assert_eq!(
+            block.into_lines(),
+            [
+                "    HostName=new.example.com",
+                "    # jump through the bastion",
+                "    ProxyJump bastion2",
+                "",
+                "    ForwardAgent yes",
+                "    User deploy",
+                "    Compression yes",
+            ]
+        );
+
+        let mut block = BlockWriter::new(&[]);
+        block.set(&EntryType::Hostname, Some("web.example.com"));
+        block.replace_lines(|line| option_type(line).is_none(), &["# sshs:tags=prod"]);
+        assert_eq!(
+            block.into_lines(),
+            ["  Hostname web.example.com", "  # sshs:tags=prod"]
+        );
+    }
+
+    #[test]
+    fn test_set_keeps_inline_comments() {
+        let lines = [
+            "  Hostname web.example.com # primary",
+            "  User \"deploy\"  # shared account",
+            "  IdentityFile \"~/.ssh/id #1\"",
+            "  Port 22\t# default",
+        ];
+        let mut block = BlockWriter::new(&lines);
+
+        // Unchanged values, quotes aside, are left alone
+        block.set(&EntryType::Hostname, Some("web.example.com"));
+        block.set(&EntryType::User, Some("deploy"));
+        block.set(&EntryType::IdentityFile, Some("~/.ssh/id #1"));
+        assert_eq!(block.clone().into_lines(), lines);
+
+        block.set(&EntryType::Hostname, Some("web2.example.com"));
+        block.set(&EntryType::User, Some("\"ops\""));
+        block.set(&EntryType::IdentityFile, Some("\"~/.ssh/id #2\""));
+        block.set(&EntryType::Port, Some("2222"));
+        assert_eq!(
+            block.into_lines(),
+            [
+                "  Hostname web2.example.com # primary",
+                "  User \"ops\"  # shared account",
+                "  IdentityFile \"~/.ssh/id #2\"",
+                "  Port 2222\t# default",
+            ]
+        );
+
+        assert_eq!(split_comment("a#b"), ("a#b", ""));
+        assert_eq!(split_comment("#b"), ("#b", ""));
+    }
+}